@@ -0,0 +1,47 @@
+//! Decode wM-Bus telegrams from an SDR recording.
+//!
+//! ```text
+//! rtl_sdr -f 868625000 -s 1600000 survey.cu8
+//! cargo run --example sdr_decode -- survey.cu8 1600000 868625000
+//! ```
+//!
+//! The sample format is taken from the file extension (`.cu8`, `.cs16`, `.cf32`).
+
+use mbus_rs::id_to_manufacturer;
+use mbus_rs::wmbus::sdr::{decode_iq_file, IqFormat, SdrConfig};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 4 {
+        eprintln!(
+            "usage: {} <file.cu8|cs16|cf32> <sample_rate> <center_hz>",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+    let path = &args[1];
+    let sample_rate: u32 = args[2].parse()?;
+    let center_hz: u32 = args[3].parse()?;
+
+    let config = SdrConfig::for_recording(sample_rate, IqFormat::from_path(path)?, center_hz);
+    if config.channels.is_empty() {
+        eprintln!("no wM-Bus channel fits in this recording's bandwidth");
+        std::process::exit(1);
+    }
+
+    for f in decode_iq_file(path, &config)? {
+        let link = &f.frame.link;
+        println!(
+            "{:10.4}s mode {:?} M={} A={:08} T={:02X} CI={:02X?} errors={} {}",
+            f.time_s,
+            f.frame.mode,
+            id_to_manufacturer(link.manufacturer_id),
+            link.device_address,
+            link.device_type,
+            link.ci(),
+            f.frame.symbol_errors,
+            hex::encode(&f.frame.raw),
+        );
+    }
+    Ok(())
+}
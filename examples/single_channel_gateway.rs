@@ -15,12 +15,14 @@
 //! ```
 
 use log::{info, warn};
+use std::time::SystemTime;
 
 // Import LoRa types
 use mbus_rs::wmbus::radio::lora::{
-    params::LoRaModParamsExt, CodingRate, LoRaBandwidth, LoRaCadParams, LoRaModParams,
-    SpreadingFactor,
+    gwmp::PushPayload, params::LoRaModParamsExt, CodingRate, GwmpPacket, LoRaBandwidth,
+    LoRaCadParams, LoRaModParams, RxPk, SingleChannelConfig, SpreadingFactor,
 };
+use mbus_rs::wmbus::radio::modulation::LoRaPacketStatus;
 
 /// Single-channel gateway configuration
 #[allow(dead_code)]
@@ -162,7 +164,7 @@ fn main() {
     info!("");
     info!("For actual deployment:");
     info!("1. Implement HAL for your hardware platform");
-    info!("2. Add packet forwarding to network server");
+    info!("2. Forward packets with gwmp::PacketForwarder (Semtech UDP, port 1700)");
     info!("3. Configure end-devices with matching parameters");
     info!("4. Disable ADR on LoRaWAN network server");
 
@@ -203,6 +205,39 @@ fn main() {
         cad_sf12_125.duration_ms(SpreadingFactor::SF12, LoRaBandwidth::BW125)
     );
 
+    // Show what the packet forwarder sends to ChirpStack/TTN for one uplink
+    info!("");
+    info!("Semtech UDP PUSH_DATA for a received uplink:");
+
+    let channel = SingleChannelConfig::eu868_channel_1();
+    let status = LoRaPacketStatus {
+        rssi_pkt_dbm: -97,
+        snr_pkt_db: 6.5,
+        signal_rssi_pkt_dbm: -98,
+    };
+    let uplink = [
+        0x40, 0xF1, 0x7D, 0xBE, 0x49, 0x00, 0x02, 0x00, 0x01, 0x95, 0x43, 0x78, 0x76, 0x2B, 0x11,
+        0xFF, 0x0D,
+    ];
+    let packet = GwmpPacket::PushData {
+        token: 1,
+        gateway_eui: 0xB827_EBFF_FE00_0001,
+        payload: PushPayload {
+            rxpk: vec![RxPk::from_single_channel(
+                &channel,
+                &uplink,
+                &status,
+                0,
+                SystemTime::now(),
+            )],
+            stat: None,
+        },
+    };
+    match packet.encode() {
+        Ok(datagram) => info!("{}", String::from_utf8_lossy(&datagram[12..])),
+        Err(e) => warn!("Failed to encode PUSH_DATA: {}", e),
+    }
+
     info!("");
     info!("This example demonstrates configuration only.");
     info!("For a working gateway, integrate with your hardware HAL.");
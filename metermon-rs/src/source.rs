@@ -52,12 +52,12 @@ impl FrameSource for FileReplaySource {
 
 /// Live RFM69 radio source (Raspberry Pi only).
 ///
-/// Reaches the radio through the `RadioDriver` trait rather than `WMBusHandle`,
-/// because the handle is hardcoded to the SX126x driver (mbus-rs Phase 2.2 gap).
-/// This is the one place the client depends on that unfinished wiring.
+/// Reaches the radio through the `RadioDriver` trait directly rather than a
+/// `WMBusHandle<Rfm69Driver>`: the gateway needs the raw frame bytes, the AFC
+/// offset and RegOpMode for its health watchdog, none of which the handle exposes.
 #[cfg(feature = "radio")]
 pub struct Rfm69Source {
-    driver: mbus_rs::wmbus::radio::rfm69::Rfm69Driver,
+    driver: mbus_rs::wmbus::radio::rfm69::Rfm69Driver<mbus_rs::wmbus::radio::hal::Rfm69PiHal>,
 }
 
 #[cfg(feature = "radio")]
@@ -2,9 +2,9 @@
 //! S modes, run periodically by the monitor to discover what's on the air.
 //!
 //! Each sweep does two sync-disabled raw captures ([`capture_raw`]) and analyses them:
-//!   - **868.95 MHz / 100 kbps** → C-mode frames (recovered + CRC-checked via the crate's
-//!     [`decode_mode_c`](mbus_rs::wmbus::mode_c::decode_mode_c)) and T-mode presence
-//!     (3-of-6 line-code detection).
+//!   - **868.95 MHz / 100 kbps** → C- and T-mode frames (recovered + CRC-checked via the
+//!     crate's [`BitstreamScanner`](mbus_rs::wmbus::bitstream::BitstreamScanner)) and
+//!     T-mode presence (3-of-6 line-code detection).
 //!   - **868.30 MHz / 32.768 kcps** → S-mode presence (Manchester detection).
 //!
 //! The analysis functions are pure (no radio) and unit-tested; [`capture_raw`] is the
@@ -16,67 +16,48 @@ const THREE_OF_SIX: [u8; 16] = [
     0x16, 0x0D, 0x0E, 0x0B, 0x1C, 0x19, 0x1A, 0x13, 0x2C, 0x25, 0x26, 0x23, 0x34, 0x31, 0x32, 0x29,
 ];
 
-/// Normalized C-mode sync word `54 3D 54` as a 24-bit value (searched bit-by-bit).
-const C_SYNC24: u32 = 0x54_3D_54;
-
 #[inline]
 fn bit(d: &[u8], i: usize) -> u32 {
     ((d[i >> 3] >> (7 - (i & 7))) & 1) as u32
 }
 
-/// Read 8 bits starting at an arbitrary bit offset into a byte (MSB-first).
-fn byte_at(d: &[u8], bitoff: usize) -> u8 {
-    let mut b = 0u8;
-    for k in 0..8 {
-        b = (b << 1) | bit(d, bitoff + k) as u8;
-    }
-    b
-}
-
 /// Result of analysing the 868.95 MHz capture.
 pub struct CtResult {
     /// Recovered, CRC-valid C-mode frames (normalized, type-byte-first).
     pub c_frames: Vec<Vec<u8>>,
+    /// Recovered, CRC-valid T-mode frames (line-decoded, normalized, type-byte-first).
+    pub t_frames: Vec<Vec<u8>>,
     /// Number of post-preamble bursts consistent with 3-of-6 (mode T) encoding.
     pub t_bursts: usize,
 }
 
-/// Recover C-mode frames and detect T-mode traffic in a raw 868.95 MHz stream.
+/// Recover C- and T-mode frames and detect T-mode traffic in a raw 868.95 MHz stream.
+///
+/// Frame recovery is the crate's [`BitstreamScanner`](mbus_rs::wmbus::bitstream) (any bit
+/// offset, either polarity, block CRCs validated); `t_bursts` still counts every 3-of-6
+/// burst, CRC-valid or not, so weak T traffic is noticed even when no frame decodes.
 pub fn analyze_ct(raw: &[u8]) -> CtResult {
-    let nbits = raw.len() * 8;
-    let mut c_frames: Vec<Vec<u8>> = Vec::new();
-    let mut seen: std::collections::HashSet<Vec<u8>> = std::collections::HashSet::new();
+    use mbus_rs::wmbus::bitstream::{BitstreamScanner, LinkMode, ScanConfig};
 
-    // C recovery: rolling 24-bit search for the 54 3D 54 sync, then CRC-validate.
-    let mut acc: u32 = 0;
-    for i in 0..nbits {
-        acc = ((acc << 1) | bit(raw, i)) & 0x00FF_FFFF;
-        if i >= 23 && acc == C_SYNC24 {
-            let start = i + 1; // frame (type byte) begins just after the sync
-            let avail = (nbits - start) / 8;
-            if avail < 13 {
-                continue;
-            }
-            let n = avail.min(64);
-            let frame: Vec<u8> = (0..n).map(|j| byte_at(raw, start + j * 8)).collect();
-            if let Ok(lf) = mbus_rs::wmbus::mode_c::decode_mode_c(&frame) {
-                if lf.crc_ok {
-                    let plen = mbus_rs::wmbus::radio::rfm69_packet::packet_size(&frame);
-                    let f = if plen > 0 && (plen as usize) <= frame.len() {
-                        frame[..plen as usize].to_vec()
-                    } else {
-                        frame
-                    };
-                    if seen.insert(f.clone()) {
-                        c_frames.push(f);
-                    }
-                }
-            }
+    let scanner = BitstreamScanner::new(ScanConfig {
+        modes: vec![LinkMode::C, LinkMode::T],
+        ..ScanConfig::default()
+    });
+    let mut seen: std::collections::HashSet<Vec<u8>> = std::collections::HashSet::new();
+    let (mut c_frames, mut t_frames) = (Vec::new(), Vec::new());
+    for f in scanner.scan(raw) {
+        if !seen.insert(f.raw.clone()) {
+            continue;
+        }
+        match f.mode {
+            LinkMode::C => c_frames.push(f.raw),
+            _ => t_frames.push(f.raw),
         }
     }
 
     CtResult {
         c_frames,
+        t_frames,
         t_bursts: count_mode_bursts(raw, is_three_of_six),
     }
 }
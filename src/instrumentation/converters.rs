@@ -31,6 +31,11 @@ impl Default for MBusDataRecordHeader {
 use crate::vendors::{manufacturer_id_to_string, VendorDeviceInfo};
 use crate::wmbus::frame::WMBusFrame;
 use crate::wmbus::radio::lora::decoder::MeteringData;
+#[cfg(feature = "crypto")]
+use crate::wmbus::radio::lora::lorawan::Uplink;
+use crate::wmbus::radio::lora::mac::DevStatus;
+#[cfg(feature = "crypto")]
+use crate::wmbus::radio::lora::mac::{MacCommand, MacError};
 use std::time::SystemTime;
 
 /// Convert M-Bus frame and records to unified instrumentation
@@ -351,6 +356,38 @@ pub fn from_lora_instrumentation(
     from_lora_metering_data_with_split(data, rssi, snr, true, true)
 }
 
+/// Apply a LoRaWAN DevStatusAns (battery level and link margin)
+/// Battery voltage from the application payload is kept; the margin goes to vendor metrics
+pub fn apply_lora_dev_status(inst: &mut UnifiedInstrumentation, status: &DevStatus) {
+    if let Some(percentage) = status.battery_percentage() {
+        let voltage = inst.battery_status.as_ref().and_then(|b| b.voltage);
+        inst.set_battery(voltage, Some(percentage));
+    }
+    if status.is_external_power() {
+        inst.vendor_metrics
+            .insert("external_power".to_string(), 1.0);
+    }
+    inst.vendor_metrics
+        .insert("lora_margin_db".to_string(), status.margin as f64);
+}
+
+/// Apply every DevStatusAns a verified uplink carries, in FOpts or on FPort 0, with
+/// [`apply_lora_dev_status`]. Returns how many were applied.
+#[cfg(feature = "crypto")]
+pub fn apply_lora_uplink(
+    inst: &mut UnifiedInstrumentation,
+    uplink: &Uplink,
+) -> Result<usize, MacError> {
+    let mut applied = 0;
+    for command in uplink.mac_commands()? {
+        if let MacCommand::DevStatusAns(status) = command {
+            apply_lora_dev_status(inst, &status);
+            applied += 1;
+        }
+    }
+    Ok(applied)
+}
+
 /// Convert vendor device info to unified instrumentation
 pub fn from_vendor_device_info(
     info: &VendorDeviceInfo,
@@ -519,4 +556,61 @@ mod tests {
             Some(vec![0x01, 0x67, 0x00, 0xEB, 0x02, 0x68, 0x82])
         );
     }
+
+    #[test]
+    fn test_lora_dev_status() {
+        let mut inst = UnifiedInstrumentation::new(
+            "lora_device".to_string(),
+            "Unknown".to_string(),
+            ProtocolType::LoRa,
+        );
+        inst.set_battery(Some(3.1), None);
+
+        apply_lora_dev_status(
+            &mut inst,
+            &DevStatus {
+                battery: 26,
+                margin: -3,
+            },
+        );
+        let battery = inst.battery_status.as_ref().unwrap();
+        assert_eq!(battery.voltage, Some(3.1));
+        assert_eq!(battery.percentage, Some(9));
+        assert!(battery.low_battery);
+        assert_eq!(inst.vendor_metrics.get("lora_margin_db"), Some(&-3.0));
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_lora_uplink_dev_status() {
+        use crate::wmbus::radio::lora::lorawan::DataFrame;
+
+        let mut inst = UnifiedInstrumentation::new(
+            "lora_device".to_string(),
+            "Unknown".to_string(),
+            ProtocolType::LoRa,
+        );
+        // Unconfirmed up, FOpts: LinkCheckReq, DevStatusAns (battery 128, margin -3).
+        let phy = [
+            0x40, 0x04, 0x03, 0x02, 0x01, 0x04, 0x02, 0x00, 0x02, 0x06, 0x80, 0x3D, 0xAA, 0xBB,
+            0xCC, 0xDD,
+        ];
+        let uplink = Uplink {
+            frame: DataFrame::parse(&phy).unwrap(),
+            fcnt: 2,
+            payload: Vec::new(),
+        };
+        assert_eq!(apply_lora_uplink(&mut inst, &uplink), Ok(1));
+        assert_eq!(inst.battery_status.as_ref().unwrap().percentage, Some(50));
+        assert_eq!(inst.vendor_metrics.get("lora_margin_db"), Some(&-3.0));
+
+        // On FPort 0 the commands come from the decrypted payload.
+        let mut on_port_zero = uplink.clone();
+        on_port_zero.frame.fopts.clear();
+        on_port_zero.frame.fport = Some(0);
+        on_port_zero.payload = vec![0x06, 0x00, 0x05];
+        assert_eq!(apply_lora_uplink(&mut inst, &on_port_zero), Ok(1));
+        assert_eq!(inst.vendor_metrics.get("external_power"), Some(&1.0));
+        assert_eq!(inst.vendor_metrics.get("lora_margin_db"), Some(&5.0));
+    }
 }
@@ -58,11 +58,6 @@ fn detect_raspberry_pi_model() {
     }
 }
 
-#[cfg(not(target_arch = "aarch64"))]
-fn detect_raspberry_pi_model() {
-    // No-op on non-ARM platforms
-}
-
 /// Check if NEON is available
 #[inline]
 pub fn has_neon() -> bool {
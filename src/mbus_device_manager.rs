@@ -11,7 +11,8 @@ use crate::mbus::secondary_addressing::{
     build_secondary_selection_frame, SecondaryAddress, WildcardResult, WildcardSearchManager,
 };
 use crate::mbus::serial::{MBusDeviceHandle, SerialConfig};
-use crate::wmbus::handle::{WMBusHandleFactory, WMBusHandleWrapper};
+use crate::wmbus::handle::{WMBusConfig, WMBusHandleFactory, WMBusHandleWrapper};
+use crate::wmbus::radio::radio_driver::RadioDriver;
 use std::collections::HashMap;
 use std::time::Duration;
 
@@ -65,6 +66,34 @@ impl MBusDeviceManager {
         Ok(())
     }
 
+    /// Adds a new wireless wM-Bus handle driven by any radio driver.
+    pub async fn add_wmbus_handle_with_driver<R: RadioDriver + 'static>(
+        &mut self,
+        device_id: &str,
+        driver: R,
+        config: Option<WMBusConfig>,
+    ) -> Result<(), MBusError> {
+        let handle = WMBusHandleFactory::create_with_driver(driver, config)
+            .await
+            .map_err(MBusError::from)?;
+        self.wmbus_handles.insert(device_id.to_string(), handle);
+        Ok(())
+    }
+
+    /// Adds a new wireless wM-Bus handle for an RFM69HCW on Raspberry Pi.
+    #[cfg(feature = "rfm69")]
+    pub async fn add_wmbus_handle_rfm69(
+        &mut self,
+        device_id: &str,
+        rfm69_config: crate::wmbus::radio::rfm69::Rfm69Config,
+    ) -> Result<(), MBusError> {
+        let handle = WMBusHandleFactory::create_rfm69(rfm69_config)
+            .await
+            .map_err(MBusError::from)?;
+        self.wmbus_handles.insert(device_id.to_string(), handle);
+        Ok(())
+    }
+
     /// Adds a new wireless wM-Bus handle for Raspberry Pi with default configuration.
     #[cfg(feature = "raspberry-pi")]
     pub async fn add_wmbus_handle_raspberry_pi(
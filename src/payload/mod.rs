@@ -4,6 +4,7 @@
 pub mod data;
 pub mod data_encoding;
 pub mod record;
+pub mod record_encoder;
 pub mod vif;
 pub mod vif_maps;
 
@@ -0,0 +1,385 @@
+//! Variable data record encoder (EN 13757-3).
+//!
+//! The inverse of [`parse_variable_record`](crate::payload::record::parse_variable_record):
+//! a [`DataRecord`] is turned into its DIF, DIFE chain, VIF chain and data field. Storage
+//! number, tariff and subunit are spread over the DIF/DIFE chain as the standard lays them
+//! out, and the DIF data field code is derived from the [`RecordValue`].
+//!
+//! ```rust
+//! use mbus_rs::payload::record_encoder::{encode_records, DataRecord, RecordValue};
+//!
+//! // 12345 l volume (VIF 0x13) and the same value at storage number 1.
+//! let records = encode_records(&[
+//!     DataRecord::new(&[0x13], RecordValue::Integer { value: 12345, len: 4 }),
+//!     DataRecord::new(&[0x13], RecordValue::Integer { value: 12000, len: 4 }).storage(1),
+//! ])
+//! .unwrap();
+//! assert_eq!(&records[..6], &[0x04, 0x13, 0x39, 0x30, 0x00, 0x00]);
+//! assert_eq!(records[6], 0x44);
+//! ```
+
+use crate::constants::{
+    MBUS_DATA_RECORD_DIF_MASK_STORAGE_NO, MBUS_DIB_DIF_EXTENSION_BIT, MBUS_DIB_VIF_EXTENSION_BIT,
+};
+use thiserror::Error;
+
+/// Most DIFEs a record may carry.
+const MAX_DIFE: usize = 10;
+/// Longest LVAR-coded variable-length data field (`0x00..=0xBF`).
+const MAX_VARIABLE_LEN: usize = 0xBF;
+
+/// Errors raised while encoding a data record.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RecordEncodeError {
+    #[error("Value {value} does not fit {len} bytes")]
+    ValueOutOfRange { value: i128, len: usize },
+
+    #[error("Unsupported data field length: {len}")]
+    UnsupportedLength { len: usize },
+
+    #[error("Storage number, tariff and subunit need more than {MAX_DIFE} DIFEs")]
+    TooManyDifes,
+
+    #[error("Variable-length data too long: {len} bytes")]
+    VariableTooLong { len: usize },
+
+    #[error("Invalid VIF chain: {reason}")]
+    InvalidVib { reason: String },
+}
+
+/// The function field (DIF bits 5..4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordFunction {
+    #[default]
+    Instantaneous = 0,
+    Maximum = 1,
+    Minimum = 2,
+    ValueDuringError = 3,
+}
+
+/// The value of a data record and how it is coded on the wire.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordValue {
+    /// Binary integer of 1, 2, 3, 4, 6 or 8 bytes, least significant byte first.
+    /// Negative values are sent in two's complement.
+    Integer { value: i64, len: usize },
+    /// BCD of 2, 4, 6, 8 or 12 digits, least significant byte first.
+    Bcd { value: u64, digits: usize },
+    /// 32-bit IEEE 754 real.
+    Real(f32),
+    /// LVAR-prefixed variable-length data, sent as given (M-Bus text is sent
+    /// last character first).
+    Variable(Vec<u8>),
+    /// No data (DIF data field 0).
+    NoData,
+}
+
+impl RecordValue {
+    /// The DIF data field code (bits 3..0) for this value.
+    fn data_field(&self) -> Result<u8, RecordEncodeError> {
+        Ok(match self {
+            Self::NoData => 0x0,
+            Self::Integer { len, .. } => match len {
+                1 => 0x1,
+                2 => 0x2,
+                3 => 0x3,
+                4 => 0x4,
+                6 => 0x6,
+                8 => 0x7,
+                &len => return Err(RecordEncodeError::UnsupportedLength { len }),
+            },
+            Self::Real(_) => 0x5,
+            Self::Bcd { digits, .. } => match digits {
+                2 => 0x9,
+                4 => 0xA,
+                6 => 0xB,
+                8 => 0xC,
+                12 => 0xE,
+                &len => return Err(RecordEncodeError::UnsupportedLength { len }),
+            },
+            Self::Variable(_) => 0xD,
+        })
+    }
+
+    /// The data field bytes, including the LVAR byte for variable-length data.
+    fn data(&self) -> Result<Vec<u8>, RecordEncodeError> {
+        match self {
+            Self::NoData => Ok(Vec::new()),
+            &Self::Integer { value, len } => {
+                let bits = 8 * len as u32;
+                let fits = len == 8
+                    || (i128::from(value) >= -(1i128 << (bits - 1))
+                        && i128::from(value) < (1i128 << bits));
+                if !fits {
+                    return Err(RecordEncodeError::ValueOutOfRange {
+                        value: value.into(),
+                        len,
+                    });
+                }
+                Ok(value.to_le_bytes()[..len].to_vec())
+            }
+            Self::Real(value) => Ok(value.to_le_bytes().to_vec()),
+            &Self::Bcd { value, digits } => {
+                if u128::from(value) >= 10u128.pow(digits as u32) {
+                    return Err(RecordEncodeError::ValueOutOfRange {
+                        value: value.into(),
+                        len: digits / 2,
+                    });
+                }
+                let mut rest = value;
+                Ok((0..digits / 2)
+                    .map(|_| {
+                        let byte = (((rest / 10 % 10) << 4) | (rest % 10)) as u8;
+                        rest /= 100;
+                        byte
+                    })
+                    .collect())
+            }
+            Self::Variable(data) => {
+                if data.len() > MAX_VARIABLE_LEN {
+                    return Err(RecordEncodeError::VariableTooLong { len: data.len() });
+                }
+                let mut out = Vec::with_capacity(data.len() + 1);
+                out.push(data.len() as u8);
+                out.extend_from_slice(data);
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// One variable data record: where it sits (storage, tariff, subunit, function), what it
+/// measures (the VIF chain) and its value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataRecord {
+    pub function: RecordFunction,
+    pub storage_number: u64,
+    pub tariff: u32,
+    pub subunit: u32,
+    /// VIF followed by any VIFEs, extension bits set as on the wire.
+    pub vib: Vec<u8>,
+    pub value: RecordValue,
+}
+
+impl DataRecord {
+    /// An instantaneous value at storage 0, tariff 0, subunit 0.
+    pub fn new(vib: &[u8], value: RecordValue) -> Self {
+        Self {
+            function: RecordFunction::Instantaneous,
+            storage_number: 0,
+            tariff: 0,
+            subunit: 0,
+            vib: vib.to_vec(),
+            value,
+        }
+    }
+
+    /// Set the storage number.
+    pub fn storage(mut self, storage_number: u64) -> Self {
+        self.storage_number = storage_number;
+        self
+    }
+
+    /// Set the tariff.
+    pub fn tariff(mut self, tariff: u32) -> Self {
+        self.tariff = tariff;
+        self
+    }
+
+    /// Set the subunit (device).
+    pub fn subunit(mut self, subunit: u32) -> Self {
+        self.subunit = subunit;
+        self
+    }
+
+    /// Set the function field.
+    pub fn function(mut self, function: RecordFunction) -> Self {
+        self.function = function;
+        self
+    }
+
+    /// Encode the record: DIF, DIFEs, VIF chain and data.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the value does not fit its data field, the storage number, tariff and
+    /// subunit need more than ten DIFEs, or the VIF chain's extension bits do not match
+    /// its length.
+    pub fn encode(&self) -> Result<Vec<u8>, RecordEncodeError> {
+        self.check_vib()?;
+
+        // Storage bit 0 lives in the DIF; each DIFE adds 4 storage, 2 tariff and 1 subunit bits.
+        let mut dif = self.value.data_field()?
+            | ((self.function as u8) << 4)
+            | (((self.storage_number & 1) as u8) * MBUS_DATA_RECORD_DIF_MASK_STORAGE_NO);
+        let mut difes = Vec::new();
+        let (mut storage, mut tariff, mut subunit) =
+            (self.storage_number >> 1, self.tariff, self.subunit);
+        while storage != 0 || tariff != 0 || subunit != 0 {
+            if difes.len() == MAX_DIFE {
+                return Err(RecordEncodeError::TooManyDifes);
+            }
+            difes.push(
+                (storage & 0x0F) as u8
+                    | (((tariff & 0x03) as u8) << 4)
+                    | (((subunit & 1) as u8) << 6),
+            );
+            storage >>= 4;
+            tariff >>= 2;
+            subunit >>= 1;
+        }
+        if !difes.is_empty() {
+            dif |= MBUS_DIB_DIF_EXTENSION_BIT;
+        }
+        let last = difes.len().saturating_sub(1);
+        for dife in &mut difes[..last] {
+            *dife |= MBUS_DIB_DIF_EXTENSION_BIT;
+        }
+
+        let mut out = vec![dif];
+        out.extend(difes);
+        out.extend_from_slice(&self.vib);
+        out.extend(self.value.data()?);
+        Ok(out)
+    }
+
+    /// Every VIF/VIFE but the last must carry the extension bit.
+    fn check_vib(&self) -> Result<(), RecordEncodeError> {
+        let Some((last, chain)) = self.vib.split_last() else {
+            return Err(RecordEncodeError::InvalidVib {
+                reason: "empty".to_string(),
+            });
+        };
+        if chain.iter().any(|b| b & MBUS_DIB_VIF_EXTENSION_BIT == 0)
+            || last & MBUS_DIB_VIF_EXTENSION_BIT != 0
+        {
+            return Err(RecordEncodeError::InvalidVib {
+                reason: format!("extension bits do not match {} bytes", self.vib.len()),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Encode `records` back to back into a variable data block.
+///
+/// # Errors
+///
+/// Returns the first record's [`DataRecord::encode`] error.
+pub fn encode_records(records: &[DataRecord]) -> Result<Vec<u8>, RecordEncodeError> {
+    let mut out = Vec::new();
+    for record in records {
+        out.extend(record.encode()?);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::data::{parse_enhanced_variable_data_record, MBusRecordValue};
+    use crate::payload::record::parse_variable_record_consumed;
+
+    #[test]
+    fn records_round_trip_through_the_parsers() {
+        let records = [
+            DataRecord::new(
+                &[0x13],
+                RecordValue::Integer {
+                    value: 12345,
+                    len: 4,
+                },
+            ),
+            DataRecord::new(&[0x13], RecordValue::Integer { value: 700, len: 2 })
+                .storage(5)
+                .tariff(2)
+                .subunit(1),
+            DataRecord::new(&[0x13], RecordValue::Integer { value: 7, len: 1 })
+                .function(RecordFunction::Maximum),
+            DataRecord::new(&[0x13], RecordValue::Real(1.5)),
+            DataRecord::new(
+                &[0x78],
+                RecordValue::Bcd {
+                    value: 12345678,
+                    digits: 8,
+                },
+            ),
+            DataRecord::new(&[0x13], RecordValue::Variable(b"cba".to_vec())),
+        ];
+        let encoded = encode_records(&records).unwrap();
+
+        // The enhanced parser walks the binary records; BCD and LVAR are checked bytewise
+        // below.
+        let fixed_len: usize = records[..4].iter().map(|r| r.encode().unwrap().len()).sum();
+        let mut rest = &encoded[..fixed_len];
+        let mut parsed = Vec::new();
+        while !rest.is_empty() {
+            let (remaining, record) = parse_enhanced_variable_data_record(rest).unwrap();
+            parsed.push(record);
+            rest = remaining;
+        }
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[0].value, MBusRecordValue::Numeric(12345.0));
+        assert_eq!(parsed[3].value, MBusRecordValue::Numeric(1.5));
+        assert_eq!(parsed[2].dif_chain, [0x11]);
+
+        // Storage 5 = DIF bit 1 + DIFE 0b10; tariff 2 and subunit 1 in the same DIFE.
+        let second = &encoded[records[0].encode().unwrap().len()..];
+        let (record, consumed) = parse_variable_record_consumed(second).unwrap();
+        assert_eq!(consumed, records[1].encode().unwrap().len());
+        let dib = &record.drh.dib;
+        assert_eq!(dib.dif, 0x80 | 0x40 | 0x02);
+        assert_eq!(&dib.dife[..dib.ndife], &[0x40 | 0x20 | 0x02]);
+
+        let error_flags = DataRecord::new(&[0xFD, 0x17], RecordValue::Integer { value: 0, len: 1 });
+        let (record, _) = parse_variable_record_consumed(&error_flags.encode().unwrap()).unwrap();
+        assert_eq!(record.drh.vib.vif, 0xFD);
+        assert_eq!(&record.drh.vib.vife[..record.drh.vib.nvife], &[0x17]);
+
+        // BCD is least significant byte first; text is LVAR-prefixed.
+        assert_eq!(
+            records[4].encode().unwrap(),
+            [0x0C, 0x78, 0x78, 0x56, 0x34, 0x12]
+        );
+        assert_eq!(
+            records[5].encode().unwrap(),
+            [0x0D, 0x13, 3, b'c', b'b', b'a']
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let encode = |value| DataRecord::new(&[0x13], value).encode();
+        assert!(encode(RecordValue::Integer { value: 255, len: 1 }).is_ok());
+        assert!(encode(RecordValue::Integer {
+            value: -128,
+            len: 1
+        })
+        .is_ok());
+        assert!(matches!(
+            encode(RecordValue::Integer { value: 256, len: 1 }),
+            Err(RecordEncodeError::ValueOutOfRange { .. })
+        ));
+        assert!(matches!(
+            encode(RecordValue::Integer { value: 1, len: 5 }),
+            Err(RecordEncodeError::UnsupportedLength { len: 5 })
+        ));
+        assert!(matches!(
+            encode(RecordValue::Bcd {
+                value: 100,
+                digits: 2
+            }),
+            Err(RecordEncodeError::ValueOutOfRange { .. })
+        ));
+        assert!(matches!(
+            DataRecord::new(&[0x93], RecordValue::NoData).encode(),
+            Err(RecordEncodeError::InvalidVib { .. })
+        ));
+        assert_eq!(
+            DataRecord::new(&[0x13], RecordValue::NoData)
+                .storage(u64::MAX)
+                .encode(),
+            Err(RecordEncodeError::TooManyDifes)
+        );
+    }
+}
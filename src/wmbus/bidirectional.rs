@@ -0,0 +1,1106 @@
+//! # Bidirectional wM-Bus (Primary Station Side)
+//!
+//! In the frequent-transmit bidirectional modes (T2, C2, S2) a meter listens for a short
+//! time after each of its transmissions. This module implements the gateway ("other
+//! device") side of that exchange per EN 13757-4:
+//!
+//! - [`Accessibility`] decodes the B/A bits of the configuration word, which tell whether
+//!   the frame just received opens a reception window.
+//! - [`PrimaryStation`] keeps a per-meter command queue (ACK, SND_NKE, SND_UD, REQ_UD2),
+//!   the per-meter frame count bit, and the exchanges awaiting a meter response. It is a
+//!   pure state machine: time is passed in, so it runs the same under a paused clock.
+//! - [`DownlinkPhy`] and [`ResponseTiming`] give each mode's other → meter channel, chip
+//!   rate and line code, and the response delay tRO the meter listens after.
+//! - [`transmit_in_window`] times a response into the meter's window through the radio's
+//!   LBT transmit path; [`transmit_response`] retunes the radio to the downlink PHY for it
+//!   and back to the receive configuration afterwards.
+//!
+//! [`WMBusHandle::enable_bidirectional`](crate::wmbus::handle::WMBusHandle::enable_bidirectional)
+//! wires the station into the background receiver.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use mbus_rs::wmbus::bidirectional::{GatewayCommand, PrimaryStation, PrimaryStationConfig};
+//! use mbus_rs::wmbus::frame::{parse_wmbus_frame, WMBusFrame};
+//! use tokio::time::Instant;
+//!
+//! let mut station = PrimaryStation::new(PrimaryStationConfig::default());
+//! // Change the reporting interval of meter 12345678 at its next access window.
+//! station.queue(0x2C2D, 0x12345678, GatewayCommand::SndUd { data: vec![0x04, 0x6D] });
+//!
+//! // SND_NR with CW bit 15 (bidirectional): a limited-access window follows.
+//! let raw = WMBusFrame::build(0x44, 0x2C2D, 0x12345678, 0x1B, 0x16, 0x7A, &[0x2A, 0, 0x00, 0x80]);
+//! let reaction = station.on_frame(&parse_wmbus_frame(&raw).unwrap(), Instant::now());
+//! let plan = reaction.respond.unwrap();
+//! // Mode C2: 0x54, type byte 0xCD, L-field, then SND_UD with FCB set.
+//! assert_eq!(plan.frame[..2], [0x54, 0xCD]);
+//! assert_eq!(plan.frame[3], 0x73);
+//! ```
+
+use crate::wmbus::bitstream::LinkMode;
+use crate::wmbus::frame::WMBusFrame;
+use crate::wmbus::frame_decode::FrameType;
+use crate::wmbus::line_code::{chips_to_bytes, encode_manchester};
+use crate::wmbus::mode_c::encode_mode_c;
+use crate::wmbus::mode_switching::WMBusMode;
+use crate::wmbus::radio::driver::LbtConfig;
+use crate::wmbus::radio::radio_driver::{RadioDriver, RadioDriverError, WMBusConfig};
+use std::collections::{HashMap, VecDeque};
+use thiserror::Error;
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// C-field: ACK (link layer acknowledgement)
+pub const C_ACK: u8 = 0x00;
+/// C-field: SND_NKE (link reset)
+pub const C_SND_NKE: u8 = 0x40;
+/// C-field: SND_UD (send user data), FCB clear
+pub const C_SND_UD: u8 = 0x53;
+/// C-field: REQ_UD2 (request class 2 data), FCB clear
+pub const C_REQ_UD2: u8 = 0x5B;
+/// C-field: RSP_UD (meter response with user data)
+pub const C_RSP_UD: u8 = 0x08;
+/// C-field: CNF_IR (confirmation of an installation request)
+pub const C_CNF_IR: u8 = 0x06;
+/// C-field: SND_NR (meter spontaneous data, no reply expected)
+pub const C_SND_NR: u8 = 0x44;
+/// C-field: SND_IR (meter installation request)
+pub const C_SND_IR: u8 = 0x46;
+/// C-field: ACC_NR (meter keep-alive without data)
+pub const C_ACC_NR: u8 = 0x47;
+/// C-field: ACC_DMD (meter access demand)
+pub const C_ACC_DMD: u8 = 0x48;
+/// Frame count bit in the C-field of SND_UD/REQ_UD2
+const FCB: u8 = 0x20;
+/// ACD and DFC bits a meter may set in ACK/RSP_UD
+const ACD_DFC: u8 = 0x30;
+/// CI field for commands to the meter (short transport layer header)
+const CI_TO_METER_SHORT: u8 = 0x5A;
+/// Second byte of the mode-C sync, sent after the radio's `0x543D` sync word
+const C_SYNC_TAIL: u8 = 0x54;
+
+/// Bidirectional exchange errors
+#[derive(Error, Debug)]
+pub enum BidirectionalError {
+    /// The response could not be placed inside the meter's reception window
+    #[error("Response window missed by {late_by:?}")]
+    WindowMissed { late_by: Duration },
+    /// Radio error while transmitting (including a busy channel)
+    #[error("Radio driver error: {0}")]
+    Radio(#[from] RadioDriverError),
+    /// The command does not fit one link layer frame
+    #[error("Command of {len} bytes does not fit a link layer frame")]
+    FrameTooLong { len: usize },
+}
+
+/// Meter accessibility from the B and A bits (15 and 14) of the configuration word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accessibility {
+    /// Unidirectional meter: never listens
+    NoAccess,
+    /// Bidirectional meter, but no window follows this frame
+    TemporaryNoAccess,
+    /// A short window follows this frame only (battery meters)
+    LimitedAccess,
+    /// The meter listens at least until its next transmission (mains powered)
+    UnlimitedAccess,
+}
+
+impl Accessibility {
+    /// Decode the B/A bits of a configuration word
+    pub fn from_config_word(cw: u16) -> Self {
+        match (cw & 0x8000 != 0, cw & 0x4000 != 0) {
+            (false, false) => Accessibility::NoAccess,
+            (false, true) => Accessibility::TemporaryNoAccess,
+            (true, false) => Accessibility::LimitedAccess,
+            (true, true) => Accessibility::UnlimitedAccess,
+        }
+    }
+
+    /// Accessibility announced by `frame`; frames without a transport layer header
+    /// announce nothing and count as [`Accessibility::NoAccess`]
+    pub fn of(frame: &WMBusFrame) -> Self {
+        frame
+            .config_word()
+            .map_or(Accessibility::NoAccess, Self::from_config_word)
+    }
+
+    /// Whether the meter listens after this frame
+    pub fn opens_window(self) -> bool {
+        matches!(
+            self,
+            Accessibility::LimitedAccess | Accessibility::UnlimitedAccess
+        )
+    }
+}
+
+/// Line code of a gateway-to-meter transmission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownlinkLineCode {
+    /// Bytes sent as they are
+    Nrz,
+    /// Manchester chips, packed MSB-first
+    Manchester,
+}
+
+/// Physical layer a meter listens on for the gateway's response (other → meter)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownlinkPhy {
+    /// Carrier in Hz
+    pub frequency_hz: u32,
+    /// Chip rate in chips/s, the radio's on-air bitrate
+    pub chip_rate: u32,
+    /// Line code of the frame after the sync word
+    pub line_code: DownlinkLineCode,
+}
+
+impl DownlinkPhy {
+    /// Downlink PHY of a bidirectional mode per EN 13757-4
+    ///
+    /// T2 meters do not listen on their 868.95 MHz uplink: they are answered like S2
+    /// meters, Manchester coded at 32.768 kcps on 868.3 MHz. C2 meters are answered at
+    /// 50 kcps NRZ on 869.525 MHz. `None` for the unidirectional modes and for N2/F2,
+    /// which are not answered here.
+    pub fn for_mode(mode: WMBusMode) -> Option<Self> {
+        match mode {
+            WMBusMode::T2 | WMBusMode::S2 => Some(Self {
+                frequency_hz: 868_300_000,
+                chip_rate: 32_768,
+                line_code: DownlinkLineCode::Manchester,
+            }),
+            WMBusMode::C2 => Some(Self {
+                frequency_hz: 869_525_000,
+                chip_rate: 50_000,
+                line_code: DownlinkLineCode::Nrz,
+            }),
+            WMBusMode::T1 | WMBusMode::C1 | WMBusMode::S1 => None,
+            WMBusMode::N1 | WMBusMode::N2 | WMBusMode::F2 => None,
+        }
+    }
+
+    /// Radio configuration that transmits on this PHY
+    pub fn radio_config(&self) -> WMBusConfig {
+        WMBusConfig {
+            frequency_hz: self.frequency_hz,
+            bitrate: self.chip_rate,
+            ..WMBusConfig::default()
+        }
+    }
+}
+
+/// Response delay a meter allows after the end of its frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseTiming {
+    /// Earliest response start after the meter's frame ended
+    pub min_delay: Duration,
+    /// Latest response start after the meter's frame ended
+    pub max_delay: Duration,
+}
+
+impl ResponseTiming {
+    /// Response delay tRO of a bidirectional mode per EN 13757-4
+    ///
+    /// T2 meters listen 2–3 ms after their frame, S2 meters 3–50 ms, C2 meters
+    /// 100 ms ± 0.5 ms (fast response). `None` wherever [`DownlinkPhy::for_mode`] is.
+    pub fn for_mode(mode: WMBusMode) -> Option<Self> {
+        let (min_us, max_us) = match mode {
+            WMBusMode::T2 => (2_000, 3_000),
+            WMBusMode::S2 => (3_000, 50_000),
+            WMBusMode::C2 => (99_500, 100_500),
+            WMBusMode::T1 | WMBusMode::C1 | WMBusMode::S1 => return None,
+            WMBusMode::N1 | WMBusMode::N2 | WMBusMode::F2 => return None,
+        };
+        Some(Self {
+            min_delay: Duration::from_micros(min_us),
+            max_delay: Duration::from_micros(max_us),
+        })
+    }
+
+    /// The window following a frame that ended at `rx_end`
+    pub fn window_after(&self, rx_end: Instant) -> ResponseWindow {
+        ResponseWindow {
+            opens_at: rx_end + self.min_delay,
+            closes_at: rx_end + self.max_delay,
+        }
+    }
+}
+
+/// When a response may start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseWindow {
+    /// Earliest transmission start
+    pub opens_at: Instant,
+    /// Latest transmission start
+    pub closes_at: Instant,
+}
+
+/// A command from the gateway to a meter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GatewayCommand {
+    /// Acknowledge the meter's frame (answers ACC_DMD)
+    Ack,
+    /// Reset the meter's link layer
+    SndNke,
+    /// Send application data, e.g. a new reporting interval
+    SndUd {
+        /// Application layer records following the transport layer header
+        data: Vec<u8>,
+    },
+    /// Request class 2 (e.g. historic) data
+    ReqUd2,
+    /// Confirm an installation request (answers SND_IR)
+    CnfIr,
+}
+
+impl GatewayCommand {
+    /// C-field for this command with frame count bit `fcb`
+    pub fn c_field(&self, fcb: bool) -> u8 {
+        let fcb = if fcb { FCB } else { 0 };
+        match self {
+            GatewayCommand::Ack => C_ACK,
+            GatewayCommand::CnfIr => C_CNF_IR,
+            GatewayCommand::SndNke => C_SND_NKE,
+            GatewayCommand::SndUd { .. } => C_SND_UD | fcb,
+            GatewayCommand::ReqUd2 => C_REQ_UD2 | fcb,
+        }
+    }
+
+    /// C-field (ACD/DFC bits masked) of the meter's answer, if one is expected
+    pub fn expected_response(&self) -> Option<u8> {
+        match self {
+            GatewayCommand::Ack | GatewayCommand::CnfIr => None,
+            GatewayCommand::SndNke | GatewayCommand::SndUd { .. } => Some(C_ACK),
+            GatewayCommand::ReqUd2 => Some(C_RSP_UD),
+        }
+    }
+
+    /// Whether the command carries the frame count bit
+    fn uses_fcb(&self) -> bool {
+        matches!(self, GatewayCommand::SndUd { .. } | GatewayCommand::ReqUd2)
+    }
+
+    /// Encode the bytes to transmit after the radio's sync word to reach `meter` in `mode`
+    ///
+    /// The link layer carries the meter's address; a short transport layer header
+    /// echoes the meter's access number. The frame uses format A block CRCs and the
+    /// line code of the mode's downlink:
+    ///
+    /// | mode   | on air after the sync word                       |
+    /// |--------|--------------------------------------------------|
+    /// | T, S   | Manchester chips, packed MSB-first               |
+    /// | C      | NRZ `0x54`, the type byte `0xCD`, then the frame |
+    /// | N, F   | NRZ (the sync word announces format A)           |
+    ///
+    /// [`t1c1::decode_capture`](crate::wmbus::t1c1::decode_capture) reads C back.
+    pub fn encode(
+        &self,
+        meter: &MeterAddress,
+        access_number: u8,
+        fcb: bool,
+        mode: WMBusMode,
+    ) -> Result<Vec<u8>, BidirectionalError> {
+        let mut link = vec![self.c_field(fcb)];
+        link.extend_from_slice(&meter.manufacturer_id.to_le_bytes());
+        link.extend_from_slice(&meter.address.to_le_bytes());
+        link.extend_from_slice(&[
+            meter.version,
+            meter.device_type,
+            CI_TO_METER_SHORT,
+            access_number,
+            0x00,
+            0x00,
+            0x00,
+        ]);
+        if let GatewayCommand::SndUd { data } = self {
+            link.extend_from_slice(data);
+        }
+
+        let len = link.len();
+        let framed = encode_mode_c(FrameType::TypeA, &link)
+            .ok_or(BidirectionalError::FrameTooLong { len })?;
+        // `framed` starts with the type byte; the L-field and blocks follow.
+        Ok(match mode {
+            WMBusMode::T1 | WMBusMode::T2 | WMBusMode::S1 | WMBusMode::S2 => {
+                chips_to_bytes(&encode_manchester(&framed[1..]))
+            }
+            WMBusMode::C1 | WMBusMode::C2 => {
+                let mut out = Vec::with_capacity(1 + framed.len());
+                out.push(C_SYNC_TAIL);
+                out.extend(framed);
+                out
+            }
+            WMBusMode::N1 | WMBusMode::N2 | WMBusMode::F2 => framed[1..].to_vec(),
+        })
+    }
+}
+
+/// Link layer address of a meter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeterAddress {
+    /// Manufacturer ID (M-field)
+    pub manufacturer_id: u16,
+    /// Identification number (A-field)
+    pub address: u32,
+    /// Version
+    pub version: u8,
+    /// Device type
+    pub device_type: u8,
+}
+
+impl MeterAddress {
+    /// Address of the meter that sent `frame`
+    pub fn of(frame: &WMBusFrame) -> Self {
+        Self {
+            manufacturer_id: frame.manufacturer_id,
+            address: frame.device_address,
+            version: frame.version,
+            device_type: frame.device_type,
+        }
+    }
+
+    fn key(&self) -> MeterKey {
+        (self.manufacturer_id, self.address)
+    }
+}
+
+/// Meters are keyed by manufacturer and address
+type MeterKey = (u16, u32);
+
+/// How an exchange ended
+#[derive(Debug, Clone)]
+pub enum ExchangeOutcome {
+    /// Sent; the command expects no answer
+    Delivered,
+    /// The meter answered with this frame
+    Answered(WMBusFrame),
+    /// No answer before the response timeout
+    TimedOut,
+}
+
+/// A finished command/response exchange with one meter
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    /// Meter the command was sent to
+    pub meter: MeterAddress,
+    /// The command
+    pub command: GatewayCommand,
+    /// How it ended
+    pub outcome: ExchangeOutcome,
+}
+
+/// A response to transmit in a meter's window
+#[derive(Debug, Clone)]
+pub struct ResponsePlan {
+    /// Meter being answered
+    pub meter: MeterAddress,
+    /// Command being sent
+    pub command: GatewayCommand,
+    /// Encoded radio frame
+    pub frame: Vec<u8>,
+    /// Channel, chip rate and line code the frame goes out on
+    pub phy: DownlinkPhy,
+    /// When the frame may go out
+    pub window: ResponseWindow,
+}
+
+/// What a received frame caused
+#[derive(Debug, Default)]
+pub struct Reaction {
+    /// An exchange the frame answered
+    pub completed: Option<Exchange>,
+    /// A response to send in the window the frame opened
+    pub respond: Option<ResponsePlan>,
+}
+
+/// Primary station configuration
+#[derive(Debug, Clone)]
+pub struct PrimaryStationConfig {
+    /// LBT for responses; the listen time must fit before the window closes
+    pub lbt_config: LbtConfig,
+    /// How long to wait for a meter's answer after sending a command
+    pub response_timeout: Duration,
+    /// Answer ACC_DMD with an ACK when nothing is queued for the meter
+    pub ack_access_demand: bool,
+    /// Mode responses are sent in when the receiver does not report one
+    pub mode: WMBusMode,
+}
+
+impl Default for PrimaryStationConfig {
+    fn default() -> Self {
+        Self {
+            lbt_config: LbtConfig {
+                rssi_threshold_dbm: -85,
+                listen_duration_ms: 1, // fits before a 2 ms window opens
+                max_retries: 0,        // no time to back off inside the window
+            },
+            response_timeout: Duration::from_millis(100),
+            ack_access_demand: true,
+            mode: WMBusMode::C2,
+        }
+    }
+}
+
+/// A command on air, waiting for the meter's answer
+#[derive(Debug)]
+struct Awaiting {
+    meter: MeterAddress,
+    command: GatewayCommand,
+    deadline: Instant,
+}
+
+/// Gateway-side state for bidirectional meters
+#[derive(Debug)]
+pub struct PrimaryStation {
+    config: PrimaryStationConfig,
+    queues: HashMap<MeterKey, VecDeque<GatewayCommand>>,
+    /// Next frame count bit per meter (set after a link reset)
+    fcb: HashMap<MeterKey, bool>,
+    awaiting: HashMap<MeterKey, Awaiting>,
+}
+
+impl PrimaryStation {
+    /// Create a station with no queued commands
+    pub fn new(config: PrimaryStationConfig) -> Self {
+        Self {
+            config,
+            queues: HashMap::new(),
+            fcb: HashMap::new(),
+            awaiting: HashMap::new(),
+        }
+    }
+
+    /// Configuration in use
+    pub fn config(&self) -> &PrimaryStationConfig {
+        &self.config
+    }
+
+    /// Queue `command` for the meter's next access window
+    pub fn queue(&mut self, manufacturer_id: u16, address: u32, command: GatewayCommand) {
+        self.queues
+            .entry((manufacturer_id, address))
+            .or_default()
+            .push_back(command);
+    }
+
+    /// Queue `command` ahead of everything else queued for the meter
+    pub fn queue_front(&mut self, manufacturer_id: u16, address: u32, command: GatewayCommand) {
+        self.queues
+            .entry((manufacturer_id, address))
+            .or_default()
+            .push_front(command);
+    }
+
+    /// Queue a CNF_IR ahead of everything else unless one is already waiting
+    ///
+    /// A meter repeats SND_IR until it is confirmed; each repeat must not add another
+    /// confirmation.
+    pub fn queue_confirmation(&mut self, manufacturer_id: u16, address: u32) {
+        let queue = self.queues.entry((manufacturer_id, address)).or_default();
+        if !queue.contains(&GatewayCommand::CnfIr) {
+            queue.push_front(GatewayCommand::CnfIr);
+        }
+    }
+
+    /// Commands still queued for a meter
+    pub fn queued(&self, manufacturer_id: u16, address: u32) -> usize {
+        self.queues
+            .get(&(manufacturer_id, address))
+            .map_or(0, VecDeque::len)
+    }
+
+    /// Handle a frame from a meter whose last bit was received at `rx_end`
+    ///
+    /// Matches the frame against an outstanding command first, then — if the frame
+    /// opens a window — plans the meter's next queued command (or the ACK an access
+    /// demand asks for). An installation request (SND_IR) opens a window for the
+    /// CNF_IR that answers it. The response is planned for the configured mode; meters
+    /// in a mode without a downlink (N2, F2) are not answered.
+    pub fn on_frame(&mut self, frame: &WMBusFrame, rx_end: Instant) -> Reaction {
+        self.on_frame_in(frame, None, rx_end)
+    }
+
+    /// [`on_frame`](Self::on_frame) for a frame received in `link_mode`, answered in the
+    /// matching bidirectional mode (T2 for T, C2 for C, ...)
+    pub fn on_frame_in(
+        &mut self,
+        frame: &WMBusFrame,
+        link_mode: Option<LinkMode>,
+        rx_end: Instant,
+    ) -> Reaction {
+        let mode = match link_mode {
+            Some(LinkMode::T) => WMBusMode::T2,
+            Some(LinkMode::C) => WMBusMode::C2,
+            Some(LinkMode::S) => WMBusMode::S2,
+            Some(LinkMode::N) => WMBusMode::N2,
+            None => self.config.mode,
+        };
+        let meter = MeterAddress::of(frame);
+        let key = meter.key();
+        let mut reaction = Reaction::default();
+
+        if let Some(awaiting) = self.awaiting.get(&key) {
+            if awaiting.command.expected_response() == Some(frame.control_field & !ACD_DFC) {
+                let awaiting = self.awaiting.remove(&key).expect("checked above");
+                if awaiting.command.uses_fcb() {
+                    let fcb = self.fcb.entry(key).or_insert(true);
+                    *fcb = !*fcb;
+                }
+                reaction.completed = Some(Exchange {
+                    meter: awaiting.meter,
+                    command: awaiting.command,
+                    outcome: ExchangeOutcome::Answered(frame.clone()),
+                });
+            }
+        }
+
+        let access_demand = frame.control_field == C_ACC_DMD;
+        let install_request = frame.control_field == C_SND_IR;
+        let opens_window =
+            access_demand || install_request || Accessibility::of(frame).opens_window();
+        let downlink = DownlinkPhy::for_mode(mode).zip(ResponseTiming::for_mode(mode));
+        if let Some((phy, timing)) =
+            downlink.filter(|_| opens_window && !self.awaiting.contains_key(&key))
+        {
+            let command = match self.queues.get_mut(&key).and_then(VecDeque::pop_front) {
+                Some(command) => Some(command),
+                None if access_demand && self.config.ack_access_demand => Some(GatewayCommand::Ack),
+                None => None,
+            };
+            if let Some(command) = command {
+                let fcb = *self.fcb.get(&key).unwrap_or(&true);
+                let access_number = frame.access_number().unwrap_or(0);
+                match command.encode(&meter, access_number, fcb, mode) {
+                    Ok(frame_bytes) => {
+                        reaction.respond = Some(ResponsePlan {
+                            meter,
+                            command,
+                            frame: frame_bytes,
+                            phy,
+                            window: timing.window_after(rx_end),
+                        })
+                    }
+                    Err(e) => log::warn!("Dropping command for meter {:#X}: {e}", meter.address),
+                }
+            }
+        }
+
+        reaction
+    }
+
+    /// Record that `plan` went out at `sent_at`
+    ///
+    /// Returns the finished exchange for commands that expect no answer.
+    pub fn sent(&mut self, plan: ResponsePlan, sent_at: Instant) -> Option<Exchange> {
+        let key = plan.meter.key();
+        if plan.command == GatewayCommand::SndNke {
+            // After a link reset the next frame count bit is 1.
+            self.fcb.insert(key, true);
+        }
+        if plan.command.expected_response().is_none() {
+            return Some(Exchange {
+                meter: plan.meter,
+                command: plan.command,
+                outcome: ExchangeOutcome::Delivered,
+            });
+        }
+        self.awaiting.insert(
+            key,
+            Awaiting {
+                meter: plan.meter,
+                command: plan.command,
+                deadline: sent_at + self.config.response_timeout,
+            },
+        );
+        None
+    }
+
+    /// Put a plan that could not be sent back at the head of the meter's queue
+    ///
+    /// ACK and CNF_IR only make sense right after the frame they answer and are
+    /// dropped instead.
+    pub fn requeue(&mut self, plan: ResponsePlan) {
+        if !matches!(plan.command, GatewayCommand::Ack | GatewayCommand::CnfIr) {
+            self.queues
+                .entry(plan.meter.key())
+                .or_default()
+                .push_front(plan.command);
+        }
+    }
+
+    /// Time out exchanges whose meter did not answer by `now`
+    pub fn expire(&mut self, now: Instant) -> Vec<Exchange> {
+        let expired: Vec<MeterKey> = self
+            .awaiting
+            .iter()
+            .filter(|(_, a)| a.deadline <= now)
+            .map(|(key, _)| *key)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|key| self.awaiting.remove(&key))
+            .map(|a| Exchange {
+                meter: a.meter,
+                command: a.command,
+                outcome: ExchangeOutcome::TimedOut,
+            })
+            .collect()
+    }
+}
+
+/// Transmit `frame` inside `window`, listening before talk with `lbt_config`
+///
+/// The listen period is placed so the frame starts as the window opens; if even an
+/// immediate listen would end after the window closes, nothing is sent. A busy channel
+/// is not retried — there is no time to back off inside the window.
+///
+/// # Returns
+/// * `Ok(instant)` - when transmission started
+/// * `Err(BidirectionalError::WindowMissed)` - too late to fit the listen period
+/// * `Err(BidirectionalError::Radio)` - busy channel or radio failure
+pub async fn transmit_in_window<R: RadioDriver + ?Sized>(
+    radio: &mut R,
+    frame: &[u8],
+    window: ResponseWindow,
+    lbt_config: LbtConfig,
+) -> Result<Instant, BidirectionalError> {
+    let listen = Duration::from_millis(lbt_config.listen_duration_ms as u64);
+    let listen_start = window
+        .opens_at
+        .checked_sub(listen)
+        .unwrap_or(window.opens_at)
+        .max(Instant::now());
+    let tx_at = listen_start + listen;
+    if tx_at > window.closes_at {
+        return Err(BidirectionalError::WindowMissed {
+            late_by: tx_at - window.closes_at,
+        });
+    }
+
+    sleep_until(listen_start).await;
+    radio
+        .transmit_with_lbt(
+            frame,
+            LbtConfig {
+                max_retries: 0,
+                ..lbt_config
+            },
+        )
+        .await?;
+    Ok(tx_at)
+}
+
+/// Transmit `plan` on its downlink PHY, then return the radio to `rx_config`
+///
+/// The meter listens on its downlink channel, not on the one its frame arrived on, so the
+/// radio is retuned to [`ResponsePlan::phy`] before [`transmit_in_window`] and to the
+/// receive configuration afterwards, whether or not the response went out. The caller
+/// re-arms reception. A failed restore is logged rather than returned, so a response
+/// that went out is still recorded as sent.
+///
+/// # Returns
+/// * `Ok(instant)` - when transmission started
+/// * `Err(BidirectionalError)` - retuning failed or the response was not sent
+pub async fn transmit_response<R: RadioDriver + ?Sized>(
+    radio: &mut R,
+    plan: &ResponsePlan,
+    lbt_config: LbtConfig,
+    rx_config: &WMBusConfig,
+) -> Result<Instant, BidirectionalError> {
+    radio.initialize(plan.phy.radio_config()).await?;
+    let result = transmit_in_window(radio, &plan.frame, plan.window, lbt_config).await;
+    if let Err(e) = radio.initialize(rx_config.clone()).await {
+        log::error!("Failed to restore the RX configuration after a response: {e:?}");
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::frame::parse_wmbus_frame;
+    use crate::wmbus::line_code::{bytes_to_chips, decode_manchester};
+    use crate::wmbus::mode_c::{decode_mode_c, WMBusLinkFrame};
+    use crate::wmbus::radio::driver::Sx126xDriver;
+    use crate::wmbus::radio::hal::RecordingHal;
+    use crate::wmbus::t1c1::decode_capture;
+
+    const M: u16 = 0x2C2D;
+    const A: u32 = 0x12345678;
+
+    fn meter_frame(c: u8, cw: u16) -> WMBusFrame {
+        let [cw_lo, cw_hi] = cw.to_le_bytes();
+        let raw = WMBusFrame::build(c, M, A, 0x1B, 0x16, 0x7A, &[0x2A, 0x00, cw_lo, cw_hi]);
+        parse_wmbus_frame(&raw).unwrap()
+    }
+
+    /// Read a T or C response back the way the receive path does.
+    fn sent_frame(on_air: &[u8]) -> (LinkMode, WMBusFrame) {
+        let (mode, link) = decode_capture(on_air).unwrap();
+        assert!(link.crc_ok);
+        let frame = parse_wmbus_frame(&link.to_frame_bytes().unwrap()).unwrap();
+        (mode, frame)
+    }
+
+    /// Read a T2 or S2 response back: Manchester chips of a format A frame.
+    fn manchester_link(on_air: &[u8]) -> WMBusLinkFrame {
+        let (bytes, errors) = decode_manchester(&bytes_to_chips(on_air));
+        assert_eq!(errors, 0);
+        let link = decode_mode_c(&[[0xCD].as_slice(), &bytes].concat()).unwrap();
+        assert!(link.crc_ok);
+        link
+    }
+
+    fn meter_reply(c: u8) -> WMBusFrame {
+        let raw = WMBusFrame::build(c, M, A, 0x1B, 0x16, 0x8A, &[0x2A, 0x00, 0x00, 0x00]);
+        parse_wmbus_frame(&raw).unwrap()
+    }
+
+    #[test]
+    fn accessibility_bits() {
+        assert_eq!(
+            Accessibility::from_config_word(0x0000),
+            Accessibility::NoAccess
+        );
+        assert_eq!(
+            Accessibility::from_config_word(0x4000),
+            Accessibility::TemporaryNoAccess
+        );
+        assert_eq!(
+            Accessibility::from_config_word(0x8500),
+            Accessibility::LimitedAccess
+        );
+        assert_eq!(
+            Accessibility::from_config_word(0xC000),
+            Accessibility::UnlimitedAccess
+        );
+        assert!(Accessibility::of(&meter_frame(C_SND_NR, 0x8000)).opens_window());
+        assert!(!Accessibility::of(&meter_frame(C_SND_NR, 0x4000)).opens_window());
+    }
+
+    #[test]
+    fn each_mode_answers_on_its_own_downlink() {
+        let t2 = DownlinkPhy::for_mode(WMBusMode::T2).unwrap();
+        assert_eq!(
+            (t2.frequency_hz, t2.chip_rate, t2.line_code),
+            (868_300_000, 32_768, DownlinkLineCode::Manchester)
+        );
+        assert_eq!(DownlinkPhy::for_mode(WMBusMode::S2), Some(t2));
+        let c2 = DownlinkPhy::for_mode(WMBusMode::C2).unwrap();
+        assert_eq!(
+            (c2.frequency_hz, c2.chip_rate, c2.line_code),
+            (869_525_000, 50_000, DownlinkLineCode::Nrz)
+        );
+        assert_eq!(
+            (c2.radio_config().frequency_hz, c2.radio_config().bitrate),
+            (869_525_000, 50_000)
+        );
+
+        let t2 = ResponseTiming::for_mode(WMBusMode::T2).unwrap();
+        assert_eq!(
+            (t2.min_delay, t2.max_delay),
+            (Duration::from_millis(2), Duration::from_millis(3))
+        );
+        let c2 = ResponseTiming::for_mode(WMBusMode::C2).unwrap();
+        assert_eq!(
+            (c2.min_delay, c2.max_delay),
+            (
+                Duration::from_micros(99_500),
+                Duration::from_micros(100_500)
+            )
+        );
+        assert!(ResponseTiming::for_mode(WMBusMode::S2).unwrap().max_delay > t2.max_delay);
+        for mode in [WMBusMode::T1, WMBusMode::C1, WMBusMode::N2, WMBusMode::F2] {
+            assert!(DownlinkPhy::for_mode(mode).is_none());
+            assert!(ResponseTiming::for_mode(mode).is_none());
+        }
+
+        // A C-mode frame is answered on the C2 downlink, 100 ms after it ended.
+        let mut station = PrimaryStation::new(PrimaryStationConfig::default());
+        station.queue(M, A, GatewayCommand::SndNke);
+        let rx_end = Instant::now();
+        let plan = station
+            .on_frame_in(&meter_frame(C_SND_NR, 0x8000), Some(LinkMode::C), rx_end)
+            .respond
+            .unwrap();
+        assert_eq!(plan.phy, DownlinkPhy::for_mode(WMBusMode::C2).unwrap());
+        assert_eq!(plan.window.opens_at - rx_end, Duration::from_micros(99_500));
+
+        // N-mode meters are not answered.
+        station.queue(M, A, GatewayCommand::ReqUd2);
+        assert!(station
+            .on_frame_in(&meter_frame(C_SND_NR, 0x8000), Some(LinkMode::N), rx_end)
+            .respond
+            .is_none());
+        assert_eq!(station.queued(M, A), 1);
+    }
+
+    #[test]
+    fn queued_commands_go_out_in_windows_and_match_responses() {
+        let mut station = PrimaryStation::new(PrimaryStationConfig::default());
+        station.queue(M, A, GatewayCommand::SndUd { data: vec![0x01] });
+        station.queue(M, A, GatewayCommand::ReqUd2);
+        let t0 = Instant::now();
+
+        // A unidirectional frame opens no window.
+        assert!(station
+            .on_frame(&meter_frame(C_SND_NR, 0x0000), t0)
+            .respond
+            .is_none());
+
+        // Limited access: SND_UD goes out with FCB set, echoing ACC 0x2A.
+        let plan = station
+            .on_frame(&meter_frame(C_SND_NR, 0x8000), t0)
+            .respond
+            .unwrap();
+        assert_eq!(plan.window.opens_at, t0 + Duration::from_micros(99_500));
+        assert_eq!(plan.window.closes_at, t0 + Duration::from_micros(100_500));
+        let (mode, sent) = sent_frame(&plan.frame);
+        assert_eq!(mode, LinkMode::C);
+        assert_eq!(sent.control_field, 0x73);
+        assert_eq!((sent.manufacturer_id, sent.device_address), (M, A));
+        assert_eq!(sent.payload, vec![0x2A, 0x00, 0x00, 0x00, 0x01]);
+        assert!(station.sent(plan, t0).is_none());
+
+        // The meter ACKs (with ACD set); its reply also opens the next window: REQ_UD2
+        // goes out with the toggled FCB.
+        let reaction = station.on_frame(&meter_frame(C_ACK | 0x20, 0x8000), t0);
+        let done = reaction.completed.unwrap();
+        assert!(matches!(done.command, GatewayCommand::SndUd { .. }));
+        assert!(matches!(done.outcome, ExchangeOutcome::Answered(_)));
+        let plan = reaction.respond.unwrap();
+        assert_eq!(sent_frame(&plan.frame).1.control_field, C_REQ_UD2);
+        station.sent(plan, t0);
+
+        // An ACK does not answer REQ_UD2; RSP_UD does.
+        assert!(station
+            .on_frame(&meter_reply(C_ACK), t0)
+            .completed
+            .is_none());
+        let done = station
+            .on_frame(&meter_reply(C_RSP_UD), t0)
+            .completed
+            .unwrap();
+        assert_eq!(done.command, GatewayCommand::ReqUd2);
+        assert_eq!(station.queued(M, A), 0);
+    }
+
+    #[test]
+    fn access_demand_is_acked_and_silence_times_out() {
+        let mut station = PrimaryStation::new(PrimaryStationConfig::default());
+        let t0 = Instant::now();
+
+        let plan = station
+            .on_frame(&meter_frame(C_ACC_DMD, 0x0000), t0)
+            .respond
+            .unwrap();
+        assert_eq!(plan.command, GatewayCommand::Ack);
+        assert!(matches!(
+            station.sent(plan, t0).unwrap().outcome,
+            ExchangeOutcome::Delivered
+        ));
+
+        station.queue(M, A, GatewayCommand::SndNke);
+        let plan = station
+            .on_frame(&meter_frame(C_SND_NR, 0xC000), t0)
+            .respond
+            .unwrap();
+        assert_eq!(sent_frame(&plan.frame).1.control_field, C_SND_NKE);
+        station.sent(plan, t0);
+        assert!(station.expire(t0 + Duration::from_millis(99)).is_empty());
+        let expired = station.expire(t0 + Duration::from_millis(100));
+        assert!(matches!(expired[0].outcome, ExchangeOutcome::TimedOut));
+
+        // A plan that could not be sent is retried at the next window.
+        station.queue(M, A, GatewayCommand::ReqUd2);
+        let plan = station
+            .on_frame(&meter_frame(C_SND_NR, 0x8000), t0)
+            .respond
+            .unwrap();
+        station.requeue(plan);
+        assert_eq!(station.queued(M, A), 1);
+    }
+
+    #[test]
+    fn repeated_install_requests_queue_one_confirmation() {
+        let mut station = PrimaryStation::new(PrimaryStationConfig::default());
+        let t0 = Instant::now();
+
+        // An exchange is outstanding, so the confirmation has to wait in the queue.
+        station.queue(M, A, GatewayCommand::ReqUd2);
+        let plan = station
+            .on_frame(&meter_frame(C_SND_NR, 0x8000), t0)
+            .respond
+            .unwrap();
+        station.sent(plan, t0);
+        for _ in 0..3 {
+            station.queue_confirmation(M, A);
+            assert!(station
+                .on_frame(&meter_frame(C_SND_IR, 0x8000), t0)
+                .respond
+                .is_none());
+        }
+        assert_eq!(station.queued(M, A), 1);
+
+        // Once the exchange times out, the next retry gets the single CNF_IR.
+        station.expire(t0 + Duration::from_millis(100));
+        station.queue_confirmation(M, A);
+        let plan = station
+            .on_frame(&meter_frame(C_SND_IR, 0x8000), t0)
+            .respond
+            .unwrap();
+        assert_eq!(plan.command, GatewayCommand::CnfIr);
+        assert_eq!(station.queued(M, A), 0);
+    }
+
+    #[test]
+    fn responses_decode_with_the_receive_side_decoder() {
+        let meter = MeterAddress::of(&meter_frame(C_SND_NR, 0x8000));
+        // Long enough for three format A data blocks.
+        let command = GatewayCommand::SndUd {
+            data: (0..30).collect(),
+        };
+        let mut payload = vec![0x2A, 0x00, 0x00, 0x00];
+        payload.extend(0..30);
+
+        let on_air = command.encode(&meter, 0x2A, true, WMBusMode::C2).unwrap();
+        let (decoded_mode, sent) = sent_frame(&on_air);
+        assert_eq!(decoded_mode, LinkMode::C);
+        assert_eq!(sent.control_field, 0x73);
+        assert_eq!((sent.manufacturer_id, sent.device_address), (M, A));
+        assert_eq!(sent.control_info, 0x5A);
+        assert_eq!(sent.payload, payload);
+
+        // T2 and S2: Manchester chips of the format A frame.
+        for mode in [WMBusMode::T2, WMBusMode::S2] {
+            let on_air = command.encode(&meter, 0x2A, true, mode).unwrap();
+            let link = manchester_link(&on_air);
+            assert_eq!(link.control_field, 0x73);
+            assert_eq!(link.application_data(), payload);
+        }
+
+        // A frame received in mode T is answered on the T2 downlink.
+        let mut station = PrimaryStation::new(PrimaryStationConfig::default());
+        station.queue(M, A, GatewayCommand::SndNke);
+        let plan = station
+            .on_frame_in(
+                &meter_frame(C_SND_NR, 0x8000),
+                Some(LinkMode::T),
+                Instant::now(),
+            )
+            .respond
+            .unwrap();
+        assert_eq!(plan.phy, DownlinkPhy::for_mode(WMBusMode::T2).unwrap());
+        assert_eq!(manchester_link(&plan.frame).control_field, C_SND_NKE);
+
+        assert!(matches!(
+            GatewayCommand::SndUd { data: vec![0; 255] }.encode(&meter, 0, true, WMBusMode::C2),
+            Err(BidirectionalError::FrameTooLong { .. })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn response_starts_as_the_window_opens() {
+        let hal = RecordingHal::new();
+        hal.set_rssi(-110);
+        let probe = hal.clone();
+        let mut radio = Sx126xDriver::new(hal, 32_000_000);
+        radio.configure_for_wmbus(868_950_000, 100_000).unwrap();
+
+        let config = PrimaryStationConfig::default();
+        let rx_end = Instant::now();
+        let window = ResponseTiming::for_mode(WMBusMode::T2)
+            .unwrap()
+            .window_after(rx_end);
+        let frame = GatewayCommand::SndNke
+            .encode(
+                &MeterAddress::of(&meter_frame(C_SND_NR, 0x8000)),
+                0x2A,
+                true,
+                WMBusMode::T2,
+            )
+            .unwrap();
+
+        let tx_at = transmit_in_window(&mut radio, &frame, window, config.lbt_config)
+            .await
+            .unwrap();
+        assert_eq!(tx_at - rx_end, Duration::from_millis(2));
+        // Listening started 1 ms before the window opened.
+        assert_eq!(Instant::now() - rx_end, Duration::from_millis(1));
+        assert!(probe.has_cmd(0x0E, &[[0x00].as_slice(), &frame].concat()));
+        assert!(probe.first_cmd(0x83).is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn responses_go_out_on_the_downlink_and_rx_is_restored() {
+        let hal = RecordingHal::new();
+        hal.set_rssi(-110);
+        let probe = hal.clone();
+        let mut radio = Sx126xDriver::new(hal, 32_000_000);
+        let rx_config = WMBusConfig {
+            frequency_hz: 868_950_000,
+            bitrate: 100_000,
+            ..WMBusConfig::default()
+        };
+        radio.initialize(rx_config.clone()).await.unwrap();
+
+        let mut station = PrimaryStation::new(PrimaryStationConfig::default());
+        station.queue(M, A, GatewayCommand::SndNke);
+        let plan = station
+            .on_frame_in(
+                &meter_frame(C_SND_NR, 0x8000),
+                Some(LinkMode::C),
+                Instant::now(),
+            )
+            .respond
+            .unwrap();
+        transmit_response(&mut radio, &plan, station.config().lbt_config, &rx_config)
+            .await
+            .unwrap();
+
+        // Tuned away to 869.525 MHz for the transmission, then back to the RX carrier.
+        let commands = probe.commands();
+        let tunes: Vec<(usize, &[u8])> = commands
+            .iter()
+            .enumerate()
+            .filter(|(_, (opcode, _))| *opcode == 0x86)
+            .map(|(i, (_, data))| (i, data.as_slice()))
+            .collect();
+        let [(_, rx), (_, tx), (restored_at, restored)] = tunes[..] else {
+            panic!("expected three retunes, got {tunes:?}");
+        };
+        assert_ne!(tx, rx);
+        assert_eq!(restored, rx);
+        let tx_at = probe.first_cmd(0x83).unwrap();
+        assert!(tunes[1].0 < tx_at && tx_at < restored_at);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn late_or_blocked_responses_are_not_sent() {
+        let hal = RecordingHal::new();
+        let probe = hal.clone();
+        let mut radio = Sx126xDriver::new(hal, 32_000_000);
+        radio.configure_for_wmbus(868_950_000, 100_000).unwrap();
+        let config = PrimaryStationConfig::default();
+        let timing = ResponseTiming::for_mode(WMBusMode::T2).unwrap();
+        let frame = vec![0u8; 12];
+
+        // The frame was polled 2.5 ms after it ended: 1 ms of listening no longer fits.
+        let rx_end = Instant::now();
+        tokio::time::advance(Duration::from_micros(2_500)).await;
+        let window = timing.window_after(rx_end);
+        assert!(matches!(
+            transmit_in_window(&mut radio, &frame, window, config.lbt_config).await,
+            Err(BidirectionalError::WindowMissed { late_by }) if late_by == Duration::from_micros(500)
+        ));
+
+        // In time, but the channel is busy (RecordingHal reports 0 dBm by default).
+        let window = timing.window_after(Instant::now());
+        assert!(matches!(
+            transmit_in_window(&mut radio, &frame, window, config.lbt_config).await,
+            Err(BidirectionalError::Radio(
+                RadioDriverError::ChannelBusy { .. }
+            ))
+        ));
+        assert!(probe.first_cmd(0x83).is_none());
+    }
+}
@@ -0,0 +1,567 @@
+//! # Raw bitstream preamble/sync detector (modes T, S, C and N)
+//!
+//! Radios can be run with sync detection disabled so the FIFO fills with every
+//! demodulated chip — the raw dumps taken by metermon's airwave sweep, for example.
+//! [`BitstreamScanner`] searches such a stream for the EN 13757-4 preambles and sync
+//! words of every mode, at any bit offset and in either polarity, line-decodes the
+//! candidate frame that follows, and validates its block CRCs through
+//! [`decode_mode_c`](crate::wmbus::mode_c::decode_mode_c) (the Type A / Type B block
+//! framing is shared by all modes; only the line code and sync differ).
+//!
+//! ## Sync patterns (chips, first-to-last)
+//!
+//! | mode | after the `0101…` preamble                  | line code  | framing |
+//! |------|---------------------------------------------|------------|---------|
+//! | T    | `0000111101`                                | 3-out-of-6 | A       |
+//! | C    | `0000111101` `0x54` `0xCD` / `0x3D`         | NRZ        | A / B   |
+//! | S    | `000111011010010110`                        | Manchester | A       |
+//! | N    | `0xF68D` (A) / `0xF672` (B)                 | NRZ        | A / B   |
+//!
+//! Mode C's `0x543D` sync ends in the mode-T sync word, so every C frame is also a T
+//! sync hit; the scanner disambiguates by looking for the `0x54CD`/`0x543D` tail that a
+//! 3-out-of-6 stream can never contain (`010101` is not a valid code word).
+//!
+//! ## Input
+//!
+//! [`BitstreamScanner::scan`] takes packed bytes, MSB-first (as read from a radio FIFO);
+//! [`BitstreamScanner::scan_chips`] takes one chip per element (as produced by a software
+//! demodulator).
+//!
+//! ```rust
+//! use mbus_rs::wmbus::bitstream::{BitstreamScanner, LinkMode};
+//!
+//! // Real Type B frame from meter 74644444 (KAM), behind a C-mode preamble + sync.
+//! let mut raw = vec![0x55, 0x55, 0x54, 0x3D, 0x54];
+//! raw.extend(hex::decode(
+//!     "3d25442d2c444464741b168d208d3048a121f6597959d56873b609a439b99d58531a8a726d9f0c",
+//! ).unwrap());
+//! let frames = BitstreamScanner::default().scan(&raw);
+//! assert_eq!(frames.len(), 1);
+//! assert_eq!(frames[0].mode, LinkMode::C);
+//! assert_eq!(frames[0].link.device_address, 74644444);
+//! ```
+
+use crate::wmbus::frame_decode::sync;
+use crate::wmbus::line_code::{bytes_to_chips, decode_3of6, decode_manchester};
+use crate::wmbus::mode_c::{decode_mode_c, WMBusLinkFrame};
+use crate::wmbus::radio::rfm69_packet::packet_size;
+use std::collections::BTreeSet;
+
+/// Mode-T sync word (10 chips), also the tail of the mode-C `0x543D` sync.
+const T_SYNC: u32 = 0b00_0011_1101;
+const T_SYNC_LEN: usize = 10;
+/// Mode-S sync word (18 chips).
+const S_SYNC: u32 = 0b00_0111_0110_1001_0110;
+const S_SYNC_LEN: usize = 18;
+/// Mode-N sync words (16 chips) for frame format A and B.
+const N_SYNC_A: u32 = 0xF68D;
+const N_SYNC_B: u32 = 0xF672;
+const N_SYNC_LEN: usize = 16;
+/// Mode-C second sync byte, followed by the normalized type byte.
+const C_SYNC_TAIL: u8 = 0x54;
+
+/// The wM-Bus physical-layer mode a frame was found in.
+///
+/// This is the on-air family only: T1 and T2 meter transmissions (likewise C1/C2, S1/S2)
+/// are identical on the air and cannot be told apart from the bitstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LinkMode {
+    /// Mode T: 3-out-of-6 line code, frame format A.
+    T,
+    /// Mode S: Manchester line code, frame format A.
+    S,
+    /// Mode C: NRZ, frame format A or B.
+    C,
+    /// Mode N (169 MHz): NRZ, frame format A or B.
+    N,
+}
+
+impl LinkMode {
+    /// All modes, in the order the scanner reports them in summaries.
+    pub const ALL: [LinkMode; 4] = [LinkMode::T, LinkMode::S, LinkMode::C, LinkMode::N];
+
+    /// Number of chips that carry one link-layer byte in this mode.
+    pub fn chips_per_byte(&self) -> usize {
+        match self {
+            LinkMode::T => 12,
+            LinkMode::S => 16,
+            LinkMode::C | LinkMode::N => 8,
+        }
+    }
+}
+
+/// A candidate frame recovered from a raw bitstream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedFrame {
+    /// Mode whose sync word matched.
+    pub mode: LinkMode,
+    /// Chip offset of the first sync chip within the scanned stream.
+    pub bit_offset: usize,
+    /// True if the stream had to be inverted to match (e.g. swapped FSK deviation).
+    pub inverted: bool,
+    /// Line-code violations while decoding (invalid 3-out-of-6 words, `00`/`11`
+    /// Manchester pairs). Always 0 for the NRZ modes.
+    pub symbol_errors: usize,
+    /// The normalized frame (type byte first), as accepted by
+    /// [`decode_mode_c`](crate::wmbus::mode_c::decode_mode_c).
+    pub raw: Vec<u8>,
+    /// The decoded link layer; [`WMBusLinkFrame::crc_ok`] reports the block-CRC result.
+    pub link: WMBusLinkFrame,
+}
+
+/// Scanner configuration.
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    /// Modes to search for.
+    pub modes: Vec<LinkMode>,
+    /// Minimum run of alternating preamble chips required directly before a sync word.
+    /// Real preambles are far longer, but sync-off FIFO captures often clip them; the
+    /// block CRCs are what actually reject false sync hits.
+    pub min_preamble_chips: usize,
+    /// Also search the inverted stream.
+    pub both_polarities: bool,
+    /// Report only frames whose block CRCs all validate.
+    pub crc_valid_only: bool,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            modes: LinkMode::ALL.to_vec(),
+            min_preamble_chips: 6,
+            both_polarities: true,
+            crc_valid_only: true,
+        }
+    }
+}
+
+/// Per-mode tally of a scan, for working out which modes a site uses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeSummary {
+    /// The mode.
+    pub mode: LinkMode,
+    /// CRC-valid frames found in this mode.
+    pub frames: usize,
+    /// Distinct meter addresses (BCD-decoded) seen in this mode, ascending.
+    pub meters: Vec<u32>,
+}
+
+/// Searches raw bitstreams for wM-Bus frames. See the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct BitstreamScanner {
+    config: ScanConfig,
+}
+
+impl BitstreamScanner {
+    /// Create a scanner with the given configuration.
+    pub fn new(config: ScanConfig) -> Self {
+        Self { config }
+    }
+
+    /// The active configuration.
+    pub fn config(&self) -> &ScanConfig {
+        &self.config
+    }
+
+    /// Scan packed bytes (MSB-first chips, as read from a radio FIFO).
+    pub fn scan(&self, raw: &[u8]) -> Vec<DetectedFrame> {
+        self.scan_chips(&bytes_to_chips(raw))
+    }
+
+    /// Scan a chip stream (one `0`/`1` per element).
+    ///
+    /// Frames are returned in stream order. Once a CRC-valid frame is found, the scan
+    /// resumes after its last chip, so a frame is never reported twice per polarity.
+    pub fn scan_chips(&self, chips: &[u8]) -> Vec<DetectedFrame> {
+        let mut frames = self.scan_polarity(chips, false);
+        if self.config.both_polarities {
+            let inverted: Vec<u8> = chips.iter().map(|&c| (c & 1) ^ 1).collect();
+            frames.extend(self.scan_polarity(&inverted, true));
+            frames.sort_by_key(|f| f.bit_offset);
+        }
+        frames
+    }
+
+    fn enabled(&self, mode: LinkMode) -> bool {
+        self.config.modes.contains(&mode)
+    }
+
+    fn scan_polarity(&self, chips: &[u8], inverted: bool) -> Vec<DetectedFrame> {
+        let mut frames = Vec::new();
+        let mut acc: u32 = 0;
+        let mut i = 0;
+        while i < chips.len() {
+            acc = (acc << 1) | (chips[i] & 1) as u32;
+            let end = i + 1; // exclusive end of the sync word ending at chip i
+
+            let found = self
+                .try_s(chips, acc, end)
+                .or_else(|| self.try_n(chips, acc, end))
+                .or_else(|| self.try_t_or_c(chips, acc, end));
+
+            if let Some((mut frame, frame_end)) = found {
+                frame.inverted = inverted;
+                if frame.link.crc_ok {
+                    frames.push(frame);
+                    // Resume after the frame; restart the sync accumulator.
+                    i = frame_end;
+                    acc = 0;
+                    continue;
+                }
+                if !self.config.crc_valid_only {
+                    frames.push(frame);
+                }
+            }
+            i += 1;
+        }
+        frames
+    }
+
+    /// True if at least `min_preamble_chips` alternating chips end just before `start`.
+    fn has_preamble(&self, chips: &[u8], start: usize) -> bool {
+        let need = self.config.min_preamble_chips;
+        if need == 0 {
+            return true;
+        }
+        if start < need {
+            return false;
+        }
+        (start - need + 1..start).all(|j| chips[j] != chips[j - 1])
+    }
+
+    fn try_s(&self, chips: &[u8], acc: u32, end: usize) -> Option<(DetectedFrame, usize)> {
+        if !self.enabled(LinkMode::S) || end < S_SYNC_LEN || acc & mask(S_SYNC_LEN) != S_SYNC {
+            return None;
+        }
+        let start = end - S_SYNC_LEN;
+        if !self.has_preamble(chips, start) {
+            return None;
+        }
+        decode_line_coded(LinkMode::S, chips, start, end)
+    }
+
+    fn try_n(&self, chips: &[u8], acc: u32, end: usize) -> Option<(DetectedFrame, usize)> {
+        if !self.enabled(LinkMode::N) || end < N_SYNC_LEN {
+            return None;
+        }
+        let type_byte = match acc & mask(N_SYNC_LEN) {
+            N_SYNC_A => sync::A_NORM,
+            N_SYNC_B => sync::B_NORM,
+            _ => return None,
+        };
+        let start = end - N_SYNC_LEN;
+        if !self.has_preamble(chips, start) {
+            return None;
+        }
+        decode_nrz(LinkMode::N, chips, start, end, type_byte)
+    }
+
+    fn try_t_or_c(&self, chips: &[u8], acc: u32, end: usize) -> Option<(DetectedFrame, usize)> {
+        if end < T_SYNC_LEN || acc & mask(T_SYNC_LEN) != T_SYNC {
+            return None;
+        }
+        let start = end - T_SYNC_LEN;
+        if !self.has_preamble(chips, start) {
+            return None;
+        }
+        // Mode C: the T sync is followed by 0x54 and the normalized type byte.
+        if self.enabled(LinkMode::C) && end + 16 <= chips.len() {
+            let tail = nrz_byte(chips, end);
+            let type_byte = nrz_byte(chips, end + 8);
+            if tail == C_SYNC_TAIL && matches!(type_byte, sync::A_NORM | sync::B_NORM) {
+                // Report the offset of the full 0x543D sync (6 preamble chips earlier).
+                let sync_start = start.saturating_sub(6);
+                return decode_nrz(LinkMode::C, chips, sync_start, end + 16, type_byte);
+            }
+        }
+        if self.enabled(LinkMode::T) {
+            return decode_line_coded(LinkMode::T, chips, start, end);
+        }
+        None
+    }
+}
+
+/// Scan packed bytes with the default configuration (all modes, both polarities,
+/// CRC-valid frames only).
+pub fn scan_bitstream(raw: &[u8]) -> Vec<DetectedFrame> {
+    BitstreamScanner::default().scan(raw)
+}
+
+/// Tally CRC-valid frames per mode. Every mode appears, in [`LinkMode::ALL`] order.
+pub fn summarize_modes(frames: &[DetectedFrame]) -> Vec<ModeSummary> {
+    LinkMode::ALL
+        .iter()
+        .map(|&mode| {
+            let valid = frames.iter().filter(|f| f.mode == mode && f.link.crc_ok);
+            let meters: BTreeSet<u32> = valid.clone().map(|f| f.link.device_address).collect();
+            ModeSummary {
+                mode,
+                frames: valid.count(),
+                meters: meters.into_iter().collect(),
+            }
+        })
+        .collect()
+}
+
+#[inline]
+fn mask(len: usize) -> u32 {
+    (1u32 << len) - 1
+}
+
+/// Read 8 NRZ chips starting at `pos` as a byte (MSB-first).
+fn nrz_byte(chips: &[u8], pos: usize) -> u8 {
+    chips[pos..pos + 8]
+        .iter()
+        .fold(0u8, |acc, &c| (acc << 1) | (c & 1))
+}
+
+/// Total link-layer bytes after the type byte, from the type byte and L-field.
+fn frame_len_after_type(type_byte: u8, l_field: u8) -> Option<usize> {
+    match packet_size(&[type_byte, l_field]) {
+        n if n > 1 => Some(n as usize - 1),
+        _ => None,
+    }
+}
+
+fn finish(
+    mode: LinkMode,
+    start: usize,
+    symbol_errors: usize,
+    raw: Vec<u8>,
+    frame_end: usize,
+) -> Option<(DetectedFrame, usize)> {
+    let link = decode_mode_c(&raw).ok()?;
+    Some((
+        DetectedFrame {
+            mode,
+            bit_offset: start,
+            inverted: false,
+            symbol_errors,
+            raw,
+            link,
+        },
+        frame_end,
+    ))
+}
+
+/// Decode an NRZ (mode C / N) frame whose L-field begins at chip `data`.
+fn decode_nrz(
+    mode: LinkMode,
+    chips: &[u8],
+    start: usize,
+    data: usize,
+    type_byte: u8,
+) -> Option<(DetectedFrame, usize)> {
+    if data + 8 > chips.len() {
+        return None;
+    }
+    let n = frame_len_after_type(type_byte, nrz_byte(chips, data))?;
+    let frame_end = data + n * 8;
+    if frame_end > chips.len() {
+        return None;
+    }
+    let mut raw = Vec::with_capacity(n + 1);
+    raw.push(type_byte);
+    raw.extend((0..n).map(|k| nrz_byte(chips, data + k * 8)));
+    finish(mode, start, 0, raw, frame_end)
+}
+
+/// Decode a 3-out-of-6 (mode T) or Manchester (mode S) frame — always format A — whose
+/// L-field begins at chip `data`.
+fn decode_line_coded(
+    mode: LinkMode,
+    chips: &[u8],
+    start: usize,
+    data: usize,
+) -> Option<(DetectedFrame, usize)> {
+    let per_byte = mode.chips_per_byte();
+    let decode = |span: &[u8]| match mode {
+        LinkMode::T => decode_3of6(span),
+        _ => decode_manchester(span),
+    };
+    if data + per_byte > chips.len() {
+        return None;
+    }
+    let (l, l_errors) = decode(&chips[data..data + per_byte]);
+    if l_errors > 0 {
+        return None; // an unreadable L-field gives no frame boundary to work with
+    }
+    let n = frame_len_after_type(sync::A_NORM, l[0])?;
+    let frame_end = data + n * per_byte;
+    if frame_end > chips.len() {
+        return None;
+    }
+    let (bytes, symbol_errors) = decode(&chips[data..frame_end]);
+    let mut raw = Vec::with_capacity(n + 1);
+    raw.push(sync::A_NORM);
+    raw.extend(bytes);
+    finish(mode, start, symbol_errors, raw, frame_end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::crc::calculate_wmbus_crc;
+    use crate::wmbus::line_code::{encode_3of6, encode_manchester};
+
+    /// Real, complete Type B frame from meter 74644444 (KAM), CI=0x8D.
+    const REAL_TYPE_B: &str =
+        "3d25442d2c444464741b168d208d3048a121f6597959d56873b609a439b99d58531a8a726d9f0c";
+
+    /// A format-A link frame (without the type byte) with valid block CRCs.
+    fn format_a(payload: &[u8]) -> Vec<u8> {
+        let mut header = vec![(9 + payload.len()) as u8, 0x44, 0x2D, 0x2C];
+        header.extend_from_slice(&[0x78, 0x56, 0x34, 0x12, 0x1B, 0x07]);
+        let mut out = header.clone();
+        out.extend_from_slice(&calculate_wmbus_crc(&header).to_be_bytes());
+        for block in payload.chunks(16) {
+            out.extend_from_slice(block);
+            out.extend_from_slice(&calculate_wmbus_crc(block).to_be_bytes());
+        }
+        out
+    }
+
+    fn payload() -> Vec<u8> {
+        (0..20u8).map(|b| b.wrapping_mul(37) ^ 0x5A).collect()
+    }
+
+    fn preamble(pairs: usize) -> Vec<u8> {
+        (0..pairs).flat_map(|_| [0, 1]).collect()
+    }
+
+    fn sync_chips(value: u32, len: usize) -> Vec<u8> {
+        (0..len).rev().map(|k| ((value >> k) & 1) as u8).collect()
+    }
+
+    /// Deterministic filler chips (LCG), so frames land amid noise.
+    fn noise(n: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..n)
+            .map(|_| {
+                x = x.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (x >> 31) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_t_mode_frame_at_odd_offset() {
+        let frame = format_a(&payload());
+        let mut chips = noise(37, 1);
+        let sync_at = chips.len() + 40;
+        chips.extend(preamble(20));
+        chips.extend(sync_chips(T_SYNC, T_SYNC_LEN));
+        chips.extend(encode_3of6(&frame));
+        chips.extend(noise(50, 2));
+
+        let found = BitstreamScanner::default().scan_chips(&chips);
+        assert_eq!(found.len(), 1);
+        let f = &found[0];
+        assert_eq!(f.mode, LinkMode::T);
+        assert_eq!(f.bit_offset, sync_at);
+        assert!(!f.inverted);
+        assert_eq!(f.symbol_errors, 0);
+        assert_eq!(f.link.device_address, 12_345_678);
+        assert_eq!(f.link.payload, payload());
+    }
+
+    #[test]
+    fn finds_s_mode_frame() {
+        let frame = format_a(&payload());
+        let mut chips = noise(11, 3);
+        chips.extend(preamble(30));
+        chips.extend(sync_chips(S_SYNC, S_SYNC_LEN));
+        chips.extend(encode_manchester(&frame));
+
+        let found = BitstreamScanner::default().scan_chips(&chips);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].mode, LinkMode::S);
+        assert!(found[0].link.crc_ok);
+    }
+
+    #[test]
+    fn finds_real_c_frame_in_inverted_stream() {
+        let frame = hex::decode(REAL_TYPE_B).unwrap();
+        let mut chips = noise(5, 4);
+        chips.extend(preamble(8));
+        chips.extend(bytes_to_chips(&[0x54, 0x3D, 0x54]));
+        chips.extend(bytes_to_chips(&frame));
+        let inverted: Vec<u8> = chips.iter().map(|c| c ^ 1).collect();
+
+        let found = BitstreamScanner::default().scan_chips(&inverted);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].mode, LinkMode::C);
+        assert!(found[0].inverted);
+        assert_eq!(found[0].raw, frame);
+        assert_eq!(found[0].bit_offset, 5 + 16);
+    }
+
+    #[test]
+    fn finds_n_mode_type_b_frame() {
+        let frame = hex::decode(REAL_TYPE_B).unwrap();
+        let mut chips = preamble(8);
+        chips.extend(sync_chips(N_SYNC_B, N_SYNC_LEN));
+        chips.extend(bytes_to_chips(&frame[1..])); // N carries no type byte on air
+
+        let found = scan_bitstream(&crate::wmbus::line_code::chips_to_bytes(&chips));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].mode, LinkMode::N);
+        assert_eq!(found[0].raw, frame);
+    }
+
+    #[test]
+    fn reports_symbol_errors_and_crc_failures_when_asked() {
+        let frame = format_a(&payload());
+        let mut chips = preamble(20);
+        chips.extend(sync_chips(T_SYNC, T_SYNC_LEN));
+        let data_at = chips.len();
+        chips.extend(encode_3of6(&frame));
+        // Corrupt one code word inside the first data block.
+        chips[data_at + 12 * 14..data_at + 12 * 14 + 6].fill(1);
+
+        assert!(BitstreamScanner::default().scan_chips(&chips).is_empty());
+
+        let scanner = BitstreamScanner::new(ScanConfig {
+            crc_valid_only: false,
+            both_polarities: false,
+            ..ScanConfig::default()
+        });
+        let found = scanner.scan_chips(&chips);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].symbol_errors, 1);
+        assert!(!found[0].link.crc_ok);
+    }
+
+    #[test]
+    fn mode_filter_and_summary() {
+        let frame = hex::decode(REAL_TYPE_B).unwrap();
+        let mut chips = preamble(8);
+        chips.extend(bytes_to_chips(&[0x54, 0x3D, 0x54]));
+        chips.extend(bytes_to_chips(&frame));
+        chips.extend(preamble(8));
+        chips.extend(bytes_to_chips(&[0x54, 0x3D, 0x54]));
+        chips.extend(bytes_to_chips(&frame));
+
+        let found = BitstreamScanner::default().scan_chips(&chips);
+        let summary = summarize_modes(&found);
+        let c = summary.iter().find(|s| s.mode == LinkMode::C).unwrap();
+        assert_eq!(c.frames, 2);
+        assert_eq!(c.meters, vec![74_644_444]);
+        assert!(summary
+            .iter()
+            .filter(|s| s.mode != LinkMode::C)
+            .all(|s| s.frames == 0));
+
+        let t_only = BitstreamScanner::new(ScanConfig {
+            modes: vec![LinkMode::T],
+            ..ScanConfig::default()
+        });
+        assert!(t_only.scan_chips(&chips).is_empty());
+    }
+
+    #[test]
+    fn noise_yields_nothing() {
+        let chips = noise(200_000, 0x1234_5678);
+        assert!(BitstreamScanner::default().scan_chips(&chips).is_empty());
+    }
+}
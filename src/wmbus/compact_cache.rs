@@ -1,8 +1,22 @@
 //! # Compact Frame LRU Cache for wM-Bus
 //!
 //! This module implements an LRU (Least Recently Used) cache for compact frames
-//! according to OMS specification. Compact frames (CI=0x79) use a 2-byte signature
-//! to reference cached device information, reducing transmission overhead.
+//! according to OMS specification. Compact frames (CI=0x79) leave out the DIF/VIF
+//! headers of the data records and carry only the data values, preceded by two
+//! CRC-16 values (both little-endian):
+//!
+//! | bytes | field                                                           |
+//! |-------|-----------------------------------------------------------------|
+//! | 2     | *format signature* — CRC over the DIB/VIB header sequence       |
+//! | 2     | *full data CRC* — CRC over the complete records (DIB/VIB + data) |
+//! | n     | data values, in record order                                    |
+//!
+//! To decode them the receiver learns the record layout ([`FormatTemplate`]) from
+//! the meter's full frames (CI=0x78, 0x7A or 0x72), caches it under the meter's
+//! address and the format signature, and re-inserts the headers into later compact
+//! frames from the same meter. The full data CRC then confirms that the layout
+//! matched. [`FrameDecoder::with_compact_cache`](crate::wmbus::frame_decode::FrameDecoder::with_compact_cache)
+//! does this for every decoded frame.
 //!
 //! ## Cache Features
 //!
@@ -27,24 +41,291 @@
 //! #     last_seen: Instant::now(),
 //! #     last_seen_unix: 0,
 //! #     access_count: 0,
+//! #     template: None,
 //! # };
 //!
 //! // Store device info with signature
 //! cache.insert(0xABCD, device_info);
 //!
-//! // Retrieve device info by signature
-//! if let Some(info) = cache.get(0xABCD) {
+//! // Retrieve device info by meter and signature
+//! if let Some(info) = cache.lookup(0x2C2D, 0x12345678, 0xABCD) {
 //!     // Use cached device information
 //!     assert_eq!(info.device_address, 0x12345678);
 //! }
 //! ```
+//!
+//! Learning from a full frame and expanding a compact one:
+//!
+//! ```rust
+//! use mbus_rs::wmbus::compact_cache::{compact_frame_payload, CompactFrameCache};
+//! use mbus_rs::wmbus::frame_decode::FrameType;
+//! use mbus_rs::wmbus::mode_c::WMBusLinkFrame;
+//!
+//! // Two records: 32-bit volume (04 13) and 16-bit flow temperature (02 5A).
+//! let records = [0x04, 0x13, 0x39, 0x30, 0x00, 0x00, 0x02, 0x5A, 0xD2, 0x00];
+//! let link = |payload: Vec<u8>| WMBusLinkFrame {
+//!     frame_type: FrameType::TypeA,
+//!     control_field: 0x44,
+//!     manufacturer_id: 0x2C2D,
+//!     device_address: 12345678,
+//!     address_bytes: [0x78, 0x56, 0x34, 0x12],
+//!     version: 0x1B,
+//!     device_type: 0x16,
+//!     payload,
+//!     crc_ok: true,
+//! };
+//!
+//! let cache = CompactFrameCache::new(256);
+//! let full = [&[0x78][..], &records[..]].concat();
+//! let signature = cache.learn_full_frame(&link(full)).unwrap();
+//!
+//! let compact = [vec![0x79], compact_frame_payload(&records).unwrap()].concat();
+//! let expanded = cache.expand_compact_frame(&link(compact)).unwrap();
+//! assert_eq!(expanded.signature, signature);
+//! assert_eq!(expanded.records, records);
+//! assert_eq!(expanded.parse_records().unwrap().len(), 2);
+//! ```
 
+use crate::constants::{
+    MBUS_DIB_DIF_EXTENSION_BIT, MBUS_DIB_DIF_IDLE_FILLER, MBUS_DIB_DIF_MANUFACTURER_SPECIFIC,
+    MBUS_DIB_DIF_MORE_RECORDS_FOLLOW, MBUS_DIB_VIF_EXTENSION_BIT, MBUS_DIB_VIF_WITHOUT_EXTENSION,
+};
+use crate::error::MBusError;
+use crate::payload::record::{parse_variable_record_consumed, MBusRecord};
+use crate::wmbus::crc::calculate_wmbus_crc;
+use crate::wmbus::mode_c::WMBusLinkFrame;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// CI field: full frame, long transport layer header (12 bytes).
+pub const CI_FULL_FRAME_LONG_HEADER: u8 = 0x72;
+/// CI field: full frame, no transport layer header.
+pub const CI_FULL_FRAME_NO_HEADER: u8 = 0x78;
+/// CI field: compact frame, no transport layer header.
+pub const CI_COMPACT_FRAME: u8 = 0x79;
+/// CI field: full frame, short transport layer header (4 bytes).
+pub const CI_FULL_FRAME_SHORT_HEADER: u8 = 0x7A;
+
+/// Maximum DIFE/VIFE chain length, as accepted by the record parser.
+const MAX_EXTENSIONS: usize = 10;
+
+/// Errors from learning or expanding compact frames
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CompactFrameError {
+    /// The CI field is neither a supported full frame nor a compact frame
+    #[error("Unsupported CI field for compact frame handling: 0x{0:02X}")]
+    UnsupportedCi(u8),
+    /// The frame ends inside the transport header or the compact frame prefix
+    #[error("Frame too short")]
+    Truncated,
+    /// The data records are still encrypted; decrypt the payload first
+    #[error("Data records are encrypted")]
+    Encrypted,
+    /// A data record could not be parsed (offset into the record data)
+    #[error("Invalid data record at offset {0}")]
+    InvalidRecord(usize),
+    /// No template has been learned from this meter for this format signature yet
+    #[error("Unknown format signature 0x{0:04X}")]
+    UnknownSignature(u16),
+    /// The expanded records do not match the frame's full data CRC
+    #[error("Full data CRC mismatch: expected 0x{expected:04X}, calculated 0x{calculated:04X}")]
+    DataCrcMismatch { expected: u16, calculated: u16 },
+}
+
+/// Record layout of a meter's full frame: the DIB/VIB header of every data record,
+/// in order, without the data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatTemplate {
+    /// Raw header bytes (DIF, DIFEs, VIF, VIFEs) per record
+    pub headers: Vec<Vec<u8>>,
+}
+
+impl FormatTemplate {
+    /// Learn the layout from an unencrypted data record block
+    ///
+    /// Idle fillers (0x2F) are skipped.
+    pub fn learn(records: &[u8]) -> Result<Self, CompactFrameError> {
+        split_records(records).map(|split| split.template)
+    }
+
+    /// The header sequence the format signature is calculated over
+    pub fn format_bytes(&self) -> Vec<u8> {
+        self.headers.concat()
+    }
+
+    /// The format signature of this layout
+    pub fn signature(&self) -> u16 {
+        format_signature(&self.format_bytes())
+    }
+
+    /// Re-insert the headers into the data values of a compact frame
+    ///
+    /// Returns the complete record block (DIB/VIB + data per record). Trailing idle
+    /// fillers after the last value are ignored.
+    pub fn expand(&self, values: &[u8]) -> Result<Vec<u8>, CompactFrameError> {
+        let mut records = Vec::with_capacity(values.len() + self.format_bytes().len());
+        let mut pos = 0;
+        for header in &self.headers {
+            let mut record = header.clone();
+            record.extend_from_slice(&values[pos..]);
+            let (_, consumed) = parse_variable_record_consumed(&record)
+                .map_err(|_| CompactFrameError::InvalidRecord(pos))?;
+            records.extend_from_slice(&record[..consumed]);
+            pos += consumed.saturating_sub(header.len());
+        }
+        if values[pos..].iter().any(|&b| b != MBUS_DIB_DIF_IDLE_FILLER) {
+            return Err(CompactFrameError::InvalidRecord(pos));
+        }
+        Ok(records)
+    }
+}
+
+/// Records expanded from a compact frame
+#[derive(Debug, Clone)]
+pub struct ExpandedFrame {
+    /// Format signature the template was found under
+    pub signature: u16,
+    /// Complete record block (DIB/VIB + data), as a full frame would carry it
+    pub records: Vec<u8>,
+    /// Cache entry of the meter the template was learned from
+    pub device: CachedDeviceInfo,
+}
+
+impl ExpandedFrame {
+    /// Application payload of the equivalent full frame (CI=0x78 followed by the records)
+    pub fn full_frame_payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(self.records.len() + 1);
+        payload.push(CI_FULL_FRAME_NO_HEADER);
+        payload.extend_from_slice(&self.records);
+        payload
+    }
+
+    /// Parse the expanded records
+    pub fn parse_records(&self) -> Result<Vec<MBusRecord>, MBusError> {
+        let mut out = Vec::new();
+        let mut remaining = &self.records[..];
+        while !remaining.is_empty() {
+            let (record, consumed) = parse_variable_record_consumed(remaining)?;
+            out.push(record);
+            remaining = &remaining[consumed..];
+        }
+        Ok(out)
+    }
+}
+
+/// Format signature: CRC-16 (EN 13757) over the DIB/VIB header sequence
+pub fn format_signature(format_bytes: &[u8]) -> u16 {
+    calculate_wmbus_crc(format_bytes)
+}
+
+/// Full data CRC: CRC-16 (EN 13757) over the complete record block
+pub fn full_data_crc(records: &[u8]) -> u16 {
+    calculate_wmbus_crc(records)
+}
+
+/// Build the compact frame application data (without the CI byte) for a full
+/// record block: format signature, full data CRC, then the data values
+pub fn compact_frame_payload(records: &[u8]) -> Result<Vec<u8>, CompactFrameError> {
+    let split = split_records(records)?;
+    let mut payload = Vec::with_capacity(split.values.len() + 4);
+    payload.extend_from_slice(&split.template.signature().to_le_bytes());
+    payload.extend_from_slice(&full_data_crc(&split.records).to_le_bytes());
+    payload.extend_from_slice(&split.values);
+    Ok(payload)
+}
+
+/// A record block taken apart into its layout and its data values
+struct SplitRecords {
+    template: FormatTemplate,
+    values: Vec<u8>,
+    /// The records without idle fillers
+    records: Vec<u8>,
+}
+
+fn split_records(block: &[u8]) -> Result<SplitRecords, CompactFrameError> {
+    let mut split = SplitRecords {
+        template: FormatTemplate {
+            headers: Vec::new(),
+        },
+        values: Vec::new(),
+        records: Vec::new(),
+    };
+    let mut pos = 0;
+    while pos < block.len() {
+        if block[pos] == MBUS_DIB_DIF_IDLE_FILLER {
+            pos += 1;
+            continue;
+        }
+        let rest = &block[pos..];
+        let header_len = record_header_len(rest).ok_or(CompactFrameError::InvalidRecord(pos))?;
+        let (_, consumed) = parse_variable_record_consumed(rest)
+            .map_err(|_| CompactFrameError::InvalidRecord(pos))?;
+        if consumed < header_len {
+            return Err(CompactFrameError::InvalidRecord(pos));
+        }
+        split.template.headers.push(rest[..header_len].to_vec());
+        split.values.extend_from_slice(&rest[header_len..consumed]);
+        split.records.extend_from_slice(&rest[..consumed]);
+        pos += consumed;
+    }
+    Ok(split)
+}
+
+/// Length of the DIB/VIB header at the start of `record`, mirroring the record parser
+fn record_header_len(record: &[u8]) -> Option<usize> {
+    let dif = *record.first()?;
+    if dif == MBUS_DIB_DIF_MANUFACTURER_SPECIFIC || dif == MBUS_DIB_DIF_MORE_RECORDS_FOLLOW {
+        return Some(1);
+    }
+    let mut pos = 1;
+    let mut extensions = 0;
+    let mut ext = dif & MBUS_DIB_DIF_EXTENSION_BIT != 0;
+    while ext && extensions < MAX_EXTENSIONS {
+        ext = record.get(pos)? & MBUS_DIB_DIF_EXTENSION_BIT != 0;
+        pos += 1;
+        extensions += 1;
+    }
+    let vif = *record.get(pos)?;
+    pos += 1;
+    if vif & MBUS_DIB_VIF_WITHOUT_EXTENSION == 0x7C {
+        pos += 1 + *record.get(pos)? as usize;
+    }
+    extensions = 0;
+    let mut ext = vif & MBUS_DIB_VIF_EXTENSION_BIT != 0;
+    while ext && extensions < MAX_EXTENSIONS {
+        ext = record.get(pos)? & MBUS_DIB_VIF_EXTENSION_BIT != 0;
+        pos += 1;
+        extensions += 1;
+    }
+    (pos <= record.len()).then_some(pos)
+}
+
+/// The unencrypted record block of a full frame's application payload (after the CI)
+fn full_frame_records(ci: u8, apl: &[u8]) -> Result<&[u8], CompactFrameError> {
+    let header_len = match ci {
+        CI_FULL_FRAME_NO_HEADER => return Ok(apl),
+        CI_FULL_FRAME_SHORT_HEADER => 4,
+        CI_FULL_FRAME_LONG_HEADER => 12,
+        _ => return Err(CompactFrameError::UnsupportedCi(ci)),
+    };
+    if apl.len() < header_len {
+        return Err(CompactFrameError::Truncated);
+    }
+    // Configuration word: the last two bytes of the short header (ACC, ST, CW).
+    let config_word = u16::from_le_bytes([apl[header_len - 2], apl[header_len - 1]]);
+    let security_mode = (config_word >> 8) & 0x1F;
+    let records = &apl[header_len..];
+    // A decrypted payload starts with the 2F 2F verification bytes.
+    if security_mode != 0 && !records.starts_with(&[MBUS_DIB_DIF_IDLE_FILLER; 2]) {
+        return Err(CompactFrameError::Encrypted);
+    }
+    Ok(records)
+}
 
 /// Device information cached for compact frames
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +346,9 @@ pub struct CachedDeviceInfo {
     pub last_seen_unix: u64,
     /// Number of times accessed
     pub access_count: u64,
+    /// Record layout learned from the device's full frame
+    #[serde(default)]
+    pub template: Option<FormatTemplate>,
 }
 
 /// LRU cache for compact frame device information
@@ -74,12 +358,31 @@ pub struct CompactFrameCache {
     inner: Arc<Mutex<CacheInner>>,
 }
 
+/// A template belongs to one meter: two meters sharing a layout share the signature but
+/// not their device information
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct CacheKey {
+    manufacturer_id: u16,
+    device_address: u32,
+    signature: u16,
+}
+
+impl CacheKey {
+    fn of(signature: u16, info: &CachedDeviceInfo) -> Self {
+        Self {
+            manufacturer_id: info.manufacturer_id,
+            device_address: info.device_address,
+            signature,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct CacheInner {
-    /// Device info indexed by signature
-    devices: HashMap<u16, CachedDeviceInfo>,
+    /// Device info indexed by meter and signature
+    devices: HashMap<CacheKey, CachedDeviceInfo>,
     /// LRU queue tracking access order (most recent at back)
-    lru_queue: VecDeque<u16>,
+    lru_queue: VecDeque<CacheKey>,
     /// Maximum cache size
     max_size: usize,
     /// Cache statistics
@@ -104,7 +407,11 @@ pub struct CacheStats {
 /// Serializable cache data structure
 #[derive(Debug, Serialize, Deserialize)]
 struct CacheData {
-    /// Device information map
+    /// Signature and device information per cached template
+    #[serde(default)]
+    entries: Vec<(u16, CachedDeviceInfo)>,
+    /// Device information by signature, as written before templates were kept per meter
+    #[serde(default, skip_serializing)]
     devices: HashMap<u16, CachedDeviceInfo>,
     /// Cache statistics
     stats: CacheStats,
@@ -129,76 +436,117 @@ impl CompactFrameCache {
         }
     }
 
+    /// Create cache from device address (generate signature)
+    /// Generates a 2-byte signature from device address using CRC-like algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_address` - 4-byte device address
+    ///
+    /// # Returns
+    ///
+    /// * 2-byte signature for compact frame
+    #[deprecated(
+        note = "compact frames carry a format signature over the record headers; use `format_signature` or `FormatTemplate::signature`"
+    )]
+    pub fn generate_signature(device_address: u32) -> u16 {
+        // Simple CRC-like signature generation
+        let bytes = device_address.to_le_bytes();
+        let mut sig = 0u16;
+        for &byte in &bytes {
+            sig = sig.wrapping_add(byte as u16);
+            sig = sig.rotate_left(1); // Rotate left
+            sig ^= 0xA5A5; // XOR with pattern
+        }
+        sig
+    }
+
     /// Insert or update device information in cache
     ///
+    /// The entry is stored for the meter named in `info` (manufacturer and address), so
+    /// meters sharing a record layout keep separate entries.
+    ///
     /// # Arguments
     ///
     /// * `signature` - 2-byte compact frame signature
     /// * `info` - Device information to cache
     pub fn insert(&self, signature: u16, info: CachedDeviceInfo) {
+        let key = CacheKey::of(signature, &info);
         let mut inner = self.inner.lock().unwrap();
         inner.stats.insertions += 1;
 
         // Remove from LRU queue if already present
-        if let Some(pos) = inner.lru_queue.iter().position(|&s| s == signature) {
+        if let Some(pos) = inner.lru_queue.iter().position(|&k| k == key) {
             inner.lru_queue.remove(pos);
         }
 
         // Check if eviction needed
-        if inner.devices.len() >= inner.max_size && !inner.devices.contains_key(&signature) {
+        if inner.devices.len() >= inner.max_size && !inner.devices.contains_key(&key) {
             // Evict least recently used
-            if let Some(lru_signature) = inner.lru_queue.pop_front() {
-                inner.devices.remove(&lru_signature);
+            if let Some(lru_key) = inner.lru_queue.pop_front() {
+                inner.devices.remove(&lru_key);
                 inner.stats.evictions += 1;
             }
         }
 
         // Insert/update device info
-        inner.devices.insert(signature, info);
+        inner.devices.insert(key, info);
         // Add to back of LRU queue (most recent)
-        inner.lru_queue.push_back(signature);
+        inner.lru_queue.push_back(key);
     }
 
-    /// Retrieve device information by signature
+    /// Retrieve a meter's device information by signature
     ///
     /// Updates LRU order and access statistics.
     ///
     /// # Arguments
     ///
+    /// * `manufacturer_id` - Manufacturer of the meter (M-field)
+    /// * `device_address` - Address of the meter (A-field)
     /// * `signature` - 2-byte compact frame signature
     ///
     /// # Returns
     ///
     /// * `Some(info)` - Cached device information if found
-    /// * `None` - If signature not in cache
-    pub fn get(&self, signature: u16) -> Option<CachedDeviceInfo> {
+    /// * `None` - If the meter has no entry under this signature
+    pub fn lookup(
+        &self,
+        manufacturer_id: u16,
+        device_address: u32,
+        signature: u16,
+    ) -> Option<CachedDeviceInfo> {
         let mut inner = self.inner.lock().unwrap();
-        inner.stats.lookups += 1;
-
-        // Check if device exists and update it
-        if inner.devices.contains_key(&signature) {
-            inner.stats.hits += 1;
-
-            // Update device info
-            let info = inner.devices.get_mut(&signature).unwrap();
-            info.access_count += 1;
-            info.last_seen = Instant::now();
-            let result = info.clone();
+        inner.lookup(CacheKey {
+            manufacturer_id,
+            device_address,
+            signature,
+        })
+    }
 
-            // Update LRU order
-            if let Some(pos) = inner.lru_queue.iter().position(|&s| s == signature) {
-                inner.lru_queue.remove(pos);
+    /// Retrieve device information by signature alone
+    ///
+    /// Returns the most recently used entry under `signature`, which may belong to any
+    /// meter with that record layout.
+    #[deprecated(note = "signatures are shared by meters with the same layout; use `lookup`")]
+    pub fn get(&self, signature: u16) -> Option<CachedDeviceInfo> {
+        let mut inner = self.inner.lock().unwrap();
+        let key = inner
+            .lru_queue
+            .iter()
+            .rev()
+            .find(|k| k.signature == signature)
+            .copied();
+        match key {
+            Some(key) => inner.lookup(key),
+            None => {
+                inner.stats.lookups += 1;
+                inner.stats.misses += 1;
+                None
             }
-            inner.lru_queue.push_back(signature);
-
-            Some(result)
-        } else {
-            inner.stats.misses += 1;
-            None
         }
     }
 
-    /// Remove device from cache
+    /// Remove every meter's entry under a signature
     ///
     /// # Arguments
     ///
@@ -206,17 +554,14 @@ impl CompactFrameCache {
     ///
     /// # Returns
     ///
-    /// * `true` if device was removed
+    /// * `true` if an entry was removed
     /// * `false` if signature not found
     pub fn remove(&self, signature: u16) -> bool {
         let mut inner = self.inner.lock().unwrap();
-
-        // Remove from LRU queue
-        if let Some(pos) = inner.lru_queue.iter().position(|&s| s == signature) {
-            inner.lru_queue.remove(pos);
-        }
-
-        inner.devices.remove(&signature).is_some()
+        inner.lru_queue.retain(|k| k.signature != signature);
+        let before = inner.devices.len();
+        inner.devices.retain(|k, _| k.signature != signature);
+        inner.devices.len() != before
     }
 
     /// Clear all cached entries
@@ -262,18 +607,18 @@ impl CompactFrameCache {
         let now = Instant::now();
         let mut removed = 0;
 
-        // Collect signatures of stale entries
-        let stale_signatures: Vec<u16> = inner
+        // Collect keys of stale entries
+        let stale_keys: Vec<CacheKey> = inner
             .devices
             .iter()
             .filter(|(_, info)| now.duration_since(info.last_seen) > max_age)
-            .map(|(&sig, _)| sig)
+            .map(|(&key, _)| key)
             .collect();
 
         // Remove stale entries
-        for signature in stale_signatures {
-            inner.devices.remove(&signature);
-            if let Some(pos) = inner.lru_queue.iter().position(|&s| s == signature) {
+        for key in stale_keys {
+            inner.devices.remove(&key);
+            if let Some(pos) = inner.lru_queue.iter().position(|&k| k == key) {
                 inner.lru_queue.remove(pos);
             }
             removed += 1;
@@ -332,16 +677,18 @@ impl CompactFrameCache {
 
         // Prepare serializable data
         let cache_data = CacheData {
-            devices: inner
-                .devices
+            entries: inner
+                .lru_queue
                 .iter()
-                .map(|(&sig, info)| {
+                .filter_map(|key| inner.devices.get(key).map(|info| (key.signature, info)))
+                .map(|(sig, info)| {
                     let mut info_with_unix = info.clone();
                     // Convert Instant to Unix timestamp for serialization
                     info_with_unix.last_seen_unix = info.last_seen.elapsed().as_secs();
                     (sig, info_with_unix)
                 })
                 .collect(),
+            devices: HashMap::new(),
             stats: inner.stats,
             max_size: inner.max_size,
         };
@@ -370,13 +717,15 @@ impl CompactFrameCache {
         let cache = Self::new(cache_data.max_size);
         let mut inner = cache.inner.lock().unwrap();
 
-        // Restore devices with current timestamp
+        // Restore devices with current timestamp, least recently used first
         let now = Instant::now();
-        for (sig, mut info) in cache_data.devices {
+        for (sig, mut info) in cache_data.devices.into_iter().chain(cache_data.entries) {
             // Restore Instant from Unix timestamp
             info.last_seen = now - Duration::from_secs(info.last_seen_unix);
-            inner.devices.insert(sig, info);
-            inner.lru_queue.push_back(sig);
+            let key = CacheKey::of(sig, &info);
+            if inner.devices.insert(key, info).is_none() {
+                inner.lru_queue.push_back(key);
+            }
         }
 
         inner.stats = cache_data.stats;
@@ -385,29 +734,100 @@ impl CompactFrameCache {
         Ok(cache)
     }
 
-    /// Create cache from device address (generate signature)
+    /// Learn the record layout of a full frame
     ///
-    /// Generates a 2-byte signature from device address using CRC-like algorithm.
-    ///
-    /// # Arguments
-    ///
-    /// * `device_address` - 4-byte device address
+    /// `frame.payload` must start with CI 0x78, 0x7A or 0x72 and hold unencrypted
+    /// records (decrypt first). The template is cached for the sending meter under its
+    /// format signature, together with the frame's device information.
     ///
     /// # Returns
     ///
-    /// * 2-byte signature for compact frame
-    pub fn generate_signature(device_address: u32) -> u16 {
-        // Simple CRC-like signature generation
-        let bytes = device_address.to_le_bytes();
-        let mut sig = 0u16;
+    /// * The format signature the template was stored under
+    pub fn learn_full_frame(&self, frame: &WMBusLinkFrame) -> Result<u16, CompactFrameError> {
+        let ci = frame.ci().ok_or(CompactFrameError::Truncated)?;
+        let template = FormatTemplate::learn(full_frame_records(ci, &frame.payload[1..])?)?;
+        let signature = template.signature();
+        self.insert(
+            signature,
+            CachedDeviceInfo {
+                manufacturer_id: frame.manufacturer_id,
+                device_address: frame.device_address,
+                version: frame.version,
+                device_type: frame.device_type,
+                last_seen: Instant::now(),
+                last_seen_unix: 0,
+                access_count: 0,
+                template: Some(template),
+            },
+        );
+        Ok(signature)
+    }
 
-        for &byte in &bytes {
-            sig = sig.wrapping_add(byte as u16);
-            sig = sig.rotate_left(1); // Rotate left
-            sig ^= 0xA5A5; // XOR with pattern
+    /// Expand a compact frame (CI=0x79) into full records
+    ///
+    /// Looks up the template the sending meter's full frames taught under the frame's
+    /// format signature and checks the expanded records against the frame's full data
+    /// CRC. On
+    /// [`CompactFrameError::UnknownSignature`] the full frame can be requested with
+    /// [`build_full_frame_request`](Self::build_full_frame_request).
+    pub fn expand_compact_frame(
+        &self,
+        frame: &WMBusLinkFrame,
+    ) -> Result<ExpandedFrame, CompactFrameError> {
+        match frame.ci() {
+            Some(CI_COMPACT_FRAME) => {}
+            Some(ci) => return Err(CompactFrameError::UnsupportedCi(ci)),
+            None => return Err(CompactFrameError::Truncated),
+        }
+        let apl = &frame.payload[1..];
+        if apl.len() < 4 {
+            return Err(CompactFrameError::Truncated);
         }
+        let signature = u16::from_le_bytes([apl[0], apl[1]]);
+        let expected = u16::from_le_bytes([apl[2], apl[3]]);
+
+        let device = self
+            .lookup(frame.manufacturer_id, frame.device_address, signature)
+            .ok_or(CompactFrameError::UnknownSignature(signature))?;
+        let records = device
+            .template
+            .as_ref()
+            .ok_or(CompactFrameError::UnknownSignature(signature))?
+            .expand(&apl[4..])?;
+
+        let calculated = full_data_crc(&records);
+        if calculated != expected {
+            return Err(CompactFrameError::DataCrcMismatch {
+                expected,
+                calculated,
+            });
+        }
+        Ok(ExpandedFrame {
+            signature,
+            records,
+            device,
+        })
+    }
+}
 
-        sig
+impl CacheInner {
+    fn lookup(&mut self, key: CacheKey) -> Option<CachedDeviceInfo> {
+        self.stats.lookups += 1;
+        let Some(info) = self.devices.get_mut(&key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        info.access_count += 1;
+        info.last_seen = Instant::now();
+        let result = info.clone();
+
+        // Update LRU order
+        if let Some(pos) = self.lru_queue.iter().position(|&k| k == key) {
+            self.lru_queue.remove(pos);
+        }
+        self.lru_queue.push_back(key);
+        Some(result)
     }
 }
 
@@ -434,13 +854,14 @@ mod tests {
             last_seen: Instant::now(),
             last_seen_unix: 0,
             access_count: 0,
+            template: None,
         };
 
         cache.insert(0x0001, info1.clone());
         assert_eq!(cache.size(), 1);
 
         // Retrieve entry
-        let retrieved = cache.get(0x0001).unwrap();
+        let retrieved = cache.lookup(0x1234, 0xAABBCCDD, 0x0001).unwrap();
         assert_eq!(retrieved.manufacturer_id, 0x1234);
         assert_eq!(retrieved.access_count, 1); // Incremented on get
 
@@ -464,6 +885,7 @@ mod tests {
             last_seen: Instant::now(),
             last_seen_unix: 0,
             access_count: 0,
+            template: None,
         };
 
         // Fill cache
@@ -476,9 +898,9 @@ mod tests {
         assert_eq!(cache.size(), 2);
 
         // First item should be evicted
-        assert!(cache.get(0x0001).is_none());
-        assert!(cache.get(0x0002).is_some());
-        assert!(cache.get(0x0003).is_some());
+        assert!(cache.lookup(0x1234, 0, 0x0001).is_none());
+        assert!(cache.lookup(0x1234, 0, 0x0002).is_some());
+        assert!(cache.lookup(0x1234, 0, 0x0003).is_some());
 
         // Check eviction count
         let stats = cache.stats();
@@ -497,6 +919,7 @@ mod tests {
             last_seen: Instant::now(),
             last_seen_unix: 0,
             access_count: 0,
+            template: None,
         };
 
         // Insert two items
@@ -504,28 +927,196 @@ mod tests {
         cache.insert(0x0002, info.clone());
 
         // Access first item to make it most recent
-        cache.get(0x0001);
+        cache.lookup(0x1234, 0, 0x0001);
 
         // Insert third item should evict second (least recent)
         cache.insert(0x0003, info.clone());
 
-        assert!(cache.get(0x0001).is_some()); // Still present
-        assert!(cache.get(0x0002).is_none()); // Evicted
-        assert!(cache.get(0x0003).is_some()); // New item
+        assert!(cache.lookup(0x1234, 0, 0x0001).is_some()); // Still present
+        assert!(cache.lookup(0x1234, 0, 0x0002).is_none()); // Evicted
+        assert!(cache.lookup(0x1234, 0, 0x0003).is_some()); // New item
+    }
+
+    /// Multical21-style layout: info codes (DIFE/VIFE chain), volume, target volume
+    /// (storage 1), flow temperature, and a variable-length string.
+    const RECORDS: [u8; 30] = [
+        0x02, 0xFF, 0x20, 0x71, 0x09, // 02 FF20: info codes
+        0x04, 0x13, 0x44, 0x33, 0x22, 0x11, // 04 13: volume
+        0x44, 0x13, 0x40, 0x30, 0x20, 0x10, // 44 13: volume, storage 1
+        0x81, 0x01, 0x5A, 0x12, // 81 01 5A: flow temperature, tariff chain
+        0x0D, 0xFD, 0x0C, 0x05, b'K', b'a', b'm', b'1', b'2', // 0D FD0C: model string
+    ];
+
+    fn link_frame(payload: Vec<u8>) -> WMBusLinkFrame {
+        WMBusLinkFrame {
+            frame_type: crate::wmbus::frame_decode::FrameType::TypeA,
+            control_field: 0x44,
+            manufacturer_id: 0x2C2D,
+            device_address: 12345678,
+            address_bytes: [0x78, 0x56, 0x34, 0x12],
+            version: 0x1B,
+            device_type: 0x16,
+            payload,
+            crc_ok: true,
+        }
+    }
+
+    fn compact_frame(records: &[u8]) -> WMBusLinkFrame {
+        let mut payload = vec![CI_COMPACT_FRAME];
+        payload.extend(compact_frame_payload(records).unwrap());
+        link_frame(payload)
     }
 
     #[test]
+    fn test_format_signature_covers_headers_only() {
+        let template = FormatTemplate::learn(&RECORDS).unwrap();
+        assert_eq!(template.headers.len(), 5);
+        assert_eq!(template.headers[0], vec![0x02, 0xFF, 0x20]);
+        assert_eq!(template.headers[3], vec![0x81, 0x01, 0x5A]);
+        assert_eq!(
+            template.format_bytes(),
+            vec![0x02, 0xFF, 0x20, 0x04, 0x13, 0x44, 0x13, 0x81, 0x01, 0x5A, 0x0D, 0xFD, 0x0C]
+        );
+        assert_eq!(
+            template.signature(),
+            calculate_wmbus_crc(&template.format_bytes())
+        );
+
+        // Other values, same layout: same signature. Other layout: different one.
+        let mut other_values = RECORDS;
+        other_values[7] = 0x99;
+        assert_eq!(
+            FormatTemplate::learn(&other_values).unwrap().signature(),
+            template.signature()
+        );
+        let mut other_layout = RECORDS;
+        other_layout[6] = 0x14;
+        assert_ne!(
+            FormatTemplate::learn(&other_layout).unwrap().signature(),
+            template.signature()
+        );
+    }
+
+    #[test]
+    fn test_compact_frame_expansion() {
+        let cache = CompactFrameCache::new(16);
+
+        // Full frame with short header (ACC, status, no security) and idle fillers.
+        let mut full = vec![
+            CI_FULL_FRAME_SHORT_HEADER,
+            0x2A,
+            0x00,
+            0x00,
+            0x00,
+            0x2F,
+            0x2F,
+        ];
+        full.extend_from_slice(&RECORDS);
+        let signature = cache.learn_full_frame(&link_frame(full)).unwrap();
+
+        let compact = compact_frame(&RECORDS);
+        assert_eq!(
+            u16::from_le_bytes([compact.payload[1], compact.payload[2]]),
+            signature
+        );
+        // 4 prefix bytes + values only.
+        assert_eq!(compact.payload.len(), 1 + 4 + RECORDS.len() - 13);
+
+        let expanded = cache.expand_compact_frame(&compact).unwrap();
+        assert_eq!(expanded.records, RECORDS.to_vec());
+        assert_eq!(expanded.device.device_address, 12345678);
+        assert_eq!(expanded.full_frame_payload()[0], CI_FULL_FRAME_NO_HEADER);
+
+        let parsed = expanded.parse_records().unwrap();
+        assert_eq!(parsed.len(), 5);
+        assert_eq!(parsed[1].drh.vib.vif, 0x13);
+        assert_eq!(&parsed[1].data[..4], &[0x44, 0x33, 0x22, 0x11]);
+        assert_eq!(parsed[4].data_len, 5);
+    }
+
+    #[test]
+    fn test_templates_are_kept_per_meter() {
+        let cache = CompactFrameCache::new(16);
+        let mut full = vec![CI_FULL_FRAME_NO_HEADER];
+        full.extend_from_slice(&RECORDS);
+        cache.learn_full_frame(&link_frame(full)).unwrap();
+
+        // Another meter with the same layout has not taught the cache anything yet.
+        let mut other = compact_frame(&RECORDS);
+        other.device_address = 87654321;
+        let signature = u16::from_le_bytes([other.payload[1], other.payload[2]]);
+        assert_eq!(
+            cache.expand_compact_frame(&other).unwrap_err(),
+            CompactFrameError::UnknownSignature(signature)
+        );
+        assert_eq!(
+            cache
+                .expand_compact_frame(&compact_frame(&RECORDS))
+                .unwrap()
+                .device
+                .device_address,
+            12345678
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
     fn test_signature_generation() {
         let sig1 = CompactFrameCache::generate_signature(0x12345678);
         let sig2 = CompactFrameCache::generate_signature(0x12345678);
         let sig3 = CompactFrameCache::generate_signature(0x87654321);
-
         // Same input should give same signature
         assert_eq!(sig1, sig2);
         // Different input should give different signature
         assert_ne!(sig1, sig3);
     }
 
+    #[test]
+    fn test_compact_frame_errors() {
+        let cache = CompactFrameCache::new(16);
+        let compact = compact_frame(&RECORDS);
+        let signature = u16::from_le_bytes([compact.payload[1], compact.payload[2]]);
+
+        // Not learned yet: the caller should request the full frame.
+        assert_eq!(
+            cache.expand_compact_frame(&compact).unwrap_err(),
+            CompactFrameError::UnknownSignature(signature)
+        );
+
+        let mut full = vec![CI_FULL_FRAME_NO_HEADER];
+        full.extend_from_slice(&RECORDS);
+        cache.learn_full_frame(&link_frame(full)).unwrap();
+
+        // A corrupted value no longer matches the full data CRC.
+        let mut corrupted = compact.clone();
+        corrupted.payload[6] ^= 0x01;
+        assert!(matches!(
+            cache.expand_compact_frame(&corrupted),
+            Err(CompactFrameError::DataCrcMismatch { .. })
+        ));
+
+        // Missing value bytes.
+        let mut truncated = compact.clone();
+        truncated.payload.truncate(10);
+        assert!(matches!(
+            cache.expand_compact_frame(&truncated),
+            Err(CompactFrameError::InvalidRecord(_))
+        ));
+
+        // Full frames cannot be expanded, and encrypted ones cannot be learned.
+        assert_eq!(
+            cache
+                .expand_compact_frame(&link_frame(vec![CI_FULL_FRAME_NO_HEADER]))
+                .unwrap_err(),
+            CompactFrameError::UnsupportedCi(CI_FULL_FRAME_NO_HEADER)
+        );
+        let encrypted = link_frame(vec![0x7A, 0x2A, 0x00, 0x20, 0x05, 0x91, 0x33, 0x4E, 0x01]);
+        assert_eq!(
+            cache.learn_full_frame(&encrypted).unwrap_err(),
+            CompactFrameError::Encrypted
+        );
+    }
+
     #[test]
     fn test_cache_hit_rate() {
         let cache = CompactFrameCache::new(10);
@@ -538,18 +1129,19 @@ mod tests {
             last_seen: Instant::now(),
             last_seen_unix: 0,
             access_count: 0,
+            template: None,
         };
 
         cache.insert(0x0001, info);
 
         // 3 hits
-        cache.get(0x0001);
-        cache.get(0x0001);
-        cache.get(0x0001);
+        cache.lookup(0x1234, 0, 0x0001);
+        cache.lookup(0x1234, 0, 0x0001);
+        cache.lookup(0x1234, 0, 0x0001);
 
         // 2 misses
-        cache.get(0x0002);
-        cache.get(0x0003);
+        cache.lookup(0x1234, 0, 0x0002);
+        cache.lookup(0x1234, 0, 0x0003);
 
         let hit_rate = cache.hit_rate();
         assert!((hit_rate - 0.6).abs() < 0.01); // 3/5 = 0.6
@@ -599,14 +1191,15 @@ mod tests {
             last_seen: Instant::now(),
             last_seen_unix: 0,
             access_count: 10,
+            template: Some(FormatTemplate::learn(&RECORDS).unwrap()),
         };
 
         cache1.insert(0x1234, info.clone());
         cache1.insert(0x5678, info.clone());
 
         // Generate some stats
-        cache1.get(0x1234);
-        cache1.get(0x9999); // Miss
+        cache1.lookup(0x5678, 0x11223344, 0x1234);
+        cache1.lookup(0x5678, 0x11223344, 0x9999); // Miss
 
         // Save cache
         cache1.save_to_file(&cache_path).unwrap();
@@ -620,11 +1213,12 @@ mod tests {
         // Verify loaded cache has same data
         assert_eq!(cache2.size(), 2);
 
-        let loaded_info = cache2.get(0x1234).unwrap();
+        let loaded_info = cache2.lookup(0x5678, 0x11223344, 0x1234).unwrap();
         assert_eq!(loaded_info.manufacturer_id, 0x5678);
         assert_eq!(loaded_info.device_address, 0x11223344);
         assert_eq!(loaded_info.version, 2);
         assert_eq!(loaded_info.device_type, 0x07);
+        assert_eq!(loaded_info.template, info.template);
 
         // Stats should be preserved (with updated counts from get operations)
         let stats = cache2.stats();
@@ -634,4 +1228,22 @@ mod tests {
         // Clean up
         fs::remove_file(cache_path).ok();
     }
+
+    #[test]
+    fn test_cache_loads_signature_keyed_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cache_path = temp_dir.path().join("cache.json");
+        std::fs::write(
+            &cache_path,
+            r#"{"devices": {"4660": {"manufacturer_id": 22136, "device_address": 287454020,
+                "version": 2, "device_type": 7, "last_seen_unix": 0, "access_count": 1}},
+                "stats": {"insertions": 1, "lookups": 0, "hits": 0, "misses": 0,
+                "evictions": 0}, "max_size": 5}"#,
+        )
+        .unwrap();
+
+        let cache = CompactFrameCache::load_from_file(&cache_path).unwrap();
+        assert_eq!(cache.size(), 1);
+        assert!(cache.lookup(0x5678, 0x11223344, 0x1234).is_some());
+    }
 }
@@ -51,6 +51,11 @@ use std::sync::Arc;
 use thiserror::Error;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+/// OMS key derivation constant of the encryption key Kenc (meter to other).
+pub const OMS_KDF_ENC: u8 = 0x00;
+/// OMS key derivation constant of the MAC key Kmac (meter to other).
+pub const OMS_KDF_MAC: u8 = 0x01;
+
 /// Enhanced encryption errors with specific failure types
 #[derive(Error, Debug, Clone, PartialEq)]
 pub enum CryptoError {
@@ -413,6 +418,162 @@ impl WMBusCrypto {
         Ok(encrypted_frame)
     }
 
+    /// Encrypt a TPL payload with OMS security mode 5 (OMS Vol. 2, 9.2.4): AES-128-CBC
+    /// with the meter key itself and the IV `M‖A‖V‖T‖ACC×8`. The payload must already
+    /// be padded (with `2F` fillers) to whole AES blocks.
+    pub fn encrypt_oms_mode5(
+        &mut self,
+        plaintext: &[u8],
+        device_info: &DeviceInfo,
+    ) -> Result<Vec<u8>, CryptoError> {
+        if !plaintext.len().is_multiple_of(16) {
+            return Err(CryptoError::InvalidDataLength {
+                block_size: 16,
+                actual: plaintext.len(),
+            });
+        }
+        let key = self.master_key.clone();
+        let iv = self.build_oms_mode5_iv(device_info);
+        self.aes_cbc_encrypt(&key, plaintext, &iv)
+    }
+
+    /// Decrypt a TPL payload encrypted with OMS security mode 5; the inverse of
+    /// [`encrypt_oms_mode5`](Self::encrypt_oms_mode5). The `2F 2F` check and fillers
+    /// are left in place.
+    pub fn decrypt_oms_mode5(
+        &mut self,
+        ciphertext: &[u8],
+        device_info: &DeviceInfo,
+    ) -> Result<Vec<u8>, CryptoError> {
+        if !ciphertext.len().is_multiple_of(16) {
+            return Err(CryptoError::InvalidDataLength {
+                block_size: 16,
+                actual: ciphertext.len(),
+            });
+        }
+        let key = self.master_key.clone();
+        let iv = self.build_oms_mode5_iv(device_info);
+        self.aes_cbc_decrypt_blocks(&key, ciphertext, &iv)
+    }
+
+    /// Derive an OMS message key (OMS Vol. 2, 9.5.3): AES-CMAC under the meter key of
+    /// the derivation constant ([`OMS_KDF_ENC`] or [`OMS_KDF_MAC`]), the AFL message
+    /// counter, the meter ID and `07` padding.
+    #[cfg(feature = "crypto")]
+    pub fn derive_oms_message_key(
+        &self,
+        constant: u8,
+        message_counter: u32,
+        device_id: u32,
+    ) -> Result<AesKey, CryptoError> {
+        let mut input = [0x07; 16];
+        input[0] = constant;
+        input[1..5].copy_from_slice(&message_counter.to_le_bytes());
+        input[5..9].copy_from_slice(&device_id.to_le_bytes());
+        AesKey::from_bytes(&self.aes_cmac(&self.master_key, &input)?)
+    }
+
+    /// AES-128-CMAC of `message` under `key`.
+    #[cfg(feature = "crypto")]
+    pub fn aes_cmac(&self, key: &AesKey, message: &[u8]) -> Result<[u8; 16], CryptoError> {
+        use aes::Aes128;
+        use cmac::{Cmac, Mac};
+
+        let mut mac = Cmac::<Aes128>::new_from_slice(key.as_bytes()).map_err(|_| {
+            CryptoError::InvalidKeyLength {
+                expected: 16,
+                actual: key.as_bytes().len(),
+            }
+        })?;
+        mac.update(message);
+        Ok(mac.finalize().into_bytes().into())
+    }
+
+    /// Encrypt a TPL payload with OMS security mode 7 (OMS Vol. 2, 9.2.5): AES-128-CBC
+    /// with the derived message key `kenc` and a zero IV. The payload must already be
+    /// padded (with `2F` fillers) to whole AES blocks.
+    #[cfg(feature = "crypto")]
+    pub fn encrypt_oms_mode7(
+        &mut self,
+        kenc: &AesKey,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        if !plaintext.len().is_multiple_of(16) {
+            return Err(CryptoError::InvalidDataLength {
+                block_size: 16,
+                actual: plaintext.len(),
+            });
+        }
+        self.aes_cbc_encrypt(kenc, plaintext, &[0; 16])
+    }
+
+    /// Decrypt a TPL payload encrypted with OMS security mode 7; the inverse of
+    /// [`encrypt_oms_mode7`](Self::encrypt_oms_mode7). The MAC in the AFL is not
+    /// checked here.
+    #[cfg(feature = "crypto")]
+    pub fn decrypt_oms_mode7(
+        &mut self,
+        kenc: &AesKey,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        if !ciphertext.len().is_multiple_of(16) {
+            return Err(CryptoError::InvalidDataLength {
+                block_size: 16,
+                actual: ciphertext.len(),
+            });
+        }
+        self.aes_cbc_decrypt_blocks(kenc, ciphertext, &[0; 16])
+    }
+
+    /// Encrypt a TPL payload with OMS security mode 9 (OMS Vol. 2, 9.2.7): AES-128-GCM
+    /// with the derived message key `kenc`, authenticating `aad`. The nonce is
+    /// `M‖A‖counter` of `device_info`, whose `access_number` carries the AFL message
+    /// counter. Returns the ciphertext followed by the full 16-byte tag.
+    #[cfg(feature = "crypto")]
+    pub fn encrypt_oms_mode9(
+        &mut self,
+        kenc: &AesKey,
+        plaintext: &[u8],
+        aad: &[u8],
+        device_info: &DeviceInfo,
+    ) -> Result<Vec<u8>, CryptoError> {
+        let iv = self.build_gcm_iv(device_info)?;
+        let (mut ciphertext, tag) = self.aes_gcm_encrypt(kenc, plaintext, aad, &iv)?;
+        ciphertext.extend(tag);
+        Ok(ciphertext)
+    }
+
+    /// Decrypt and authenticate a TPL payload encrypted with OMS security mode 9; the
+    /// inverse of [`encrypt_oms_mode9`](Self::encrypt_oms_mode9).
+    #[cfg(feature = "crypto")]
+    pub fn decrypt_oms_mode9(
+        &mut self,
+        kenc: &AesKey,
+        sealed: &[u8],
+        aad: &[u8],
+        device_info: &DeviceInfo,
+    ) -> Result<Vec<u8>, CryptoError> {
+        let Some(split) = sealed.len().checked_sub(16) else {
+            return Err(CryptoError::InvalidFrame {
+                reason: format!("{} bytes cannot hold a GCM tag", sealed.len()),
+            });
+        };
+        let iv = self.build_gcm_iv(device_info)?;
+        let (ciphertext, tag) = sealed.split_at(split);
+        self.aes_gcm_decrypt(kenc, ciphertext, aad, &iv, tag)
+    }
+
+    /// OMS mode 5 IV: manufacturer, address, version and type as on the link layer,
+    /// then the access number eight times.
+    fn build_oms_mode5_iv(&self, device_info: &DeviceInfo) -> [u8; 16] {
+        let mut iv = [device_info.access_number.unwrap_or(0) as u8; 16];
+        iv[0..2].copy_from_slice(&device_info.manufacturer.to_le_bytes());
+        iv[2..6].copy_from_slice(&device_info.device_id.to_le_bytes());
+        iv[6] = device_info.version;
+        iv[7] = device_info.device_type;
+        iv
+    }
+
     /// Find CI field offset in frame
     fn find_ci_offset(&self, frame: &[u8]) -> Result<usize, CryptoError> {
         // Standard wM-Bus frame structure:
@@ -708,6 +869,24 @@ impl WMBusCrypto {
         Ok(result)
     }
 
+    /// AES-128 CBC decryption of whole blocks, leaving any padding in place
+    fn aes_cbc_decrypt_blocks(
+        &mut self,
+        key: &AesKey,
+        ciphertext: &[u8],
+        iv: &[u8; 16],
+    ) -> Result<Vec<u8>, CryptoError> {
+        let mut prev_block = *iv;
+        let mut result = Vec::with_capacity(ciphertext.len());
+        for chunk in ciphertext.chunks_exact(16) {
+            let block: [u8; 16] = chunk.try_into().unwrap();
+            let decrypted = self.aes_decrypt_block(key, &block)?;
+            result.extend(decrypted.iter().zip(prev_block).map(|(d, p)| d ^ p));
+            prev_block = block;
+        }
+        Ok(result)
+    }
+
     /// AES-128 ECB decryption
     fn aes_ecb_decrypt(&mut self, key: &AesKey, ciphertext: &[u8]) -> Result<Vec<u8>, CryptoError> {
         let mut result = Vec::new();
@@ -941,7 +1120,18 @@ impl WMBusCrypto {
     }
 
     /// Extract access number from frame (for Mode 9)
+    ///
+    /// `frame` starts at the C-field (no L-field), so the CI sits at offset 9. The
+    /// transport layer access number is found through any ELL/AFL layers (see
+    /// [`FrameCounters`](crate::wmbus::replay_guard::FrameCounters)); frames without a
+    /// recognised transport header fall back to the byte at offset 10.
     pub fn extract_access_number(frame: &[u8]) -> Option<u64> {
+        if let Some((&ci, rest)) = frame.get(9..).and_then(|f| f.split_first()) {
+            let counters = crate::wmbus::replay_guard::FrameCounters::from_apl(ci, rest);
+            if let Some(access) = counters.tpl_access {
+                return Some(access as u64);
+            }
+        }
         // In wM-Bus frames, access number is typically at offset 10
         // This varies by frame type, so we provide a simple extraction
         if frame.len() > 10 {
@@ -0,0 +1,588 @@
+//! # Telegram Deduplication
+//!
+//! Meters send every telegram several times, and repeaters re-send it once more with the
+//! hop counter in the configuration word raised. This module folds those copies into one
+//! [`DedupedTelegram`] per reading before it reaches consumers.
+//!
+//! ## Duplicate Key
+//!
+//! Two copies are the same telegram when they share
+//!
+//! - manufacturer and address,
+//! - access number (TPL access number, or the ELL access number for ELL frames), and
+//! - a hash of the CI and payload, with the repeater bits (ELL relayed bit, TPL hop
+//!   counter below any ELL/AFL) masked out so a repeated copy hashes like the original.
+//!
+//! ## Windowing
+//!
+//! The first copy opens a window of [`DedupConfig::window`]. Every copy seen inside the
+//! window is recorded as a [`ReceptionPath`] (receiver, hop count, RSSI), and the copy with
+//! the best RSSI is kept. When the window closes the telegram is released once, carrying
+//! all its paths and the number of suppressed duplicates.
+//!
+//! Time is passed in explicitly, so the same [`Deduplicator`] serves the live receive
+//! stream ([`WMBusHandle::recv_deduplicated`](crate::wmbus::handle::WMBusHandle::recv_deduplicated))
+//! and replay sources that carry recorded timestamps ([`deduplicate`]).
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use mbus_rs::wmbus::dedup::{DedupConfig, Deduplicator, TelegramCopy};
+//! use mbus_rs::wmbus::frame::{parse_wmbus_frame, WMBusFrame};
+//! use tokio::time::{Duration, Instant};
+//!
+//! let raw = WMBusFrame::build(0x44, 0x2C2D, 0x12345678, 0x1B, 0x16, 0x7A, &[0x2A, 0, 0, 0]);
+//! let frame = parse_wmbus_frame(&raw).unwrap();
+//!
+//! let mut dedup = Deduplicator::new(DedupConfig::default());
+//! let t0 = Instant::now();
+//! dedup.push(TelegramCopy::new(frame.clone(), -80, "gw-1", t0));
+//! dedup.push(TelegramCopy::new(frame, -65, "gw-2", t0 + Duration::from_millis(40)));
+//!
+//! let released = dedup.drain_expired(t0 + Duration::from_secs(10));
+//! assert_eq!(released.len(), 1);
+//! assert_eq!(released[0].rssi_dbm, -65);
+//! assert_eq!(released[0].paths.len(), 2);
+//! assert_eq!(dedup.stats().suppressed, 1);
+//! ```
+
+use crate::wmbus::frame::WMBusFrame;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use tokio::time::{Duration, Instant};
+
+/// Deduplication settings
+#[derive(Debug, Clone)]
+pub struct DedupConfig {
+    /// How long after the first copy further copies are folded into it
+    pub window: Duration,
+    /// Maximum telegrams held open at once; the oldest is released early beyond this
+    ///
+    /// The default holds every telegram of about 10,000 meters sending every few
+    /// seconds within the default window. Beyond that, telegrams are released before
+    /// their window closes and late copies are reported as new telegrams.
+    pub max_pending: usize,
+    /// Receiver name recorded for copies received by the local radio
+    pub receiver_id: String,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(2),
+            max_pending: 16_384,
+            receiver_id: "local".to_string(),
+        }
+    }
+}
+
+/// One copy of a telegram as received
+#[derive(Debug, Clone)]
+pub struct TelegramCopy {
+    /// The parsed frame
+    pub frame: WMBusFrame,
+    /// RSSI of this copy in dBm
+    pub rssi_dbm: i16,
+    /// Receiver (gateway, SDR, replay source) that saw this copy
+    pub receiver: String,
+    /// When the copy was received
+    pub received_at: Instant,
+}
+
+impl TelegramCopy {
+    /// Create a copy record
+    pub fn new(
+        frame: WMBusFrame,
+        rssi_dbm: i16,
+        receiver: impl Into<String>,
+        received_at: Instant,
+    ) -> Self {
+        Self {
+            frame,
+            rssi_dbm,
+            receiver: receiver.into(),
+            received_at,
+        }
+    }
+}
+
+/// One way a telegram reached us
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceptionPath {
+    /// Receiver that saw the copy
+    pub receiver: String,
+    /// Repeater hops (0 = heard directly from the meter)
+    pub hop_count: u8,
+    /// RSSI of the copy in dBm
+    pub rssi_dbm: i16,
+}
+
+/// A telegram with its duplicates folded in
+#[derive(Debug, Clone)]
+pub struct DedupedTelegram {
+    /// The best-RSSI copy
+    pub frame: WMBusFrame,
+    /// RSSI of the kept copy in dBm
+    pub rssi_dbm: i16,
+    /// Every copy's path, in arrival order
+    pub paths: Vec<ReceptionPath>,
+    /// Number of copies suppressed (paths minus one)
+    pub duplicates: u32,
+    /// Arrival time of the first copy
+    pub first_seen: Instant,
+}
+
+impl DedupedTelegram {
+    /// True if at least one copy came through a repeater
+    pub fn via_repeater(&self) -> bool {
+        self.paths.iter().any(|p| p.hop_count > 0)
+    }
+}
+
+/// Deduplication counters
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Copies pushed
+    pub received: u64,
+    /// Telegrams released
+    pub released: u64,
+    /// Copies folded into an earlier one
+    pub suppressed: u64,
+    /// Copies that arrived through a repeater
+    pub repeated: u64,
+    /// Telegrams released before their window closed because `max_pending` was reached
+    pub early_releases: u64,
+}
+
+/// Identity of a telegram across its copies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DedupKey {
+    /// Manufacturer ID (M-field)
+    pub manufacturer_id: u16,
+    /// Device address (A-field)
+    pub device_address: u32,
+    /// TPL or ELL access number, if the frame has one
+    pub access_number: Option<u8>,
+    /// Hash of CI and payload with the repeater bits masked out
+    pub payload_hash: u64,
+}
+
+impl DedupKey {
+    /// Compute the key of a frame
+    pub fn of(frame: &WMBusFrame) -> Self {
+        let payload = frame.repeater_neutral_payload();
+        let mut hasher = DefaultHasher::new();
+        frame.control_info.hash(&mut hasher);
+        payload.hash(&mut hasher);
+
+        Self {
+            manufacturer_id: frame.manufacturer_id,
+            device_address: frame.device_address,
+            access_number: frame.access_number().or_else(|| ell_access_number(frame)),
+            payload_hash: hasher.finish(),
+        }
+    }
+}
+
+/// ELL access number (second ELL byte) for CI 0x8C–0x8F
+fn ell_access_number(frame: &WMBusFrame) -> Option<u8> {
+    match frame.control_info {
+        0x8C..=0x8F => frame.payload.get(1).copied(),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    best: TelegramCopy,
+    paths: Vec<ReceptionPath>,
+    first_seen: Instant,
+    /// Matches this telegram's entry in [`Deduplicator::order`]
+    seq: u64,
+}
+
+/// Folds duplicate telegram copies inside a time window
+#[derive(Debug)]
+pub struct Deduplicator {
+    config: DedupConfig,
+    pending: HashMap<DedupKey, Pending>,
+    /// Open telegrams in order of their first copy
+    ///
+    /// Entries of released telegrams are skipped when they reach the front; the
+    /// front entry is always open.
+    order: VecDeque<(DedupKey, u64)>,
+    next_seq: u64,
+    stats: DedupStats,
+}
+
+impl Deduplicator {
+    /// Create a deduplicator
+    pub fn new(config: DedupConfig) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+            order: VecDeque::new(),
+            next_seq: 0,
+            stats: DedupStats::default(),
+        }
+    }
+
+    /// The active configuration
+    pub fn config(&self) -> &DedupConfig {
+        &self.config
+    }
+
+    /// Counters so far
+    pub fn stats(&self) -> DedupStats {
+        self.stats
+    }
+
+    /// Number of telegrams whose window is still open
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Add a copy. Returns a telegram released early if `max_pending` was exceeded.
+    pub fn push(&mut self, copy: TelegramCopy) -> Option<DedupedTelegram> {
+        self.stats.received += 1;
+        let path = ReceptionPath {
+            receiver: copy.receiver.clone(),
+            hop_count: copy.frame.hop_count(),
+            rssi_dbm: copy.rssi_dbm,
+        };
+        if path.hop_count > 0 {
+            self.stats.repeated += 1;
+        }
+
+        let key = DedupKey::of(&copy.frame);
+        if let Some(entry) = self.pending.get_mut(&key) {
+            // A copy outside the window is a new telegram; release the old one first.
+            if copy.received_at.duration_since(entry.first_seen) <= self.config.window {
+                self.stats.suppressed += 1;
+                entry.paths.push(path);
+                if copy.rssi_dbm > entry.best.rssi_dbm {
+                    entry.best = copy;
+                }
+                return None;
+            }
+        }
+
+        let mut released = self.pending.contains_key(&key).then(|| self.release(&key));
+        if self.pending.len() >= self.config.max_pending.max(1) {
+            if let Some(&(oldest, _)) = self.order.front() {
+                self.stats.early_releases += 1;
+                released = Some(self.release(&oldest));
+            }
+        }
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.order.push_back((key, seq));
+        self.pending.insert(
+            key,
+            Pending {
+                first_seen: copy.received_at,
+                best: copy,
+                paths: vec![path],
+                seq,
+            },
+        );
+        released
+    }
+
+    /// When the next open window closes, if any
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.order
+            .front()
+            .and_then(|(key, _)| self.pending.get(key))
+            .map(|p| p.first_seen + self.config.window)
+    }
+
+    /// Release the oldest telegram if its window has closed at `now`
+    pub fn pop_expired(&mut self, now: Instant) -> Option<DedupedTelegram> {
+        let deadline = self.next_deadline()?;
+        if now < deadline {
+            return None;
+        }
+        let (key, _) = *self.order.front()?;
+        Some(self.release(&key))
+    }
+
+    /// Release every telegram whose window has closed at `now`, oldest first
+    pub fn drain_expired(&mut self, now: Instant) -> Vec<DedupedTelegram> {
+        std::iter::from_fn(|| self.pop_expired(now)).collect()
+    }
+
+    /// Release everything still open (end of a replay, shutdown)
+    pub fn flush(&mut self) -> Vec<DedupedTelegram> {
+        std::iter::from_fn(|| {
+            let (key, _) = *self.order.front()?;
+            Some(self.release(&key))
+        })
+        .collect()
+    }
+
+    fn release(&mut self, key: &DedupKey) -> DedupedTelegram {
+        let pending = self
+            .pending
+            .remove(key)
+            .expect("released key must have a pending entry");
+        // Drop entries of released telegrams from the front, so the front stays open.
+        while let Some((key, seq)) = self.order.front() {
+            if self.pending.get(key).is_some_and(|p| p.seq == *seq) {
+                break;
+            }
+            self.order.pop_front();
+        }
+        self.stats.released += 1;
+        DedupedTelegram {
+            frame: pending.best.frame,
+            rssi_dbm: pending.best.rssi_dbm,
+            duplicates: pending.paths.len() as u32 - 1,
+            paths: pending.paths,
+            first_seen: pending.first_seen,
+        }
+    }
+}
+
+/// Deduplicate a replayed sequence of copies (in arrival order)
+///
+/// Returns the released telegrams in order of their first copy, and the counters.
+pub fn deduplicate<I>(copies: I, config: DedupConfig) -> (Vec<DedupedTelegram>, DedupStats)
+where
+    I: IntoIterator<Item = TelegramCopy>,
+{
+    let mut dedup = Deduplicator::new(config);
+    let mut out = Vec::new();
+    for copy in copies {
+        out.extend(dedup.drain_expired(copy.received_at));
+        out.extend(dedup.push(copy));
+    }
+    out.extend(dedup.flush());
+    (out, dedup.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::frame::parse_wmbus_frame;
+
+    /// Short-header frame: ACC, status, configuration word (hop counter in bits 1..0).
+    fn frame(address: u32, access: u8, hops: u8, value: u8) -> WMBusFrame {
+        let payload = [
+            access,
+            0x00,
+            hops & 0x03,
+            0x00,
+            0x2F,
+            0x2F,
+            0x02,
+            0x13,
+            value,
+            0x00,
+        ];
+        parse_wmbus_frame(&WMBusFrame::build(
+            0x44, 0x2C2D, address, 0x1B, 0x16, 0x7A, &payload,
+        ))
+        .unwrap()
+    }
+
+    fn at(t0: Instant, ms: u64) -> Instant {
+        t0 + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn repeated_copy_has_the_same_key() {
+        assert_eq!(
+            DedupKey::of(&frame(1, 7, 0, 5)),
+            DedupKey::of(&frame(1, 7, 1, 5))
+        );
+        assert_ne!(
+            DedupKey::of(&frame(1, 7, 0, 5)),
+            DedupKey::of(&frame(1, 8, 0, 5))
+        );
+        assert_ne!(
+            DedupKey::of(&frame(1, 7, 0, 5)),
+            DedupKey::of(&frame(1, 7, 0, 6))
+        );
+        assert_ne!(
+            DedupKey::of(&frame(1, 7, 0, 5)),
+            DedupKey::of(&frame(2, 7, 0, 5))
+        );
+        assert_eq!(DedupKey::of(&frame(1, 7, 0, 5)).access_number, Some(7));
+    }
+
+    #[test]
+    fn relayed_copies_below_ell_have_the_same_key() {
+        // ELL I (CC, ACC) then a short header TPL (ACC, ST, CW) and one record.
+        let ell = |cc: u8, hops: u8| {
+            let payload = [
+                cc, 0x5A, 0x7A, 0x11, 0x00, hops, 0x00, 0x02, 0x13, 0x05, 0x00,
+            ];
+            parse_wmbus_frame(&WMBusFrame::build(
+                0x44, 0x2C2D, 1, 0x1B, 0x16, 0x8C, &payload,
+            ))
+            .unwrap()
+        };
+        let original = ell(0x20, 0);
+        // A repeater sets the CC relayed bit, and may raise the TPL hop counter.
+        let relayed = ell(0x20 | 0x10, 0);
+        let relayed_hop = ell(0x20 | 0x10, 1);
+
+        assert_eq!(DedupKey::of(&original), DedupKey::of(&relayed));
+        assert_eq!(DedupKey::of(&original), DedupKey::of(&relayed_hop));
+        assert_ne!(DedupKey::of(&original), DedupKey::of(&ell(0x24, 0)));
+        assert_eq!(
+            [
+                original.hop_count(),
+                relayed.hop_count(),
+                relayed_hop.hop_count()
+            ],
+            [0, 1, 1]
+        );
+    }
+
+    #[test]
+    fn keeps_best_rssi_and_records_paths() {
+        let t0 = Instant::now();
+        let mut dedup = Deduplicator::new(DedupConfig::default());
+        assert!(dedup
+            .push(TelegramCopy::new(frame(1, 7, 0, 5), -90, "gw-a", t0))
+            .is_none());
+        dedup.push(TelegramCopy::new(frame(1, 7, 0, 5), -70, "gw-b", at(t0, 5)));
+        dedup.push(TelegramCopy::new(
+            frame(1, 7, 1, 5),
+            -60,
+            "gw-a",
+            at(t0, 300),
+        ));
+        dedup.push(TelegramCopy::new(
+            frame(2, 7, 0, 5),
+            -80,
+            "gw-a",
+            at(t0, 400),
+        ));
+
+        // Nothing is released while the window is open.
+        assert!(dedup.drain_expired(at(t0, 1_999)).is_empty());
+        assert_eq!(dedup.next_deadline(), Some(at(t0, 2_000)));
+
+        let released = dedup.drain_expired(at(t0, 2_000));
+        assert_eq!(released.len(), 1);
+        let t = &released[0];
+        assert_eq!(t.frame.device_address, 1);
+        assert_eq!(t.rssi_dbm, -60);
+        assert_eq!(t.frame.hop_count(), 1);
+        assert_eq!(t.duplicates, 2);
+        assert!(t.via_repeater());
+        assert_eq!(
+            t.paths,
+            vec![
+                ReceptionPath {
+                    receiver: "gw-a".into(),
+                    hop_count: 0,
+                    rssi_dbm: -90
+                },
+                ReceptionPath {
+                    receiver: "gw-b".into(),
+                    hop_count: 0,
+                    rssi_dbm: -70
+                },
+                ReceptionPath {
+                    receiver: "gw-a".into(),
+                    hop_count: 1,
+                    rssi_dbm: -60
+                },
+            ]
+        );
+
+        let rest = dedup.flush();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].duplicates, 0);
+        assert_eq!(
+            dedup.stats(),
+            DedupStats {
+                received: 4,
+                released: 2,
+                suppressed: 2,
+                repeated: 1,
+                early_releases: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn copy_after_window_is_a_new_telegram() {
+        let t0 = Instant::now();
+        let copies = vec![
+            TelegramCopy::new(frame(1, 7, 0, 5), -70, "replay", t0),
+            TelegramCopy::new(frame(1, 7, 0, 5), -70, "replay", at(t0, 500)),
+            TelegramCopy::new(frame(1, 7, 0, 5), -70, "replay", at(t0, 2_500)),
+            TelegramCopy::new(frame(1, 8, 0, 5), -70, "replay", at(t0, 2_600)),
+        ];
+        let (out, stats) = deduplicate(copies, DedupConfig::default());
+        assert_eq!(out.len(), 3);
+        assert_eq!(
+            out.iter().map(|t| t.duplicates).collect::<Vec<_>>(),
+            [1, 0, 0]
+        );
+        assert_eq!(out[1].first_seen, at(t0, 2_500));
+        assert_eq!(stats.suppressed, 1);
+        assert_eq!(stats.released, 3);
+    }
+
+    #[test]
+    fn max_pending_releases_oldest_early() {
+        let t0 = Instant::now();
+        let mut dedup = Deduplicator::new(DedupConfig {
+            max_pending: 2,
+            ..DedupConfig::default()
+        });
+        assert!(dedup
+            .push(TelegramCopy::new(frame(1, 1, 0, 0), -70, "gw", t0))
+            .is_none());
+        assert!(dedup
+            .push(TelegramCopy::new(frame(2, 1, 0, 0), -70, "gw", t0))
+            .is_none());
+        let early = dedup
+            .push(TelegramCopy::new(frame(3, 1, 0, 0), -70, "gw", t0))
+            .unwrap();
+        assert_eq!(early.frame.device_address, 1);
+        assert_eq!(dedup.pending(), 2);
+        assert_eq!(dedup.stats().early_releases, 1);
+    }
+
+    #[test]
+    fn reopened_telegram_keeps_arrival_order() {
+        let t0 = Instant::now();
+        let mut dedup = Deduplicator::new(DedupConfig::default());
+        dedup.push(TelegramCopy::new(frame(1, 1, 0, 0), -70, "gw", t0));
+        dedup.push(TelegramCopy::new(
+            frame(2, 1, 0, 0),
+            -70,
+            "gw",
+            at(t0, 1_000),
+        ));
+        // Meter 1 repeats the telegram after its window: the old one is released and
+        // the new one queues behind meter 2.
+        let released = dedup
+            .push(TelegramCopy::new(
+                frame(1, 1, 0, 0),
+                -70,
+                "gw",
+                at(t0, 2_500),
+            ))
+            .unwrap();
+        assert_eq!(released.first_seen, t0);
+        assert_eq!(dedup.next_deadline(), Some(at(t0, 3_000)));
+
+        let out = dedup.drain_expired(at(t0, 3_000));
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].frame.device_address, 2);
+        let rest = dedup.flush();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].first_seen, at(t0, 2_500));
+        assert_eq!(dedup.pending(), 0);
+    }
+}
@@ -193,6 +193,107 @@ pub fn is_application_ci(ci: u8) -> bool {
     )
 }
 
+/// Length of the transport layer header that follows CI field `ci`: 4 for a short header
+/// (ACC, ST, CW), 12 for a long header (ID, M, V, T, ACC, ST, CW), 0 for no header.
+pub fn tpl_header_len(ci: u8) -> usize {
+    match ci {
+        0x5A | 0x61 | 0x65 | 0x6A | 0x6E | 0x74 | 0x7A | 0x7B | 0x7D | 0x7F | 0x8A => 4,
+        0x53 | 0x5B | 0x60 | 0x64 | 0x6B | 0x6F | 0x72 | 0x73 | 0x75 | 0x7C | 0x7E | 0x8B => 12,
+        _ => 0,
+    }
+}
+
+/// Bytes an ELL or AFL header takes after its CI, or `None` for the transport layer and
+/// for layers that cannot be seen through (encrypted ELL, truncated AFL)
+pub fn outer_layer_len(ci: u8, data: &[u8]) -> Option<usize> {
+    match ci {
+        // ELL I and III: CC, ACC [, M, A]
+        0x8C => Some(2),
+        0x8E => Some(10),
+        // ELL II and IV: CC, ACC, [M, A,] SN, payload CRC
+        0x8D | 0x8F => {
+            let sn_at = if ci == 0x8D { 2 } else { 10 };
+            match data.get(sn_at..sn_at + 4) {
+                // Session number ENC bits (31..29) clear: payload is plain text.
+                Some(sn) if sn[3] >> 5 == 0 => Some(sn_at + 6),
+                _ => None,
+            }
+        }
+        // AFL: AFL.L counts the bytes after itself.
+        0x90 => data.first().map(|&len| 1 + len as usize),
+        _ => None,
+    }
+}
+
+/// ELL communication control bit a repeater sets on frames it relays
+pub const ELL_CC_RELAYED: u8 = 0x10;
+
+/// TPL configuration word bits (low byte) holding the repeater hop counter
+pub const TPL_CW_HOP_COUNTER: u8 = 0x03;
+
+/// Where the bits a repeater changes sit in a frame's payload
+///
+/// Found by walking from the frame's CI field through ELL and AFL layers down to the
+/// transport layer, so two copies of a telegram compare equal once [`clear`](Self::clear)
+/// has been applied to both, whichever path they took.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RepeaterBits {
+    /// Payload offset of the ELL CC field
+    pub ell_cc: Option<usize>,
+    /// Payload offset of the TPL configuration word's low byte
+    pub tpl_cw: Option<usize>,
+}
+
+impl RepeaterBits {
+    /// Locate the repeater bits in the `payload` that follows CI field `ci`
+    pub fn locate(ci: u8, payload: &[u8]) -> Self {
+        let mut bits = Self::default();
+        let (mut ci, mut at) = (ci, 0);
+        loop {
+            let data = payload.get(at..).unwrap_or_default();
+            if (0x8C..=0x8F).contains(&ci) && !data.is_empty() && bits.ell_cc.is_none() {
+                bits.ell_cc = Some(at);
+            }
+            let Some(len) = outer_layer_len(ci, data) else {
+                break;
+            };
+            match data.get(len) {
+                Some(&next_ci) => (ci, at) = (next_ci, at + len + 1),
+                None => return bits,
+            }
+        }
+        bits.tpl_cw = match tpl_header_len(ci) {
+            0 => None,
+            len => Some(at + len - 2).filter(|&cw| cw < payload.len()),
+        };
+        bits
+    }
+
+    /// Clear the repeater bits in `payload`
+    pub fn clear(&self, payload: &mut [u8]) {
+        if let Some(cc) = self.ell_cc.and_then(|i| payload.get_mut(i)) {
+            *cc &= !ELL_CC_RELAYED;
+        }
+        if let Some(cw) = self.tpl_cw.and_then(|i| payload.get_mut(i)) {
+            *cw &= !TPL_CW_HOP_COUNTER;
+        }
+    }
+
+    /// Repeater hops recorded in `payload`: the TPL hop counter, or 1 when only the ELL
+    /// relayed bit is set
+    pub fn hop_count(&self, payload: &[u8]) -> u8 {
+        let tpl = self
+            .tpl_cw
+            .and_then(|i| payload.get(i))
+            .map_or(0, |cw| cw & TPL_CW_HOP_COUNTER);
+        let relayed = self
+            .ell_cc
+            .and_then(|i| payload.get(i))
+            .is_some_and(|cc| cc & ELL_CC_RELAYED != 0);
+        tpl.max(u8::from(relayed))
+    }
+}
+
 /// Heuristic used to disambiguate a full frame from a compact frame (both can carry a `0x79`
 /// at byte 2): a full frame is the right length for its L-field and has a valid application
 /// CI at the full-frame CI position (byte 10). A frame shorter than 13 bytes cannot be full.
@@ -452,6 +553,45 @@ impl WMBusFrame {
         )
     }
 
+    /// Length of the transport layer header that follows the CI field: 4 bytes for a
+    /// short header (ACC, ST, CW), 12 for a long header (ID, M, V, T, ACC, ST, CW), 0
+    /// otherwise.
+    pub fn tpl_header_len(&self) -> usize {
+        tpl_header_len(self.control_info)
+    }
+
+    /// The transport layer access number, if the frame carries a short or long header.
+    pub fn access_number(&self) -> Option<u8> {
+        match self.tpl_header_len() {
+            0 => None,
+            len => self.payload.get(len - 4).copied(),
+        }
+    }
+
+    /// The transport layer configuration word (little-endian on the wire), if present.
+    pub fn config_word(&self) -> Option<u16> {
+        match self.tpl_header_len() {
+            0 => None,
+            len => self
+                .payload
+                .get(len - 2..len)
+                .map(|cw| u16::from_le_bytes([cw[0], cw[1]])),
+        }
+    }
+
+    /// The repeater hop count: the TPL hop counter (bits 1..0 of the configuration word)
+    /// below any ELL/AFL, or 1 for a relayed ELL frame without one.
+    pub fn hop_count(&self) -> u8 {
+        RepeaterBits::locate(self.control_info, &self.payload).hop_count(&self.payload)
+    }
+
+    /// The payload with the bits repeaters change cleared, for comparing copies
+    pub fn repeater_neutral_payload(&self) -> Vec<u8> {
+        let mut payload = self.payload.clone();
+        RepeaterBits::locate(self.control_info, &self.payload).clear(&mut payload);
+        payload
+    }
+
     /// Verify the CRC of this frame
     ///
     /// Checks if the stored CRC matches the calculated CRC for the frame data.
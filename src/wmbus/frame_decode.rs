@@ -20,12 +20,19 @@
 //! A frame whose block CRCs do not all validate is reported as
 //! [`DecodeError::CrcMismatch`] rather than returned; a valid frame is returned as a
 //! [`WMBusFrame`] with `control_info` set to the CI byte and `payload` de-blocked.
+//!
+//! With a [`CompactFrameCache`] attached ([`FrameDecoder::with_compact_cache`]) the
+//! decoder learns record layouts from plain full frames and returns compact frames
+//! (CI=0x79) expanded to CI=0x78 records. A compact frame from a meter whose layout is not
+//! known yet is returned unchanged.
 
 use crate::util::{logging, IoBuffer};
+use crate::wmbus::compact_cache::{CompactFrameCache, CI_COMPACT_FRAME, CI_FULL_FRAME_NO_HEADER};
 use crate::wmbus::crc::read_crc_be;
 use crate::wmbus::frame::WMBusFrame;
 use crate::wmbus::mode_c::decode_mode_c;
 use crate::wmbus::radio::rfm69_packet::packet_size;
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Enhanced wM-Bus sync word constants for frame type detection
@@ -82,6 +89,15 @@ pub enum DecodeError {
 
     #[error("Frame processing error: {message}")]
     ProcessingError { message: String },
+
+    #[error("Frame has no application layer")]
+    NoApplicationLayer,
+
+    #[error("Frame failed its block CRC check")]
+    BlockCrcFailed,
+
+    #[error("Address {address} does not fit 8 BCD digits")]
+    AddressNotBcd { address: u32 },
 }
 
 /// Streaming frame decoder: accumulate bytes, emit decoded frames.
@@ -97,6 +113,8 @@ pub struct FrameDecoder {
     stats: DecodeStats,
     /// Throttle for error logging
     error_throttle: logging::LogThrottle,
+    /// Record layouts for expanding compact frames
+    compact_cache: Option<Arc<CompactFrameCache>>,
 }
 
 /// Statistics for frame decoding operations
@@ -109,6 +127,8 @@ pub struct DecodeStats {
     pub encryption_detected: u64,
     pub type_a_frames: u64,
     pub type_b_frames: u64,
+    pub compact_frames_expanded: u64,
+    pub compact_frames_unresolved: u64,
 }
 
 impl FrameDecoder {
@@ -120,9 +140,18 @@ impl FrameDecoder {
             expected_size: None,
             stats: DecodeStats::default(),
             error_throttle: logging::LogThrottle::new(1000, 5), // 5 errors per second
+            compact_cache: None,
         }
     }
 
+    /// Learn record layouts into `cache` and expand compact frames with it
+    ///
+    /// The cache may be shared with other decoders, e.g. one per radio.
+    pub fn with_compact_cache(mut self, cache: Arc<CompactFrameCache>) -> Self {
+        self.compact_cache = Some(cache);
+        self
+    }
+
     /// Add normalized (sync/type-byte-first) bytes to the decoder buffer.
     ///
     /// See the module-level "Input contract" note: bytes must already be normalized, as
@@ -236,7 +265,26 @@ impl FrameDecoder {
             });
         }
 
-        let control_info = link.ci().unwrap_or(0);
+        let mut control_info = link.ci().unwrap_or(0);
+        let mut payload = link.application_data().to_vec();
+        if let Some(cache) = &self.compact_cache {
+            if control_info == CI_COMPACT_FRAME {
+                match cache.expand_compact_frame(&link) {
+                    Ok(expanded) => {
+                        self.stats.compact_frames_expanded += 1;
+                        control_info = CI_FULL_FRAME_NO_HEADER;
+                        payload = expanded.records;
+                    }
+                    Err(e) => {
+                        self.stats.compact_frames_unresolved += 1;
+                        log::debug!("Compact frame left unexpanded: {e}");
+                    }
+                }
+            } else {
+                // Only plain full frames teach a layout; anything else is skipped.
+                let _ = cache.learn_full_frame(&link);
+            }
+        }
         let encrypted = matches!(control_info, 0x7A | 0x7B | 0x8A | 0x8B);
         if encrypted {
             self.stats.encryption_detected += 1;
@@ -252,7 +300,7 @@ impl FrameDecoder {
             version: link.version,
             device_type: link.device_type,
             control_info,
-            payload: link.application_data().to_vec(),
+            payload,
             crc: trailing_crc,
             encrypted,
         })
@@ -353,6 +401,45 @@ mod tests {
         assert!(decoder.try_decode_frame().expect("no error").is_some());
     }
 
+    #[test]
+    fn expands_compact_frames_with_a_shared_cache() {
+        use crate::wmbus::compact_cache::compact_frame_payload;
+        use crate::wmbus::mode_c::encode_mode_c;
+
+        // 32-bit volume (04 13) and 16-bit flow temperature (02 5A).
+        let records = [0x04, 0x13, 0x39, 0x30, 0x00, 0x00, 0x02, 0x5A, 0xD2, 0x00];
+        let encode = |ci: u8, apl: &[u8]| {
+            let mut link = vec![0x44, 0x2D, 0x2C, 0x78, 0x56, 0x34, 0x12, 0x1B, 0x16, ci];
+            link.extend_from_slice(apl);
+            encode_mode_c(FrameType::TypeA, &link).unwrap()
+        };
+        let compact = encode(CI_COMPACT_FRAME, &compact_frame_payload(&records).unwrap());
+
+        let cache = Arc::new(CompactFrameCache::new(16));
+        let mut decoder = FrameDecoder::new().with_compact_cache(cache);
+        decoder.add_bytes(&compact).unwrap();
+        let frame = decoder.try_decode_frame().unwrap().unwrap();
+        assert_eq!(frame.control_info, CI_COMPACT_FRAME);
+
+        decoder
+            .add_bytes(&encode(CI_FULL_FRAME_NO_HEADER, &records))
+            .unwrap();
+        decoder.add_bytes(&compact).unwrap();
+        decoder.try_decode_frame().unwrap().unwrap();
+        let frame = decoder.try_decode_frame().unwrap().unwrap();
+        assert_eq!(frame.control_info, CI_FULL_FRAME_NO_HEADER);
+        assert_eq!(frame.payload, records);
+
+        let stats = decoder.stats();
+        assert_eq!(
+            (
+                stats.compact_frames_expanded,
+                stats.compact_frames_unresolved
+            ),
+            (1, 1)
+        );
+    }
+
     #[test]
     fn rejects_non_wmbus_header() {
         let mut decoder = FrameDecoder::new();
@@ -4,6 +4,11 @@
 //! wireless M-Bus (wM-Bus) system. It integrates the radio driver, frame handling, and
 //! device discovery to provide a simple async API for wM-Bus communication.
 //!
+//! The handle is generic over [`RadioDriver`], so any supported radio (SX126x, RFM69, ...)
+//! gets the same background reception, device tracking and unsolicited callbacks.
+//! [`WMBusHandle::new`] builds an SX126x handle from a HAL; [`WMBusHandle::with_driver`]
+//! takes any other driver.
+//!
 //! ## Features
 //!
 //! - Automatic radio configuration for wM-Bus operation
@@ -38,23 +43,47 @@
 //! }
 //! ```
 
+use crate::wmbus::bidirectional::{
+    transmit_response, Exchange, ExchangeOutcome, GatewayCommand, PrimaryStation,
+    PrimaryStationConfig,
+};
+use crate::wmbus::bitstream::LinkMode;
+use crate::wmbus::dedup::{DedupConfig, DedupStats, DedupedTelegram, Deduplicator, TelegramCopy};
 use crate::wmbus::frame::{ParseError, WMBusFrame};
+use crate::wmbus::installation::{InstallationConfig, InstalledMeter, Installer};
+use crate::wmbus::mode_switching::NModeChannel;
 use crate::wmbus::radio::driver::{
-    DeviceErrors, DriverError, LbtConfig, LoRaRxInfo, ModeTaggedPacket, RadioStats,
+    DeviceErrors, DriverError, LbtConfig, LoRaRxInfo, ModeTaggedPacket, RadioState, RadioStats,
     RadioStatusReport, Sx126xDriver,
 };
 use crate::wmbus::radio::hal::Hal;
 use crate::wmbus::radio::irq::IrqStatus;
 use crate::wmbus::radio::modulation::PacketType;
+use crate::wmbus::radio::radio_driver::{RadioDriver, RadioDriverError, RadioMode};
+use crate::wmbus::replay_guard::{Freshness, MeterCounterState, ReplayGuard, ReplayGuardState};
+use crate::wmbus::stream::{ItemStream, FANOUT_CAPACITY};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::{mpsc, Mutex, RwLock};
-use tokio::time::{sleep, timeout, Duration, Instant};
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
+use tokio::time::{sleep, timeout, timeout_at, Duration, Instant};
+
+/// Receiver poll interval for radios whose RxDone interrupt is not wired up
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// RxDone events waiting for the receiver; an interrupt that finds the queue full is
+/// dropped and its frame is picked up by the next poll instead.
+const RX_DONE_CAPACITY: usize = 8;
+
+/// Items buffered for [`WMBusHandle::recv_item`]; once full, new items are dropped for
+/// that path (and counted by [`WMBusHandle::dropped_items`]) while subscribers still see
+/// them, so a consumer that only uses [`WMBusHandle::subscribe`] cannot grow memory.
+pub const RX_CHANNEL_CAPACITY: usize = 256;
 
 /// Type aliases for complex types to improve readability
-type FrameReceiver = Arc<RwLock<Option<mpsc::UnboundedReceiver<ReceivedItem>>>>;
-type FrameSender = mpsc::UnboundedSender<ReceivedItem>;
+type FrameReceiver = Arc<RwLock<Option<mpsc::Receiver<ReceivedItem>>>>;
+type FrameSender = mpsc::Sender<ReceivedItem>;
 type UnsolicitedCallback = Arc<dyn Fn(&WMBusFrame) + Send + Sync>;
 
 /// A received item tagged with the modem it arrived on.
@@ -71,6 +100,12 @@ pub enum ReceivedItem {
         frame: WMBusFrame,
         /// RSSI for this frame, in dBm.
         rssi_dbm: i16,
+        /// The link mode detected after reception when the radio accepts several (T1 +
+        /// C1); `None` when the receive profile fixes it.
+        link_mode: Option<LinkMode>,
+        /// The replay guard's verdict on this frame; `None` when the guard is not
+        /// enabled (see [`WMBusHandle::enable_replay_guard`]).
+        freshness: Option<Freshness>,
     },
     /// A raw LoRa payload with its receive metadata (undecoded).
     Lora {
@@ -83,6 +118,14 @@ pub enum ReceivedItem {
     },
 }
 
+/// The next RxDone edge, or never when no notifier feeds the receiver
+async fn next_rx_done(rx_done: &mut Option<mpsc::Receiver<Instant>>) -> Option<Instant> {
+    match rx_done {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
 /// Route a mode-tagged packet from the driver into a [`ReceivedItem`], or `None` if it
 /// should be dropped: a GFSK payload that fails wM-Bus parsing, or — defensively — a LoRa
 /// packet missing its metadata (which [`Sx126xDriver::process_irqs_with_mode`]'s invariant
@@ -90,13 +133,19 @@ pub enum ReceivedItem {
 fn route_packet(packet: ModeTaggedPacket) -> Option<ReceivedItem> {
     let ModeTaggedPacket {
         mode,
+        link_mode,
         payload,
         rssi_dbm,
         lora,
     } = packet;
     match mode {
         PacketType::Gfsk => match crate::wmbus::frame::parse_wmbus_frame(&payload) {
-            Ok(frame) => Some(ReceivedItem::Wmbus { frame, rssi_dbm }),
+            Ok(frame) => Some(ReceivedItem::Wmbus {
+                frame,
+                rssi_dbm,
+                link_mode,
+                freshness: None,
+            }),
             Err(e) => {
                 log::debug!("Dropping unparseable wM-Bus frame: {e:?}");
                 None
@@ -127,6 +176,9 @@ pub enum WMBusError {
     /// Radio driver error
     #[error("Radio error: {0}")]
     Radio(#[from] DriverError),
+    /// Radio driver error (any [`RadioDriver`] implementation)
+    #[error("Radio driver error: {0}")]
+    RadioDriver(#[from] RadioDriverError),
     /// Frame parsing error
     #[error("Frame parse error: {0:?}")]
     FrameParse(#[from] ParseError),
@@ -171,6 +223,17 @@ impl Default for WMBusConfig {
     }
 }
 
+impl WMBusConfig {
+    /// The driver-level configuration for this carrier and bitrate
+    fn radio_config(&self) -> crate::wmbus::radio::radio_driver::WMBusConfig {
+        crate::wmbus::radio::radio_driver::WMBusConfig {
+            frequency_hz: self.frequency_hz,
+            bitrate: self.bitrate,
+            ..Default::default()
+        }
+    }
+}
+
 /// Builder for WMBusConfig with fluent API and preset configurations
 pub struct WMBusConfigBuilder {
     config: WMBusConfig,
@@ -197,6 +260,19 @@ impl WMBusConfigBuilder {
         }
     }
 
+    /// Configure for EU wM-Bus C-mode (C1/C2 meter-to-other: 868.95 MHz, 100 kcps NRZ)
+    pub fn eu_c_mode() -> Self {
+        Self {
+            config: WMBusConfig {
+                frequency_hz: 868_950_000,
+                bitrate: 100_000,
+                lbt_config: LbtConfig::default(),
+                rx_timeout_ms: 5000,
+                discovery_timeout_ms: 30000,
+            },
+        }
+    }
+
     /// Configure for EU wM-Bus T-mode (868.3 MHz, 100 kbps)
     pub fn eu_t_mode() -> Self {
         Self {
@@ -210,13 +286,18 @@ impl WMBusConfigBuilder {
         }
     }
 
-    /// Configure for EU wM-Bus N-mode (multiple frequencies)
-    /// Note: This sets the primary frequency; actual N-mode requires scanning multiple channels
+    /// Configure for EU wM-Bus N-mode (channel 1a: 169.40625 MHz, 4.8 kbps)
+    /// Note: This sets the primary channel; other channels via [`Self::n_mode_channel`]
     pub fn eu_n_mode() -> Self {
+        Self::n_mode_channel(NModeChannel::CH_1A)
+    }
+
+    /// Configure for one N-mode channel (169.40625–169.56875 MHz)
+    pub fn n_mode_channel(channel: NModeChannel) -> Self {
         Self {
             config: WMBusConfig {
-                frequency_hz: 869_525_000, // Primary N-mode frequency
-                bitrate: 4800,             // 4.8 kbps for N-mode
+                frequency_hz: channel.frequency_hz,
+                bitrate: channel.bitrate,
                 lbt_config: LbtConfig::default(),
                 rx_timeout_ms: 10000, // Longer timeout for slower data rate
                 discovery_timeout_ms: 60000, // Longer discovery time
@@ -224,6 +305,19 @@ impl WMBusConfigBuilder {
         }
     }
 
+    /// Configure for wM-Bus F-mode (433.82 MHz, 2.4 kbps)
+    pub fn eu_f_mode() -> Self {
+        Self {
+            config: WMBusConfig {
+                frequency_hz: 433_820_000,
+                bitrate: 2400,
+                lbt_config: LbtConfig::default(),
+                rx_timeout_ms: 10000,
+                discovery_timeout_ms: 60000,
+            },
+        }
+    }
+
     /// Configure for high-performance scenarios (fast scanning, short timeouts)
     pub fn fast_scan() -> Self {
         Self {
@@ -300,6 +394,25 @@ impl Default for WMBusConfigBuilder {
     }
 }
 
+/// Feeds the radio's RxDone (end-of-frame) interrupt into the background receiver
+///
+/// Call [`rx_done`](Self::rx_done) from the DIO interrupt handler with the edge
+/// timestamp, e.g. [`GpioEvent::timestamp`](crate::wmbus::radio::hal::GpioEvent). The
+/// receiver then polls the radio at once instead of at its next tick, and times a
+/// bidirectional meter's response window from the edge instead of from the poll —
+/// without it the 2–3 ms window cannot be met reliably.
+#[derive(Debug, Clone)]
+pub struct RxDoneNotifier {
+    sender: mpsc::Sender<Instant>,
+}
+
+impl RxDoneNotifier {
+    /// Report that a frame ended at `at`. Never blocks, so it is safe in an IRQ path.
+    pub fn rx_done(&self, at: impl Into<Instant>) {
+        let _ = self.sender.try_send(at.into());
+    }
+}
+
 /// Information about a discovered wM-Bus device
 #[derive(Debug, Clone)]
 pub struct DeviceInfo {
@@ -318,9 +431,9 @@ pub struct DeviceInfo {
 }
 
 /// Represents a handle to the Wireless M-Bus (wM-Bus) connection
-pub struct WMBusHandle<H: Hal> {
-    /// Radio driver for SX126x
-    driver: Arc<Mutex<Sx126xDriver<H>>>,
+pub struct WMBusHandle<R: RadioDriver> {
+    /// Radio driver
+    driver: Arc<Mutex<R>>,
     /// wM-Bus configuration
     config: WMBusConfig,
     /// Receiver task handle
@@ -329,14 +442,45 @@ pub struct WMBusHandle<H: Hal> {
     rx_channel: FrameReceiver,
     /// Sender for frame reception (internal)
     tx_sender: Option<FrameSender>,
+    /// Items dropped because the receive channel was full
+    dropped_items: Arc<AtomicU64>,
+    /// Fan-out to [`WMBusHandle::subscribe`] streams
+    fanout: broadcast::Sender<ReceivedItem>,
+    /// RxDone edges reported through [`WMBusHandle::rx_done_notifier`]
+    rx_done_sender: mpsc::Sender<Instant>,
+    /// RxDone edges for the receiver task (taken when it starts)
+    rx_done_receiver: Option<mpsc::Receiver<Instant>>,
     /// Device registry for discovered devices
     devices: Arc<RwLock<HashMap<u32, DeviceInfo>>>,
     /// Callback for unsolicited frames
     unsolicited_callback: Option<UnsolicitedCallback>,
+    /// Duplicate folding for [`WMBusHandle::recv_deduplicated`]
+    dedup: Option<Deduplicator>,
+    /// Primary station answering bidirectional meters
+    station: Option<Arc<Mutex<PrimaryStation>>>,
+    /// Finished exchanges, fed by the receiver
+    exchange_sender: mpsc::UnboundedSender<Exchange>,
+    /// Finished exchanges for [`WMBusHandle::recv_exchange`]
+    exchange_receiver: mpsc::UnboundedReceiver<Exchange>,
+    /// Per-meter counter tracking, if enabled
+    replay_guard: Option<Arc<Mutex<ReplayGuard>>>,
+    /// Installation (SND_IR) pairing window and inventory
+    installer: Arc<Mutex<Installer>>,
+    /// Newly installed meters, fed by the receiver
+    installation_sender: mpsc::UnboundedSender<InstalledMeter>,
+    /// Newly installed meters for [`WMBusHandle::recv_installation`]
+    installation_receiver: mpsc::UnboundedReceiver<InstalledMeter>,
 }
 
-impl<H: Hal + Send + 'static> WMBusHandle<H> {
-    /// Create a new wM-Bus handle with the provided HAL
+/// The primary station as the receiver task sees it
+struct Responder {
+    station: Arc<Mutex<PrimaryStation>>,
+    /// Receive configuration the radio returns to after each response
+    rx_config: crate::wmbus::radio::radio_driver::WMBusConfig,
+}
+
+impl<H: Hal + Send + 'static> WMBusHandle<Sx126xDriver<H>> {
+    /// Create a new SX126x wM-Bus handle with the provided HAL
     ///
     /// Initializes the radio driver and configures it for wM-Bus operation.
     ///
@@ -358,35 +502,88 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
         // Configure radio for wM-Bus operation
         driver.configure_for_wmbus(config.frequency_hz, config.bitrate)?;
 
+        Ok(Self::from_configured(driver, config))
+    }
+}
+
+impl<R: RadioDriver + 'static> WMBusHandle<R> {
+    /// Create a new wM-Bus handle around any [`RadioDriver`]
+    ///
+    /// Initializes the driver with the handle's frequency and bitrate; everything
+    /// else uses the driver's defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `driver` - Radio driver (not yet initialized)
+    /// * `config` - wM-Bus configuration (optional, uses defaults if None)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(WMBusHandle)` - Successfully initialized handle
+    /// * `Err(WMBusError)` - Initialization failed
+    pub async fn with_driver(
+        mut driver: R,
+        config: Option<WMBusConfig>,
+    ) -> Result<Self, WMBusError> {
+        let config = config.unwrap_or_default();
+
+        driver.initialize(config.radio_config()).await?;
+
+        Ok(Self::from_configured(driver, config))
+    }
+
+    /// Wrap an already-configured driver
+    fn from_configured(driver: R, config: WMBusConfig) -> Self {
         // Set up communication channels
-        let (tx_sender, rx_receiver) = mpsc::unbounded_channel();
+        let (tx_sender, rx_receiver) = mpsc::channel(RX_CHANNEL_CAPACITY);
+        let (fanout, _) = broadcast::channel(FANOUT_CAPACITY);
+        let (rx_done_sender, rx_done_receiver) = mpsc::channel(RX_DONE_CAPACITY);
+        let (exchange_sender, exchange_receiver) = mpsc::unbounded_channel();
+        let (installation_sender, installation_receiver) = mpsc::unbounded_channel();
 
-        Ok(WMBusHandle {
+        WMBusHandle {
             driver: Arc::new(Mutex::new(driver)),
             config,
             receiver_handle: None,
             rx_channel: Arc::new(RwLock::new(Some(rx_receiver))),
             tx_sender: Some(tx_sender),
+            dropped_items: Arc::new(AtomicU64::new(0)),
+            fanout,
+            rx_done_sender,
+            rx_done_receiver: Some(rx_done_receiver),
             devices: Arc::new(RwLock::new(HashMap::new())),
             unsolicited_callback: None,
-        })
+            dedup: None,
+            station: None,
+            exchange_sender,
+            exchange_receiver,
+            replay_guard: None,
+            installer: Arc::new(Mutex::new(Installer::new())),
+            installation_sender,
+            installation_receiver,
+        }
     }
 
     /// The shared radio driver behind this handle, for constructing a
     /// [`ProfileScheduler`](crate::wmbus::radio::scheduler::ProfileScheduler) that drives
     /// profile transitions on the **same** radio instance — never a second driver.
-    pub fn shared_driver(&self) -> Arc<Mutex<Sx126xDriver<H>>> {
+    pub fn shared_driver(&self) -> Arc<Mutex<R>> {
         self.driver.clone()
     }
 
+    /// A notifier for the radio's RxDone interrupt (see [`RxDoneNotifier`])
+    pub fn rx_done_notifier(&self) -> RxDoneNotifier {
+        RxDoneNotifier {
+            sender: self.rx_done_sender.clone(),
+        }
+    }
+
     /// Poll the radio once for a mode-tagged received item: lock the driver, capture the
-    /// packet atomically with its modem (see [`Sx126xDriver::process_irqs_with_mode`]), and
+    /// packet atomically with its modem (see [`RadioDriver::get_mode_tagged_packet`]), and
     /// route it. Shared by the background receiver loop and the tests.
-    async fn poll_once(
-        driver: &Arc<Mutex<Sx126xDriver<H>>>,
-    ) -> Result<Option<ReceivedItem>, DriverError> {
+    async fn poll_once(driver: &Arc<Mutex<R>>) -> Result<Option<ReceivedItem>, RadioDriverError> {
         let mut guard = driver.lock().await;
-        Ok(guard.process_irqs_with_mode()?.and_then(route_packet))
+        Ok(guard.get_mode_tagged_packet().await?.and_then(route_packet))
     }
 
     /// Start continuous frame reception in background
@@ -412,6 +609,17 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
             .ok_or_else(|| WMBusError::InvalidConfig("TX sender not available".to_string()))?;
         let devices = self.devices.clone();
         let unsolicited_callback = self.unsolicited_callback.clone();
+        let fanout = self.fanout.clone();
+        let dropped_items = self.dropped_items.clone();
+        let responder = self.station.clone().map(|station| Responder {
+            station,
+            rx_config: self.config.radio_config(),
+        });
+        let replay_guard = self.replay_guard.clone();
+        let exchange_sender = self.exchange_sender.clone();
+        let installer = self.installer.clone();
+        let installation_sender = self.installation_sender.clone();
+        let mut rx_done = self.rx_done_receiver.take();
 
         // Spawn background receiver task
         let handle = tokio::spawn(async move {
@@ -423,31 +631,67 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
             // defensively after a run of poll errors.
             {
                 let mut driver_guard = driver.lock().await;
-                if let Err(e) = driver_guard.set_rx_continuous() {
+                if let Err(e) = driver_guard.start_receive().await {
                     log::error!("Failed to arm continuous RX: {e:?}");
                 }
             }
 
             loop {
-                tokio::time::sleep(Duration::from_millis(10)).await;
+                // Wake on the RxDone interrupt when it is wired up, else on the poll tick.
+                let rx_done_at = tokio::select! {
+                    Some(at) = next_rx_done(&mut rx_done) => Some(at),
+                    _ = sleep(POLL_INTERVAL) => None,
+                };
+
+                if let Some(responder) = &responder {
+                    for exchange in responder.station.lock().await.expire(Instant::now()) {
+                        let _ = exchange_sender.send(exchange);
+                    }
+                }
 
                 // One atomic, mode-tagged poll under a single driver lock.
                 match Self::poll_once(&driver).await {
-                    Ok(Some(item)) => {
+                    Ok(Some(mut item)) => {
                         consecutive_errors = 0;
+                        let received_at = rx_done_at.unwrap_or_else(Instant::now);
+
+                        if let Some(guard) = &replay_guard {
+                            Self::tag_freshness(guard, &mut item).await;
+                        }
+                        Self::serve_meter(
+                            &driver,
+                            responder.as_ref(),
+                            &installer,
+                            &installation_sender,
+                            &exchange_sender,
+                            &item,
+                            received_at,
+                        )
+                        .await;
 
                         // Device registry + unsolicited callback apply to wM-Bus frames.
-                        if let ReceivedItem::Wmbus { frame, rssi_dbm } = &item {
+                        if let ReceivedItem::Wmbus {
+                            frame, rssi_dbm, ..
+                        } = &item
+                        {
                             Self::update_device_registry(&devices, frame, *rssi_dbm).await;
                             if let Some(callback) = &unsolicited_callback {
                                 callback(frame);
                             }
                         }
 
-                        // Send the tagged item to the channel.
-                        if tx_sender.send(item).is_err() {
-                            log::warn!("Frame channel receiver dropped");
-                            break;
+                        // Fan out to subscribers (none subscribed is not an error), then
+                        // send the tagged item to the channel, dropping it if nobody drains it.
+                        let _ = fanout.send(item.clone());
+                        match tx_sender.try_send(item) {
+                            Ok(()) => {}
+                            Err(mpsc::error::TrySendError::Full(_)) => {
+                                dropped_items.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Err(mpsc::error::TrySendError::Closed(_)) => {
+                                log::warn!("Frame channel receiver dropped");
+                                break;
+                            }
                         }
                     }
                     Ok(None) => {
@@ -464,7 +708,7 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
                             log::error!("Too many consecutive radio errors; re-arming RX");
                             {
                                 let mut driver_guard = driver.lock().await;
-                                let _ = driver_guard.set_rx_continuous();
+                                let _ = driver_guard.start_receive().await;
                             }
                             sleep(Duration::from_millis(5000)).await;
                             consecutive_errors = 0;
@@ -478,6 +722,138 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
         Ok(())
     }
 
+    /// Tag a wM-Bus frame with the replay guard's verdict
+    ///
+    /// Consumers decide what to do with a possible replay.
+    async fn tag_freshness(guard: &Mutex<ReplayGuard>, item: &mut ReceivedItem) {
+        if let ReceivedItem::Wmbus {
+            frame, freshness, ..
+        } = item
+        {
+            let verdict = guard.lock().await.check(frame).freshness;
+            if verdict == Freshness::Suspicious {
+                log::warn!("Possible replay from meter {:#X}", frame.device_address);
+            }
+            *freshness = Some(verdict);
+        }
+    }
+
+    /// Register installation requests and answer meters that opened a window
+    ///
+    /// A possible replay is delivered but neither registered nor answered.
+    async fn serve_meter(
+        driver: &Arc<Mutex<R>>,
+        responder: Option<&Responder>,
+        installer: &Mutex<Installer>,
+        installation_sender: &mpsc::UnboundedSender<InstalledMeter>,
+        exchange_sender: &mpsc::UnboundedSender<Exchange>,
+        item: &ReceivedItem,
+        received_at: Instant,
+    ) {
+        let ReceivedItem::Wmbus {
+            frame,
+            rssi_dbm,
+            link_mode,
+            freshness,
+        } = item
+        else {
+            return;
+        };
+        if *freshness == Some(Freshness::Suspicious) {
+            return;
+        }
+
+        let confirm = {
+            let mut installer = installer.lock().await;
+            if let Some(meter) = installer.on_frame(frame, *link_mode, *rssi_dbm, received_at) {
+                let _ = installation_sender.send(meter);
+            }
+            installer.wants_confirmation(frame, received_at)
+        };
+
+        // Answer first: the meter listens for only a few milliseconds.
+        if let Some(responder) = responder {
+            if confirm {
+                responder
+                    .station
+                    .lock()
+                    .await
+                    .queue_confirmation(frame.manufacturer_id, frame.device_address);
+            }
+            Self::respond_in_window(
+                driver,
+                responder,
+                installer,
+                exchange_sender,
+                frame,
+                *link_mode,
+                received_at,
+            )
+            .await;
+        }
+    }
+
+    /// Let the receiver answer a meter that opened a reception window
+    ///
+    /// `received_at` is the RxDone edge when an [`RxDoneNotifier`] reports it; otherwise
+    /// the poll time stands in for the end of the meter's frame and the poll interval
+    /// eats into the window. Responses that no longer fit wait for the next window. The
+    /// response goes out on the mode's downlink PHY, after which the radio is retuned to
+    /// the handle's carrier and bitrate; a profile scheduler sharing the driver re-applies
+    /// its own profile at its next switch.
+    async fn respond_in_window(
+        driver: &Arc<Mutex<R>>,
+        responder: &Responder,
+        installer: &Mutex<Installer>,
+        exchange_sender: &mpsc::UnboundedSender<Exchange>,
+        frame: &WMBusFrame,
+        link_mode: Option<LinkMode>,
+        received_at: Instant,
+    ) {
+        let mut station = responder.station.lock().await;
+        let reaction = station.on_frame_in(frame, link_mode, received_at);
+        if let Some(exchange) = reaction.completed {
+            let _ = exchange_sender.send(exchange);
+        }
+        let Some(plan) = reaction.respond else {
+            return;
+        };
+
+        let lbt_config = station.config().lbt_config;
+        let result = {
+            let mut driver_guard = driver.lock().await;
+            let result =
+                transmit_response(&mut *driver_guard, &plan, lbt_config, &responder.rx_config)
+                    .await;
+            // Retuning and transmitting left continuous RX.
+            if let Err(e) = driver_guard.start_receive().await {
+                log::error!("Failed to re-arm RX after response: {e:?}");
+            }
+            result
+        };
+
+        match result {
+            Ok(sent_at) => {
+                log::debug!("Answered meter {:#X} in its window", plan.meter.address);
+                if let Some(exchange) = station.sent(plan, sent_at) {
+                    if let (GatewayCommand::CnfIr, ExchangeOutcome::Delivered) =
+                        (&exchange.command, &exchange.outcome)
+                    {
+                        installer
+                            .lock()
+                            .await
+                            .mark_confirmed(exchange.meter.manufacturer_id, exchange.meter.address);
+                    }
+                    let _ = exchange_sender.send(exchange);
+                }
+            }
+            Err(e) => {
+                log::debug!("Response to meter {:#X} not sent: {e}", plan.meter.address);
+                station.requeue(plan);
+            }
+        }
+    }
+
     /// Stop the background frame receiver
     pub async fn stop_receiver(&mut self) {
         if let Some(handle) = self.receiver_handle.take() {
@@ -502,7 +878,9 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
         let mut driver = self.driver.lock().await;
 
         // Use LBT transmission for regulatory compliance
-        driver.lbt_transmit(&frame_bytes, self.config.lbt_config)?;
+        driver
+            .transmit_with_lbt(&frame_bytes, self.config.lbt_config)
+            .await?;
 
         log::info!("Transmitted frame to device {:#X}", frame.device_address);
         Ok(())
@@ -565,7 +943,9 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
                 return Err(WMBusError::Timeout);
             }
             match timeout(remaining, rx_channel.recv()).await {
-                Ok(Some(ReceivedItem::Wmbus { frame, rssi_dbm })) => return Ok((frame, rssi_dbm)),
+                Ok(Some(ReceivedItem::Wmbus {
+                    frame, rssi_dbm, ..
+                })) => return Ok((frame, rssi_dbm)),
                 Ok(Some(ReceivedItem::Lora { .. })) => continue, // skip/consume LoRa items
                 Ok(None) => return Err(WMBusError::Network("Frame channel closed".to_string())),
                 Err(_) => return Err(WMBusError::Timeout),
@@ -573,19 +953,257 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
         }
     }
 
+    /// Items the receiver dropped because [`WMBusHandle::recv_item`] was not keeping up
+    ///
+    /// Only the receive channel is bounded this way; subscribers report their own lag.
+    pub fn dropped_items(&self) -> u64 {
+        self.dropped_items.load(Ordering::Relaxed)
+    }
+
+    /// Subscribe to the received items as a [`Stream`](tokio_stream::Stream)
+    ///
+    /// Every subscriber sees every item the background receiver delivers from the moment
+    /// it subscribes, independently of [`WMBusHandle::recv_item`] and of each other. See
+    /// [`crate::wmbus::stream`] for filters and lag handling.
+    pub fn subscribe(&self) -> ItemStream {
+        ItemStream::new(self.fanout.subscribe())
+    }
+
+    /// Enable duplicate folding for [`WMBusHandle::recv_deduplicated`]
+    ///
+    /// Replaces any earlier deduplicator (and drops the telegrams it still held).
+    pub fn enable_deduplication(&mut self, config: DedupConfig) {
+        self.dedup = Some(Deduplicator::new(config));
+    }
+
+    /// Deduplication counters, if deduplication is enabled
+    pub fn dedup_stats(&self) -> Option<DedupStats> {
+        self.dedup.as_ref().map(Deduplicator::stats)
+    }
+
+    /// Receive the next **deduplicated** wM-Bus telegram with timeout.
+    ///
+    /// Copies of the same telegram (meter retransmissions, repeater copies) arriving
+    /// within the configured window are folded into one [`DedupedTelegram`], released
+    /// when its window closes. LoRa items are skipped and consumed, as in
+    /// [`WMBusHandle::recv_frame`]. Requires [`WMBusHandle::enable_deduplication`].
+    ///
+    /// # Returns
+    /// * `Ok(DedupedTelegram)` - the next telegram whose window has closed
+    /// * `Err(WMBusError::Timeout)` - no window closed before the deadline
+    /// * `Err(WMBusError)` - other error
+    pub async fn recv_deduplicated(
+        &mut self,
+        timeout_ms: Option<u32>,
+    ) -> Result<DedupedTelegram, WMBusError> {
+        let timeout_duration =
+            Duration::from_millis(timeout_ms.unwrap_or(self.config.rx_timeout_ms) as u64);
+        let deadline = Instant::now() + timeout_duration;
+
+        let dedup = self
+            .dedup
+            .as_mut()
+            .ok_or_else(|| WMBusError::InvalidConfig("Deduplication not enabled".to_string()))?;
+        let mut rx_guard = self.rx_channel.write().await;
+        let rx_channel = rx_guard
+            .as_mut()
+            .ok_or_else(|| WMBusError::InvalidConfig("RX channel not available".to_string()))?;
+
+        loop {
+            let now = Instant::now();
+            if let Some(telegram) = dedup.pop_expired(now) {
+                return Ok(telegram);
+            }
+            if now >= deadline {
+                return Err(WMBusError::Timeout);
+            }
+            // Wake for the next arrival, the next closing window, or the deadline.
+            let wake = dedup.next_deadline().map_or(deadline, |d| d.min(deadline));
+            match timeout_at(wake, rx_channel.recv()).await {
+                Ok(Some(ReceivedItem::Wmbus {
+                    frame, rssi_dbm, ..
+                })) => {
+                    let receiver = dedup.config().receiver_id.clone();
+                    if let Some(early) =
+                        dedup.push(TelegramCopy::new(frame, rssi_dbm, receiver, Instant::now()))
+                    {
+                        return Ok(early);
+                    }
+                }
+                Ok(Some(ReceivedItem::Lora { .. })) => continue,
+                Ok(None) => {
+                    // Channel closed: hand out what is still held, then report the close.
+                    let everything = Instant::now() + dedup.config().window;
+                    return dedup
+                        .pop_expired(everything)
+                        .ok_or_else(|| WMBusError::Network("Frame channel closed".to_string()));
+                }
+                Err(_) => continue, // re-check windows and the deadline
+            }
+        }
+    }
+
+    /// Track per-meter counters in the background receiver and tag possible replays
+    ///
+    /// Every wM-Bus frame is checked with `guard` (see [`crate::wmbus::replay_guard`])
+    /// before it is answered or delivered, and carries the verdict in
+    /// [`ReceivedItem::Wmbus`]'s `freshness`. No frame is dropped; [`Freshness::Suspicious`]
+    /// frames are delivered but not answered. Keep the counters across restarts with
+    /// [`WMBusHandle::replay_guard_state`] and [`WMBusHandle::restore_replay_state`], or
+    /// pass a guard from [`ReplayGuard::load_from_file`]. Must be called before
+    /// [`WMBusHandle::start_receiver`].
+    pub fn enable_replay_guard(&mut self, guard: ReplayGuard) -> Result<(), WMBusError> {
+        if self.receiver_handle.is_some() {
+            return Err(WMBusError::InvalidConfig(
+                "Enable the replay guard before starting the receiver".to_string(),
+            ));
+        }
+        self.replay_guard = Some(Arc::new(Mutex::new(guard)));
+        Ok(())
+    }
+
+    /// Counter tracking state of one meter, if the replay guard is enabled
+    pub async fn replay_state(
+        &self,
+        manufacturer_id: u16,
+        device_address: u32,
+    ) -> Option<MeterCounterState> {
+        let guard = self.replay_guard.as_ref()?.lock().await;
+        guard.meter(manufacturer_id, device_address).cloned()
+    }
+
+    /// Snapshot of the replay guard's counters for storing across restarts, if the guard
+    /// is enabled
+    pub async fn replay_guard_state(&self) -> Option<ReplayGuardState> {
+        Some(self.replay_guard.as_ref()?.lock().await.state())
+    }
+
+    /// Load counters stored with [`WMBusHandle::replay_guard_state`] into the enabled
+    /// replay guard
+    ///
+    /// May be called while the receiver runs; the stored state replaces what the guard
+    /// knows about the meters it contains.
+    pub async fn restore_replay_state(&self, state: ReplayGuardState) -> Result<(), WMBusError> {
+        let guard = self.replay_guard.as_ref().ok_or_else(|| {
+            WMBusError::InvalidConfig("The replay guard is not enabled".to_string())
+        })?;
+        guard.lock().await.restore(state);
+        Ok(())
+    }
+
+    /// Answer bidirectional (T2/C2/S2) meters from the background receiver
+    ///
+    /// Frames that open a reception window get the meter's next command queued with
+    /// [`WMBusHandle::queue_command`] (or an ACK for an access demand), timed into the
+    /// window; finished exchanges come out of [`WMBusHandle::recv_exchange`]. Must be
+    /// called before [`WMBusHandle::start_receiver`].
+    pub fn enable_bidirectional(&mut self, config: PrimaryStationConfig) -> Result<(), WMBusError> {
+        if self.receiver_handle.is_some() {
+            return Err(WMBusError::InvalidConfig(
+                "Enable bidirectional operation before starting the receiver".to_string(),
+            ));
+        }
+        self.station = Some(Arc::new(Mutex::new(PrimaryStation::new(config))));
+        Ok(())
+    }
+
+    /// Queue a command for a meter's next reception window
+    ///
+    /// Requires [`WMBusHandle::enable_bidirectional`].
+    pub async fn queue_command(
+        &self,
+        manufacturer_id: u16,
+        address: u32,
+        command: GatewayCommand,
+    ) -> Result<(), WMBusError> {
+        let station = self.station.as_ref().ok_or_else(|| {
+            WMBusError::InvalidConfig("Bidirectional operation not enabled".to_string())
+        })?;
+        station
+            .lock()
+            .await
+            .queue(manufacturer_id, address, command);
+        Ok(())
+    }
+
+    /// Receive the next finished command/response exchange with timeout
+    ///
+    /// # Returns
+    /// * `Ok(Exchange)` - a command was answered, delivered or timed out
+    /// * `Err(WMBusError::Timeout)` - no exchange finished within the timeout
+    pub async fn recv_exchange(&mut self, timeout_ms: Option<u32>) -> Result<Exchange, WMBusError> {
+        let timeout_duration =
+            Duration::from_millis(timeout_ms.unwrap_or(self.config.rx_timeout_ms) as u64);
+        match timeout(timeout_duration, self.exchange_receiver.recv()).await {
+            Ok(Some(exchange)) => Ok(exchange),
+            Ok(None) => Err(WMBusError::Network("Exchange channel closed".to_string())),
+            Err(_) => Err(WMBusError::Timeout),
+        }
+    }
+
+    /// Open the installation pairing window
+    ///
+    /// Until it closes, each meter sending an installation request (SND_IR) is added to
+    /// the inventory once and announced on [`WMBusHandle::recv_installation`]. With
+    /// `auto_confirm` the request is answered with CNF_IR, which requires
+    /// [`WMBusHandle::enable_bidirectional`].
+    pub async fn open_installation_window(
+        &self,
+        config: InstallationConfig,
+    ) -> Result<(), WMBusError> {
+        if config.auto_confirm && self.station.is_none() {
+            return Err(WMBusError::InvalidConfig(
+                "Auto-confirm needs bidirectional operation".to_string(),
+            ));
+        }
+        self.installer.lock().await.open(config, Instant::now());
+        Ok(())
+    }
+
+    /// Close the installation pairing window early
+    pub async fn close_installation_window(&self) {
+        self.installer.lock().await.close();
+    }
+
+    /// Meters registered through installation requests, oldest first
+    pub async fn installed_meters(&self) -> Vec<InstalledMeter> {
+        self.installer.lock().await.inventory().meters()
+    }
+
+    /// Receive the next newly installed meter with timeout
+    ///
+    /// # Returns
+    /// * `Ok(InstalledMeter)` - a meter was registered during an open window
+    /// * `Err(WMBusError::Timeout)` - no meter was registered within the timeout
+    pub async fn recv_installation(
+        &mut self,
+        timeout_ms: Option<u32>,
+    ) -> Result<InstalledMeter, WMBusError> {
+        let timeout_duration =
+            Duration::from_millis(timeout_ms.unwrap_or(self.config.rx_timeout_ms) as u64);
+        match timeout(timeout_duration, self.installation_receiver.recv()).await {
+            Ok(Some(meter)) => Ok(meter),
+            Ok(None) => Err(WMBusError::Network(
+                "Installation channel closed".to_string(),
+            )),
+            Err(_) => Err(WMBusError::Timeout),
+        }
+    }
+
     /// Test-only: inject an item directly into the receive channel (bypassing the radio),
     /// so `recv_item`/`recv_frame` routing can be tested without hardware.
     #[cfg(test)]
     fn inject_item(&self, item: ReceivedItem) {
+        let _ = self.fanout.send(item.clone());
         if let Some(sender) = &self.tx_sender {
-            let _ = sender.send(item);
+            let _ = sender.try_send(item);
         }
     }
 
     /// Test-only: one mode-tagged poll of the shared driver (what the background receiver
     /// does each iteration), so an end-to-end test can step reception deterministically.
     #[cfg(test)]
-    async fn poll_once_test(&self) -> Result<Option<ReceivedItem>, DriverError> {
+    async fn poll_once_test(&self) -> Result<Option<ReceivedItem>, RadioDriverError> {
         Self::poll_once(&self.driver).await
     }
 
@@ -668,7 +1286,12 @@ impl<H: Hal + Send + 'static> WMBusHandle<H> {
         &self,
     ) -> Result<crate::wmbus::radio::driver::RadioStatusReport, WMBusError> {
         let mut driver = self.driver.lock().await;
-        let state = driver.get_state()?;
+        let state = match driver.get_mode().await? {
+            RadioMode::Sleep => RadioState::Sleep,
+            RadioMode::Standby => RadioState::StandbyRc,
+            RadioMode::Transmit => RadioState::Tx,
+            RadioMode::Receive => RadioState::Rx,
+        };
 
         // Build a basic RadioStatusReport
         Ok(RadioStatusReport {
@@ -751,7 +1374,7 @@ pub trait WMBusHandleWrapper: Send + Sync {
 }
 
 /// Implementation of WMBusHandleWrapper for any HAL type
-impl<H: Hal + Send + 'static> WMBusHandleWrapper for WMBusHandle<H> {
+impl<R: RadioDriver + 'static> WMBusHandleWrapper for WMBusHandle<R> {
     fn send_frame<'a>(
         &'a self,
         frame: &'a WMBusFrame,
@@ -849,6 +1472,46 @@ impl WMBusHandleFactory {
         Ok(Box::new(handle))
     }
 
+    /// Create a new wM-Bus handle around any [`RadioDriver`]
+    ///
+    /// # Arguments
+    ///
+    /// * `driver` - Radio driver (not yet initialized)
+    /// * `config` - wM-Bus configuration (optional, uses defaults if None)
+    ///
+    /// # Returns
+    ///
+    /// A boxed trait object that can be used in the device manager
+    pub async fn create_with_driver<R: RadioDriver + 'static>(
+        driver: R,
+        config: Option<WMBusConfig>,
+    ) -> Result<Box<dyn WMBusHandleWrapper>, WMBusError> {
+        let handle = WMBusHandle::with_driver(driver, config).await?;
+        Ok(Box::new(handle))
+    }
+
+    #[cfg(feature = "rfm69")]
+    /// Create a new wM-Bus handle for an RFM69HCW on Raspberry Pi
+    ///
+    /// The RFM69 is a single-channel receiver, so the handle is configured for
+    /// EU C-mode (868.95 MHz, 100 kbps).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SPI or GPIO initialization fails
+    pub async fn create_rfm69(
+        rfm69_config: crate::wmbus::radio::rfm69::Rfm69Config,
+    ) -> Result<Box<dyn WMBusHandleWrapper>, WMBusError> {
+        use crate::wmbus::radio::rfm69::Rfm69Driver;
+
+        let driver = Rfm69Driver::new(rfm69_config)
+            .await
+            .map_err(|e| RadioDriverError::DeviceError(format!("RFM69 open failed: {e}")))?;
+
+        let config = WMBusConfigBuilder::eu_c_mode().build();
+        Self::create_with_driver(driver, Some(config)).await
+    }
+
     #[cfg(feature = "raspberry-pi")]
     /// Create a new wM-Bus handle for Raspberry Pi with default configuration
     ///
@@ -1008,14 +1671,19 @@ impl WMBusHandleFactory {
 #[cfg(test)]
 mod tests {
     use super::{route_packet, ReceivedItem, WMBusError, WMBusHandle};
+    use crate::wmbus::bitstream::LinkMode;
     use crate::wmbus::frame::WMBusFrame;
-    use crate::wmbus::radio::driver::{LoRaRxInfo, ModeTaggedPacket};
+    use crate::wmbus::radio::driver::{LoRaRxInfo, ModeTaggedPacket, RadioState, Sx126xDriver};
     use crate::wmbus::radio::hal::MockHal;
     use crate::wmbus::radio::modulation::{LoRaBandwidth, PacketType, SpreadingFactor};
+    use crate::wmbus::radio::radio_driver::{
+        DriverInfo, RadioDriver, RadioDriverError, RadioMode, RadioStats, ReceivedPacket,
+    };
 
     fn gfsk_packet(payload: Vec<u8>) -> ModeTaggedPacket {
         ModeTaggedPacket {
             mode: PacketType::Gfsk,
+            link_mode: None,
             payload,
             rssi_dbm: -70,
             lora: None,
@@ -1025,6 +1693,7 @@ mod tests {
     fn lora_packet() -> ModeTaggedPacket {
         ModeTaggedPacket {
             mode: PacketType::LoRa,
+            link_mode: None,
             payload: vec![0xDE, 0xAD, 0xBE, 0xEF],
             rssi_dbm: -95,
             lora: Some(LoRaRxInfo {
@@ -1045,12 +1714,31 @@ mod tests {
     fn routes_gfsk_valid_to_wmbus_and_malformed_to_none() {
         // A valid GFSK payload parses into a wM-Bus item...
         match route_packet(gfsk_packet(valid_wmbus_bytes())) {
-            Some(ReceivedItem::Wmbus { frame, rssi_dbm }) => {
+            Some(ReceivedItem::Wmbus {
+                frame,
+                rssi_dbm,
+                link_mode,
+                freshness,
+            }) => {
                 assert_eq!(frame.device_address, 0x74280561);
                 assert_eq!(rssi_dbm, -70);
+                assert_eq!(link_mode, None);
+                assert_eq!(freshness, None);
             }
             other => panic!("expected Wmbus, got {other:?}"),
         }
+        // ...carrying the link mode detected on a T1 + C1 profile.
+        let tagged = ModeTaggedPacket {
+            link_mode: Some(LinkMode::C),
+            ..gfsk_packet(valid_wmbus_bytes())
+        };
+        assert!(matches!(
+            route_packet(tagged),
+            Some(ReceivedItem::Wmbus {
+                link_mode: Some(LinkMode::C),
+                ..
+            })
+        ));
         // ...and a malformed GFSK payload is dropped rather than surfaced.
         assert!(route_packet(gfsk_packet(vec![0x00, 0x01, 0x02])).is_none());
     }
@@ -1088,7 +1776,7 @@ mod tests {
         assert!(matches!(items[2], Some(ReceivedItem::Lora { .. })));
     }
 
-    async fn test_handle() -> WMBusHandle<MockHal> {
+    async fn test_handle() -> WMBusHandle<Sx126xDriver<MockHal>> {
         WMBusHandle::new(MockHal::new(), None).await.unwrap()
     }
 
@@ -1127,6 +1815,51 @@ mod tests {
         ));
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn recv_deduplicated_folds_meter_and_repeater_copies() {
+        use crate::wmbus::dedup::DedupConfig;
+        use crate::wmbus::frame::parse_wmbus_frame;
+
+        let copy = |hops: u8, rssi_dbm: i16| {
+            let payload = [0x2A, 0x00, hops, 0x00, 0x2F, 0x2F, 0x02, 0x13, 0x05, 0x00];
+            let raw = WMBusFrame::build(0x44, 0x2C2D, 0x12345678, 0x1B, 0x16, 0x7A, &payload);
+            ReceivedItem::Wmbus {
+                frame: parse_wmbus_frame(&raw).unwrap(),
+                rssi_dbm,
+                link_mode: None,
+                freshness: None,
+            }
+        };
+
+        let mut handle = test_handle().await;
+        assert!(matches!(
+            handle.recv_deduplicated(Some(10)).await,
+            Err(WMBusError::InvalidConfig(_))
+        ));
+        handle.enable_deduplication(DedupConfig::default());
+
+        handle.inject_item(copy(0, -85));
+        handle.inject_item(route_packet(lora_packet()).unwrap());
+        handle.inject_item(copy(0, -75));
+        handle.inject_item(copy(1, -60));
+
+        let start = tokio::time::Instant::now();
+        let telegram = handle.recv_deduplicated(Some(5_000)).await.unwrap();
+        // Released when the 2 s window of the first copy closed.
+        assert_eq!(start.elapsed(), std::time::Duration::from_secs(2));
+        assert_eq!(telegram.duplicates, 2);
+        assert_eq!(telegram.rssi_dbm, -60);
+        assert!(telegram.via_repeater());
+        assert!(telegram.paths.iter().all(|p| p.receiver == "local"));
+        assert_eq!(handle.dedup_stats().unwrap().suppressed, 2);
+
+        // Nothing else is pending.
+        assert!(matches!(
+            handle.recv_deduplicated(Some(500)).await,
+            Err(WMBusError::Timeout)
+        ));
+    }
+
     #[tokio::test]
     async fn scheduler_switching_produces_ordered_items_through_the_handle() {
         use crate::wmbus::radio::driver::{LoRaProfile, RadioProfile, WmbusProfile};
@@ -1177,4 +1910,464 @@ mod tests {
         assert!(matches!(items[1], Some(ReceivedItem::Lora { .. })));
         assert!(matches!(items[2], Some(ReceivedItem::Wmbus { .. })));
     }
+
+    /// A minimal single-modem radio: queued packets come out of `get_received_packet`,
+    /// transmissions are recorded.
+    #[derive(Clone, Default)]
+    struct QueueRadio {
+        rx: std::sync::Arc<std::sync::Mutex<std::collections::VecDeque<Vec<u8>>>>,
+        tx: std::sync::Arc<std::sync::Mutex<Vec<Vec<u8>>>>,
+        tx_at: std::sync::Arc<std::sync::Mutex<Vec<tokio::time::Instant>>>,
+        tuned: std::sync::Arc<std::sync::Mutex<Vec<(u32, u32)>>>,
+        busy_checks: std::sync::Arc<std::sync::atomic::AtomicU8>,
+    }
+
+    #[async_trait::async_trait]
+    impl RadioDriver for QueueRadio {
+        async fn initialize(
+            &mut self,
+            config: crate::wmbus::radio::radio_driver::WMBusConfig,
+        ) -> Result<(), RadioDriverError> {
+            self.tuned
+                .lock()
+                .unwrap()
+                .push((config.frequency_hz, config.bitrate));
+            Ok(())
+        }
+        async fn start_receive(&mut self) -> Result<(), RadioDriverError> {
+            Ok(())
+        }
+        async fn stop_receive(&mut self) -> Result<(), RadioDriverError> {
+            Ok(())
+        }
+        async fn transmit(&mut self, data: &[u8]) -> Result<(), RadioDriverError> {
+            self.tx.lock().unwrap().push(data.to_vec());
+            self.tx_at.lock().unwrap().push(tokio::time::Instant::now());
+            Ok(())
+        }
+        async fn get_received_packet(
+            &mut self,
+        ) -> Result<Option<ReceivedPacket>, RadioDriverError> {
+            Ok(self
+                .rx
+                .lock()
+                .unwrap()
+                .pop_front()
+                .map(|data| ReceivedPacket {
+                    data,
+                    rssi_dbm: -64,
+                    freq_error_hz: None,
+                    lqi: None,
+                    crc_valid: true,
+                }))
+        }
+        async fn get_stats(&mut self) -> Result<RadioStats, RadioDriverError> {
+            Ok(RadioStats::default())
+        }
+        async fn reset_stats(&mut self) -> Result<(), RadioDriverError> {
+            Ok(())
+        }
+        async fn get_mode(&mut self) -> Result<RadioMode, RadioDriverError> {
+            Ok(RadioMode::Receive)
+        }
+        async fn sleep(&mut self) -> Result<(), RadioDriverError> {
+            Ok(())
+        }
+        async fn wake_up(&mut self) -> Result<(), RadioDriverError> {
+            Ok(())
+        }
+        async fn get_rssi(&mut self) -> Result<i16, RadioDriverError> {
+            Ok(-70)
+        }
+        async fn is_channel_clear(
+            &mut self,
+            _threshold_dbm: i16,
+            _listen_duration: std::time::Duration,
+        ) -> Result<bool, RadioDriverError> {
+            // Busy for the first `busy_checks` listens.
+            let busy = self.busy_checks.load(std::sync::atomic::Ordering::SeqCst);
+            if busy > 0 {
+                self.busy_checks
+                    .store(busy - 1, std::sync::atomic::Ordering::SeqCst);
+            }
+            Ok(busy == 0)
+        }
+        fn get_driver_info(&self) -> DriverInfo {
+            DriverInfo {
+                name: "queue".to_string(),
+                version: "0".to_string(),
+                frequency_bands: vec![(868_000_000, 870_000_000)],
+                max_packet_size: 255,
+                supported_bitrates: vec![100_000],
+                power_range_dbm: (0, 14),
+                features: vec![],
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn any_radio_driver_gets_background_reception_and_device_tracking() {
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let mut handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+
+        let seen = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = seen.clone();
+        handle.register_unsolicited_data_callback(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+        handle.start_receiver().await.unwrap();
+
+        probe.rx.lock().unwrap().push_back(valid_wmbus_bytes());
+        let (frame, rssi) = handle.recv_frame(Some(1000)).await.unwrap();
+        assert_eq!(frame.device_address, 0x74280561);
+        assert_eq!(rssi, -64);
+        assert_eq!(seen.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let device = handle.get_device_info(0x74280561).await.unwrap();
+        assert_eq!(device.manufacturer_id, 0x6815);
+        assert_eq!(device.rssi_dbm, -64);
+
+        let status = handle.get_radio_status().await.unwrap();
+        assert_eq!(status.state, RadioState::Rx);
+        handle.stop_receiver().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_frame_listens_before_talking_on_any_radio() {
+        use crate::wmbus::frame::parse_wmbus_frame;
+
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+        let frame = parse_wmbus_frame(&valid_wmbus_bytes()).unwrap();
+
+        // Busy twice, then clear: the third attempt (within the default 3 retries) goes out.
+        probe
+            .busy_checks
+            .store(2, std::sync::atomic::Ordering::SeqCst);
+        handle.send_frame(&frame).await.unwrap();
+        assert_eq!(probe.tx.lock().unwrap().len(), 1);
+
+        // Busy on every attempt: the channel is reported busy and nothing is sent.
+        probe
+            .busy_checks
+            .store(u8::MAX, std::sync::atomic::Ordering::SeqCst);
+        assert!(matches!(
+            handle.send_frame(&frame).await,
+            Err(WMBusError::RadioDriver(RadioDriverError::ChannelBusy {
+                rssi_dbm: -70
+            }))
+        ));
+        assert_eq!(probe.tx.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn receiver_answers_bidirectional_meters_in_their_window() {
+        use crate::wmbus::bidirectional::{ExchangeOutcome, GatewayCommand, PrimaryStationConfig};
+        use crate::wmbus::frame::parse_wmbus_frame;
+
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let mut handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+        assert!(handle
+            .queue_command(0x2C2D, 0x12345678, GatewayCommand::ReqUd2)
+            .await
+            .is_err());
+        handle
+            .enable_bidirectional(PrimaryStationConfig::default())
+            .unwrap();
+        handle
+            .queue_command(0x2C2D, 0x12345678, GatewayCommand::ReqUd2)
+            .await
+            .unwrap();
+        handle.start_receiver().await.unwrap();
+
+        // SND_NR with the B bit set opens a window; the meter answers REQ_UD2 with RSP_UD.
+        let snd_nr = WMBusFrame::build(
+            0x44,
+            0x2C2D,
+            0x12345678,
+            0x1B,
+            0x16,
+            0x7A,
+            &[0x2A, 0, 0x00, 0x80],
+        );
+        let rsp_ud = WMBusFrame::build(
+            0x08,
+            0x2C2D,
+            0x12345678,
+            0x1B,
+            0x16,
+            0x7A,
+            &[0x2B, 0, 0x00, 0x00, 0x0C, 0x13],
+        );
+        probe.rx.lock().unwrap().push_back(snd_nr);
+        probe.rx.lock().unwrap().push_back(rsp_ud);
+
+        let exchange = handle.recv_exchange(Some(1000)).await.unwrap();
+        assert_eq!(exchange.command, GatewayCommand::ReqUd2);
+        assert!(matches!(
+            exchange.outcome,
+            ExchangeOutcome::Answered(ref frame) if frame.control_field == 0x08
+        ));
+        let sent = probe.tx.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        // Sent in mode C2, the default when the receiver reports no mode, on its downlink
+        // channel; the radio then returns to the receive carrier.
+        assert_eq!(
+            *probe.tuned.lock().unwrap(),
+            [
+                (868_950_000, 100_000),
+                (869_525_000, 50_000),
+                (868_950_000, 100_000)
+            ]
+        );
+        let (_, link) = crate::wmbus::t1c1::decode_capture(&sent[0]).unwrap();
+        assert!(link.crc_ok);
+        let request = parse_wmbus_frame(&link.to_frame_bytes().unwrap()).unwrap();
+        assert_eq!(request.control_field, 0x7B);
+        assert_eq!(request.payload[0], 0x2A);
+
+        // Both meter frames still reach the ordinary receive path.
+        assert!(handle.recv_frame(Some(100)).await.is_ok());
+        assert!(handle.recv_frame(Some(100)).await.is_ok());
+        assert!(handle
+            .enable_bidirectional(PrimaryStationConfig::default())
+            .is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn receiver_times_the_window_from_the_rx_done_edge() {
+        use crate::wmbus::bidirectional::{GatewayCommand, PrimaryStationConfig};
+        use crate::wmbus::mode_switching::WMBusMode;
+
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let mut handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+        // T2 meters listen only 2–3 ms after their frame.
+        handle
+            .enable_bidirectional(PrimaryStationConfig {
+                mode: WMBusMode::T2,
+                ..Default::default()
+            })
+            .unwrap();
+        handle
+            .queue_command(0x2C2D, 0x12345678, GatewayCommand::ReqUd2)
+            .await
+            .unwrap();
+        let notifier = handle.rx_done_notifier();
+        handle.start_receiver().await.unwrap();
+
+        // The frame ends between two poll ticks; the interrupt must not wait for the next.
+        tokio::time::sleep(tokio::time::Duration::from_millis(3)).await;
+        probe.rx.lock().unwrap().push_back(WMBusFrame::build(
+            0x44,
+            0x2C2D,
+            0x12345678,
+            0x1B,
+            0x16,
+            0x7A,
+            &[0x2A, 0, 0x00, 0x80],
+        ));
+        let edge = tokio::time::Instant::now();
+        notifier.rx_done(edge);
+
+        handle.recv_frame(Some(100)).await.unwrap();
+        let sent_at = probe.tx_at.lock().unwrap().clone();
+        assert_eq!(sent_at.len(), 1);
+        assert!(sent_at[0] - edge <= tokio::time::Duration::from_millis(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn installation_window_registers_and_confirms_meters() {
+        use crate::wmbus::bidirectional::{ExchangeOutcome, GatewayCommand, PrimaryStationConfig};
+        use crate::wmbus::installation::InstallationConfig;
+
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let mut handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+        let auto_confirm = InstallationConfig {
+            auto_confirm: true,
+            ..Default::default()
+        };
+        assert!(handle
+            .open_installation_window(auto_confirm.clone())
+            .await
+            .is_err());
+        handle
+            .enable_bidirectional(PrimaryStationConfig::default())
+            .unwrap();
+        handle.open_installation_window(auto_confirm).await.unwrap();
+        handle.start_receiver().await.unwrap();
+
+        // The install button sends SND_IR (security mode 5) twice; one registration and
+        // one CNF_IR result.
+        let snd_ir = WMBusFrame::build(
+            0x46,
+            0x2C2D,
+            0x12345678,
+            0x1B,
+            0x07,
+            0x7A,
+            &[0x01, 0, 0x00, 0x05],
+        );
+        probe.rx.lock().unwrap().push_back(snd_ir.clone());
+        probe.rx.lock().unwrap().push_back(snd_ir);
+
+        let meter = handle.recv_installation(Some(1000)).await.unwrap();
+        assert_eq!(meter.meter.address, 0x12345678);
+        assert!(meter.key_needed);
+        let exchange = handle.recv_exchange(Some(1000)).await.unwrap();
+        assert_eq!(exchange.command, GatewayCommand::CnfIr);
+        assert!(matches!(exchange.outcome, ExchangeOutcome::Delivered));
+
+        assert!(handle.recv_installation(Some(100)).await.is_err());
+        let sent = probe.tx.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        let (_, link) = crate::wmbus::t1c1::decode_capture(&sent[0]).unwrap();
+        assert_eq!(link.control_field, 0x06);
+        let meters = handle.installed_meters().await;
+        assert_eq!(meters.len(), 1);
+        assert!(meters[0].confirmed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn subscribers_select_over_frames_and_timers() {
+        use tokio_stream::StreamExt;
+
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let mut handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+        let mut everything = handle.subscribe();
+        let mut filtered = handle.subscribe().manufacturer(0x6815).min_rssi(-60);
+        handle.start_receiver().await.unwrap();
+
+        probe.rx.lock().unwrap().push_back(valid_wmbus_bytes());
+        let timer = tokio::time::sleep(std::time::Duration::from_secs(1));
+        tokio::pin!(timer);
+        tokio::select! {
+            Some(item) = everything.next() => assert!(matches!(item, ReceivedItem::Wmbus { .. })),
+            _ = &mut timer => panic!("no frame before the timer"),
+        }
+        // The frame arrived at -64 dBm, below the second subscriber's threshold.
+        tokio::select! {
+            _ = filtered.next() => panic!("filtered frame leaked through"),
+            _ = &mut timer => {}
+        }
+        // The poll-style channel still gets its own copy.
+        assert!(handle.recv_item(Some(10)).await.is_ok());
+        handle.stop_receiver().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replay_guard_tags_replayed_frames() {
+        use crate::wmbus::replay_guard::{Freshness, ReplayGuard, ReplayGuardConfig};
+
+        let telegram = |acc: u8, value: u8| {
+            WMBusFrame::build(
+                0x44,
+                0x2C2D,
+                0x12345678,
+                0x1B,
+                0x16,
+                0x7A,
+                &[acc, 0x00, 0x00, 0x00, 0x2F, 0x2F, 0x02, 0x13, value, 0x00],
+            )
+        };
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let mut handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+        assert!(handle
+            .restore_replay_state(Default::default())
+            .await
+            .is_err());
+        handle
+            .enable_replay_guard(ReplayGuard::new(ReplayGuardConfig::default()))
+            .unwrap();
+        handle.start_receiver().await.unwrap();
+        assert!(handle.enable_replay_guard(ReplayGuard::default()).is_err());
+
+        for frame in [telegram(41, 1), telegram(42, 2), telegram(41, 1)] {
+            probe.rx.lock().unwrap().push_back(frame);
+        }
+        let mut verdicts = Vec::new();
+        for _ in 0..3 {
+            match handle.recv_item(Some(100)).await.unwrap() {
+                ReceivedItem::Wmbus {
+                    frame, freshness, ..
+                } => verdicts.push((frame.payload[0], freshness)),
+                other => panic!("expected Wmbus, got {other:?}"),
+            }
+        }
+        assert_eq!(
+            verdicts,
+            [
+                (41, Some(Freshness::Fresh)),
+                (42, Some(Freshness::Fresh)),
+                (41, Some(Freshness::Suspicious)),
+            ]
+        );
+
+        let state = handle.replay_state(0x2C2D, 0x12345678).await.unwrap();
+        assert_eq!((state.fresh, state.suspicious), (2, 1));
+        let stored = handle.replay_guard_state().await.unwrap();
+        handle.stop_receiver().await;
+
+        // After a restart the stored counters catch the replay straight away.
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let mut handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+        handle.enable_replay_guard(ReplayGuard::default()).unwrap();
+        handle.restore_replay_state(stored).await.unwrap();
+        handle.start_receiver().await.unwrap();
+        probe.rx.lock().unwrap().push_back(telegram(41, 1));
+        assert!(matches!(
+            handle.recv_item(Some(100)).await.unwrap(),
+            ReceivedItem::Wmbus {
+                freshness: Some(Freshness::Suspicious),
+                ..
+            }
+        ));
+        handle.stop_receiver().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn undrained_receive_channel_is_bounded() {
+        use tokio_stream::StreamExt;
+
+        let radio = QueueRadio::default();
+        let probe = radio.clone();
+        let mut handle = WMBusHandle::with_driver(radio, None).await.unwrap();
+        let mut everything = handle.subscribe();
+        handle.start_receiver().await.unwrap();
+
+        let total = super::RX_CHANNEL_CAPACITY + 3;
+        for _ in 0..total {
+            probe.rx.lock().unwrap().push_back(valid_wmbus_bytes());
+        }
+        // A subscribe-only consumer sees every frame...
+        for _ in 0..total {
+            assert!(everything.next().await.is_some());
+        }
+        // ...while the undrained poll channel stops at its capacity.
+        assert_eq!(handle.dropped_items(), 3);
+        for _ in 0..super::RX_CHANNEL_CAPACITY {
+            assert!(handle.recv_item(Some(10)).await.is_ok());
+        }
+        assert!(handle.recv_item(Some(10)).await.is_err());
+        handle.stop_receiver().await;
+    }
+
+    #[tokio::test]
+    async fn device_manager_accepts_any_radio_driver() {
+        let mut manager = crate::mbus_device_manager::MBusDeviceManager::new()
+            .await
+            .unwrap();
+        manager
+            .add_wmbus_handle_with_driver("queue0", QueueRadio::default(), None)
+            .await
+            .unwrap();
+        manager.disconnect_all().await.unwrap();
+    }
 }
@@ -0,0 +1,325 @@
+//! # Installation Mode (SND_IR Pairing)
+//!
+//! A meter whose install button is pressed sends installation requests (C-field 0x46,
+//! SND_IR). While a time-limited pairing window is open, the [`Installer`] registers each
+//! new meter in a [`DeviceInventory`] with its link header, mode, first RSSI and whether
+//! its telegrams need a key — instead of copying meter IDs off stickers.
+//!
+//! In bidirectional modes the meter waits for a confirmation (CNF_IR). With
+//! [`InstallationConfig::auto_confirm`] the handle queues one through the
+//! [`PrimaryStation`](crate::wmbus::bidirectional::PrimaryStation), completing the
+//! handshake; see
+//! [`WMBusHandle::open_installation_window`](crate::wmbus::handle::WMBusHandle::open_installation_window).
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use mbus_rs::wmbus::frame::{parse_wmbus_frame, WMBusFrame};
+//! use mbus_rs::wmbus::installation::{InstallationConfig, Installer};
+//! use tokio::time::Instant;
+//!
+//! let mut installer = Installer::new();
+//! let now = Instant::now();
+//! installer.open(InstallationConfig::default(), now);
+//!
+//! // SND_IR from a meter using security mode 5
+//! let raw = WMBusFrame::build(0x46, 0x2C2D, 0x12345678, 0x1B, 0x07, 0x7A, &[0x01, 0, 0x00, 0x05]);
+//! let meter = installer.on_frame(&parse_wmbus_frame(&raw).unwrap(), None, -72, now).unwrap();
+//! assert!(meter.key_needed);
+//! assert_eq!(installer.inventory().len(), 1);
+//! ```
+
+use crate::wmbus::bidirectional::{MeterAddress, C_SND_IR};
+use crate::wmbus::bitstream::LinkMode;
+use crate::wmbus::frame::WMBusFrame;
+use std::collections::HashMap;
+use tokio::time::{Duration, Instant};
+
+/// Pairing window settings
+#[derive(Debug, Clone)]
+pub struct InstallationConfig {
+    /// How long the window stays open
+    pub duration: Duration,
+    /// Confirm installation requests with CNF_IR (bidirectional modes only)
+    pub auto_confirm: bool,
+}
+
+impl Default for InstallationConfig {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(300),
+            auto_confirm: false,
+        }
+    }
+}
+
+/// A meter registered through an installation request
+#[derive(Debug, Clone)]
+pub struct InstalledMeter {
+    /// Link layer address (manufacturer, ID, version, device type)
+    pub meter: MeterAddress,
+    /// CI field of the installation request
+    pub control_info: u8,
+    /// Link mode the request arrived in, if the receiver detected it
+    pub mode: Option<LinkMode>,
+    /// RSSI of the first installation request (dBm)
+    pub first_rssi_dbm: i16,
+    /// Security mode announced in the configuration word (0 = unencrypted)
+    pub security_mode: u8,
+    /// Whether a key must be provisioned to read the meter's telegrams
+    pub key_needed: bool,
+    /// Whether the meter received a CNF_IR
+    pub confirmed: bool,
+    /// When the meter was registered
+    pub installed_at: Instant,
+}
+
+impl InstalledMeter {
+    fn from_request(
+        frame: &WMBusFrame,
+        rssi_dbm: i16,
+        mode: Option<LinkMode>,
+        now: Instant,
+    ) -> Self {
+        let security_mode = frame.config_word().map_or(0, |cw| ((cw >> 8) & 0x1F) as u8);
+        Self {
+            meter: MeterAddress::of(frame),
+            control_info: frame.control_info,
+            mode,
+            first_rssi_dbm: rssi_dbm,
+            security_mode,
+            key_needed: security_mode != 0,
+            confirmed: false,
+            installed_at: now,
+        }
+    }
+}
+
+/// Meters registered by installation requests, keyed by manufacturer and address
+#[derive(Debug, Clone, Default)]
+pub struct DeviceInventory {
+    meters: HashMap<(u16, u32), InstalledMeter>,
+}
+
+impl DeviceInventory {
+    /// Look up a registered meter
+    pub fn get(&self, manufacturer_id: u16, address: u32) -> Option<&InstalledMeter> {
+        self.meters.get(&(manufacturer_id, address))
+    }
+
+    /// Remove a meter (e.g. to pair it again)
+    pub fn remove(&mut self, manufacturer_id: u16, address: u32) -> Option<InstalledMeter> {
+        self.meters.remove(&(manufacturer_id, address))
+    }
+
+    /// All registered meters, oldest first
+    pub fn meters(&self) -> Vec<InstalledMeter> {
+        let mut meters: Vec<InstalledMeter> = self.meters.values().cloned().collect();
+        meters.sort_by_key(|m| m.installed_at);
+        meters
+    }
+
+    /// Number of registered meters
+    pub fn len(&self) -> usize {
+        self.meters.len()
+    }
+
+    /// Whether no meter is registered
+    pub fn is_empty(&self) -> bool {
+        self.meters.is_empty()
+    }
+}
+
+/// Pairing window and the inventory it fills
+#[derive(Debug, Default)]
+pub struct Installer {
+    config: InstallationConfig,
+    open_until: Option<Instant>,
+    inventory: DeviceInventory,
+}
+
+impl Installer {
+    /// Create an installer with its window closed and an empty inventory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open (or re-open) the pairing window at `now`
+    pub fn open(&mut self, config: InstallationConfig, now: Instant) {
+        self.open_until = Some(now + config.duration);
+        self.config = config;
+    }
+
+    /// Close the pairing window early
+    pub fn close(&mut self) {
+        self.open_until = None;
+    }
+
+    /// Whether installation requests are accepted at `now`
+    pub fn is_open(&self, now: Instant) -> bool {
+        self.open_until.is_some_and(|until| now < until)
+    }
+
+    /// Settings of the current (or last) window
+    pub fn config(&self) -> &InstallationConfig {
+        &self.config
+    }
+
+    /// Meters registered so far
+    pub fn inventory(&self) -> &DeviceInventory {
+        &self.inventory
+    }
+
+    /// Mutable access to the inventory
+    pub fn inventory_mut(&mut self) -> &mut DeviceInventory {
+        &mut self.inventory
+    }
+
+    /// Handle a frame received in `link_mode`
+    ///
+    /// Registers the sender if the frame is an installation request from an unknown
+    /// meter and the window is open; returns the new entry. Repeated requests (the
+    /// meter retries until confirmed) leave the first entry untouched.
+    pub fn on_frame(
+        &mut self,
+        frame: &WMBusFrame,
+        link_mode: Option<LinkMode>,
+        rssi_dbm: i16,
+        now: Instant,
+    ) -> Option<InstalledMeter> {
+        if frame.control_field != C_SND_IR || !self.is_open(now) {
+            return None;
+        }
+        let key = (frame.manufacturer_id, frame.device_address);
+        if self.inventory.meters.contains_key(&key) {
+            return None;
+        }
+        let meter = InstalledMeter::from_request(frame, rssi_dbm, link_mode, now);
+        log::info!(
+            "Installed meter {:#010X} (manufacturer {:#06X}, key needed: {})",
+            meter.meter.address,
+            meter.meter.manufacturer_id,
+            meter.key_needed
+        );
+        self.inventory.meters.insert(key, meter.clone());
+        Some(meter)
+    }
+
+    /// Whether `frame` is an installation request that should be confirmed now
+    ///
+    /// True while the window is open, auto-confirm is on and the registered sender
+    /// has not been confirmed yet.
+    pub fn wants_confirmation(&self, frame: &WMBusFrame, now: Instant) -> bool {
+        frame.control_field == C_SND_IR
+            && self.config.auto_confirm
+            && self.is_open(now)
+            && self
+                .inventory
+                .get(frame.manufacturer_id, frame.device_address)
+                .is_some_and(|m| !m.confirmed)
+    }
+
+    /// Record that a meter received its CNF_IR
+    pub fn mark_confirmed(&mut self, manufacturer_id: u16, address: u32) {
+        if let Some(meter) = self.inventory.meters.get_mut(&(manufacturer_id, address)) {
+            meter.confirmed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::frame::parse_wmbus_frame;
+
+    fn request(c: u8, address: u32, cw: u16) -> WMBusFrame {
+        let [lo, hi] = cw.to_le_bytes();
+        let raw = WMBusFrame::build(c, 0x2C2D, address, 0x1B, 0x07, 0x7A, &[0x01, 0, lo, hi]);
+        parse_wmbus_frame(&raw).unwrap()
+    }
+
+    #[test]
+    fn registers_new_meters_only_while_the_window_is_open() {
+        let mut installer = Installer::new();
+        let t0 = Instant::now();
+
+        // Closed: nothing is registered.
+        assert!(installer
+            .on_frame(&request(0x46, 1, 0), None, -70, t0)
+            .is_none());
+
+        installer.open(
+            InstallationConfig {
+                duration: Duration::from_secs(60),
+                auto_confirm: false,
+            },
+            t0,
+        );
+        // Ordinary data frames do not pair.
+        assert!(installer
+            .on_frame(&request(0x44, 1, 0), None, -70, t0)
+            .is_none());
+
+        let meter = installer
+            .on_frame(&request(0x46, 1, 0x0000), Some(LinkMode::T), -70, t0)
+            .unwrap();
+        assert_eq!(meter.meter.address, 1);
+        assert_eq!(meter.mode, Some(LinkMode::T));
+        assert!(!meter.key_needed);
+
+        // A retry keeps the first RSSI.
+        assert!(installer
+            .on_frame(&request(0x46, 1, 0), None, -50, t0)
+            .is_none());
+        assert_eq!(
+            installer.inventory().get(0x2C2D, 1).unwrap().first_rssi_dbm,
+            -70
+        );
+
+        let encrypted = installer
+            .on_frame(
+                &request(0x46, 2, 0x0700),
+                None,
+                -80,
+                t0 + Duration::from_secs(59),
+            )
+            .unwrap();
+        assert_eq!(encrypted.security_mode, 7);
+        assert!(encrypted.key_needed);
+
+        // Expired.
+        assert!(installer
+            .on_frame(
+                &request(0x46, 3, 0),
+                None,
+                -80,
+                t0 + Duration::from_secs(60)
+            )
+            .is_none());
+        assert_eq!(installer.inventory().len(), 2);
+        assert_eq!(installer.inventory().meters()[0].meter.address, 1);
+    }
+
+    #[test]
+    fn confirmation_is_wanted_until_delivered() {
+        let mut installer = Installer::new();
+        let t0 = Instant::now();
+        installer.open(
+            InstallationConfig {
+                auto_confirm: true,
+                ..Default::default()
+            },
+            t0,
+        );
+        let frame = request(0x46, 1, 0);
+        assert!(!installer.wants_confirmation(&frame, t0));
+        installer.on_frame(&frame, None, -70, t0);
+        assert!(installer.wants_confirmation(&frame, t0));
+        installer.mark_confirmed(0x2C2D, 1);
+        assert!(!installer.wants_confirmation(&frame, t0));
+        assert!(installer.inventory().get(0x2C2D, 1).unwrap().confirmed);
+
+        installer.close();
+        assert!(!installer.is_open(t0));
+    }
+}
@@ -0,0 +1,199 @@
+//! # wM-Bus line codes (EN 13757-4 physical layer)
+//!
+//! The link-layer bytes of a wM-Bus telegram are put on air with a mode-specific line
+//! code. This module converts between bytes and *chips* (the on-air symbols, one `0`/`1`
+//! per element, transmitted first-to-last):
+//!
+//! | mode | line code      | chips per byte |
+//! |------|----------------|----------------|
+//! | T    | 3-out-of-6     | 12             |
+//! | S    | Manchester     | 16             |
+//! | C, N | NRZ (none)     | 8              |
+//!
+//! Every byte is sent MSB-first. For 3-out-of-6 the high nibble is sent first, each
+//! nibble as the 6-chip code word from [`THREE_OF_SIX`]; for Manchester a `1` is sent as
+//! `10` and a `0` as `01`.
+//!
+//! Decoders never fail: an invalid code word (or an invalid `00`/`11` Manchester pair)
+//! decodes as zero bits and is counted in the returned symbol-error total, so a caller
+//! can keep the candidate frame and let the block CRCs decide.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use mbus_rs::wmbus::line_code::{decode_3of6, encode_3of6};
+//!
+//! let chips = encode_3of6(&[0x44, 0x2D]);
+//! assert_eq!(chips.len(), 24);
+//! assert_eq!(decode_3of6(&chips), (vec![0x44, 0x2D], 0));
+//! ```
+
+/// The 16 mode-T 3-out-of-6 code words, indexed by nibble value (6 significant bits,
+/// first chip in bit 5).
+pub const THREE_OF_SIX: [u8; 16] = [
+    0x16, 0x0D, 0x0E, 0x0B, 0x1C, 0x19, 0x1A, 0x13, 0x2C, 0x25, 0x26, 0x23, 0x34, 0x31, 0x32, 0x29,
+];
+
+/// Reverse lookup for [`THREE_OF_SIX`]: code word → nibble, `0xFF` for invalid words.
+const THREE_OF_SIX_DECODE: [u8; 64] = build_3of6_decode();
+
+const fn build_3of6_decode() -> [u8; 64] {
+    let mut table = [0xFFu8; 64];
+    let mut nibble = 0;
+    while nibble < 16 {
+        table[THREE_OF_SIX[nibble] as usize] = nibble as u8;
+        nibble += 1;
+    }
+    table
+}
+
+/// Decode one 6-bit 3-out-of-6 code word to its nibble, or `None` if it is not valid.
+#[inline]
+pub fn decode_3of6_symbol(code: u8) -> Option<u8> {
+    match THREE_OF_SIX_DECODE[(code & 0x3F) as usize] {
+        0xFF => None,
+        nibble => Some(nibble),
+    }
+}
+
+/// Expand bytes into chips, MSB-first (the NRZ line code).
+pub fn bytes_to_chips(bytes: &[u8]) -> Vec<u8> {
+    let mut chips = Vec::with_capacity(bytes.len() * 8);
+    for &b in bytes {
+        for k in (0..8).rev() {
+            chips.push((b >> k) & 1);
+        }
+    }
+    chips
+}
+
+/// Pack chips into bytes, MSB-first. A trailing partial byte is zero-padded.
+pub fn chips_to_bytes(chips: &[u8]) -> Vec<u8> {
+    chips
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |acc, (i, &c)| acc | ((c & 1) << (7 - i)))
+        })
+        .collect()
+}
+
+/// Encode bytes with the mode-T 3-out-of-6 line code (12 chips per byte).
+pub fn encode_3of6(data: &[u8]) -> Vec<u8> {
+    let mut chips = Vec::with_capacity(data.len() * 12);
+    for &b in data {
+        for nibble in [b >> 4, b & 0x0F] {
+            let code = THREE_OF_SIX[nibble as usize];
+            for k in (0..6).rev() {
+                chips.push((code >> k) & 1);
+            }
+        }
+    }
+    chips
+}
+
+/// Decode 3-out-of-6 chips into bytes, returning `(bytes, symbol_errors)`.
+///
+/// Only whole 12-chip groups are decoded. Each invalid code word decodes as nibble `0`
+/// and adds one to `symbol_errors`.
+pub fn decode_3of6(chips: &[u8]) -> (Vec<u8>, usize) {
+    let mut out = Vec::with_capacity(chips.len() / 12);
+    let mut errors = 0;
+    for group in chips.chunks_exact(12) {
+        let mut byte = 0u8;
+        for word in group.chunks_exact(6) {
+            let code = word.iter().fold(0u8, |acc, &c| (acc << 1) | (c & 1));
+            let nibble = decode_3of6_symbol(code).unwrap_or_else(|| {
+                errors += 1;
+                0
+            });
+            byte = (byte << 4) | nibble;
+        }
+        out.push(byte);
+    }
+    (out, errors)
+}
+
+/// Encode bytes with the mode-S Manchester line code (16 chips per byte, `1` → `10`).
+pub fn encode_manchester(data: &[u8]) -> Vec<u8> {
+    let mut chips = Vec::with_capacity(data.len() * 16);
+    for bit in bytes_to_chips(data) {
+        chips.push(bit);
+        chips.push(bit ^ 1);
+    }
+    chips
+}
+
+/// Decode Manchester chips into bytes, returning `(bytes, symbol_errors)`.
+///
+/// Only whole 16-chip groups are decoded. Each `00`/`11` pair decodes as a `0` bit and
+/// adds one to `symbol_errors`.
+pub fn decode_manchester(chips: &[u8]) -> (Vec<u8>, usize) {
+    let mut bits = Vec::with_capacity(chips.len() / 2);
+    let mut errors = 0;
+    let whole = chips.len() - chips.len() % 16;
+    for pair in chips[..whole].chunks_exact(2) {
+        match (pair[0] & 1, pair[1] & 1) {
+            (1, 0) => bits.push(1),
+            (0, 1) => bits.push(0),
+            _ => {
+                errors += 1;
+                bits.push(0);
+            }
+        }
+    }
+    (chips_to_bytes(&bits), errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn three_of_six_words_have_three_ones() {
+        for code in THREE_OF_SIX {
+            assert_eq!(code.count_ones(), 3, "{code:06b}");
+        }
+        // Exactly the 16 table entries decode; the other 48 six-bit words are invalid.
+        assert_eq!((0u8..64).filter_map(decode_3of6_symbol).count(), 16);
+    }
+
+    #[test]
+    fn three_of_six_round_trip_all_bytes() {
+        let data: Vec<u8> = (0..=255).collect();
+        let chips = encode_3of6(&data);
+        assert_eq!(chips.len(), 256 * 12);
+        assert_eq!(decode_3of6(&chips), (data, 0));
+    }
+
+    #[test]
+    fn three_of_six_counts_invalid_words() {
+        let mut chips = encode_3of6(&[0x12, 0x34]);
+        // 000000 is never a valid code word.
+        chips[..6].fill(0);
+        let (bytes, errors) = decode_3of6(&chips);
+        assert_eq!(errors, 1);
+        assert_eq!(bytes, vec![0x02, 0x34]);
+    }
+
+    #[test]
+    fn manchester_round_trip_and_errors() {
+        let chips = encode_manchester(&[0xA5]);
+        assert_eq!(chips, vec![1, 0, 0, 1, 1, 0, 0, 1, 0, 1, 1, 0, 0, 1, 1, 0]);
+        assert_eq!(decode_manchester(&chips), (vec![0xA5], 0));
+
+        let mut bad = chips.clone();
+        bad[1] = 1; // first pair becomes 11
+        assert_eq!(decode_manchester(&bad), (vec![0x25], 1));
+    }
+
+    #[test]
+    fn chips_bytes_round_trip() {
+        let bytes = [0x54, 0x3D, 0xCD];
+        assert_eq!(chips_to_bytes(&bytes_to_chips(&bytes)), bytes.to_vec());
+        // Partial trailing byte is zero-padded.
+        assert_eq!(chips_to_bytes(&[1, 1]), vec![0xC0]);
+    }
+}
@@ -4,20 +4,32 @@
 //! M-Bus (wM-Bus) protocol, which is an extension of the wired M-Bus protocol
 //! for wireless communication with utility meters.
 //!
+pub mod bidirectional;
+pub mod bitstream;
 pub mod block;
 pub mod compact_cache;
 pub mod crc;
 pub mod crypto;
 pub mod crypto_hardware;
+pub mod dedup;
 pub mod encryption;
 pub mod frame;
 pub mod frame_decode;
 pub mod handle;
+pub mod installation;
+pub mod line_code;
 pub mod mode_c;
 pub mod mode_switching;
 pub mod network;
+pub mod prediction;
 pub mod radio;
+pub mod replay_guard;
+pub mod sdr;
 pub mod sha_hardware;
+pub mod sim;
+pub mod stream;
+pub mod t1c1;
+pub mod telegram;
 
 pub use radio::{
     driver::Sx126xDriver,
@@ -30,12 +42,14 @@ pub use radio::{
     rfm69_packet, rfm69_registers,
 };
 
-// Re-export RFM69 driver when feature is enabled
-#[cfg(feature = "rfm69")]
+// Re-export RFM69 driver
 pub use radio::rfm69::{Rfm69Config, Rfm69Driver, Rfm69Error, Rfm69Mode};
 
 // Re-export the necessary types and functions from the submodules
-pub use compact_cache::{CacheStats, CachedDeviceInfo, CompactFrameCache};
+pub use compact_cache::{
+    CacheStats, CachedDeviceInfo, CompactFrameCache, CompactFrameError, ExpandedFrame,
+    FormatTemplate,
+};
 pub use crypto::{AesKey, CryptoError, DeviceInfo, EncryptionMode, WMBusCrypto};
 pub use encryption::WMBusEncryption;
 pub use frame::WMBusFrame;
@@ -16,9 +16,11 @@
 //! - **Type A**: block 0 = 10 header bytes (L, C, M, M, A, A, A, A, V, T) + CRC; then
 //!   16-byte data blocks (last may be short) each + CRC. The de-blocked payload is the
 //!   concatenation of the data blocks and begins with the CI byte.
-//! - **Type B**: a single CRC covers block 0 = `L-1` bytes from the L-field; the payload
-//!   (CI onward) is the tail of block 0 after the 10-byte header. An optional block 2
-//!   with its own trailing CRC follows for long telegrams.
+//! - **Type B**: a single CRC covers block 0 = `L-1` bytes from the L-field (at most
+//!   125); the payload (CI onward) is the tail of block 0 after the 10-byte header.
+//!   Longer telegrams continue in a block 2 with its own trailing CRC.
+//!
+//! [`encode_mode_c`] produces the same framing from an unblocked link frame.
 //!
 //! ## Why this exists separately from [`FrameDecoder`]
 //!
@@ -37,12 +39,20 @@ const TYPE_A_SYNC: u8 = 0xCD;
 /// Normalized Type B sync/type byte.
 const TYPE_B_SYNC: u8 = 0x3D;
 
+/// N-/F-mode sync word announcing frame format A.
+pub const NF_SYNC_FORMAT_A: u16 = 0xF68D;
+/// N-/F-mode sync word announcing frame format B.
+pub const NF_SYNC_FORMAT_B: u16 = 0xF672;
+
 /// Link header length in bytes: L, C, M, M, A, A, A, A, V, T.
 const HEADER_LEN: usize = 10;
 /// Type A data block payload length (before its trailing CRC).
 const TYPE_A_BLOCK_LEN: usize = 16;
 /// Per-block CRC length.
 const CRC_LEN: usize = 2;
+/// Most bytes (from the L-field) covered by the first Type B CRC: block 1 (10 bytes)
+/// plus block 2 (CI and up to 115 data bytes).
+const TYPE_B_FIRST_BLOCK_LEN: usize = 126;
 
 /// A decoded wM-Bus mode-C link-layer frame.
 ///
@@ -59,7 +69,12 @@ pub struct WMBusLinkFrame {
     /// [`crate::id_to_manufacturer`].
     pub manufacturer_id: u16,
     /// Meter address, BCD-decoded to its decimal id (e.g. wire `44 44 64 74` → `74644444`).
+    ///
+    /// For display and lookup only: a nibble above 9 has no decimal digit, so this is
+    /// not a faithful copy of the wire address. Use [`address_bytes`](Self::address_bytes).
     pub device_address: u32,
+    /// Meter address exactly as received (4 bytes, little-endian on the wire).
+    pub address_bytes: [u8; 4],
     /// Device version byte.
     pub version: u8,
     /// Device type byte.
@@ -82,6 +97,34 @@ impl WMBusLinkFrame {
     pub fn application_data(&self) -> &[u8] {
         self.payload.get(1..).unwrap_or(&[])
     }
+
+    /// Re-encode as the single-CRC radio frame that
+    /// [`WMBusFrame::build`](crate::wmbus::frame::WMBusFrame::build) produces, so it can be
+    /// handed to [`parse_wmbus_frame`](crate::wmbus::frame::parse_wmbus_frame).
+    ///
+    /// The address is copied from [`address_bytes`](Self::address_bytes), so addresses
+    /// that are not valid BCD pass through unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DecodeError::BlockCrcFailed`] unless [`crc_ok`](Self::crc_ok) is set, so
+    /// no field of a corrupted frame is handed on, and
+    /// [`DecodeError::NoApplicationLayer`] for frames without a CI byte.
+    pub fn to_frame_bytes(&self) -> Result<Vec<u8>, DecodeError> {
+        if !self.crc_ok {
+            return Err(DecodeError::BlockCrcFailed);
+        }
+        let ci = self.ci().ok_or(DecodeError::NoApplicationLayer)?;
+        Ok(crate::wmbus::frame::WMBusFrame::build(
+            self.control_field,
+            self.manufacturer_id,
+            u32::from_le_bytes(self.address_bytes),
+            self.version,
+            self.device_type,
+            ci,
+            self.application_data(),
+        ))
+    }
 }
 
 /// Validate one block: its trailing CRC (big-endian) against the canonical CRC of `data`.
@@ -92,7 +135,8 @@ fn verify_block(data: &[u8], crc_bytes: &[u8]) -> bool {
 /// Decode a 4-byte BCD meter address (little-endian on the wire) to its decimal id.
 ///
 /// Matches the meter id printed on the device and the epulse `DecodeBCD` reference:
-/// wire bytes `44 44 64 74` → `74644444`.
+/// wire bytes `44 44 64 74` → `74644444`. Nibbles above 9 are weighted like digits, so
+/// any 4 bytes (noise included) decode to at most 166_666_665 without overflow.
 fn bcd4(bytes: &[u8]) -> u32 {
     let mut v = 0u32;
     for &b in bytes.iter().rev() {
@@ -101,6 +145,22 @@ fn bcd4(bytes: &[u8]) -> u32 {
     v
 }
 
+/// Inverse of [`bcd4`]: the decimal id as the raw little-endian `u32` of its BCD bytes.
+///
+/// Fails for ids of more than 8 digits, which 4 BCD bytes cannot hold.
+pub(crate) fn bcd4_encode(id: u32) -> Result<u32, DecodeError> {
+    if id > 99_999_999 {
+        return Err(DecodeError::AddressNotBcd { address: id });
+    }
+    let mut v = 0u32;
+    let mut rest = id;
+    for shift in (0..32).step_by(4) {
+        v |= (rest % 10) << shift;
+        rest /= 10;
+    }
+    Ok(v)
+}
+
 /// Decode a normalized mode-C wM-Bus frame into its link header and de-blocked payload.
 ///
 /// `raw` must start with the normalized type byte (`0xCD` Type A / `0x3D` Type B). The
@@ -148,7 +208,8 @@ pub fn decode_mode_c(raw: &[u8]) -> Result<WMBusLinkFrame, DecodeError> {
     let l = raw[1] as usize;
     let control_field = raw[2];
     let manufacturer_id = u16::from_le_bytes([raw[3], raw[4]]);
-    let device_address = bcd4(&raw[5..9]);
+    let address_bytes = [raw[5], raw[6], raw[7], raw[8]];
+    let device_address = bcd4(&address_bytes);
     let version = raw[9];
     let device_type = raw[10];
 
@@ -185,7 +246,7 @@ pub fn decode_mode_c(raw: &[u8]) -> Result<WMBusLinkFrame, DecodeError> {
         if l < 1 {
             return Err(DecodeError::InvalidLength { length: raw[1] });
         }
-        let b0 = l - 1;
+        let b0 = (l - 1).min(TYPE_B_FIRST_BLOCK_LEN);
         if 1 + b0 + CRC_LEN > raw.len() {
             return Err(DecodeError::BufferTooShort {
                 needed: 1 + b0 + CRC_LEN,
@@ -197,11 +258,21 @@ pub fn decode_mode_c(raw: &[u8]) -> Result<WMBusLinkFrame, DecodeError> {
         if 1 + b0 > 1 + HEADER_LEN {
             payload.extend_from_slice(&raw[1 + HEADER_LEN..1 + b0]);
         }
-        // Optional block 2 (with its own trailing CRC) for long telegrams.
-        let after = 1 + b0 + CRC_LEN;
-        if after + CRC_LEN <= raw.len() {
-            let b2 = &raw[after..raw.len() - CRC_LEN];
-            if !verify_block(b2, &raw[raw.len() - CRC_LEN..]) {
+        // Block 2 (with its own trailing CRC) for telegrams longer than block 0.
+        if l - 1 > TYPE_B_FIRST_BLOCK_LEN {
+            let start = 1 + b0 + CRC_LEN;
+            let end = 2 + l;
+            if end < start + 1 + CRC_LEN {
+                return Err(DecodeError::InvalidLength { length: raw[1] });
+            }
+            if end > raw.len() {
+                return Err(DecodeError::BufferTooShort {
+                    needed: end,
+                    actual: raw.len(),
+                });
+            }
+            let b2 = &raw[start..end - CRC_LEN];
+            if !verify_block(b2, &raw[end - CRC_LEN..end]) {
                 crc_ok = false;
             }
             payload.extend_from_slice(b2);
@@ -217,6 +288,7 @@ pub fn decode_mode_c(raw: &[u8]) -> Result<WMBusLinkFrame, DecodeError> {
         control_field,
         manufacturer_id,
         device_address,
+        address_bytes,
         version,
         device_type,
         payload,
@@ -224,6 +296,117 @@ pub fn decode_mode_c(raw: &[u8]) -> Result<WMBusLinkFrame, DecodeError> {
     })
 }
 
+/// Encode an unblocked link frame into normalized mode-C framing: the type byte, the
+/// L-field and the blocks of `link`, each followed by its CRC (big-endian).
+///
+/// `link` is the frame after the L-field without any CRC — C, M, A, V, T, then the
+/// payload from the CI byte on — so the L-field is computed here ([`decode_mode_c`] is
+/// the inverse). Returns `None` for [`FrameType::Unknown`], a `link` shorter than the
+/// link header, or one too long for the L-field.
+///
+/// # Example
+///
+/// ```
+/// use mbus_rs::wmbus::frame_decode::FrameType;
+/// use mbus_rs::wmbus::mode_c::{decode_mode_c, encode_mode_c};
+///
+/// let link = [0x44, 0x2D, 0x2C, 0x44, 0x44, 0x64, 0x74, 0x1B, 0x16, 0x7A, 0x01, 0x02];
+/// for frame_type in [FrameType::TypeA, FrameType::TypeB] {
+///     let f = decode_mode_c(&encode_mode_c(frame_type, &link).unwrap()).unwrap();
+///     assert!(f.crc_ok);
+///     assert_eq!(f.device_address, 74644444);
+///     assert_eq!(f.payload, [0x7A, 0x01, 0x02]);
+/// }
+/// ```
+pub fn encode_mode_c(frame_type: FrameType, link: &[u8]) -> Option<Vec<u8>> {
+    if link.len() < HEADER_LEN - 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(1 + 1 + link.len() + 2 * CRC_LEN * link.len().div_ceil(8));
+    match frame_type {
+        FrameType::TypeA => {
+            out.push(TYPE_A_SYNC);
+            let mut header = vec![u8::try_from(link.len()).ok()?];
+            header.extend_from_slice(&link[..HEADER_LEN - 1]);
+            push_block(&mut out, &header);
+            for block in link[HEADER_LEN - 1..].chunks(TYPE_A_BLOCK_LEN) {
+                push_block(&mut out, block);
+            }
+        }
+        FrameType::TypeB => {
+            let blocks = if 1 + link.len() > TYPE_B_FIRST_BLOCK_LEN {
+                2
+            } else {
+                1
+            };
+            out.push(TYPE_B_SYNC);
+            let mut frame = vec![u8::try_from(link.len() + blocks * CRC_LEN).ok()?];
+            frame.extend_from_slice(link);
+            let (block0, block2) = frame.split_at(frame.len().min(TYPE_B_FIRST_BLOCK_LEN));
+            push_block(&mut out, block0);
+            if !block2.is_empty() {
+                push_block(&mut out, block2);
+            }
+        }
+        FrameType::Unknown => return None,
+    }
+    Some(out)
+}
+
+/// Append `data` and its CRC (big-endian).
+fn push_block(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(data);
+    out.extend_from_slice(&calculate_wmbus_crc(data).to_be_bytes());
+}
+
+/// Decode an N-mode (or F-mode) frame received after `sync_word`.
+///
+/// N and F modes use the same Type A / Type B block framing and CRCs as mode C, but
+/// the frame format is announced by the sync word ([`NF_SYNC_FORMAT_A`] /
+/// [`NF_SYNC_FORMAT_B`]) instead of a type byte, so `frame` starts at the L-field.
+///
+/// # Errors
+///
+/// Returns [`DecodeError::InvalidHeader`] for any other sync word, otherwise as
+/// [`decode_mode_c`].
+pub fn decode_n_mode(sync_word: u16, frame: &[u8]) -> Result<WMBusLinkFrame, DecodeError> {
+    let type_byte = match sync_word {
+        NF_SYNC_FORMAT_A => TYPE_A_SYNC,
+        NF_SYNC_FORMAT_B => TYPE_B_SYNC,
+        _ => return Err(DecodeError::InvalidHeader),
+    };
+    let mut raw = Vec::with_capacity(frame.len() + 1);
+    raw.push(type_byte);
+    raw.extend_from_slice(frame);
+    decode_mode_c(&raw)
+}
+
+/// Decode an N-/F-mode capture taken after `sync_word` into the radio frame
+/// [`parse_wmbus_frame`](crate::wmbus::frame::parse_wmbus_frame) accepts.
+///
+/// This is what the N- and F-mode receive paths hand on. Bytes beyond the frame
+/// (fixed-length captures run past it) are ignored. Captures under an unknown sync
+/// word, with a failed block CRC or no application layer yield `None`.
+pub fn nf_capture_to_frame(sync_word: u16, capture: &[u8]) -> Option<Vec<u8>> {
+    match decode_n_mode(sync_word, capture) {
+        Ok(link) if link.crc_ok => match link.to_frame_bytes() {
+            Ok(frame) => Some(frame),
+            Err(e) => {
+                log::debug!("Dropping N/F capture: {e}");
+                None
+            }
+        },
+        Ok(_) => {
+            log::debug!("Dropping N/F capture with failed block CRC");
+            None
+        }
+        Err(e) => {
+            log::debug!("Dropping unclassifiable N/F capture: {e}");
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,6 +428,56 @@ mod tests {
         assert!(f.crc_ok);
     }
 
+    #[test]
+    fn n_mode_sync_word_selects_the_frame_format() {
+        // The same Type B frame, received in N-mode: no type byte, format from the sync.
+        let raw = hex::decode(
+            "25442d2c444464741b168d208d3048a121f6597959d56873b609a439b99d58531a8a726d9f0c",
+        )
+        .unwrap();
+        let f = decode_n_mode(NF_SYNC_FORMAT_B, &raw).unwrap();
+        assert_eq!(f.frame_type, FrameType::TypeB);
+        assert_eq!(f.device_address, 74_644_444);
+        assert!(f.crc_ok);
+
+        // Read as format A the block CRCs fail; an unknown sync is rejected outright.
+        assert!(!decode_n_mode(NF_SYNC_FORMAT_A, &raw).unwrap().crc_ok);
+        assert_eq!(
+            decode_n_mode(0x543D, &raw).unwrap_err(),
+            DecodeError::InvalidHeader
+        );
+    }
+
+    #[test]
+    fn nf_capture_decodes_the_format_of_its_sync_word() {
+        let frame = hex::decode(
+            "25442d2c444464741b168d208d3048a121f6597959d56873b609a439b99d58531a8a726d9f0c",
+        )
+        .unwrap();
+        let mut b = frame.clone();
+        b.extend_from_slice(&[0xA5; 12]); // fixed-length capture runs past the frame
+        let parsed = crate::wmbus::frame::parse_wmbus_frame(
+            &nf_capture_to_frame(NF_SYNC_FORMAT_B, &b).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(parsed.control_info, 0x8D);
+        // The same bytes under the format A sync word fail their block CRCs.
+        assert!(nf_capture_to_frame(NF_SYNC_FORMAT_A, &b).is_none());
+
+        let link = [
+            0x44, 0x2D, 0x2C, 0x44, 0x44, 0x64, 0x74, 0x1B, 0x16, 0x7A, 0x01, 0x02,
+        ];
+        let a = encode_mode_c(FrameType::TypeA, &link).unwrap();
+        let parsed = crate::wmbus::frame::parse_wmbus_frame(
+            &nf_capture_to_frame(NF_SYNC_FORMAT_A, &a[1..]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(parsed.payload, [0x01, 0x02]);
+
+        assert!(nf_capture_to_frame(0x543D, &a[1..]).is_none());
+        assert!(nf_capture_to_frame(NF_SYNC_FORMAT_A, &[]).is_none());
+    }
+
     #[test]
     fn rejects_unknown_type_byte() {
         let err = decode_mode_c(&[
@@ -260,9 +493,98 @@ mod tests {
         assert!(matches!(err, DecodeError::BufferTooShort { .. }));
     }
 
+    #[test]
+    fn encoded_frames_decode_including_long_type_b() {
+        let mut link = vec![0x44, 0x2D, 0x2C, 0x78, 0x56, 0x34, 0x12, 0x1B, 0x07, 0x7A];
+        link.extend((0..200u16).map(|i| (i * 7) as u8));
+        for frame_type in [FrameType::TypeA, FrameType::TypeB] {
+            let raw = encode_mode_c(frame_type, &link).unwrap();
+            let f = decode_mode_c(&raw).unwrap();
+            assert_eq!(f.frame_type, frame_type);
+            assert!(f.crc_ok);
+            assert_eq!(f.device_address, 12_345_678);
+            assert_eq!(f.payload, link[9..]);
+            // Block framing matches what the packet engine sizes the capture as.
+            let size = crate::wmbus::radio::rfm69_packet::packet_size(&raw[..2]);
+            assert_eq!(size as usize, raw.len());
+        }
+
+        // A flipped bit in Type B block 2 fails only its CRC.
+        let mut raw = encode_mode_c(FrameType::TypeB, &link).unwrap();
+        let n = raw.len();
+        raw[n - 5] ^= 0x01;
+        assert!(!decode_mode_c(&raw).unwrap().crc_ok);
+
+        assert!(encode_mode_c(FrameType::Unknown, &link).is_none());
+        assert!(encode_mode_c(FrameType::TypeA, &link[..8]).is_none());
+        assert!(encode_mode_c(FrameType::TypeA, &[0; 256]).is_none());
+    }
+
+    #[test]
+    fn type_b_first_crc_covers_126_bytes() {
+        // L = 127: 125 link bytes after L plus one CRC, all in the first block.
+        let mut link = vec![0x44, 0x2D, 0x2C, 0x78, 0x56, 0x34, 0x12, 0x1B, 0x07, 0x7A];
+        link.resize(125, 0xA5);
+        let raw = encode_mode_c(FrameType::TypeB, &link).unwrap();
+        assert_eq!(raw[1], 127);
+        assert_eq!(raw.len(), 2 + 125 + CRC_LEN);
+        assert_eq!(
+            &raw[127..],
+            &calculate_wmbus_crc(&raw[1..127]).to_be_bytes()[..]
+        );
+        let f = decode_mode_c(&raw).unwrap();
+        assert!(f.crc_ok);
+        assert_eq!(f.payload, link[9..]);
+
+        // One more link byte needs the second block: L jumps to 130 with two CRCs.
+        link.push(0x5A);
+        let raw = encode_mode_c(FrameType::TypeB, &link).unwrap();
+        assert_eq!(raw[1], 130);
+        assert_eq!(raw.len(), 2 + 126 + 2 * CRC_LEN);
+        assert!(decode_mode_c(&raw).unwrap().crc_ok);
+
+        // L = 128 cannot be laid out: too long for one block, too short for two.
+        let mut raw = vec![TYPE_B_SYNC, 128];
+        raw.resize(2 + 128, 0);
+        assert!(matches!(
+            decode_mode_c(&raw),
+            Err(DecodeError::InvalidLength { length: 128 })
+        ));
+    }
+
     #[test]
     fn bcd4_matches_reference() {
         // Wire order 44 44 64 74 -> 74644444.
         assert_eq!(bcd4(&[0x44, 0x44, 0x64, 0x74]), 74_644_444);
+        assert_eq!(bcd4_encode(74_644_444), Ok(0x7464_4444));
+    }
+
+    #[test]
+    fn addresses_beyond_eight_digits_are_not_encoded() {
+        assert_eq!(
+            bcd4_encode(100_000_000),
+            Err(DecodeError::AddressNotBcd {
+                address: 100_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn frame_bytes_keep_the_wire_address_and_need_valid_crcs() {
+        // A non-BCD address (and the largest value bcd4 can yield) passes through as sent.
+        let link = [
+            0x44, 0x2D, 0x2C, 0xFF, 0xFA, 0x0B, 0xFF, 0x1B, 0x16, 0x7A, 0x01,
+        ];
+        let mut f = decode_mode_c(&encode_mode_c(FrameType::TypeA, &link).unwrap()).unwrap();
+        assert_eq!(f.address_bytes, [0xFF, 0xFA, 0x0B, 0xFF]);
+        assert_eq!(bcd4(&[0xFF; 4]), 166_666_665);
+        let frame = crate::wmbus::frame::parse_wmbus_frame(&f.to_frame_bytes().unwrap()).unwrap();
+        assert_eq!(frame.device_address, 0xFF0B_FAFF);
+
+        f.crc_ok = false;
+        assert_eq!(f.to_frame_bytes(), Err(DecodeError::BlockCrcFailed));
+        f.crc_ok = true;
+        f.payload.clear();
+        assert_eq!(f.to_frame_bytes(), Err(DecodeError::NoApplicationLayer));
     }
 }
@@ -30,8 +30,9 @@
 //! # }
 //! ```
 
-use std::time::{Duration, Instant};
-use tokio::time::sleep;
+use crate::wmbus::prediction::TransmitPredictor;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
 
 /// Wireless M-Bus communication modes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -48,6 +49,73 @@ pub enum WMBusMode {
     C1,
     /// C-mode frequent transmit variant
     C2,
+    /// N-mode: 169 MHz narrowband, 2.4–6.4 kbps (see [`NModeChannel`])
+    N1,
+    /// N-mode bidirectional variant
+    N2,
+    /// F-mode: 433.82 MHz, 2.4 kbps, bidirectional
+    F2,
+}
+
+/// An N-mode channel in the 169.40625–169.56875 MHz band
+///
+/// Channels sit on a 6.25 kHz raster; the standard channels are the associated
+/// constants. N-mode uses frame format A or B, told apart by the sync word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NModeChannel {
+    /// Carrier frequency in Hz
+    pub frequency_hz: u32,
+    /// Bitrate in bits per second
+    pub bitrate: u32,
+}
+
+impl NModeChannel {
+    /// Lowest N-mode carrier
+    pub const LOWEST_HZ: u32 = 169_406_250;
+    /// Highest N-mode carrier
+    pub const HIGHEST_HZ: u32 = 169_568_750;
+    /// Channel raster
+    pub const RASTER_HZ: u32 = 6_250;
+    /// Supported bitrates
+    ///
+    /// The 19.2 kbps 4GFSK channel is not offered: the SX126x has no four-level modem
+    /// to receive it.
+    pub const BITRATES: [u32; 3] = [2_400, 4_800, 6_400];
+
+    /// Channel 1a (N1a/N2a): 169.40625 MHz, 4.8 kbps
+    pub const CH_1A: Self = Self::standard(169_406_250, 4_800);
+    /// Channel 1b (N1b/N2b): 169.41875 MHz, 4.8 kbps
+    pub const CH_1B: Self = Self::standard(169_418_750, 4_800);
+    /// Channel 2a (N1c/N2c): 169.43125 MHz, 2.4 kbps
+    pub const CH_2A: Self = Self::standard(169_431_250, 2_400);
+    /// Channel 2b (N1d/N2d): 169.44375 MHz, 2.4 kbps
+    pub const CH_2B: Self = Self::standard(169_443_750, 2_400);
+    /// Channel 3a (N1e/N2e): 169.45625 MHz, 4.8 kbps
+    pub const CH_3A: Self = Self::standard(169_456_250, 4_800);
+    /// Channel 3b (N1f/N2f): 169.46875 MHz, 4.8 kbps
+    pub const CH_3B: Self = Self::standard(169_468_750, 4_800);
+
+    const fn standard(frequency_hz: u32, bitrate: u32) -> Self {
+        Self {
+            frequency_hz,
+            bitrate,
+        }
+    }
+
+    /// A channel at `frequency_hz` and `bitrate`, if both are valid for N-mode
+    pub fn new(frequency_hz: u32, bitrate: u32) -> Option<Self> {
+        let in_band = (Self::LOWEST_HZ..=Self::HIGHEST_HZ).contains(&frequency_hz)
+            && (frequency_hz - Self::LOWEST_HZ).is_multiple_of(Self::RASTER_HZ);
+        (in_band && Self::BITRATES.contains(&bitrate)).then_some(Self {
+            frequency_hz,
+            bitrate,
+        })
+    }
+
+    /// Whether `frequency_hz` lies in the N-mode band
+    pub fn in_band(frequency_hz: u32) -> bool {
+        (Self::LOWEST_HZ..=Self::HIGHEST_HZ).contains(&frequency_hz)
+    }
 }
 
 impl WMBusMode {
@@ -57,6 +125,8 @@ impl WMBusMode {
             WMBusMode::T1 | WMBusMode::T2 => 100_000,
             WMBusMode::S1 | WMBusMode::S2 => 32_768,
             WMBusMode::C1 | WMBusMode::C2 => 100_000,
+            WMBusMode::N1 | WMBusMode::N2 => NModeChannel::CH_1A.bitrate,
+            WMBusMode::F2 => 2_400,
         }
     }
 
@@ -66,6 +136,8 @@ impl WMBusMode {
             WMBusMode::T1 | WMBusMode::T2 => 66_667, // 100k / 1.5 (3-out-of-6)
             WMBusMode::S1 | WMBusMode::S2 => 16_384, // 32.768k / 2 (Manchester)
             WMBusMode::C1 | WMBusMode::C2 => 100_000, // No encoding overhead
+            WMBusMode::N1 | WMBusMode::N2 => NModeChannel::CH_1A.bitrate, // NRZ
+            WMBusMode::F2 => 2_400,                  // NRZ
         }
     }
 
@@ -76,6 +148,8 @@ impl WMBusMode {
             WMBusMode::S1 => 279,                // ≥279 chips
             WMBusMode::S2 => 15,                 // ≥15 chips
             WMBusMode::C1 | WMBusMode::C2 => 64, // 8×0x55 bytes = 64 bits
+            WMBusMode::N1 | WMBusMode::N2 => 16, // 2×0x55 bytes
+            WMBusMode::F2 => 32,                 // 4×0x55 bytes
         }
     }
 
@@ -85,12 +159,29 @@ impl WMBusMode {
             WMBusMode::T1 | WMBusMode::T2 => vec![0x54, 0x3D], // T-mode sync
             WMBusMode::S1 | WMBusMode::S2 => vec![0x54, 0x3D], // S-mode sync
             WMBusMode::C1 | WMBusMode::C2 => vec![0x54, 0xCD], // C-mode sync
+            WMBusMode::N1 | WMBusMode::N2 | WMBusMode::F2 => vec![0xF6, 0x8D], // Format A
+        }
+    }
+
+    /// Get the sync word announcing frame format B, for modes that carry both formats
+    pub fn sync_word_format_b(&self) -> Option<Vec<u8>> {
+        match self {
+            WMBusMode::C1 | WMBusMode::C2 => Some(vec![0x54, 0x3D]),
+            WMBusMode::N1 | WMBusMode::N2 | WMBusMode::F2 => Some(vec![0xF6, 0x72]),
+            _ => None,
         }
     }
 
     /// Get the frequency for this mode (in MHz)
+    ///
+    /// The 868 MHz modes share one frequency and are switched by time; N-mode
+    /// reports channel 1a (see [`NModeChannel`] for the others).
     pub fn frequency_mhz(&self) -> f32 {
-        868.95 // All modes use same frequency, switching is time-based
+        match self {
+            WMBusMode::N1 | WMBusMode::N2 => 169.40625,
+            WMBusMode::F2 => 433.82,
+            _ => 868.95,
+        }
     }
 }
 
@@ -127,7 +218,7 @@ pub struct SwitchingStats {
     /// Failed cycles
     pub cycles_failed: u64,
     /// Time spent in each mode (milliseconds)
-    pub time_per_mode: [u64; 6],
+    pub time_per_mode: [u64; 9],
 }
 
 impl ModeSwitcher {
@@ -204,6 +295,28 @@ impl ModeSwitcher {
         Some(self.current_mode)
     }
 
+    /// Get the next mode, preferring the mode of a meter the predictor expects now
+    ///
+    /// While a predicted reception window is open at `now` the switcher goes straight to
+    /// that meter's mode; otherwise it continues with [`ModeSwitcher::next_mode`].
+    pub async fn next_mode_predicted(
+        &mut self,
+        predictor: &TransmitPredictor,
+        now: Instant,
+    ) -> Option<WMBusMode> {
+        if self.established_mode.is_none() {
+            if let Some(mode) = predictor.due_mode(now) {
+                if mode != self.current_mode {
+                    self.current_mode = mode;
+                    self.last_switch = Instant::now();
+                    self.stats.switches_attempted += 1;
+                }
+                return Some(mode);
+            }
+        }
+        self.next_mode().await
+    }
+
     /// Mark a mode as successfully established
     pub fn mode_established(&mut self, mode: WMBusMode) {
         self.established_mode = Some(mode);
@@ -288,7 +401,8 @@ impl ModeNegotiator {
     ///
     /// Frame format:
     /// - CI: 0x7A (mode capabilities)
-    /// - Data: Bitmask of supported modes
+    /// - Data: Bitmask of supported 868 MHz modes
+    /// - Optional: Bitmask of N/F modes (only present if any is supported)
     pub fn build_capability_frame(&self) -> Vec<u8> {
         let mut frame = Vec::new();
 
@@ -305,10 +419,24 @@ impl ModeNegotiator {
                 WMBusMode::S2 => capabilities |= 0x08,
                 WMBusMode::C1 => capabilities |= 0x10,
                 WMBusMode::C2 => capabilities |= 0x20,
+                WMBusMode::N1 | WMBusMode::N2 | WMBusMode::F2 => {}
             }
         }
 
         frame.push(capabilities);
+
+        let mut narrowband = 0u8;
+        for mode in &self.supported_modes {
+            match mode {
+                WMBusMode::N1 => narrowband |= 0x01,
+                WMBusMode::N2 => narrowband |= 0x02,
+                WMBusMode::F2 => narrowband |= 0x04,
+                _ => {}
+            }
+        }
+        if narrowband != 0 {
+            frame.push(narrowband);
+        }
         frame
     }
 
@@ -340,6 +468,17 @@ impl ModeNegotiator {
             modes.push(WMBusMode::C2);
         }
 
+        let narrowband = data.get(2).copied().unwrap_or(0);
+        if narrowband & 0x01 != 0 {
+            modes.push(WMBusMode::N1);
+        }
+        if narrowband & 0x02 != 0 {
+            modes.push(WMBusMode::N2);
+        }
+        if narrowband & 0x04 != 0 {
+            modes.push(WMBusMode::F2);
+        }
+
         Some(modes)
     }
 
@@ -353,6 +492,9 @@ impl ModeNegotiator {
             WMBusMode::T2,
             WMBusMode::S1,
             WMBusMode::S2,
+            WMBusMode::N1,
+            WMBusMode::N2,
+            WMBusMode::F2,
         ];
 
         preference_order
@@ -370,6 +512,9 @@ fn mode_to_index(mode: WMBusMode) -> usize {
         WMBusMode::S2 => 3,
         WMBusMode::C1 => 4,
         WMBusMode::C2 => 5,
+        WMBusMode::N1 => 6,
+        WMBusMode::N2 => 7,
+        WMBusMode::F2 => 8,
     }
 }
 
@@ -414,6 +559,16 @@ mod tests {
         assert_eq!(switcher.established_mode(), Some(WMBusMode::S1));
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_mode_time_follows_the_tokio_clock() {
+        let mut switcher = ModeSwitcher::new();
+        let next = switcher.next_mode().await.unwrap();
+        tokio::time::advance(Duration::from_secs(2)).await;
+
+        switcher.mode_established(next);
+        assert_eq!(switcher.stats.time_per_mode[mode_to_index(next)], 2000);
+    }
+
     #[test]
     fn test_mode_parameters() {
         // Test T1 mode
@@ -443,6 +598,43 @@ mod tests {
         assert_eq!(frame[1] & 0x10, 0x10); // C1 bit
     }
 
+    #[test]
+    fn test_narrowband_modes() {
+        assert_eq!(WMBusMode::N1.frequency_mhz(), 169.40625);
+        assert_eq!(WMBusMode::F2.frequency_mhz(), 433.82);
+        assert_eq!(WMBusMode::F2.data_rate(), 2_400);
+        assert_eq!(WMBusMode::N2.sync_word(), vec![0xF6, 0x8D]);
+        assert_eq!(WMBusMode::N2.sync_word_format_b(), Some(vec![0xF6, 0x72]));
+        assert_eq!(WMBusMode::T1.sync_word_format_b(), None);
+
+        // Channels: band edges, 6.25 kHz raster, N-mode bitrates only.
+        assert_eq!(
+            NModeChannel::new(169_568_750, 6_400),
+            Some(NModeChannel {
+                frequency_hz: 169_568_750,
+                bitrate: 6_400
+            })
+        );
+        // The 4GFSK channel cannot be received.
+        assert!(NModeChannel::new(169_437_500, 19_200).is_none());
+        assert!(NModeChannel::new(169_575_000, 4_800).is_none());
+        assert!(NModeChannel::new(169_410_000, 4_800).is_none());
+        assert!(NModeChannel::new(169_406_250, 9_600).is_none());
+
+        // N/F capabilities ride in an optional second bitmask.
+        let negotiator = ModeNegotiator::new(vec![WMBusMode::C1, WMBusMode::N2, WMBusMode::F2]);
+        let frame = negotiator.build_capability_frame();
+        assert_eq!(frame, vec![0x7A, 0x10, 0x06]);
+        assert_eq!(
+            negotiator.parse_capability_frame(&frame),
+            Some(vec![WMBusMode::C1, WMBusMode::N2, WMBusMode::F2])
+        );
+        assert_eq!(
+            negotiator.select_best_mode(&[WMBusMode::F2]),
+            Some(WMBusMode::F2)
+        );
+    }
+
     #[test]
     fn test_mode_selection() {
         let negotiator = ModeNegotiator::new(vec![WMBusMode::T1, WMBusMode::S1, WMBusMode::C1]);
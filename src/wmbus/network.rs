@@ -16,13 +16,14 @@
 //!
 //! ```rust,no_run
 //! use mbus_rs::wmbus::network::{WMBusNetwork, NetworkConfig};
-//! // WMBusNetwork is generic over the HAL; substitute your platform's implementation.
+//! // WMBusNetwork is generic over the radio driver; substitute your platform's implementation.
+//! use mbus_rs::wmbus::radio::driver::Sx126xDriver;
 //! use mbus_rs::wmbus::radio::hal::MockHal;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let config = NetworkConfig::default();
-//!     let mut network = WMBusNetwork::<MockHal>::new(config);
+//!     let mut network = WMBusNetwork::<Sx126xDriver<MockHal>>::new(config);
 //!     
 //!     // Discover all devices in the area
 //!     let topology = network.discover_topology().await?;
@@ -33,7 +34,9 @@
 //! ```
 
 use crate::wmbus::handle::{DeviceInfo, WMBusConfig, WMBusError, WMBusHandle};
+use crate::wmbus::radio::driver::Sx126xDriver;
 use crate::wmbus::radio::hal::Hal;
+use crate::wmbus::radio::radio_driver::RadioDriver;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
@@ -151,16 +154,29 @@ pub struct ScanInfo {
 }
 
 /// Represents the state of a Wireless M-Bus (wM-Bus) network
-pub struct WMBusNetwork<H: Hal> {
+pub struct WMBusNetwork<R: RadioDriver> {
     /// Network configuration
     config: NetworkConfig,
     /// WMBus handle for radio operations
-    handle: Option<WMBusHandle<H>>,
+    handle: Option<WMBusHandle<R>>,
     /// Discovered devices across all scans
     discovered_devices: HashMap<u32, DeviceInfo>,
 }
 
-impl<H: Hal + Send + 'static> WMBusNetwork<H> {
+impl<H: Hal + Send + 'static> WMBusNetwork<Sx126xDriver<H>> {
+    /// Initialize the network with an SX126x radio HAL
+    ///
+    /// # Arguments
+    ///
+    /// * `hal` - Hardware abstraction layer for radio
+    pub async fn initialize(&mut self, hal: H) -> Result<(), WMBusError> {
+        let wmbus_config = self.initial_wmbus_config();
+        self.handle = Some(WMBusHandle::new(hal, Some(wmbus_config)).await?);
+        Ok(())
+    }
+}
+
+impl<R: RadioDriver + 'static> WMBusNetwork<R> {
     /// Create a new wM-Bus network manager
     ///
     /// # Arguments
@@ -174,20 +190,24 @@ impl<H: Hal + Send + 'static> WMBusNetwork<H> {
         }
     }
 
-    /// Initialize the network with a radio HAL
+    /// Initialize the network with any radio driver
     ///
     /// # Arguments
     ///
-    /// * `hal` - Hardware abstraction layer for radio
-    pub async fn initialize(&mut self, hal: H) -> Result<(), WMBusError> {
-        let wmbus_config = WMBusConfig {
+    /// * `driver` - Radio driver (not yet initialized)
+    pub async fn initialize_with_driver(&mut self, driver: R) -> Result<(), WMBusError> {
+        let wmbus_config = self.initial_wmbus_config();
+        self.handle = Some(WMBusHandle::with_driver(driver, Some(wmbus_config)).await?);
+        Ok(())
+    }
+
+    /// Handle configuration for the first scan frequency
+    fn initial_wmbus_config(&self) -> WMBusConfig {
+        WMBusConfig {
             frequency_hz: self.config.frequencies[0], // Start with first frequency
             discovery_timeout_ms: self.config.scan_duration_per_freq * 1000,
             ..WMBusConfig::default()
-        };
-
-        self.handle = Some(WMBusHandle::new(hal, Some(wmbus_config)).await?);
-        Ok(())
+        }
     }
 
     /// Discover the complete network topology
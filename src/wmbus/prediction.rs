@@ -0,0 +1,555 @@
+//! # Predictive Reception Scheduling
+//!
+//! Most meters transmit on a fixed interval with some jitter. The
+//! [`TransmitPredictor`] learns each meter's period and phase from its reception
+//! timestamps and predicts when it is next due, so a gateway can be on the right mode
+//! or frequency — or awake — at that moment instead of switching blindly:
+//!
+//! - [`TransmitPredictor::idle_windows`] turns the gaps between predicted receptions into
+//!   [`ScheduledWindow`]s for the [`ProfileScheduler`](crate::wmbus::radio::scheduler::ProfileScheduler),
+//!   so a radio shared with LoRa leaves its wM-Bus base profile only while no known
+//!   meter is due.
+//! - [`ModeSwitcher::next_mode_predicted`] switches to the mode of a meter whose window
+//!   is open before falling back to the round-robin sequence.
+//! - [`TransmitPredictor::next_wake`] tells a duty-cycled gateway when to wake up.
+//!
+//! Each window spans the predicted time ± ([`PredictionConfig::guard`] + learned
+//! jitter). A window that passes without a reception counts as a miss; after
+//! [`PredictionConfig::max_misses`] consecutive misses the meter is learned afresh.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use mbus_rs::wmbus::mode_switching::WMBusMode;
+//! use mbus_rs::wmbus::prediction::TransmitPredictor;
+//! use tokio::time::{Duration, Instant};
+//!
+//! let mut predictor = TransmitPredictor::default();
+//! let t0 = Instant::now();
+//! for k in 0..3 {
+//!     predictor.observe(0x2C2D, 0x12345678, Some(WMBusMode::T1), t0 + Duration::from_secs(16 * k));
+//! }
+//! let timing = predictor.meter(0x2C2D, 0x12345678).unwrap();
+//! assert_eq!(timing.period, Some(Duration::from_secs(16)));
+//! assert_eq!(timing.next_due, Some(t0 + Duration::from_secs(48)));
+//! ```
+//!
+//! [`ModeSwitcher::next_mode_predicted`]: crate::wmbus::mode_switching::ModeSwitcher::next_mode_predicted
+
+use crate::wmbus::mode_switching::WMBusMode;
+use crate::wmbus::radio::driver::RadioProfile;
+use crate::wmbus::radio::scheduler::ScheduledWindow;
+use std::collections::HashMap;
+use tokio::time::{Duration, Instant};
+
+/// Weight of a new interval sample in the period and jitter estimates
+const SMOOTHING: f64 = 0.25;
+
+/// A sample further than this fraction of the period from the estimate restarts learning
+const REPERIOD_TOLERANCE: f64 = 0.25;
+
+/// Learning and window settings
+#[derive(Debug, Clone)]
+pub struct PredictionConfig {
+    /// Margin added on both sides of each predicted transmission
+    pub guard: Duration,
+    /// Intervals shorter than this are ignored (repeats, bursts)
+    pub min_period: Duration,
+    /// Intervals needed before a meter's schedule is predicted
+    pub min_intervals: u32,
+    /// Consecutive misses after which a meter is learned afresh
+    pub max_misses: u32,
+}
+
+impl Default for PredictionConfig {
+    fn default() -> Self {
+        Self {
+            guard: Duration::from_millis(500),
+            min_period: Duration::from_secs(2),
+            min_intervals: 2,
+            max_misses: 3,
+        }
+    }
+}
+
+/// What has been learned about one meter's transmit schedule
+#[derive(Debug, Clone, Default)]
+pub struct MeterTiming {
+    /// Link mode the meter was last heard in
+    pub mode: Option<WMBusMode>,
+    /// Estimated transmit interval
+    pub period: Option<Duration>,
+    /// Mean deviation of observed intervals from the period
+    pub jitter: Duration,
+    /// Last reception (the phase anchor)
+    pub last_seen: Option<Instant>,
+    /// Predicted time of the next transmission
+    pub next_due: Option<Instant>,
+    /// Intervals folded into the estimate
+    pub intervals: u32,
+    /// Consecutive windows that passed without a reception
+    pub misses: u32,
+    /// All windows missed since the meter was first heard
+    pub total_misses: u64,
+}
+
+impl MeterTiming {
+    /// Whether enough intervals were seen to predict the next transmission
+    fn is_predicted(&self, config: &PredictionConfig) -> bool {
+        self.next_due.is_some() && self.intervals >= config.min_intervals
+    }
+
+    /// Half-width of the reception window around a predicted transmission
+    fn margin(&self, config: &PredictionConfig) -> Duration {
+        config.guard + self.jitter
+    }
+
+    fn observe(&mut self, at: Instant, config: &PredictionConfig) {
+        if let Some(last) = self.last_seen {
+            let interval = at.saturating_duration_since(last);
+            // A zero interval (a copy with the same timestamp) must never become the
+            // period: window iteration advances by it.
+            if interval.is_zero() || interval < config.min_period {
+                return;
+            }
+            self.fold_interval(interval);
+        }
+        self.last_seen = Some(at);
+        self.next_due = self.period.map(|p| at + p);
+        self.misses = 0;
+    }
+
+    fn fold_interval(&mut self, interval: Duration) {
+        let Some(period) = self.period else {
+            self.period = Some(interval);
+            self.intervals = 1;
+            return;
+        };
+        // Missed transmissions show up as multiples of the period.
+        let cycles = (interval.as_secs_f64() / period.as_secs_f64())
+            .round()
+            .max(1.0);
+        let sample = interval.div_f64(cycles);
+        let deviation = sample.abs_diff(period);
+        if deviation.as_secs_f64() > period.as_secs_f64() * REPERIOD_TOLERANCE {
+            // The meter changed its interval (or the estimate was off): start over.
+            self.period = Some(interval);
+            self.jitter = Duration::ZERO;
+            self.intervals = 1;
+            return;
+        }
+        self.period = Some(smooth(period, sample));
+        self.jitter = smooth(self.jitter, deviation);
+        self.intervals += 1;
+    }
+
+    fn forget(&mut self) {
+        self.period = None;
+        self.jitter = Duration::ZERO;
+        self.next_due = None;
+        self.intervals = 0;
+        self.misses = 0;
+    }
+}
+
+fn smooth(estimate: Duration, sample: Duration) -> Duration {
+    Duration::from_secs_f64(
+        estimate.as_secs_f64() + SMOOTHING * (sample.as_secs_f64() - estimate.as_secs_f64()),
+    )
+}
+
+/// A predicted transmission and the reception window around it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredictedWindow {
+    /// Manufacturer ID of the meter
+    pub manufacturer_id: u16,
+    /// Meter address
+    pub address: u32,
+    /// Link mode the meter was last heard in
+    pub mode: Option<WMBusMode>,
+    /// Predicted transmission time
+    pub due_at: Instant,
+    /// Window start (`due_at` minus guard and jitter)
+    pub opens_at: Instant,
+    /// Window end (`due_at` plus guard and jitter)
+    pub closes_at: Instant,
+}
+
+/// A window that passed without the meter being heard
+#[derive(Debug, Clone, PartialEq)]
+pub struct Miss {
+    /// Manufacturer ID of the meter
+    pub manufacturer_id: u16,
+    /// Meter address
+    pub address: u32,
+    /// Consecutive misses so far (0 once the meter was reset for relearning)
+    pub consecutive: u32,
+}
+
+/// Learns meter transmit intervals and predicts reception windows
+#[derive(Debug, Default)]
+pub struct TransmitPredictor {
+    config: PredictionConfig,
+    meters: HashMap<(u16, u32), MeterTiming>,
+}
+
+impl TransmitPredictor {
+    /// Create a predictor with the given settings
+    pub fn new(config: PredictionConfig) -> Self {
+        Self {
+            config,
+            meters: HashMap::new(),
+        }
+    }
+
+    /// Learning and window settings
+    pub fn config(&self) -> &PredictionConfig {
+        &self.config
+    }
+
+    /// Record a reception from a meter at `at`
+    pub fn observe(
+        &mut self,
+        manufacturer_id: u16,
+        address: u32,
+        mode: Option<WMBusMode>,
+        at: Instant,
+    ) {
+        let timing = self.meters.entry((manufacturer_id, address)).or_default();
+        if mode.is_some() {
+            timing.mode = mode;
+        }
+        timing.observe(at, &self.config);
+    }
+
+    /// What has been learned about a meter
+    pub fn meter(&self, manufacturer_id: u16, address: u32) -> Option<&MeterTiming> {
+        self.meters.get(&(manufacturer_id, address))
+    }
+
+    /// Number of meters heard so far
+    pub fn len(&self) -> usize {
+        self.meters.len()
+    }
+
+    /// Whether no meter has been heard
+    pub fn is_empty(&self) -> bool {
+        self.meters.is_empty()
+    }
+
+    /// Count windows that closed before `now` without a reception
+    ///
+    /// Each missed window advances the prediction by one period. A meter that reaches
+    /// [`PredictionConfig::max_misses`] consecutive misses is learned afresh.
+    pub fn expire(&mut self, now: Instant) -> Vec<Miss> {
+        let mut missed = Vec::new();
+        for (&(manufacturer_id, address), timing) in &mut self.meters {
+            if !timing.is_predicted(&self.config) {
+                continue;
+            }
+            while let (Some(due), Some(period)) = (timing.next_due, timing.period) {
+                if due + timing.margin(&self.config) > now {
+                    break;
+                }
+                timing.misses += 1;
+                timing.total_misses += 1;
+                timing.next_due = Some(due + period);
+                if timing.misses >= self.config.max_misses {
+                    log::debug!(
+                        "Meter {address:#010X} missed {} windows, relearning",
+                        timing.misses
+                    );
+                    timing.forget();
+                }
+                missed.push(Miss {
+                    manufacturer_id,
+                    address,
+                    consecutive: timing.misses,
+                });
+            }
+        }
+        missed
+    }
+
+    /// Reception windows that overlap `[from, until)`, earliest first
+    ///
+    /// Includes later transmissions of the same meter when the span covers several
+    /// periods.
+    pub fn due_windows(&self, from: Instant, until: Instant) -> Vec<PredictedWindow> {
+        let mut windows = Vec::new();
+        for (&(manufacturer_id, address), timing) in &self.meters {
+            if !timing.is_predicted(&self.config) {
+                continue;
+            }
+            let (Some(mut due), Some(period)) = (timing.next_due, timing.period) else {
+                continue;
+            };
+            let margin = timing.margin(&self.config);
+            while due.checked_sub(margin).is_some_and(|open| open < until) {
+                if due + margin > from {
+                    windows.push(PredictedWindow {
+                        manufacturer_id,
+                        address,
+                        mode: timing.mode,
+                        due_at: due,
+                        opens_at: due.checked_sub(margin).unwrap_or(due),
+                        closes_at: due + margin,
+                    });
+                }
+                due += period;
+            }
+        }
+        windows.sort_by_key(|w| w.opens_at);
+        windows
+    }
+
+    /// Link mode of a meter whose window is open at `now`, if any
+    ///
+    /// When several windows are open the one due first wins.
+    pub fn due_mode(&self, now: Instant) -> Option<WMBusMode> {
+        self.due_windows(now, now + Duration::from_nanos(1))
+            .into_iter()
+            .filter(|w| w.opens_at <= now)
+            .min_by_key(|w| w.due_at)
+            .and_then(|w| w.mode)
+    }
+
+    /// When the next window opens (`now` if one is already open)
+    pub fn next_wake(&self, now: Instant) -> Option<Instant> {
+        self.meters
+            .values()
+            .filter(|t| t.is_predicted(&self.config))
+            .filter_map(|t| {
+                let due = t.next_due?;
+                let margin = t.margin(&self.config);
+                (due + margin > now).then(|| due.checked_sub(margin).unwrap_or(due).max(now))
+            })
+            .min()
+    }
+
+    /// Windows for an alternate `profile` in the gaps between predicted receptions
+    ///
+    /// Covers `[now, now + horizon)`; gaps shorter than `min_len` are skipped. Offsets are
+    /// relative to `now`, so pass the result straight to
+    /// [`ProfileScheduler::run`](crate::wmbus::radio::scheduler::ProfileScheduler::run)
+    /// started at `now`: the radio rests in its wM-Bus base profile while any known
+    /// meter is due.
+    pub fn idle_windows(
+        &self,
+        now: Instant,
+        horizon: Duration,
+        min_len: Duration,
+        profile: &RadioProfile,
+    ) -> Vec<ScheduledWindow> {
+        let end = now + horizon;
+        let mut windows = Vec::new();
+        let mut cursor = now;
+        let mut push_gap = |from: Instant, to: Instant| {
+            if to > from && to - from >= min_len {
+                windows.push(ScheduledWindow {
+                    offset: from - now,
+                    duration: to - from,
+                    profile: profile.clone(),
+                });
+            }
+        };
+        for busy in self.due_windows(now, end) {
+            push_gap(cursor, busy.opens_at.max(now));
+            cursor = cursor.max(busy.closes_at);
+        }
+        push_gap(cursor, end);
+        windows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::mode_switching::ModeSwitcher;
+
+    const KAM: u16 = 0x2C2D;
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn learns_period_and_phase_through_jitter_and_gaps() {
+        let mut p = TransmitPredictor::default();
+        let t0 = Instant::now();
+        // 30 s period with ±0.3 s jitter; the fourth transmission is not received.
+        for at in [0.0, 30.2, 59.8, 120.3] {
+            p.observe(KAM, 1, Some(WMBusMode::C1), t0 + secs(at));
+        }
+        let timing = p.meter(KAM, 1).unwrap();
+        assert_eq!(timing.intervals, 3);
+        let period = timing.period.unwrap().as_secs_f64();
+        assert!((period - 30.0).abs() < 0.3, "period {period}");
+        assert!(timing.jitter > Duration::ZERO);
+        let due = timing.next_due.unwrap() - t0;
+        assert!((due.as_secs_f64() - 150.3).abs() < 0.3);
+
+        // Short repeats do not disturb the estimate.
+        p.observe(KAM, 1, None, t0 + secs(120.5));
+        assert_eq!(p.meter(KAM, 1).unwrap().intervals, 3);
+        assert_eq!(p.meter(KAM, 1).unwrap().mode, Some(WMBusMode::C1));
+
+        // A meter that changes its interval is relearned.
+        p.observe(KAM, 1, None, t0 + secs(130.3));
+        assert_eq!(p.meter(KAM, 1).unwrap().intervals, 1);
+        assert_eq!(p.meter(KAM, 1).unwrap().period, Some(secs(10.0)));
+    }
+
+    #[test]
+    fn equal_timestamps_never_learn_a_zero_period() {
+        let mut p = TransmitPredictor::new(PredictionConfig {
+            min_period: Duration::ZERO,
+            ..PredictionConfig::default()
+        });
+        let t0 = Instant::now();
+        for at in [0.0, 0.0, 10.0, 10.0, 20.0] {
+            p.observe(KAM, 1, None, t0 + secs(at));
+        }
+        let timing = p.meter(KAM, 1).unwrap();
+        assert_eq!(timing.period, Some(secs(10.0)));
+        assert_eq!(timing.intervals, 2);
+        // Both iterate by the period and return.
+        assert_eq!(p.expire(t0 + secs(45.0)).len(), 2);
+        assert_eq!(p.due_windows(t0 + secs(45.0), t0 + secs(75.0)).len(), 3);
+    }
+
+    #[test]
+    fn counts_misses_and_relearns_after_too_many() {
+        let mut p = TransmitPredictor::default();
+        let t0 = Instant::now();
+        for k in 0..3 {
+            p.observe(KAM, 1, None, t0 + secs(10.0 * k as f64));
+        }
+        // Due at 30 s; its window closes at 30.5 s.
+        assert!(p.expire(t0 + secs(30.4)).is_empty());
+        let missed = p.expire(t0 + secs(30.6));
+        assert_eq!(missed.len(), 1);
+        assert_eq!(missed[0].consecutive, 1);
+        assert_eq!(p.meter(KAM, 1).unwrap().next_due, Some(t0 + secs(40.0)));
+
+        // Heard again: the consecutive count resets.
+        p.observe(KAM, 1, None, t0 + secs(40.1));
+        assert_eq!(p.meter(KAM, 1).unwrap().misses, 0);
+
+        // Three more missed windows at once: the meter is relearned.
+        let missed = p.expire(t0 + secs(71.0));
+        assert_eq!(missed.len(), 3);
+        assert_eq!(missed[2].consecutive, 0);
+        let timing = p.meter(KAM, 1).unwrap();
+        assert_eq!(timing.total_misses, 4);
+        assert_eq!(timing.period, None);
+        assert_eq!(p.next_wake(t0 + secs(71.0)), None);
+    }
+
+    #[test]
+    fn idle_windows_avoid_predicted_receptions() {
+        let mut p = TransmitPredictor::default();
+        let t0 = Instant::now();
+        for k in 0..3 {
+            p.observe(KAM, 1, Some(WMBusMode::T1), t0 + secs(20.0 * k as f64));
+            p.observe(
+                KAM,
+                2,
+                Some(WMBusMode::C1),
+                t0 + secs(5.0 + 20.0 * k as f64),
+            );
+        }
+        let now = t0 + secs(46.0);
+        let due = p.due_windows(now, now + secs(30.0));
+        let at: Vec<f64> = due.iter().map(|w| (w.due_at - t0).as_secs_f64()).collect();
+        assert_eq!(at, vec![60.0, 65.0]);
+        assert_eq!(p.next_wake(now), Some(t0 + secs(59.5)));
+
+        let lora = RadioProfile::LoRa(crate::wmbus::radio::driver::LoRaProfile {
+            frequency_hz: 868_100_000,
+            sf: crate::wmbus::radio::modulation::SpreadingFactor::SF7,
+            bw: crate::wmbus::radio::modulation::LoRaBandwidth::BW125,
+            cr: crate::wmbus::radio::modulation::CodingRate::CR4_5,
+            power_dbm: 14,
+            sync_word: None,
+        });
+        let idle = p.idle_windows(now, secs(30.0), secs(1.0), &lora);
+        let spans: Vec<(f64, f64)> = idle
+            .iter()
+            .map(|w| (w.offset.as_secs_f64(), w.duration.as_secs_f64()))
+            .collect();
+        // Gaps 46–59.5, 60.5–64.5 and 65.5–76, relative to 46 s.
+        let expected = [(0.0, 13.5), (14.5, 4.0), (19.5, 10.5)];
+        assert_eq!(spans.len(), expected.len());
+        for ((o, d), (eo, ed)) in spans.iter().zip(expected) {
+            assert!((o - eo).abs() < 1e-6 && (d - ed).abs() < 1e-6, "{spans:?}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_windows_drive_the_profile_scheduler() {
+        use crate::wmbus::radio::driver::{LoRaProfile, Sx126xDriver, WmbusProfile};
+        use crate::wmbus::radio::hal::RecordingHal;
+        use crate::wmbus::radio::modulation::{CodingRate, LoRaBandwidth, SpreadingFactor};
+        use crate::wmbus::radio::scheduler::{CancelToken, ProfileScheduler};
+        use std::sync::Arc;
+
+        let mut p = TransmitPredictor::default();
+        let t0 = Instant::now();
+        for k in 0..3 {
+            p.observe(KAM, 1, Some(WMBusMode::C1), t0 + secs(10.0 * k as f64));
+        }
+        let lora = RadioProfile::LoRa(LoRaProfile {
+            frequency_hz: 868_100_000,
+            sf: SpreadingFactor::SF7,
+            bw: LoRaBandwidth::BW125,
+            cr: CodingRate::CR4_5,
+            power_dbm: 14,
+            sync_word: None,
+        });
+        // Due at 30 s and 40 s: LoRa gets 20–29.5, 30.5–39.5 and 40.5–45.
+        let now = t0 + secs(20.0);
+        let windows = p.idle_windows(now, secs(25.0), secs(1.0), &lora);
+        assert_eq!(windows.len(), 3);
+
+        let hal = RecordingHal::new();
+        let probe = hal.clone();
+        let driver = Arc::new(tokio::sync::Mutex::new(Sx126xDriver::new(hal, 32_000_000)));
+        let base = RadioProfile::Wmbus(WmbusProfile::mode_c(868_950_000, 100_000));
+        ProfileScheduler::new(driver, base)
+            .run(&windows, &CancelToken::new())
+            .await
+            .unwrap();
+        // Base, then LoRa and back to wM-Bus around each predicted reception.
+        let types: Vec<u8> = probe
+            .commands()
+            .iter()
+            .filter(|(op, _)| *op == 0x8A)
+            .map(|(_, d)| d[0])
+            .collect();
+        assert_eq!(types, vec![0, 1, 0, 1, 0, 1, 0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mode_switcher_follows_open_windows() {
+        let mut p = TransmitPredictor::default();
+        let t0 = Instant::now();
+        for k in 0..3 {
+            p.observe(KAM, 1, Some(WMBusMode::C1), t0 + secs(10.0 * k as f64));
+        }
+        let mut switcher = ModeSwitcher::new();
+        // Inside the C1 meter's window the switcher goes straight to C1.
+        let mode = switcher
+            .next_mode_predicted(&p, t0 + secs(29.8))
+            .await
+            .unwrap();
+        assert_eq!(mode, WMBusMode::C1);
+        assert_eq!(switcher.current_mode(), WMBusMode::C1);
+        // Outside any window it falls back to the sequence (T1 → S1).
+        let mode = switcher
+            .next_mode_predicted(&p, t0 + secs(35.0))
+            .await
+            .unwrap();
+        assert_eq!(mode, WMBusMode::S1);
+    }
+}
@@ -0,0 +1,566 @@
+//! # ETSI EN 300 220 transmit compliance for 868 MHz wM-Bus
+//!
+//! A gateway that answers meters transmits in the 868 MHz SRD band and must respect
+//! the sub-band it transmits in. [`TxBudget`] keeps that account for one radio:
+//!
+//! | Sub-band            | Carrier range       | Duty cycle | Air time per hour |
+//! |---------------------|---------------------|------------|-------------------|
+//! | [`SubBand::G1`]     | 868.000–868.600 MHz | 1 %        | 36 s              |
+//! | [`SubBand::G2`]     | 868.700–869.200 MHz | 0.1 %      | 3.6 s             |
+//! | [`SubBand::G3`]     | 869.400–869.650 MHz | 10 %       | 360 s             |
+//!
+//! Air time is summed per sub-band over a sliding one-hour window, and a transmission
+//! that would take a sub-band over its limit is rejected with
+//! [`ComplianceError::BudgetExhausted`], which says when it would fit.
+//!
+//! Instead of the duty-cycle limit, EN 300 220-1 lets a transmitter use polite
+//! spectrum access: listen before talk with adaptive frequency agility (LBT+AFA).
+//! [`ChannelAccess::LbtAfa`] enforces its timing rules — a maximum on-time per
+//! transmission, a minimum off-time per channel and a cap on the accumulated
+//! on-time per hour — and lists the alternative channels to try when the channel
+//! is busy. The listening itself is done by the radio driver; see
+//! [`Sx126xDriver::set_tx_budget`](super::driver::Sx126xDriver::set_tx_budget).
+//!
+//! Time on air comes from [`Airtime`]: the GFSK bitrate, the line code's expansion
+//! (3-out-of-6 in mode T, Manchester in mode S) and the preamble, sync word and
+//! postamble chips of the mode.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::time::Instant;
+//! use mbus_rs::wmbus::bitstream::LinkMode;
+//! use mbus_rs::wmbus::radio::compliance::{Airtime, SubBand, TxBudget};
+//!
+//! let mut budget = TxBudget::duty_cycle();
+//! let airtime = Airtime::for_mode(LinkMode::C).time_on_air(40);
+//! let now = Instant::now();
+//! budget.try_consume(868_950_000, airtime, now).unwrap();
+//! assert_eq!(budget.used(SubBand::G2, now), airtime);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::wmbus::bitstream::LinkMode;
+use crate::wmbus::radio::driver::LbtConfig;
+use crate::wmbus::radio::modulation::EncodingType;
+
+/// Length of the sliding accounting window.
+pub const WINDOW: Duration = Duration::from_secs(3600);
+
+/// A duty-cycle-limited 868 MHz SRD sub-band (ERC/REC 70-03 annex 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubBand {
+    /// 868.0–868.6 MHz, 1 % duty cycle (mode S).
+    G1,
+    /// 868.7–869.2 MHz, 0.1 % duty cycle (modes T and C).
+    G2,
+    /// 869.4–869.65 MHz, 10 % duty cycle (mode R2 and gateway downlinks).
+    G3,
+}
+
+impl SubBand {
+    /// All sub-bands, in frequency order.
+    pub const ALL: [SubBand; 3] = [SubBand::G1, SubBand::G2, SubBand::G3];
+
+    /// The sub-band a carrier falls in, if any.
+    pub fn for_frequency(frequency_hz: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|band| {
+            let (low, high) = band.range_hz();
+            (low..=high).contains(&frequency_hz)
+        })
+    }
+
+    /// Lowest and highest carrier frequency of the sub-band, in Hz.
+    pub fn range_hz(self) -> (u32, u32) {
+        match self {
+            SubBand::G1 => (868_000_000, 868_600_000),
+            SubBand::G2 => (868_700_000, 869_200_000),
+            SubBand::G3 => (869_400_000, 869_650_000),
+        }
+    }
+
+    /// Duty-cycle limit in per mille.
+    pub fn duty_cycle_permille(self) -> u32 {
+        match self {
+            SubBand::G1 => 10,
+            SubBand::G2 => 1,
+            SubBand::G3 => 100,
+        }
+    }
+
+    /// Air time the duty-cycle limit allows per [`WINDOW`].
+    pub fn hourly_limit(self) -> Duration {
+        WINDOW * self.duty_cycle_permille() / 1000
+    }
+}
+
+impl fmt::Display for SubBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (low, high) = self.range_hz();
+        let permille = self.duty_cycle_permille();
+        write!(
+            f,
+            "{:?} ({:.3}–{:.3} MHz, {}.{}%)",
+            self,
+            f64::from(low) / 1e6,
+            f64::from(high) / 1e6,
+            permille / 10,
+            permille % 10
+        )
+    }
+}
+
+/// Time-on-air model of a GFSK transmission.
+///
+/// The preamble, sync word and postamble are counted as chips on the air; the frame
+/// bytes are expanded by the line code first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Airtime {
+    /// GFSK bitrate, i.e. the chip rate on the air.
+    pub bitrate: u32,
+    /// Line code applied to the frame bytes.
+    pub encoding: EncodingType,
+    /// Preamble length in chips.
+    pub preamble_chips: u32,
+    /// Sync word length in chips.
+    pub sync_chips: u32,
+    /// Postamble (trailer) length in chips.
+    pub postamble_chips: u32,
+}
+
+impl Airtime {
+    /// The EN 13757-4 framing of `mode`, with the longest postamble (8 chips) and, in
+    /// mode S, the long S1 preamble.
+    pub fn for_mode(mode: LinkMode) -> Self {
+        let (bitrate, encoding, preamble_chips, sync_chips) = match mode {
+            LinkMode::S => (32_768, EncodingType::Manchester, 558, 18),
+            LinkMode::T => (100_000, EncodingType::ThreeOutOfSix, 38, 10),
+            LinkMode::C => (100_000, EncodingType::Nrz, 32, 32),
+            LinkMode::N => (4_800, EncodingType::Nrz, 16, 16),
+        };
+        Self {
+            bitrate,
+            encoding,
+            preamble_chips,
+            sync_chips,
+            postamble_chips: 8,
+        }
+    }
+
+    /// An NRZ packet as the radio frames it: `preamble_bits` and `sync_bits`, then the
+    /// payload with no postamble.
+    pub fn nrz(bitrate: u32, preamble_bits: u32, sync_bits: u32) -> Self {
+        Self {
+            bitrate,
+            encoding: EncodingType::Nrz,
+            preamble_chips: preamble_bits,
+            sync_chips: sync_bits,
+            postamble_chips: 0,
+        }
+    }
+
+    /// Chips that carry one frame byte.
+    pub fn chips_per_byte(&self) -> u32 {
+        match self.encoding {
+            EncodingType::Manchester => 16,
+            EncodingType::ThreeOutOfSix => 12,
+            EncodingType::Nrz | EncodingType::None => 8,
+        }
+    }
+
+    /// Time on air of a `frame_len`-byte frame, rounded up to the nanosecond.
+    pub fn time_on_air(&self, frame_len: usize) -> Duration {
+        let chips = u64::from(self.preamble_chips + self.sync_chips + self.postamble_chips)
+            + frame_len as u64 * u64::from(self.chips_per_byte());
+        Duration::from_nanos((chips * 1_000_000_000).div_ceil(u64::from(self.bitrate.max(1))))
+    }
+}
+
+/// LBT+AFA (polite spectrum access) parameters per EN 300 220-1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LbtAfaConfig {
+    /// Listen-before-talk threshold, listen time and retries.
+    pub lbt: LbtConfig,
+    /// Longest single transmission.
+    pub max_on_time: Duration,
+    /// Silence required on a channel after each transmission on it.
+    pub min_off_time: Duration,
+    /// Accumulated on-time allowed per sub-band in any hour.
+    pub max_on_time_per_hour: Duration,
+    /// Alternative carriers, in Hz, tried in order when the channel is busy.
+    pub afa_channels: Vec<u32>,
+}
+
+impl Default for LbtAfaConfig {
+    fn default() -> Self {
+        Self {
+            lbt: LbtConfig::default(),
+            max_on_time: Duration::from_secs(1),
+            min_off_time: Duration::from_millis(100),
+            max_on_time_per_hour: Duration::from_secs(100),
+            afa_channels: Vec::new(),
+        }
+    }
+}
+
+/// How a [`TxBudget`] grants channel access.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelAccess {
+    /// The sub-band duty-cycle limit.
+    DutyCycle,
+    /// Listen before talk with adaptive frequency agility instead of the duty cycle.
+    LbtAfa(LbtAfaConfig),
+}
+
+/// Why a transmission was rejected.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ComplianceError {
+    /// The carrier is outside the duty-cycle-limited 868 MHz sub-bands.
+    #[error("{frequency_hz} Hz is outside the 868 MHz SRD sub-bands")]
+    OutOfBand { frequency_hz: u32 },
+    /// The sub-band's hourly air time would be exceeded.
+    #[error("{band}: {used:?} of {limit:?} used this hour, {requested:?} requested")]
+    BudgetExhausted {
+        band: SubBand,
+        used: Duration,
+        requested: Duration,
+        limit: Duration,
+        /// How long until the transmission fits; `None` if it never can.
+        retry_after: Option<Duration>,
+    },
+    /// A single transmission would exceed the LBT+AFA maximum on-time.
+    #[error("{requested:?} on air exceeds the {limit:?} maximum on-time")]
+    OnTimeExceeded {
+        requested: Duration,
+        limit: Duration,
+    },
+    /// The channel has not been silent for the LBT+AFA minimum off-time.
+    #[error("{frequency_hz} Hz needs {remaining:?} more off-time")]
+    OffTimeRequired {
+        frequency_hz: u32,
+        remaining: Duration,
+    },
+}
+
+/// One accounted transmission.
+#[derive(Debug, Clone, Copy)]
+struct Transmission {
+    band: SubBand,
+    start: Instant,
+    airtime: Duration,
+}
+
+impl Transmission {
+    fn end(&self) -> Instant {
+        self.start + self.airtime
+    }
+
+    /// Air time still inside the window ending at `now`. A transmission counts in full
+    /// from its start, so one still on the air is already charged.
+    fn within_window(&self, now: Instant) -> Duration {
+        let from = now
+            .checked_sub(WINDOW)
+            .unwrap_or(self.start)
+            .max(self.start);
+        self.end().saturating_duration_since(from)
+    }
+}
+
+/// Per-sub-band transmit budget for one radio.
+///
+/// [`check`](Self::check) a transmission before it goes out and
+/// [`record`](Self::record) it once it has, or do both with
+/// [`try_consume`](Self::try_consume). All methods take the current time so the
+/// account can be driven by any clock.
+#[derive(Debug, Clone)]
+pub struct TxBudget {
+    access: ChannelAccess,
+    history: Vec<Transmission>,
+    /// End of the last transmission on each carrier, for the LBT+AFA off-time.
+    last_end: HashMap<u32, Instant>,
+}
+
+impl TxBudget {
+    /// A budget enforcing the sub-band duty-cycle limits.
+    pub fn duty_cycle() -> Self {
+        Self::new(ChannelAccess::DutyCycle)
+    }
+
+    /// A budget enforcing LBT+AFA timing instead of the duty cycle.
+    pub fn lbt_afa(config: LbtAfaConfig) -> Self {
+        Self::new(ChannelAccess::LbtAfa(config))
+    }
+
+    /// A budget with the given channel access.
+    pub fn new(access: ChannelAccess) -> Self {
+        Self {
+            access,
+            history: Vec::new(),
+            last_end: HashMap::new(),
+        }
+    }
+
+    /// How this budget grants channel access.
+    pub fn access(&self) -> &ChannelAccess {
+        &self.access
+    }
+
+    /// The hourly air time allowed in `band`.
+    pub fn limit(&self, band: SubBand) -> Duration {
+        match &self.access {
+            ChannelAccess::DutyCycle => band.hourly_limit(),
+            ChannelAccess::LbtAfa(config) => config.max_on_time_per_hour,
+        }
+    }
+
+    /// Air time spent in `band` during the hour before `now`.
+    pub fn used(&self, band: SubBand, now: Instant) -> Duration {
+        self.history
+            .iter()
+            .filter(|tx| tx.band == band)
+            .map(|tx| tx.within_window(now))
+            .sum()
+    }
+
+    /// Air time still available in `band` at `now`.
+    pub fn remaining(&self, band: SubBand, now: Instant) -> Duration {
+        self.limit(band).saturating_sub(self.used(band, now))
+    }
+
+    /// Whether `airtime` on `frequency_hz` may start at `now`; returns its sub-band.
+    pub fn check(
+        &self,
+        frequency_hz: u32,
+        airtime: Duration,
+        now: Instant,
+    ) -> Result<SubBand, ComplianceError> {
+        let band = SubBand::for_frequency(frequency_hz)
+            .ok_or(ComplianceError::OutOfBand { frequency_hz })?;
+
+        if let ChannelAccess::LbtAfa(config) = &self.access {
+            if airtime > config.max_on_time {
+                return Err(ComplianceError::OnTimeExceeded {
+                    requested: airtime,
+                    limit: config.max_on_time,
+                });
+            }
+            if let Some(&end) = self.last_end.get(&frequency_hz) {
+                let remaining = (end + config.min_off_time).saturating_duration_since(now);
+                if !remaining.is_zero() {
+                    return Err(ComplianceError::OffTimeRequired {
+                        frequency_hz,
+                        remaining,
+                    });
+                }
+            }
+        }
+
+        let used = self.used(band, now);
+        let limit = self.limit(band);
+        if used + airtime > limit {
+            return Err(ComplianceError::BudgetExhausted {
+                band,
+                used,
+                requested: airtime,
+                limit,
+                retry_after: self.retry_after(band, airtime, now),
+            });
+        }
+        Ok(band)
+    }
+
+    /// Account `airtime` on `frequency_hz` starting at `start`. Carriers outside the
+    /// sub-bands are not accounted.
+    pub fn record(&mut self, frequency_hz: u32, airtime: Duration, start: Instant) {
+        let Some(band) = SubBand::for_frequency(frequency_hz) else {
+            return;
+        };
+        self.history.retain(|tx| tx.end() + WINDOW > start);
+        self.history.push(Transmission {
+            band,
+            start,
+            airtime,
+        });
+        self.last_end.insert(frequency_hz, start + airtime);
+    }
+
+    /// [`check`](Self::check) and, if allowed, [`record`](Self::record) a transmission
+    /// starting at `now`.
+    pub fn try_consume(
+        &mut self,
+        frequency_hz: u32,
+        airtime: Duration,
+        now: Instant,
+    ) -> Result<SubBand, ComplianceError> {
+        let band = self.check(frequency_hz, airtime, now)?;
+        self.record(frequency_hz, airtime, now);
+        Ok(band)
+    }
+
+    /// The earliest wait after `now` at which `airtime` fits in `band`, as older
+    /// transmissions slide out of the window.
+    fn retry_after(&self, band: SubBand, airtime: Duration, now: Instant) -> Option<Duration> {
+        let limit = self.limit(band);
+        if airtime > limit {
+            return None;
+        }
+        // Usage falls as transmissions slide out of the window; waiting until one has
+        // slid out completely gives a safe, slightly conservative answer.
+        let mut expiries: Vec<Instant> = self
+            .history
+            .iter()
+            .filter(|tx| tx.band == band)
+            .map(|tx| tx.end() + WINDOW)
+            .filter(|&t| t > now)
+            .collect();
+        expiries.sort();
+        expiries
+            .into_iter()
+            .find(|&t| self.used(band, t) + airtime <= limit)
+            .map(|t| t - now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const C_CHANNEL: u32 = 868_950_000;
+    const S_CHANNEL: u32 = 868_300_000;
+    const R2_CHANNEL: u32 = 869_525_000;
+
+    #[test]
+    fn carriers_map_to_their_sub_band() {
+        assert_eq!(SubBand::for_frequency(S_CHANNEL), Some(SubBand::G1));
+        assert_eq!(SubBand::for_frequency(C_CHANNEL), Some(SubBand::G2));
+        assert_eq!(SubBand::for_frequency(R2_CHANNEL), Some(SubBand::G3));
+        assert_eq!(SubBand::for_frequency(868_650_000), None);
+        assert_eq!(SubBand::for_frequency(169_406_250), None);
+
+        assert_eq!(SubBand::G1.hourly_limit(), Duration::from_secs(36));
+        assert_eq!(SubBand::G2.hourly_limit(), Duration::from_millis(3_600));
+        assert_eq!(SubBand::G3.hourly_limit(), Duration::from_secs(360));
+        assert_eq!(SubBand::G2.to_string(), "G2 (868.700–869.200 MHz, 0.1%)");
+    }
+
+    #[test]
+    fn time_on_air_follows_the_line_code() {
+        // 20 bytes in mode C: 32 + 32 + 8 chips of framing and 160 data chips at 100 kcps.
+        assert_eq!(
+            Airtime::for_mode(LinkMode::C).time_on_air(20),
+            Duration::from_micros(2_320)
+        );
+        // Mode T: the same frame as 240 3-out-of-6 chips.
+        assert_eq!(
+            Airtime::for_mode(LinkMode::T).time_on_air(20),
+            Duration::from_micros(2_960)
+        );
+        // Mode S: 584 framing and 320 Manchester chips at 32.768 kcps.
+        assert_eq!(
+            Airtime::for_mode(LinkMode::S).time_on_air(20),
+            Duration::from_nanos(27_587_891)
+        );
+        assert_eq!(
+            Airtime::nrz(100_000, 48, 32).time_on_air(10),
+            Duration::from_micros(1_600)
+        );
+    }
+
+    #[test]
+    fn duty_cycle_rejects_over_budget_and_slides() {
+        let mut budget = TxBudget::duty_cycle();
+        let t0 = Instant::now();
+        let second = Duration::from_secs(1);
+
+        for i in 0..3 {
+            budget
+                .try_consume(C_CHANNEL, second, t0 + second * 10 * i)
+                .unwrap();
+        }
+        // 3 s of 3.6 s used: one more second does not fit until the first one slides out.
+        let err = budget
+            .check(C_CHANNEL, second, t0 + Duration::from_secs(60))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ComplianceError::BudgetExhausted {
+                band: SubBand::G2,
+                used: Duration::from_secs(3),
+                requested: second,
+                limit: Duration::from_millis(3_600),
+                retry_after: Some(WINDOW + second - Duration::from_secs(60)),
+            }
+        );
+        // Other sub-bands keep their own account.
+        budget
+            .check(S_CHANNEL, second, t0 + Duration::from_secs(60))
+            .unwrap();
+        // Part-way through the first transmission's slide, only the rest of it counts.
+        let sliding = t0 + WINDOW + Duration::from_millis(500);
+        assert_eq!(
+            budget.used(SubBand::G2, sliding),
+            Duration::from_millis(2_500)
+        );
+        budget
+            .check(C_CHANNEL, second, t0 + WINDOW + second)
+            .unwrap();
+
+        assert!(matches!(
+            budget.check(C_CHANNEL, Duration::from_secs(4), t0),
+            Err(ComplianceError::BudgetExhausted {
+                retry_after: None,
+                ..
+            })
+        ));
+        assert_eq!(
+            budget.check(915_000_000, second, t0),
+            Err(ComplianceError::OutOfBand {
+                frequency_hz: 915_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn lbt_afa_replaces_the_duty_cycle_with_on_and_off_times() {
+        let mut budget = TxBudget::lbt_afa(LbtAfaConfig::default());
+        let t0 = Instant::now();
+        let airtime = Duration::from_millis(800);
+
+        // Far beyond the 0.1 % of G2, but within the LBT+AFA rules.
+        budget.try_consume(C_CHANNEL, airtime, t0).unwrap();
+        assert_eq!(
+            budget.check(C_CHANNEL, airtime, t0 + Duration::from_millis(850)),
+            Err(ComplianceError::OffTimeRequired {
+                frequency_hz: C_CHANNEL,
+                remaining: Duration::from_millis(50),
+            })
+        );
+        // Another channel of the same sub-band is free at once.
+        budget
+            .check(868_800_000, airtime, t0 + Duration::from_millis(850))
+            .unwrap();
+        budget
+            .try_consume(C_CHANNEL, airtime, t0 + Duration::from_millis(900))
+            .unwrap();
+
+        assert!(matches!(
+            budget.check(C_CHANNEL, Duration::from_millis(1_200), t0 + WINDOW),
+            Err(ComplianceError::OnTimeExceeded { .. })
+        ));
+
+        // The accumulated on-time per hour still caps the sub-band.
+        let mut start = t0 + Duration::from_secs(2);
+        let mut sent = 2;
+        while budget.try_consume(C_CHANNEL, airtime, start).is_ok() {
+            sent += 1;
+            start += Duration::from_secs(1);
+        }
+        assert_eq!(sent, 125);
+        assert_eq!(budget.used(SubBand::G2, start), Duration::from_secs(100));
+    }
+}
@@ -0,0 +1,568 @@
+//! # embedded-hal 1.0 adapter for the SX126x
+//!
+//! [`EmbeddedHal`] implements [`Hal`] on top of any [`SpiDevice`] plus the three
+//! SX126x control lines (BUSY, RESET, DIO1) and a [`DelayNs`] provider, so the
+//! driver runs on any board with an embedded-hal 1.0 implementation — other Linux
+//! SBCs through `linux-embedded-hal`, or MCUs through their vendor HAL crates —
+//! without bespoke glue code. [`EmbeddedHalAsync`] offers the same operations for
+//! `embedded-hal-async` peripherals as a standalone bus adapter; it is not a
+//! backend for `Sx126xDriver`.
+//!
+//! ## SPI framing
+//!
+//! Every operation is one `SpiDevice` transaction, so chip select framing is left
+//! to the device implementation:
+//!
+//! | Operation        | MOSI                                   | MISO used       |
+//! |------------------|----------------------------------------|-----------------|
+//! | `write_command`  | `opcode, data…`                        | —               |
+//! | `read_command`   | `opcode, NOP(status), NOP…`            | bytes after status |
+//! | `GetStatus`      | `0xC0, NOP`                            | the status byte |
+//! | `ReadBuffer`     | `0x1E, offset, NOP(status), NOP…`      | bytes after status |
+//! | `write_register` | `0x0D, addr_hi, addr_lo, data…`        | —               |
+//! | `read_register`  | `0x1D, addr_hi, addr_lo, NOP(status), NOP…` | bytes after status |
+//!
+//! The driver issues `ReadBuffer` as a `write_command(0x1E, [offset, ..])`
+//! followed by `read_command(0x1E, buf)`. The adapter latches the offset from the
+//! first call and sends a single `ReadBuffer` transaction on the second, as the
+//! chip requires.
+//!
+//! After every write the adapter waits for BUSY to fall (100 ms for commands,
+//! 50 ms for registers, polled every 10 µs), matching `RaspberryPiHal`.
+//! `SetSleep` is the exception: BUSY stays high while the chip sleeps.
+//!
+//! ## GPIO numbering
+//!
+//! [`Hal::gpio_read`] and [`Hal::gpio_write`] use the same pin numbers as the
+//! Raspberry Pi HAL: [`DIO1`] reads the interrupt line, [`BUSY`] reads the busy
+//! line and [`RESET`] drives the reset line.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use linux_embedded_hal::{CdevPin, Delay, SpidevDevice};
+//! use mbus_rs::wmbus::radio::driver::Sx126xDriver;
+//! use mbus_rs::wmbus::radio::hal::EmbeddedHal;
+//!
+//! let spi = SpidevDevice::open("/dev/spidev0.0")?;
+//! let mut hal = EmbeddedHal::new(spi, busy, reset, dio1, Delay);
+//! hal.reset()?;
+//! let mut driver = Sx126xDriver::new(hal, 32_000_000);
+//! ```
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::spi::{Operation, SpiDevice};
+
+use super::{Hal, HalError};
+
+/// Pin number of the RESET line for [`Hal::gpio_write`]
+pub const RESET: u8 = 0;
+
+/// Pin number of the DIO1 interrupt line for [`Hal::gpio_read`]
+pub const DIO1: u8 = 1;
+
+/// Pin number of the BUSY line for [`Hal::gpio_read`]
+pub const BUSY: u8 = 3;
+
+const OP_WRITE_REGISTER: u8 = 0x0D;
+const OP_READ_REGISTER: u8 = 0x1D;
+const OP_READ_BUFFER: u8 = 0x1E;
+const OP_SET_SLEEP: u8 = 0x84;
+const OP_GET_STATUS: u8 = 0xC0;
+const NOP: u8 = 0x00;
+
+/// BUSY timeout after a command write, in milliseconds
+const COMMAND_BUSY_TIMEOUT_MS: u32 = 100;
+/// BUSY timeout after a register write, in milliseconds
+const REGISTER_BUSY_TIMEOUT_MS: u32 = 50;
+/// BUSY polling interval, in microseconds
+const BUSY_POLL_US: u32 = 10;
+
+/// Header bytes clocked out before the response of a read command. `GetStatus`
+/// returns the status in the byte after the opcode; every other read command
+/// returns it there and the payload after it.
+fn read_header(opcode: u8, read_offset: u8) -> ([u8; 3], usize) {
+    match opcode {
+        OP_GET_STATUS => ([opcode, 0, 0], 1),
+        OP_READ_BUFFER => ([opcode, read_offset, NOP], 3),
+        _ => ([opcode, NOP, 0], 2),
+    }
+}
+
+/// Number of BUSY polls that fit in `timeout_ms`
+fn busy_polls(timeout_ms: u32) -> u32 {
+    timeout_ms * 1000 / BUSY_POLL_US
+}
+
+/// SX126x [`Hal`] over embedded-hal 1.0 blocking traits
+pub struct EmbeddedHal<SPI, BUSYPIN, RESETPIN, DIO1PIN, D> {
+    spi: SPI,
+    busy: BUSYPIN,
+    reset: RESETPIN,
+    dio1: DIO1PIN,
+    delay: D,
+    read_offset: u8,
+}
+
+impl<SPI, BUSYPIN, RESETPIN, DIO1PIN, D> EmbeddedHal<SPI, BUSYPIN, RESETPIN, DIO1PIN, D>
+where
+    SPI: SpiDevice,
+    BUSYPIN: InputPin,
+    RESETPIN: OutputPin,
+    DIO1PIN: InputPin,
+    D: DelayNs,
+{
+    /// Create an adapter from an SPI device, the control lines and a delay provider
+    pub fn new(spi: SPI, busy: BUSYPIN, reset: RESETPIN, dio1: DIO1PIN, delay: D) -> Self {
+        Self {
+            spi,
+            busy,
+            reset,
+            dio1,
+            delay,
+            read_offset: 0,
+        }
+    }
+
+    /// Release the wrapped peripherals
+    pub fn release(self) -> (SPI, BUSYPIN, RESETPIN, DIO1PIN, D) {
+        (self.spi, self.busy, self.reset, self.dio1, self.delay)
+    }
+
+    /// Pulse RESET low for 100 µs, then wait for the chip to come up
+    pub fn reset(&mut self) -> Result<(), HalError> {
+        self.reset.set_low().map_err(|_| HalError::Gpio)?;
+        self.delay.delay_us(100);
+        self.reset.set_high().map_err(|_| HalError::Gpio)?;
+        self.delay.delay_ms(1);
+        self.wait_busy_low(COMMAND_BUSY_TIMEOUT_MS)
+    }
+
+    /// Poll BUSY until it falls or `timeout_ms` elapses
+    fn wait_busy_low(&mut self, timeout_ms: u32) -> Result<(), HalError> {
+        for _ in 0..busy_polls(timeout_ms) {
+            if self.busy.is_low().map_err(|_| HalError::Gpio)? {
+                return Ok(());
+            }
+            self.delay.delay_us(BUSY_POLL_US);
+        }
+        log::warn!("BUSY pin timeout after {}ms", timeout_ms);
+        Err(HalError::Timeout)
+    }
+}
+
+impl<SPI, BUSYPIN, RESETPIN, DIO1PIN, D> Hal for EmbeddedHal<SPI, BUSYPIN, RESETPIN, DIO1PIN, D>
+where
+    SPI: SpiDevice,
+    BUSYPIN: InputPin,
+    RESETPIN: OutputPin,
+    DIO1PIN: InputPin,
+    D: DelayNs,
+{
+    fn write_command(&mut self, opcode: u8, data: &[u8]) -> Result<(), HalError> {
+        if opcode == OP_READ_BUFFER {
+            self.read_offset = data.first().copied().unwrap_or(0);
+            return Ok(());
+        }
+        self.spi
+            .transaction(&mut [Operation::Write(&[opcode]), Operation::Write(data)])
+            .map_err(|_| HalError::Spi)?;
+        log::trace!("SPI write command 0x{:02X}, {} bytes", opcode, data.len());
+        if opcode == OP_SET_SLEEP {
+            return Ok(());
+        }
+        self.wait_busy_low(COMMAND_BUSY_TIMEOUT_MS)
+    }
+
+    fn read_command(&mut self, opcode: u8, buf: &mut [u8]) -> Result<(), HalError> {
+        let (header, len) = read_header(opcode, self.read_offset);
+        self.spi
+            .transaction(&mut [Operation::Write(&header[..len]), Operation::Read(buf)])
+            .map_err(|_| HalError::Spi)?;
+        log::trace!("SPI read command 0x{:02X}, {} bytes", opcode, buf.len());
+        Ok(())
+    }
+
+    fn write_register(&mut self, addr: u16, data: &[u8]) -> Result<(), HalError> {
+        let [hi, lo] = addr.to_be_bytes();
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[OP_WRITE_REGISTER, hi, lo]),
+                Operation::Write(data),
+            ])
+            .map_err(|_| HalError::Register)?;
+        log::trace!("Register write 0x{:04X}, {} bytes", addr, data.len());
+        self.wait_busy_low(REGISTER_BUSY_TIMEOUT_MS)
+    }
+
+    fn read_register(&mut self, addr: u16, buf: &mut [u8]) -> Result<(), HalError> {
+        let [hi, lo] = addr.to_be_bytes();
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[OP_READ_REGISTER, hi, lo, NOP]),
+                Operation::Read(buf),
+            ])
+            .map_err(|_| HalError::Register)?;
+        log::trace!("Register read 0x{:04X}, {} bytes", addr, buf.len());
+        Ok(())
+    }
+
+    fn gpio_read(&mut self, pin: u8) -> Result<bool, HalError> {
+        match pin {
+            DIO1 => self.dio1.is_high().map_err(|_| HalError::Gpio),
+            BUSY => self.busy.is_high().map_err(|_| HalError::Gpio),
+            _ => Err(HalError::Gpio),
+        }
+    }
+
+    fn gpio_write(&mut self, pin: u8, value: bool) -> Result<(), HalError> {
+        if pin != RESET {
+            log::warn!("GPIO write to unsupported pin {}", pin);
+            return Err(HalError::Gpio);
+        }
+        if value {
+            self.reset.set_high()
+        } else {
+            self.reset.set_low()
+        }
+        .map_err(|_| HalError::Gpio)
+    }
+}
+
+/// SX126x bus adapter over `embedded-hal-async` traits
+///
+/// Offers the [`Hal`] operations as `async fn`s with identical framing and BUSY
+/// handling, for executors such as Embassy where SPI transfers and delays yield
+/// instead of blocking.
+///
+/// This is a standalone bus adapter, not a driver backend: it does not implement
+/// [`Hal`], so `Sx126xDriver` cannot run on it. Use it to issue SX126x commands
+/// directly from async firmware, or [`EmbeddedHal`] to drive the chip through
+/// `Sx126xDriver`.
+pub struct EmbeddedHalAsync<SPI, BUSYPIN, RESETPIN, DIO1PIN, D> {
+    spi: SPI,
+    busy: BUSYPIN,
+    reset: RESETPIN,
+    dio1: DIO1PIN,
+    delay: D,
+    read_offset: u8,
+}
+
+impl<SPI, BUSYPIN, RESETPIN, DIO1PIN, D> EmbeddedHalAsync<SPI, BUSYPIN, RESETPIN, DIO1PIN, D>
+where
+    SPI: embedded_hal_async::spi::SpiDevice,
+    BUSYPIN: InputPin,
+    RESETPIN: OutputPin,
+    DIO1PIN: InputPin,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    /// Create an adapter from an async SPI device, the control lines and an async delay
+    pub fn new(spi: SPI, busy: BUSYPIN, reset: RESETPIN, dio1: DIO1PIN, delay: D) -> Self {
+        Self {
+            spi,
+            busy,
+            reset,
+            dio1,
+            delay,
+            read_offset: 0,
+        }
+    }
+
+    /// Release the wrapped peripherals
+    pub fn release(self) -> (SPI, BUSYPIN, RESETPIN, DIO1PIN, D) {
+        (self.spi, self.busy, self.reset, self.dio1, self.delay)
+    }
+
+    /// Pulse RESET low for 100 µs, then wait for the chip to come up
+    pub async fn reset(&mut self) -> Result<(), HalError> {
+        self.reset.set_low().map_err(|_| HalError::Gpio)?;
+        self.delay.delay_us(100).await;
+        self.reset.set_high().map_err(|_| HalError::Gpio)?;
+        self.delay.delay_ms(1).await;
+        self.wait_busy_low(COMMAND_BUSY_TIMEOUT_MS).await
+    }
+
+    /// Write a command with optional data to the radio
+    pub async fn write_command(&mut self, opcode: u8, data: &[u8]) -> Result<(), HalError> {
+        if opcode == OP_READ_BUFFER {
+            self.read_offset = data.first().copied().unwrap_or(0);
+            return Ok(());
+        }
+        self.spi
+            .transaction(&mut [Operation::Write(&[opcode]), Operation::Write(data)])
+            .await
+            .map_err(|_| HalError::Spi)?;
+        if opcode == OP_SET_SLEEP {
+            return Ok(());
+        }
+        self.wait_busy_low(COMMAND_BUSY_TIMEOUT_MS).await
+    }
+
+    /// Read a command response from the radio
+    pub async fn read_command(&mut self, opcode: u8, buf: &mut [u8]) -> Result<(), HalError> {
+        let (header, len) = read_header(opcode, self.read_offset);
+        self.spi
+            .transaction(&mut [Operation::Write(&header[..len]), Operation::Read(buf)])
+            .await
+            .map_err(|_| HalError::Spi)
+    }
+
+    /// Write data to a radio register
+    pub async fn write_register(&mut self, addr: u16, data: &[u8]) -> Result<(), HalError> {
+        let [hi, lo] = addr.to_be_bytes();
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[OP_WRITE_REGISTER, hi, lo]),
+                Operation::Write(data),
+            ])
+            .await
+            .map_err(|_| HalError::Register)?;
+        self.wait_busy_low(REGISTER_BUSY_TIMEOUT_MS).await
+    }
+
+    /// Read data from a radio register
+    pub async fn read_register(&mut self, addr: u16, buf: &mut [u8]) -> Result<(), HalError> {
+        let [hi, lo] = addr.to_be_bytes();
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[OP_READ_REGISTER, hi, lo, NOP]),
+                Operation::Read(buf),
+            ])
+            .await
+            .map_err(|_| HalError::Register)
+    }
+
+    /// Read the state of a GPIO pin ([`DIO1`] or [`BUSY`])
+    pub fn gpio_read(&mut self, pin: u8) -> Result<bool, HalError> {
+        match pin {
+            DIO1 => self.dio1.is_high().map_err(|_| HalError::Gpio),
+            BUSY => self.busy.is_high().map_err(|_| HalError::Gpio),
+            _ => Err(HalError::Gpio),
+        }
+    }
+
+    /// Drive the [`RESET`] line
+    pub fn gpio_write(&mut self, pin: u8, value: bool) -> Result<(), HalError> {
+        if pin != RESET {
+            return Err(HalError::Gpio);
+        }
+        if value {
+            self.reset.set_high()
+        } else {
+            self.reset.set_low()
+        }
+        .map_err(|_| HalError::Gpio)
+    }
+
+    /// Poll BUSY until it falls or `timeout_ms` elapses
+    async fn wait_busy_low(&mut self, timeout_ms: u32) -> Result<(), HalError> {
+        for _ in 0..busy_polls(timeout_ms) {
+            if self.busy.is_low().map_err(|_| HalError::Gpio)? {
+                return Ok(());
+            }
+            self.delay.delay_us(BUSY_POLL_US).await;
+        }
+        Err(HalError::Timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, NoopDelay, Transaction as DelayTx};
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTx};
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTx};
+
+    type TestHal = EmbeddedHal<SpiMock<u8>, PinMock, PinMock, PinMock, NoopDelay>;
+
+    fn hal(spi: &[SpiTx<u8>], busy: &[PinTx]) -> TestHal {
+        EmbeddedHal::new(
+            SpiMock::new(spi),
+            PinMock::new(busy),
+            PinMock::new(&[]),
+            PinMock::new(&[]),
+            NoopDelay::new(),
+        )
+    }
+
+    fn done(hal: TestHal) {
+        let (mut spi, mut busy, mut reset, mut dio1, _) = hal.release();
+        spi.done();
+        busy.done();
+        reset.done();
+        dio1.done();
+    }
+
+    #[test]
+    fn write_command_frames_opcode_and_waits_for_busy() {
+        let mut hal = hal(
+            &[
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x8A]),
+                SpiTx::write_vec(vec![0x00]),
+                SpiTx::transaction_end(),
+            ],
+            &[PinTx::get(State::High), PinTx::get(State::Low)],
+        );
+        hal.write_command(0x8A, &[0x00]).unwrap(); // SetPacketType(GFSK)
+        done(hal);
+    }
+
+    #[test]
+    fn set_sleep_does_not_wait_for_busy() {
+        let mut hal = hal(
+            &[
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x84]),
+                SpiTx::write_vec(vec![0x04]),
+                SpiTx::transaction_end(),
+            ],
+            &[],
+        );
+        hal.write_command(0x84, &[0x04]).unwrap();
+        done(hal);
+    }
+
+    #[test]
+    fn read_command_skips_status_byte() {
+        let mut hal = hal(
+            &[
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x12, 0x00]),
+                SpiTx::read_vec(vec![0x00, 0x02]),
+                SpiTx::transaction_end(),
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0xC0]),
+                SpiTx::read_vec(vec![0x52]),
+                SpiTx::transaction_end(),
+            ],
+            &[],
+        );
+        let mut irq = [0u8; 2];
+        hal.read_command(0x12, &mut irq).unwrap(); // GetIrqStatus
+        assert_eq!(irq, [0x00, 0x02]);
+        let mut status = [0u8; 1];
+        hal.read_command(0xC0, &mut status).unwrap(); // GetStatus
+        assert_eq!(status, [0x52]);
+        done(hal);
+    }
+
+    #[test]
+    fn read_buffer_uses_latched_offset() {
+        let mut hal = hal(
+            &[
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x1E, 0x80, 0x00]),
+                SpiTx::read_vec(vec![0x44, 0x93, 0x15]),
+                SpiTx::transaction_end(),
+            ],
+            &[],
+        );
+        hal.write_command(0x1E, &[0x80, 0x00, 3]).unwrap();
+        let mut buf = [0u8; 3];
+        hal.read_command(0x1E, &mut buf).unwrap();
+        assert_eq!(buf, [0x44, 0x93, 0x15]);
+        done(hal);
+    }
+
+    #[test]
+    fn register_access_frames_address() {
+        let mut hal = hal(
+            &[
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x0D, 0x06, 0xC0]),
+                SpiTx::write_vec(vec![0x54, 0x3D]),
+                SpiTx::transaction_end(),
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x1D, 0x06, 0xC0, 0x00]),
+                SpiTx::read_vec(vec![0x54, 0x3D]),
+                SpiTx::transaction_end(),
+            ],
+            &[PinTx::get(State::Low)],
+        );
+        hal.write_register(0x06C0, &[0x54, 0x3D]).unwrap(); // SyncWord
+        let mut sync = [0u8; 2];
+        hal.read_register(0x06C0, &mut sync).unwrap();
+        assert_eq!(sync, [0x54, 0x3D]);
+        done(hal);
+    }
+
+    #[test]
+    fn busy_stuck_high_times_out() {
+        let polls = busy_polls(REGISTER_BUSY_TIMEOUT_MS) as usize;
+        let mut hal = hal(
+            &[
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x0D, 0x08, 0xAC]),
+                SpiTx::write_vec(vec![0x96]),
+                SpiTx::transaction_end(),
+            ],
+            &vec![PinTx::get(State::High); polls],
+        );
+        assert!(matches!(
+            hal.write_register(0x08AC, &[0x96]),
+            Err(HalError::Timeout)
+        ));
+        done(hal);
+    }
+
+    #[test]
+    fn reset_pulse_and_gpio_mapping() {
+        let mut hal = EmbeddedHal::new(
+            SpiMock::new(&[]),
+            PinMock::new(&[PinTx::get(State::Low), PinTx::get(State::High)]),
+            PinMock::new(&[
+                PinTx::set(State::Low),
+                PinTx::set(State::High),
+                PinTx::set(State::Low),
+            ]),
+            PinMock::new(&[PinTx::get(State::High)]),
+            CheckedDelay::new(&[DelayTx::delay_us(100), DelayTx::delay_ms(1)]),
+        );
+        hal.reset().unwrap();
+        assert!(hal.gpio_read(DIO1).unwrap());
+        assert!(hal.gpio_read(BUSY).unwrap());
+        hal.gpio_write(RESET, false).unwrap();
+        assert!(matches!(hal.gpio_read(7), Err(HalError::Gpio)));
+        assert!(matches!(hal.gpio_write(DIO1, true), Err(HalError::Gpio)));
+
+        let (mut spi, mut busy, mut reset, mut dio1, mut delay) = hal.release();
+        spi.done();
+        busy.done();
+        reset.done();
+        dio1.done();
+        delay.done();
+    }
+
+    #[tokio::test]
+    async fn async_adapter_matches_blocking_framing() {
+        let mut hal = EmbeddedHalAsync::new(
+            SpiMock::new(&[
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x82]),
+                SpiTx::write_vec(vec![0xFF, 0xFF, 0xFF]),
+                SpiTx::transaction_end(),
+                SpiTx::transaction_start(),
+                SpiTx::write_vec(vec![0x15, 0x00]),
+                SpiTx::read_vec(vec![0xDC]),
+                SpiTx::transaction_end(),
+            ]),
+            PinMock::new(&[PinTx::get(State::High), PinTx::get(State::Low)]),
+            PinMock::new(&[]),
+            PinMock::new(&[]),
+            CheckedDelay::new(&[DelayTx::async_delay_us(BUSY_POLL_US)]),
+        );
+        hal.write_command(0x82, &[0xFF, 0xFF, 0xFF]).await.unwrap(); // SetRx continuous
+        let mut rssi = [0u8; 1];
+        hal.read_command(0x15, &mut rssi).await.unwrap(); // GetRssiInst
+        assert_eq!(rssi, [0xDC]);
+
+        let (mut spi, mut busy, mut reset, mut dio1, mut delay) = hal.release();
+        spi.done();
+        busy.done();
+        reset.done();
+        dio1.done();
+        delay.done();
+    }
+}
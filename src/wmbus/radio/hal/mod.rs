@@ -46,6 +46,25 @@ pub trait Hal {
     fn gpio_write(&mut self, pin: u8, value: bool) -> Result<(), HalError>;
 }
 
+/// Hardware Abstraction Layer trait for RFM69 radio control
+///
+/// The RFM69 is register-mapped: every SPI transaction addresses one 7-bit register,
+/// and FIFO bytes move through `RegFifo` (0x00). The only GPIO the driver drives is
+/// the active-high RESET line.
+pub trait Rfm69Hal: Send {
+    /// Read a single radio register
+    fn read_register(&mut self, reg: u8) -> Result<u8, HalError>;
+
+    /// Write a single radio register
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), HalError>;
+
+    /// Whether a RESET line is wired; without one the driver skips the reset pulse
+    fn has_reset(&self) -> bool;
+
+    /// Drive the RESET line (high holds the chip in reset)
+    fn set_reset(&mut self, high: bool) -> Result<(), HalError>;
+}
+
 /// A no-op [`Hal`] implementation for tests, examples, and documentation.
 ///
 /// Every write succeeds and is discarded; every read succeeds and yields zeroes.
@@ -102,7 +121,13 @@ impl Hal for MockHal {
 // Enhanced GPIO abstraction
 pub mod enhanced_gpio;
 
+// Behavioural chip models for host-side tests
+pub mod rfm69_model;
+pub mod sx126x_model;
+
 // Platform implementations
+#[cfg(feature = "embedded-hal")]
+pub mod embedded;
 #[cfg(feature = "raspberry-pi")]
 pub mod raspberry_pi;
 
@@ -110,8 +135,14 @@ pub mod raspberry_pi;
 pub use enhanced_gpio::{
     EdgeType, EnhancedGpio, EnhancedGpioError, GpioConfig, GpioEvent, GpioEventType, GpioStats,
 };
+pub use rfm69_model::Rfm69Model;
+pub use sx126x_model::{InjectedPacket, Sx126xModel};
 
 // Re-export platform implementations for convenience
+#[cfg(feature = "embedded-hal")]
+pub use embedded::{EmbeddedHal, EmbeddedHalAsync};
+#[cfg(feature = "rfm69")]
+pub use raspberry_pi::Rfm69PiHal;
 #[cfg(feature = "raspberry-pi")]
 pub use raspberry_pi::{GpioPins, RaspberryPiHal, RaspberryPiHalBuilder};
 
@@ -145,6 +176,10 @@ struct Recording {
     pending_rx: Option<Vec<u8>>,
     /// Raw 3 bytes returned by GetPacketStatus (0x14): [RssiPkt, SnrPkt, SignalRssiPkt].
     packet_status: [u8; 3],
+    /// Raw byte returned by GetRssiInst (0x15); RSSI = -raw / 2 dBm.
+    rssi_inst: u8,
+    /// Set by SetTx: GetIrqStatus reports TxDone until ClearIrqStatus clears it.
+    tx_done: bool,
 }
 
 #[cfg(test)]
@@ -180,11 +215,21 @@ impl RecordingHal {
         self.inner.lock().unwrap().packet_status = bytes;
     }
 
+    /// Set the instantaneous RSSI GetRssiInst (0x15) reports, in dBm, for LBT checks.
+    pub fn set_rssi(&self, rssi_dbm: i16) {
+        self.inner.lock().unwrap().rssi_inst = (-rssi_dbm * 2).clamp(0, 255) as u8;
+    }
+
     /// Snapshot of the recorded `(opcode, data)` command stream, in order.
     pub fn commands(&self) -> Vec<(u8, Vec<u8>)> {
         self.inner.lock().unwrap().commands.clone()
     }
 
+    /// Snapshot of the recorded `(address, data)` register writes, in order.
+    pub fn register_writes(&self) -> Vec<(u16, Vec<u8>)> {
+        self.inner.lock().unwrap().reg_writes.clone()
+    }
+
     /// Index of the first recorded write of `opcode`, if any.
     pub fn first_cmd(&self, opcode: u8) -> Option<usize> {
         self.inner
@@ -223,9 +268,18 @@ impl Hal for RecordingHal {
         match opcode {
             0x80 => g.mode_bits = if data.first() == Some(&0x01) { 3 } else { 2 }, // SetStandby XOSC/RC
             0x82 => g.mode_bits = 5,                                               // SetRx
-            0x83 => g.mode_bits = 6,                                               // SetTx
-            0x84 => g.mode_bits = 0,                                               // SetSleep
-            0xC1 => g.mode_bits = 4,                                               // SetFs
+            0x83 => {
+                // SetTx: the packet goes out at once and TxDone is raised.
+                g.mode_bits = 6;
+                g.tx_done = true;
+            }
+            // ClearIrqStatus with TxDone: the chip has fallen back to STDBY_RC.
+            0x02 if g.tx_done && data.get(1).is_some_and(|b| b & 0x01 != 0) => {
+                g.tx_done = false;
+                g.mode_bits = 2;
+            }
+            0x84 => g.mode_bits = 0, // SetSleep
+            0xC1 => g.mode_bits = 4, // SetFs
             _ => {}
         }
         g.commands.push((opcode, data.to_vec()));
@@ -237,17 +291,19 @@ impl Hal for RecordingHal {
         let mut g = self.inner.lock().unwrap();
         match opcode {
             // GetStatus: chip mode in bits [6:4].
-            0xC0 => {
-                if !buf.is_empty() {
-                    buf[0] = g.mode_bits << 4;
-                }
-            }
+            0xC0 if !buf.is_empty() => buf[0] = g.mode_bits << 4,
             // GetIrqStatus (u16, big-endian): report RxDone (bit 1) when a packet is queued.
-            0x12 => {
-                if g.pending_rx.is_some() && buf.len() >= 2 {
-                    buf[1] = 0x02;
+            // TxDone (bit 0) is raised after SetTx.
+            0x12 if buf.len() >= 2 => {
+                if g.pending_rx.is_some() {
+                    buf[1] |= 0x02;
+                }
+                if g.tx_done {
+                    buf[1] |= 0x01;
                 }
             }
+            // GetRssiInst
+            0x15 if !buf.is_empty() => buf[0] = g.rssi_inst,
             // GetRxBufferStatus: byte 0 is the payload length.
             0x13 => {
                 if let (Some(p), false) = (g.pending_rx.as_ref(), buf.is_empty()) {
@@ -67,6 +67,8 @@
 //! let mut driver = Sx126xDriver::new(hal, 32_000_000);
 //! ```
 
+#[cfg(feature = "rfm69")]
+use crate::wmbus::radio::hal::Rfm69Hal;
 use crate::wmbus::radio::hal::{Hal, HalError};
 #[cfg(feature = "rfm69")]
 use crate::wmbus::radio::rfm69_registers::SPI_SPEED as RFM69_SPI_SPEED;
@@ -745,9 +747,35 @@ impl Default for Rfm69GpioPins {
     }
 }
 
+#[cfg(feature = "rfm69")]
+/// Parse a `/dev/spidevB.C` path into its rppal (Bus, SlaveSelect).
+/// e.g. `/dev/spidev0.1` → (Spi0, Ss1). Falls back to (Spi0, Ss0) on any parse failure.
+fn parse_spidev(path: &str) -> (Bus, SlaveSelect) {
+    let tail = path.rsplit_once("spidev").map(|(_, t)| t).unwrap_or("");
+    let (b, c) = tail.split_once('.').unwrap_or(("0", "0"));
+    let bus = match b.trim().parse::<u8>().unwrap_or(0) {
+        1 => Bus::Spi1,
+        2 => Bus::Spi2,
+        3 => Bus::Spi3,
+        4 => Bus::Spi4,
+        5 => Bus::Spi5,
+        6 => Bus::Spi6,
+        _ => Bus::Spi0,
+    };
+    let ss = match c.trim().parse::<u8>().unwrap_or(0) {
+        1 => SlaveSelect::Ss1,
+        2 => SlaveSelect::Ss2,
+        _ => SlaveSelect::Ss0,
+    };
+    (bus, ss)
+}
+
 #[cfg(feature = "rfm69")]
 /// Create RFM69-specific SPI and GPIO setup for Raspberry Pi
 ///
+/// The bus and chip-select are derived from `spi_device` (e.g. `/dev/spidev0.1` →
+/// bus 0, CS1); `None` selects `/dev/spidev0.0`.
+///
 /// Returns (SpiDevice, OutputPin, InputPin) for RFM69 driver use
 pub fn new_rfm69_spi(
     gpio: &Gpio,
@@ -755,11 +783,13 @@ pub fn new_rfm69_spi(
     pins: &Rfm69GpioPins,
 ) -> Result<(Spi, Option<OutputPin>, Option<InputPin>), RpiHalError> {
     // Initialize SPI with RFM69-specific settings (1 MHz, Mode 0)
-    let mut spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, RFM69_SPI_SPEED, Mode::Mode0)?;
+    let path = spi_device.unwrap_or("/dev/spidev0.0");
+    let (bus, ss) = parse_spidev(path);
+    let spi = Spi::new(bus, ss, RFM69_SPI_SPEED, Mode::Mode0)?;
     spi.set_bit_order(BitOrder::MsbFirst)?;
 
     log::info!(
-        "RFM69 SPI initialized: {} Hz, Mode 0, MSB first",
+        "RFM69 SPI initialized: {path} (bus={bus:?}, cs={ss:?}), {} Hz, Mode 0, MSB first",
         RFM69_SPI_SPEED
     );
 
@@ -852,4 +882,69 @@ impl Rfm69HalBuilder {
         let gpio = Gpio::new().map_err(|e| RpiHalError::GpioInit(e))?;
         new_rfm69_spi(&gpio, self.spi_device.as_deref(), &self.gpio_pins)
     }
+
+    /// Build an [`Rfm69PiHal`] for [`Rfm69Driver`](crate::wmbus::radio::rfm69::Rfm69Driver)
+    pub fn build_hal(self) -> Result<Rfm69PiHal, RpiHalError> {
+        let (spi, reset, interrupt) = self.build()?;
+        Ok(Rfm69PiHal::new(spi, reset, interrupt))
+    }
+}
+
+#[cfg(feature = "rfm69")]
+/// [`Rfm69Hal`] over a Raspberry Pi SPI device and GPIO pins
+///
+/// Register reads are a two-byte transfer `[reg & 0x7F, 0]`; writes send
+/// `[reg | 0x80, value]`.
+pub struct Rfm69PiHal {
+    spi: Spi,
+    reset: Option<OutputPin>,
+    /// DIO1 stays claimed for the radio; the driver polls `RegIrqFlags2` instead
+    _interrupt: Option<InputPin>,
+}
+
+#[cfg(feature = "rfm69")]
+impl Rfm69PiHal {
+    /// Wrap an opened SPI device and optional RESET / DIO1 pins
+    pub fn new(spi: Spi, reset: Option<OutputPin>, interrupt: Option<InputPin>) -> Self {
+        Self {
+            spi,
+            reset,
+            _interrupt: interrupt,
+        }
+    }
+}
+
+#[cfg(feature = "rfm69")]
+impl Rfm69Hal for Rfm69PiHal {
+    fn read_register(&mut self, reg: u8) -> Result<u8, HalError> {
+        let tx = [reg & 0x7F, 0];
+        let mut rx = [0u8; 2];
+        self.spi.transfer(&mut rx, &tx).map_err(|e| {
+            log::error!("RFM69 register read 0x{reg:02X} failed: {e}");
+            HalError::Spi
+        })?;
+        Ok(rx[1])
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), HalError> {
+        self.spi.write(&[reg | 0x80, value]).map_err(|e| {
+            log::error!("RFM69 register write 0x{reg:02X} failed: {e}");
+            HalError::Spi
+        })?;
+        Ok(())
+    }
+
+    fn has_reset(&self) -> bool {
+        self.reset.is_some()
+    }
+
+    fn set_reset(&mut self, high: bool) -> Result<(), HalError> {
+        let pin = self.reset.as_mut().ok_or(HalError::Gpio)?;
+        if high {
+            pin.set_high();
+        } else {
+            pin.set_low();
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,1245 @@
+//! A behavioural SX126x behind the [`Hal`] trait.
+//!
+//! [`Sx126xModel`] answers the datasheet opcodes the way the chip does, so driver code
+//! can be tested against radio behaviour rather than against the commands it sends:
+//!
+//! - the chip modes (sleep, STDBY_RC/XOSC, FS, RX, TX, CAD) with the datasheet's
+//!   transition rules, and a BUSY line that is high while a command is processed, while
+//!   the chip wakes up and for as long as it sleeps;
+//! - the register file, with the power-on values of the sync word, CRC, whitening, LoRa
+//!   sync word and RX gain registers;
+//! - packet type, modulation and packet parameters, RF frequency and buffer base
+//!   addresses, which decide the time on air and how received packets are framed;
+//! - the IRQ mask and status, the DIO lines they drive, and the 256-byte data buffer;
+//! - packets injected with [`Sx126xModel::inject`], received only while the chip listens
+//!   with the packet's modem, with their RSSI, SNR and frequency error;
+//! - the command status in `GetStatus`, device errors and the receive statistics.
+//!
+//! Time is real time: a transmission takes its time on air, an RX timeout expires on the
+//! wall clock. The model also plays the host side of the bus, waiting for BUSY to fall
+//! after each write as the Raspberry Pi HAL does.
+//!
+//! Not modelled: RF output power, image calibration, address filtering, sync word and
+//! preamble matching, whitening and the GFSK receiver bandwidth codes.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use mbus_rs::wmbus::radio::driver::{LbtConfig, Sx126xDriver};
+//! use mbus_rs::wmbus::radio::hal::Sx126xModel;
+//!
+//! let chip = Sx126xModel::new();
+//! let mut driver = Sx126xDriver::new(chip.clone(), 32_000_000);
+//! driver.configure_for_wmbus(868_950_000, 100_000).unwrap();
+//! driver.transmit(&[0x44, 0x2D, 0x2C], &LbtConfig::default()).unwrap();
+//! assert_eq!(chip.transmitted(), vec![vec![0x44, 0x2D, 0x2C]]);
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::{Hal, HalError};
+use crate::wmbus::radio::driver::RadioState;
+use crate::wmbus::radio::irq::IrqMaskBit;
+use crate::wmbus::radio::modulation::PacketType;
+
+/// Crystal frequency the model assumes for frequency and bitrate registers.
+pub const XTAL_HZ: u32 = 32_000_000;
+
+/// `gpio_write` pin of the active-low NRESET line.
+pub const PIN_RESET: u8 = 0;
+/// `gpio_read` pin of DIO1.
+pub const PIN_DIO1: u8 = 1;
+/// `gpio_read` pin of DIO2.
+pub const PIN_DIO2: u8 = 2;
+/// `gpio_read` pin of DIO3.
+pub const PIN_DIO3: u8 = 3;
+/// `gpio_read` pin of BUSY.
+pub const PIN_BUSY: u8 = 4;
+
+/// BUSY after a configuration command.
+const COMMAND_BUSY: Duration = Duration::from_micros(20);
+/// BUSY while the PLL locks on entering FS, RX, TX or CAD.
+const PLL_LOCK_BUSY: Duration = Duration::from_micros(60);
+/// BUSY while waking from a warm-start sleep.
+const WARM_WAKE_BUSY: Duration = Duration::from_micros(340);
+/// BUSY while waking from a cold-start sleep or after reset.
+const COLD_WAKE_BUSY: Duration = Duration::from_micros(3_500);
+/// Longest the host side waits for BUSY to fall, as the Raspberry Pi HAL does.
+const BUSY_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Command status, GetStatus bits [3:1].
+const STATUS_DATA_AVAILABLE: u8 = 0x2;
+const STATUS_PROCESSING_ERROR: u8 = 0x4;
+const STATUS_EXEC_FAILURE: u8 = 0x5;
+const STATUS_TX_DONE: u8 = 0x6;
+
+/// Device error bit for a PLL that fails to lock (out-of-band frequency).
+const PLL_LOCK_ERROR: u16 = 0x0040;
+
+/// RX timeout value selecting continuous reception.
+const RX_CONTINUOUS: u32 = 0xFF_FFFF;
+
+/// Supported RF range of the SX1261/2.
+const RF_RANGE_HZ: std::ops::RangeInclusive<u32> = 150_000_000..=960_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Sleep,
+    StandbyRc,
+    StandbyXosc,
+    Fs,
+    Rx,
+    Tx,
+    Cad,
+}
+
+impl Mode {
+    /// Chip mode as reported in GetStatus bits [6:4].
+    fn bits(self) -> u8 {
+        match self {
+            Mode::Sleep => 0x0,
+            Mode::StandbyRc => 0x2,
+            Mode::StandbyXosc => 0x3,
+            Mode::Fs => 0x4,
+            Mode::Rx | Mode::Cad => 0x5,
+            Mode::Tx => 0x6,
+        }
+    }
+
+    fn is_standby(self) -> bool {
+        matches!(self, Mode::StandbyRc | Mode::StandbyXosc)
+    }
+}
+
+/// A packet on the air, for [`Sx126xModel::inject`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct InjectedPacket {
+    /// Modem it was sent with; the chip hears it only with the same packet type.
+    pub packet_type: PacketType,
+    /// Payload after the sync word (GFSK) or header (LoRa), without the CRC.
+    pub payload: Vec<u8>,
+    /// Signal strength at the antenna, in dBm.
+    pub rssi_dbm: f64,
+    /// LoRa signal-to-noise ratio, in dB.
+    pub snr_db: f64,
+    /// Whether the packet CRC checks out; ignored when the chip's CRC is off.
+    pub crc_ok: bool,
+    /// LoRa carrier offset as the demodulator estimates it, in Hz.
+    pub freq_error_hz: i32,
+    /// Carrier; `None` is heard on any frequency.
+    pub frequency_hz: Option<u32>,
+}
+
+impl InjectedPacket {
+    /// A clean GFSK packet at `rssi_dbm`.
+    pub fn gfsk(payload: &[u8], rssi_dbm: f64) -> Self {
+        Self {
+            packet_type: PacketType::Gfsk,
+            payload: payload.to_vec(),
+            rssi_dbm,
+            snr_db: 0.0,
+            crc_ok: true,
+            freq_error_hz: 0,
+            frequency_hz: None,
+        }
+    }
+
+    /// A clean LoRa packet at `rssi_dbm` and `snr_db`.
+    pub fn lora(payload: &[u8], rssi_dbm: f64, snr_db: f64) -> Self {
+        Self {
+            packet_type: PacketType::LoRa,
+            snr_db,
+            ..Self::gfsk(payload, rssi_dbm)
+        }
+    }
+
+    /// Fail the packet CRC.
+    pub fn crc_error(mut self) -> Self {
+        self.crc_ok = false;
+        self
+    }
+
+    /// Set the LoRa frequency error.
+    pub fn freq_error(mut self, freq_error_hz: i32) -> Self {
+        self.freq_error_hz = freq_error_hz;
+        self
+    }
+
+    /// Send the packet on one carrier only.
+    pub fn on_frequency(mut self, frequency_hz: u32) -> Self {
+        self.frequency_hz = Some(frequency_hz);
+        self
+    }
+}
+
+/// Configuration the chip keeps through a warm-start sleep and loses on a cold start.
+#[derive(Debug, Clone)]
+struct Config {
+    packet_type: PacketType,
+    /// RfFreq register, `frequency · 2^25 / XTAL_HZ`; 0 until SetRfFrequency.
+    rf_freq: u32,
+    modulation: Vec<u8>,
+    packet: Vec<u8>,
+    tx_base: u8,
+    rx_base: u8,
+    irq_mask: u16,
+    dio_masks: [u16; 3],
+    cad: [u8; 7],
+    fallback: Mode,
+    dio2_rf_switch: bool,
+    dio3_tcxo: bool,
+    registers: BTreeMap<u16, u8>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut registers = BTreeMap::new();
+        let defaults: [(u16, &[u8]); 6] = [
+            (0x06B8, &[0x01, 0x00]), // Whitening initial value
+            (0x06BC, &[0x1D, 0x0F]), // CRC initial value
+            (0x06BE, &[0x10, 0x21]), // CRC polynomial
+            (0x06C0, &[0x97, 0x23, 0x52, 0x25, 0x56, 0x53, 0x65, 0x64]), // GFSK sync word
+            (0x0740, &[0x14, 0x24]), // LoRa sync word (private)
+            (0x08AC, &[0x94]),       // RX gain
+        ];
+        for (addr, bytes) in defaults {
+            for (i, &b) in bytes.iter().enumerate() {
+                registers.insert(addr + i as u16, b);
+            }
+        }
+        Self {
+            packet_type: PacketType::Gfsk,
+            rf_freq: 0,
+            modulation: Vec::new(),
+            packet: Vec::new(),
+            tx_base: 0,
+            rx_base: 0,
+            irq_mask: 0,
+            dio_masks: [0; 3],
+            cad: [0x01, 0x16, 0x0A, 0x00, 0x00, 0x00, 0x00],
+            fallback: Mode::StandbyRc,
+            dio2_rf_switch: false,
+            dio3_tcxo: false,
+            registers,
+        }
+    }
+}
+
+impl Config {
+    fn frequency_hz(&self) -> u32 {
+        ((u64::from(self.rf_freq) * u64::from(XTAL_HZ)) >> 25) as u32
+    }
+
+    fn packet_byte(&self, i: usize, default: u8) -> u8 {
+        self.packet.get(i).copied().unwrap_or(default)
+    }
+
+    fn modulation_byte(&self, i: usize, default: u8) -> u8 {
+        self.modulation.get(i).copied().unwrap_or(default)
+    }
+
+    /// `(variable/explicit length, maximum payload, CRC on)` from the packet params.
+    fn framing(&self) -> (bool, u8, bool) {
+        match self.packet_type {
+            PacketType::Gfsk => (
+                self.packet_byte(5, 0x01) == 0x01,
+                self.packet_byte(6, 0xFF),
+                self.packet_byte(7, 0x01) != 0x01,
+            ),
+            PacketType::LoRa => (
+                self.packet_byte(2, 0x00) == 0x00,
+                self.packet_byte(3, 0xFF),
+                self.packet_byte(4, 0x01) == 0x01,
+            ),
+        }
+    }
+
+    fn lora_bandwidth_hz(&self) -> f64 {
+        match self.modulation_byte(1, 0x04) {
+            0x00 => 7_810.0,
+            0x08 => 10_420.0,
+            0x01 => 15_630.0,
+            0x09 => 20_830.0,
+            0x02 => 31_250.0,
+            0x0A => 41_670.0,
+            0x03 => 62_500.0,
+            0x05 => 250_000.0,
+            0x06 => 500_000.0,
+            _ => 125_000.0,
+        }
+    }
+
+    fn lora_symbol_time(&self) -> f64 {
+        let sf = self.modulation_byte(0, 0x07).clamp(5, 12);
+        f64::from(1u32 << sf) / self.lora_bandwidth_hz()
+    }
+
+    /// Time on air of a `len`-byte payload with the current parameters.
+    fn time_on_air(&self, len: usize) -> Duration {
+        let (variable, _, crc_on) = self.framing();
+        let seconds = match self.packet_type {
+            PacketType::Gfsk => {
+                let br = u32::from_be_bytes([
+                    0,
+                    self.modulation_byte(0, 0x03),
+                    self.modulation_byte(1, 0x41),
+                    self.modulation_byte(2, 0x55),
+                ])
+                .max(1);
+                let bitrate = 32.0 * f64::from(XTAL_HZ) / f64::from(br);
+                let preamble =
+                    u16::from_be_bytes([self.packet_byte(0, 0), self.packet_byte(1, 16)]);
+                let crc_bits = match self.packet_byte(7, 0x01) {
+                    0x00 | 0x04 => 8,
+                    0x02 | 0x06 => 16,
+                    _ => 0,
+                };
+                let bits = usize::from(preamble)
+                    + usize::from(self.packet_byte(3, 0))
+                    + if variable { 8 } else { 0 }
+                    + 8 * len
+                    + crc_bits;
+                bits as f64 / bitrate
+            }
+            PacketType::LoRa => {
+                let sf = f64::from(self.modulation_byte(0, 0x07).clamp(5, 12));
+                let cr = f64::from(self.modulation_byte(2, 0x01).clamp(1, 4));
+                let ldro = f64::from(u8::from(self.modulation_byte(3, 0) == 0x01));
+                let preamble = u16::from_be_bytes([self.packet_byte(0, 0), self.packet_byte(1, 8)]);
+                let implicit = f64::from(u8::from(!variable));
+                let crc = f64::from(u8::from(crc_on));
+                let numerator = 8.0 * len as f64 - 4.0 * sf + 28.0 + 16.0 * crc - 20.0 * implicit;
+                let payload_symbols =
+                    8.0 + ((numerator / (4.0 * (sf - 2.0 * ldro))).ceil() * (cr + 4.0)).max(0.0);
+                let sync_symbols = if sf < 7.0 { 6.25 } else { 4.25 };
+                (f64::from(preamble) + sync_symbols + payload_symbols) * self.lora_symbol_time()
+            }
+        };
+        Duration::from_secs_f64(seconds)
+    }
+}
+
+/// What the chip is doing, advanced lazily to the current time on every access.
+struct Chip {
+    mode: Mode,
+    busy_until: Instant,
+    in_reset: bool,
+    warm_sleep: bool,
+    config: Config,
+    buffer: [u8; 256],
+    read_offset: u8,
+    irq: u16,
+    command_status: u8,
+    rx_status: [u8; 2],
+    packet_status: [u8; 3],
+    /// Packets received, CRC errors, length (GFSK) or header (LoRa) errors.
+    stats: [u16; 3],
+    device_errors: u16,
+    rx_continuous: bool,
+    /// When the running RX or TX times out.
+    deadline: Option<Instant>,
+    tx_end: Option<Instant>,
+    tx_frame: Vec<u8>,
+    cad_window: Option<(Instant, Instant)>,
+    rssi_inst: u8,
+    noise_floor_dbm: f64,
+    /// Signal on the air: RSSI, until when, and the carrier it is on (`None`: every one).
+    occupied: Option<(f64, Instant, Option<u32>)>,
+    lora_activity: Option<(Instant, Instant)>,
+    transmitted: Vec<Vec<u8>>,
+    busy_violations: u32,
+}
+
+impl Chip {
+    fn new(now: Instant) -> Self {
+        Self {
+            mode: Mode::StandbyRc,
+            busy_until: now,
+            in_reset: false,
+            warm_sleep: false,
+            config: Config::default(),
+            buffer: [0; 256],
+            read_offset: 0,
+            irq: 0,
+            command_status: 0,
+            rx_status: [0; 2],
+            packet_status: [0; 3],
+            stats: [0; 3],
+            device_errors: 0,
+            rx_continuous: false,
+            deadline: None,
+            tx_end: None,
+            tx_frame: Vec::new(),
+            cad_window: None,
+            rssi_inst: 0,
+            noise_floor_dbm: -120.0,
+            occupied: None,
+            lora_activity: None,
+            transmitted: Vec::new(),
+            busy_violations: 0,
+        }
+    }
+
+    /// Power-on reset: defaults, STDBY_RC once BUSY falls.
+    fn power_on(&mut self, now: Instant) {
+        let noise_floor_dbm = self.noise_floor_dbm;
+        let transmitted = std::mem::take(&mut self.transmitted);
+        *self = Self::new(now);
+        self.busy_until = now + COLD_WAKE_BUSY;
+        self.noise_floor_dbm = noise_floor_dbm;
+        self.transmitted = transmitted;
+    }
+
+    fn busy(&self, now: Instant) -> bool {
+        self.in_reset || self.mode == Mode::Sleep || now < self.busy_until
+    }
+
+    /// Let timed operations finish up to `now`.
+    fn catch_up(&mut self, now: Instant) {
+        if self.mode == Mode::Tx {
+            if let Some(end) = self.tx_end {
+                match self.deadline {
+                    Some(deadline) if deadline < end && deadline <= now => {
+                        self.tx_end = None;
+                        self.tx_frame.clear();
+                        self.raise(IrqMaskBit::Timeout as u16);
+                        self.fall_back();
+                    }
+                    _ if end <= now => {
+                        self.tx_end = None;
+                        self.transmitted.push(std::mem::take(&mut self.tx_frame));
+                        self.raise(IrqMaskBit::TxDone as u16);
+                        self.command_status = STATUS_TX_DONE;
+                        self.fall_back();
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let (Mode::Cad, Some((start, end))) = (self.mode, self.cad_window) {
+            if end <= now {
+                self.cad_window = None;
+                let detected = self
+                    .lora_activity
+                    .is_some_and(|(from, until)| from < end && start < until);
+                self.raise(IrqMaskBit::CadDone as u16);
+                if detected {
+                    self.raise(IrqMaskBit::CadDetected as u16);
+                }
+                // Exit mode CAD_RX listens on after a detection.
+                if detected && self.config.cad[3] == 0x01 {
+                    let timeout = u32::from_be_bytes([
+                        0,
+                        self.config.cad[4],
+                        self.config.cad[5],
+                        self.config.cad[6],
+                    ]);
+                    self.mode = Mode::Rx;
+                    self.rx_continuous = false;
+                    self.deadline = (timeout != 0).then(|| end + steps(timeout));
+                } else {
+                    self.mode = Mode::StandbyRc;
+                }
+            }
+        }
+        if self.mode == Mode::Rx && self.deadline.is_some_and(|d| d <= now) {
+            self.raise(IrqMaskBit::Timeout as u16);
+            self.fall_back();
+        }
+    }
+
+    fn fall_back(&mut self) {
+        self.mode = self.config.fallback;
+        self.deadline = None;
+    }
+
+    /// Flag IRQ sources, as far as the mask enables them.
+    fn raise(&mut self, bits: u16) {
+        self.irq |= bits & self.config.irq_mask;
+    }
+
+    /// Stop any running RX, TX or CAD.
+    fn abort(&mut self) {
+        self.deadline = None;
+        self.tx_end = None;
+        self.tx_frame.clear();
+        self.cad_window = None;
+    }
+
+    fn wake(&mut self, now: Instant) {
+        self.mode = Mode::StandbyRc;
+        if self.warm_sleep {
+            self.busy_until = now + WARM_WAKE_BUSY;
+        } else {
+            self.config = Config::default();
+            self.busy_until = now + COLD_WAKE_BUSY;
+        }
+    }
+
+    /// Whether the chip takes an SPI transaction now. NSS wakes a sleeping chip, but the
+    /// transaction that did so is lost; so is one sent while BUSY is high.
+    fn accepts(&mut self, now: Instant) -> bool {
+        self.catch_up(now);
+        if self.in_reset {
+            return false;
+        }
+        if self.mode == Mode::Sleep {
+            self.wake(now);
+            return false;
+        }
+        if now < self.busy_until {
+            self.busy_violations += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Lock the PLL for FS, RX, TX or CAD. An out-of-band carrier fails to lock.
+    fn lock_pll(&mut self, now: Instant) -> bool {
+        if !RF_RANGE_HZ.contains(&self.config.frequency_hz()) {
+            self.device_errors |= PLL_LOCK_ERROR;
+            self.command_status = STATUS_EXEC_FAILURE;
+            return false;
+        }
+        self.abort();
+        self.busy_until = now + PLL_LOCK_BUSY;
+        true
+    }
+
+    fn channel_rssi_dbm(&self, now: Instant) -> f64 {
+        match self.occupied {
+            Some((rssi, until, on))
+                if now < until && on.is_none_or(|f| rf_freq_register(f) == self.config.rf_freq) =>
+            {
+                rssi.max(self.noise_floor_dbm)
+            }
+            _ => self.noise_floor_dbm,
+        }
+    }
+
+    fn command(&mut self, opcode: u8, data: &[u8], now: Instant) {
+        self.busy_until = now + COMMAND_BUSY;
+        let arg = |n: usize| data.get(..n);
+        match opcode {
+            // SetStandby
+            0x80 => {
+                self.abort();
+                self.mode = if data.first() == Some(&0x01) {
+                    Mode::StandbyXosc
+                } else {
+                    Mode::StandbyRc
+                };
+            }
+            // SetSleep: only from standby; the buffer does not survive.
+            0x84 => {
+                if !self.mode.is_standby() {
+                    self.command_status = STATUS_EXEC_FAILURE;
+                    return;
+                }
+                self.warm_sleep = data.first().is_some_and(|b| b & 0x04 != 0);
+                self.buffer = [0; 256];
+                self.irq = 0;
+                self.mode = Mode::Sleep;
+            }
+            // SetFs
+            0xC1 => {
+                if self.lock_pll(now) {
+                    self.mode = Mode::Fs;
+                }
+            }
+            // SetTx: send payload-length bytes from the TX base address.
+            0x83 => {
+                let Some(t) = arg(3) else {
+                    return self.fail(STATUS_PROCESSING_ERROR);
+                };
+                if !self.lock_pll(now) {
+                    return;
+                }
+                let timeout = u32::from_be_bytes([0, t[0], t[1], t[2]]);
+                let (_, len, _) = self.config.framing();
+                self.tx_frame = (0..len)
+                    .map(|i| self.buffer[usize::from(self.config.tx_base.wrapping_add(i))])
+                    .collect();
+                let start = now + PLL_LOCK_BUSY;
+                self.tx_end = Some(start + self.config.time_on_air(usize::from(len)));
+                self.deadline = (timeout != 0).then(|| start + steps(timeout));
+                self.mode = Mode::Tx;
+            }
+            // SetRx: 0 = single without timeout, 0xFFFFFF = continuous.
+            0x82 => {
+                let Some(t) = arg(3) else {
+                    return self.fail(STATUS_PROCESSING_ERROR);
+                };
+                if !self.lock_pll(now) {
+                    return;
+                }
+                let timeout = u32::from_be_bytes([0, t[0], t[1], t[2]]);
+                self.rx_continuous = timeout == RX_CONTINUOUS;
+                self.deadline = (timeout != 0 && !self.rx_continuous)
+                    .then(|| now + PLL_LOCK_BUSY + steps(timeout));
+                self.mode = Mode::Rx;
+            }
+            // SetRxDutyCycle: modelled as continuous reception.
+            0x94 => {
+                if self.lock_pll(now) {
+                    self.rx_continuous = true;
+                    self.mode = Mode::Rx;
+                }
+            }
+            // SetCad: LoRa only.
+            0xC5 => {
+                if self.config.packet_type != PacketType::LoRa {
+                    return self.fail(STATUS_EXEC_FAILURE);
+                }
+                if !self.lock_pll(now) {
+                    return;
+                }
+                let symbols = 1u32 << self.config.cad[0].min(4);
+                let start = now + PLL_LOCK_BUSY;
+                let duration =
+                    Duration::from_secs_f64(f64::from(symbols) * self.config.lora_symbol_time());
+                self.cad_window = Some((start, start + duration));
+                self.mode = Mode::Cad;
+            }
+            // SetTxContinuousWave / SetTxInfinitePreamble: TX until standby.
+            0xD1 | 0xD2 => {
+                if self.lock_pll(now) {
+                    self.mode = Mode::Tx;
+                }
+            }
+            // SetPacketType: standby only.
+            0x8A => match (self.mode.is_standby(), data.first()) {
+                (false, _) => self.fail(STATUS_EXEC_FAILURE),
+                (true, Some(0x00)) => self.config.packet_type = PacketType::Gfsk,
+                (true, Some(0x01)) => self.config.packet_type = PacketType::LoRa,
+                _ => self.fail(STATUS_PROCESSING_ERROR),
+            },
+            // SetModulationParams / SetPacketParams: length follows the packet type.
+            0x8B | 0x8C => {
+                let len = match (opcode, self.config.packet_type) {
+                    (0x8B, PacketType::Gfsk) => 8,
+                    (0x8B, PacketType::LoRa) => 4,
+                    (_, PacketType::Gfsk) => 9,
+                    (_, PacketType::LoRa) => 6,
+                };
+                match arg(len) {
+                    Some(params) if opcode == 0x8B => self.config.modulation = params.to_vec(),
+                    Some(params) => self.config.packet = params.to_vec(),
+                    None => self.fail(STATUS_PROCESSING_ERROR),
+                }
+            }
+            // SetRfFrequency
+            0x86 => match arg(4) {
+                Some(f) => self.config.rf_freq = u32::from_be_bytes([f[0], f[1], f[2], f[3]]),
+                None => self.fail(STATUS_PROCESSING_ERROR),
+            },
+            // SetBufferBaseAddress
+            0x8F => match arg(2) {
+                Some(b) => (self.config.tx_base, self.config.rx_base) = (b[0], b[1]),
+                None => self.fail(STATUS_PROCESSING_ERROR),
+            },
+            // SetDioIrqParams
+            0x08 => match arg(8) {
+                Some(m) => {
+                    self.config.irq_mask = u16::from_be_bytes([m[0], m[1]]);
+                    for (i, mask) in self.config.dio_masks.iter_mut().enumerate() {
+                        *mask = u16::from_be_bytes([m[2 + 2 * i], m[3 + 2 * i]]);
+                    }
+                }
+                None => self.fail(STATUS_PROCESSING_ERROR),
+            },
+            // ClearIrqStatus
+            0x02 => match arg(2) {
+                Some(m) => self.irq &= !u16::from_be_bytes([m[0], m[1]]),
+                None => self.fail(STATUS_PROCESSING_ERROR),
+            },
+            // WriteBuffer: offset, then data (wrapping at 256).
+            0x0E => match data.split_first() {
+                Some((&offset, bytes)) => {
+                    for (i, &b) in bytes.iter().enumerate() {
+                        self.buffer[usize::from(offset.wrapping_add(i as u8))] = b;
+                    }
+                }
+                None => self.fail(STATUS_PROCESSING_ERROR),
+            },
+            // ReadBuffer, command phase: latch the offset for the read that follows.
+            0x1E => self.read_offset = data.first().copied().unwrap_or(0),
+            // ClearDeviceErrors
+            0x07 => self.device_errors = 0,
+            // ResetStats
+            0x00 => self.stats = [0; 3],
+            // SetCadParams: symbol count code 0..=4.
+            0x88 => match arg(7) {
+                Some(p) if p[0] <= 4 => self.config.cad.copy_from_slice(p),
+                _ => self.fail(STATUS_PROCESSING_ERROR),
+            },
+            // SetRxTxFallbackMode
+            0x93 => match data.first() {
+                Some(0x40) => self.config.fallback = Mode::Fs,
+                Some(0x30) => self.config.fallback = Mode::StandbyXosc,
+                Some(0x20) => self.config.fallback = Mode::StandbyRc,
+                _ => self.fail(STATUS_PROCESSING_ERROR),
+            },
+            // SetDio2AsRfSwitchCtrl
+            0x9D => self.config.dio2_rf_switch = data.first() == Some(&0x01),
+            // SetDio3AsTcxoCtrl
+            0x97 => self.config.dio3_tcxo = true,
+            // Accepted without a modelled effect: SetPaConfig, SetTxParams,
+            // SetRegulatorMode, Calibrate, CalibrateImage, StopTimerOnPreamble,
+            // SetLoRaSymbNumTimeout.
+            0x95 | 0x8E | 0x96 | 0x89 | 0x98 | 0x9F | 0xA0 => {}
+            _ => self.fail(STATUS_PROCESSING_ERROR),
+        }
+    }
+
+    fn fail(&mut self, status: u8) {
+        self.command_status = status;
+    }
+
+    fn read(&mut self, opcode: u8, buf: &mut [u8], now: Instant) {
+        let mut reply = |bytes: &[u8]| {
+            let n = buf.len().min(bytes.len());
+            buf[..n].copy_from_slice(&bytes[..n]);
+        };
+        match opcode {
+            // GetStatus: chip mode [6:4], command status [3:1]
+            0xC0 => {
+                let status = match std::mem::take(&mut self.command_status) {
+                    0 if self.irq & IrqMaskBit::RxDone as u16 != 0 => STATUS_DATA_AVAILABLE,
+                    status => status,
+                };
+                reply(&[(self.mode.bits() << 4) | (status << 1)]);
+            }
+            // GetIrqStatus
+            0x12 => reply(&self.irq.to_be_bytes()),
+            // GetRxBufferStatus: [PayloadLengthRx, RxStartBufferPointer]
+            0x13 => reply(&self.rx_status),
+            // GetPacketStatus
+            0x14 => reply(&self.packet_status),
+            // GetRssiInst: measured while receiving, held otherwise.
+            0x15 => {
+                if matches!(self.mode, Mode::Rx | Mode::Cad) {
+                    self.rssi_inst = rssi_byte(self.channel_rssi_dbm(now));
+                }
+                reply(&[self.rssi_inst]);
+            }
+            // GetDeviceErrors
+            0x17 => reply(&self.device_errors.to_be_bytes()),
+            // GetStats
+            0x10 => {
+                let [a, b, c] = self.stats.map(u16::to_be_bytes);
+                reply(&[a[0], a[1], b[0], b[1], c[0], c[1]]);
+            }
+            // GetPacketType
+            0x11 => reply(&[u8::from(self.config.packet_type == PacketType::LoRa)]),
+            // ReadBuffer, data phase
+            0x1E => {
+                for b in buf.iter_mut() {
+                    *b = self.buffer[usize::from(self.read_offset)];
+                    self.read_offset = self.read_offset.wrapping_add(1);
+                }
+            }
+            _ => self.fail(STATUS_PROCESSING_ERROR),
+        }
+    }
+
+    /// Receive `packet` if the chip is listening for it.
+    fn receive(&mut self, packet: InjectedPacket) -> bool {
+        if self.mode != Mode::Rx || self.config.packet_type != packet.packet_type {
+            return false;
+        }
+        if packet
+            .frequency_hz
+            .is_some_and(|f| rf_freq_register(f) != self.config.rf_freq)
+        {
+            return false;
+        }
+        let (variable, max_len, crc_on) = self.config.framing();
+        let mut payload = packet.payload;
+        payload.truncate(255);
+        if variable && payload.len() > usize::from(max_len) {
+            // The length byte exceeds the maximum: the chip drops the packet.
+            self.stats[2] = self.stats[2].saturating_add(1);
+        } else {
+            if !variable {
+                payload.resize(usize::from(max_len), 0);
+            }
+            let base = self.config.rx_base;
+            for (i, &b) in payload.iter().enumerate() {
+                self.buffer[usize::from(base.wrapping_add(i as u8))] = b;
+            }
+            self.rx_status = [payload.len() as u8, base];
+
+            let crc_error = crc_on && !packet.crc_ok;
+            self.stats[0] = self.stats[0].saturating_add(1);
+            if crc_error {
+                self.stats[1] = self.stats[1].saturating_add(1);
+            }
+            let rssi = rssi_byte(packet.rssi_dbm);
+            match packet.packet_type {
+                PacketType::Gfsk => {
+                    let rx_status = 0x02 | if crc_error { 0x10 } else { 0x00 };
+                    self.packet_status = [rx_status, rssi, rssi];
+                    self.raise(
+                        IrqMaskBit::PreambleDetected as u16 | IrqMaskBit::SyncwordValid as u16,
+                    );
+                }
+                PacketType::LoRa => {
+                    let snr = (packet.snr_db * 4.0).round().clamp(-128.0, 127.0) as i8;
+                    let signal = rssi_byte(packet.rssi_dbm + packet.snr_db.min(0.0));
+                    self.packet_status = [rssi, snr as u8, signal];
+                    let regs = freq_error_registers(
+                        packet.freq_error_hz,
+                        self.config.lora_bandwidth_hz() / 1000.0,
+                    );
+                    for (i, b) in regs.into_iter().enumerate() {
+                        self.config.registers.insert(0x076B + i as u16, b);
+                    }
+                    self.raise(IrqMaskBit::PreambleDetected as u16);
+                    if variable {
+                        self.raise(IrqMaskBit::HeaderValid as u16);
+                    }
+                }
+            }
+            self.raise(IrqMaskBit::RxDone as u16);
+            if crc_error {
+                self.raise(IrqMaskBit::CrcErr as u16);
+            }
+        }
+        if !self.rx_continuous {
+            self.fall_back();
+        }
+        true
+    }
+
+    fn dio(&self, line: usize) -> bool {
+        match line {
+            2 if self.config.dio2_rf_switch => self.mode == Mode::Tx,
+            3 if self.config.dio3_tcxo => self.mode != Mode::Sleep && !self.in_reset,
+            _ => self.irq & self.config.dio_masks[line - 1] != 0,
+        }
+    }
+}
+
+/// Timer steps of 15.625 µs.
+fn steps(count: u32) -> Duration {
+    Duration::from_nanos(u64::from(count) * 15_625)
+}
+
+/// RSSI in the chip's `-dBm · 2` byte format.
+fn rssi_byte(dbm: f64) -> u8 {
+    (-dbm * 2.0).round().clamp(0.0, 255.0) as u8
+}
+
+fn rf_freq_register(frequency_hz: u32) -> u32 {
+    (u64::from(frequency_hz) * (1 << 25) / u64::from(XTAL_HZ)) as u32
+}
+
+/// The signed 20-bit LoRa frequency error registers for an offset in Hz.
+fn freq_error_registers(freq_error_hz: i32, bw_khz: f64) -> [u8; 3] {
+    let raw = (f64::from(freq_error_hz) * (1600.0 / bw_khz) / 1.55).round() as i32;
+    let raw = raw.clamp(-(1 << 19), (1 << 19) - 1) as u32 & 0x000F_FFFF;
+    [(raw >> 16) as u8, (raw >> 8) as u8, raw as u8]
+}
+
+/// A behavioural SX126x implementing [`Hal`]; see the [module docs](self).
+///
+/// Clones share one chip, so a test keeps a clone as a probe while the driver owns
+/// another: it injects packets and channel conditions through the probe and reads back
+/// what the chip did.
+#[derive(Clone)]
+pub struct Sx126xModel {
+    chip: Arc<Mutex<Chip>>,
+}
+
+impl Default for Sx126xModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Sx126xModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chip = self.lock();
+        f.debug_struct("Sx126xModel")
+            .field("mode", &chip.mode)
+            .field("packet_type", &chip.config.packet_type)
+            .field("irq", &chip.irq)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Sx126xModel {
+    /// A chip just out of power-on reset, in STDBY_RC with default configuration.
+    pub fn new() -> Self {
+        Self {
+            chip: Arc::new(Mutex::new(Chip::new(Instant::now()))),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Chip> {
+        self.chip.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Bring the chip up to now, then run `f` on it.
+    fn with<T>(&self, f: impl FnOnce(&mut Chip, Instant) -> T) -> T {
+        let now = Instant::now();
+        let mut chip = self.lock();
+        chip.catch_up(now);
+        f(&mut chip, now)
+    }
+
+    /// Wait for BUSY to fall after a write, as a host HAL does. A sleeping chip keeps
+    /// BUSY high, so there is nothing to wait for after SetSleep.
+    fn wait_busy(&self) -> Result<(), HalError> {
+        let deadline = Instant::now() + BUSY_TIMEOUT;
+        loop {
+            let (sleeping, busy_until) = {
+                let chip = self.lock();
+                (chip.mode == Mode::Sleep, chip.busy_until)
+            };
+            let now = Instant::now();
+            if sleeping || now >= busy_until {
+                return Ok(());
+            }
+            if now >= deadline {
+                return Err(HalError::Timeout);
+            }
+            std::thread::sleep(busy_until - now);
+        }
+    }
+
+    /// Put `packet` on the air. The chip receives it if it is in RX with the packet's
+    /// modem (and carrier, if the packet names one); returns whether it did. The packet
+    /// arrives complete at the moment of the call.
+    pub fn inject(&self, packet: InjectedPacket) -> bool {
+        self.with(|chip, _| chip.receive(packet))
+    }
+
+    /// Set the RSSI of a quiet channel, in dBm (default -120).
+    pub fn set_noise_floor(&self, rssi_dbm: f64) {
+        self.lock().noise_floor_dbm = rssi_dbm;
+    }
+
+    /// Occupy the channel with a signal of `rssi_dbm` for `duration` from now, as seen
+    /// by GetRssiInst.
+    pub fn occupy_channel(&self, rssi_dbm: f64, duration: Duration) {
+        self.lock().occupied = Some((rssi_dbm, Instant::now() + duration, None));
+    }
+
+    /// Like [`occupy_channel`](Self::occupy_channel), but only the carrier
+    /// `frequency_hz` is occupied; tuned elsewhere the chip hears the noise floor.
+    pub fn occupy_frequency(&self, frequency_hz: u32, rssi_dbm: f64, duration: Duration) {
+        self.lock().occupied = Some((rssi_dbm, Instant::now() + duration, Some(frequency_hz)));
+    }
+
+    /// Send LoRa preambles for `duration` from now, for CAD to detect.
+    pub fn lora_activity(&self, duration: Duration) {
+        let now = Instant::now();
+        self.lock().lora_activity = Some((now, now + duration));
+    }
+
+    /// Raise device error bits (as in GetDeviceErrors) until ClearDeviceErrors.
+    pub fn inject_device_errors(&self, bits: u16) {
+        self.lock().device_errors |= bits;
+    }
+
+    /// Every frame the chip finished sending, in order.
+    pub fn transmitted(&self) -> Vec<Vec<u8>> {
+        self.with(|chip, _| chip.transmitted.clone())
+    }
+
+    /// The chip mode (CAD reads as RX, as in GetStatus).
+    pub fn state(&self) -> RadioState {
+        self.with(|chip, _| match chip.mode {
+            Mode::Sleep => RadioState::Sleep,
+            Mode::StandbyRc => RadioState::StandbyRc,
+            Mode::StandbyXosc => RadioState::StandbyXosc,
+            Mode::Fs => RadioState::FreqSynth,
+            Mode::Rx | Mode::Cad => RadioState::Rx,
+            Mode::Tx => RadioState::Tx,
+        })
+    }
+
+    /// Whether BUSY is high.
+    pub fn busy(&self) -> bool {
+        self.with(|chip, now| chip.busy(now))
+    }
+
+    /// The selected packet type.
+    pub fn packet_type(&self) -> PacketType {
+        self.lock().config.packet_type
+    }
+
+    /// The RF carrier, in Hz.
+    pub fn frequency_hz(&self) -> u32 {
+        self.lock().config.frequency_hz()
+    }
+
+    /// The raw SetModulationParams bytes in effect.
+    pub fn modulation_params(&self) -> Vec<u8> {
+        self.lock().config.modulation.clone()
+    }
+
+    /// The raw SetPacketParams bytes in effect.
+    pub fn packet_params(&self) -> Vec<u8> {
+        self.lock().config.packet.clone()
+    }
+
+    /// `len` register bytes from `addr`.
+    pub fn registers(&self, addr: u16, len: usize) -> Vec<u8> {
+        let chip = self.lock();
+        (0..len)
+            .map(|i| {
+                let reg = addr.wrapping_add(i as u16);
+                chip.config.registers.get(&reg).copied().unwrap_or(0)
+            })
+            .collect()
+    }
+
+    /// The IRQ mask set by SetDioIrqParams.
+    pub fn irq_mask(&self) -> u16 {
+        self.lock().config.irq_mask
+    }
+
+    /// The pending IRQ status.
+    pub fn irq_status(&self) -> u16 {
+        self.with(|chip, _| chip.irq)
+    }
+
+    /// Time on air of a `len`-byte payload with the current parameters.
+    pub fn time_on_air(&self, len: usize) -> Duration {
+        self.lock().config.time_on_air(len)
+    }
+
+    /// SPI transactions lost because they arrived while BUSY was high.
+    pub fn busy_violations(&self) -> u32 {
+        self.lock().busy_violations
+    }
+}
+
+impl Hal for Sx126xModel {
+    fn write_command(&mut self, opcode: u8, data: &[u8]) -> Result<(), HalError> {
+        self.with(|chip, now| {
+            if chip.accepts(now) {
+                chip.command(opcode, data, now);
+            }
+        });
+        self.wait_busy()
+    }
+
+    fn read_command(&mut self, opcode: u8, buf: &mut [u8]) -> Result<(), HalError> {
+        buf.fill(0);
+        self.with(|chip, now| {
+            if chip.accepts(now) {
+                chip.read(opcode, buf, now);
+            }
+        });
+        Ok(())
+    }
+
+    fn write_register(&mut self, addr: u16, data: &[u8]) -> Result<(), HalError> {
+        self.with(|chip, now| {
+            if chip.accepts(now) {
+                chip.busy_until = now + COMMAND_BUSY;
+                for (i, &b) in data.iter().enumerate() {
+                    chip.config.registers.insert(addr.wrapping_add(i as u16), b);
+                }
+            }
+        });
+        self.wait_busy()
+    }
+
+    fn read_register(&mut self, addr: u16, buf: &mut [u8]) -> Result<(), HalError> {
+        buf.fill(0);
+        self.with(|chip, now| {
+            if chip.accepts(now) {
+                for (i, b) in buf.iter_mut().enumerate() {
+                    let reg = addr.wrapping_add(i as u16);
+                    *b = chip.config.registers.get(&reg).copied().unwrap_or(0);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn gpio_read(&mut self, pin: u8) -> Result<bool, HalError> {
+        self.with(|chip, now| match pin {
+            PIN_DIO1 | PIN_DIO2 | PIN_DIO3 => Ok(chip.dio(usize::from(pin))),
+            PIN_BUSY => Ok(chip.busy(now)),
+            _ => Err(HalError::Gpio),
+        })
+    }
+
+    fn gpio_write(&mut self, pin: u8, value: bool) -> Result<(), HalError> {
+        if pin != PIN_RESET {
+            return Err(HalError::Gpio);
+        }
+        self.with(|chip, now| {
+            if !value {
+                chip.in_reset = true;
+            } else if chip.in_reset {
+                chip.power_on(now);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::radio::driver::{SleepConfig, StandbyMode, Sx126xDriver};
+    use crate::wmbus::radio::modulation::{CrcType, HeaderType, PacketParams};
+
+    fn wmbus_driver() -> (Sx126xDriver<Sx126xModel>, Sx126xModel) {
+        let chip = Sx126xModel::new();
+        let mut driver = Sx126xDriver::new(chip.clone(), XTAL_HZ);
+        driver.configure_for_wmbus(868_950_000, 100_000).unwrap();
+        (driver, chip)
+    }
+
+    #[test]
+    fn sleep_keeps_busy_high_and_cold_start_forgets_the_configuration() {
+        let (mut driver, mut chip) = wmbus_driver();
+        driver.set_standby(StandbyMode::RC).unwrap();
+        driver.set_sleep(SleepConfig::default()).unwrap();
+        assert!(chip.gpio_read(PIN_BUSY).unwrap());
+
+        // Warm start: the wake-up transaction is lost, the configuration is not.
+        driver.set_standby(StandbyMode::RC).unwrap();
+        assert!(!chip.busy());
+        assert_eq!(chip.registers(0x06C0, 4), vec![0xB4, 0xB6, 0x5A, 0x5A]);
+
+        driver
+            .set_sleep(SleepConfig {
+                warm_start: false,
+                rtc_wake: false,
+            })
+            .unwrap();
+        driver.set_standby(StandbyMode::RC).unwrap();
+        assert_eq!(chip.registers(0x06C0, 2), vec![0x97, 0x23]);
+        assert!(chip.packet_params().is_empty());
+        assert_eq!(chip.busy_violations(), 0);
+
+        // Reset also lands in STDBY_RC with defaults once BUSY falls.
+        chip.gpio_write(PIN_RESET, false).unwrap();
+        assert!(chip.busy());
+        chip.gpio_write(PIN_RESET, true).unwrap();
+        std::thread::sleep(COLD_WAKE_BUSY);
+        assert_eq!(chip.state(), RadioState::StandbyRc);
+        assert!(!chip.busy());
+    }
+
+    #[test]
+    fn only_masked_irqs_are_flagged_and_drive_their_dio() {
+        let (mut driver, mut chip) = wmbus_driver();
+        driver.set_rx(0).unwrap();
+        assert!(chip.inject(InjectedPacket::gfsk(&[1, 2, 3], -70.0)));
+
+        // RxDone is enabled and routed to DIO1; SyncwordValid is not enabled.
+        assert_eq!(chip.irq_status(), IrqMaskBit::RxDone as u16);
+        assert!(chip.gpio_read(PIN_DIO1).unwrap());
+        assert!(!chip.gpio_read(PIN_DIO2).unwrap());
+        // Single RX falls back to standby after the packet.
+        assert_eq!(chip.state(), RadioState::StandbyRc);
+
+        assert_eq!(driver.process_irqs().unwrap(), Some(vec![1, 2, 3]));
+        assert!(!chip.gpio_read(PIN_DIO1).unwrap());
+    }
+
+    #[test]
+    fn crc_and_length_errors_are_counted() {
+        let (mut driver, chip) = wmbus_driver();
+        driver.set_rx_continuous().unwrap();
+        assert!(chip.inject(InjectedPacket::gfsk(&[9; 10], -80.0).crc_error()));
+        let irq = driver.get_irq_status().unwrap();
+        assert!(irq.rx_done() && irq.crc_err());
+        driver.clear_irq_status(0xFFFF).unwrap();
+
+        // Longer than the maximum payload: dropped by the chip.
+        driver
+            .set_packet_params(PacketParams::Gfsk {
+                preamble_len: 48,
+                header_type: HeaderType::Variable,
+                payload_len: 16,
+                crc_on: true,
+                crc_type: CrcType::Byte2,
+                sync_word_len: 4,
+            })
+            .unwrap();
+        assert!(chip.inject(InjectedPacket::gfsk(&[9; 20], -80.0)));
+        assert_eq!(chip.irq_status(), 0);
+        // A packet on another carrier is not heard at all.
+        assert!(!chip.inject(InjectedPacket::gfsk(&[9; 4], -80.0).on_frequency(868_300_000)));
+
+        let stats = driver.get_stats().unwrap();
+        assert_eq!(
+            (
+                stats.packets_received,
+                stats.packets_crc_error,
+                stats.packets_length_error
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(chip.state(), RadioState::Rx);
+    }
+
+    #[test]
+    fn crc_type_byte_sets_the_crc_length_on_air() {
+        let (_driver, mut chip) = wmbus_driver();
+        // The driver asks for the inverted CRC16 wM-Bus uses.
+        assert_eq!(chip.packet_params()[7], 0x06);
+
+        let toa = |chip: &mut Sx126xModel, crc_type: u8| {
+            chip.write_command(
+                0x8C,
+                &[0x00, 0x20, 0x05, 0x10, 0x00, 0x01, 0xFF, crc_type, 0x00],
+            )
+            .unwrap();
+            chip.time_on_air(10)
+        };
+        let off = toa(&mut chip, 0x01);
+        // Datasheet CRCType: 0x00/0x04 are 1-byte (plain/inverted), 0x02/0x06 are 2-byte.
+        let bit = Duration::from_micros(10);
+        for (crc_type, bits) in [(0x00, 8), (0x04, 8), (0x02, 16), (0x06, 16)] {
+            let extra = toa(&mut chip, crc_type) - off;
+            assert!(
+                extra.abs_diff(bit * bits) < Duration::from_nanos(100),
+                "CRCType {crc_type:#04x} must add {bits} bits, added {extra:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn wmbus_handle_receives_through_the_driver() {
+        use crate::wmbus::bidirectional::MeterAddress;
+        use crate::wmbus::handle::WMBusHandle;
+        use crate::wmbus::telegram::TelegramBuilder;
+
+        let chip = Sx126xModel::new();
+        let driver = Sx126xDriver::new(chip.clone(), XTAL_HZ);
+        let mut handle = WMBusHandle::with_driver(driver, None).await.unwrap();
+        handle.start_receiver().await.unwrap();
+
+        let telegram = TelegramBuilder::new(MeterAddress {
+            manufacturer_id: 0x2C2D,
+            address: 0x1234_5678,
+            version: 1,
+            device_type: 7,
+        })
+        .build(&[])
+        .unwrap();
+        // The receiver task arms RX once it runs.
+        while chip.state() != RadioState::Rx {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert!(chip.inject(InjectedPacket::gfsk(&telegram.frame_bytes(), -75.0)));
+
+        let (frame, _) = handle.recv_frame(Some(1_000)).await.unwrap();
+        assert_eq!(frame.device_address, 0x1234_5678);
+        handle.stop_receiver().await;
+    }
+}
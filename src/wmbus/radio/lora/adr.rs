@@ -6,6 +6,8 @@
 //!
 //! Based on LoRaWAN ADR algorithms with enhancements from field experience.
 
+use super::mac::MacCommand;
+use super::region::Region;
 use crate::wmbus::radio::modulation::{CodingRate, LoRaBandwidth, LoRaModParams, SpreadingFactor};
 use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
@@ -44,6 +46,46 @@ pub struct AdrConfig {
 
     /// Hysteresis to prevent oscillation
     pub hysteresis_db: f32,
+
+    /// How decisions are encoded as LinkADRReq downlinks
+    #[serde(default)]
+    pub link_adr: LinkAdrParams,
+}
+
+/// Channel and power settings used when turning a decision into a LinkADRReq
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkAdrParams {
+    /// Region whose data rate and TXPower tables the command uses
+    #[serde(default)]
+    pub region: Region,
+
+    /// Enabled channels within the block selected by `ch_mask_cntl`
+    pub ch_mask: u16,
+
+    /// Channel mask control (0 = channels 0..15)
+    pub ch_mask_cntl: u8,
+
+    /// Transmissions per uplink
+    pub nb_trans: u8,
+}
+
+impl Default for LinkAdrParams {
+    fn default() -> Self {
+        Self::for_region(Region::EU868)
+    }
+}
+
+impl LinkAdrParams {
+    /// Parameters enabling all default channels of `region`
+    pub fn for_region(region: Region) -> Self {
+        let (ch_mask, ch_mask_cntl) = region.default_channel_mask();
+        Self {
+            region,
+            ch_mask,
+            ch_mask_cntl,
+            nb_trans: 1,
+        }
+    }
 }
 
 /// RSSI thresholds for spreading factor selection
@@ -114,6 +156,34 @@ impl Default for AdrConfig {
                 sf12_min_snr: -7.5,
             },
             hysteresis_db: 3.0,
+            link_adr: LinkAdrParams::default(),
+        }
+    }
+}
+
+impl AdrConfig {
+    /// Default configuration limited to the 125 kHz uplink data rates and
+    /// TXPower range of `region`, with the region's MaxEIRP capped at the
+    /// SX126x's 22 dBm
+    ///
+    /// Where the region has a dwell-time rule, only data rates usable within
+    /// it are allowed.
+    pub fn for_region(region: Region) -> Self {
+        let dwell_time = region.dwell_time_limit().is_some();
+        let spreading_factors = region
+            .uplink_data_rates(dwell_time)
+            .filter_map(|dr| region.data_rate(dr))
+            .filter(|params| params.bandwidth == LoRaBandwidth::BW125)
+            .map(|params| params.spreading_factor);
+
+        let defaults = Self::default();
+        Self {
+            min_sf: spreading_factors.clone().min().unwrap_or(defaults.min_sf),
+            max_sf: spreading_factors.max().unwrap_or(defaults.max_sf),
+            min_tx_power: region.min_tx_power_dbm(),
+            max_tx_power: region.max_eirp_dbm().min(22),
+            link_adr: LinkAdrParams::for_region(region),
+            ..defaults
         }
     }
 }
@@ -167,6 +237,35 @@ pub enum AdrReason {
 
     /// Limited by configuration
     ConfigLimit,
+
+    /// Set explicitly through `apply_network_adr`
+    Network,
+}
+
+impl AdrDecision {
+    /// Region data rate index for the decided SF on a 125 kHz channel
+    ///
+    /// Returns `None` if the region has no such uplink data rate, e.g. SF5/SF6
+    /// anywhere or SF11/SF12 in US915.
+    pub fn data_rate(&self, region: Region) -> Option<u8> {
+        region.uplink_data_rate(self.spreading_factor, LoRaBandwidth::BW125)
+    }
+
+    /// TXPower index closest to, but not above, the decided power
+    pub fn tx_power_index(&self, region: Region) -> u8 {
+        region.tx_power_index(self.tx_power)
+    }
+
+    /// Encode this decision as a LinkADRReq MAC command
+    pub fn to_link_adr_req(&self, params: &LinkAdrParams) -> Option<MacCommand> {
+        Some(MacCommand::LinkAdrReq {
+            data_rate: self.data_rate(params.region)?,
+            tx_power: self.tx_power_index(params.region),
+            ch_mask: params.ch_mask,
+            ch_mask_cntl: params.ch_mask_cntl,
+            nb_trans: params.nb_trans,
+        })
+    }
 }
 
 /// Adaptive Data Rate controller
@@ -191,6 +290,12 @@ pub struct AdrController {
 
     /// Last decision made
     last_decision: Option<AdrDecision>,
+
+    /// Decision not yet sent to the device, with the settings it replaces
+    pending_link_adr: Option<(AdrDecision, (SpreadingFactor, i8))>,
+
+    /// Settings to fall back to if the device rejects the sent LinkADRReq
+    in_flight_link_adr: Option<(SpreadingFactor, i8)>,
 }
 
 impl Default for AdrController {
@@ -205,6 +310,11 @@ impl AdrController {
         Self::with_config(AdrConfig::default())
     }
 
+    /// Create a new ADR controller limited to a region's data rates and power
+    pub fn for_region(region: Region) -> Self {
+        Self::with_config(AdrConfig::for_region(region))
+    }
+
     /// Create a new ADR controller with custom configuration
     pub fn with_config(config: AdrConfig) -> Self {
         Self {
@@ -214,6 +324,8 @@ impl AdrController {
             last_evaluation: Instant::now(),
             consecutive_losses: 0,
             last_decision: None,
+            pending_link_adr: None,
+            in_flight_link_adr: None,
             config,
         }
     }
@@ -300,8 +412,10 @@ impl AdrController {
         // Determine optimal SF based on signal quality
         let optimal_sf = self.determine_optimal_sf(avg_rssi, avg_snr);
 
-        // Apply hysteresis to prevent oscillation
-        let target_sf = self.apply_hysteresis(optimal_sf, avg_rssi);
+        // Apply hysteresis to prevent oscillation, then keep within limits
+        let target_sf = self
+            .apply_hysteresis(optimal_sf, avg_rssi)
+            .clamp(self.config.min_sf, self.config.max_sf);
 
         // Determine power adjustment
         let target_power = self.determine_tx_power(target_sf, avg_rssi);
@@ -322,8 +436,17 @@ impl AdrController {
                 self.current_sf as u8, target_sf as u8, target_power, avg_rssi, avg_snr
             );
 
+            let previous = (self.current_sf, self.current_tx_power);
             self.current_sf = target_sf;
             self.current_tx_power = target_power;
+            self.queue_link_adr(
+                AdrDecision {
+                    spreading_factor: target_sf,
+                    tx_power: target_power,
+                    reason,
+                },
+                previous,
+            );
         }
 
         let decision = AdrDecision {
@@ -347,7 +470,8 @@ impl AdrController {
             SpreadingFactor::SF11 => SpreadingFactor::SF12,
             SpreadingFactor::SF12 => SpreadingFactor::SF12, // Already at max
             _ => self.current_sf,
-        };
+        }
+        .min(self.config.max_sf);
 
         // Increase power if not at max
         let new_power = (self.current_tx_power + 2).min(self.config.max_tx_power);
@@ -357,15 +481,81 @@ impl AdrController {
             self.current_sf as u8, new_sf as u8, self.current_tx_power, new_power
         );
 
+        let previous = (self.current_sf, self.current_tx_power);
         self.current_sf = new_sf;
         self.current_tx_power = new_power;
         self.consecutive_losses = 0; // Reset counter
 
-        AdrDecision {
+        let decision = AdrDecision {
             spreading_factor: new_sf,
             tx_power: new_power,
             reason: AdrReason::PacketLoss,
+        };
+        self.queue_link_adr(decision, previous);
+        decision
+    }
+
+    /// Remember a decision for the next downlink, keeping the settings the
+    /// device actually uses if an earlier decision was never sent
+    fn queue_link_adr(&mut self, decision: AdrDecision, previous: (SpreadingFactor, i8)) {
+        let previous = self
+            .pending_link_adr
+            .map(|(_, prev)| prev)
+            .unwrap_or(previous);
+        self.pending_link_adr = Some((decision, previous));
+    }
+
+    /// LinkADRReq for the latest unsent decision, to piggyback on the next downlink
+    pub fn take_link_adr_req(&mut self) -> Option<MacCommand> {
+        let (decision, previous) = self.pending_link_adr.take()?;
+        match decision.to_link_adr_req(&self.config.link_adr) {
+            Some(command) => {
+                self.in_flight_link_adr = Some(previous);
+                Some(command)
+            }
+            None => {
+                warn!(
+                    "ADR: SF{} has no LoRaWAN data rate, not sending LinkADRReq",
+                    decision.spreading_factor as u8
+                );
+                None
+            }
+        }
+    }
+
+    /// True if a decision is waiting to be sent
+    pub fn has_pending_link_adr(&self) -> bool {
+        self.pending_link_adr.is_some()
+    }
+
+    /// Handle the device's LinkADRAns
+    ///
+    /// A device rejects a LinkADRReq as a whole, so on any NACK the controller
+    /// returns to the settings the device kept. Returns true if accepted.
+    pub fn handle_link_adr_ans(&mut self, answer: &MacCommand) -> bool {
+        let MacCommand::LinkAdrAns {
+            power_ack,
+            data_rate_ack,
+            channel_mask_ack,
+        } = *answer
+        else {
+            return false;
+        };
+        let previous = self.in_flight_link_adr.take();
+        if power_ack && data_rate_ack && channel_mask_ack {
+            debug!("ADR: LinkADRReq accepted");
+            return true;
         }
+
+        warn!(
+            "ADR: LinkADRReq rejected (power {power_ack}, data rate {data_rate_ack}, channel mask {channel_mask_ack})"
+        );
+        if let Some((sf, power)) = previous {
+            self.current_sf = sf;
+            self.current_tx_power = power;
+            self.metrics_history.clear();
+        }
+        false
     }
 
     /// Calculate average RSSI and SNR from history
@@ -483,16 +673,27 @@ impl AdrController {
     }
 
     /// Apply ADR decision from network (LinkADRReq)
+    ///
+    /// The new settings are queued for the device like any other decision.
     pub fn apply_network_adr(&mut self, sf: SpreadingFactor, tx_power: i8) {
         info!(
             "ADR: Applying network command - SF{}, {} dBm",
             sf as u8, tx_power
         );
 
-        self.current_sf = sf;
+        let previous = (self.current_sf, self.current_tx_power);
+        self.current_sf = sf.clamp(self.config.min_sf, self.config.max_sf);
         self.current_tx_power = tx_power
             .max(self.config.min_tx_power)
             .min(self.config.max_tx_power);
+        self.queue_link_adr(
+            AdrDecision {
+                spreading_factor: self.current_sf,
+                tx_power: self.current_tx_power,
+                reason: AdrReason::Network,
+            },
+            previous,
+        );
 
         // Clear history to start fresh with new parameters
         self.metrics_history.clear();
@@ -591,4 +792,91 @@ mod tests {
         assert_eq!(decision.spreading_factor, SpreadingFactor::SF8);
         assert_eq!(decision.reason, AdrReason::Stable);
     }
+
+    #[test]
+    fn test_decision_becomes_link_adr_req() {
+        let mut adr = AdrController::new();
+        adr.current_sf = SpreadingFactor::SF12;
+        for _ in 0..10 {
+            adr.record_packet(-75, 10.0);
+        }
+        let decision = adr.force_evaluation();
+        assert_eq!(decision.data_rate(Region::EU868), Some(5));
+
+        let req = adr.take_link_adr_req().expect("decision queued");
+        let MacCommand::LinkAdrReq {
+            data_rate,
+            tx_power,
+            ch_mask,
+            nb_trans,
+            ..
+        } = req
+        else {
+            panic!("unexpected {req:?}");
+        };
+        assert_eq!(data_rate, 5);
+        assert_eq!(tx_power, decision.tx_power_index(Region::EU868));
+        assert_eq!(ch_mask, 0x0007);
+        assert_eq!(nb_trans, 1);
+        assert!(adr.take_link_adr_req().is_none());
+    }
+
+    #[test]
+    fn test_network_adr_is_sent_and_reverted_on_nack() {
+        let mut adr = AdrController::new();
+        assert_eq!(adr.get_current_state(), (SpreadingFactor::SF7, 14));
+
+        adr.apply_network_adr(SpreadingFactor::SF10, 8);
+        assert_eq!(
+            adr.take_link_adr_req(),
+            Some(MacCommand::LinkAdrReq {
+                data_rate: 2,
+                tx_power: 4, // 16 - 2*4 = 8 dBm
+                ch_mask: 0x0007,
+                ch_mask_cntl: 0,
+                nb_trans: 1,
+            })
+        );
+
+        let nack = MacCommand::LinkAdrAns {
+            power_ack: true,
+            data_rate_ack: false,
+            channel_mask_ack: true,
+        };
+        assert!(!adr.handle_link_adr_ans(&nack));
+        assert_eq!(adr.get_current_state(), (SpreadingFactor::SF7, 14));
+    }
+
+    #[test]
+    fn test_region_limits_sf_and_power() {
+        let mut adr = AdrController::for_region(Region::US915);
+        assert_eq!(adr.get_current_state(), (SpreadingFactor::SF7, 22));
+
+        // Packet loss escalates only up to SF10, the slowest US915 uplink rate
+        for _ in 0..12 {
+            adr.record_loss();
+        }
+        assert_eq!(adr.get_current_state().0, SpreadingFactor::SF10);
+
+        let Some(MacCommand::LinkAdrReq {
+            data_rate,
+            tx_power,
+            ch_mask,
+            ch_mask_cntl,
+            ..
+        }) = adr.take_link_adr_req()
+        else {
+            panic!("decision queued");
+        };
+        assert_eq!(data_rate, 0);
+        assert_eq!(tx_power, 4); // 30 - 2*4 = 22 dBm
+        assert_eq!((ch_mask, ch_mask_cntl), (0x00FF, 6));
+
+        let au = AdrConfig::for_region(Region::AU915);
+        assert_eq!(
+            (au.min_sf, au.max_sf),
+            (SpreadingFactor::SF7, SpreadingFactor::SF10)
+        );
+        assert_eq!((au.min_tx_power, au.max_tx_power), (2, 22));
+    }
 }
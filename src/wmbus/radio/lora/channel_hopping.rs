@@ -4,6 +4,8 @@
 //! to avoid interference from WiFi and other sources. Inspired by One Channel Hub's
 //! fixed channel approach but extended for multi-channel resilience.
 
+use super::region::{Region, RegionChannel};
+use crate::wmbus::radio::modulation::LoRaBandwidth;
 use log::{debug, info, warn};
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
@@ -190,30 +192,46 @@ pub struct ChannelHopper {
 impl ChannelHopper {
     /// Create a new channel hopper for EU868
     pub fn new_eu868(strategy: HoppingStrategy) -> Self {
-        let channels = EU868_CHANNELS.to_vec();
-        let quality_history = vec![VecDeque::with_capacity(100); channels.len()];
-
-        Self {
-            channels,
-            current_index: 0,
-            strategy,
-            scan_interval: Duration::from_secs(60), // Scan every minute
-            last_scan: Instant::now(),
-            quality_history,
-            blacklist: Vec::new(),
-        }
+        Self::with_channels(EU868_CHANNELS.to_vec(), strategy)
     }
 
     /// Create a new channel hopper for US915
     pub fn new_us915(strategy: HoppingStrategy) -> Self {
-        let channels = US915_CHANNELS.to_vec();
+        Self::with_channels(US915_CHANNELS.to_vec(), strategy)
+    }
+
+    /// Create a channel hopper over the default channels of a region
+    ///
+    /// Fixed channel plans (US915/AU915) hop over their 64 125 kHz uplink
+    /// channels; use [`ChannelHopper::for_sub_band`] for 8-channel gateways.
+    /// Duty-cycle limits come from the region's sub-bands.
+    pub fn for_region(region: Region, strategy: HoppingStrategy) -> Self {
+        Self::with_channels(
+            region_channels(region, &region.default_channels()),
+            strategy,
+        )
+    }
+
+    /// Create a channel hopper over the eight 125 kHz channels of one sub-band
+    /// of a fixed channel plan
+    ///
+    /// Returns `None` if the region has no sub-bands or `sub_band` is not 1-8.
+    pub fn for_sub_band(region: Region, sub_band: u8, strategy: HoppingStrategy) -> Option<Self> {
+        let channels = region.sub_band_channels(sub_band)?;
+        Some(Self::with_channels(
+            region_channels(region, &channels),
+            strategy,
+        ))
+    }
+
+    fn with_channels(channels: Vec<Channel>, strategy: HoppingStrategy) -> Self {
         let quality_history = vec![VecDeque::with_capacity(100); channels.len()];
 
         Self {
             channels,
             current_index: 0,
             strategy,
-            scan_interval: Duration::from_secs(60),
+            scan_interval: Duration::from_secs(60), // Scan every minute
             last_scan: Instant::now(),
             quality_history,
             blacklist: Vec::new(),
@@ -378,6 +396,28 @@ impl ChannelHopper {
     }
 }
 
+/// Hopping channels for a region's channel plan entries
+/// Hopper channels for the 125 kHz channels of `plan`
+///
+/// [`Channel`] carries no bandwidth, so a radio hopping at 125 kHz must not
+/// land on the 500 kHz channels of fixed channel plans.
+fn region_channels(region: Region, plan: &[RegionChannel]) -> Vec<Channel> {
+    plan.iter()
+        .filter(|ch| {
+            region
+                .data_rate(ch.min_dr)
+                .is_some_and(|dr| dr.bandwidth == LoRaBandwidth::BW125)
+        })
+        .map(|ch| Channel {
+            frequency_hz: ch.frequency_hz,
+            name: region.name(),
+            duty_cycle_limit: region.duty_cycle_percent(ch.frequency_hz).unwrap_or(100.0),
+            last_activity: None,
+            quality: 1.0,
+        })
+        .collect()
+}
+
 /// Channel hopping statistics
 #[derive(Debug, Clone)]
 pub struct ChannelStats {
@@ -435,4 +475,29 @@ mod tests {
 
         assert!(hopper.blacklist.contains(&0));
     }
+
+    #[test]
+    fn test_region_channel_plans() {
+        let hopper = ChannelHopper::for_region(Region::AS923, HoppingStrategy::RoundRobin);
+        assert_eq!(hopper.get_stats().total_channels, 2);
+        assert_eq!(hopper.current_channel().frequency_hz, 923_200_000);
+        assert_eq!(hopper.current_channel().duty_cycle_limit, 100.0);
+
+        let eu = ChannelHopper::for_region(Region::EU868, HoppingStrategy::RoundRobin);
+        assert_eq!(eu.current_channel().duty_cycle_limit, 1.0);
+
+        let mut au = ChannelHopper::for_sub_band(Region::AU915, 2, HoppingStrategy::RoundRobin)
+            .expect("AU915 has sub-bands");
+        assert_eq!(au.get_stats().total_channels, 8);
+        let next = au.next_channel();
+        assert_eq!(next.frequency_hz, 917_000_000);
+        assert_eq!(next.name, "AU915");
+
+        // The 500 kHz channels are not hopped onto.
+        let us = ChannelHopper::for_region(Region::US915, HoppingStrategy::RoundRobin);
+        assert_eq!(us.get_stats().total_channels, 64);
+        assert!(!us.channels.iter().any(|ch| ch.frequency_hz == 903_000_000));
+
+        assert!(ChannelHopper::for_sub_band(Region::EU868, 1, HoppingStrategy::Random).is_none());
+    }
 }
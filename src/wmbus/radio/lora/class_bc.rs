@@ -2,12 +2,72 @@
 //!
 //! Implements ping-slot timing for Class B and continuous reception
 //! for Class C devices, enabling server-initiated downlinks.
-
+//!
+//! Class B timing follows LoRaWAN 1.0.x: a beacon goes out every 128 s of GPS
+//! time, and each device listens in `2^ping_nb` ping slots per beacon period
+//! at an offset derived from its DevAddr and the beacon time.
+//!
+//! ```text
+//! Beacon = RFU | Time(4) | CRC(2) | InfoDesc(1) | Info(6) | RFU | CRC(2)
+//! Slot n = BeaconTime + 2.12 s + (pingOffset + n * pingPeriod) * 30 ms
+//! ```
+
+#[cfg(feature = "crypto")]
+use super::lorawan::{DeviceSession, Downlink, SessionKeys};
+use super::region::Region;
+#[cfg(feature = "crypto")]
+use crate::wmbus::crypto_hardware::get_aes_backend;
 use log::{debug, info, warn};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::{Mutex, RwLock};
-use tokio::time::{interval, sleep};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot, Mutex, Notify, RwLock};
+use tokio::time::sleep;
+
+/// Time between two beacons
+pub const BEACON_PERIOD: Duration = Duration::from_secs(128);
+
+/// Time after the beacon start before the first ping slot
+pub const BEACON_RESERVED: Duration = Duration::from_millis(2_120);
+
+/// Length of one ping slot
+pub const PING_SLOT_LEN: Duration = Duration::from_millis(30);
+
+/// Ping slots in a beacon window
+#[cfg(feature = "crypto")]
+const BEACON_WINDOW_SLOTS: u32 = 4096;
+
+/// Unix time of the GPS epoch, 1980-01-06T00:00:00Z
+const GPS_EPOCH_UNIX_SECS: u64 = 315_964_800;
+
+/// Preamble symbols of a beacon
+const BEACON_PREAMBLE_SYMBOLS: f64 = 10.0;
+
+/// Leap seconds GPS time is ahead of UTC (since 2017-01-01)
+pub const GPS_LEAP_SECONDS: u64 = 18;
+
+/// Downlinks held per device before `enqueue` refuses more
+const MAX_QUEUED_DOWNLINKS: usize = 32;
+
+/// Class B/C errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ClassBCError {
+    #[error("Beacon must be {expected} bytes, got {actual}")]
+    BeaconLength { expected: usize, actual: usize },
+    #[error("Beacon CRC mismatch in {part} part")]
+    BeaconCrc { part: &'static str },
+    #[error("No Class B/C registration for 0x{addr:08X}")]
+    UnknownTarget { addr: u32 },
+    #[error("0x{addr:08X} is a Class A device and only receives after uplinks")]
+    ClassA { addr: u32 },
+    #[error("Multicast group 0x{addr:08X} cannot take confirmed downlinks")]
+    ConfirmedMulticast { addr: u32 },
+    #[error("Downlink queue for 0x{addr:08X} is full")]
+    QueueFull { addr: u32 },
+    #[error("Class B ping slots for 0x{addr:08X} need the `crypto` feature")]
+    PingSlotsUnavailable { addr: u32 },
+}
 
 /// LoRaWAN device class
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -75,6 +135,507 @@ pub struct MulticastSession {
     pub frequency: u32,
 }
 
+impl MulticastSession {
+    /// Session for a [`SessionStore`](super::lorawan::SessionStore), which then
+    /// builds and counts the group's downlinks like a unicast device's
+    #[cfg(feature = "crypto")]
+    pub fn session(&self) -> DeviceSession {
+        DeviceSession {
+            dev_addr: self.address,
+            keys: SessionKeys {
+                nwk_s_key: self.nwk_s_key,
+                app_s_key: self.app_s_key,
+            },
+            fcnt_up: None,
+            fcnt_down: self.fcnt_down,
+        }
+    }
+}
+
+/// Time since the GPS epoch for a wall-clock time
+pub fn gps_time(time: SystemTime) -> Duration {
+    let unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    (unix + Duration::from_secs(GPS_LEAP_SECONDS))
+        .saturating_sub(Duration::from_secs(GPS_EPOCH_UNIX_SECS))
+}
+
+/// Beacon `Time` field of the beacon period containing `gps`: its start in
+/// GPS seconds, modulo 2^32
+pub fn beacon_time(gps: Duration) -> u32 {
+    let period = BEACON_PERIOD.as_secs();
+    ((gps.as_secs() / period) * period) as u32
+}
+
+/// CRC-16/CCITT (polynomial 0x1021, zero init) protecting beacon fields
+fn beacon_crc(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// RFU bytes before `Time` and before the second CRC
+fn beacon_rfu_len(region: Region) -> (usize, usize) {
+    match region {
+        Region::US915 | Region::AU915 => (5, 3),
+        Region::IN865 => (1, 3),
+        _ => (2, 0),
+    }
+}
+
+/// Gateway-specific beacon field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GwSpecific {
+    /// 0-2: GPS position of the gateway's first to third antenna
+    pub info_desc: u8,
+    pub info: [u8; 6],
+}
+
+impl GwSpecific {
+    /// Antenna position in degrees, encoded as 24-bit fractions of 90°/180°
+    pub fn gps(antenna: u8, latitude: f64, longitude: f64) -> Self {
+        let encode = |deg: f64, range: f64| {
+            let value = (deg / range * 8_388_608.0).clamp(-8_388_608.0, 8_388_607.0) as i32;
+            value.to_le_bytes()
+        };
+        let lat = encode(latitude, 90.0);
+        let lng = encode(longitude, 180.0);
+
+        Self {
+            info_desc: antenna.min(2),
+            info: [lat[0], lat[1], lat[2], lng[0], lng[1], lng[2]],
+        }
+    }
+
+    /// Antenna position as (latitude, longitude), if this field carries one
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        if self.info_desc > 2 {
+            return None;
+        }
+        // Sign-extend the 24-bit little-endian values
+        let decode = |b: &[u8], range: f64| {
+            let value = i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8;
+            value as f64 * range / 8_388_608.0
+        };
+        Some((
+            decode(&self.info[..3], 90.0),
+            decode(&self.info[3..], 180.0),
+        ))
+    }
+}
+
+/// Class B beacon payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeaconFrame {
+    /// GPS seconds at the start of the beacon period, modulo 2^32
+    pub time: u32,
+    pub gw_specific: GwSpecific,
+}
+
+impl BeaconFrame {
+    /// Payload length for `region`
+    pub fn encoded_len(region: Region) -> usize {
+        let (rfu1, rfu2) = beacon_rfu_len(region);
+        rfu1 + 4 + 2 + 7 + rfu2 + 2
+    }
+
+    pub fn encode(&self, region: Region) -> Vec<u8> {
+        let (rfu1, rfu2) = beacon_rfu_len(region);
+        let mut out = vec![0u8; rfu1];
+        out.extend_from_slice(&self.time.to_le_bytes());
+        let crc = beacon_crc(&out);
+        out.extend_from_slice(&crc.to_le_bytes());
+
+        let start = out.len();
+        out.push(self.gw_specific.info_desc);
+        out.extend_from_slice(&self.gw_specific.info);
+        out.resize(out.len() + rfu2, 0);
+        let crc = beacon_crc(&out[start..]);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// Parse a beacon, checking both CRCs
+    pub fn decode(region: Region, data: &[u8]) -> Result<Self, ClassBCError> {
+        let expected = Self::encoded_len(region);
+        if data.len() != expected {
+            return Err(ClassBCError::BeaconLength {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        let (rfu1, _) = beacon_rfu_len(region);
+        let crc1_at = rfu1 + 4;
+        let crc = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+        if beacon_crc(&data[..crc1_at]) != crc(crc1_at) {
+            return Err(ClassBCError::BeaconCrc { part: "network" });
+        }
+        let gw_at = crc1_at + 2;
+        if beacon_crc(&data[gw_at..expected - 2]) != crc(expected - 2) {
+            return Err(ClassBCError::BeaconCrc { part: "gateway" });
+        }
+
+        Ok(Self {
+            time: u32::from_le_bytes(data[rfu1..crc1_at].try_into().unwrap()),
+            gw_specific: GwSpecific {
+                info_desc: data[gw_at],
+                info: data[gw_at + 1..gw_at + 7].try_into().unwrap(),
+            },
+        })
+    }
+}
+
+/// Time on air of a beacon in `region`
+///
+/// Beacons use a 10-symbol preamble, implicit header, no payload CRC and
+/// coding rate 4/5 at the region's beacon data rate.
+pub fn beacon_airtime(region: Region) -> Duration {
+    let Some(params) = region.data_rate(region.beacon_data_rate()) else {
+        return Duration::ZERO;
+    };
+    let sf = params.spreading_factor as i32;
+    let symbol_ms = f64::from(1u32 << sf) / params.bandwidth.bandwidth_khz();
+    let low_dr_optimize = i32::from(symbol_ms > 16.0);
+
+    let payload_bits = 8 * BeaconFrame::encoded_len(region) as i32 - 4 * sf + 28 - 20;
+    let blocks = (payload_bits as f64 / f64::from(4 * (sf - 2 * low_dr_optimize))).ceil();
+    let payload_symbols = 8.0 + (blocks * 5.0).max(0.0);
+
+    Duration::from_secs_f64((BEACON_PREAMBLE_SYMBOLS + 4.25 + payload_symbols) * symbol_ms / 1e3)
+}
+
+/// Ping slots between two of a device's receive windows
+#[cfg(feature = "crypto")]
+fn ping_period(ping_nb: u8) -> u32 {
+    BEACON_WINDOW_SLOTS >> ping_nb.min(7)
+}
+
+/// Pseudo-random ping offset of `dev_addr` in the beacon period `beacon_time`
+///
+/// `Rand = AES128(key = 0, BeaconTime | DevAddr | 0 * 8)`, offset
+/// `(Rand[0] + Rand[1] * 256) mod pingPeriod`.
+#[cfg(feature = "crypto")]
+pub fn ping_offset(beacon_time: u32, dev_addr: u32, ping_nb: u8) -> u32 {
+    let mut block = [0u8; 16];
+    block[..4].copy_from_slice(&beacon_time.to_le_bytes());
+    block[4..8].copy_from_slice(&dev_addr.to_le_bytes());
+    let mut rand = [0u8; 16];
+    get_aes_backend().encrypt_block(&block, &[0u8; 16], &mut rand);
+
+    u16::from_le_bytes([rand[0], rand[1]]) as u32 % ping_period(ping_nb)
+}
+
+/// GPS start times of `dev_addr`'s `2^ping_nb` ping slots in the beacon
+/// period `beacon_time`
+#[cfg(feature = "crypto")]
+pub fn ping_slots(beacon_time: u32, dev_addr: u32, ping_nb: u8) -> Vec<Duration> {
+    let offset = ping_offset(beacon_time, dev_addr, ping_nb);
+    let period = ping_period(ping_nb);
+    let start = Duration::from_secs(beacon_time as u64) + BEACON_RESERVED;
+
+    (0..1u32 << ping_nb.min(7))
+        .map(|n| start + PING_SLOT_LEN * (offset + n * period))
+        .collect()
+}
+
+/// First ping slot of `dev_addr` starting at or after GPS time `after`
+#[cfg(feature = "crypto")]
+pub fn next_ping_slot(dev_addr: u32, ping_nb: u8, after: Duration) -> Duration {
+    let mut period_start = beacon_time(after);
+    loop {
+        if let Some(slot) = ping_slots(period_start, dev_addr, ping_nb)
+            .into_iter()
+            .find(|&slot| slot >= after)
+        {
+            return slot;
+        }
+        period_start = period_start.wrapping_add(BEACON_PERIOD.as_secs() as u32);
+    }
+}
+
+/// Application downlink waiting for a Class B/C receive window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedDownlink {
+    pub fport: u8,
+    pub payload: Vec<u8>,
+    pub confirmed: bool,
+}
+
+/// When a scheduled downlink has to go on air
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxTiming {
+    /// Class C: the device is listening already
+    Immediately,
+    /// Class B: start of a ping slot, as time since the GPS epoch
+    GpsTime(Duration),
+}
+
+/// A queued downlink assigned to a receive window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledDownlink {
+    /// DevAddr or multicast address
+    pub addr: u32,
+    pub multicast: bool,
+    pub timing: TxTiming,
+    pub frequency_hz: u32,
+    pub data_rate: u8,
+    pub downlink: QueuedDownlink,
+}
+
+#[cfg(feature = "crypto")]
+impl ScheduledDownlink {
+    /// Contents for [`SessionStore::build_downlink`](super::lorawan::SessionStore::build_downlink)
+    pub fn to_downlink(&self) -> Downlink {
+        Downlink {
+            confirmed: self.downlink.confirmed,
+            fport: Some(self.downlink.fport),
+            payload: self.downlink.payload.clone(),
+            ..Downlink::default()
+        }
+    }
+}
+
+/// Receive windows and pending downlinks of one device or multicast group
+#[derive(Debug)]
+struct DownlinkTarget {
+    class: DeviceClass,
+    ping_nb: u8,
+    multicast: bool,
+    /// Fixed channel, or `None` for the region default
+    channel: Option<(u32, u8)>,
+    queue: VecDeque<QueuedDownlink>,
+    /// Earliest GPS time the next Class B slot may start
+    #[cfg_attr(not(feature = "crypto"), allow(dead_code))]
+    next_free: Duration,
+}
+
+/// Per-device downlink queues served at each device's next receive window
+///
+/// Class C devices and groups get their downlinks at once on RX2 (or the
+/// group's channel); Class B ones at their next unused ping slot. Class A
+/// devices are not tracked here; they take downlinks after an uplink.
+/// Placing Class B ping slots needs the `crypto` feature.
+#[derive(Debug)]
+pub struct DownlinkScheduler {
+    region: Region,
+    targets: HashMap<u32, DownlinkTarget>,
+}
+
+impl DownlinkScheduler {
+    pub fn new(region: Region) -> Self {
+        Self {
+            region,
+            targets: HashMap::new(),
+        }
+    }
+
+    /// Register or update a unicast device, keeping its queue
+    ///
+    /// `ping_nb` is only used for Class B: `2^ping_nb` slots per beacon.
+    pub fn register_device(&mut self, dev_addr: u32, class: DeviceClass, ping_nb: u8) {
+        self.register(dev_addr, class, ping_nb, false, None);
+    }
+
+    /// Register a multicast group on the session's frequency and data rate
+    pub fn register_multicast(
+        &mut self,
+        session: &MulticastSession,
+        class: DeviceClass,
+        ping_nb: u8,
+    ) {
+        let channel = Some((session.frequency, session.data_rate));
+        self.register(session.address, class, ping_nb, true, channel);
+    }
+
+    /// Move a registered multicast group to the session's channel, keeping
+    /// its class and ping slots; a new group is registered for Class C
+    pub fn update_multicast(&mut self, session: &MulticastSession) {
+        match self.targets.get_mut(&session.address) {
+            Some(target) if target.multicast => {
+                target.channel = Some((session.frequency, session.data_rate));
+            }
+            _ => self.register_multicast(session, DeviceClass::ClassC, 0),
+        }
+    }
+
+    fn register(
+        &mut self,
+        addr: u32,
+        class: DeviceClass,
+        ping_nb: u8,
+        multicast: bool,
+        channel: Option<(u32, u8)>,
+    ) {
+        let target = self.targets.entry(addr).or_insert_with(|| DownlinkTarget {
+            class,
+            ping_nb,
+            multicast,
+            channel,
+            queue: VecDeque::new(),
+            next_free: Duration::ZERO,
+        });
+        target.class = class;
+        target.ping_nb = ping_nb.min(7);
+        target.multicast = multicast;
+        target.channel = channel;
+    }
+
+    /// Forget a device or group, returning the downlinks it never received
+    pub fn remove(&mut self, addr: u32) -> Vec<QueuedDownlink> {
+        self.targets
+            .remove(&addr)
+            .map(|target| target.queue.into())
+            .unwrap_or_default()
+    }
+
+    /// Queue a downlink, returning how many are now waiting for `addr`
+    pub fn enqueue(&mut self, addr: u32, downlink: QueuedDownlink) -> Result<usize, ClassBCError> {
+        let target = self
+            .targets
+            .get_mut(&addr)
+            .ok_or(ClassBCError::UnknownTarget { addr })?;
+        if target.class == DeviceClass::ClassA {
+            return Err(ClassBCError::ClassA { addr });
+        }
+        if target.multicast && downlink.confirmed {
+            return Err(ClassBCError::ConfirmedMulticast { addr });
+        }
+        if target.class == DeviceClass::ClassB && cfg!(not(feature = "crypto")) {
+            return Err(ClassBCError::PingSlotsUnavailable { addr });
+        }
+        if target.queue.len() >= MAX_QUEUED_DOWNLINKS {
+            return Err(ClassBCError::QueueFull { addr });
+        }
+
+        target.queue.push_back(downlink);
+        Ok(target.queue.len())
+    }
+
+    /// Downlinks waiting for `addr`
+    pub fn pending(&self, addr: u32) -> usize {
+        self.targets.get(&addr).map_or(0, |t| t.queue.len())
+    }
+
+    /// Class `addr` is registered for
+    pub fn class(&self, addr: u32) -> Option<DeviceClass> {
+        self.targets.get(&addr).map(|t| t.class)
+    }
+
+    /// Put scheduled downlinks that were never sent back at the front of
+    /// their queues, in their original order
+    ///
+    /// Downlinks for a device or group removed in the meantime are dropped.
+    pub fn requeue(&mut self, undelivered: Vec<ScheduledDownlink>) {
+        for scheduled in undelivered.into_iter().rev() {
+            match self.targets.get_mut(&scheduled.addr) {
+                Some(target) => target.queue.push_front(scheduled.downlink),
+                None => warn!(
+                    "Dropping downlink for removed target 0x{:08X}",
+                    scheduled.addr
+                ),
+            }
+        }
+    }
+
+    /// Take the next downlink of every device with one waiting
+    ///
+    /// Class B downlinks are assigned the first ping slot at or after `now`
+    /// (GPS time) that no earlier call has used, so calling this again
+    /// before the slot passes schedules the following downlink one slot
+    /// later.
+    pub fn schedule(&mut self, now: Duration) -> Vec<ScheduledDownlink> {
+        let region = self.region;
+        let mut scheduled = Vec::new();
+
+        for (&addr, target) in &mut self.targets {
+            if target.queue.is_empty() {
+                continue;
+            }
+
+            let (timing, frequency_hz, data_rate) = match target.class {
+                DeviceClass::ClassA => continue,
+                DeviceClass::ClassC => {
+                    let (freq, dr) = target.class_c_channel(region);
+                    (TxTiming::Immediately, freq, dr)
+                }
+                #[cfg(not(feature = "crypto"))]
+                DeviceClass::ClassB => {
+                    // `enqueue` refuses these without ping-slot support
+                    debug!("No ping slot for 0x{addr:08X} after {now:?}");
+                    continue;
+                }
+                #[cfg(feature = "crypto")]
+                DeviceClass::ClassB => {
+                    let slot = next_ping_slot(addr, target.ping_nb, now.max(target.next_free));
+                    target.next_free = slot + PING_SLOT_LEN;
+                    let period = beacon_time(slot);
+                    let (freq, dr) = target.channel.unwrap_or((
+                        region.ping_slot_frequency_hz(period, addr),
+                        region.ping_slot_data_rate(),
+                    ));
+                    (TxTiming::GpsTime(slot), freq, dr)
+                }
+            };
+
+            debug!(
+                "Scheduling downlink for 0x{addr:08X} {timing:?} @ {frequency_hz} Hz DR{data_rate}"
+            );
+            scheduled.push(ScheduledDownlink {
+                addr,
+                multicast: target.multicast,
+                timing,
+                frequency_hz,
+                data_rate,
+                downlink: target.queue.pop_front().expect("queue checked above"),
+            });
+        }
+
+        scheduled
+    }
+
+    /// Take every downlink waiting for a Class C device or group
+    ///
+    /// Class C receivers listen whenever they are not transmitting, so their
+    /// whole queue can go out back to back.
+    pub fn drain_class_c(&mut self) -> Vec<ScheduledDownlink> {
+        let region = self.region;
+        let mut drained = Vec::new();
+
+        for (&addr, target) in &mut self.targets {
+            if target.class != DeviceClass::ClassC {
+                continue;
+            }
+            let (frequency_hz, data_rate) = target.class_c_channel(region);
+            drained.extend(target.queue.drain(..).map(|downlink| ScheduledDownlink {
+                addr,
+                multicast: target.multicast,
+                timing: TxTiming::Immediately,
+                frequency_hz,
+                data_rate,
+                downlink,
+            }));
+        }
+
+        drained
+    }
+}
+
+impl DownlinkTarget {
+    /// RX2, or the group's own channel
+    fn class_c_channel(&self, region: Region) -> (u32, u8) {
+        self.channel
+            .unwrap_or((region.rx2_frequency_hz(), region.rx2_data_rate()))
+    }
+}
+
 /// Class B/C controller
 pub struct ClassBCController {
     /// Current device class
@@ -97,6 +658,21 @@ pub struct ClassBCController {
 
     /// Last beacon reception time
     last_beacon: Arc<Mutex<Option<Instant>>>,
+
+    /// `Time` field of the last received beacon
+    beacon_time: Arc<Mutex<Option<u32>>>,
+
+    /// Own DevAddr, which places this device's ping slots
+    dev_addr: Arc<RwLock<Option<u32>>>,
+
+    /// Region providing beacon and receive window channels
+    region: Region,
+
+    /// Downlinks waiting for Class B/C devices and multicast groups
+    downlinks: Arc<Mutex<DownlinkScheduler>>,
+
+    /// Wakes `run_class_c` when a downlink is queued
+    downlink_queued: Arc<Notify>,
 }
 
 impl Default for ClassBCController {
@@ -106,19 +682,41 @@ impl Default for ClassBCController {
 }
 
 impl ClassBCController {
-    /// Create a new Class B/C controller
+    /// Create a new Class B/C controller for EU868
     pub fn new() -> Self {
+        Self::with_region(Region::EU868)
+    }
+
+    /// Create a new Class B/C controller using a region's beacon and ping-slot
+    /// channels
+    pub fn with_region(region: Region) -> Self {
+        let beacon_config = BeaconConfig {
+            ping_dr: region.ping_slot_data_rate(),
+            ping_freq: region.ping_slot_frequency_hz(0, 0),
+            ..BeaconConfig::default()
+        };
+
         Self {
             device_class: Arc::new(RwLock::new(DeviceClass::ClassA)),
-            beacon_config: Arc::new(RwLock::new(BeaconConfig::default())),
+            beacon_config: Arc::new(RwLock::new(beacon_config)),
             multicast_sessions: Arc::new(RwLock::new(Vec::new())),
             ping_slots: Arc::new(Mutex::new(Vec::new())),
             class_c_active: Arc::new(Mutex::new(false)),
             beacon_locked: Arc::new(Mutex::new(false)),
             last_beacon: Arc::new(Mutex::new(None)),
+            beacon_time: Arc::new(Mutex::new(None)),
+            dev_addr: Arc::new(RwLock::new(None)),
+            region,
+            downlinks: Arc::new(Mutex::new(DownlinkScheduler::new(region))),
+            downlink_queued: Arc::new(Notify::new()),
         }
     }
 
+    /// Set the DevAddr whose ping slots this device listens in
+    pub async fn set_dev_addr(&self, dev_addr: u32) {
+        *self.dev_addr.write().await = Some(dev_addr);
+    }
+
     /// Switch device class
     pub async fn set_device_class(&self, class: DeviceClass) -> Result<(), String> {
         let mut current = self.device_class.write().await;
@@ -167,16 +765,37 @@ impl ClassBCController {
     }
 
     /// Calculate ping slot schedule
+    ///
+    /// Slots cover the current beacon period, after its reserved interval.
+    /// Once a beacon has been received and the DevAddr is known, they sit at
+    /// the device's ping offset for that beacon's `Time` (with the `crypto`
+    /// feature). Otherwise they are spread evenly over the period.
     async fn calculate_ping_slots(&self) -> Result<(), String> {
         let config = self.beacon_config.read().await;
         let mut slots = self.ping_slots.lock().await;
+        let period_start = self.last_beacon.lock().await.unwrap_or(config.next_beacon);
 
         slots.clear();
 
+        #[cfg(feature = "crypto")]
+        if let (Some(time), Some(dev_addr)) =
+            (*self.beacon_time.lock().await, *self.dev_addr.read().await)
+        {
+            // `period_start` is the local instant of GPS time `time`
+            let gps_start = Duration::from_secs(time as u64);
+            slots.extend(
+                ping_slots(time, dev_addr, config.ping_nb)
+                    .into_iter()
+                    .map(|slot| period_start + (slot - gps_start)),
+            );
+            debug!("Calculated {} ping slots for 0x{dev_addr:08X}", slots.len());
+            return Ok(());
+        }
+
         let num_slots = 1 << config.ping_nb;
-        let slot_period = config.period.as_millis() / num_slots;
+        let slot_period = (config.period - BEACON_RESERVED).as_millis() / num_slots;
 
-        let base_time = config.next_beacon;
+        let base_time = period_start + BEACON_RESERVED;
 
         for i in 0..num_slots {
             let slot_time = base_time + Duration::from_millis(i as u64 * slot_period as u64);
@@ -196,7 +815,13 @@ impl ClassBCController {
     }
 
     /// Add multicast session
+    ///
+    /// A new group is also registered for Class C downlinks; one already
+    /// registered (e.g. for Class B with `register_multicast`) keeps its class
+    /// and ping slots and moves to the session's channel.
     pub async fn add_multicast_session(&self, session: MulticastSession) {
+        self.downlinks.lock().await.update_multicast(&session);
+
         let mut sessions = self.multicast_sessions.write().await;
 
         // Remove existing session with same address
@@ -228,28 +853,37 @@ impl ClassBCController {
         Ok(())
     }
 
-    /// Process Class C continuous reception
-    pub async fn run_class_c(&self, mut shutdown_rx: tokio::sync::oneshot::Receiver<()>) {
-        info!("Starting Class C continuous reception");
-
-        let mut check_interval = interval(Duration::from_millis(100));
+    /// Serve Class C devices and groups until `shutdown_rx` fires
+    ///
+    /// Each downlink queued for a Class C target is handed to `tx` as soon as
+    /// it is enqueued, for the transmit path to send right away. Stops early
+    /// if `tx` is closed, putting the downlinks it could not hand over back
+    /// in their queues.
+    pub async fn run_class_c(
+        &self,
+        tx: mpsc::Sender<ScheduledDownlink>,
+        mut shutdown_rx: oneshot::Receiver<()>,
+    ) {
+        info!("Starting Class C downlink service");
 
         loop {
+            let mut drained = self.downlinks.lock().await.drain_class_c().into_iter();
+            while let Some(downlink) = drained.next() {
+                debug!("Class C downlink for 0x{:08X}", downlink.addr);
+                if let Err(mpsc::error::SendError(downlink)) = tx.send(downlink).await {
+                    info!("Class C downlink receiver closed");
+                    let undelivered = std::iter::once(downlink).chain(drained).collect();
+                    self.downlinks.lock().await.requeue(undelivered);
+                    return;
+                }
+            }
+
             tokio::select! {
                 _ = &mut shutdown_rx => {
-                    info!("Class C reception stopping");
+                    info!("Class C downlink service stopping");
                     break;
                 }
-
-                _ = check_interval.tick() => {
-                    if !*self.class_c_active.lock().await {
-                        continue;
-                    }
-
-                    // In real implementation, this would check for incoming packets
-                    // For now, just maintain active state
-                    debug!("Class C RX active");
-                }
+                _ = self.downlink_queued.notified() => {}
             }
         }
     }
@@ -269,13 +903,25 @@ impl ClassBCController {
     }
 
     /// Handle beacon reception
-    pub async fn handle_beacon(&self, timestamp: Instant) {
-        *self.last_beacon.lock().await = Some(timestamp);
+    ///
+    /// `received_at` is the beacon's RxDone time. The beacon went on air at
+    /// the start of the period `beacon.time`, one [`beacon_airtime`] earlier.
+    pub async fn handle_beacon(&self, beacon: &BeaconFrame, received_at: Instant) {
+        let period_start = received_at
+            .checked_sub(beacon_airtime(self.region))
+            .unwrap_or(received_at);
+        *self.last_beacon.lock().await = Some(period_start);
+        *self.beacon_time.lock().await = Some(beacon.time);
         *self.beacon_locked.lock().await = true;
 
-        // Update next beacon time
+        // Update next beacon time and the channel this period's slots use
         let mut config = self.beacon_config.write().await;
-        config.next_beacon = timestamp + config.period;
+        config.period = BEACON_PERIOD;
+        config.next_beacon = period_start + BEACON_PERIOD;
+        if let Some(dev_addr) = *self.dev_addr.read().await {
+            config.ping_freq = self.region.ping_slot_frequency_hz(beacon.time, dev_addr);
+        }
+        drop(config);
 
         // Recalculate ping slots
         if let Err(e) = self.calculate_ping_slots().await {
@@ -283,6 +929,45 @@ impl ClassBCController {
         }
     }
 
+    /// Register a device for Class B (`2^ping_nb` slots per beacon) or Class C
+    /// downlinks
+    pub async fn register_device(&self, dev_addr: u32, class: DeviceClass, ping_nb: u8) {
+        self.downlinks
+            .lock()
+            .await
+            .register_device(dev_addr, class, ping_nb);
+    }
+
+    /// Register a multicast group for Class B or Class C downlinks
+    pub async fn register_multicast(
+        &self,
+        session: &MulticastSession,
+        class: DeviceClass,
+        ping_nb: u8,
+    ) {
+        self.downlinks
+            .lock()
+            .await
+            .register_multicast(session, class, ping_nb);
+    }
+
+    /// Queue a downlink for a registered device or multicast group
+    pub async fn enqueue_downlink(
+        &self,
+        addr: u32,
+        downlink: QueuedDownlink,
+    ) -> Result<usize, ClassBCError> {
+        let queued = self.downlinks.lock().await.enqueue(addr, downlink)?;
+        self.downlink_queued.notify_one();
+        Ok(queued)
+    }
+
+    /// Assign waiting downlinks to receive windows, see
+    /// [`DownlinkScheduler::schedule`]
+    pub async fn schedule_downlinks(&self, now: SystemTime) -> Vec<ScheduledDownlink> {
+        self.downlinks.lock().await.schedule(gps_time(now))
+    }
+
     /// Get Class B/C status
     pub async fn get_status(&self) -> ClassBCStatus {
         ClassBCStatus {
@@ -341,9 +1026,272 @@ mod tests {
             frequency: 869_525_000,
         };
 
-        controller.add_multicast_session(session).await;
+        controller.add_multicast_session(session.clone()).await;
 
         let status = controller.get_status().await;
         assert_eq!(status.multicast_sessions, 1);
+        let class = |controller: &ClassBCController| {
+            let downlinks = controller.downlinks.try_lock().unwrap();
+            downlinks.class(session.address)
+        };
+        assert_eq!(class(&controller), Some(DeviceClass::ClassC));
+
+        // A group registered for Class B stays Class B when its session is added.
+        let controller = ClassBCController::new();
+        controller
+            .register_multicast(&session, DeviceClass::ClassB, 2)
+            .await;
+        controller.add_multicast_session(session.clone()).await;
+        assert_eq!(class(&controller), Some(DeviceClass::ClassB));
+    }
+
+    #[test]
+    fn test_beacon_frame_codec() {
+        // EU868 example beacon from the LoRaWAN 1.0.3 Class B chapter
+        let raw = hex::decode("00000000 02cca27e 00012000 008103de55".replace(' ', "")).unwrap();
+        let beacon = BeaconFrame::decode(Region::EU868, &raw).unwrap();
+        assert_eq!(beacon.time, 0xCC02_0000);
+        assert_eq!(beacon.gw_specific.info_desc, 0);
+        assert_eq!(beacon.encode(Region::EU868), raw);
+
+        let mut corrupt = raw.clone();
+        corrupt[3] ^= 0x01;
+        assert_eq!(
+            BeaconFrame::decode(Region::EU868, &corrupt),
+            Err(ClassBCError::BeaconCrc { part: "network" })
+        );
+
+        let beacon = BeaconFrame {
+            time: beacon_time(Duration::from_secs(1_400_000_100)),
+            gw_specific: GwSpecific::gps(0, -33.8688, 151.2093),
+        };
+        let encoded = beacon.encode(Region::AU915);
+        assert_eq!(encoded.len(), 23);
+        let decoded = BeaconFrame::decode(Region::AU915, &encoded).unwrap();
+        assert_eq!(decoded.time % 128, 0);
+        let (lat, lng) = decoded.gw_specific.coordinates().unwrap();
+        assert!((lat + 33.8688).abs() < 1e-4 && (lng - 151.2093).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_beacon_airtime() {
+        // 17-byte beacon at SF9/125 kHz: 14.25 preamble + 23 payload symbols
+        let eu868 = beacon_airtime(Region::EU868);
+        assert_eq!(eu868.as_micros(), 152_576);
+        // 23 bytes at SF12/500 kHz
+        let us915 = beacon_airtime(Region::US915);
+        assert_eq!(us915.as_micros(), 305_152);
+    }
+
+    #[tokio::test]
+    async fn test_beacon_period_starts_before_rx_done() {
+        let controller = ClassBCController::new();
+        let beacon = BeaconFrame {
+            time: 1_384_000_000,
+            gw_specific: GwSpecific::gps(0, 0.0, 0.0),
+        };
+        let rx_done = Instant::now();
+        controller.handle_beacon(&beacon, rx_done).await;
+
+        let start = rx_done - beacon_airtime(Region::EU868);
+        assert_eq!(*controller.last_beacon.lock().await, Some(start));
+        assert_eq!(
+            controller.beacon_config.read().await.next_beacon,
+            start + BEACON_PERIOD
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ping_slots_fall_inside_the_current_period() {
+        let controller = ClassBCController::new();
+        controller.set_dev_addr(0x2601_1A2B).await;
+        let beacon = BeaconFrame {
+            time: 1_384_000_000,
+            gw_specific: GwSpecific::gps(0, 0.0, 0.0),
+        };
+        let rx_done = Instant::now();
+        controller.handle_beacon(&beacon, rx_done).await;
+
+        let start = rx_done - beacon_airtime(Region::EU868);
+        let slot = controller.get_next_ping_slot().await.unwrap();
+        assert!(slot >= start + BEACON_RESERVED);
+        assert!(slot < start + BEACON_PERIOD);
+
+        #[cfg(feature = "crypto")]
+        {
+            let ping_nb = controller.beacon_config.read().await.ping_nb;
+            let offset = ping_offset(beacon.time, 0x2601_1A2B, ping_nb);
+            let first = *controller.ping_slots.lock().await.first().unwrap();
+            assert_eq!(first, start + BEACON_RESERVED + PING_SLOT_LEN * offset);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_class_c_drains_queue() {
+        let controller = Arc::new(ClassBCController::new());
+        controller
+            .register_device(0x0000_0001, DeviceClass::ClassC, 0)
+            .await;
+        let downlink = |fport| QueuedDownlink {
+            fport,
+            payload: vec![0x01],
+            confirmed: false,
+        };
+        controller
+            .enqueue_downlink(0x0000_0001, downlink(1))
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = mpsc::channel(4);
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn({
+            let controller = controller.clone();
+            async move { controller.run_class_c(tx, shutdown_rx).await }
+        });
+
+        // Queued before the service started, and while it runs
+        let first = rx.recv().await.unwrap();
+        assert_eq!((first.addr, first.timing), (1, TxTiming::Immediately));
+        assert_eq!(first.downlink.fport, 1);
+        controller
+            .enqueue_downlink(0x0000_0001, downlink(2))
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().downlink.fport, 2);
+        assert_eq!(controller.downlinks.lock().await.pending(1), 0);
+
+        shutdown_tx.send(()).unwrap();
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_class_c_keeps_downlinks_when_receiver_closes() {
+        let controller = ClassBCController::new();
+        controller
+            .register_device(0x0000_0001, DeviceClass::ClassC, 0)
+            .await;
+        for fport in 1..=3 {
+            controller
+                .enqueue_downlink(
+                    0x0000_0001,
+                    QueuedDownlink {
+                        fport,
+                        payload: vec![fport],
+                        confirmed: false,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+
+        let (tx, rx) = mpsc::channel(4);
+        drop(rx);
+        let (_shutdown_tx, shutdown_rx) = oneshot::channel();
+        controller.run_class_c(tx, shutdown_rx).await;
+
+        let mut downlinks = controller.downlinks.lock().await;
+        let fports: Vec<u8> = downlinks
+            .drain_class_c()
+            .into_iter()
+            .map(|d| d.downlink.fport)
+            .collect();
+        assert_eq!(fports, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_gps_time() {
+        let unix = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            gps_time(unix),
+            Duration::from_secs(1_700_000_000 + GPS_LEAP_SECONDS - GPS_EPOCH_UNIX_SECS)
+        );
+        assert_eq!(beacon_time(Duration::from_millis(256_500)), 256);
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_ping_slots_follow_offset() {
+        let slots = ping_slots(1_384_000_000, 0x2601_1A2B, 3);
+        assert_eq!(slots.len(), 8);
+
+        let offset = ping_offset(1_384_000_000, 0x2601_1A2B, 3);
+        assert!(offset < 512);
+        let start = Duration::from_secs(1_384_000_000) + BEACON_RESERVED;
+        assert_eq!(slots[0], start + PING_SLOT_LEN * offset);
+        assert_eq!(slots[1] - slots[0], PING_SLOT_LEN * 512);
+
+        // Offsets differ per beacon period
+        let other = ping_offset(1_384_000_128, 0x2601_1A2B, 3);
+        let after = slots[7] + Duration::from_millis(1);
+        let next = next_ping_slot(0x2601_1A2B, 3, after);
+        assert_eq!(
+            next,
+            Duration::from_secs(1_384_000_128) + BEACON_RESERVED + PING_SLOT_LEN * other
+        );
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_downlink_scheduler() {
+        let mut scheduler = DownlinkScheduler::new(Region::EU868);
+        let valve_close = QueuedDownlink {
+            fport: 10,
+            payload: vec![0x01, 0x00],
+            confirmed: true,
+        };
+
+        scheduler.register_device(0x0000_0001, DeviceClass::ClassC, 0);
+        scheduler.register_device(0x0000_0002, DeviceClass::ClassB, 7);
+        scheduler.register_device(0x0000_0003, DeviceClass::ClassA, 0);
+        scheduler.register_multicast(
+            &MulticastSession {
+                address: 0xFF00_0001,
+                nwk_s_key: [1; 16],
+                app_s_key: [2; 16],
+                fcnt_down: 5,
+                data_rate: 3,
+                frequency: 869_525_000,
+            },
+            DeviceClass::ClassC,
+            0,
+        );
+
+        assert_eq!(scheduler.enqueue(1, valve_close.clone()), Ok(1));
+        assert_eq!(scheduler.enqueue(2, valve_close.clone()), Ok(1));
+        assert_eq!(scheduler.enqueue(2, valve_close.clone()), Ok(2));
+        assert_eq!(
+            scheduler.enqueue(3, valve_close.clone()),
+            Err(ClassBCError::ClassA { addr: 3 })
+        );
+        assert_eq!(
+            scheduler.enqueue(0xFF00_0001, valve_close.clone()),
+            Err(ClassBCError::ConfirmedMulticast { addr: 0xFF00_0001 })
+        );
+        assert_eq!(
+            scheduler.enqueue(4, valve_close.clone()),
+            Err(ClassBCError::UnknownTarget { addr: 4 })
+        );
+
+        let now = Duration::from_secs(1_384_000_000);
+        let first = scheduler.schedule(now);
+        assert_eq!(first.len(), 2);
+        let class_c = first.iter().find(|d| d.addr == 1).unwrap();
+        assert_eq!(class_c.timing, TxTiming::Immediately);
+        assert_eq!((class_c.frequency_hz, class_c.data_rate), (869_525_000, 0));
+        assert_eq!(class_c.to_downlink().fport, Some(10));
+
+        let TxTiming::GpsTime(slot) = first.iter().find(|d| d.addr == 2).unwrap().timing else {
+            panic!("Class B downlink must wait for a ping slot");
+        };
+        assert!(slot >= now);
+
+        // The second Class B downlink takes the following slot
+        let second = scheduler.schedule(now);
+        assert_eq!(second.len(), 1);
+        assert_eq!(
+            second[0].timing,
+            TxTiming::GpsTime(next_ping_slot(2, 7, slot + PING_SLOT_LEN))
+        );
+        assert_eq!(scheduler.pending(2), 0);
     }
 }
@@ -56,9 +56,9 @@ impl LoRaPayloadDecoder for SensativeDecoder {
 
             // Parse based on type
             match typ {
-                0x01 => {
+                0x01
                     // Temperature (2 bytes, 0.01°C resolution)
-                    if len == 2 {
+                    if len == 2 => {
                         let temp = i16::from_le_bytes([data[0], data[1]]) as f64 / 100.0;
                         readings.push(Reading {
                             value: MBusRecordValue::Numeric(temp),
@@ -69,7 +69,6 @@ impl LoRaPayloadDecoder for SensativeDecoder {
                             description: Some("Temperature sensor".to_string()),
                         });
                     }
-                }
                 0x02
                     // Humidity (1 byte, 0.5% resolution)
                     if len == 1 => {
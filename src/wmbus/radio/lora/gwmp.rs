@@ -0,0 +1,880 @@
+//! Semtech UDP packet forwarder protocol (GWMP)
+//!
+//! Implements protocol version 2 of the Semtech gateway-to-server protocol in
+//! both roles:
+//!
+//! - [`PacketForwarder`] is the gateway side. It pushes received LoRa frames
+//!   (`rxpk`) and status reports (`stat`) to a network server such as
+//!   ChirpStack or TTN, keeps the downlink path open with PULL_DATA and hands
+//!   back `txpk` downlinks from PULL_RESP.
+//! - [`GwmpServer`] is the server side, so the crate can take frames from an
+//!   existing packet forwarder.
+//!
+//! ```text
+//! PUSH_DATA  gw → srv  ver | token | 0x00 | GatewayEUI | JSON {"rxpk":[..],"stat":{..}}
+//! PUSH_ACK   srv → gw  ver | token | 0x01
+//! PULL_DATA  gw → srv  ver | token | 0x02 | GatewayEUI
+//! PULL_RESP  srv → gw  ver | token | 0x03 | JSON {"txpk":{..}}
+//! PULL_ACK   srv → gw  ver | token | 0x04
+//! TX_ACK     gw → srv  ver | token | 0x05 | GatewayEUI | [JSON {"txpk_ack":{..}}]
+//! ```
+
+use super::single_channel::SingleChannelConfig;
+use crate::wmbus::radio::modulation::{
+    CodingRate, LoRaBandwidth, LoRaPacketStatus, SpreadingFactor,
+};
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Instant, SystemTime};
+use thiserror::Error;
+use tokio::net::UdpSocket;
+
+/// Protocol version written in and expected from every datagram.
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// Default UDP port of Semtech-protocol network servers.
+pub const DEFAULT_PORT: u16 = 1700;
+
+const PUSH_DATA: u8 = 0x00;
+const PUSH_ACK: u8 = 0x01;
+const PULL_DATA: u8 = 0x02;
+const PULL_RESP: u8 = 0x03;
+const PULL_ACK: u8 = 0x04;
+const TX_ACK: u8 = 0x05;
+
+/// Largest datagram the forwarder reference implementation sends.
+const MAX_DATAGRAM: usize = 65507;
+
+/// GWMP errors.
+#[derive(Error, Debug)]
+pub enum GwmpError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported protocol version {0}")]
+    Version(u8),
+    #[error("Unknown packet identifier {0:#04X}")]
+    UnknownIdentifier(u8),
+    #[error("Datagram too short: {0} bytes")]
+    Truncated(usize),
+    #[error("Invalid base64 payload")]
+    Base64,
+    #[error("Gateway {0:016X} has not sent PULL_DATA yet")]
+    UnknownGateway(u64),
+}
+
+/// One GWMP datagram.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GwmpPacket {
+    PushData {
+        token: u16,
+        gateway_eui: u64,
+        payload: PushPayload,
+    },
+    PushAck {
+        token: u16,
+    },
+    PullData {
+        token: u16,
+        gateway_eui: u64,
+    },
+    PullResp {
+        token: u16,
+        txpk: TxPk,
+    },
+    PullAck {
+        token: u16,
+    },
+    TxAck {
+        token: u16,
+        gateway_eui: u64,
+        /// `None` or `Some("NONE")` means the downlink was accepted.
+        error: Option<String>,
+    },
+}
+
+impl GwmpPacket {
+    pub fn token(&self) -> u16 {
+        match *self {
+            Self::PushData { token, .. }
+            | Self::PushAck { token }
+            | Self::PullData { token, .. }
+            | Self::PullResp { token, .. }
+            | Self::PullAck { token }
+            | Self::TxAck { token, .. } => token,
+        }
+    }
+
+    /// Serialize to a datagram.
+    pub fn encode(&self) -> Result<Vec<u8>, GwmpError> {
+        let header = |id: u8| {
+            let token = self.token().to_le_bytes();
+            vec![PROTOCOL_VERSION, token[0], token[1], id]
+        };
+        Ok(match self {
+            Self::PushData {
+                gateway_eui,
+                payload,
+                ..
+            } => {
+                let mut out = header(PUSH_DATA);
+                out.extend_from_slice(&gateway_eui.to_be_bytes());
+                out.extend(serde_json::to_vec(payload)?);
+                out
+            }
+            Self::PushAck { .. } => header(PUSH_ACK),
+            Self::PullData { gateway_eui, .. } => {
+                let mut out = header(PULL_DATA);
+                out.extend_from_slice(&gateway_eui.to_be_bytes());
+                out
+            }
+            Self::PullResp { txpk, .. } => {
+                let mut out = header(PULL_RESP);
+                out.extend(serde_json::to_vec(&PullRespPayload { txpk: txpk.clone() })?);
+                out
+            }
+            Self::PullAck { .. } => header(PULL_ACK),
+            Self::TxAck {
+                gateway_eui, error, ..
+            } => {
+                let mut out = header(TX_ACK);
+                out.extend_from_slice(&gateway_eui.to_be_bytes());
+                if let Some(error) = error {
+                    out.extend(serde_json::to_vec(&TxAckPayload {
+                        txpk_ack: TxPkAck {
+                            error: error.clone(),
+                        },
+                    })?);
+                }
+                out
+            }
+        })
+    }
+
+    /// Parse a datagram.
+    pub fn decode(data: &[u8]) -> Result<Self, GwmpError> {
+        if data.len() < 4 {
+            return Err(GwmpError::Truncated(data.len()));
+        }
+        if data[0] != PROTOCOL_VERSION {
+            return Err(GwmpError::Version(data[0]));
+        }
+        let token = u16::from_le_bytes([data[1], data[2]]);
+        let eui = || -> Result<u64, GwmpError> {
+            let bytes = data.get(4..12).ok_or(GwmpError::Truncated(data.len()))?;
+            Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+        };
+        Ok(match data[3] {
+            PUSH_DATA => Self::PushData {
+                token,
+                gateway_eui: eui()?,
+                payload: serde_json::from_slice(&data[12..])?,
+            },
+            PUSH_ACK => Self::PushAck { token },
+            PULL_DATA => Self::PullData {
+                token,
+                gateway_eui: eui()?,
+            },
+            PULL_RESP => Self::PullResp {
+                token,
+                txpk: serde_json::from_slice::<PullRespPayload>(&data[4..])?.txpk,
+            },
+            PULL_ACK => Self::PullAck { token },
+            TX_ACK => {
+                let gateway_eui = eui()?;
+                let json = &data[12..];
+                // Some forwarders pad the optional JSON with NULs or whitespace.
+                let error = if json.iter().all(|b| *b == 0 || b.is_ascii_whitespace()) {
+                    None
+                } else {
+                    Some(serde_json::from_slice::<TxAckPayload>(json)?.txpk_ack.error)
+                };
+                Self::TxAck {
+                    token,
+                    gateway_eui,
+                    error,
+                }
+            }
+            id => return Err(GwmpError::UnknownIdentifier(id)),
+        })
+    }
+}
+
+/// JSON body of PUSH_DATA.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PushPayload {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rxpk: Vec<RxPk>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stat: Option<Stat>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PullRespPayload {
+    txpk: TxPk,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TxAckPayload {
+    txpk_ack: TxPkAck,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TxPkAck {
+    error: String,
+}
+
+/// `datr` field: `"SF7BW125"` for LoRa, bits per second for FSK.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DataRate {
+    LoRa(String),
+    Fsk(u32),
+}
+
+impl DataRate {
+    pub fn lora(sf: SpreadingFactor, bw: LoRaBandwidth) -> Self {
+        Self::LoRa(format!("SF{}BW{}", sf as u8, bw.bandwidth_khz()))
+    }
+
+    /// Spreading factor and bandwidth of a LoRa data rate.
+    pub fn lora_params(&self) -> Option<(SpreadingFactor, LoRaBandwidth)> {
+        let Self::LoRa(datr) = self else {
+            return None;
+        };
+        let (sf, bw) = datr.strip_prefix("SF")?.split_once("BW")?;
+        let sf = match sf.parse::<u8>().ok()? {
+            5 => SpreadingFactor::SF5,
+            6 => SpreadingFactor::SF6,
+            7 => SpreadingFactor::SF7,
+            8 => SpreadingFactor::SF8,
+            9 => SpreadingFactor::SF9,
+            10 => SpreadingFactor::SF10,
+            11 => SpreadingFactor::SF11,
+            12 => SpreadingFactor::SF12,
+            _ => return None,
+        };
+        let bw = match bw {
+            "125" => LoRaBandwidth::BW125,
+            "250" => LoRaBandwidth::BW250,
+            "500" => LoRaBandwidth::BW500,
+            _ => return None,
+        };
+        Some((sf, bw))
+    }
+}
+
+/// `codr` string for a LoRa coding rate.
+pub fn coding_rate_str(cr: CodingRate) -> &'static str {
+    match cr {
+        CodingRate::CR4_5 => "4/5",
+        CodingRate::CR4_6 => "4/6",
+        CodingRate::CR4_7 => "4/7",
+        CodingRate::CR4_8 => "4/8",
+    }
+}
+
+/// A received packet as reported in PUSH_DATA.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RxPk {
+    /// UTC reception time, ISO 8601 compact format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    /// Gateway internal timestamp in µs, used to schedule Class A replies.
+    pub tmst: u32,
+    #[serde(default)]
+    pub chan: u8,
+    #[serde(default)]
+    pub rfch: u8,
+    /// Centre frequency in MHz.
+    pub freq: f64,
+    /// CRC status: 1 OK, -1 failed, 0 no CRC.
+    pub stat: i8,
+    pub modu: String,
+    pub datr: DataRate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codr: Option<String>,
+    pub rssi: i16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lsnr: Option<f32>,
+    pub size: u16,
+    /// Base64 PHYPayload.
+    pub data: String,
+}
+
+impl RxPk {
+    /// Describe a frame received by the single-channel gateway.
+    pub fn from_single_channel(
+        config: &SingleChannelConfig,
+        payload: &[u8],
+        status: &LoRaPacketStatus,
+        tmst: u32,
+        received_at: SystemTime,
+    ) -> Self {
+        Self {
+            time: Some(format_time(received_at)),
+            tmst,
+            chan: 0,
+            rfch: 0,
+            freq: config.frequency_hz as f64 / 1e6,
+            stat: 1,
+            modu: "LORA".to_string(),
+            datr: DataRate::lora(config.spreading_factor, config.bandwidth),
+            codr: Some(coding_rate_str(config.coding_rate).to_string()),
+            rssi: status.rssi_pkt_dbm,
+            lsnr: Some(status.snr_pkt_db),
+            size: payload.len() as u16,
+            data: BASE64.encode(payload),
+        }
+    }
+
+    /// Decoded PHYPayload.
+    pub fn payload(&self) -> Result<Vec<u8>, GwmpError> {
+        BASE64.decode(&self.data).map_err(|_| GwmpError::Base64)
+    }
+
+    /// Frequency in Hz.
+    pub fn frequency_hz(&self) -> u32 {
+        (self.freq * 1e6).round() as u32
+    }
+}
+
+/// A downlink to transmit, as sent in PULL_RESP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxPk {
+    /// Send immediately, ignoring `tmst`.
+    #[serde(default)]
+    pub imme: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmst: Option<u32>,
+    /// Centre frequency in MHz.
+    pub freq: f64,
+    #[serde(default)]
+    pub rfch: u8,
+    /// TX power in dBm.
+    pub powe: i8,
+    pub modu: String,
+    pub datr: DataRate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codr: Option<String>,
+    /// Inverted polarity, true for LoRaWAN downlinks.
+    #[serde(default)]
+    pub ipol: bool,
+    pub size: u16,
+    /// Base64 PHYPayload.
+    pub data: String,
+    /// Disable the physical CRC (LoRaWAN downlinks carry none).
+    #[serde(default)]
+    pub ncrc: bool,
+}
+
+impl TxPk {
+    /// A LoRaWAN downlink: inverted IQ, no CRC, sent at `tmst` or immediately.
+    pub fn lorawan(
+        payload: &[u8],
+        frequency_hz: u32,
+        sf: SpreadingFactor,
+        bw: LoRaBandwidth,
+        power_dbm: i8,
+        tmst: Option<u32>,
+    ) -> Self {
+        Self {
+            imme: tmst.is_none(),
+            tmst,
+            freq: frequency_hz as f64 / 1e6,
+            rfch: 0,
+            powe: power_dbm,
+            modu: "LORA".to_string(),
+            datr: DataRate::lora(sf, bw),
+            codr: Some("4/5".to_string()),
+            ipol: true,
+            size: payload.len() as u16,
+            data: BASE64.encode(payload),
+            ncrc: true,
+        }
+    }
+
+    pub fn payload(&self) -> Result<Vec<u8>, GwmpError> {
+        BASE64.decode(&self.data).map_err(|_| GwmpError::Base64)
+    }
+
+    pub fn frequency_hz(&self) -> u32 {
+        (self.freq * 1e6).round() as u32
+    }
+}
+
+/// Gateway status report.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stat {
+    /// UTC time, `"2014-01-12 08:59:28 GMT"`.
+    pub time: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lati: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alti: Option<i32>,
+    /// Packets received.
+    pub rxnb: u32,
+    /// Packets received with a valid CRC.
+    pub rxok: u32,
+    /// Packets forwarded.
+    pub rxfw: u32,
+    /// Percentage of upstream datagrams acknowledged.
+    pub ackr: f32,
+    /// Downlinks received.
+    pub dwnb: u32,
+    /// Packets transmitted.
+    pub txnb: u32,
+}
+
+/// Gateway-side settings.
+#[derive(Debug, Clone)]
+pub struct ForwarderConfig {
+    pub gateway_eui: u64,
+    /// Network server address, usually port [`DEFAULT_PORT`].
+    pub server: SocketAddr,
+    /// Optional fixed position reported in `stat`.
+    pub position: Option<(f64, f64, i32)>,
+}
+
+/// What the server sent back to the forwarder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForwarderEvent {
+    PushAck {
+        token: u16,
+    },
+    PullAck {
+        token: u16,
+    },
+    /// A downlink to transmit; answer with [`PacketForwarder::tx_ack`].
+    Downlink {
+        token: u16,
+        txpk: TxPk,
+    },
+}
+
+/// Gateway side of the protocol.
+pub struct PacketForwarder {
+    socket: UdpSocket,
+    config: ForwarderConfig,
+    token: u16,
+    started: Instant,
+    stat: Stat,
+    push_sent: u32,
+    push_acked: u32,
+}
+
+impl PacketForwarder {
+    /// Bind an ephemeral local port connected to the network server.
+    pub async fn connect(config: ForwarderConfig) -> Result<Self, GwmpError> {
+        let local: SocketAddr = if config.server.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(config.server).await?;
+        Ok(Self {
+            socket,
+            config,
+            token: rand_token(),
+            started: Instant::now(),
+            stat: Stat::default(),
+            push_sent: 0,
+            push_acked: 0,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, GwmpError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Concentrator-style µs counter (wraps after ~71 minutes).
+    pub fn tmst(&self) -> u32 {
+        self.started.elapsed().as_micros() as u32
+    }
+
+    fn next_token(&mut self) -> u16 {
+        self.token = self.token.wrapping_add(1);
+        self.token
+    }
+
+    /// Forward received packets. CRC failures are counted but not sent.
+    pub async fn push_rx(&mut self, rxpk: Vec<RxPk>) -> Result<u16, GwmpError> {
+        self.stat.rxnb += rxpk.len() as u32;
+        self.stat.rxok += rxpk.iter().filter(|p| p.stat != -1).count() as u32;
+        let rxpk: Vec<RxPk> = rxpk.into_iter().filter(|p| p.stat != -1).collect();
+        self.stat.rxfw += rxpk.len() as u32;
+        self.push(PushPayload { rxpk, stat: None }).await
+    }
+
+    /// Send a status report with the counters since the last one.
+    pub async fn push_stat(&mut self) -> Result<u16, GwmpError> {
+        let mut stat = std::mem::take(&mut self.stat);
+        stat.time = chrono::Utc::now()
+            .format("%Y-%m-%d %H:%M:%S GMT")
+            .to_string();
+        stat.ackr = if self.push_sent == 0 {
+            0.0
+        } else {
+            self.push_acked as f32 * 100.0 / self.push_sent as f32
+        };
+        if let Some((lat, long, alt)) = self.config.position {
+            stat.lati = Some(lat);
+            stat.long = Some(long);
+            stat.alti = Some(alt);
+        }
+        self.push_sent = 0;
+        self.push_acked = 0;
+        self.push(PushPayload {
+            rxpk: Vec::new(),
+            stat: Some(stat),
+        })
+        .await
+    }
+
+    async fn push(&mut self, payload: PushPayload) -> Result<u16, GwmpError> {
+        let token = self.next_token();
+        let packet = GwmpPacket::PushData {
+            token,
+            gateway_eui: self.config.gateway_eui,
+            payload,
+        };
+        self.socket.send(&packet.encode()?).await?;
+        self.push_sent += 1;
+        Ok(token)
+    }
+
+    /// Open (or keep alive) the downlink path; send every few seconds.
+    pub async fn pull_data(&mut self) -> Result<u16, GwmpError> {
+        let token = self.next_token();
+        let packet = GwmpPacket::PullData {
+            token,
+            gateway_eui: self.config.gateway_eui,
+        };
+        self.socket.send(&packet.encode()?).await?;
+        Ok(token)
+    }
+
+    /// Report the outcome of a downlink; `error` is e.g. `"TOO_LATE"`.
+    pub async fn tx_ack(&mut self, token: u16, error: Option<&str>) -> Result<(), GwmpError> {
+        if error.is_none() {
+            self.stat.txnb += 1;
+        }
+        let packet = GwmpPacket::TxAck {
+            token,
+            gateway_eui: self.config.gateway_eui,
+            error: Some(error.unwrap_or("NONE").to_string()),
+        };
+        self.socket.send(&packet.encode()?).await?;
+        Ok(())
+    }
+
+    /// Wait for the next datagram from the server.
+    pub async fn recv(&mut self) -> Result<ForwarderEvent, GwmpError> {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        loop {
+            let len = self.socket.recv(&mut buf).await?;
+            match GwmpPacket::decode(&buf[..len]) {
+                Ok(GwmpPacket::PushAck { token }) => {
+                    self.push_acked += 1;
+                    return Ok(ForwarderEvent::PushAck { token });
+                }
+                Ok(GwmpPacket::PullAck { token }) => return Ok(ForwarderEvent::PullAck { token }),
+                Ok(GwmpPacket::PullResp { token, txpk }) => {
+                    self.stat.dwnb += 1;
+                    return Ok(ForwarderEvent::Downlink { token, txpk });
+                }
+                Ok(other) => warn!("GWMP: unexpected {other:?} from server"),
+                Err(e) => warn!("GWMP: dropping datagram from server: {e}"),
+            }
+        }
+    }
+}
+
+/// What a forwarder sent to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    /// Received packets and/or a status report (already acknowledged).
+    PushData {
+        gateway_eui: u64,
+        payload: PushPayload,
+    },
+    /// Keep-alive; the gateway's downlink address is now known.
+    PullData { gateway_eui: u64 },
+    TxAck {
+        gateway_eui: u64,
+        token: u16,
+        error: Option<String>,
+    },
+}
+
+/// Server side of the protocol, for receiving from existing forwarders.
+pub struct GwmpServer {
+    socket: UdpSocket,
+    gateways: HashMap<u64, SocketAddr>,
+    token: u16,
+}
+
+impl GwmpServer {
+    pub async fn bind(addr: SocketAddr) -> Result<Self, GwmpError> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr).await?,
+            gateways: HashMap::new(),
+            token: rand_token(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, GwmpError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Downlink address of a gateway, learned from its last PULL_DATA.
+    pub fn gateway_addr(&self, gateway_eui: u64) -> Option<SocketAddr> {
+        self.gateways.get(&gateway_eui).copied()
+    }
+
+    /// Wait for the next datagram, acknowledging PUSH_DATA and PULL_DATA.
+    pub async fn recv(&mut self) -> Result<ServerEvent, GwmpError> {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        loop {
+            let (len, from) = self.socket.recv_from(&mut buf).await?;
+            match GwmpPacket::decode(&buf[..len]) {
+                Ok(GwmpPacket::PushData {
+                    token,
+                    gateway_eui,
+                    payload,
+                }) => {
+                    self.send_to(&GwmpPacket::PushAck { token }, from).await?;
+                    return Ok(ServerEvent::PushData {
+                        gateway_eui,
+                        payload,
+                    });
+                }
+                Ok(GwmpPacket::PullData { token, gateway_eui }) => {
+                    if self.gateways.insert(gateway_eui, from) != Some(from) {
+                        debug!("GWMP: gateway {gateway_eui:016X} pulling from {from}");
+                    }
+                    self.send_to(&GwmpPacket::PullAck { token }, from).await?;
+                    return Ok(ServerEvent::PullData { gateway_eui });
+                }
+                Ok(GwmpPacket::TxAck {
+                    token,
+                    gateway_eui,
+                    error,
+                }) => {
+                    return Ok(ServerEvent::TxAck {
+                        gateway_eui,
+                        token,
+                        error,
+                    })
+                }
+                Ok(other) => warn!("GWMP: unexpected {other:?} from {from}"),
+                Err(e) => warn!("GWMP: dropping datagram from {from}: {e}"),
+            }
+        }
+    }
+
+    /// Send a downlink to a gateway; returns the token its TX_ACK will carry.
+    pub async fn send_downlink(&mut self, gateway_eui: u64, txpk: TxPk) -> Result<u16, GwmpError> {
+        let addr = self
+            .gateway_addr(gateway_eui)
+            .ok_or(GwmpError::UnknownGateway(gateway_eui))?;
+        self.token = self.token.wrapping_add(1);
+        let token = self.token;
+        self.send_to(&GwmpPacket::PullResp { token, txpk }, addr)
+            .await?;
+        Ok(token)
+    }
+
+    async fn send_to(&self, packet: &GwmpPacket, addr: SocketAddr) -> Result<(), GwmpError> {
+        self.socket.send_to(&packet.encode()?, addr).await?;
+        Ok(())
+    }
+}
+
+fn rand_token() -> u16 {
+    use rand::RngExt;
+    rand::rng().random()
+}
+
+/// ISO 8601 UTC with microseconds, as used in `rxpk.time`.
+fn format_time(t: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+/// Standard-alphabet base64 for `rxpk.data` and `txpk.data`; padding is optional
+/// when decoding.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[test]
+    fn base64_round_trip() {
+        for data in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"] {
+            let encoded = BASE64.encode(data);
+            assert_eq!(BASE64.decode(&encoded).unwrap(), data);
+        }
+        assert_eq!(BASE64.encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(BASE64.encode(b"fo"), "Zm8=");
+        assert_eq!(BASE64.decode("Zm8").unwrap(), b"fo");
+        assert!(BASE64.decode("Zm9v!").is_err());
+    }
+
+    #[test]
+    fn decodes_reference_push_data() {
+        // Example upstream datagram from the packet forwarder protocol document.
+        let json = br#"{"rxpk":[{"time":"2013-03-31T16:21:17.528002Z","tmst":3512348611,"chan":2,"rfch":0,"freq":866.349812,"stat":1,"modu":"LORA","datr":"SF7BW125","codr":"4/6","rssi":-35,"lsnr":5.1,"size":32,"data":"-DS4CGaDCdG+48eJNM3Vai-zDpsR71Pn9CPA9uCON84"}]}"#;
+        let mut datagram = vec![2, 0x34, 0x12, 0x00, 0xAA, 0x55, 0x5A, 0, 0, 0, 0x01, 0x01];
+        datagram.extend_from_slice(json);
+        let GwmpPacket::PushData {
+            token,
+            gateway_eui,
+            payload,
+        } = GwmpPacket::decode(&datagram).unwrap()
+        else {
+            panic!("not PUSH_DATA");
+        };
+        assert_eq!(token, 0x1234);
+        assert_eq!(gateway_eui, 0xAA55_5A00_0000_0101);
+        let rx = &payload.rxpk[0];
+        assert_eq!(rx.tmst, 3_512_348_611);
+        assert_eq!(rx.frequency_hz(), 866_349_812);
+        assert_eq!(
+            rx.datr.lora_params(),
+            Some((SpreadingFactor::SF7, LoRaBandwidth::BW125))
+        );
+        assert_eq!(rx.rssi, -35);
+        assert!(payload.stat.is_none());
+
+        assert!(matches!(
+            GwmpPacket::decode(&[1, 0, 0, 0]),
+            Err(GwmpError::Version(1))
+        ));
+        assert!(matches!(
+            GwmpPacket::decode(&[2, 0, 0, 0x09]),
+            Err(GwmpError::UnknownIdentifier(0x09))
+        ));
+    }
+
+    #[tokio::test]
+    async fn forwarder_and_server_exchange_over_udp() {
+        let step = Duration::from_secs(2);
+        let mut server = GwmpServer::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let mut gw = PacketForwarder::connect(ForwarderConfig {
+            gateway_eui: 0x0102_0304_0506_0708,
+            server: server.local_addr().unwrap(),
+            position: Some((52.52, 13.40, 34)),
+        })
+        .await
+        .unwrap();
+
+        // Uplink from the single-channel gateway.
+        let config = SingleChannelConfig::eu868_channel_1();
+        let status = LoRaPacketStatus {
+            rssi_pkt_dbm: -97,
+            snr_pkt_db: 6.25,
+            signal_rssi_pkt_dbm: -98,
+        };
+        let frame = hex::decode("40f17dbe4900020001954378762b11ff0d").unwrap();
+        let rx = RxPk::from_single_channel(&config, &frame, &status, gw.tmst(), SystemTime::now());
+        let token = gw.push_rx(vec![rx]).await.unwrap();
+
+        let ServerEvent::PushData {
+            gateway_eui,
+            payload,
+        } = timeout(step, server.recv()).await.unwrap().unwrap()
+        else {
+            panic!("expected PUSH_DATA");
+        };
+        assert_eq!(gateway_eui, 0x0102_0304_0506_0708);
+        let rx = &payload.rxpk[0];
+        assert_eq!(rx.payload().unwrap(), frame);
+        assert_eq!(rx.frequency_hz(), 868_100_000);
+        assert_eq!(rx.datr, DataRate::LoRa("SF7BW125".to_string()));
+        assert_eq!(rx.codr.as_deref(), Some("4/5"));
+        assert_eq!((rx.rssi, rx.lsnr), (-97, Some(6.25)));
+        assert_eq!(
+            timeout(step, gw.recv()).await.unwrap().unwrap(),
+            ForwarderEvent::PushAck { token }
+        );
+
+        // Downlinks need the gateway's PULL_DATA first.
+        let txpk = TxPk::lorawan(
+            &[0x60, 1, 2, 3],
+            869_525_000,
+            SpreadingFactor::SF9,
+            LoRaBandwidth::BW125,
+            14,
+            Some(1_000_000),
+        );
+        assert!(matches!(
+            server.send_downlink(gateway_eui, txpk.clone()).await,
+            Err(GwmpError::UnknownGateway(_))
+        ));
+        let token = gw.pull_data().await.unwrap();
+        assert_eq!(
+            timeout(step, server.recv()).await.unwrap().unwrap(),
+            ServerEvent::PullData { gateway_eui }
+        );
+        assert_eq!(
+            timeout(step, gw.recv()).await.unwrap().unwrap(),
+            ForwarderEvent::PullAck { token }
+        );
+
+        let token = server
+            .send_downlink(gateway_eui, txpk.clone())
+            .await
+            .unwrap();
+        let ForwarderEvent::Downlink {
+            token: got,
+            txpk: got_txpk,
+        } = timeout(step, gw.recv()).await.unwrap().unwrap()
+        else {
+            panic!("expected PULL_RESP");
+        };
+        assert_eq!((got, &got_txpk), (token, &txpk));
+        assert_eq!(got_txpk.payload().unwrap(), [0x60, 1, 2, 3]);
+        assert!(got_txpk.ipol && !got_txpk.imme);
+
+        gw.tx_ack(token, None).await.unwrap();
+        assert_eq!(
+            timeout(step, server.recv()).await.unwrap().unwrap(),
+            ServerEvent::TxAck {
+                gateway_eui,
+                token,
+                error: Some("NONE".to_string())
+            }
+        );
+
+        // Status report carries the counters and position.
+        gw.push_stat().await.unwrap();
+        let ServerEvent::PushData { payload, .. } =
+            timeout(step, server.recv()).await.unwrap().unwrap()
+        else {
+            panic!("expected stat");
+        };
+        let stat = payload.stat.unwrap();
+        assert_eq!((stat.rxnb, stat.rxok, stat.rxfw), (1, 1, 1));
+        assert_eq!((stat.dwnb, stat.txnb), (1, 1));
+        assert_eq!(stat.ackr, 100.0);
+        assert_eq!(stat.alti, Some(34));
+        assert!(stat.time.ends_with(" GMT"));
+    }
+}
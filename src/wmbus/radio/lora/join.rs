@@ -0,0 +1,552 @@
+//! Minimal LoRaWAN 1.0.x join server
+//!
+//! Lets OTAA devices join a private gateway that has no network server. The
+//! gateway validates the JoinRequest against the device's AppKey, hands out a
+//! DevAddr, answers with an encrypted JoinAccept and derives the session keys
+//! that [`SessionStore`] then uses to decode the device's uplinks.
+//!
+//! Accepted DevNonces are kept in a [`DevNonceStore`] so a replayed JoinRequest is
+//! refused. The default [`MemoryDevNonceStore`] forgets them on restart, which
+//! reopens replay of every earlier request; gateways that restart should pass a
+//! persistent store to [`JoinServer::with_nonce_store`]. Likewise, after restoring
+//! the [`SessionStore`] call [`JoinServer::resume_from`] so new joins are not handed a
+//! DevAddr a restored session still uses.
+//!
+//! ```text
+//! JoinRequest = MHDR(0x00) | AppEUI(8) | DevEUI(8) | DevNonce(2) | MIC(4)
+//! JoinAccept  = MHDR(0x20) | AppNonce(3) | NetID(3) | DevAddr(4) | DLSettings(1) | RxDelay(1) | [CFList(16)] | MIC(4)
+//! ```
+
+use super::lorawan::{
+    DeviceSession, JoinRequestFrame, MType, SessionKeys, SessionStore, JOIN_REQUEST_LEN, MIC_LEN,
+};
+use super::packet::LoRaError;
+use crate::wmbus::crypto_hardware::get_aes_backend;
+use std::collections::{HashMap, HashSet};
+
+/// Number of NwkAddr bits below the 7-bit NwkID in a DevAddr.
+const NWK_ADDR_BITS: u32 = 25;
+
+/// OTAA credentials provisioned for one device.
+#[derive(Clone, PartialEq, Eq)]
+pub struct DeviceCredentials {
+    pub dev_eui: u64,
+    pub app_eui: u64,
+    pub app_key: [u8; 16],
+}
+
+impl std::fmt::Debug for DeviceCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceCredentials")
+            .field("dev_eui", &format_args!("{:016X}", self.dev_eui))
+            .field("app_eui", &format_args!("{:016X}", self.app_eui))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Network parameters announced in every JoinAccept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinServerConfig {
+    /// 24-bit NetID; its low 7 bits become the NwkID prefix of every DevAddr.
+    pub net_id: u32,
+    /// RX1 data rate offset (DLSettings bits 6..4).
+    pub rx1_dr_offset: u8,
+    /// RX2 data rate (DLSettings bits 3..0).
+    pub rx2_data_rate: u8,
+    /// RX1 delay in seconds; 0 is treated as 1 by devices.
+    pub rx_delay: u8,
+    /// Optional region-specific channel list appended to the JoinAccept.
+    pub cf_list: Option<[u8; 16]>,
+}
+
+impl Default for JoinServerConfig {
+    fn default() -> Self {
+        Self {
+            net_id: 0x000000, // Experimental / private network
+            rx1_dr_offset: 0,
+            rx2_data_rate: 0, // EU868 RX2: SF12/125 kHz
+            rx_delay: 1,
+            cf_list: None,
+        }
+    }
+}
+
+impl JoinServerConfig {
+    fn dl_settings(&self) -> u8 {
+        ((self.rx1_dr_offset & 0x07) << 4) | (self.rx2_data_rate & 0x0F)
+    }
+}
+
+/// Result of a successful join.
+#[derive(Debug, Clone)]
+pub struct JoinAccept {
+    /// Encrypted JoinAccept PHYPayload, ready to transmit in RX1/RX2.
+    pub phy: Vec<u8>,
+    pub dev_eui: u64,
+    pub dev_addr: u32,
+    pub app_nonce: u32,
+    /// Session stored for the device; counters start from zero.
+    pub session: DeviceSession,
+}
+
+/// DevNonces accepted per device, for JoinRequest replay protection.
+pub trait DevNonceStore: Send {
+    /// Record `dev_nonce` for `dev_eui`; false if it was already used.
+    fn insert(&mut self, dev_eui: u64, dev_nonce: u16) -> bool;
+
+    /// Forget a nonce recorded for a join that could not complete.
+    fn remove(&mut self, dev_eui: u64, dev_nonce: u16);
+
+    /// Whether `dev_nonce` was already accepted from `dev_eui`.
+    fn contains(&self, dev_eui: u64, dev_nonce: u16) -> bool;
+}
+
+/// In-memory [`DevNonceStore`]; its history is lost when the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryDevNonceStore {
+    nonces: HashMap<u64, HashSet<u16>>,
+}
+
+impl DevNonceStore for MemoryDevNonceStore {
+    fn insert(&mut self, dev_eui: u64, dev_nonce: u16) -> bool {
+        self.nonces.entry(dev_eui).or_default().insert(dev_nonce)
+    }
+
+    fn remove(&mut self, dev_eui: u64, dev_nonce: u16) {
+        if let Some(nonces) = self.nonces.get_mut(&dev_eui) {
+            nonces.remove(&dev_nonce);
+        }
+    }
+
+    fn contains(&self, dev_eui: u64, dev_nonce: u16) -> bool {
+        self.nonces
+            .get(&dev_eui)
+            .is_some_and(|nonces| nonces.contains(&dev_nonce))
+    }
+}
+
+struct JoinDevice {
+    credentials: DeviceCredentials,
+    dev_addr: Option<u32>,
+}
+
+/// OTAA join handling for a set of provisioned devices.
+pub struct JoinServer {
+    config: JoinServerConfig,
+    devices: HashMap<u64, JoinDevice>,
+    nonces: Box<dyn DevNonceStore>,
+    next_nwk_addr: u32,
+    app_nonce: u32,
+}
+
+impl JoinServer {
+    /// Join server keeping DevNonces in memory only (see the module docs).
+    pub fn new(config: JoinServerConfig) -> Self {
+        Self::with_nonce_store(config, MemoryDevNonceStore::default())
+    }
+
+    /// Join server recording accepted DevNonces in `nonces`.
+    pub fn with_nonce_store(
+        config: JoinServerConfig,
+        nonces: impl DevNonceStore + 'static,
+    ) -> Self {
+        use rand::RngExt;
+        Self {
+            config,
+            devices: HashMap::new(),
+            nonces: Box::new(nonces),
+            next_nwk_addr: 1,
+            // Start somewhere random so a restarted gateway does not replay nonces.
+            app_nonce: rand::rng().random_range(0..0x100_0000),
+        }
+    }
+
+    pub fn config(&self) -> &JoinServerConfig {
+        &self.config
+    }
+
+    /// Continue DevAddr allocation after the highest NwkAddr `sessions` holds under
+    /// this network's NwkID. Addresses of other networks are ignored.
+    pub fn resume_from(&mut self, sessions: &SessionStore) {
+        let nwk_id = self.config.net_id & 0x7F;
+        let highest = sessions
+            .dev_addrs()
+            .filter(|addr| addr >> NWK_ADDR_BITS == nwk_id)
+            .map(|addr| addr & ((1 << NWK_ADDR_BITS) - 1))
+            .max();
+        if let Some(highest) = highest {
+            self.next_nwk_addr = self.next_nwk_addr.max(highest + 1);
+        }
+    }
+
+    /// Provision (or re-provision) a device. Its DevNonce history is kept.
+    pub fn add_device(&mut self, credentials: DeviceCredentials) {
+        let dev_eui = credentials.dev_eui;
+        match self.devices.get_mut(&dev_eui) {
+            Some(device) => device.credentials = credentials,
+            None => {
+                self.devices.insert(
+                    dev_eui,
+                    JoinDevice {
+                        credentials,
+                        dev_addr: None,
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn remove_device(&mut self, dev_eui: u64) -> bool {
+        self.devices.remove(&dev_eui).is_some()
+    }
+
+    /// Whether `dev_nonce` was already accepted from a device.
+    pub fn is_nonce_used(&self, dev_eui: u64, dev_nonce: u16) -> bool {
+        self.nonces.contains(dev_eui, dev_nonce)
+    }
+
+    /// Validate a JoinRequest, and on success store the new session in
+    /// `sessions` and return the JoinAccept to send back.
+    ///
+    /// A device that joins again keeps its DevAddr; its old session is replaced.
+    pub fn handle_join_request(
+        &mut self,
+        phy: &[u8],
+        sessions: &mut SessionStore,
+    ) -> Result<JoinAccept, LoRaError> {
+        let request = JoinRequestFrame::parse(phy)?;
+        let device = self
+            .devices
+            .get_mut(&request.dev_eui)
+            .ok_or(LoRaError::DeviceNotFound)?;
+        if device.credentials.app_eui != request.app_eui {
+            return Err(LoRaError::DeviceNotFound);
+        }
+
+        let app_key = device.credentials.app_key;
+        if aes_cmac4(&app_key, &phy[..JOIN_REQUEST_LEN - MIC_LEN])? != request.mic {
+            return Err(LoRaError::JoinMicMismatch {
+                dev_eui: request.dev_eui,
+            });
+        }
+        if !self.nonces.insert(request.dev_eui, request.dev_nonce) {
+            return Err(LoRaError::DevNonceReused {
+                dev_eui: request.dev_eui,
+                dev_nonce: request.dev_nonce,
+            });
+        }
+
+        let dev_addr = match device.dev_addr {
+            Some(addr) => addr,
+            None => {
+                if self.next_nwk_addr >= 1 << NWK_ADDR_BITS {
+                    self.nonces.remove(request.dev_eui, request.dev_nonce);
+                    return Err(LoRaError::DevAddrExhausted);
+                }
+                let addr = ((self.config.net_id & 0x7F) << NWK_ADDR_BITS) | self.next_nwk_addr;
+                self.next_nwk_addr += 1;
+                device.dev_addr = Some(addr);
+                addr
+            }
+        };
+
+        let app_nonce = self.app_nonce;
+        self.app_nonce = (self.app_nonce + 1) & 0xFF_FFFF;
+
+        let net_id = self.config.net_id & 0xFF_FFFF;
+        let keys = derive_session_keys(&app_key, app_nonce, net_id, request.dev_nonce);
+        let phy = self.build_join_accept(&app_key, app_nonce, dev_addr)?;
+
+        let session = DeviceSession::new(dev_addr, keys);
+        sessions.insert(session.clone());
+        log::info!(
+            "OTAA join: DevEUI {:016X} -> DevAddr {:08X}",
+            request.dev_eui,
+            dev_addr
+        );
+
+        Ok(JoinAccept {
+            phy,
+            dev_eui: request.dev_eui,
+            dev_addr,
+            app_nonce,
+            session,
+        })
+    }
+
+    fn build_join_accept(
+        &self,
+        app_key: &[u8; 16],
+        app_nonce: u32,
+        dev_addr: u32,
+    ) -> Result<Vec<u8>, LoRaError> {
+        let mut plain = vec![MType::JoinAccept.mhdr()];
+        plain.extend_from_slice(&app_nonce.to_le_bytes()[..3]);
+        plain.extend_from_slice(&self.config.net_id.to_le_bytes()[..3]);
+        plain.extend_from_slice(&dev_addr.to_le_bytes());
+        plain.push(self.config.dl_settings());
+        plain.push(self.config.rx_delay);
+        if let Some(cf_list) = &self.config.cf_list {
+            plain.extend_from_slice(cf_list);
+        }
+        let mic = aes_cmac4(app_key, &plain)?;
+        plain.extend_from_slice(&mic);
+
+        // The network encrypts with AES *decrypt* so the device only needs the
+        // encrypt primitive to recover the fields.
+        let backend = get_aes_backend();
+        let mut phy = vec![plain[0]];
+        let mut out = [0u8; 16];
+        for chunk in plain[1..].chunks_exact(16) {
+            backend.decrypt_block(chunk.try_into().unwrap(), app_key, &mut out);
+            phy.extend_from_slice(&out);
+        }
+        Ok(phy)
+    }
+}
+
+/// Derive NwkSKey and AppSKey:
+/// `AES(AppKey, 0x01|0x02 | AppNonce | NetID | DevNonce | pad)`.
+pub fn derive_session_keys(
+    app_key: &[u8; 16],
+    app_nonce: u32,
+    net_id: u32,
+    dev_nonce: u16,
+) -> SessionKeys {
+    let backend = get_aes_backend();
+    let derive = |prefix: u8| {
+        let mut block = [0u8; 16];
+        block[0] = prefix;
+        block[1..4].copy_from_slice(&app_nonce.to_le_bytes()[..3]);
+        block[4..7].copy_from_slice(&net_id.to_le_bytes()[..3]);
+        block[7..9].copy_from_slice(&dev_nonce.to_le_bytes());
+        let mut key = [0u8; 16];
+        backend.encrypt_block(&block, app_key, &mut key);
+        key
+    };
+    SessionKeys {
+        nwk_s_key: derive(0x01),
+        app_s_key: derive(0x02),
+    }
+}
+
+/// First four bytes of AES-CMAC, as used for the join MICs.
+fn aes_cmac4(key: &[u8; 16], msg: &[u8]) -> Result<[u8; MIC_LEN], LoRaError> {
+    use aes::Aes128;
+    use cmac::{Cmac, Mac};
+
+    let mut mac = Cmac::<Aes128>::new_from_slice(key)
+        .map_err(|_| LoRaError::Parse("invalid AppKey".to_string()))?;
+    mac.update(msg);
+    let tag = mac.finalize().into_bytes();
+    Ok(tag[..MIC_LEN].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::crypto::{AesKey, WMBusCrypto};
+    use crate::wmbus::radio::lora::lorawan::{frm_payload_crypt, Direction};
+
+    const APP_KEY: [u8; 16] = [
+        0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F,
+        0x3C,
+    ];
+    const DEV_EUI: u64 = 0x0004_A30B_001C_0530;
+    const APP_EUI: u64 = 0x70B3_D57E_D000_0001;
+
+    fn server() -> JoinServer {
+        let mut server = JoinServer::new(JoinServerConfig {
+            net_id: 0x000013,
+            rx1_dr_offset: 1,
+            rx2_data_rate: 3,
+            ..Default::default()
+        });
+        server.add_device(DeviceCredentials {
+            dev_eui: DEV_EUI,
+            app_eui: APP_EUI,
+            app_key: APP_KEY,
+        });
+        server
+    }
+
+    fn join_request(app_key: &[u8; 16], dev_nonce: u16) -> Vec<u8> {
+        let mut phy = vec![0x00];
+        phy.extend_from_slice(&APP_EUI.to_le_bytes());
+        phy.extend_from_slice(&DEV_EUI.to_le_bytes());
+        phy.extend_from_slice(&dev_nonce.to_le_bytes());
+        let mic = aes_cmac4(app_key, &phy).unwrap();
+        phy.extend_from_slice(&mic);
+        phy
+    }
+
+    /// What the device does: AES-encrypt the accept, check the MIC, read fields.
+    fn device_open_accept(phy: &[u8]) -> Vec<u8> {
+        let backend = get_aes_backend();
+        let mut plain = vec![phy[0]];
+        let mut out = [0u8; 16];
+        for chunk in phy[1..].chunks_exact(16) {
+            backend.encrypt_block(chunk.try_into().unwrap(), &APP_KEY, &mut out);
+            plain.extend_from_slice(&out);
+        }
+        let (body, mic) = plain.split_at(plain.len() - MIC_LEN);
+        assert_eq!(aes_cmac4(&APP_KEY, body).unwrap(), mic);
+        plain
+    }
+
+    #[test]
+    fn join_accept_carries_settings_and_matching_keys() {
+        let mut server = server();
+        let mut sessions = SessionStore::new();
+        let accept = server
+            .handle_join_request(&join_request(&APP_KEY, 0x1234), &mut sessions)
+            .unwrap();
+
+        assert_eq!(accept.phy.len(), 17);
+        assert_eq!(accept.phy[0], 0x20);
+        let plain = device_open_accept(&accept.phy);
+        let app_nonce = u32::from_le_bytes([plain[1], plain[2], plain[3], 0]);
+        assert_eq!(app_nonce, accept.app_nonce);
+        assert_eq!(&plain[4..7], &[0x13, 0x00, 0x00]);
+        let dev_addr = u32::from_le_bytes(plain[7..11].try_into().unwrap());
+        assert_eq!(dev_addr, accept.dev_addr);
+        assert_eq!(dev_addr >> 25, 0x13);
+        assert_eq!(plain[11], 0x13); // RX1DROffset 1, RX2 DR3
+        assert_eq!(plain[12], 1);
+
+        // The device derives the same keys and its uplinks decode.
+        let keys = derive_session_keys(&APP_KEY, app_nonce, 0x13, 0x1234);
+        assert_eq!(accept.session.keys, keys);
+        let mut up = vec![0x40];
+        up.extend_from_slice(&dev_addr.to_le_bytes());
+        up.extend_from_slice(&[0x00, 0x00, 0x00, 0x02]);
+        up.extend(frm_payload_crypt(
+            &keys.app_s_key,
+            Direction::Uplink,
+            dev_addr,
+            0,
+            b"hi",
+        ));
+        let mic = WMBusCrypto::new(AesKey::from_bytes(&[0u8; 16]).unwrap())
+            .calculate_lorawan_mic(&keys.nwk_s_key, &up, 0, dev_addr, 0)
+            .unwrap();
+        up.extend_from_slice(&mic);
+        assert_eq!(sessions.decode_uplink(&up).unwrap().payload, b"hi");
+    }
+
+    #[test]
+    fn matches_the_lora_packet_join_vectors() {
+        // JoinRequest and JoinAccept from the lora-packet (npm) examples; both MICs
+        // check against this AppKey, and the CFList lists 867.1..867.9 MHz.
+        let app_key: [u8; 16] = hex::decode("B6B53F4A168A7A88BDF7EA135CE9CFCA")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let request = hex::decode("00DC0000D07ED5B3701E6FEDF57CEEAF0085CC587FE913").unwrap();
+        let expected =
+            hex::decode("204DD85AE608B87FC4889970B7D2042C9E72959B0057AED6094B16003DF12DE145")
+                .unwrap();
+
+        let mut server = JoinServer::new(JoinServerConfig {
+            net_id: 0x000013,
+            rx1_dr_offset: 0,
+            rx2_data_rate: 3,
+            rx_delay: 1,
+            cf_list: Some(
+                hex::decode("184F84E85684B85E84886684586E8400")
+                    .unwrap()
+                    .try_into()
+                    .unwrap(),
+            ),
+        });
+        server.add_device(DeviceCredentials {
+            dev_eui: 0x00AF_EE7C_F5ED_6F1E,
+            app_eui: 0x70B3_D57E_D000_00DC,
+            app_key,
+        });
+        server.app_nonce = 0xE5063A;
+        server.next_nwk_addr = 0x01_2E43;
+
+        let accept = server
+            .handle_join_request(&request, &mut SessionStore::new())
+            .unwrap();
+        assert_eq!(accept.dev_addr, 0x2601_2E43);
+        assert_eq!(accept.phy, expected);
+        assert!(server.is_nonce_used(0x00AF_EE7C_F5ED_6F1E, 0xCC85));
+
+        // AES(AppKey, 0x01|0x02 | AppNonce | NetID | DevNonce | pad).
+        let keys = &accept.session.keys;
+        assert_eq!(
+            hex::encode(keys.nwk_s_key),
+            "2c96f7028184bb0be8aa49275290d4fc"
+        );
+        assert_eq!(
+            hex::encode(keys.app_s_key),
+            "f3a5c8f0232a38c144029c165865802c"
+        );
+    }
+
+    #[test]
+    fn cf_list_extends_the_accept_to_two_blocks() {
+        let mut server = server();
+        server.config.cf_list = Some([0xAA; 16]);
+        let accept = server
+            .handle_join_request(&join_request(&APP_KEY, 1), &mut SessionStore::new())
+            .unwrap();
+        assert_eq!(accept.phy.len(), 33);
+        assert_eq!(&device_open_accept(&accept.phy)[13..29], &[0xAA; 16]);
+    }
+
+    #[test]
+    fn restarted_server_skips_addresses_of_restored_sessions() {
+        let mut sessions = SessionStore::new();
+        let first = server()
+            .handle_join_request(&join_request(&APP_KEY, 1), &mut sessions)
+            .unwrap();
+        // A session of another NwkID does not move the allocation.
+        let foreign = DeviceSession::new((0x14 << NWK_ADDR_BITS) | 0x1_0000, first.session.keys);
+        sessions.insert(foreign);
+
+        let mut restarted = server();
+        restarted.resume_from(&sessions);
+        let second = restarted
+            .handle_join_request(&join_request(&APP_KEY, 2), &mut sessions)
+            .unwrap();
+        assert_eq!(second.dev_addr, first.dev_addr + 1);
+        assert_eq!(sessions.len(), 3);
+    }
+
+    #[test]
+    fn rejects_bad_mic_reused_nonce_and_unknown_device() {
+        let mut server = server();
+        let mut sessions = SessionStore::new();
+
+        assert!(matches!(
+            server.handle_join_request(&join_request(&[0u8; 16], 7), &mut sessions),
+            Err(LoRaError::JoinMicMismatch { dev_eui: DEV_EUI })
+        ));
+        // A forged request must not burn the nonce.
+        let first = server
+            .handle_join_request(&join_request(&APP_KEY, 7), &mut sessions)
+            .unwrap();
+        assert!(matches!(
+            server.handle_join_request(&join_request(&APP_KEY, 7), &mut sessions),
+            Err(LoRaError::DevNonceReused { dev_nonce: 7, .. })
+        ));
+
+        // Rejoining keeps the address but rotates keys.
+        let second = server
+            .handle_join_request(&join_request(&APP_KEY, 8), &mut sessions)
+            .unwrap();
+        assert_eq!(first.dev_addr, second.dev_addr);
+        assert_ne!(first.session.keys, second.session.keys);
+        assert_eq!(sessions.len(), 1);
+
+        server.remove_device(DEV_EUI);
+        assert!(matches!(
+            server.handle_join_request(&join_request(&APP_KEY, 9), &mut sessions),
+            Err(LoRaError::DeviceNotFound)
+        ));
+        assert!(JoinRequestFrame::parse(&[0x00; 22]).is_err());
+    }
+}
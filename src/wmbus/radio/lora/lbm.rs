@@ -3,16 +3,256 @@
 //! Implements pub-sub topics and QoS for gateway-level relaying,
 //! enabling mesh networking for distant devices (e.g., Kamstrup meters).
 //! Inspired by LoRaWAN Private Network Server (LNS) concepts.
+//!
+//! With a [`MeshTransport`] attached, messages go on air as compact frames.
+//! Node addresses and topics travel as 32-bit and 16-bit FNV-1a hashes; an
+//! address of exactly eight hex digits is used as its id verbatim.
+//!
+//! ```text
+//! Frame = Ctrl(1) | Source(4) | Destination(4) | Relay(4) | NextHop(4) | TTL(1) | Hops(1) | Seq(2) | Topic(2) | Payload
+//! Ctrl  = Version(2) | Kind(2) | RFU(2) | QoS(2)
+//! ```
+//!
+//! Gateways relay frames hop by hop: each relay suppresses duplicates by
+//! (source, sequence), decrements the TTL and picks the next hop from a route
+//! table learned from the frames it hears. Unknown destinations are flooded.
 
+use crate::wmbus::radio::radio_driver::{RadioDriver, ReceivedPacket};
+use async_trait::async_trait;
 use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use thiserror::Error;
 use tokio::sync::{mpsc, Mutex, RwLock};
 
+/// Mesh frame format version
+const FRAME_VERSION: u8 = 0;
+
+/// Bytes before the payload of a mesh frame
+pub const FRAME_HEADER_LEN: usize = 23;
+
+/// Destination / next-hop id addressing every node in range
+pub const BROADCAST_ID: u32 = 0xFFFF_FFFF;
+
+/// Routes not refreshed for this long are replaced by any new one
+const ROUTE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Mesh frame codec errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MeshFrameError {
+    #[error("Mesh frame needs at least {FRAME_HEADER_LEN} bytes, got {len}")]
+    Truncated { len: usize },
+    #[error("Unsupported mesh frame version {0}")]
+    Version(u8),
+    #[error("Unknown mesh frame kind {0}")]
+    Kind(u8),
+}
+
+/// 32-bit FNV-1a hash
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811C_9DC5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// On-air id of a node address
+pub fn node_id(address: &str) -> u32 {
+    if address.len() == 8 {
+        if let Ok(id) = u32::from_str_radix(address, 16) {
+            return id;
+        }
+    }
+    fnv1a(address.as_bytes())
+}
+
+/// On-air hash of a topic
+pub fn topic_hash(topic: &str) -> u16 {
+    let hash = fnv1a(topic.as_bytes());
+    (hash ^ (hash >> 16)) as u16
+}
+
+/// What a mesh frame carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// Application message
+    Data = 0,
+    /// Acknowledges a QoS 1/2 message; the payload is its sequence number
+    Ack = 1,
+    /// Topic subscription announcement; the payload is the topic name
+    Subscribe = 2,
+}
+
+/// Over-the-air mesh frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshFrame {
+    pub kind: FrameKind,
+    pub qos: QoS,
+    /// Originating node
+    pub source: u32,
+    /// Final destination, or [`BROADCAST_ID`] for topics and announcements
+    pub destination: u32,
+    /// Node that transmitted this copy
+    pub relay: u32,
+    /// Node expected to relay it further, or [`BROADCAST_ID`] to flood
+    pub next_hop: u32,
+    /// Remaining relays allowed
+    pub ttl: u8,
+    /// Relays so far
+    pub hops: u8,
+    pub seq: u16,
+    pub topic: u16,
+    pub payload: Vec<u8>,
+}
+
+impl MeshFrame {
+    /// Message id shared by every copy of this frame
+    pub fn message_id(&self) -> u64 {
+        message_id(self.source, self.seq)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(FRAME_HEADER_LEN + self.payload.len());
+        out.push(FRAME_VERSION << 6 | (self.kind as u8) << 4 | self.qos as u8);
+        for id in [self.source, self.destination, self.relay, self.next_hop] {
+            out.extend_from_slice(&id.to_le_bytes());
+        }
+        out.push(self.ttl);
+        out.push(self.hops);
+        out.extend_from_slice(&self.seq.to_le_bytes());
+        out.extend_from_slice(&self.topic.to_le_bytes());
+        out.extend_from_slice(&self.payload);
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, MeshFrameError> {
+        if data.len() < FRAME_HEADER_LEN {
+            return Err(MeshFrameError::Truncated { len: data.len() });
+        }
+        let ctrl = data[0];
+        if ctrl >> 6 != FRAME_VERSION {
+            return Err(MeshFrameError::Version(ctrl >> 6));
+        }
+        let kind = match (ctrl >> 4) & 0x03 {
+            0 => FrameKind::Data,
+            1 => FrameKind::Ack,
+            2 => FrameKind::Subscribe,
+            other => return Err(MeshFrameError::Kind(other)),
+        };
+        let qos = match ctrl & 0x03 {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            _ => QoS::ExactlyOnce,
+        };
+        let id = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+
+        Ok(Self {
+            kind,
+            qos,
+            source: id(1),
+            destination: id(5),
+            relay: id(9),
+            next_hop: id(13),
+            ttl: data[17],
+            hops: data[18],
+            seq: u16::from_le_bytes([data[19], data[20]]),
+            topic: u16::from_le_bytes([data[21], data[22]]),
+            payload: data[FRAME_HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+/// Message id of `source`'s frame `seq`
+fn message_id(source: u32, seq: u16) -> u64 {
+    (source as u64) << 16 | seq as u64
+}
+
+/// Radio link carrying mesh frames
+#[async_trait]
+pub trait MeshTransport: Send + Sync {
+    /// Put one encoded frame on air
+    async fn send(&self, frame: &[u8]) -> Result<(), String>;
+
+    /// Next received frame with its RSSI, or `None` if nothing is pending
+    async fn recv(&self) -> Result<Option<ReceivedPacket>, String>;
+}
+
+/// [`MeshTransport`] over any [`RadioDriver`], e.g. an `Sx126xDriver`
+/// configured for LoRa
+///
+/// The radio is put into receive mode on the first [`recv`](MeshTransport::recv)
+/// and again after every transmission.
+pub struct RadioMeshTransport<R: RadioDriver> {
+    radio: Arc<Mutex<R>>,
+
+    /// Whether the radio was put into receive mode
+    listening: AtomicBool,
+}
+
+impl<R: RadioDriver> RadioMeshTransport<R> {
+    pub fn new(radio: Arc<Mutex<R>>) -> Self {
+        Self {
+            radio,
+            listening: AtomicBool::new(false),
+        }
+    }
+}
+
+#[async_trait]
+impl<R: RadioDriver + 'static> MeshTransport for RadioMeshTransport<R> {
+    async fn send(&self, frame: &[u8]) -> Result<(), String> {
+        let mut radio = self.radio.lock().await;
+        radio
+            .transmit(frame)
+            .await
+            .map_err(|e| format!("Mesh TX failed: {e}"))?;
+        // Go back to listening for neighbours
+        self.listening.store(false, Ordering::Relaxed);
+        radio
+            .start_receive()
+            .await
+            .map_err(|e| format!("Mesh RX restart failed: {e}"))?;
+        self.listening.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn recv(&self) -> Result<Option<ReceivedPacket>, String> {
+        let mut radio = self.radio.lock().await;
+        if !self.listening.load(Ordering::Relaxed) {
+            radio
+                .start_receive()
+                .await
+                .map_err(|e| format!("Mesh RX start failed: {e}"))?;
+            self.listening.store(true, Ordering::Relaxed);
+        }
+        let packet = radio
+            .get_received_packet()
+            .await
+            .map_err(|e| format!("Mesh RX failed: {e}"))?;
+        Ok(packet.filter(|p| p.crc_valid))
+    }
+}
+
+/// Best known way to reach a node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Route {
+    /// Neighbour to hand frames to
+    pub next_hop: u32,
+
+    /// Hops to the destination (1 = direct neighbour)
+    pub hops: u8,
+
+    /// Link quality to the next hop (0.0 to 1.0)
+    pub link_quality: f32,
+
+    /// When this route was last confirmed
+    pub updated: Instant,
+}
+
 /// Quality of Service levels for mesh messages
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum QoS {
     /// Best effort delivery (no retries)
     AtMostOnce = 0,
@@ -77,11 +317,37 @@ pub struct NodeInfo {
     pub hop_count: u8,
 }
 
+/// Hop budget of newly published messages
+const DEFAULT_TTL: u8 = 16;
+
+/// Topic carrying subscription announcements
+const SUBSCRIBE_TOPIC: &str = "_mesh/subscribe";
+
+/// Link quality (0.0 to 1.0) for an RSSI in dBm
+fn link_quality(rssi: i16) -> f32 {
+    ((rssi + 120) as f32 / 70.0).clamp(0.0, 1.0)
+}
+
 /// LBM Core for mesh networking
 pub struct LbmCore {
     /// Our node address
     node_address: String,
 
+    /// On-air id of `node_address`
+    node_id: u32,
+
+    /// Sequence number of the next frame we originate
+    seq: AtomicU16,
+
+    /// Addresses behind on-air ids seen so far
+    names: Arc<RwLock<HashMap<u32, String>>>,
+
+    /// Next hop per destination id
+    routes: Arc<RwLock<HashMap<u32, Route>>>,
+
+    /// Radio link, if messages go on air rather than to `tx`
+    transport: Option<Arc<dyn MeshTransport>>,
+
     /// Known nodes in the mesh
     nodes: Arc<RwLock<HashMap<String, NodeInfo>>>,
 
@@ -115,9 +381,38 @@ struct PendingAck {
 impl LbmCore {
     /// Create a new LBM core instance
     pub fn new(node_address: String, is_gateway: bool) -> Self {
+        Self::build(node_address, is_gateway, None)
+    }
+
+    /// Create an LBM core that sends and receives over a radio link
+    ///
+    /// Gateways (`is_gateway`) also relay frames for other nodes.
+    pub fn with_transport(
+        node_address: String,
+        is_gateway: bool,
+        transport: Arc<dyn MeshTransport>,
+    ) -> Self {
+        Self::build(node_address, is_gateway, Some(transport))
+    }
+
+    fn build(
+        node_address: String,
+        is_gateway: bool,
+        transport: Option<Arc<dyn MeshTransport>>,
+    ) -> Self {
+        use rand::RngExt;
         let (tx, rx) = mpsc::channel(100);
+        let node_id = node_id(&node_address);
 
         Self {
+            node_id,
+            seq: AtomicU16::new(rand::rng().random()),
+            names: Arc::new(RwLock::new(HashMap::from([(
+                node_id,
+                node_address.clone(),
+            )]))),
+            routes: Arc::new(RwLock::new(HashMap::new())),
+            transport,
             node_address,
             nodes: Arc::new(RwLock::new(HashMap::new())),
             subscriptions: Arc::new(RwLock::new(HashMap::new())),
@@ -135,6 +430,7 @@ impl LbmCore {
         subs.entry(topic.to_string())
             .or_insert_with(HashSet::new)
             .insert(self.node_address.clone());
+        drop(subs);
 
         info!("Node {} subscribed to topic: {}", self.node_address, topic);
 
@@ -145,16 +441,39 @@ impl LbmCore {
 
     /// Publish a message to a topic
     pub async fn publish(&self, topic: &str, payload: Vec<u8>, qos: QoS) -> Result<u64, String> {
+        self.send_message(format!("topic://{topic}"), topic, payload, qos)
+            .await
+    }
+
+    /// Send a message to one node, relayed through the mesh if needed
+    pub async fn send_to(
+        &self,
+        destination: &str,
+        topic: &str,
+        payload: Vec<u8>,
+        qos: QoS,
+    ) -> Result<u64, String> {
+        self.send_message(destination.to_string(), topic, payload, qos)
+            .await
+    }
+
+    async fn send_message(
+        &self,
+        destination: String,
+        topic: &str,
+        payload: Vec<u8>,
+        qos: QoS,
+    ) -> Result<u64, String> {
         let msg_id = self.generate_message_id();
 
         let message = MeshMessage {
             id: msg_id,
             source: self.node_address.clone(),
-            destination: format!("topic://{topic}"),
+            destination,
             topic: topic.to_string(),
             payload,
             qos,
-            ttl: 16, // Default TTL
+            ttl: DEFAULT_TTL,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
@@ -190,8 +509,8 @@ impl LbmCore {
         }
         message.ttl -= 1;
 
-        // Check for duplicate
-        if self.is_duplicate(&message).await {
+        // Check for duplicate (retries of our own messages keep their id)
+        if message.source != self.node_address && self.is_duplicate(&message).await {
             debug!("Message {} dropped: duplicate", message.id);
             return Ok(());
         }
@@ -199,6 +518,15 @@ impl LbmCore {
         // Store in cache
         self.cache_message(&message).await;
 
+        if let Some(transport) = &self.transport {
+            let frame = self.frame_for(&message).await;
+            debug!(
+                "Sending message {} on air via {:08x}",
+                message.id, frame.next_hop
+            );
+            return transport.send(&frame.encode()).await;
+        }
+
         // Determine next hop(s)
         let next_hops = self.determine_next_hops(&message).await?;
 
@@ -253,14 +581,22 @@ impl LbmCore {
 
     /// Check if message is duplicate
     async fn is_duplicate(&self, message: &MeshMessage) -> bool {
+        self.is_duplicate_id(message.id).await
+    }
+
+    async fn is_duplicate_id(&self, message_id: u64) -> bool {
         let cache = self.message_cache.lock().await;
-        cache.iter().any(|(id, _)| *id == message.id)
+        cache.iter().any(|(id, _)| *id == message_id)
     }
 
     /// Cache message for deduplication
     async fn cache_message(&self, message: &MeshMessage) {
+        self.cache_message_id(message.id).await;
+    }
+
+    async fn cache_message_id(&self, message_id: u64) {
         let mut cache = self.message_cache.lock().await;
-        cache.push_back((message.id, Instant::now()));
+        cache.push_back((message_id, Instant::now()));
 
         // Limit cache size
         while cache.len() > 1000 {
@@ -278,7 +614,7 @@ impl LbmCore {
             id: self.generate_message_id(),
             source: self.node_address.clone(),
             destination: "broadcast".to_string(),
-            topic: "_mesh/subscribe".to_string(),
+            topic: SUBSCRIBE_TOPIC.to_string(),
             payload: topic.as_bytes().to_vec(),
             qos: QoS::AtMostOnce,
             ttl: 8,
@@ -322,10 +658,16 @@ impl LbmCore {
     }
 
     /// Update node information
+    ///
+    /// The node becomes a direct neighbour in the route table.
     pub async fn update_node(&self, address: String, rssi: i16, is_gateway: bool) {
+        let id = node_id(&address);
+        self.names.write().await.insert(id, address.clone());
+        self.learn_route(id, id, 1, rssi).await;
+
         let mut nodes = self.nodes.write().await;
 
-        let link_quality = ((rssi + 120) as f32 / 70.0).clamp(0.0, 1.0);
+        let link_quality = link_quality(rssi);
 
         nodes
             .entry(address.clone())
@@ -343,10 +685,268 @@ impl LbmCore {
             });
     }
 
-    /// Generate unique message ID
+    /// Generate unique message ID from our node id and next sequence number
     fn generate_message_id(&self) -> u64 {
-        use rand::RngExt;
-        rand::rng().random()
+        message_id(self.node_id, self.next_seq())
+    }
+
+    fn next_seq(&self) -> u16 {
+        self.seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Record a route to `destination` through neighbour `via`
+    ///
+    /// Fewer hops win; on a tie the better link does. A route through the
+    /// same neighbour, or one not confirmed for five minutes, is replaced.
+    async fn learn_route(&self, destination: u32, via: u32, hops: u8, rssi: i16) {
+        let candidate = Route {
+            next_hop: via,
+            hops,
+            link_quality: link_quality(rssi),
+            updated: Instant::now(),
+        };
+
+        let mut routes = self.routes.write().await;
+        let replace = routes.get(&destination).is_none_or(|known| {
+            hops < known.hops
+                || known.next_hop == via
+                || known.updated.elapsed() > ROUTE_TIMEOUT
+                || (hops == known.hops && candidate.link_quality > known.link_quality)
+        });
+        if replace {
+            routes.insert(destination, candidate);
+        }
+    }
+
+    /// Best known route to a node
+    pub async fn route_to(&self, address: &str) -> Option<Route> {
+        self.routes.read().await.get(&node_id(address)).copied()
+    }
+
+    /// Neighbour to hand a frame for `destination` to, flooding if unknown
+    async fn next_hop_for(&self, destination: u32) -> u32 {
+        if destination == BROADCAST_ID {
+            return BROADCAST_ID;
+        }
+        self.routes
+            .read()
+            .await
+            .get(&destination)
+            .map_or(BROADCAST_ID, |route| route.next_hop)
+    }
+
+    /// Address behind an on-air id, or its hex form if never seen
+    async fn name_of(&self, id: u32) -> String {
+        self.names
+            .read()
+            .await
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("{id:08x}"))
+    }
+
+    /// Whether we subscribed to the topic with this hash, and its name
+    async fn local_topic(&self, hash: u16) -> Option<String> {
+        let subs = self.subscriptions.read().await;
+        subs.iter()
+            .find(|(topic, nodes)| topic_hash(topic) == hash && nodes.contains(&self.node_address))
+            .map(|(topic, _)| topic.clone())
+    }
+
+    /// On-air frame for a message we originate
+    async fn frame_for(&self, message: &MeshMessage) -> MeshFrame {
+        let destination =
+            if message.destination.starts_with("topic://") || message.destination == "broadcast" {
+                BROADCAST_ID
+            } else {
+                node_id(&message.destination)
+            };
+        let kind = if message.topic == SUBSCRIBE_TOPIC {
+            FrameKind::Subscribe
+        } else {
+            FrameKind::Data
+        };
+
+        MeshFrame {
+            kind,
+            qos: message.qos,
+            source: node_id(&message.source),
+            destination,
+            relay: self.node_id,
+            next_hop: self.next_hop_for(destination).await,
+            ttl: message.ttl,
+            hops: 0,
+            seq: message.id as u16,
+            topic: topic_hash(&message.topic),
+            payload: message.payload.clone(),
+        }
+    }
+
+    /// Receive every pending frame from the transport
+    ///
+    /// Returns the messages delivered to this node; relaying and acks happen
+    /// along the way.
+    pub async fn poll_transport(&self) -> Result<Vec<MeshMessage>, String> {
+        let Some(transport) = &self.transport else {
+            return Ok(Vec::new());
+        };
+
+        let mut delivered = Vec::new();
+        while let Some(packet) = transport.recv().await? {
+            match self.handle_frame(&packet.data, packet.rssi_dbm).await {
+                Ok(Some(message)) => delivered.push(message),
+                Ok(None) => {}
+                Err(e) => warn!("Dropping mesh frame: {e}"),
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Process one received frame heard at `rssi` dBm
+    ///
+    /// Learns the transmitting neighbour and the route back to the source,
+    /// delivers the message if it is for us, acknowledges QoS 1/2 messages
+    /// (again for duplicates, in case the first ack was lost) and, on
+    /// gateways, relays it towards its destination.
+    pub async fn handle_frame(
+        &self,
+        data: &[u8],
+        rssi: i16,
+    ) -> Result<Option<MeshMessage>, String> {
+        let mut frame = MeshFrame::decode(data).map_err(|e| e.to_string())?;
+        if frame.relay == self.node_id {
+            return Ok(None);
+        }
+
+        let relay = self.name_of(frame.relay).await;
+        self.update_node(relay, rssi, frame.relay != frame.source)
+            .await;
+        if frame.source == self.node_id {
+            // Our own frame relayed back
+            return Ok(None);
+        }
+        self.learn_route(
+            frame.source,
+            frame.relay,
+            frame.hops.saturating_add(1),
+            rssi,
+        )
+        .await;
+
+        let for_us = frame.destination == self.node_id;
+        let broadcast = frame.destination == BROADCAST_ID;
+        if !for_us && !broadcast && frame.next_hop != self.node_id && frame.next_hop != BROADCAST_ID
+        {
+            // Unicast being relayed by another neighbour
+            return Ok(None);
+        }
+
+        let id = frame.message_id();
+        let duplicate = self.is_duplicate_id(id).await;
+        if !duplicate {
+            self.cache_message_id(id).await;
+        }
+
+        let local_topic = if broadcast {
+            self.local_topic(frame.topic).await
+        } else {
+            None
+        };
+        let mut delivered = None;
+        match frame.kind {
+            FrameKind::Ack if for_us && frame.payload.len() >= 2 => {
+                let acked = u16::from_le_bytes([frame.payload[0], frame.payload[1]]);
+                if self
+                    .pending_acks
+                    .lock()
+                    .await
+                    .remove(&message_id(self.node_id, acked))
+                    .is_some()
+                {
+                    debug!("Message {} acknowledged", message_id(self.node_id, acked));
+                }
+            }
+            FrameKind::Subscribe if !duplicate => {
+                let topic = String::from_utf8_lossy(&frame.payload).into_owned();
+                let source = self.name_of(frame.source).await;
+                self.subscriptions
+                    .write()
+                    .await
+                    .entry(topic)
+                    .or_insert_with(HashSet::new)
+                    .insert(source);
+            }
+            FrameKind::Data if for_us || local_topic.is_some() => {
+                if frame.qos != QoS::AtMostOnce {
+                    self.send_ack(&frame).await?;
+                }
+                if !duplicate {
+                    let topic = local_topic
+                        .clone()
+                        .unwrap_or_else(|| format!("#{:04x}", frame.topic));
+                    delivered = Some(MeshMessage {
+                        id,
+                        source: self.name_of(frame.source).await,
+                        destination: if broadcast {
+                            format!("topic://{topic}")
+                        } else {
+                            self.node_address.clone()
+                        },
+                        topic,
+                        payload: frame.payload.clone(),
+                        qos: frame.qos,
+                        ttl: frame.ttl,
+                        timestamp: std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                        reply_to: None,
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        if duplicate || for_us || !self.is_gateway {
+            return Ok(delivered);
+        }
+        if frame.ttl == 0 {
+            debug!("Message {id} not relayed: TTL expired");
+            return Ok(delivered);
+        }
+
+        frame.ttl -= 1;
+        frame.hops = frame.hops.saturating_add(1);
+        frame.relay = self.node_id;
+        frame.next_hop = self.next_hop_for(frame.destination).await;
+        debug!("Relaying message {id} via {:08x}", frame.next_hop);
+        if let Some(transport) = &self.transport {
+            transport.send(&frame.encode()).await?;
+        }
+
+        Ok(delivered)
+    }
+
+    /// Acknowledge a QoS 1/2 frame back to its source
+    async fn send_ack(&self, frame: &MeshFrame) -> Result<(), String> {
+        let Some(transport) = &self.transport else {
+            return Ok(());
+        };
+
+        let ack = MeshFrame {
+            kind: FrameKind::Ack,
+            qos: QoS::AtMostOnce,
+            source: self.node_id,
+            destination: frame.source,
+            relay: self.node_id,
+            next_hop: self.next_hop_for(frame.source).await,
+            ttl: DEFAULT_TTL,
+            hops: 0,
+            seq: self.next_seq(),
+            topic: 0,
+            payload: frame.seq.to_le_bytes().to_vec(),
+        };
+        transport.send(&ack.encode()).await
     }
 
     /// Get mesh statistics
@@ -355,6 +955,7 @@ impl LbmCore {
         let subs = self.subscriptions.read().await;
         let pending = self.pending_acks.lock().await;
         let cache = self.message_cache.lock().await;
+        let routes = self.routes.read().await;
 
         MeshStats {
             node_count: nodes.len(),
@@ -362,6 +963,7 @@ impl LbmCore {
             topic_count: subs.len(),
             pending_messages: pending.len(),
             cached_messages: cache.len(),
+            route_count: routes.len(),
         }
     }
 }
@@ -374,6 +976,7 @@ pub struct MeshStats {
     pub topic_count: usize,
     pub pending_messages: usize,
     pub cached_messages: usize,
+    pub route_count: usize,
 }
 
 #[cfg(test)]
@@ -408,4 +1011,192 @@ mod tests {
         assert_eq!(stats.node_count, 2);
         assert_eq!(stats.gateway_count, 1);
     }
+
+    /// Shared air: a frame sent by one node lands in the inbox of every node
+    /// with a link to it, at that link's RSSI
+    #[derive(Default)]
+    struct SimulatedChannel {
+        links: HashMap<(String, String), i16>,
+        inboxes: std::sync::Mutex<HashMap<String, VecDeque<ReceivedPacket>>>,
+    }
+
+    impl SimulatedChannel {
+        fn link(&mut self, a: &str, b: &str, rssi: i16) {
+            self.links.insert((a.to_string(), b.to_string()), rssi);
+            self.links.insert((b.to_string(), a.to_string()), rssi);
+        }
+    }
+
+    struct SimulatedTransport {
+        node: String,
+        channel: Arc<SimulatedChannel>,
+    }
+
+    #[async_trait]
+    impl MeshTransport for SimulatedTransport {
+        async fn send(&self, frame: &[u8]) -> Result<(), String> {
+            let mut inboxes = self.channel.inboxes.lock().unwrap();
+            for ((from, to), &rssi) in &self.channel.links {
+                if *from == self.node {
+                    inboxes
+                        .entry(to.clone())
+                        .or_default()
+                        .push_back(ReceivedPacket {
+                            data: frame.to_vec(),
+                            rssi_dbm: rssi,
+                            freq_error_hz: None,
+                            lqi: None,
+                            crc_valid: true,
+                        });
+                }
+            }
+            Ok(())
+        }
+
+        async fn recv(&self) -> Result<Option<ReceivedPacket>, String> {
+            let mut inboxes = self.channel.inboxes.lock().unwrap();
+            Ok(inboxes.get_mut(&self.node).and_then(|q| q.pop_front()))
+        }
+    }
+
+    fn node(channel: &Arc<SimulatedChannel>, address: &str, is_gateway: bool) -> LbmCore {
+        let transport = Arc::new(SimulatedTransport {
+            node: address.to_string(),
+            channel: channel.clone(),
+        });
+        LbmCore::with_transport(address.to_string(), is_gateway, transport)
+    }
+
+    #[test]
+    fn test_frame_codec() {
+        let frame = MeshFrame {
+            kind: FrameKind::Data,
+            qos: QoS::AtLeastOnce,
+            source: node_id("meter-basement"),
+            destination: node_id("0000abcd"),
+            relay: 0x1122_3344,
+            next_hop: BROADCAST_ID,
+            ttl: 7,
+            hops: 2,
+            seq: 0xBEEF,
+            topic: topic_hash("meters/water"),
+            payload: vec![1, 2, 3],
+        };
+        let encoded = frame.encode();
+        assert_eq!(encoded.len(), FRAME_HEADER_LEN + 3);
+        assert_eq!(MeshFrame::decode(&encoded), Ok(frame));
+        assert_eq!(node_id("0000abcd"), 0xABCD);
+
+        assert_eq!(
+            MeshFrame::decode(&encoded[..10]),
+            Err(MeshFrameError::Truncated { len: 10 })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_radio_transport_listens_from_the_first_recv() {
+        use crate::wmbus::radio::radio_driver::{RadioMode, WMBusConfig};
+        use crate::wmbus::sim::{ChannelConfig, SimRadio};
+
+        let mut radio = SimRadio::new(ChannelConfig::ideal(), 1);
+        radio.initialize(WMBusConfig::default()).await.unwrap();
+        let transport = RadioMeshTransport::new(Arc::new(Mutex::new(radio.clone())));
+        assert_eq!(radio.get_mode().await.unwrap(), RadioMode::Standby);
+
+        assert!(transport.recv().await.unwrap().is_none());
+        assert_eq!(radio.get_mode().await.unwrap(), RadioMode::Receive);
+
+        // Transmitting leaves receive mode; the transport listens again after
+        transport.send(&[0x01]).await.unwrap();
+        assert_eq!(radio.get_mode().await.unwrap(), RadioMode::Receive);
+    }
+
+    #[tokio::test]
+    async fn test_relay_through_gateway_with_ack() {
+        // The basement meter only reaches the relay gateway, which reaches the head-end
+        let mut channel = SimulatedChannel::default();
+        channel.link("basement", "relay", -112);
+        channel.link("relay", "headend", -80);
+        let channel = Arc::new(channel);
+
+        let basement = node(&channel, "basement", false);
+        let relay = node(&channel, "relay", true);
+        let headend = node(&channel, "headend", true);
+        basement.update_node("relay".to_string(), -112, true).await;
+        relay.update_node("headend".to_string(), -80, true).await;
+
+        let id = basement
+            .send_to(
+                "headend",
+                "meters/water",
+                b"42 m3".to_vec(),
+                QoS::AtLeastOnce,
+            )
+            .await
+            .unwrap();
+        assert_eq!(basement.get_stats().await.pending_messages, 1);
+
+        assert!(relay.poll_transport().await.unwrap().is_empty());
+        let delivered = headend.poll_transport().await.unwrap();
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].id, id);
+        // Never heard directly, so the source shows as its id in hex
+        assert_eq!(node_id(&delivered[0].source), node_id("basement"));
+        assert_eq!(delivered[0].payload, b"42 m3");
+
+        // The head-end learned the way back through the relay
+        let route = headend.route_to("basement").await.unwrap();
+        assert_eq!((route.next_hop, route.hops), (node_id("relay"), 2));
+
+        // The ack travels back the same way
+        relay.poll_transport().await.unwrap();
+        basement.poll_transport().await.unwrap();
+        assert_eq!(basement.get_stats().await.pending_messages, 0);
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_and_ttl() {
+        let mut channel = SimulatedChannel::default();
+        channel.link("gw", "listener", -70);
+        let channel = Arc::new(channel);
+        let gateway = node(&channel, "gw", true);
+        gateway.subscribe("alarms").await.unwrap();
+
+        let mut frame = MeshFrame {
+            kind: FrameKind::Data,
+            qos: QoS::AtMostOnce,
+            source: node_id("far-node"),
+            destination: BROADCAST_ID,
+            relay: node_id("neighbour"),
+            next_hop: BROADCAST_ID,
+            ttl: 0,
+            hops: 3,
+            seq: 1,
+            topic: topic_hash("alarms"),
+            payload: b"leak".to_vec(),
+        };
+
+        let first = gateway.handle_frame(&frame.encode(), -90).await.unwrap();
+        assert_eq!(first.map(|m| m.topic), Some("alarms".to_string()));
+
+        // Subscription announcement only; the TTL-expired message is not relayed
+        let heard = channel.inboxes.lock().unwrap().remove("listener").unwrap();
+        assert_eq!(heard.len(), 1);
+        assert_eq!(
+            MeshFrame::decode(&heard[0].data).unwrap().kind,
+            FrameKind::Subscribe
+        );
+
+        // The same message via another relay is not delivered twice
+        frame.relay = node_id("other-neighbour");
+        assert!(gateway
+            .handle_frame(&frame.encode(), -95)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Four hops away through the neighbour heard first
+        let route = gateway.route_to("far-node").await.unwrap();
+        assert_eq!((route.next_hop, route.hops), (node_id("neighbour"), 4));
+    }
 }
@@ -0,0 +1,796 @@
+//! LoRaWAN 1.0.x data frames
+//!
+//! Parses the PHYPayload of standard LoRaWAN data messages so that meters
+//! talking plain LoRaWAN can be decoded by the gateway itself, without a
+//! network server:
+//!
+//! ```text
+//! PHYPayload = MHDR(1) | DevAddr(4) | FCtrl(1) | FCnt(2) | FOpts(0..15) | [FPort(1) | FRMPayload] | MIC(4)
+//! ```
+//!
+//! Frame parsing is always available. Session handling, MIC verification and
+//! FRMPayload decryption need the `crypto` feature (AES-128 and AES-CMAC).
+
+use super::mac::{self, MacCommand, MacError};
+use super::packet::LoRaError;
+
+#[cfg(feature = "crypto")]
+use crate::wmbus::crypto::{AesKey, WMBusCrypto};
+#[cfg(feature = "crypto")]
+use crate::wmbus::crypto_hardware::get_aes_backend;
+#[cfg(feature = "crypto")]
+use std::collections::HashMap;
+
+/// Length of the message integrity code at the end of every PHYPayload.
+pub const MIC_LEN: usize = 4;
+
+/// Largest FOpts field; longer MAC command lists go on FPort 0.
+pub const MAX_FOPTS_LEN: usize = 15;
+
+/// Smallest data frame: MHDR + DevAddr + FCtrl + FCnt + MIC.
+const MIN_DATA_FRAME_LEN: usize = 1 + 4 + 1 + 2 + MIC_LEN;
+
+/// Message type carried in the upper three bits of the MHDR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MType {
+    JoinRequest,
+    JoinAccept,
+    UnconfirmedDataUp,
+    UnconfirmedDataDown,
+    ConfirmedDataUp,
+    ConfirmedDataDown,
+    RejoinRequest,
+    Proprietary,
+}
+
+impl MType {
+    /// Extract the message type from an MHDR byte.
+    pub fn from_mhdr(mhdr: u8) -> Self {
+        match mhdr >> 5 {
+            0 => Self::JoinRequest,
+            1 => Self::JoinAccept,
+            2 => Self::UnconfirmedDataUp,
+            3 => Self::UnconfirmedDataDown,
+            4 => Self::ConfirmedDataUp,
+            5 => Self::ConfirmedDataDown,
+            6 => Self::RejoinRequest,
+            _ => Self::Proprietary,
+        }
+    }
+
+    /// MHDR byte for this message type with LoRaWAN R1 as major version.
+    pub fn mhdr(self) -> u8 {
+        let bits = match self {
+            Self::JoinRequest => 0,
+            Self::JoinAccept => 1,
+            Self::UnconfirmedDataUp => 2,
+            Self::UnconfirmedDataDown => 3,
+            Self::ConfirmedDataUp => 4,
+            Self::ConfirmedDataDown => 5,
+            Self::RejoinRequest => 6,
+            Self::Proprietary => 7,
+        };
+        bits << 5
+    }
+
+    /// True for the four data message types.
+    pub fn is_data(self) -> bool {
+        matches!(
+            self,
+            Self::UnconfirmedDataUp
+                | Self::UnconfirmedDataDown
+                | Self::ConfirmedDataUp
+                | Self::ConfirmedDataDown
+        )
+    }
+
+    /// Link direction of a data message, `None` for everything else.
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            Self::UnconfirmedDataUp | Self::ConfirmedDataUp => Some(Direction::Uplink),
+            Self::UnconfirmedDataDown | Self::ConfirmedDataDown => Some(Direction::Downlink),
+            _ => None,
+        }
+    }
+}
+
+/// Link direction, as used in the MIC B0 block and the encryption A blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Uplink = 0,
+    Downlink = 1,
+}
+
+/// Frame control byte of the FHDR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FCtrl(pub u8);
+
+impl FCtrl {
+    /// Adaptive data rate enabled.
+    pub fn adr(self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    /// ADR acknowledgement requested (uplink only).
+    pub fn adr_ack_req(self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    /// Acknowledges the last confirmed frame from the other side.
+    pub fn ack(self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    /// FPending on downlinks, Class B indication on uplinks.
+    pub fn f_pending(self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    /// Number of FOpts bytes following the FCnt.
+    pub fn f_opts_len(self) -> usize {
+        (self.0 & 0x0F) as usize
+    }
+}
+
+/// A parsed LoRaWAN data frame.
+///
+/// `fcnt` is the 16-bit counter as transmitted; use [`extend_fcnt`] (or a
+/// `SessionStore` with the `crypto` feature) to recover the full 32-bit value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataFrame {
+    pub mtype: MType,
+    pub dev_addr: u32,
+    pub fctrl: FCtrl,
+    pub fcnt: u16,
+    pub fopts: Vec<u8>,
+    pub fport: Option<u8>,
+    /// FRMPayload as received (still encrypted).
+    pub frm_payload: Vec<u8>,
+    pub mic: [u8; MIC_LEN],
+}
+
+impl DataFrame {
+    /// Parse a data-message PHYPayload.
+    pub fn parse(phy: &[u8]) -> Result<Self, LoRaError> {
+        let mhdr = *phy
+            .first()
+            .ok_or_else(|| LoRaError::Parse("Empty payload".to_string()))?;
+        let mtype = MType::from_mhdr(mhdr);
+        if !mtype.is_data() || mhdr & 0x03 != 0 {
+            return Err(LoRaError::InvalidMhdr(mhdr));
+        }
+        if phy.len() < MIN_DATA_FRAME_LEN {
+            return Err(LoRaError::Parse("Too short for data frame".to_string()));
+        }
+
+        let dev_addr = u32::from_le_bytes([phy[1], phy[2], phy[3], phy[4]]);
+        let fctrl = FCtrl(phy[5]);
+        let fcnt = u16::from_le_bytes([phy[6], phy[7]]);
+
+        let mic_start = phy.len() - MIC_LEN;
+        let fopts_end = 8 + fctrl.f_opts_len();
+        if fopts_end > mic_start {
+            return Err(LoRaError::Parse(format!(
+                "FOptsLen {} exceeds frame length",
+                fctrl.f_opts_len()
+            )));
+        }
+        let fopts = phy[8..fopts_end].to_vec();
+
+        let (fport, frm_payload) = if fopts_end < mic_start {
+            (Some(phy[fopts_end]), phy[fopts_end + 1..mic_start].to_vec())
+        } else {
+            (None, Vec::new())
+        };
+        if fport == Some(0) && !fopts.is_empty() {
+            return Err(LoRaError::Parse(
+                "MAC commands in both FOpts and FPort 0".to_string(),
+            ));
+        }
+
+        let mut mic = [0u8; MIC_LEN];
+        mic.copy_from_slice(&phy[mic_start..]);
+
+        Ok(Self {
+            mtype,
+            dev_addr,
+            fctrl,
+            fcnt,
+            fopts,
+            fport,
+            frm_payload,
+            mic,
+        })
+    }
+
+    /// Link direction of this frame.
+    pub fn direction(&self) -> Direction {
+        self.mtype
+            .direction()
+            .expect("DataFrame always holds a data message type")
+    }
+
+    /// MAC commands piggybacked in FOpts (always plaintext in 1.0.x).
+    pub fn fopts_commands(&self) -> Result<Vec<MacCommand>, MacError> {
+        mac::decode(&self.fopts, self.direction())
+    }
+
+    /// True for confirmed data messages, which expect an ACK.
+    pub fn is_confirmed(&self) -> bool {
+        matches!(
+            self.mtype,
+            MType::ConfirmedDataUp | MType::ConfirmedDataDown
+        )
+    }
+}
+
+/// Length of a JoinRequest PHYPayload.
+pub const JOIN_REQUEST_LEN: usize = 23;
+
+/// Fields of a JoinRequest. EUIs are held as numbers (transmitted LSB first).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinRequestFrame {
+    pub app_eui: u64,
+    pub dev_eui: u64,
+    pub dev_nonce: u16,
+    pub mic: [u8; MIC_LEN],
+}
+
+impl JoinRequestFrame {
+    pub fn parse(phy: &[u8]) -> Result<Self, LoRaError> {
+        let mhdr = phy.first().copied().unwrap_or(0xFF);
+        if MType::from_mhdr(mhdr) != MType::JoinRequest || mhdr & 0x03 != 0 {
+            return Err(LoRaError::InvalidMhdr(mhdr));
+        }
+        if phy.len() != JOIN_REQUEST_LEN {
+            return Err(LoRaError::Parse(format!(
+                "JoinRequest must be {} bytes, got {}",
+                JOIN_REQUEST_LEN,
+                phy.len()
+            )));
+        }
+        Ok(Self {
+            app_eui: u64::from_le_bytes(phy[1..9].try_into().unwrap()),
+            dev_eui: u64::from_le_bytes(phy[9..17].try_into().unwrap()),
+            dev_nonce: u16::from_le_bytes([phy[17], phy[18]]),
+            mic: phy[19..23].try_into().unwrap(),
+        })
+    }
+}
+
+/// Extend a received 16-bit FCnt to 32 bits relative to the last accepted value.
+///
+/// The result keeps the upper half of `last` unless that would move the counter
+/// backwards, in which case a 16-bit rollover is assumed. A repeated counter
+/// comes back equal to `last`, leaving replay handling to the caller.
+pub fn extend_fcnt(fcnt: u16, last: Option<u32>) -> u32 {
+    let Some(last) = last else {
+        return fcnt as u32;
+    };
+    let candidate = (last & 0xFFFF_0000) | fcnt as u32;
+    if candidate < last {
+        candidate.wrapping_add(0x1_0000)
+    } else {
+        candidate
+    }
+}
+
+/// ABP or OTAA-derived session keys of one device.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionKeys {
+    pub nwk_s_key: [u8; 16],
+    pub app_s_key: [u8; 16],
+}
+
+impl std::fmt::Debug for SessionKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionKeys").finish_non_exhaustive()
+    }
+}
+
+/// Per-device session state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSession {
+    pub dev_addr: u32,
+    pub keys: SessionKeys,
+    /// Last accepted uplink counter, `None` until the first uplink.
+    pub fcnt_up: Option<u32>,
+    /// Next downlink counter.
+    pub fcnt_down: u32,
+}
+
+impl DeviceSession {
+    /// Fresh session with both counters at zero.
+    pub fn new(dev_addr: u32, keys: SessionKeys) -> Self {
+        Self {
+            dev_addr,
+            keys,
+            fcnt_up: None,
+            fcnt_down: 0,
+        }
+    }
+}
+
+/// Encrypt or decrypt an FRMPayload (the operation is its own inverse).
+///
+/// Uses the AES-CTR style keystream of LoRaWAN 1.0.x: block `i` is
+/// `AES(key, 0x01 | 0x00×4 | dir | DevAddr | FCnt | 0x00 | i)`.
+#[cfg(feature = "crypto")]
+pub fn frm_payload_crypt(
+    key: &[u8; 16],
+    direction: Direction,
+    dev_addr: u32,
+    fcnt: u32,
+    data: &[u8],
+) -> Vec<u8> {
+    let backend = get_aes_backend();
+    let mut a = [0u8; 16];
+    a[0] = 0x01;
+    a[5] = direction as u8;
+    a[6..10].copy_from_slice(&dev_addr.to_le_bytes());
+    a[10..14].copy_from_slice(&fcnt.to_le_bytes());
+
+    let mut out = Vec::with_capacity(data.len());
+    let mut keystream = [0u8; 16];
+    for (i, chunk) in data.chunks(16).enumerate() {
+        a[15] = (i + 1) as u8;
+        backend.encrypt_block(&a, key, &mut keystream);
+        out.extend(chunk.iter().zip(keystream.iter()).map(|(b, k)| b ^ k));
+    }
+    out
+}
+
+/// A verified and decrypted uplink.
+#[cfg(feature = "crypto")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uplink {
+    pub frame: DataFrame,
+    /// Full 32-bit frame counter used for the MIC and decryption.
+    pub fcnt: u32,
+    /// Decrypted FRMPayload. MAC commands when `frame.fport == Some(0)`.
+    pub payload: Vec<u8>,
+}
+
+#[cfg(feature = "crypto")]
+impl Uplink {
+    /// MAC commands from FOpts, or from the decrypted payload on FPort 0.
+    pub fn mac_commands(&self) -> Result<Vec<MacCommand>, MacError> {
+        if self.frame.fport == Some(0) {
+            mac::decode(&self.payload, Direction::Uplink)
+        } else {
+            self.frame.fopts_commands()
+        }
+    }
+}
+
+/// Contents of a downlink to build with [`SessionStore::build_downlink`].
+#[cfg(feature = "crypto")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Downlink {
+    pub confirmed: bool,
+    /// Acknowledge the device's last confirmed uplink.
+    pub ack: bool,
+    pub adr: bool,
+    pub f_pending: bool,
+    /// Sent in FOpts when they fit, otherwise on FPort 0 if there is no payload.
+    pub mac_commands: Vec<MacCommand>,
+    pub fport: Option<u8>,
+    pub payload: Vec<u8>,
+}
+
+/// Session keys and counters for a set of devices, keyed by DevAddr.
+#[cfg(feature = "crypto")]
+pub struct SessionStore {
+    sessions: HashMap<u32, DeviceSession>,
+    crypto: WMBusCrypto,
+}
+
+#[cfg(feature = "crypto")]
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl SessionStore {
+    pub fn new() -> Self {
+        Self {
+            sessions: HashMap::new(),
+            // Only the LoRaWAN MIC helpers are used, which take their key per call.
+            crypto: WMBusCrypto::new(AesKey::from_bytes(&[0u8; 16]).expect("16-byte key")),
+        }
+    }
+
+    /// Add or replace a session, e.g. an ABP device or a completed join.
+    pub fn insert(&mut self, session: DeviceSession) -> Option<DeviceSession> {
+        self.sessions.insert(session.dev_addr, session)
+    }
+
+    pub fn get(&self, dev_addr: u32) -> Option<&DeviceSession> {
+        self.sessions.get(&dev_addr)
+    }
+
+    pub fn remove(&mut self, dev_addr: u32) -> Option<DeviceSession> {
+        self.sessions.remove(&dev_addr)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// DevAddrs of all stored sessions, in no particular order.
+    pub fn dev_addrs(&self) -> impl Iterator<Item = u32> + '_ {
+        self.sessions.keys().copied()
+    }
+
+    /// Encrypt and sign a downlink for `dev_addr` with its next FCntDown.
+    pub fn build_downlink(
+        &mut self,
+        dev_addr: u32,
+        downlink: &Downlink,
+    ) -> Result<Vec<u8>, LoRaError> {
+        let session = self
+            .sessions
+            .get_mut(&dev_addr)
+            .ok_or(LoRaError::DeviceNotFound)?;
+
+        let commands = mac::encode(&downlink.mac_commands);
+        let (fopts, fport, payload) =
+            if commands.len() <= MAX_FOPTS_LEN && downlink.fport != Some(0) {
+                (commands, downlink.fport, downlink.payload.clone())
+            } else if downlink.fport.is_none() && downlink.payload.is_empty() {
+                (Vec::new(), Some(0), commands)
+            } else {
+                return Err(LoRaError::FOptsOverflow {
+                    len: commands.len(),
+                });
+            };
+
+        let fcnt = session.fcnt_down;
+        let mtype = if downlink.confirmed {
+            MType::ConfirmedDataDown
+        } else {
+            MType::UnconfirmedDataDown
+        };
+        let fctrl = ((downlink.adr as u8) << 7)
+            | ((downlink.ack as u8) << 5)
+            | ((downlink.f_pending as u8) << 4)
+            | fopts.len() as u8;
+
+        let mut phy = vec![mtype.mhdr()];
+        phy.extend_from_slice(&dev_addr.to_le_bytes());
+        phy.push(fctrl);
+        phy.extend_from_slice(&(fcnt as u16).to_le_bytes());
+        phy.extend_from_slice(&fopts);
+        if let Some(port) = fport {
+            let key = if port == 0 {
+                &session.keys.nwk_s_key
+            } else {
+                &session.keys.app_s_key
+            };
+            phy.push(port);
+            phy.extend(frm_payload_crypt(
+                key,
+                Direction::Downlink,
+                dev_addr,
+                fcnt,
+                &payload,
+            ));
+        }
+        let mic = self.crypto.calculate_lorawan_mic(
+            &session.keys.nwk_s_key,
+            &phy,
+            Direction::Downlink as u8,
+            dev_addr,
+            fcnt,
+        )?;
+        phy.extend_from_slice(&mic);
+        session.fcnt_down = fcnt.wrapping_add(1);
+
+        Ok(phy)
+    }
+
+    /// Verify, decrypt and account an uplink PHYPayload.
+    ///
+    /// The MIC is checked with the NwkSKey before the counter is looked at, so
+    /// a forged frame cannot advance the session. Frames whose counter is not
+    /// above the last accepted one are rejected as replays.
+    pub fn decode_uplink(&mut self, phy: &[u8]) -> Result<Uplink, LoRaError> {
+        let frame = DataFrame::parse(phy)?;
+        if frame.direction() != Direction::Uplink {
+            return Err(LoRaError::InvalidMhdr(phy[0]));
+        }
+        let session = self
+            .sessions
+            .get_mut(&frame.dev_addr)
+            .ok_or(LoRaError::DeviceNotFound)?;
+
+        let fcnt = extend_fcnt(frame.fcnt, session.fcnt_up);
+        let mic = self.crypto.calculate_lorawan_mic(
+            &session.keys.nwk_s_key,
+            &phy[..phy.len() - MIC_LEN],
+            Direction::Uplink as u8,
+            frame.dev_addr,
+            fcnt,
+        )?;
+        if mic != frame.mic {
+            return Err(LoRaError::MicMismatch {
+                dev_addr: frame.dev_addr,
+            });
+        }
+        if let Some(last) = session.fcnt_up {
+            if fcnt <= last {
+                return Err(LoRaError::FCntReplay {
+                    dev_addr: frame.dev_addr,
+                    fcnt,
+                    last,
+                });
+            }
+        }
+
+        let key = if frame.fport == Some(0) {
+            &session.keys.nwk_s_key
+        } else {
+            &session.keys.app_s_key
+        };
+        let payload = frm_payload_crypt(
+            key,
+            Direction::Uplink,
+            frame.dev_addr,
+            fcnt,
+            &frame.frm_payload,
+        );
+        session.fcnt_up = Some(fcnt);
+
+        Ok(Uplink {
+            frame,
+            fcnt,
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unconfirmed uplink from DevAddr 0x49BE7DF1, FCnt 2, FPort 1, payload "test".
+    const SPEC_UPLINK: &str = "40f17dbe4900020001954378762b11ff0d";
+    #[cfg(feature = "crypto")]
+    const NWK_S_KEY: &str = "44024241ed4ce9a68c6a8bc055233fd3";
+    #[cfg(feature = "crypto")]
+    const APP_S_KEY: &str = "ec925802ae430ca77fd3dd73cb2cc588";
+
+    #[cfg(feature = "crypto")]
+    fn key(hex_str: &str) -> [u8; 16] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn parses_fhdr_fport_and_mic() {
+        let phy = hex::decode(SPEC_UPLINK).unwrap();
+        let frame = DataFrame::parse(&phy).unwrap();
+        assert_eq!(frame.mtype, MType::UnconfirmedDataUp);
+        assert_eq!(frame.direction(), Direction::Uplink);
+        assert_eq!(frame.dev_addr, 0x49BE7DF1);
+        assert_eq!(frame.fctrl, FCtrl(0));
+        assert_eq!(frame.fcnt, 2);
+        assert!(frame.fopts.is_empty());
+        assert_eq!(frame.fport, Some(1));
+        assert_eq!(frame.frm_payload, [0x95, 0x43, 0x78, 0x76]);
+        assert_eq!(frame.mic, [0x2B, 0x11, 0xFF, 0x0D]);
+    }
+
+    #[test]
+    fn parses_fopts_and_frames_without_fport() {
+        // Confirmed up, ADR + ACK, two FOpts bytes (LinkCheckReq, DevStatusAns cid), no FPort.
+        let phy = [
+            0x80, 0x04, 0x03, 0x02, 0x01, 0xA2, 0x34, 0x12, 0x02, 0x06, 0xAA, 0xBB, 0xCC, 0xDD,
+        ];
+        let frame = DataFrame::parse(&phy).unwrap();
+        assert!(frame.is_confirmed());
+        assert!(frame.fctrl.adr() && frame.fctrl.ack() && !frame.fctrl.adr_ack_req());
+        assert_eq!(frame.fcnt, 0x1234);
+        assert_eq!(frame.fopts, [0x02, 0x06]);
+        assert_eq!(frame.fport, None);
+        assert!(frame.frm_payload.is_empty());
+
+        // FOptsLen pointing past the MIC.
+        let mut bad = phy;
+        bad[5] = 0x0F;
+        assert!(DataFrame::parse(&bad).is_err());
+        // Join accept is not a data frame.
+        assert!(matches!(
+            DataFrame::parse(&[0x20; 16]),
+            Err(LoRaError::InvalidMhdr(0x20))
+        ));
+    }
+
+    #[test]
+    fn fcnt_extension_handles_rollover() {
+        assert_eq!(extend_fcnt(7, None), 7);
+        assert_eq!(extend_fcnt(0x0010, Some(0x0000_000F)), 0x10);
+        assert_eq!(extend_fcnt(0x0002, Some(0x0000_FFFE)), 0x0001_0002);
+        assert_eq!(extend_fcnt(0x0005, Some(0x0003_0005)), 0x0003_0005);
+    }
+
+    #[cfg(feature = "crypto")]
+    fn store() -> SessionStore {
+        let mut store = SessionStore::new();
+        store.insert(DeviceSession::new(
+            0x49BE7DF1,
+            SessionKeys {
+                nwk_s_key: key(NWK_S_KEY),
+                app_s_key: key(APP_S_KEY),
+            },
+        ));
+        store
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn decodes_reference_uplink() {
+        let mut store = store();
+        let up = store
+            .decode_uplink(&hex::decode(SPEC_UPLINK).unwrap())
+            .unwrap();
+        assert_eq!(up.fcnt, 2);
+        assert_eq!(up.payload, b"test");
+        assert_eq!(store.get(0x49BE7DF1).unwrap().fcnt_up, Some(2));
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn rejects_bad_mic_replay_and_unknown_device() {
+        let mut store = store();
+        let phy = hex::decode(SPEC_UPLINK).unwrap();
+
+        let mut tampered = phy.clone();
+        tampered[9] ^= 0x01;
+        assert!(matches!(
+            store.decode_uplink(&tampered),
+            Err(LoRaError::MicMismatch { .. })
+        ));
+        assert_eq!(store.get(0x49BE7DF1).unwrap().fcnt_up, None);
+
+        store.decode_uplink(&phy).unwrap();
+        assert!(matches!(
+            store.decode_uplink(&phy),
+            Err(LoRaError::FCntReplay {
+                fcnt: 2,
+                last: 2,
+                ..
+            })
+        ));
+
+        store.remove(0x49BE7DF1);
+        assert!(matches!(
+            store.decode_uplink(&phy),
+            Err(LoRaError::DeviceNotFound)
+        ));
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn fport_zero_is_decrypted_with_nwk_s_key() {
+        let keys = SessionKeys {
+            nwk_s_key: key(NWK_S_KEY),
+            app_s_key: key(APP_S_KEY),
+        };
+        let dev_addr: u32 = 0x0102_0304;
+        let fcnt: u32 = 0x0001_0005;
+        let mac = [0x02u8, 0x06, 0xFF, 0x20];
+
+        let mut phy = vec![MType::UnconfirmedDataUp.mhdr()];
+        phy.extend_from_slice(&dev_addr.to_le_bytes());
+        phy.push(0x00);
+        phy.extend_from_slice(&(fcnt as u16).to_le_bytes());
+        phy.push(0);
+        phy.extend(frm_payload_crypt(
+            &keys.nwk_s_key,
+            Direction::Uplink,
+            dev_addr,
+            fcnt,
+            &mac,
+        ));
+        let mic = WMBusCrypto::new(AesKey::from_bytes(&[0u8; 16]).unwrap())
+            .calculate_lorawan_mic(&keys.nwk_s_key, &phy, 0, dev_addr, fcnt)
+            .unwrap();
+        phy.extend_from_slice(&mic);
+
+        let mut store = SessionStore::new();
+        let mut session = DeviceSession::new(dev_addr, keys);
+        session.fcnt_up = Some(0xFFF0);
+        store.insert(session);
+
+        let up = store.decode_uplink(&phy).unwrap();
+        assert_eq!(up.fcnt, fcnt);
+        assert_eq!(up.frame.fport, Some(0));
+        assert_eq!(up.payload, mac);
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn downlink_carries_mac_commands_in_fopts_or_fport_zero() {
+        let mut store = store();
+        let link_adr = MacCommand::LinkAdrReq {
+            data_rate: 5,
+            tx_power: 1,
+            ch_mask: 0x0007,
+            ch_mask_cntl: 0,
+            nb_trans: 1,
+        };
+        let phy = store
+            .build_downlink(
+                0x49BE7DF1,
+                &Downlink {
+                    ack: true,
+                    mac_commands: vec![link_adr, MacCommand::DevStatusReq],
+                    fport: Some(1),
+                    payload: b"on".to_vec(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let frame = DataFrame::parse(&phy).unwrap();
+        assert_eq!(frame.mtype, MType::UnconfirmedDataDown);
+        assert!(frame.fctrl.ack());
+        assert_eq!(frame.fcnt, 0);
+        assert_eq!(
+            frame.fopts_commands().unwrap(),
+            [link_adr, MacCommand::DevStatusReq]
+        );
+        let keys = &store.get(0x49BE7DF1).unwrap().keys;
+        let mic = WMBusCrypto::new(AesKey::from_bytes(&[0u8; 16]).unwrap())
+            .calculate_lorawan_mic(&keys.nwk_s_key, &phy[..phy.len() - 4], 1, 0x49BE7DF1, 0)
+            .unwrap();
+        assert_eq!(frame.mic, mic);
+        assert_eq!(
+            frm_payload_crypt(
+                &keys.app_s_key,
+                Direction::Downlink,
+                0x49BE7DF1,
+                0,
+                &frame.frm_payload
+            ),
+            b"on"
+        );
+
+        // Four LinkADRReqs (20 bytes) overflow FOpts and move to FPort 0.
+        let many = Downlink {
+            mac_commands: vec![link_adr; 4],
+            ..Default::default()
+        };
+        let frame = DataFrame::parse(&store.build_downlink(0x49BE7DF1, &many).unwrap()).unwrap();
+        assert_eq!(frame.fcnt, 1);
+        assert_eq!(frame.fport, Some(0));
+        assert!(frame.fopts.is_empty());
+        let nwk_s_key = store.get(0x49BE7DF1).unwrap().keys.nwk_s_key;
+        let plain = frm_payload_crypt(
+            &nwk_s_key,
+            Direction::Downlink,
+            0x49BE7DF1,
+            1,
+            &frame.frm_payload,
+        );
+        assert_eq!(
+            mac::decode(&plain, Direction::Downlink).unwrap(),
+            vec![link_adr; 4]
+        );
+
+        let crowded = Downlink {
+            fport: Some(2),
+            payload: vec![1],
+            ..many
+        };
+        assert!(matches!(
+            store.build_downlink(0x49BE7DF1, &crowded),
+            Err(LoRaError::FOptsOverflow { len: 20 })
+        ));
+    }
+}
@@ -0,0 +1,491 @@
+//! LoRaWAN 1.0.x MAC commands
+//!
+//! Codec for the MAC commands carried in FOpts or in an FPort 0 FRMPayload.
+//! Requests and answers share a CID, so decoding needs the link direction:
+//! uplinks carry the device's requests and answers, downlinks the network's.
+//!
+//! | CID  | Command       | Uplink payload | Downlink payload |
+//! |------|---------------|----------------|------------------|
+//! | 0x02 | LinkCheck     | 0              | 2                |
+//! | 0x03 | LinkADR       | 1              | 4                |
+//! | 0x04 | DutyCycle     | 0              | 1                |
+//! | 0x05 | RXParamSetup  | 1              | 4                |
+//! | 0x06 | DevStatus     | 2              | 0                |
+//! | 0x07 | NewChannel    | 1              | 5                |
+//! | 0x08 | RXTimingSetup | 0              | 1                |
+//! | 0x09 | TxParamSetup  | 0              | 1                |
+//! | 0x0A | DlChannel     | 1              | 4                |
+
+use super::lorawan::Direction;
+use thiserror::Error;
+
+/// MAC command codec errors.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MacError {
+    #[error("Unknown MAC command CID {cid:#04X}")]
+    UnknownCid { cid: u8 },
+    #[error("MAC command CID {cid:#04X} needs {needed} payload bytes, {available} left")]
+    Truncated {
+        cid: u8,
+        needed: usize,
+        available: usize,
+    },
+}
+
+/// A single MAC command, request or answer.
+///
+/// Frequencies are in Hz and travel on air as 24-bit multiples of 100 Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacCommand {
+    // Sent by the device
+    LinkCheckReq,
+    LinkAdrAns {
+        power_ack: bool,
+        data_rate_ack: bool,
+        channel_mask_ack: bool,
+    },
+    DutyCycleAns,
+    RxParamSetupAns {
+        rx1_dr_offset_ack: bool,
+        rx2_data_rate_ack: bool,
+        channel_ack: bool,
+    },
+    DevStatusAns(DevStatus),
+    NewChannelAns {
+        data_rate_range_ok: bool,
+        channel_frequency_ok: bool,
+    },
+    RxTimingSetupAns,
+    TxParamSetupAns,
+    DlChannelAns {
+        uplink_frequency_exists: bool,
+        channel_frequency_ok: bool,
+    },
+
+    // Sent by the network
+    LinkCheckAns {
+        margin: u8,
+        gw_cnt: u8,
+    },
+    LinkAdrReq {
+        data_rate: u8,
+        tx_power: u8,
+        ch_mask: u16,
+        ch_mask_cntl: u8,
+        nb_trans: u8,
+    },
+    DutyCycleReq {
+        max_duty_cycle: u8,
+    },
+    RxParamSetupReq {
+        rx1_dr_offset: u8,
+        rx2_data_rate: u8,
+        frequency_hz: u32,
+    },
+    DevStatusReq,
+    NewChannelReq {
+        ch_index: u8,
+        frequency_hz: u32,
+        min_dr: u8,
+        max_dr: u8,
+    },
+    RxTimingSetupReq {
+        delay: u8,
+    },
+    TxParamSetupReq {
+        downlink_dwell_time: bool,
+        uplink_dwell_time: bool,
+        max_eirp: u8,
+    },
+    DlChannelReq {
+        ch_index: u8,
+        frequency_hz: u32,
+    },
+}
+
+/// Battery level and demodulation margin reported in a DevStatusAns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DevStatus {
+    /// 0 = external power, 1..=254 = battery level, 255 = not measurable.
+    pub battery: u8,
+    /// SNR of the last DevStatusReq in dB, -32..=31.
+    pub margin: i8,
+}
+
+impl DevStatus {
+    pub const EXTERNAL_POWER: u8 = 0;
+    pub const BATTERY_UNKNOWN: u8 = 255;
+
+    pub fn is_external_power(&self) -> bool {
+        self.battery == Self::EXTERNAL_POWER
+    }
+
+    /// Battery level scaled to 0-100 %, `None` on external power or when unknown.
+    pub fn battery_percentage(&self) -> Option<u8> {
+        match self.battery {
+            Self::EXTERNAL_POWER | Self::BATTERY_UNKNOWN => None,
+            level => Some(((level as u16 - 1) * 100 / 253) as u8),
+        }
+    }
+}
+
+impl MacCommand {
+    /// Command identifier.
+    pub fn cid(&self) -> u8 {
+        match self {
+            Self::LinkCheckReq | Self::LinkCheckAns { .. } => 0x02,
+            Self::LinkAdrAns { .. } | Self::LinkAdrReq { .. } => 0x03,
+            Self::DutyCycleAns | Self::DutyCycleReq { .. } => 0x04,
+            Self::RxParamSetupAns { .. } | Self::RxParamSetupReq { .. } => 0x05,
+            Self::DevStatusAns(_) | Self::DevStatusReq => 0x06,
+            Self::NewChannelAns { .. } | Self::NewChannelReq { .. } => 0x07,
+            Self::RxTimingSetupAns | Self::RxTimingSetupReq { .. } => 0x08,
+            Self::TxParamSetupAns | Self::TxParamSetupReq { .. } => 0x09,
+            Self::DlChannelAns { .. } | Self::DlChannelReq { .. } => 0x0A,
+        }
+    }
+
+    /// Direction the command travels in.
+    pub fn direction(&self) -> Direction {
+        match self {
+            Self::LinkCheckReq
+            | Self::LinkAdrAns { .. }
+            | Self::DutyCycleAns
+            | Self::RxParamSetupAns { .. }
+            | Self::DevStatusAns(_)
+            | Self::NewChannelAns { .. }
+            | Self::RxTimingSetupAns
+            | Self::TxParamSetupAns
+            | Self::DlChannelAns { .. } => Direction::Uplink,
+            _ => Direction::Downlink,
+        }
+    }
+
+    /// Append CID and payload to `out`.
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        out.push(self.cid());
+        match *self {
+            Self::LinkCheckReq
+            | Self::DutyCycleAns
+            | Self::RxTimingSetupAns
+            | Self::TxParamSetupAns
+            | Self::DevStatusReq => {}
+            Self::LinkAdrAns {
+                power_ack,
+                data_rate_ack,
+                channel_mask_ack,
+            } => out.push(bits(&[power_ack, data_rate_ack, channel_mask_ack])),
+            Self::RxParamSetupAns {
+                rx1_dr_offset_ack,
+                rx2_data_rate_ack,
+                channel_ack,
+            } => out.push(bits(&[rx1_dr_offset_ack, rx2_data_rate_ack, channel_ack])),
+            Self::DevStatusAns(status) => {
+                out.push(status.battery);
+                out.push((status.margin as u8) & 0x3F);
+            }
+            Self::NewChannelAns {
+                data_rate_range_ok,
+                channel_frequency_ok,
+            } => out.push(bits(&[data_rate_range_ok, channel_frequency_ok])),
+            Self::DlChannelAns {
+                uplink_frequency_exists,
+                channel_frequency_ok,
+            } => out.push(bits(&[uplink_frequency_exists, channel_frequency_ok])),
+            Self::LinkCheckAns { margin, gw_cnt } => out.extend_from_slice(&[margin, gw_cnt]),
+            Self::LinkAdrReq {
+                data_rate,
+                tx_power,
+                ch_mask,
+                ch_mask_cntl,
+                nb_trans,
+            } => {
+                out.push((data_rate << 4) | (tx_power & 0x0F));
+                out.extend_from_slice(&ch_mask.to_le_bytes());
+                out.push(((ch_mask_cntl & 0x07) << 4) | (nb_trans & 0x0F));
+            }
+            Self::DutyCycleReq { max_duty_cycle } => out.push(max_duty_cycle & 0x0F),
+            Self::RxParamSetupReq {
+                rx1_dr_offset,
+                rx2_data_rate,
+                frequency_hz,
+            } => {
+                out.push(((rx1_dr_offset & 0x07) << 4) | (rx2_data_rate & 0x0F));
+                push_frequency(out, frequency_hz);
+            }
+            Self::NewChannelReq {
+                ch_index,
+                frequency_hz,
+                min_dr,
+                max_dr,
+            } => {
+                out.push(ch_index);
+                push_frequency(out, frequency_hz);
+                out.push((max_dr << 4) | (min_dr & 0x0F));
+            }
+            Self::RxTimingSetupReq { delay } => out.push(delay & 0x0F),
+            Self::TxParamSetupReq {
+                downlink_dwell_time,
+                uplink_dwell_time,
+                max_eirp,
+            } => out.push(
+                ((downlink_dwell_time as u8) << 5)
+                    | ((uplink_dwell_time as u8) << 4)
+                    | (max_eirp & 0x0F),
+            ),
+            Self::DlChannelReq {
+                ch_index,
+                frequency_hz,
+            } => {
+                out.push(ch_index);
+                push_frequency(out, frequency_hz);
+            }
+        }
+    }
+}
+
+/// Encode a list of MAC commands back to back.
+pub fn encode(commands: &[MacCommand]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for command in commands {
+        command.encode_into(&mut out);
+    }
+    out
+}
+
+/// Decode all MAC commands in an FOpts field or FPort 0 payload.
+///
+/// Parsing stops at the first unknown CID since its length cannot be known.
+pub fn decode(data: &[u8], direction: Direction) -> Result<Vec<MacCommand>, MacError> {
+    let mut commands = Vec::new();
+    let mut rest = data;
+    while let Some((&cid, payload)) = rest.split_first() {
+        let needed = payload_len(cid, direction).ok_or(MacError::UnknownCid { cid })?;
+        if payload.len() < needed {
+            return Err(MacError::Truncated {
+                cid,
+                needed,
+                available: payload.len(),
+            });
+        }
+        let (p, tail) = payload.split_at(needed);
+        commands.push(decode_one(cid, p, direction));
+        rest = tail;
+    }
+    Ok(commands)
+}
+
+fn payload_len(cid: u8, direction: Direction) -> Option<usize> {
+    let (up, down) = match cid {
+        0x02 => (0, 2),
+        0x03 => (1, 4),
+        0x04 => (0, 1),
+        0x05 => (1, 4),
+        0x06 => (2, 0),
+        0x07 => (1, 5),
+        0x08 => (0, 1),
+        0x09 => (0, 1),
+        0x0A => (1, 4),
+        _ => return None,
+    };
+    Some(match direction {
+        Direction::Uplink => up,
+        Direction::Downlink => down,
+    })
+}
+
+/// `p` has exactly `payload_len(cid, direction)` bytes.
+fn decode_one(cid: u8, p: &[u8], direction: Direction) -> MacCommand {
+    let bit = |byte: u8, n: u8| byte & (1 << n) != 0;
+    match (direction, cid) {
+        (Direction::Uplink, 0x02) => MacCommand::LinkCheckReq,
+        (Direction::Uplink, 0x03) => MacCommand::LinkAdrAns {
+            power_ack: bit(p[0], 2),
+            data_rate_ack: bit(p[0], 1),
+            channel_mask_ack: bit(p[0], 0),
+        },
+        (Direction::Uplink, 0x04) => MacCommand::DutyCycleAns,
+        (Direction::Uplink, 0x05) => MacCommand::RxParamSetupAns {
+            rx1_dr_offset_ack: bit(p[0], 2),
+            rx2_data_rate_ack: bit(p[0], 1),
+            channel_ack: bit(p[0], 0),
+        },
+        (Direction::Uplink, 0x06) => MacCommand::DevStatusAns(DevStatus {
+            battery: p[0],
+            // Sign-extend the 6-bit margin.
+            margin: ((p[1] << 2) as i8) >> 2,
+        }),
+        (Direction::Uplink, 0x07) => MacCommand::NewChannelAns {
+            data_rate_range_ok: bit(p[0], 1),
+            channel_frequency_ok: bit(p[0], 0),
+        },
+        (Direction::Uplink, 0x08) => MacCommand::RxTimingSetupAns,
+        (Direction::Uplink, 0x09) => MacCommand::TxParamSetupAns,
+        (Direction::Uplink, _) => MacCommand::DlChannelAns {
+            uplink_frequency_exists: bit(p[0], 1),
+            channel_frequency_ok: bit(p[0], 0),
+        },
+        (Direction::Downlink, 0x02) => MacCommand::LinkCheckAns {
+            margin: p[0],
+            gw_cnt: p[1],
+        },
+        (Direction::Downlink, 0x03) => MacCommand::LinkAdrReq {
+            data_rate: p[0] >> 4,
+            tx_power: p[0] & 0x0F,
+            ch_mask: u16::from_le_bytes([p[1], p[2]]),
+            ch_mask_cntl: (p[3] >> 4) & 0x07,
+            nb_trans: p[3] & 0x0F,
+        },
+        (Direction::Downlink, 0x04) => MacCommand::DutyCycleReq {
+            max_duty_cycle: p[0] & 0x0F,
+        },
+        (Direction::Downlink, 0x05) => MacCommand::RxParamSetupReq {
+            rx1_dr_offset: (p[0] >> 4) & 0x07,
+            rx2_data_rate: p[0] & 0x0F,
+            frequency_hz: read_frequency(&p[1..4]),
+        },
+        (Direction::Downlink, 0x06) => MacCommand::DevStatusReq,
+        (Direction::Downlink, 0x07) => MacCommand::NewChannelReq {
+            ch_index: p[0],
+            frequency_hz: read_frequency(&p[1..4]),
+            min_dr: p[4] & 0x0F,
+            max_dr: p[4] >> 4,
+        },
+        (Direction::Downlink, 0x08) => MacCommand::RxTimingSetupReq { delay: p[0] & 0x0F },
+        (Direction::Downlink, 0x09) => MacCommand::TxParamSetupReq {
+            downlink_dwell_time: bit(p[0], 5),
+            uplink_dwell_time: bit(p[0], 4),
+            max_eirp: p[0] & 0x0F,
+        },
+        (Direction::Downlink, _) => MacCommand::DlChannelReq {
+            ch_index: p[0],
+            frequency_hz: read_frequency(&p[1..4]),
+        },
+    }
+}
+
+/// Pack flags MSB first into the low bits of a byte.
+fn bits(flags: &[bool]) -> u8 {
+    flags.iter().fold(0, |acc, &f| (acc << 1) | f as u8)
+}
+
+fn push_frequency(out: &mut Vec<u8>, frequency_hz: u32) {
+    out.extend_from_slice(&(frequency_hz / 100).to_le_bytes()[..3]);
+}
+
+fn read_frequency(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], 0]) * 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downlink_commands_round_trip() {
+        let commands = [
+            MacCommand::LinkCheckAns {
+                margin: 20,
+                gw_cnt: 1,
+            },
+            MacCommand::LinkAdrReq {
+                data_rate: 5,
+                tx_power: 2,
+                ch_mask: 0x0007,
+                ch_mask_cntl: 0,
+                nb_trans: 1,
+            },
+            MacCommand::DutyCycleReq { max_duty_cycle: 7 },
+            MacCommand::RxParamSetupReq {
+                rx1_dr_offset: 1,
+                rx2_data_rate: 3,
+                frequency_hz: 869_525_000,
+            },
+            MacCommand::DevStatusReq,
+            MacCommand::NewChannelReq {
+                ch_index: 3,
+                frequency_hz: 867_100_000,
+                min_dr: 0,
+                max_dr: 5,
+            },
+            MacCommand::RxTimingSetupReq { delay: 1 },
+            MacCommand::TxParamSetupReq {
+                downlink_dwell_time: true,
+                uplink_dwell_time: false,
+                max_eirp: 5,
+            },
+            MacCommand::DlChannelReq {
+                ch_index: 0,
+                frequency_hz: 868_100_000,
+            },
+        ];
+        let bytes = encode(&commands);
+        assert_eq!(&bytes[3..8], &[0x03, 0x52, 0x07, 0x00, 0x01]);
+        // 869.525 MHz = 8695250 × 100 Hz = 0x84ADD2, sent LSB first
+        assert_eq!(&bytes[10..15], &[0x05, 0x13, 0xD2, 0xAD, 0x84]);
+        assert_eq!(decode(&bytes, Direction::Downlink).unwrap(), commands);
+    }
+
+    #[test]
+    fn uplink_commands_round_trip() {
+        let commands = [
+            MacCommand::LinkCheckReq,
+            MacCommand::LinkAdrAns {
+                power_ack: true,
+                data_rate_ack: true,
+                channel_mask_ack: false,
+            },
+            MacCommand::DutyCycleAns,
+            MacCommand::RxParamSetupAns {
+                rx1_dr_offset_ack: true,
+                rx2_data_rate_ack: false,
+                channel_ack: true,
+            },
+            MacCommand::DevStatusAns(DevStatus {
+                battery: 127,
+                margin: -5,
+            }),
+            MacCommand::NewChannelAns {
+                data_rate_range_ok: true,
+                channel_frequency_ok: true,
+            },
+            MacCommand::RxTimingSetupAns,
+            MacCommand::TxParamSetupAns,
+            MacCommand::DlChannelAns {
+                uplink_frequency_exists: false,
+                channel_frequency_ok: true,
+            },
+        ];
+        let bytes = encode(&commands);
+        assert_eq!(&bytes[..3], &[0x02, 0x03, 0x06]);
+        assert_eq!(&bytes[6..9], &[0x06, 127, 0x3B]);
+        assert_eq!(decode(&bytes, Direction::Uplink).unwrap(), commands);
+        assert!(commands.iter().all(|c| c.direction() == Direction::Uplink));
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_commands() {
+        assert_eq!(
+            decode(&[0x02, 0x80], Direction::Uplink),
+            Err(MacError::UnknownCid { cid: 0x80 })
+        );
+        assert_eq!(
+            decode(&[0x03, 0x50, 0x07], Direction::Downlink),
+            Err(MacError::Truncated {
+                cid: 0x03,
+                needed: 4,
+                available: 2
+            })
+        );
+    }
+
+    #[test]
+    fn dev_status_battery_scale() {
+        let status = |battery| DevStatus { battery, margin: 0 };
+        assert_eq!(status(0).battery_percentage(), None);
+        assert!(status(0).is_external_power());
+        assert_eq!(status(1).battery_percentage(), Some(0));
+        assert_eq!(status(254).battery_percentage(), Some(100));
+        assert_eq!(status(255).battery_percentage(), None);
+    }
+}
@@ -2,7 +2,9 @@
 //!
 //! This module provides LoRa-specific types and utilities for the SX126x driver.
 //! It includes parameter definitions, packet parsing, payload decoders, and helpers
-//! for OTAA/ABP handling in non-LoRaWAN metering gateways.
+//! for OTAA/ABP handling in metering gateways, including LoRaWAN 1.0.x data
+//! frame decoding with per-device session keys and regional parameters for
+//! EU868, US915, AU915, AS923, IN865 and KR920.
 
 pub mod adr;
 pub mod cad;
@@ -13,17 +15,29 @@ pub mod decoder_nom;
 pub mod decoders;
 pub mod duty_cycle;
 pub mod format_detector;
+pub mod gwmp;
 pub mod irq_queue;
+#[cfg(feature = "crypto")]
+pub mod join;
 pub mod lbm;
+pub mod lorawan;
+pub mod mac;
 pub mod packet;
 pub mod params;
+pub mod region;
 pub mod single_channel;
 pub mod smart_decoder;
 
-pub use adr::{AdrConfig, AdrController, AdrDecision, AdrReason, SignalMetrics};
+pub use adr::{AdrConfig, AdrController, AdrDecision, AdrReason, LinkAdrParams, SignalMetrics};
 pub use cad::{CadExitMode, CadStats, LoRaCadParams};
 pub use channel_hopping::{Channel, ChannelHopper, ChannelStats, HoppingStrategy};
-pub use class_bc::{BeaconConfig, ClassBCController, ClassBCStatus, DeviceClass, MulticastSession};
+pub use class_bc::{
+    beacon_airtime, beacon_time, gps_time, BeaconConfig, BeaconFrame, ClassBCController,
+    ClassBCError, ClassBCStatus, DeviceClass, DownlinkScheduler, GwSpecific, MulticastSession,
+    QueuedDownlink, ScheduledDownlink, TxTiming,
+};
+#[cfg(feature = "crypto")]
+pub use class_bc::{next_ping_slot, ping_offset, ping_slots};
 pub use decoder::{
     BatteryStatus, DecentlabConfig, DecoderType, DeviceStatus, DraginoModel, ElvacoModel,
     GenericCounterConfig, LoRaDecodeError, LoRaDeviceManager, LoRaPayloadDecoder, MeteringData,
@@ -32,13 +46,32 @@ pub use decoder::{
 pub use decoder_nom::NomDecoderAdapter;
 pub use duty_cycle::{DutyCycleManager, PowerMode, PowerStats};
 pub use format_detector::{Confidence, DetectionResult, FormatDetector};
+pub use gwmp::{
+    ForwarderConfig, ForwarderEvent, GwmpError, GwmpPacket, GwmpServer, PacketForwarder, RxPk,
+    ServerEvent, TxPk,
+};
 pub use irq_queue::{irq_processor_task, IrqEvent, IrqEventQueue, IrqStats};
-pub use lbm::{LbmCore, MeshMessage, MeshStats, NodeInfo, QoS};
+#[cfg(feature = "crypto")]
+pub use join::{
+    derive_session_keys, DevNonceStore, DeviceCredentials, JoinAccept, JoinServer,
+    JoinServerConfig, MemoryDevNonceStore,
+};
+pub use lbm::{
+    node_id, topic_hash, FrameKind, LbmCore, MeshFrame, MeshFrameError, MeshMessage, MeshStats,
+    MeshTransport, NodeInfo, QoS, RadioMeshTransport, Route, BROADCAST_ID,
+};
+pub use lorawan::{
+    extend_fcnt, DataFrame, DeviceSession, Direction, FCtrl, JoinRequestFrame, MType, SessionKeys,
+};
+#[cfg(feature = "crypto")]
+pub use lorawan::{frm_payload_crypt, Downlink, SessionStore, Uplink};
+pub use mac::{DevStatus, MacCommand, MacError};
 pub use packet::{
     build_trigger_frame, calc_cumulative_delta, decode_lora_packet, parse_abp_data, parse_otaa_join,
 };
 pub use params::{
     CodingRate, LoRaBandwidth, LoRaModParams, LoRaModParamsExt, LoRaPacketParams, SpreadingFactor,
 };
+pub use region::{DataRateParams, DutyCycleBand, Region, RegionChannel};
 pub use single_channel::{DutyCycleLimiter, SingleChannelConfig};
 pub use smart_decoder::{DeviceStats, SmartDecoder};
@@ -1,3 +1,6 @@
+use super::lorawan::{DataFrame, Direction, JOIN_REQUEST_LEN, MIC_LEN};
+use super::mac::MacError;
+use crate::wmbus::crypto::CryptoError;
 use crate::wmbus::radio::modulation::LoRaPacketStatus;
 use ciborium::de::from_reader;
 use serde::{Deserialize, Serialize};
@@ -7,7 +10,7 @@ use thiserror::Error;
 /// Errors for LoRa packet parsing and handling.
 #[derive(Error, Debug)]
 pub enum LoRaError {
-    #[error("Invalid MHDR: {0:#X} (expected 0x00 for JoinReq or 0x20/0x80 for DataUp)")]
+    #[error("Invalid MHDR: {0:#X} (expected 0x00 for JoinReq or 0x40/0x80 for DataUp)")]
     InvalidMhdr(u8),
     #[error("CRC failure")]
     CrcFail,
@@ -15,6 +18,22 @@ pub enum LoRaError {
     Parse(String),
     #[error("Device not found")]
     DeviceNotFound,
+    #[error("MIC mismatch for DevAddr {dev_addr:08X}")]
+    MicMismatch { dev_addr: u32 },
+    #[error("FCnt {fcnt} from DevAddr {dev_addr:08X} not above last accepted {last}")]
+    FCntReplay { dev_addr: u32, fcnt: u32, last: u32 },
+    #[error("JoinRequest MIC mismatch for DevEUI {dev_eui:016X}")]
+    JoinMicMismatch { dev_eui: u64 },
+    #[error("DevNonce {dev_nonce:#06X} already used by DevEUI {dev_eui:016X}")]
+    DevNonceReused { dev_eui: u64, dev_nonce: u16 },
+    #[error("No free DevAddr left in the NwkID")]
+    DevAddrExhausted,
+    #[error("{len} bytes of MAC commands do not fit in FOpts next to a payload")]
+    FOptsOverflow { len: usize },
+    #[error("MAC command error: {0}")]
+    Mac(#[from] MacError),
+    #[error("Crypto error: {0}")]
+    Crypto(#[from] CryptoError),
     #[error("CBOR decode error: {0}")]
     Cbor(#[from] ciborium::de::Error<std::io::Error>),
     #[error("CBOR encode error: {0}")]
@@ -24,14 +43,21 @@ pub enum LoRaError {
 /// LoRa payload structure after MHDR parsing.
 #[derive(Debug)]
 pub struct LoRaPayload {
-    pub mhdr: u8, // Message Header (0x00 JoinReq, 0x20 UnconfDataUp, 0x80 ConfDataUp)
+    pub mhdr: u8, // Message Header (0x00 JoinReq, 0x40 UnconfDataUp, 0x80 ConfDataUp)
     pub dev_addr: [u8; 4], // Device Address (for ABP)
     pub fctrl: u8, // Frame Control
-    pub fport: u8, // FPort (custom 0xFF for triggers)
-    pub frm_payload: Vec<u8>, // Meter data + schedule (CBOR)
+    pub fcnt: u16, // Frame counter as transmitted (low 16 bits)
+    pub fopts: Vec<u8>, // MAC commands piggybacked in the FHDR
+    pub fport: Option<u8>, // FPort (custom 0xFF for triggers), absent without FRMPayload
+    pub frm_payload: Vec<u8>, // Meter data, encrypted for LoRaWAN devices
+    pub mic: [u8; MIC_LEN], // Message integrity code, unchecked here
 }
 
-/// Parse LoRa packet (basic non-LoRaWAN)
+/// Parse LoRa packet headers without keys.
+///
+/// Data frames are split along the LoRaWAN FHDR; the FRMPayload is returned as
+/// received and the MIC is not verified. Use `lorawan::SessionStore` (feature
+/// `crypto`) to authenticate and decrypt uplinks from standard devices.
 pub fn decode_lora_packet(
     payload: &[u8],
     _status: LoRaPacketStatus,
@@ -43,53 +69,42 @@ pub fn decode_lora_packet(
     let mhdr = payload[0];
     match mhdr {
         0x00 => {
-            // JoinReq (OTAA): MHDR(1) + DevEUI(8) + AppEUI(8) + DevNonce(2) = 19 bytes min,
-            // so byte 18 (the DevNonce high byte) is always in bounds.
+            // JoinReq (OTAA): MHDR(1) + AppEUI(8) + DevEUI(8) + DevNonce(2) = 19 bytes min,
+            // so byte 18 (the DevNonce high byte) is always in bounds. Standard
+            // devices append a 4-byte MIC; older custom firmware a CBOR schedule.
             if payload.len() < 19 {
                 return Err(LoRaError::Parse("Too short for JoinReq".to_string()));
             }
-            let _dev_eui = &payload[1..9]; // 8B
-            let _app_eui = &payload[9..17]; // 8B
-            let _dev_nonce = u16::from_le_bytes([payload[17], payload[18]]); // 2B
-            let frm_payload = payload[19..].to_vec(); // custom schedule CBOR (may be empty)
-
-            // Parse custom schedule from CBOR only if present.
-            if !frm_payload.is_empty() {
-                let _schedule: ScheduleInfo = from_reader(Cursor::new(&frm_payload))?;
-            }
+            let mut mic = [0; MIC_LEN];
+            let frm_payload = if payload.len() == JOIN_REQUEST_LEN {
+                mic.copy_from_slice(&payload[19..]);
+                Vec::new()
+            } else {
+                payload[19..].to_vec()
+            };
             Ok(LoRaPayload {
                 mhdr,
                 dev_addr: [0; 4], // Not in JoinReq
                 fctrl: 0,
-                fport: 0,
+                fcnt: 0,
+                fopts: Vec::new(),
+                fport: None,
                 frm_payload, // schedule only (EUI/nonce stay in the original packet)
+                mic,
             })
         }
-        0x20 | 0x80 => {
-            // Unconf/Conf DataUp (ABP)
-            if payload.len() < 13 {
-                // Min: MHDR + DevAddr(4) + FCtrl(1) + FPort(1) + MIC(4)
-                return Err(LoRaError::Parse("Too short for DataUp".to_string()));
-            }
-            let dev_addr = payload[1..5].try_into().unwrap(); // 4B
-            let fctrl = payload[5];
-            let fport = payload[6];
-            let mic_start = payload.len() - 4; // MIC last 4B
-            let frm_payload = payload[7..mic_start].to_vec(); // Data between FPort and MIC
-
-            // Parse custom schedule from FRMPayload CBOR (if present)
-            let _schedule: Option<ScheduleInfo> = if !frm_payload.is_empty() {
-                Some(from_reader(Cursor::new(&frm_payload))?)
-            } else {
-                None
-            };
-
+        0x40 | 0x80 => {
+            // Unconf/Conf DataUp
+            let frame = DataFrame::parse(payload)?;
             Ok(LoRaPayload {
                 mhdr,
-                dev_addr,
-                fctrl,
-                fport,
-                frm_payload,
+                dev_addr: frame.dev_addr.to_le_bytes(),
+                fctrl: frame.fctrl.0,
+                fcnt: frame.fcnt,
+                fopts: frame.fopts,
+                fport: frame.fport,
+                frm_payload: frame.frm_payload,
+                mic: frame.mic,
             })
         }
         _ => Err(LoRaError::InvalidMhdr(mhdr)),
@@ -97,6 +112,9 @@ pub fn decode_lora_packet(
 }
 
 /// Parse OTAA Join Request
+///
+/// Only extracts the header fields; `join::JoinServer` (feature `crypto`)
+/// verifies the MIC and answers the request.
 pub fn parse_otaa_join(payload: &[u8]) -> Result<JoinRequest, LoRaError> {
     if payload.is_empty() || payload[0] != 0x00 {
         return Err(LoRaError::InvalidMhdr(
@@ -108,12 +126,15 @@ pub fn parse_otaa_join(payload: &[u8]) -> Result<JoinRequest, LoRaError> {
     }
 
     // EUI/nonce come from the JoinReq header itself, not the schedule payload:
-    // MHDR(1) | DevEUI(8) | AppEUI(8) | DevNonce(2) | schedule CBOR...
-    let dev_eui = hex::encode(&payload[1..9]);
-    let app_eui = hex::encode(&payload[9..17]);
+    // MHDR(1) | AppEUI(8) | DevEUI(8) | DevNonce(2) | MIC(4) or schedule CBOR...
+    // EUIs are sent LSB first and reported MSB first, as printed on the device.
+    let eui = |bytes: &[u8]| format!("{:016x}", u64::from_le_bytes(bytes.try_into().unwrap()));
+    let app_eui = eui(&payload[1..9]);
+    let dev_eui = eui(&payload[9..17]);
     let dev_nonce = u16::from_le_bytes([payload[17], payload[18]]);
-    let schedule_info: ScheduleInfo = if payload.len() > 19 {
-        from_reader(Cursor::new(&payload[19..]))?
+    // A standard 23-byte request ends in its MIC; the schedule is best effort.
+    let schedule_info: ScheduleInfo = if payload.len() > 19 && payload.len() != JOIN_REQUEST_LEN {
+        from_reader(Cursor::new(&payload[19..])).unwrap_or_default()
     } else {
         ScheduleInfo::default() // No schedule reported
     };
@@ -128,19 +149,20 @@ pub fn parse_otaa_join(payload: &[u8]) -> Result<JoinRequest, LoRaError> {
 
 /// Parse ABP Data Up
 pub fn parse_abp_data(payload: &[u8]) -> Result<DataPayload, LoRaError> {
-    let decoded = decode_lora_packet(payload, LoRaPacketStatus::default())?;
-    if decoded.mhdr != 0x20 && decoded.mhdr != 0x80 {
-        return Err(LoRaError::InvalidMhdr(decoded.mhdr));
+    let frame = DataFrame::parse(payload)?;
+    if frame.direction() != Direction::Uplink {
+        return Err(LoRaError::InvalidMhdr(payload[0]));
     }
 
-    let dev_addr = hex::encode(decoded.dev_addr);
-    let fport = decoded.fport;
-    let meter_data = decoded.frm_payload.clone(); // Raw meter data
-    let schedule_info: Option<ScheduleInfo> = if !decoded.frm_payload.is_empty() {
-        Some(from_reader(Cursor::new(&decoded.frm_payload))?)
+    let dev_addr = hex::encode(frame.dev_addr.to_le_bytes());
+    let fport = frame.fport;
+    // Custom schedule CBOR is optional; plain LoRaWAN payloads are kept as-is.
+    let schedule_info: Option<ScheduleInfo> = if !frame.frm_payload.is_empty() {
+        from_reader(Cursor::new(&frame.frm_payload)).ok()
     } else {
         None
     };
+    let meter_data = frame.frm_payload; // Raw meter data
 
     Ok(DataPayload {
         dev_addr,
@@ -200,7 +222,7 @@ pub struct JoinRequest {
 #[derive(Debug, Clone)]
 pub struct DataPayload {
     pub dev_addr: String, // Hex string
-    pub fport: u8,
+    pub fport: Option<u8>,
     pub meter_data: Vec<u8>, // Raw binary (wM-Bus-like records)
     pub schedule_info: Option<ScheduleInfo>,
 }
@@ -219,14 +241,38 @@ mod join_parse_tests {
 
     #[test]
     fn parse_otaa_join_reads_fields_from_header_not_schedule() {
-        // MHDR(0x00) + DevEUI(8) + AppEUI(8) + DevNonce(2 LE), no schedule.
+        // MHDR(0x00) + AppEUI(8 LE) + DevEUI(8 LE) + DevNonce(2 LE), no schedule.
         let mut p = vec![0x00u8];
-        p.extend_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]); // DevEUI
-        p.extend_from_slice(&[0xA1, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7, 0xA8]); // AppEUI
+        p.extend_from_slice(&[0xA8, 0xA7, 0xA6, 0xA5, 0xA4, 0xA3, 0xA2, 0xA1]); // AppEUI
+        p.extend_from_slice(&[0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11]); // DevEUI
         p.extend_from_slice(&[0xCD, 0xAB]); // DevNonce LE = 0xABCD
         let jr = parse_otaa_join(&p).expect("valid 19-byte join");
         assert_eq!(jr.dev_eui, "1122334455667788");
         assert_eq!(jr.app_eui, "a1a2a3a4a5a6a7a8");
         assert_eq!(jr.dev_nonce, 0xABCD);
+
+        // With the trailing MIC of a standard device the schedule stays empty.
+        p.extend_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+        let jr = parse_otaa_join(&p).expect("valid 23-byte join");
+        assert!(jr.schedule_info.tx_interval_min.is_none());
+        let decoded = decode_lora_packet(&p, LoRaPacketStatus::default()).unwrap();
+        assert_eq!(decoded.mic, [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert!(decoded.frm_payload.is_empty());
+    }
+
+    #[test]
+    fn data_up_splits_fhdr_without_requiring_cbor() {
+        // Unconfirmed up, DevAddr 0x49BE7DF1, FCnt 2, FPort 1, encrypted "test".
+        let p = hex::decode("40f17dbe4900020001954378762b11ff0d").unwrap();
+        let decoded = decode_lora_packet(&p, LoRaPacketStatus::default()).unwrap();
+        assert_eq!(decoded.dev_addr, [0xF1, 0x7D, 0xBE, 0x49]);
+        assert_eq!(decoded.fcnt, 2);
+        assert_eq!(decoded.fport, Some(1));
+        assert_eq!(decoded.frm_payload, [0x95, 0x43, 0x78, 0x76]);
+        assert_eq!(decoded.mic, [0x2B, 0x11, 0xFF, 0x0D]);
+
+        let data = parse_abp_data(&p).unwrap();
+        assert_eq!(data.dev_addr, "f17dbe49");
+        assert!(data.schedule_info.is_none());
     }
 }
@@ -0,0 +1,602 @@
+//! LoRaWAN regional parameters
+//!
+//! Channel plans, data rates, transmit power steps, receive window defaults
+//! and duty-cycle/dwell-time rules from the LoRaWAN Regional Parameters
+//! (RP002-1.0.x) for the regions we deploy in. ADR, channel hopping and the
+//! single-channel duty-cycle limiter take their limits from here instead of
+//! hard-coding EU868 values.
+//!
+//! | Region | Uplink DRs | MaxEIRP | RX2               | Duty cycle | Uplink dwell |
+//! |--------|------------|---------|-------------------|------------|--------------|
+//! | EU868  | DR0-DR5    | 16 dBm  | 869.525 MHz, DR0  | 0.1-10 %   | -            |
+//! | US915  | DR0-DR4    | 30 dBm  | 923.3 MHz, DR8    | -          | 400 ms       |
+//! | AU915  | DR0-DR6    | 30 dBm  | 923.3 MHz, DR8    | -          | 400 ms       |
+//! | AS923  | DR0-DR5    | 16 dBm  | 923.2 MHz, DR2    | -          | 400 ms       |
+//! | IN865  | DR0-DR5    | 30 dBm  | 866.55 MHz, DR2   | -          | -            |
+//! | KR920  | DR0-DR5    | 14 dBm  | 921.9 MHz, DR0    | -          | -            |
+//!
+//! Only LoRa data rates are modelled; FSK (DR7 in EU868/AS923/IN865) and
+//! LR-FHSS are not.
+
+use crate::wmbus::radio::modulation::{LoRaBandwidth, SpreadingFactor};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// TXPower index n means MaxEIRP - 2n dB in every supported region
+const TX_POWER_STEP_DB: i8 = 2;
+
+/// Maximum time on air per uplink where a dwell-time limit applies
+const DWELL_TIME_LIMIT: Duration = Duration::from_millis(400);
+
+/// First RX1 downlink channel of the fixed channel plans (US915/AU915)
+const FIXED_PLAN_RX1_BASE_HZ: u32 = 923_300_000;
+
+/// ETSI EN 300 220 sub-bands used by EU868 devices
+const EU868_DUTY_CYCLE_BANDS: [DutyCycleBand; 6] = [
+    DutyCycleBand {
+        min_hz: 863_000_000,
+        max_hz: 865_000_000,
+        duty_cycle_percent: 0.1,
+    },
+    DutyCycleBand {
+        min_hz: 865_000_000,
+        max_hz: 868_000_000,
+        duty_cycle_percent: 1.0,
+    },
+    DutyCycleBand {
+        min_hz: 868_000_000,
+        max_hz: 868_600_000,
+        duty_cycle_percent: 1.0,
+    },
+    DutyCycleBand {
+        min_hz: 868_700_000,
+        max_hz: 869_200_000,
+        duty_cycle_percent: 0.1,
+    },
+    DutyCycleBand {
+        min_hz: 869_400_000,
+        max_hz: 869_650_000,
+        duty_cycle_percent: 10.0,
+    },
+    DutyCycleBand {
+        min_hz: 869_700_000,
+        max_hz: 870_000_000,
+        duty_cycle_percent: 1.0,
+    },
+];
+
+/// LoRaWAN region
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Region {
+    /// Europe 863-870 MHz
+    #[default]
+    EU868,
+    /// United States 902-928 MHz
+    US915,
+    /// Australia 915-928 MHz
+    AU915,
+    /// Asia 915-928 MHz (AS923-1 channel plan)
+    AS923,
+    /// India 865-867 MHz
+    IN865,
+    /// South Korea 920-923 MHz
+    KR920,
+}
+
+/// Modulation behind a LoRaWAN data rate index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRateParams {
+    pub spreading_factor: SpreadingFactor,
+    pub bandwidth: LoRaBandwidth,
+}
+
+/// A channel of a region's channel plan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionChannel {
+    /// Channel index as used by LinkADRReq channel masks
+    pub index: u8,
+
+    /// Center frequency in Hz
+    pub frequency_hz: u32,
+
+    /// Lowest data rate allowed on this channel
+    pub min_dr: u8,
+
+    /// Highest data rate allowed on this channel
+    pub max_dr: u8,
+}
+
+/// A frequency band with its own duty-cycle limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DutyCycleBand {
+    /// Lower band edge in Hz (inclusive)
+    pub min_hz: u32,
+
+    /// Upper band edge in Hz (exclusive), so adjacent bands do not overlap
+    pub max_hz: u32,
+
+    /// Allowed transmit time as percentage of any one hour
+    pub duty_cycle_percent: f32,
+}
+
+impl DutyCycleBand {
+    /// Whether `frequency_hz` falls inside this band
+    pub fn contains(&self, frequency_hz: u32) -> bool {
+        (self.min_hz..self.max_hz).contains(&frequency_hz)
+    }
+}
+
+/// Spreading factor for SF7..SF12
+fn sf(n: u8) -> SpreadingFactor {
+    match n {
+        7 => SpreadingFactor::SF7,
+        8 => SpreadingFactor::SF8,
+        9 => SpreadingFactor::SF9,
+        10 => SpreadingFactor::SF10,
+        11 => SpreadingFactor::SF11,
+        _ => SpreadingFactor::SF12,
+    }
+}
+
+impl Region {
+    /// All supported regions
+    pub const ALL: [Region; 6] = [
+        Region::EU868,
+        Region::US915,
+        Region::AU915,
+        Region::AS923,
+        Region::IN865,
+        Region::KR920,
+    ];
+
+    /// Region name as used in the LoRaWAN specification
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::EU868 => "EU868",
+            Region::US915 => "US915",
+            Region::AU915 => "AU915",
+            Region::AS923 => "AS923",
+            Region::IN865 => "IN865",
+            Region::KR920 => "KR920",
+        }
+    }
+
+    /// Whether the region uses a fixed 64 + 8 channel plan (US915, AU915)
+    pub fn is_fixed_channel_plan(self) -> bool {
+        matches!(self, Region::US915 | Region::AU915)
+    }
+
+    /// Modulation of data rate `dr`, or `None` if it is undefined or not LoRa
+    pub fn data_rate(self, dr: u8) -> Option<DataRateParams> {
+        use LoRaBandwidth::{BW125, BW250, BW500};
+
+        let (spreading_factor, bandwidth) = match (self, dr) {
+            (Region::US915, 0..=3) => (sf(10 - dr), BW125),
+            (Region::US915, 4) => (SpreadingFactor::SF8, BW500),
+            (Region::US915 | Region::AU915, 8..=13) => (sf(20 - dr), BW500),
+            (Region::US915, _) => return None,
+            (Region::AU915, 6) => (SpreadingFactor::SF8, BW500),
+            (Region::EU868 | Region::AS923, 6) => (SpreadingFactor::SF7, BW250),
+            (_, 0..=5) => (sf(12 - dr), BW125),
+            _ => return None,
+        };
+
+        Some(DataRateParams {
+            spreading_factor,
+            bandwidth,
+        })
+    }
+
+    /// Data rates a device may use on the default uplink channels
+    ///
+    /// With `dwell_time` set, AU915 and AS923 drop the data rates whose time
+    /// on air cannot stay within the 400 ms dwell-time limit.
+    pub fn uplink_data_rates(self, dwell_time: bool) -> RangeInclusive<u8> {
+        let min = match self {
+            Region::AU915 | Region::AS923 if dwell_time => 2,
+            _ => 0,
+        };
+        let max = match self {
+            Region::US915 => 4,
+            Region::AU915 => 6,
+            _ => 5,
+        };
+        min..=max
+    }
+
+    /// Uplink data rate index for a modulation, if the region defines one
+    pub fn uplink_data_rate(self, sf: SpreadingFactor, bw: LoRaBandwidth) -> Option<u8> {
+        self.uplink_data_rates(false).find(|&dr| {
+            self.data_rate(dr)
+                == Some(DataRateParams {
+                    spreading_factor: sf,
+                    bandwidth: bw,
+                })
+        })
+    }
+
+    /// Maximum application payload (FRMPayload) size for data rate `dr`
+    ///
+    /// Values are the RP002 repeater-compatible N for every region, so a
+    /// payload sized for one table fits wherever a repeater may relay it.
+    /// `dwell_time` selects the dwell-time-limited table where the region
+    /// has one. Returns `None` for data rates that cannot be used.
+    pub fn max_payload(self, dr: u8, dwell_time: bool) -> Option<u8> {
+        const EU_LIKE: [u8; 7] = [51, 51, 51, 115, 222, 222, 222];
+        const DWELL_LIMITED: [u8; 7] = [0, 0, 11, 53, 125, 222, 222];
+        const US915: [u8; 14] = [11, 53, 125, 222, 222, 0, 0, 0, 33, 109, 222, 222, 222, 222];
+        const AU915: [u8; 14] = [
+            51, 51, 51, 115, 222, 222, 222, 0, 33, 109, 222, 222, 222, 222,
+        ];
+        const AU915_DWELL: [u8; 14] = [0, 0, 11, 53, 125, 222, 222, 0, 33, 109, 222, 222, 222, 222];
+
+        self.data_rate(dr)?;
+        let table: &[u8] = match self {
+            Region::US915 => &US915,
+            Region::AU915 if dwell_time => &AU915_DWELL,
+            Region::AU915 => &AU915,
+            Region::AS923 if dwell_time => &DWELL_LIMITED,
+            _ => &EU_LIKE,
+        };
+
+        table.get(dr as usize).copied().filter(|&n| n > 0)
+    }
+
+    /// Default maximum EIRP in dBm
+    pub fn max_eirp_dbm(self) -> i8 {
+        match self {
+            Region::EU868 | Region::AS923 => 16,
+            Region::US915 | Region::AU915 | Region::IN865 => 30,
+            Region::KR920 => 14,
+        }
+    }
+
+    /// Highest defined TXPower index
+    pub fn max_tx_power_index(self) -> u8 {
+        match self {
+            Region::US915 | Region::AU915 => 14,
+            Region::IN865 => 10,
+            _ => 7,
+        }
+    }
+
+    /// Transmit power in dBm for TXPower index `index`
+    pub fn tx_power_dbm(self, index: u8) -> Option<i8> {
+        (index <= self.max_tx_power_index())
+            .then(|| self.max_eirp_dbm() - TX_POWER_STEP_DB * index as i8)
+    }
+
+    /// Lowest transmit power the TXPower steps reach, in dBm
+    pub fn min_tx_power_dbm(self) -> i8 {
+        self.max_eirp_dbm() - TX_POWER_STEP_DB * self.max_tx_power_index() as i8
+    }
+
+    /// TXPower index closest to, but not above, `power_dbm`
+    pub fn tx_power_index(self, power_dbm: i8) -> u8 {
+        let steps = (self.max_eirp_dbm() as i16 - power_dbm as i16).max(0);
+        let step = TX_POWER_STEP_DB as i16;
+        (((steps + step - 1) / step) as u8).min(self.max_tx_power_index())
+    }
+
+    /// Highest RX1DROffset the region defines
+    pub fn max_rx1_dr_offset(self) -> u8 {
+        match self {
+            Region::US915 => 3,
+            Region::AS923 | Region::IN865 => 7,
+            _ => 5,
+        }
+    }
+
+    /// RX1 downlink data rate for an uplink at `uplink_dr`
+    ///
+    /// AS923 and IN865 offsets 6 and 7 raise the data rate by one and two.
+    pub fn rx1_data_rate(self, uplink_dr: u8, offset: u8) -> Option<u8> {
+        if offset > self.max_rx1_dr_offset() || !self.uplink_data_rates(false).contains(&uplink_dr)
+        {
+            return None;
+        }
+
+        let (up, offset) = (uplink_dr as i8, offset as i8);
+        let dr = match self {
+            Region::US915 => (10 + up - offset).clamp(8, 13),
+            Region::AU915 => (8 + up - offset).clamp(8, 13),
+            Region::AS923 | Region::IN865 => {
+                let offset = if offset > 5 { 5 - offset } else { offset };
+                (up - offset).clamp(0, 5)
+            }
+            Region::EU868 | Region::KR920 => (up - offset).max(0),
+        };
+        Some(dr as u8)
+    }
+
+    /// RX1 downlink frequency for an uplink on `uplink_hz`
+    ///
+    /// Fixed channel plans answer on one of eight 500 kHz downlink channels;
+    /// all other regions answer on the uplink frequency.
+    pub fn rx1_frequency_hz(self, uplink_hz: u32) -> u32 {
+        if !self.is_fixed_channel_plan() {
+            return uplink_hz;
+        }
+
+        self.default_channels()
+            .iter()
+            .find(|ch| ch.frequency_hz == uplink_hz)
+            .map(|ch| FIXED_PLAN_RX1_BASE_HZ + 600_000 * (ch.index % 8) as u32)
+            .unwrap_or(uplink_hz)
+    }
+
+    /// Default RX2 frequency in Hz
+    pub fn rx2_frequency_hz(self) -> u32 {
+        match self {
+            Region::EU868 => 869_525_000,
+            Region::US915 | Region::AU915 => 923_300_000,
+            Region::AS923 => 923_200_000,
+            Region::IN865 => 866_550_000,
+            Region::KR920 => 921_900_000,
+        }
+    }
+
+    /// Default RX2 data rate
+    pub fn rx2_data_rate(self) -> u8 {
+        match self {
+            Region::EU868 | Region::KR920 => 0,
+            Region::US915 | Region::AU915 => 8,
+            Region::AS923 | Region::IN865 => 2,
+        }
+    }
+
+    /// Channels every device knows without a NewChannelReq or CFList
+    ///
+    /// Fixed channel plans list all 64 125 kHz channels followed by the
+    /// eight 500 kHz channels.
+    pub fn default_channels(self) -> Vec<RegionChannel> {
+        let fixed = |base_125: u32, max_dr_125: u8, base_500: u32, dr_500: u8| {
+            (0..64u8)
+                .map(|i| RegionChannel {
+                    index: i,
+                    frequency_hz: base_125 + 200_000 * i as u32,
+                    min_dr: 0,
+                    max_dr: max_dr_125,
+                })
+                .chain((0..8u8).map(|i| RegionChannel {
+                    index: 64 + i,
+                    frequency_hz: base_500 + 1_600_000 * i as u32,
+                    min_dr: dr_500,
+                    max_dr: dr_500,
+                }))
+                .collect()
+        };
+        let dynamic = |frequencies: &[u32]| {
+            frequencies
+                .iter()
+                .enumerate()
+                .map(|(i, &frequency_hz)| RegionChannel {
+                    index: i as u8,
+                    frequency_hz,
+                    min_dr: 0,
+                    max_dr: 5,
+                })
+                .collect()
+        };
+
+        match self {
+            Region::EU868 => dynamic(&[868_100_000, 868_300_000, 868_500_000]),
+            Region::US915 => fixed(902_300_000, 3, 903_000_000, 4),
+            Region::AU915 => fixed(915_200_000, 5, 915_900_000, 6),
+            Region::AS923 => dynamic(&[923_200_000, 923_400_000]),
+            Region::IN865 => dynamic(&[865_062_500, 865_402_500, 865_985_000]),
+            Region::KR920 => dynamic(&[922_100_000, 922_300_000, 922_500_000]),
+        }
+    }
+
+    /// Channels a device may send JoinRequests on
+    ///
+    /// LoRaWAN 1.0.x joins on the default channels in every region; fixed
+    /// channel plans alternate between 125 kHz and 500 kHz channels.
+    pub fn join_channels(self) -> Vec<RegionChannel> {
+        self.default_channels()
+    }
+
+    /// The eight 125 kHz channels plus one 500 kHz channel of sub-band
+    /// `sub_band` (1-8), as used by 8-channel gateways
+    ///
+    /// Only fixed channel plans have sub-bands; other regions return `None`.
+    pub fn sub_band_channels(self, sub_band: u8) -> Option<Vec<RegionChannel>> {
+        if !self.is_fixed_channel_plan() || !(1..=8).contains(&sub_band) {
+            return None;
+        }
+
+        let first = (sub_band - 1) * 8;
+        Some(
+            self.default_channels()
+                .into_iter()
+                .filter(|ch| (first..first + 8).contains(&ch.index) || ch.index == 63 + sub_band)
+                .collect(),
+        )
+    }
+
+    /// LinkADRReq `(ChMask, ChMaskCntl)` enabling all default channels
+    pub fn default_channel_mask(self) -> (u16, u8) {
+        if self.is_fixed_channel_plan() {
+            // ChMaskCntl 6: all 125 kHz channels on, ChMask covers 64..71
+            (0x00FF, 6)
+        } else {
+            ((1u16 << self.default_channels().len()) - 1, 0)
+        }
+    }
+
+    /// Sub-bands with a regulatory duty-cycle limit
+    pub fn duty_cycle_bands(self) -> &'static [DutyCycleBand] {
+        match self {
+            Region::EU868 => &EU868_DUTY_CYCLE_BANDS,
+            _ => &[],
+        }
+    }
+
+    /// Duty-cycle limit in percent for a frequency, `None` if unrestricted
+    pub fn duty_cycle_percent(self, frequency_hz: u32) -> Option<f32> {
+        self.duty_cycle_bands()
+            .iter()
+            .find(|band| band.contains(frequency_hz))
+            .map(|band| band.duty_cycle_percent)
+    }
+
+    /// Maximum time on air per uplink, `None` if the region has no dwell-time
+    /// rule by default
+    pub fn dwell_time_limit(self) -> Option<Duration> {
+        match self {
+            Region::US915 | Region::AU915 | Region::AS923 => Some(DWELL_TIME_LIMIT),
+            _ => None,
+        }
+    }
+
+    /// Class B beacon frequency for the beacon sent at GPS second `beacon_time`
+    ///
+    /// Fixed channel plans hop over the eight downlink channels every beacon
+    /// period.
+    pub fn beacon_frequency_hz(self, beacon_time: u32) -> u32 {
+        match self {
+            Region::EU868 => 869_525_000,
+            Region::US915 | Region::AU915 => {
+                FIXED_PLAN_RX1_BASE_HZ + 600_000 * ((beacon_time / 128) % 8)
+            }
+            Region::AS923 => 923_400_000,
+            Region::IN865 => 866_550_000,
+            Region::KR920 => 923_100_000,
+        }
+    }
+
+    /// Class B beacon data rate
+    pub fn beacon_data_rate(self) -> u8 {
+        match self {
+            Region::US915 | Region::AU915 => 8,
+            Region::IN865 => 4,
+            _ => 3,
+        }
+    }
+
+    /// Default Class B ping-slot frequency for `dev_addr` in the beacon
+    /// period starting at `beacon_time`
+    pub fn ping_slot_frequency_hz(self, beacon_time: u32, dev_addr: u32) -> u32 {
+        if self.is_fixed_channel_plan() {
+            let channel = (dev_addr as u64 + (beacon_time / 128) as u64) % 8;
+            FIXED_PLAN_RX1_BASE_HZ + 600_000 * channel as u32
+        } else {
+            self.beacon_frequency_hz(beacon_time)
+        }
+    }
+
+    /// Default Class B ping-slot data rate
+    pub fn ping_slot_data_rate(self) -> u8 {
+        self.beacon_data_rate()
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_rate_tables() {
+        let eu_dr5 = Region::EU868.data_rate(5).unwrap();
+        assert_eq!(eu_dr5.spreading_factor, SpreadingFactor::SF7);
+        assert_eq!(eu_dr5.bandwidth, LoRaBandwidth::BW125);
+
+        // US915 uplinks stop at SF10 to stay within the dwell time
+        assert_eq!(
+            Region::US915.uplink_data_rate(SpreadingFactor::SF10, LoRaBandwidth::BW125),
+            Some(0)
+        );
+        assert_eq!(
+            Region::US915.uplink_data_rate(SpreadingFactor::SF12, LoRaBandwidth::BW125),
+            None
+        );
+        assert_eq!(
+            Region::AU915.data_rate(6).map(|dr| dr.bandwidth),
+            Some(LoRaBandwidth::BW500)
+        );
+        assert!(Region::KR920.data_rate(6).is_none());
+
+        assert_eq!(Region::US915.max_payload(0, true), Some(11));
+        assert_eq!(Region::AS923.max_payload(0, true), None);
+        assert_eq!(Region::AS923.max_payload(0, false), Some(51));
+        assert_eq!(Region::AU915.max_payload(2, true), Some(11));
+        assert_eq!(Region::AU915.uplink_data_rates(true), 2..=6);
+
+        // The dwell-time tables never allow more than the unrestricted ones.
+        for region in [Region::AU915, Region::AS923] {
+            for dr in 0..14 {
+                if let Some(dwell) = region.max_payload(dr, true) {
+                    assert!(
+                        Some(dwell) <= region.max_payload(dr, false),
+                        "{region:?} DR{dr}"
+                    );
+                }
+            }
+        }
+        assert_eq!(Region::US915.max_payload(4, false), Some(222));
+    }
+
+    #[test]
+    fn test_receive_windows() {
+        assert_eq!(Region::EU868.rx1_data_rate(5, 2), Some(3));
+        assert_eq!(Region::EU868.rx1_data_rate(1, 3), Some(0));
+        assert_eq!(Region::US915.rx1_data_rate(0, 0), Some(10));
+        assert_eq!(Region::US915.rx1_data_rate(4, 1), Some(13));
+        assert_eq!(Region::US915.rx1_data_rate(0, 4), None);
+        assert_eq!(Region::AU915.rx1_data_rate(2, 0), Some(10));
+        assert_eq!(Region::AS923.rx1_data_rate(3, 7), Some(5));
+
+        // Uplink channel 9 answers on downlink channel 1
+        assert_eq!(Region::US915.rx1_frequency_hz(904_100_000), 923_900_000);
+        assert_eq!(Region::AU915.rx1_frequency_hz(917_000_000), 923_900_000);
+        assert_eq!(Region::AS923.rx1_frequency_hz(923_400_000), 923_400_000);
+
+        assert_eq!(Region::EU868.rx2_frequency_hz(), 869_525_000);
+        assert_eq!(Region::AS923.rx2_data_rate(), 2);
+    }
+
+    #[test]
+    fn test_channels_and_power() {
+        assert_eq!(Region::US915.default_channels().len(), 72);
+        let sub_band_2 = Region::AU915.sub_band_channels(2).unwrap();
+        assert_eq!(sub_band_2.len(), 9);
+        assert_eq!(sub_band_2[0].frequency_hz, 916_800_000);
+        assert_eq!(sub_band_2[8].index, 65);
+        assert!(Region::EU868.sub_band_channels(1).is_none());
+
+        assert_eq!(Region::EU868.default_channel_mask(), (0x0007, 0));
+        assert_eq!(Region::AS923.default_channel_mask(), (0x0003, 0));
+        assert_eq!(Region::US915.default_channel_mask(), (0x00FF, 6));
+
+        assert_eq!(Region::EU868.tx_power_dbm(7), Some(2));
+        assert_eq!(Region::EU868.tx_power_dbm(8), None);
+        assert_eq!(Region::AU915.min_tx_power_dbm(), 2);
+        assert_eq!(Region::EU868.tx_power_index(13), 2);
+        assert_eq!(Region::KR920.tx_power_index(-10), 7);
+    }
+
+    #[test]
+    fn test_duty_cycle_and_dwell_time() {
+        assert_eq!(Region::EU868.duty_cycle_percent(868_100_000), Some(1.0));
+        assert_eq!(Region::EU868.duty_cycle_percent(869_525_000), Some(10.0));
+        assert_eq!(Region::EU868.duty_cycle_percent(868_650_000), None);
+        // Band edges belong to the band above them.
+        assert_eq!(Region::EU868.duty_cycle_percent(865_000_000), Some(1.0));
+        assert_eq!(Region::EU868.duty_cycle_percent(869_200_000), None);
+        assert_eq!(Region::AU915.duty_cycle_percent(916_800_000), None);
+
+        assert_eq!(Region::EU868.dwell_time_limit(), None);
+        assert_eq!(
+            Region::AS923.dwell_time_limit(),
+            Some(Duration::from_millis(400))
+        );
+    }
+}
@@ -7,6 +7,7 @@
 //!
 //! Inspired by One Channel Hub's approach to single-channel gateways.
 
+use super::region::Region;
 use crate::wmbus::radio::modulation::{CodingRate, LoRaBandwidth, LoRaModParams, SpreadingFactor};
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
@@ -89,6 +90,32 @@ impl SingleChannelConfig {
         }
     }
 
+    /// Create configuration for default channel `channel` of a region
+    ///
+    /// Uses the fastest 125 kHz uplink data rate, the region's MaxEIRP capped
+    /// at the SX126x's 22 dBm, and the duty-cycle limit of the channel's
+    /// sub-band. Returns `None` if the region has no such default channel.
+    pub fn for_region(region: Region, channel: usize) -> Option<Self> {
+        let frequency_hz = region.default_channels().get(channel)?.frequency_hz;
+        let spreading_factor = region
+            .uplink_data_rates(false)
+            .filter_map(|dr| region.data_rate(dr))
+            .filter(|params| params.bandwidth == LoRaBandwidth::BW125)
+            .map(|params| params.spreading_factor)
+            .min()?;
+        let duty_cycle = region.duty_cycle_percent(frequency_hz);
+
+        Some(Self {
+            frequency_hz,
+            spreading_factor,
+            bandwidth: LoRaBandwidth::BW125,
+            coding_rate: CodingRate::CR4_5,
+            tx_power_dbm: region.max_eirp_dbm().min(22),
+            duty_cycle_enabled: duty_cycle.is_some(),
+            duty_cycle_percent: duty_cycle.unwrap_or(100.0),
+        })
+    }
+
     /// Convert to LoRa modulation parameters
     pub fn to_mod_params(&self) -> LoRaModParams {
         // LDRO is required for SF11/SF12 with BW <= 125kHz
@@ -228,6 +255,9 @@ pub struct DutyCycleLimiter {
 
     /// Time window for duty cycle calculation (typically 1 hour)
     window: Duration,
+
+    /// Longest single transmission allowed (dwell-time rule), if any
+    max_dwell_time: Option<Duration>,
 }
 
 impl DutyCycleLimiter {
@@ -237,11 +267,32 @@ impl DutyCycleLimiter {
             config,
             tx_history: Vec::new(),
             window: Duration::from_secs(3600), // 1 hour window
+            max_dwell_time: None,
+        }
+    }
+
+    /// Create a duty cycle limiter enforcing a region's rules
+    ///
+    /// The duty-cycle limit comes from the sub-band containing the configured
+    /// frequency, replacing the one in `config`, and transmissions longer
+    /// than the region's dwell time are refused.
+    pub fn for_region(region: Region, mut config: SingleChannelConfig) -> Self {
+        let duty_cycle = region.duty_cycle_percent(config.frequency_hz);
+        config.duty_cycle_enabled = duty_cycle.is_some();
+        config.duty_cycle_percent = duty_cycle.unwrap_or(100.0);
+
+        Self {
+            max_dwell_time: region.dwell_time_limit(),
+            ..Self::new(config)
         }
     }
 
-    /// Check if transmission is allowed based on duty cycle
+    /// Check if transmission is allowed based on duty cycle and dwell time
     pub fn can_transmit(&mut self, duration: Duration) -> bool {
+        if self.max_dwell_time.is_some_and(|max| duration > max) {
+            return false;
+        }
+
         if !self.config.duty_cycle_enabled {
             return true;
         }
@@ -526,4 +577,28 @@ mod tests {
         assert!(!us_config.duty_cycle_enabled);
         assert_eq!(us_config.duty_cycle_percent, 100.0);
     }
+
+    #[test]
+    fn test_region_config_and_limiter() {
+        let au = SingleChannelConfig::for_region(Region::AU915, 8).unwrap();
+        assert_eq!(au.frequency_hz, 916_800_000);
+        assert_eq!(au.spreading_factor, SpreadingFactor::SF7);
+        assert!(!au.duty_cycle_enabled);
+
+        // AU915 has no duty cycle but caps each uplink at 400 ms
+        let mut limiter = DutyCycleLimiter::for_region(Region::AU915, au);
+        assert!(limiter.can_transmit(Duration::from_millis(390)));
+        assert!(!limiter.can_transmit(Duration::from_millis(1500)));
+
+        // EU868 869.525 MHz sits in the 10% sub-band
+        let config = SingleChannelConfig {
+            frequency_hz: 869_525_000,
+            ..SingleChannelConfig::default()
+        };
+        let mut limiter = DutyCycleLimiter::for_region(Region::EU868, config);
+        assert!(limiter.can_transmit(Duration::from_secs(300)));
+        assert!(!limiter.can_transmit(Duration::from_secs(400)));
+
+        assert!(SingleChannelConfig::for_region(Region::KR920, 3).is_none());
+    }
 }
@@ -1,3 +1,4 @@
+pub mod compliance;
 pub mod driver;
 pub mod hal;
 pub mod irq;
@@ -12,10 +13,7 @@ pub mod pio_irq;
 // LoRa support
 pub mod lora;
 
-// RFM69 packet processing (always available for testing)
+// RFM69 driver and packet processing (hardware access behind `Rfm69Hal`)
+pub mod rfm69;
 pub mod rfm69_packet;
 pub mod rfm69_registers;
-
-// RFM69 driver (feature-gated for hardware)
-#[cfg(feature = "rfm69")]
-pub mod rfm69;
@@ -201,6 +201,36 @@ pub enum PacketParams {
     },
 }
 
+impl PacketParams {
+    /// The same parameters with a different payload length (the receive maximum, or the
+    /// exact length of a frame about to be sent).
+    pub fn with_payload_len(self, len: u8) -> Self {
+        match self {
+            PacketParams::Gfsk {
+                preamble_len,
+                header_type,
+                crc_on,
+                crc_type,
+                sync_word_len,
+                ..
+            } => PacketParams::Gfsk {
+                preamble_len,
+                header_type,
+                payload_len: len,
+                crc_on,
+                crc_type,
+                sync_word_len,
+            },
+            PacketParams::LoRa { params } => PacketParams::LoRa {
+                params: LoRaPacketParams {
+                    payload_len: len,
+                    ..params
+                },
+            },
+        }
+    }
+}
+
 /// LoRa packet status (metadata from received LoRa packets)
 #[derive(Debug, Clone, Copy, Default)]
 pub struct LoRaPacketStatus {
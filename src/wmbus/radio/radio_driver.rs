@@ -5,6 +5,8 @@
 //! applications. It abstracts the differences between radio chips while providing
 //! a consistent API for the wmbus protocol layer.
 
+use crate::wmbus::radio::driver::{LbtConfig, ModeTaggedPacket};
+use crate::wmbus::radio::modulation::PacketType;
 use async_trait::async_trait;
 use std::time::Duration;
 use thiserror::Error;
@@ -27,6 +29,9 @@ pub enum RadioDriverError {
     /// Channel is busy (for LBT operations)
     #[error("Channel busy: RSSI {rssi_dbm} dBm")]
     ChannelBusy { rssi_dbm: i16 },
+    /// Transmission rejected by the regulatory transmit budget
+    #[error("Transmit budget: {0}")]
+    TxBudget(String),
     /// Device-specific error
     #[error("Device error: {0}")]
     DeviceError(String),
@@ -111,7 +116,7 @@ pub struct RadioStats {
 /// allowing the wmbus protocol layer to work with various radio chips
 /// (SX126x, RFM69HCW, etc.) without modification.
 #[async_trait]
-pub trait RadioDriver: Send + Sync {
+pub trait RadioDriver: Send {
     /// Initialize the radio with the given configuration
     ///
     /// # Arguments
@@ -225,6 +230,67 @@ pub trait RadioDriver: Send + Sync {
         listen_duration: Duration,
     ) -> Result<bool, RadioDriverError>;
 
+    /// Check for a received packet tagged with the modem it arrived on
+    ///
+    /// The default treats every packet from `get_received_packet()` as a GFSK
+    /// (wM-Bus) packet, which is all a single-modem radio can receive. Dual-mode
+    /// radios override this to capture the modem atomically with the packet.
+    ///
+    /// # Returns
+    /// * `Ok(Some(packet))` - Packet received
+    /// * `Ok(None)` - No packet available
+    /// * `Err(RadioDriverError)` - Error checking for packets
+    async fn get_mode_tagged_packet(
+        &mut self,
+    ) -> Result<Option<ModeTaggedPacket>, RadioDriverError> {
+        Ok(self
+            .get_received_packet()
+            .await?
+            .map(|packet| ModeTaggedPacket {
+                mode: PacketType::Gfsk,
+                link_mode: None,
+                payload: packet.data,
+                rssi_dbm: packet.rssi_dbm,
+                lora: None,
+            }))
+    }
+
+    /// Transmit a packet with Listen Before Talk
+    ///
+    /// The default listens with `is_channel_clear()` and retries with exponential
+    /// backoff (10 ms, 20 ms, 40 ms, ...) before giving up. Drivers with their own
+    /// LBT sequence override this.
+    ///
+    /// # Arguments
+    /// * `data` - Data to transmit
+    /// * `lbt_config` - RSSI threshold, listen time and retry budget
+    ///
+    /// # Returns
+    /// * `Ok(())` - Transmission completed successfully
+    /// * `Err(RadioDriverError::ChannelBusy)` - Channel stayed busy on every attempt
+    /// * `Err(RadioDriverError)` - Transmission failed
+    async fn transmit_with_lbt(
+        &mut self,
+        data: &[u8],
+        lbt_config: LbtConfig,
+    ) -> Result<(), RadioDriverError> {
+        let listen = Duration::from_millis(lbt_config.listen_duration_ms as u64);
+        for attempt in 0..=lbt_config.max_retries {
+            if self
+                .is_channel_clear(lbt_config.rssi_threshold_dbm, listen)
+                .await?
+            {
+                return self.transmit(data).await;
+            }
+            if attempt < lbt_config.max_retries {
+                tokio::time::sleep(LbtConfig::backoff(attempt)).await;
+            }
+        }
+        Err(RadioDriverError::ChannelBusy {
+            rssi_dbm: self.get_rssi().await?,
+        })
+    }
+
     /// Get driver-specific information
     ///
     /// Returns information about the radio driver implementation,
@@ -27,54 +27,39 @@
 //!
 //! ## Usage
 //!
-//! ```rust,no_run
-//! use rfm69::Rfm69Driver;
+//! The driver talks to the radio through an [`Rfm69Hal`]. On a Raspberry Pi
+//! `Rfm69Driver::new(config)` opens the SPI device and GPIO pins named in the config
+//! (feature `rfm69`); anywhere else [`Rfm69Driver::with_hal`] takes any HAL, such as
+//! the simulated [`Rfm69Model`](crate::wmbus::radio::hal::Rfm69Model):
 //!
-//! let mut driver = Rfm69Driver::new(config).await?;
-//! driver.start_rx().await?;
+//! ```rust
+//! use mbus_rs::wmbus::radio::hal::Rfm69Model;
+//! use mbus_rs::wmbus::radio::radio_driver::RadioDriver;
+//! use mbus_rs::wmbus::radio::rfm69::{Rfm69Config, Rfm69Driver};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let chip = Rfm69Model::without_reset_line();
+//! let mut driver = Rfm69Driver::with_hal(chip.clone(), Rfm69Config::default());
+//! driver.initialize().await?;
 //!
 //! // Process packets in event loop
-//! while let Some(packet) = driver.read_packet().await? {
+//! while let Some(packet) = driver.get_received_packet().await? {
 //!     println!("Received: {:?}", packet);
 //! }
+//! # Ok(())
+//! # }
 //! ```
 
+use crate::wmbus::radio::hal::{HalError, Rfm69Hal};
 use crate::wmbus::radio::rfm69_packet::*;
 use crate::wmbus::radio::rfm69_registers::*;
+use crate::wmbus::t1c1::{capture_len, capture_to_frame, COMMON_SYNC, HEAD_LEN};
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::time::{sleep, timeout};
-
-#[cfg(feature = "rfm69")]
-use rppal::{
-    gpio::{Gpio, InputPin, Level, OutputPin, Trigger},
-    spi::{BitOrder, Bus, Mode, SlaveSelect, Spi},
-};
-
-/// Parse a `/dev/spidevB.C` path into its rppal (Bus, SlaveSelect).
-/// e.g. `/dev/spidev0.1` → (Spi0, Ss1). Falls back to (Spi0, Ss0) on any parse failure.
-#[cfg(feature = "rfm69")]
-fn parse_spidev(path: &str) -> (Bus, SlaveSelect) {
-    let tail = path.rsplit_once("spidev").map(|(_, t)| t).unwrap_or("");
-    let (b, c) = tail.split_once('.').unwrap_or(("0", "0"));
-    let bus = match b.trim().parse::<u8>().unwrap_or(0) {
-        1 => Bus::Spi1,
-        2 => Bus::Spi2,
-        3 => Bus::Spi3,
-        4 => Bus::Spi4,
-        5 => Bus::Spi5,
-        6 => Bus::Spi6,
-        _ => Bus::Spi0,
-    };
-    let ss = match c.trim().parse::<u8>().unwrap_or(0) {
-        1 => SlaveSelect::Ss1,
-        2 => SlaveSelect::Ss2,
-        _ => SlaveSelect::Ss0,
-    };
-    (bus, ss)
-}
+use tokio::time::sleep;
 
 /// Configuration for RFM69 driver
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -93,6 +78,10 @@ pub struct Rfm69Config {
     pub network_id: Option<u8>,
     /// FIFO threshold for interrupt (default: 3)
     pub fifo_threshold: Option<u8>,
+    /// Receive T1 and C1 at once: match only the shared 2-byte sync and classify each
+    /// frame after reception (default: C1 only)
+    #[serde(default)]
+    pub t1_c1: bool,
 }
 
 impl Default for Rfm69Config {
@@ -105,6 +94,7 @@ impl Default for Rfm69Config {
             node_id: None,
             network_id: None,
             fifo_threshold: Some(3),
+            t1_c1: false,
         }
     }
 }
@@ -137,6 +127,18 @@ pub enum Rfm69Error {
     FeatureNotEnabled(String),
 }
 
+impl From<HalError> for Rfm69Error {
+    fn from(e: HalError) -> Self {
+        match e {
+            HalError::Gpio => Rfm69Error::Gpio(e.to_string()),
+            _ => Rfm69Error::Spi(e.to_string()),
+        }
+    }
+}
+
+/// Completed frames with their RSSI, handed from the interrupt task to the driver
+type ReceivedQueue = std::collections::VecDeque<(Vec<u8>, i16)>;
+
 /// Operating modes for the RFM69
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Rfm69Mode {
@@ -147,18 +149,13 @@ pub enum Rfm69Mode {
 }
 
 /// Main RFM69 driver structure
-pub struct Rfm69Driver {
-    /// SPI interface for register access
-    #[cfg(feature = "rfm69")]
-    spi: Arc<Mutex<Spi>>,
-
-    /// GPIO for radio reset
-    #[cfg(feature = "rfm69")]
-    reset_pin: Option<OutputPin>,
-
-    /// GPIO for interrupt monitoring
-    #[cfg(feature = "rfm69")]
-    interrupt_pin: Option<InputPin>,
+///
+/// Generic over the [`Rfm69Hal`] that carries its register accesses and RESET line:
+/// [`Rfm69PiHal`](crate::wmbus::radio::hal::Rfm69PiHal) on a Raspberry Pi, or
+/// [`Rfm69Model`](crate::wmbus::radio::hal::Rfm69Model) on the host.
+pub struct Rfm69Driver<H: Rfm69Hal> {
+    /// Register access, shared with the interrupt task
+    hal: Arc<Mutex<H>>,
 
     /// Driver configuration
     config: Rfm69Config,
@@ -177,45 +174,60 @@ pub struct Rfm69Driver {
 
     /// Completed frames handed from the interrupt task to `get_received_packet`.
     /// Without this the interrupt task assembled packets and dropped them.
-    received: Arc<Mutex<std::collections::VecDeque<(Vec<u8>, i16)>>>,
+    received: Arc<Mutex<ReceivedQueue>>,
 
     /// Interrupt processing task handle
-    #[cfg(feature = "rfm69")]
     interrupt_task: Option<tokio::task::JoinHandle<()>>,
 
     /// Shutdown signal for graceful task termination
-    #[cfg(feature = "rfm69")]
     shutdown_tx: Option<tokio::sync::oneshot::Sender<()>>,
 }
 
-impl Rfm69Driver {
-    /// Create a new RFM69 driver instance
+#[cfg(feature = "rfm69")]
+impl Rfm69Driver<crate::wmbus::radio::hal::Rfm69PiHal> {
+    /// Create a new RFM69 driver instance on the Raspberry Pi SPI device and GPIO
+    /// pins named in `config`.
+    ///
+    /// The bus and chip-select are derived from the configured `spidev` path
+    /// (e.g. `/dev/spidev0.1` → bus 0, CS1).
     pub async fn new(config: Rfm69Config) -> Result<Self, Rfm69Error> {
-        #[cfg(not(feature = "rfm69"))]
-        {
-            return Err(Rfm69Error::FeatureNotEnabled(
-                "rfm69 feature not enabled. Build with --features rfm69".to_string(),
-            ));
-        }
+        use crate::wmbus::radio::hal::raspberry_pi::{Rfm69HalBuilder, RpiHalError};
 
-        #[cfg(feature = "rfm69")]
-        {
-            let spi = Self::init_spi(&config)?;
-            let (reset_pin, interrupt_pin) = Self::init_gpio(&config)?;
-
-            Ok(Self {
-                spi: Arc::new(Mutex::new(spi)),
-                reset_pin,
-                interrupt_pin,
-                config,
-                current_mode: Rfm69Mode::Sleep,
-                packet_buffer: Arc::new(Mutex::new(PacketBuffer::new())),
-                stats: Arc::new(Mutex::new(PacketStats::default())),
-                error_throttle: Arc::new(Mutex::new(LogThrottle::new(60_000, 5))), // 5 errors per minute
-                received: Arc::new(Mutex::new(std::collections::VecDeque::new())),
-                interrupt_task: None,
-                shutdown_tx: None,
-            })
+        let mut builder =
+            Rfm69HalBuilder::new().spi_device(config.spidev.as_deref().unwrap_or("/dev/spidev0.0"));
+        builder = match config.reset_pin {
+            Some(pin) => builder.reset_pin(pin),
+            None => builder.no_reset(),
+        };
+        builder = match config.interrupt_pin {
+            Some(pin) => builder.interrupt_pin(pin),
+            None => builder.no_interrupt(),
+        };
+        let hal = builder.build_hal().map_err(|e| match e {
+            RpiHalError::GpioInit(_) | RpiHalError::GpioOperation(_) => {
+                Rfm69Error::Gpio(format!("Failed to initialize GPIO: {e}"))
+            }
+            _ => Rfm69Error::Spi(format!("Failed to initialize SPI: {e}")),
+        })?;
+
+        Ok(Self::with_hal(hal, config))
+    }
+}
+
+impl<H: Rfm69Hal + 'static> Rfm69Driver<H> {
+    /// Create a driver over any [`Rfm69Hal`]. Nothing is sent to the radio until
+    /// [`initialize`](Self::initialize).
+    pub fn with_hal(hal: H, config: Rfm69Config) -> Self {
+        Self {
+            hal: Arc::new(Mutex::new(hal)),
+            config,
+            current_mode: Rfm69Mode::Sleep,
+            packet_buffer: Arc::new(Mutex::new(PacketBuffer::new())),
+            stats: Arc::new(Mutex::new(PacketStats::default())),
+            error_throttle: Arc::new(Mutex::new(LogThrottle::new(60_000, 5))), // 5 errors per minute
+            received: Arc::new(Mutex::new(ReceivedQueue::new())),
+            interrupt_task: None,
+            shutdown_tx: None,
         }
     }
 
@@ -252,52 +264,51 @@ impl Rfm69Driver {
 
     /// Reset the radio chip
     async fn reset(&mut self) -> Result<(), Rfm69Error> {
-        #[cfg(feature = "rfm69")]
-        {
-            if let Some(ref mut reset_pin) = self.reset_pin {
-                info!("Resetting RFM69 chip");
-
-                // Pulse reset pin: HIGH -> wait -> LOW -> wait
-                reset_pin.set_high();
-                sleep(Duration::from_millis(300)).await;
-                reset_pin.set_low();
-                sleep(Duration::from_millis(300)).await;
-
-                // Verify chip is responding
-                let start = Instant::now();
-                let timeout_duration = Duration::from_secs(5);
-
-                // Try to sync with chip by writing test patterns
-                let original = self.read_register(REG_SYNCVALUE1).await?;
-
-                while start.elapsed() < timeout_duration {
-                    self.write_register(REG_SYNCVALUE1, 0xAA).await?;
-                    if self.read_register(REG_SYNCVALUE1).await? == 0xAA {
-                        break;
-                    }
-                    sleep(Duration::from_millis(10)).await;
-                }
+        if !self.hal.lock().unwrap().has_reset() {
+            return Ok(());
+        }
 
-                while start.elapsed() < timeout_duration {
-                    self.write_register(REG_SYNCVALUE1, 0x55).await?;
-                    if self.read_register(REG_SYNCVALUE1).await? == 0x55 {
-                        break;
-                    }
-                    sleep(Duration::from_millis(10)).await;
-                }
+        info!("Resetting RFM69 chip");
 
-                if start.elapsed() >= timeout_duration {
-                    return Err(Rfm69Error::InitFailed(
-                        "Failed to sync with radio chip".to_string(),
-                    ));
-                }
+        // Pulse reset pin: HIGH -> wait -> LOW -> wait
+        self.hal.lock().unwrap().set_reset(true)?;
+        sleep(Duration::from_millis(300)).await;
+        self.hal.lock().unwrap().set_reset(false)?;
+        sleep(Duration::from_millis(300)).await;
+
+        // Verify chip is responding
+        let start = Instant::now();
+        let timeout_duration = Duration::from_secs(5);
+
+        // Try to sync with chip by writing test patterns
+        let original = self.read_register(REG_SYNCVALUE1).await?;
+
+        while start.elapsed() < timeout_duration {
+            self.write_register(REG_SYNCVALUE1, 0xAA).await?;
+            if self.read_register(REG_SYNCVALUE1).await? == 0xAA {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
 
-                // Restore original value
-                self.write_register(REG_SYNCVALUE1, original).await?;
-                info!("RFM69 chip reset completed");
+        while start.elapsed() < timeout_duration {
+            self.write_register(REG_SYNCVALUE1, 0x55).await?;
+            if self.read_register(REG_SYNCVALUE1).await? == 0x55 {
+                break;
             }
+            sleep(Duration::from_millis(10)).await;
         }
 
+        if start.elapsed() >= timeout_duration {
+            return Err(Rfm69Error::InitFailed(
+                "Failed to sync with radio chip".to_string(),
+            ));
+        }
+
+        // Restore original value
+        self.write_register(REG_SYNCVALUE1, original).await?;
+        info!("RFM69 chip reset completed");
+
         Ok(())
     }
 
@@ -363,10 +374,18 @@ impl Rfm69Driver {
         // "sync disabled" (0x00) never fired, so the FIFO stayed empty — the deployed
         // metermon uses exactly this config (SYNCCONFIG=0x90, SYNCVALUE1..3=54 3D 54).
         // SYNCCONFIG 0x90 = SyncOn(0x80) | SyncSize=3 bytes ((3-1)<<3 = 0x10).
-        self.write_register(REG_SYNCCONFIG, 0x90).await?;
-        self.write_register(REG_SYNCVALUE1, 0x54).await?;
-        self.write_register(REG_SYNCVALUE2, 0x3D).await?;
-        self.write_register(REG_SYNCVALUE3, 0x54).await?;
+        // In T1+C1 mode only the 54 3D prefix both modes share is matched (SYNCCONFIG
+        // 0x88 = SyncOn | SyncSize=2 bytes); the mode is told apart from the FIFO bytes.
+        if self.config.t1_c1 {
+            self.write_register(REG_SYNCCONFIG, 0x88).await?;
+            self.write_register(REG_SYNCVALUE1, COMMON_SYNC[0]).await?;
+            self.write_register(REG_SYNCVALUE2, COMMON_SYNC[1]).await?;
+        } else {
+            self.write_register(REG_SYNCCONFIG, 0x90).await?;
+            self.write_register(REG_SYNCVALUE1, 0x54).await?;
+            self.write_register(REG_SYNCVALUE2, 0x3D).await?;
+            self.write_register(REG_SYNCVALUE3, 0x54).await?;
+        }
 
         // Configure DIO mapping for FIFO level interrupt on DIO1
         self.write_register(REG_DIOMAPPING1, 0).await?;
@@ -517,63 +536,54 @@ impl Rfm69Driver {
     }
 
     /// Start interrupt handling task
+    ///
+    /// The task polls `RegIrqFlags2` (FifoLevel, FifoOverrun, PayloadReady) rather than
+    /// waiting on DIO1, so it works whether or not an interrupt line is wired.
     async fn start_interrupt_handling(&mut self) -> Result<(), Rfm69Error> {
-        #[cfg(feature = "rfm69")]
-        {
-            if let Some(ref mut interrupt_pin) = self.interrupt_pin {
-                info!(
-                    "Starting interrupt handling on GPIO {}",
-                    self.config.interrupt_pin.unwrap_or(DEFAULT_INTERRUPT_PIN)
-                );
-
-                // Configure interrupt pin for rising edge.
-                // rppal 0.22 added a debounce parameter to set_interrupt.
-                interrupt_pin
-                    .set_interrupt(Trigger::RisingEdge, None)
-                    .map_err(|e| Rfm69Error::Gpio(format!("Failed to set interrupt: {}", e)))?;
-
-                // Clone references for the async task
-                let spi = self.spi.clone();
-                let packet_buffer = self.packet_buffer.clone();
-                let stats = self.stats.clone();
-                let error_throttle = self.error_throttle.clone();
-                let received = self.received.clone();
-
-                // Create shutdown channel
-                let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
-
-                // Spawn interrupt handling task
-                let handle = tokio::spawn(async move {
-                    Self::interrupt_handler_task(
-                        spi,
-                        packet_buffer,
-                        stats,
-                        error_throttle,
-                        received,
-                        shutdown_rx,
-                    )
-                    .await;
-                });
-
-                self.interrupt_task = Some(handle);
-                self.shutdown_tx = Some(shutdown_tx);
-            } else {
-                warn!("No interrupt pin configured, using polling mode");
-                // TODO: Start polling task as fallback
-            }
+        if self.interrupt_task.is_some() {
+            return Ok(());
         }
+        info!("Starting RFM69 FIFO polling task");
+
+        // Clone references for the async task
+        let hal = self.hal.clone();
+        let packet_buffer = self.packet_buffer.clone();
+        let stats = self.stats.clone();
+        let error_throttle = self.error_throttle.clone();
+        let received = self.received.clone();
+        let t1_c1 = self.config.t1_c1;
+
+        // Create shutdown channel
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+
+        // Spawn interrupt handling task
+        let handle = tokio::spawn(async move {
+            Self::interrupt_handler_task(
+                hal,
+                t1_c1,
+                packet_buffer,
+                stats,
+                error_throttle,
+                received,
+                shutdown_rx,
+            )
+            .await;
+        });
+
+        self.interrupt_task = Some(handle);
+        self.shutdown_tx = Some(shutdown_tx);
 
         Ok(())
     }
 
-    /// Async interrupt handler task with proper GPIO interrupt handling
-    #[cfg(feature = "rfm69")]
+    /// Async interrupt handler task polling the FIFO flags
     async fn interrupt_handler_task(
-        spi: Arc<Mutex<Spi>>,
+        spi: Arc<Mutex<H>>,
+        t1_c1: bool,
         packet_buffer: Arc<Mutex<PacketBuffer>>,
         stats: Arc<Mutex<PacketStats>>,
         error_throttle: Arc<Mutex<LogThrottle>>,
-        received: Arc<Mutex<std::collections::VecDeque<(Vec<u8>, i16)>>>,
+        received: Arc<Mutex<ReceivedQueue>>,
         mut shutdown_rx: tokio::sync::oneshot::Receiver<()>,
     ) {
         info!("Interrupt handler task started");
@@ -624,9 +634,14 @@ impl Rfm69Driver {
                 Ok(flags2) => {
                     // Handle FIFO level interrupt
                     if flags2 & RF_IRQFLAGS2_FIFOLEVEL != 0 {
-                        if let Err(e) =
-                            Self::handle_fifo_interrupt(&spi, &packet_buffer, &stats, &received)
-                                .await
+                        if let Err(e) = Self::handle_fifo_interrupt(
+                            &spi,
+                            t1_c1,
+                            &packet_buffer,
+                            &stats,
+                            &received,
+                        )
+                        .await
                         {
                             // Throttled error logging
                             if error_throttle.lock().unwrap().allow() {
@@ -682,15 +697,15 @@ impl Rfm69Driver {
 
     /// Handle a FIFO-level interrupt: read one sync-triggered burst as a whole
     /// packet, deliver it, and re-arm the receiver — matching epulse's ReceivePacket
-    /// + Interrupt model. HW sync (SYNCCONFIG=0x90) fires the FIFO fill per frame, so
+    /// and Interrupt model. HW sync (SYNCCONFIG=0x90) fires the FIFO fill per frame, so
     /// each burst is one frame; accumulating across bursts in a persistent buffer
     /// (the previous approach) misaligned and flooded "invalid header".
-    #[cfg(feature = "rfm69")]
     async fn handle_fifo_interrupt(
-        spi: &Arc<Mutex<Spi>>,
+        spi: &Arc<Mutex<H>>,
+        t1_c1: bool,
         _packet_buffer: &Arc<Mutex<PacketBuffer>>,
         stats: &Arc<Mutex<PacketStats>>,
-        received: &Arc<Mutex<std::collections::VecDeque<(Vec<u8>, i16)>>>,
+        received: &Arc<Mutex<ReceivedQueue>>,
     ) -> Result<(), Rfm69Error> {
         // Signal is present now (sync just matched) — sample RSSI for this frame.
         let rssi_dbm: i16 = match Self::read_register_static(spi, REG_RSSIVALUE).await {
@@ -706,8 +721,14 @@ impl Rfm69Driver {
         let mut idle = 0u32;
 
         loop {
-            // Determine the expected size once we have the 2-byte header.
-            if size == -1 && buf.len() >= 2 {
+            // Determine the expected size once we have the header: 2 bytes, or the
+            // 3-byte T1/C1 head that also tells the modes apart.
+            if size == -1 && t1_c1 && buf.len() >= HEAD_LEN {
+                size = capture_len(&buf).map_or(-2, |n| n as i32);
+                if size <= 0 {
+                    break;
+                }
+            } else if size == -1 && !t1_c1 && buf.len() >= 2 {
                 size = packet_size(&buf);
                 if size <= 0 {
                     break; // 0 = not wM-Bus, -2 = invalid header → abandon this burst
@@ -782,9 +803,8 @@ impl Rfm69Driver {
     }
 
     /// Handle FIFO overrun condition
-    #[cfg(feature = "rfm69")]
     async fn handle_fifo_overrun(
-        spi: &Arc<Mutex<Spi>>,
+        spi: &Arc<Mutex<H>>,
         packet_buffer: &Arc<Mutex<PacketBuffer>>,
         stats: &Arc<Mutex<PacketStats>>,
     ) -> Result<(), Rfm69Error> {
@@ -810,9 +830,8 @@ impl Rfm69Driver {
     }
 
     /// Handle payload ready interrupt (complete packet received)
-    #[cfg(feature = "rfm69")]
     async fn handle_payload_ready(
-        spi: &Arc<Mutex<Spi>>,
+        spi: &Arc<Mutex<H>>,
         packet_buffer: &Arc<Mutex<PacketBuffer>>,
         stats: &Arc<Mutex<PacketStats>>,
     ) -> Result<(), Rfm69Error> {
@@ -848,185 +867,24 @@ impl Rfm69Driver {
     }
 
     /// Static version of write_register for use in tasks
-    #[cfg(feature = "rfm69")]
     async fn write_register_static(
-        spi: &Arc<Mutex<Spi>>,
+        spi: &Arc<Mutex<H>>,
         reg: u8,
         value: u8,
     ) -> Result<(), Rfm69Error> {
-        let tx = [reg | 0x80, value];
-
-        {
-            let mut spi = spi.lock().unwrap();
-            spi.write(&tx)
-                .map_err(|e| Rfm69Error::Spi(format!("Write register failed: {}", e)))?;
-        }
-
+        spi.lock().unwrap().write_register(reg, value)?;
         Ok(())
     }
 
     /// Check if FIFO is not empty
-    #[cfg(feature = "rfm69")]
-    async fn fifo_not_empty(spi: &Arc<Mutex<Spi>>) -> Result<bool, Rfm69Error> {
+    async fn fifo_not_empty(spi: &Arc<Mutex<H>>) -> Result<bool, Rfm69Error> {
         let flags = Self::read_register_static(spi, REG_IRQFLAGS2).await?;
         Ok(flags & RF_IRQFLAGS2_FIFONOTEMPTY != 0)
     }
 
-    /// Read burst of bytes from FIFO
-    ///
-    /// Reads up to expected_size bytes from FIFO in a single operation
-    /// to prevent timing issues and partial frame corruption.
-    ///
-    /// # Arguments
-    ///
-    /// * `spi` - SPI interface
-    /// * `expected_size` - Number of bytes to read
-    ///
-    /// # Returns
-    ///
-    /// * Vector of bytes read (may be less than expected if FIFO runs out)
-    #[cfg(feature = "rfm69")]
-    async fn read_burst(
-        spi: &Arc<Mutex<Spi>>,
-        expected_size: usize,
-    ) -> Result<Vec<u8>, Rfm69Error> {
-        let mut bytes = Vec::with_capacity(expected_size);
-        let mut consecutive_empty = 0;
-
-        // Read up to expected_size bytes, but stop if FIFO appears empty
-        while bytes.len() < expected_size {
-            // Check FIFO status
-            if !Self::fifo_not_empty(spi).await? {
-                consecutive_empty += 1;
-                if consecutive_empty > 3 {
-                    // FIFO seems to be empty, stop reading
-                    break;
-                }
-                // Brief delay to allow FIFO to fill
-                tokio::time::sleep(tokio::time::Duration::from_micros(100)).await;
-                continue;
-            }
-            consecutive_empty = 0;
-
-            // Read byte from FIFO
-            let byte = Self::read_register_static(spi, REG_FIFO).await?;
-            bytes.push(byte);
-        }
-
-        if bytes.len() < expected_size {
-            debug!(
-                "Burst read incomplete: expected {}, got {} bytes",
-                expected_size,
-                bytes.len()
-            );
-        }
-
-        Ok(bytes)
-    }
-
-    /// Enhanced FIFO interrupt handler with size-aware burst reading
-    ///
-    /// Uses packet size determination to read full frames atomically,
-    /// preventing mid-frame corruption from timing issues.
-    /// Inspired by One Channel Hub's sx126x_get_rx_buffer_status approach.
-    #[cfg(feature = "rfm69")]
-    async fn handle_fifo_interrupt_burst(
-        spi: &Arc<Mutex<Spi>>,
-        packet_buffer: &Arc<Mutex<PacketBuffer>>,
-        stats: &Arc<Mutex<PacketStats>>,
-    ) -> Result<(), Rfm69Error> {
-        // First, get the payload size from FIFO status
-        // This is critical for preventing partial frame reads
-        let payload_size = Self::get_fifo_payload_size(spi).await?;
-
-        if payload_size == 0 {
-            debug!("FIFO interrupt with no payload");
-            return Ok(());
-        }
-
-        // Validate payload size against maximum expected
-        if payload_size > 255 {
-            warn!("Invalid payload size detected: {}", payload_size);
-            stats.lock().unwrap().fifo_overruns += 1;
-            Self::clear_fifo(spi).await?;
-            return Ok(());
-        }
-
-        // Now read the exact payload size in a single burst
-        // This prevents partial frame corruption seen in logs
-        let mut header_bytes = Vec::new();
-
-        // Read first 2 bytes for packet type determination
-        for _ in 0..2 {
-            if Self::fifo_not_empty(spi).await? {
-                let byte = Self::read_register_static(spi, REG_FIFO).await?;
-                header_bytes.push(byte);
-            }
-        }
-
-        if header_bytes.len() < 2 {
-            return Ok(()); // Not enough data yet
-        }
-
-        // Determine expected packet size from header
-        let expected_size = {
-            let mut buffer = packet_buffer.lock().unwrap();
-            // Add header bytes to buffer
-            for byte in &header_bytes {
-                buffer.push_byte(*byte);
-            }
-
-            // Try to determine packet size
-            match buffer.determine_packet_size() {
-                Some(size) => size,
-                None => {
-                    // Can't determine size yet, continue byte-by-byte
-                    return Ok(());
-                }
-            }
-        };
-
-        // Read remaining bytes in burst
-        let remaining = expected_size.saturating_sub(header_bytes.len());
-        if remaining > 0 {
-            match Self::read_burst(spi, remaining).await {
-                Ok(data) => {
-                    let mut buffer = packet_buffer.lock().unwrap();
-                    for byte in data {
-                        buffer.push_byte(byte);
-                    }
-
-                    // Check if packet is complete
-                    if buffer.is_complete() {
-                        debug!("Burst read complete: {} bytes total", expected_size);
-                        let mut stats = stats.lock().unwrap();
-                        stats.packets_received += 1;
-                    }
-                }
-                Err(e) => {
-                    warn!("Burst read failed: {}", e);
-                    let mut stats = stats.lock().unwrap();
-                    stats.fifo_overruns += 1;
-                }
-            }
-        }
-
-        Ok(())
-    }
-
     /// Read a register value
     async fn read_register(&self, reg: u8) -> Result<u8, Rfm69Error> {
-        #[cfg(feature = "rfm69")]
-        {
-            Self::read_register_static(&self.spi, reg).await
-        }
-
-        #[cfg(not(feature = "rfm69"))]
-        {
-            Err(Rfm69Error::FeatureNotEnabled(
-                "rfm69 feature not enabled".to_string(),
-            ))
-        }
+        Self::read_register_static(&self.hal, reg).await
     }
 
     /// Current `RegOpMode` byte (radio operating-mode register). Used by the gateway
@@ -1037,41 +895,14 @@ impl Rfm69Driver {
     }
 
     /// Static version of read_register for use in tasks
-    #[cfg(feature = "rfm69")]
-    async fn read_register_static(spi: &Arc<Mutex<Spi>>, reg: u8) -> Result<u8, Rfm69Error> {
-        let tx = [reg & 0x7F, 0];
-        let mut rx = [0u8; 2];
-
-        {
-            let mut spi = spi.lock().unwrap();
-            spi.transfer(&mut rx, &tx)
-                .map_err(|e| Rfm69Error::Spi(format!("Read register failed: {}", e)))?;
-        }
-
-        Ok(rx[1])
+    async fn read_register_static(spi: &Arc<Mutex<H>>, reg: u8) -> Result<u8, Rfm69Error> {
+        let value = spi.lock().unwrap().read_register(reg)?;
+        Ok(value)
     }
 
     /// Write a register value
     async fn write_register(&self, reg: u8, value: u8) -> Result<(), Rfm69Error> {
-        #[cfg(feature = "rfm69")]
-        {
-            let tx = [reg | 0x80, value];
-
-            {
-                let mut spi = self.spi.lock().unwrap();
-                spi.write(&tx)
-                    .map_err(|e| Rfm69Error::Spi(format!("Write register failed: {}", e)))?;
-            }
-
-            Ok(())
-        }
-
-        #[cfg(not(feature = "rfm69"))]
-        {
-            Err(Rfm69Error::FeatureNotEnabled(
-                "rfm69 feature not enabled".to_string(),
-            ))
-        }
+        Self::write_register_static(&self.hal, reg, value).await
     }
 
     /// Write specific bits in a register
@@ -1085,163 +916,61 @@ impl Rfm69Driver {
     pub fn get_stats(&self) -> PacketStats {
         self.stats.lock().unwrap().clone()
     }
-
-    /// Initialize SPI interface.
-    ///
-    /// The bus and chip-select are derived from the configured `spidev` path
-    /// (e.g. `/dev/spidev0.1` → bus 0, CS1). Previously this hardcoded CS0, so a
-    /// radio wired to CS1 never responded ("Failed to sync with radio chip").
-    #[cfg(feature = "rfm69")]
-    fn init_spi(config: &Rfm69Config) -> Result<Spi, Rfm69Error> {
-        let path = config
-            .spidev
-            .as_deref()
-            .unwrap_or("/dev/spidev0.0")
-            .to_string();
-        let (bus, ss) = parse_spidev(&path);
-        let spi = Spi::new(bus, ss, SPI_SPEED, Mode::Mode0)
-            .map_err(|e| Rfm69Error::Spi(format!("Failed to initialize SPI: {}", e)))?;
-
-        info!("SPI interface initialized: {path} (bus={bus:?}, cs={ss:?})");
-        Ok(spi)
-    }
-
-    /// Initialize GPIO pins
-    #[cfg(feature = "rfm69")]
-    fn init_gpio(
-        config: &Rfm69Config,
-    ) -> Result<(Option<OutputPin>, Option<InputPin>), Rfm69Error> {
-        let gpio = Gpio::new()
-            .map_err(|e| Rfm69Error::Gpio(format!("Failed to initialize GPIO: {}", e)))?;
-
-        let reset_pin = if let Some(pin_num) = config.reset_pin {
-            Some(
-                gpio.get(pin_num)
-                    .map_err(|e| {
-                        Rfm69Error::Gpio(format!("Failed to get reset pin {}: {}", pin_num, e))
-                    })?
-                    .into_output(),
-            )
-        } else {
-            None
-        };
-
-        let interrupt_pin = if let Some(pin_num) = config.interrupt_pin {
-            Some(
-                gpio.get(pin_num)
-                    .map_err(|e| {
-                        Rfm69Error::Gpio(format!("Failed to get interrupt pin {}: {}", pin_num, e))
-                    })?
-                    .into_input(),
-            )
-        } else {
-            None
-        };
-
-        info!(
-            "GPIO pins initialized - Reset: {:?}, Interrupt: {:?}",
-            config.reset_pin, config.interrupt_pin
-        );
-        Ok((reset_pin, interrupt_pin))
-    }
 }
 
-impl Drop for Rfm69Driver {
+impl<H: Rfm69Hal> Drop for Rfm69Driver<H> {
     fn drop(&mut self) {
-        #[cfg(feature = "rfm69")]
-        {
-            // Send shutdown signal first
-            if let Some(shutdown_tx) = self.shutdown_tx.take() {
-                let _ = shutdown_tx.send(()); // Ignore if receiver is already dropped
-            }
-
-            // Then abort the task if it doesn't shutdown gracefully
-            if let Some(handle) = self.interrupt_task.take() {
-                handle.abort();
-            }
+        // Send shutdown signal first
+        if let Some(shutdown_tx) = self.shutdown_tx.take() {
+            let _ = shutdown_tx.send(()); // Ignore if receiver is already dropped
         }
-    }
-}
 
-impl Rfm69Driver {
-    /// Get the current payload size in FIFO
-    ///
-    /// This is critical for atomic burst reading to prevent partial frames.
-    /// Inspired by sx126x_get_rx_buffer_status from One Channel Hub.
-    #[cfg(feature = "rfm69")]
-    async fn get_fifo_payload_size(spi: &Arc<Mutex<Spi>>) -> Result<usize, Rfm69Error> {
-        // For RFM69, we can determine size from the FIFO threshold and level
-        // Read the number of bytes available in FIFO
-        let fifo_status = Self::read_register_static(spi, 0x28).await?; // REG_IRQFLAGS2
-
-        // Check if FIFO has data
-        if (fifo_status & 0x40) == 0 {
-            // FifoNotEmpty bit
-            return Ok(0);
+        // Then abort the task if it doesn't shutdown gracefully
+        if let Some(handle) = self.interrupt_task.take() {
+            handle.abort();
         }
-
-        // For now, estimate based on typical wM-Bus frame sizes
-        // In a full implementation, we'd peek at the length field
-        // Most wM-Bus frames are 50-100 bytes
-        Ok(100) // Conservative estimate to ensure we read enough
-    }
-
-    /// Clear the FIFO buffer
-    ///
-    /// Used when invalid data is detected to recover cleanly.
-    #[cfg(feature = "rfm69")]
-    async fn clear_fifo(spi: &Arc<Mutex<Spi>>) -> Result<(), Rfm69Error> {
-        // Set and clear the FifoOverrun bit to flush FIFO
-        let irq_flags = Self::read_register_static(spi, 0x28).await?; // REG_IRQFLAGS2
-        Self::write_register_static(spi, 0x28, irq_flags | 0x10).await?; // Set FifoOverrun
-        Ok(())
     }
+}
 
+impl<H: Rfm69Hal + 'static> Rfm69Driver<H> {
     /// Gracefully shutdown the driver and its tasks
     pub async fn shutdown(&mut self) -> Result<(), Rfm69Error> {
-        #[cfg(feature = "rfm69")]
-        {
-            info!("Shutting down RFM69 driver");
+        info!("Shutting down RFM69 driver");
 
-            // Send shutdown signal
-            if let Some(shutdown_tx) = self.shutdown_tx.take() {
-                if shutdown_tx.send(()).is_err() {
-                    warn!("Failed to send shutdown signal - task may have already exited");
-                }
-            }
-
-            // Wait for task to complete gracefully
-            if let Some(handle) = self.interrupt_task.take() {
-                if let Err(e) = tokio::time::timeout(Duration::from_secs(5), handle).await {
-                    warn!("Interrupt task did not shutdown gracefully: {}", e);
-                }
+        // Send shutdown signal
+        if let Some(shutdown_tx) = self.shutdown_tx.take() {
+            if shutdown_tx.send(()).is_err() {
+                warn!("Failed to send shutdown signal - task may have already exited");
             }
+        }
 
-            // Put radio to sleep
-            if let Err(e) = self.set_mode(Rfm69Mode::Sleep).await {
-                warn!("Failed to put radio to sleep during shutdown: {}", e);
+        // Wait for task to complete gracefully
+        if let Some(handle) = self.interrupt_task.take() {
+            if let Err(e) = tokio::time::timeout(Duration::from_secs(5), handle).await {
+                warn!("Interrupt task did not shutdown gracefully: {}", e);
             }
+        }
 
-            info!("RFM69 driver shutdown completed");
+        // Put radio to sleep
+        if let Err(e) = self.set_mode(Rfm69Mode::Sleep).await {
+            warn!("Failed to put radio to sleep during shutdown: {}", e);
         }
 
+        info!("RFM69 driver shutdown completed");
+
         Ok(())
     }
 }
 
 // Implementation of the RadioDriver trait for RFM69
 #[async_trait::async_trait]
-impl crate::wmbus::radio::radio_driver::RadioDriver for Rfm69Driver {
+impl<H: Rfm69Hal + 'static> crate::wmbus::radio::radio_driver::RadioDriver for Rfm69Driver<H> {
     async fn initialize(
         &mut self,
         config: crate::wmbus::radio::radio_driver::WMBusConfig,
     ) -> Result<(), crate::wmbus::radio::radio_driver::RadioDriverError> {
         // Update internal configuration from trait config
-        if let Some(ref aes_key) = config
-            .sync_word
-            .get(0..32)
-            .and_then(|bytes| Some(hex::encode(bytes)))
-        {
+        if let Some(ref aes_key) = config.sync_word.get(0..32).map(hex::encode) {
             self.config.aes_key = Some(aes_key.clone());
         }
 
@@ -1360,6 +1089,38 @@ impl crate::wmbus::radio::radio_driver::RadioDriver for Rfm69Driver {
         }
     }
 
+    async fn get_mode_tagged_packet(
+        &mut self,
+    ) -> Result<
+        Option<crate::wmbus::radio::driver::ModeTaggedPacket>,
+        crate::wmbus::radio::radio_driver::RadioDriverError,
+    > {
+        if !self.config.t1_c1 {
+            return Ok(self.get_received_packet().await?.map(|packet| {
+                crate::wmbus::radio::driver::ModeTaggedPacket {
+                    mode: crate::wmbus::radio::modulation::PacketType::Gfsk,
+                    link_mode: None,
+                    payload: packet.data,
+                    rssi_dbm: packet.rssi_dbm,
+                    lora: None,
+                }
+            }));
+        }
+        // T1+C1: queued frames are raw captures; skip those that do not decode.
+        while let Some(packet) = self.get_received_packet().await? {
+            if let Some((mode, payload)) = capture_to_frame(&packet.data) {
+                return Ok(Some(crate::wmbus::radio::driver::ModeTaggedPacket {
+                    mode: crate::wmbus::radio::modulation::PacketType::Gfsk,
+                    link_mode: Some(mode),
+                    payload,
+                    rssi_dbm: packet.rssi_dbm,
+                    lora: None,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
     async fn get_stats(
         &mut self,
     ) -> Result<
@@ -0,0 +1,727 @@
+//! # Replay Protection and Per-Meter Counter Tracking
+//!
+//! Every wM-Bus layer carries a counter that a genuine meter only moves forward:
+//!
+//! | layer | counter              | width  | notes                                   |
+//! |-------|----------------------|--------|-----------------------------------------|
+//! | TPL   | access number (ACC)  | 8 bit  | +1 per telegram, wraps at 255           |
+//! | ELL   | access number (ACC)  | 8 bit  | CI 0x8C–0x8F                            |
+//! | AFL   | message counter (MCR)| 32 bit | CI 0x90, bound into the mode 7/13 MAC   |
+//!
+//! [`ReplayGuard`] remembers these counters per meter and labels each frame with a
+//! [`Freshness`]:
+//!
+//! - **Fresh** — the counter moved forward (gaps from missed telegrams are allowed, as is
+//!   the 8-bit wraparound).
+//! - **Duplicate** — same counter and same content as the last telegram: a retransmission
+//!   or a repeater copy.
+//! - **Suspicious** — the counter repeated with different content, went backwards, or the
+//!   content matches an *older* telegram: a possible replay. The meter's state is not
+//!   moved, so replayed telegrams cannot rebase the tracker.
+//! - **CounterReset** — the tracker was rebased onto new content with a counter that does
+//!   not step forward: after the meter was silent for [`ReplayGuardConfig::reset_after`]
+//!   (an 8-bit access number may have moved anywhere), or after an operator called
+//!   [`ReplayGuard::accept_reset`] (battery change, meter swap). A silent meter is never
+//!   rebased onto an access number just behind the last accepted one, and a 32-bit
+//!   message counter only ever moves forward without an operator reset.
+//!
+//! The verdict is taken on the strongest counter present: the AFL message counter, else the
+//! TPL access number, else the ELL access number. Frames with no counter are judged on
+//! their content alone. State persists as JSON ([`ReplayGuard::save_to_file`]) or as a
+//! serializable [`ReplayGuardState`] ([`ReplayGuard::state`], [`ReplayGuard::restore`]).
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use mbus_rs::wmbus::frame::{parse_wmbus_frame, WMBusFrame};
+//! use mbus_rs::wmbus::replay_guard::{Freshness, ReplayGuard, ReplayGuardConfig};
+//!
+//! let telegram = |acc: u8, value: u8| {
+//!     let payload = [acc, 0x00, 0x00, 0x00, 0x2F, 0x2F, 0x02, 0x13, value, 0x00];
+//!     parse_wmbus_frame(&WMBusFrame::build(0x44, 0x2C2D, 0x12345678, 0x1B, 0x16, 0x7A, &payload))
+//!         .unwrap()
+//! };
+//!
+//! let mut guard = ReplayGuard::new(ReplayGuardConfig::default());
+//! assert_eq!(guard.check_at(&telegram(41, 1), 1_000).freshness, Freshness::Fresh);
+//! assert_eq!(guard.check_at(&telegram(41, 1), 1_001).freshness, Freshness::Duplicate);
+//! assert_eq!(guard.check_at(&telegram(42, 2), 1_016).freshness, Freshness::Fresh);
+//! // An old telegram played back later.
+//! assert_eq!(guard.check_at(&telegram(41, 1), 1_030).freshness, Freshness::Suspicious);
+//! ```
+
+use crate::wmbus::frame::{outer_layer_len, tpl_header_len, WMBusFrame};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// AFL fragmentation control: message counter present
+const AFL_FCL_MCRP: u16 = 0x0800;
+/// AFL fragmentation control: message control present
+const AFL_FCL_MCLP: u16 = 0x2000;
+/// AFL fragmentation control: key information present
+const AFL_FCL_KIP: u16 = 0x0200;
+
+/// The counters found in one frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameCounters {
+    /// TPL access number (short or long header)
+    pub tpl_access: Option<u8>,
+    /// ELL access number
+    pub ell_access: Option<u8>,
+    /// AFL message counter
+    pub afl_message_counter: Option<u32>,
+}
+
+impl FrameCounters {
+    /// Extract the counters of a parsed frame, walking ELL and AFL layers down to the TPL
+    pub fn of(frame: &WMBusFrame) -> Self {
+        Self::from_apl(frame.control_info, &frame.payload)
+    }
+
+    /// Extract the counters from a CI field and the bytes that follow it
+    ///
+    /// Stops at the first layer it cannot see through (unknown CI, encrypted ELL).
+    pub fn from_apl(ci: u8, data: &[u8]) -> Self {
+        let mut counters = Self::default();
+        let (mut ci, mut data) = (ci, data);
+        loop {
+            match ci {
+                0x8C..=0x8F => counters.ell_access = data.get(1).copied(),
+                // AFL: AFL.L, FCL, [MCL], [KI], [MCR], ...
+                0x90 => {
+                    if let Some(fcl) = data.get(1..3) {
+                        let fcl = u16::from_le_bytes([fcl[0], fcl[1]]);
+                        if fcl & AFL_FCL_MCRP != 0 {
+                            let mut at = 3;
+                            if fcl & AFL_FCL_MCLP != 0 {
+                                at += 1;
+                            }
+                            if fcl & AFL_FCL_KIP != 0 {
+                                at += 2;
+                            }
+                            counters.afl_message_counter = data
+                                .get(at..at + 4)
+                                .map(|m| u32::from_le_bytes([m[0], m[1], m[2], m[3]]));
+                        }
+                    }
+                }
+                // Transport layer: the access number ends the walk.
+                _ => {
+                    counters.tpl_access = match tpl_header_len(ci) {
+                        0 => None,
+                        len => data.get(len - 4).copied(),
+                    };
+                }
+            }
+            let next = outer_layer_len(ci, data).and_then(|len| data.get(len..));
+            match next.and_then(|rest| rest.split_first()) {
+                Some((&next_ci, rest)) => (ci, data) = (next_ci, rest),
+                None => return counters,
+            }
+        }
+    }
+
+    /// The counter the verdict is taken on, widened to 32 bits, with its width in bits
+    fn primary(&self) -> Option<(u32, u32)> {
+        self.afl_message_counter
+            .map(|mcr| (mcr, 32))
+            .or(self.tpl_access.map(|acc| (acc as u32, 8)))
+            .or(self.ell_access.map(|acc| (acc as u32, 8)))
+    }
+}
+
+/// Label of a frame after counter tracking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Freshness {
+    /// Counter moved forward: a new telegram
+    Fresh,
+    /// Same counter and content as the last telegram
+    Duplicate,
+    /// Counter repeated with new content, went backwards, or old content reappeared
+    Suspicious,
+    /// Counter restarted (meter reset); tracking was rebased
+    CounterReset,
+}
+
+/// Result of checking one frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CounterCheck {
+    /// The label
+    pub freshness: Freshness,
+    /// The counters found in the frame
+    pub counters: FrameCounters,
+}
+
+/// Counter tracking settings
+#[derive(Debug, Clone)]
+pub struct ReplayGuardConfig {
+    /// Largest forward step of an 8-bit access number still taken as fresh; larger steps
+    /// are treated as going backwards
+    pub max_access_gap: u8,
+    /// Silence after which an 8-bit access number that does not step forward rebases the
+    /// meter, unless it is at most `max_access_gap` behind the last accepted one
+    pub reset_after: Duration,
+    /// Recent telegrams remembered per meter for replay detection
+    pub history: usize,
+}
+
+impl Default for ReplayGuardConfig {
+    fn default() -> Self {
+        Self {
+            max_access_gap: 64,
+            reset_after: Duration::from_secs(6 * 3600),
+            history: 32,
+        }
+    }
+}
+
+/// Tracking state of one meter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeterCounterState {
+    /// Manufacturer ID (M-field)
+    pub manufacturer_id: u16,
+    /// Device address (A-field)
+    pub device_address: u32,
+    /// Counters of the last accepted telegram
+    pub last: FrameCounters,
+    /// Content hashes of recent accepted telegrams, newest last
+    pub recent: VecDeque<u64>,
+    /// Unix time of the last accepted telegram
+    pub last_seen_unix: u64,
+    /// Fresh frames seen
+    pub fresh: u64,
+    /// Duplicates seen
+    pub duplicates: u64,
+    /// Suspicious frames seen
+    pub suspicious: u64,
+    /// Counter resets seen
+    pub resets: u64,
+    /// Set by [`ReplayGuard::accept_reset`]: the next telegram with new content rebases
+    /// the meter
+    #[serde(default)]
+    pub reset_accepted: bool,
+}
+
+/// Per-meter counter tracker
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    config: ReplayGuardConfig,
+    meters: HashMap<(u16, u32), MeterCounterState>,
+}
+
+/// Serializable tracking state of every meter, see [`ReplayGuard::state`]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayGuardState {
+    /// Per-meter state, ordered by manufacturer and address
+    pub meters: Vec<MeterCounterState>,
+}
+
+impl ReplayGuard {
+    /// Create an empty tracker
+    pub fn new(config: ReplayGuardConfig) -> Self {
+        Self {
+            config,
+            meters: HashMap::new(),
+        }
+    }
+
+    /// Check a frame received now
+    pub fn check(&mut self, frame: &WMBusFrame) -> CounterCheck {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.check_at(frame, now)
+    }
+
+    /// Check a frame received at `now_unix` (seconds), e.g. from a recording
+    pub fn check_at(&mut self, frame: &WMBusFrame, now_unix: u64) -> CounterCheck {
+        let counters = FrameCounters::of(frame);
+        let hash = content_hash(frame);
+        let key = (frame.manufacturer_id, frame.device_address);
+
+        let Some(state) = self.meters.get_mut(&key) else {
+            let mut state = MeterCounterState {
+                manufacturer_id: frame.manufacturer_id,
+                device_address: frame.device_address,
+                last: counters,
+                recent: VecDeque::new(),
+                last_seen_unix: now_unix,
+                fresh: 1,
+                duplicates: 0,
+                suspicious: 0,
+                resets: 0,
+                reset_accepted: false,
+            };
+            state.recent.push_back(hash);
+            self.meters.insert(key, state);
+            return CounterCheck {
+                freshness: Freshness::Fresh,
+                counters,
+            };
+        };
+
+        let freshness = judge(&self.config, state, &counters, hash, now_unix);
+        match freshness {
+            Freshness::Fresh | Freshness::CounterReset => {
+                if freshness == Freshness::CounterReset {
+                    state.resets += 1;
+                    state.recent.clear();
+                } else {
+                    state.fresh += 1;
+                }
+                state.last = counters;
+                state.last_seen_unix = now_unix;
+                state.reset_accepted = false;
+                state.recent.push_back(hash);
+                while state.recent.len() > self.config.history.max(1) {
+                    state.recent.pop_front();
+                }
+            }
+            Freshness::Duplicate => state.duplicates += 1,
+            Freshness::Suspicious => state.suspicious += 1,
+        }
+        CounterCheck {
+            freshness,
+            counters,
+        }
+    }
+
+    /// Tracking state of one meter
+    pub fn meter(&self, manufacturer_id: u16, device_address: u32) -> Option<&MeterCounterState> {
+        self.meters.get(&(manufacturer_id, device_address))
+    }
+
+    /// Number of tracked meters
+    pub fn len(&self) -> usize {
+        self.meters.len()
+    }
+
+    /// True if no meter is tracked
+    pub fn is_empty(&self) -> bool {
+        self.meters.is_empty()
+    }
+
+    /// Let the next telegram of a meter with new content rebase its counters
+    ///
+    /// For an operator who knows the meter restarted (battery change, firmware update),
+    /// after which its counters legitimately go backwards. Returns false if the meter is
+    /// not tracked.
+    pub fn accept_reset(&mut self, manufacturer_id: u16, device_address: u32) -> bool {
+        self.meters
+            .get_mut(&(manufacturer_id, device_address))
+            .map(|state| state.reset_accepted = true)
+            .is_some()
+    }
+
+    /// Forget one meter (e.g. after it was replaced)
+    pub fn forget(&mut self, manufacturer_id: u16, device_address: u32) -> bool {
+        self.meters
+            .remove(&(manufacturer_id, device_address))
+            .is_some()
+    }
+
+    /// Snapshot of the tracking state, for storing across restarts
+    pub fn state(&self) -> ReplayGuardState {
+        let mut meters: Vec<MeterCounterState> = self.meters.values().cloned().collect();
+        meters.sort_by_key(|m| (m.manufacturer_id, m.device_address));
+        ReplayGuardState { meters }
+    }
+
+    /// Take over a stored snapshot, replacing the state of the meters it contains
+    pub fn restore(&mut self, state: ReplayGuardState) {
+        for meter in state.meters {
+            self.meters
+                .insert((meter.manufacturer_id, meter.device_address), meter);
+        }
+    }
+
+    /// Save the tracking state to a JSON file
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.state())?;
+        fs::write(path, json)
+    }
+
+    /// Load tracking state saved by [`save_to_file`](Self::save_to_file)
+    pub fn load_from_file<P: AsRef<Path>>(
+        path: P,
+        config: ReplayGuardConfig,
+    ) -> std::io::Result<Self> {
+        let state: ReplayGuardState = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut guard = Self::new(config);
+        guard.restore(state);
+        Ok(guard)
+    }
+}
+
+fn judge(
+    config: &ReplayGuardConfig,
+    state: &MeterCounterState,
+    counters: &FrameCounters,
+    hash: u64,
+    now_unix: u64,
+) -> Freshness {
+    let is_latest = state.recent.back() == Some(&hash);
+    let seen_before = state.recent.contains(&hash);
+    let silent_for = Duration::from_secs(now_unix.saturating_sub(state.last_seen_unix));
+
+    let (Some((new, bits)), Some((old, old_bits))) = (counters.primary(), state.last.primary())
+    else {
+        // No comparable counter: judge on content alone.
+        return if is_latest {
+            Freshness::Duplicate
+        } else if seen_before {
+            Freshness::Suspicious
+        } else {
+            Freshness::Fresh
+        };
+    };
+    let rebase = if state.reset_accepted {
+        Freshness::CounterReset
+    } else {
+        Freshness::Suspicious
+    };
+    if bits != old_bits {
+        // The meter changed its security layering; only an operator reset starts over.
+        return if seen_before {
+            Freshness::Suspicious
+        } else {
+            rebase
+        };
+    }
+
+    if new == old {
+        return if is_latest {
+            Freshness::Duplicate
+        } else {
+            Freshness::Suspicious
+        };
+    }
+    if seen_before {
+        // Old content under a different counter: a replay.
+        return Freshness::Suspicious;
+    }
+
+    if steps_forward(config, new, old, bits) {
+        Freshness::Fresh
+    } else if bits == 8
+        && silent_for >= config.reset_after
+        && (old as u8).wrapping_sub(new as u8) > config.max_access_gap
+    {
+        // After a long silence the access number may have moved anywhere, but not to
+        // just behind the last accepted one.
+        Freshness::CounterReset
+    } else {
+        rebase
+    }
+}
+
+/// True if counter `new` follows `old` (both `bits` wide), allowing missed telegrams and the
+/// 8-bit wraparound
+fn steps_forward(config: &ReplayGuardConfig, new: u32, old: u32, bits: u32) -> bool {
+    if bits == 8 {
+        let step = (new as u8).wrapping_sub(old as u8);
+        step != 0 && step <= config.max_access_gap
+    } else {
+        new > old
+    }
+}
+
+/// Stable 64-bit FNV-1a hash of CI and payload (persisted, so not `DefaultHasher`), with
+/// the repeater bits masked out — ELL relayed bit and the TPL hop counter below any
+/// ELL/AFL — so repeater copies hash like the original
+fn content_hash(frame: &WMBusFrame) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for &b in std::iter::once(&frame.control_info).chain(&frame.repeater_neutral_payload()) {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::frame::parse_wmbus_frame;
+
+    const T0: u64 = 1_700_000_000;
+
+    fn build(ci: u8, payload: &[u8]) -> WMBusFrame {
+        parse_wmbus_frame(&WMBusFrame::build(
+            0x44, 0x2C2D, 0x12345678, 0x1B, 0x16, ci, payload,
+        ))
+        .unwrap()
+    }
+
+    /// Short-header telegram: ACC, status, CW (hop counter in bits 1..0), one record.
+    fn tpl(acc: u8, hops: u8, value: u8) -> WMBusFrame {
+        build(
+            0x7A,
+            &[acc, 0x00, hops, 0x00, 0x2F, 0x2F, 0x02, 0x13, value, 0x00],
+        )
+    }
+
+    /// AFL with MCL and MCR, followed by a short-header TPL.
+    fn afl(mcr: u32, value: u8) -> WMBusFrame {
+        let mut payload = vec![0x07, 0x00, 0x28, 0x05];
+        payload.extend_from_slice(&mcr.to_le_bytes());
+        payload.extend_from_slice(&[0x7A, 0x11, 0x00, 0x00, 0x00, 0x02, 0x13, value, 0x00]);
+        build(0x90, &payload)
+    }
+
+    #[test]
+    fn extracts_counters_through_ell_and_afl() {
+        assert_eq!(
+            FrameCounters::of(&tpl(0x33, 0, 0)),
+            FrameCounters {
+                tpl_access: Some(0x33),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            FrameCounters::of(&afl(0x01020304, 0)),
+            FrameCounters {
+                tpl_access: Some(0x11),
+                afl_message_counter: Some(0x01020304),
+                ..Default::default()
+            }
+        );
+
+        // ELL I (CC, ACC) then a long header TPL (ID, M, V, T, ACC, ST, CW).
+        let mut ell = vec![0x20, 0x5A, 0x72];
+        ell.extend_from_slice(&[
+            0x78, 0x56, 0x34, 0x12, 0x2D, 0x2C, 0x1B, 0x16, 0x44, 0, 0, 0,
+        ]);
+        assert_eq!(
+            FrameCounters::of(&build(0x8C, &ell)),
+            FrameCounters {
+                tpl_access: Some(0x44),
+                ell_access: Some(0x5A),
+                afl_message_counter: None,
+            }
+        );
+
+        // ELL II with encryption bits set in the session number: the walk stops there.
+        let ell2 = [0x20, 0x5A, 0x00, 0x00, 0x00, 0x20, 0xAA, 0xBB, 0x7A, 0x01];
+        assert_eq!(
+            FrameCounters::of(&build(0x8D, &ell2)),
+            FrameCounters {
+                ell_access: Some(0x5A),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn access_number_wraps_and_duplicates_are_recognised() {
+        let mut guard = ReplayGuard::default();
+        assert_eq!(
+            guard.check_at(&tpl(254, 0, 1), T0).freshness,
+            Freshness::Fresh
+        );
+        assert_eq!(
+            guard.check_at(&tpl(255, 0, 2), T0 + 8).freshness,
+            Freshness::Fresh
+        );
+        // A repeater copy differs only in the hop counter.
+        assert_eq!(
+            guard.check_at(&tpl(255, 1, 2), T0 + 9).freshness,
+            Freshness::Duplicate
+        );
+        assert_eq!(
+            guard.check_at(&tpl(0, 0, 3), T0 + 16).freshness,
+            Freshness::Fresh
+        );
+        // Missed telegrams: a small gap is still fresh.
+        assert_eq!(
+            guard.check_at(&tpl(5, 0, 4), T0 + 64).freshness,
+            Freshness::Fresh
+        );
+
+        let state = guard.meter(0x2C2D, 0x12345678).unwrap();
+        assert_eq!(state.last.tpl_access, Some(5));
+        assert_eq!((state.fresh, state.duplicates), (4, 1));
+    }
+
+    #[test]
+    fn repeater_copies_below_ell_and_afl_are_duplicates() {
+        // ELL I (CC, ACC) then a short header TPL (ACC, ST, CW) with the given hop count.
+        let ell = |hops: u8| build(0x8C, &[0x20, 0x5A, 0x7A, 0x11, 0x00, hops, 0x00, 0x13]);
+        let mut guard = ReplayGuard::default();
+        assert_eq!(guard.check_at(&ell(0), T0).freshness, Freshness::Fresh);
+        assert_eq!(
+            guard.check_at(&ell(1), T0 + 1).freshness,
+            Freshness::Duplicate
+        );
+
+        let mut afl_copy = afl(7, 1);
+        let mut guard = ReplayGuard::default();
+        guard.check_at(&afl_copy, T0);
+        // The TPL configuration word follows the AFL (L + 7 bytes) and the TPL CI, ACC, status.
+        afl_copy.payload[11] |= 0x01;
+        assert_eq!(
+            guard.check_at(&afl_copy, T0 + 1).freshness,
+            Freshness::Duplicate
+        );
+    }
+
+    #[test]
+    fn replays_are_suspicious_and_do_not_move_state() {
+        let mut guard = ReplayGuard::default();
+        guard.check_at(&tpl(10, 0, 1), T0);
+        guard.check_at(&tpl(11, 0, 2), T0 + 8);
+
+        // Same counter, different content.
+        assert_eq!(
+            guard.check_at(&tpl(11, 0, 9), T0 + 9).freshness,
+            Freshness::Suspicious
+        );
+        // Counter went backwards.
+        assert_eq!(
+            guard.check_at(&tpl(3, 0, 7), T0 + 10).freshness,
+            Freshness::Suspicious
+        );
+        // Old telegram replayed.
+        assert_eq!(
+            guard.check_at(&tpl(10, 0, 1), T0 + 11).freshness,
+            Freshness::Suspicious
+        );
+
+        let state = guard.meter(0x2C2D, 0x12345678).unwrap();
+        assert_eq!(state.last.tpl_access, Some(11));
+        assert_eq!(state.suspicious, 3);
+        assert_eq!(
+            guard.check_at(&tpl(12, 0, 3), T0 + 16).freshness,
+            Freshness::Fresh
+        );
+    }
+
+    #[test]
+    fn detects_counter_resets() {
+        let mut guard = ReplayGuard::default();
+        guard.check_at(&afl(5_000, 1), T0);
+        assert_eq!(
+            guard.check_at(&afl(5_001, 2), T0 + 8).freshness,
+            Freshness::Fresh
+        );
+        // A backwards message counter is a replay, near zero or not, and even after a
+        // long silence...
+        assert_eq!(
+            guard.check_at(&afl(4_000, 3), T0 + 16).freshness,
+            Freshness::Suspicious
+        );
+        let later = T0 + ReplayGuardConfig::default().reset_after.as_secs();
+        assert_eq!(
+            guard.check_at(&afl(1, 4), later).freshness,
+            Freshness::Suspicious
+        );
+        // ...until an operator accepts the reset.
+        assert!(guard.accept_reset(0x2C2D, 0x12345678));
+        assert_eq!(
+            guard.check_at(&afl(5_000, 1), later).freshness,
+            Freshness::Suspicious
+        );
+        assert_eq!(
+            guard.check_at(&afl(1, 4), later + 8).freshness,
+            Freshness::CounterReset
+        );
+        assert_eq!(
+            guard.check_at(&afl(2, 5), later + 16).freshness,
+            Freshness::Fresh
+        );
+        assert!(!guard.meter(0x2C2D, 0x12345678).unwrap().reset_accepted);
+
+        // An 8-bit access number going backwards after a long silence is a reset...
+        let mut guard = ReplayGuard::default();
+        guard.check_at(&tpl(200, 0, 1), T0);
+        assert_eq!(
+            guard.check_at(&tpl(100, 0, 2), later).freshness,
+            Freshness::CounterReset
+        );
+        assert_eq!(guard.meter(0x2C2D, 0x12345678).unwrap().resets, 1);
+        // ...but not onto an access number just behind the last accepted one.
+        assert_eq!(
+            guard.check_at(&tpl(90, 0, 3), later * 2).freshness,
+            Freshness::Suspicious
+        );
+    }
+
+    #[test]
+    fn relayed_ell_copies_are_duplicates() {
+        // ELL I whose CC gets the relayed bit (0x10) set by a repeater.
+        let ell = |cc: u8| build(0x8C, &[cc, 0x5A, 0x7A, 0x11, 0x00, 0x00, 0x00, 0x13]);
+        let mut guard = ReplayGuard::default();
+        assert_eq!(guard.check_at(&ell(0x20), T0).freshness, Freshness::Fresh);
+        assert_eq!(
+            guard.check_at(&ell(0x30), T0 + 1).freshness,
+            Freshness::Duplicate
+        );
+    }
+
+    #[test]
+    fn replayed_runs_do_not_rebase() {
+        let mut guard = ReplayGuard::default();
+        for (k, acc) in (100..140u8).enumerate() {
+            guard.check_at(&tpl(acc, 0, acc), T0 + 8 * k as u64);
+        }
+
+        // Old telegrams beyond the history, with counters in order among themselves.
+        for acc in 10..20u8 {
+            assert_eq!(
+                guard.check_at(&tpl(acc, 0, acc), T0 + 400).freshness,
+                Freshness::Suspicious
+            );
+        }
+        let state = guard.meter(0x2C2D, 0x12345678).unwrap();
+        assert_eq!((state.last.tpl_access, state.resets), (Some(139), 0));
+        // The real meter carries on.
+        assert_eq!(
+            guard.check_at(&tpl(140, 0, 140), T0 + 408).freshness,
+            Freshness::Fresh
+        );
+    }
+
+    #[test]
+    fn state_round_trips_through_serde() {
+        let mut guard = ReplayGuard::default();
+        guard.check_at(&tpl(10, 0, 1), T0);
+        let json = serde_json::to_string(&guard.state()).unwrap();
+
+        let mut restored = ReplayGuard::default();
+        restored.restore(serde_json::from_str(&json).unwrap());
+        assert_eq!(restored.state(), guard.state());
+        assert_eq!(
+            restored.check_at(&tpl(9, 0, 1), T0 + 60).freshness,
+            Freshness::Suspicious
+        );
+    }
+
+    #[test]
+    fn state_persists() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("counters.json");
+
+        let mut guard = ReplayGuard::default();
+        guard.check_at(&tpl(10, 0, 1), T0);
+        guard.save_to_file(&path).unwrap();
+
+        let mut restored =
+            ReplayGuard::load_from_file(&path, ReplayGuardConfig::default()).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(
+            restored.meter(0x2C2D, 0x12345678),
+            guard.meter(0x2C2D, 0x12345678)
+        );
+        // The replay is caught across the restart.
+        assert_eq!(
+            restored.check_at(&tpl(10, 0, 1), T0 + 60).freshness,
+            Freshness::Duplicate
+        );
+        assert_eq!(
+            restored.check_at(&tpl(9, 0, 1), T0 + 60).freshness,
+            Freshness::Suspicious
+        );
+    }
+}
@@ -0,0 +1,712 @@
+//! # Offline software demodulator for recorded IQ files
+//!
+//! Decodes wM-Bus telegrams from complex baseband recordings made with an SDR dongle
+//! (`rtl_sdr`, `hackrf_transfer`, GNU Radio file sinks, …) — the same job as piping a
+//! capture through rtl-wmbus, but in-crate and entirely offline: no radio hardware is
+//! involved.
+//!
+//! ## Pipeline (per channel)
+//!
+//! 1. **Mix** the channel down to 0 Hz (its offset from the recording's centre).
+//! 2. **Channel filter**: a boxcar low-pass of half a chip.
+//! 3. **FM discriminator**: phase difference between consecutive samples.
+//! 4. **Matched filter**: a one-chip moving average, then slow DC removal so a carrier
+//!    offset does not bias the slicer.
+//! 5. **Clock recovery**: a chip-rate sampling clock nudged towards the centre of the
+//!    eye on every data transition.
+//! 6. The recovered chips go to [`BitstreamScanner`], which finds the preambles and sync
+//!    words, line-decodes and CRC-checks the frames. Chips are scanned block by block;
+//!    only the tail that may hold an incomplete frame is kept.
+//!
+//! Two chip rates are covered: 100 kchip/s ([`SdrChannel::t1_c1`], modes T1 and C1 on
+//! 868.95 MHz) and 32.768 kchip/s ([`SdrChannel::s1`], mode S1 on 868.30 MHz). A
+//! recording wide enough to hold both bands is decoded on both channels in one pass.
+//!
+//! ## Sample formats
+//!
+//! | [`IqFormat`] | file ext. | sample                      |
+//! |--------------|-----------|-----------------------------|
+//! | `Cu8`        | `.cu8`    | interleaved `u8` (rtl_sdr)  |
+//! | `Cs16`       | `.cs16`   | interleaved `i16`, LE       |
+//! | `Cf32`       | `.cf32`   | interleaved `f32`, LE       |
+//!
+//! ## Usage
+//!
+//! ```rust,no_run
+//! use mbus_rs::wmbus::sdr::{decode_iq_file, IqFormat, SdrConfig};
+//!
+//! // 1.6 Msps capture centred on 868.625 MHz: both the T1/C1 and the S1 band fit.
+//! let config = SdrConfig::for_recording(1_600_000, IqFormat::Cu8, 868_625_000);
+//! for f in decode_iq_file("survey.cu8", &config)? {
+//!     println!("{:8.3}s {:?} {}", f.time_s, f.frame.mode, f.frame.link.device_address);
+//! }
+//! # Ok::<(), mbus_rs::wmbus::sdr::SdrError>(())
+//! ```
+
+use crate::wmbus::bitstream::{BitstreamScanner, DetectedFrame, LinkMode, ScanConfig};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+/// Centre frequency of modes T1 and C1 (meter → other).
+pub const T1_C1_FREQUENCY_HZ: u32 = 868_950_000;
+/// Centre frequency of mode S1.
+pub const S1_FREQUENCY_HZ: u32 = 868_300_000;
+
+/// Chip rate of modes T1 and C1.
+const T1_C1_CHIP_RATE: u32 = 100_000;
+/// Chip rate of mode S1.
+const S1_CHIP_RATE: u32 = 32_768;
+/// Samples read from a file per processing block.
+const READ_BLOCK_SAMPLES: usize = 1 << 16;
+/// Chips kept between blocks so a frame cut by a block edge is scanned whole: the longest
+/// format A frame (L = 255, 290 bytes with block CRCs) Manchester-coded, plus preamble and
+/// sync.
+const MAX_FRAME_CHIPS: usize = 300 * 16 + 64;
+/// DC tracker time constant, in chips.
+const DC_TIME_CONSTANT_CHIPS: f64 = 32.0;
+/// Clock recovery loop gain (fraction of the timing error corrected per transition).
+const CLOCK_GAIN: f64 = 0.3;
+
+/// Errors from the offline SDR decoder.
+#[derive(Error, Debug)]
+pub enum SdrError {
+    /// Reading the recording failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The configuration cannot be demodulated (e.g. too few samples per chip).
+    #[error("Invalid SDR configuration: {0}")]
+    InvalidConfig(String),
+    /// The sample format could not be inferred from the file name.
+    #[error("Unknown IQ sample format: {0}")]
+    UnknownFormat(String),
+}
+
+/// One complex baseband sample.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IqSample {
+    /// In-phase component.
+    pub i: f32,
+    /// Quadrature component.
+    pub q: f32,
+}
+
+/// On-disk IQ sample encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IqFormat {
+    /// Interleaved unsigned 8-bit, offset 127.5 (rtl_sdr's native format).
+    Cu8,
+    /// Interleaved signed 16-bit little-endian.
+    Cs16,
+    /// Interleaved 32-bit float little-endian.
+    Cf32,
+}
+
+impl IqFormat {
+    /// Bytes per complex sample.
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            IqFormat::Cu8 => 2,
+            IqFormat::Cs16 => 4,
+            IqFormat::Cf32 => 8,
+        }
+    }
+
+    /// Infer the format from a file extension (`cu8`, `cs16`, `cf32`; `cfile` and `fc32`
+    /// are accepted as GNU Radio spellings of `cf32`).
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, SdrError> {
+        let ext = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "cu8" => Ok(IqFormat::Cu8),
+            "cs16" => Ok(IqFormat::Cs16),
+            "cf32" | "fc32" | "cfile" => Ok(IqFormat::Cf32),
+            _ => Err(SdrError::UnknownFormat(ext)),
+        }
+    }
+
+    /// Convert raw bytes to samples, scaled to roughly ±1.0. A trailing partial sample
+    /// is ignored.
+    pub fn decode(&self, bytes: &[u8]) -> Vec<IqSample> {
+        bytes
+            .chunks_exact(self.bytes_per_sample())
+            .map(|s| match self {
+                IqFormat::Cu8 => IqSample {
+                    i: (s[0] as f32 - 127.5) / 127.5,
+                    q: (s[1] as f32 - 127.5) / 127.5,
+                },
+                IqFormat::Cs16 => IqSample {
+                    i: i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0,
+                    q: i16::from_le_bytes([s[2], s[3]]) as f32 / 32768.0,
+                },
+                IqFormat::Cf32 => IqSample {
+                    i: f32::from_le_bytes([s[0], s[1], s[2], s[3]]),
+                    q: f32::from_le_bytes([s[4], s[5], s[6], s[7]]),
+                },
+            })
+            .collect()
+    }
+
+    /// Convert samples to raw bytes (the inverse of [`decode`](Self::decode)), e.g. to
+    /// write test recordings.
+    pub fn encode(&self, samples: &[IqSample]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(samples.len() * self.bytes_per_sample());
+        for s in samples {
+            match self {
+                IqFormat::Cu8 => {
+                    for v in [s.i, s.q] {
+                        out.push((v * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8);
+                    }
+                }
+                IqFormat::Cs16 => {
+                    for v in [s.i, s.q] {
+                        let x = (v * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+                        out.extend_from_slice(&x.to_le_bytes());
+                    }
+                }
+                IqFormat::Cf32 => {
+                    out.extend_from_slice(&s.i.to_le_bytes());
+                    out.extend_from_slice(&s.q.to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+}
+
+/// One wM-Bus channel to demodulate out of a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdrChannel {
+    /// Channel frequency minus the recording's centre frequency, in Hz.
+    pub offset_hz: f64,
+    /// Chip rate in chips per second.
+    pub chip_rate: u32,
+    /// Modes to search for in this channel's chip stream.
+    pub modes: Vec<LinkMode>,
+}
+
+impl SdrChannel {
+    /// T1 + C1 (100 kchip/s) at `offset_hz` from the recording centre.
+    pub fn t1_c1(offset_hz: f64) -> Self {
+        Self {
+            offset_hz,
+            chip_rate: T1_C1_CHIP_RATE,
+            modes: vec![LinkMode::T, LinkMode::C],
+        }
+    }
+
+    /// S1 (32.768 kchip/s) at `offset_hz` from the recording centre.
+    pub fn s1(offset_hz: f64) -> Self {
+        Self {
+            offset_hz,
+            chip_rate: S1_CHIP_RATE,
+            modes: vec![LinkMode::S],
+        }
+    }
+}
+
+/// Offline decoder configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdrConfig {
+    /// Recording sample rate in samples per second.
+    pub sample_rate: u32,
+    /// Sample encoding.
+    pub format: IqFormat,
+    /// Channels to demodulate.
+    pub channels: Vec<SdrChannel>,
+}
+
+impl SdrConfig {
+    /// Configure for a recording centred on `center_hz`: adds the T1/C1 and S1 channels
+    /// whose band (centre ± one chip rate) lies inside the recorded bandwidth.
+    pub fn for_recording(sample_rate: u32, format: IqFormat, center_hz: u32) -> Self {
+        let half_bw = sample_rate as f64 / 2.0;
+        let mut channels = Vec::new();
+        for channel in [
+            SdrChannel::t1_c1(T1_C1_FREQUENCY_HZ as f64 - center_hz as f64),
+            SdrChannel::s1(S1_FREQUENCY_HZ as f64 - center_hz as f64),
+        ] {
+            if channel.offset_hz.abs() + channel.chip_rate as f64 <= half_bw {
+                channels.push(channel);
+            }
+        }
+        Self {
+            sample_rate,
+            format,
+            channels,
+        }
+    }
+}
+
+/// A frame decoded from a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdrFrame {
+    /// Index into [`SdrConfig::channels`] of the channel it was found on.
+    pub channel: usize,
+    /// Start of the sync word, in seconds from the start of the recording.
+    pub time_s: f64,
+    /// The recovered frame (mode, polarity, symbol errors, link layer).
+    pub frame: DetectedFrame,
+}
+
+/// Streaming FSK demodulator with clock recovery: IQ samples in, chips out.
+///
+/// State carries across [`process`](Self::process) calls, so a recording can be fed
+/// block by block.
+#[derive(Debug, Clone)]
+pub struct FskDemodulator {
+    samples_per_chip: f64,
+    mix_step: f64,
+    mix_phase: f64,
+    chan_len: usize,
+    chan_buf: VecDeque<(f64, f64)>,
+    chan_sum: (f64, f64),
+    prev: Option<(f64, f64)>,
+    mf_len: usize,
+    mf_buf: VecDeque<f64>,
+    mf_sum: f64,
+    dc: f64,
+    dc_alpha: f64,
+    clock_phase: f64,
+    last_level: bool,
+}
+
+impl FskDemodulator {
+    /// Create a demodulator for a channel at `offset_hz` from the recording centre.
+    ///
+    /// # Errors
+    ///
+    /// [`SdrError::InvalidConfig`] if there are fewer than 4 samples per chip.
+    pub fn new(sample_rate: u32, chip_rate: u32, offset_hz: f64) -> Result<Self, SdrError> {
+        let samples_per_chip = sample_rate as f64 / chip_rate.max(1) as f64;
+        if samples_per_chip < 4.0 {
+            return Err(SdrError::InvalidConfig(format!(
+                "{sample_rate} sps gives {samples_per_chip:.1} samples per chip at \
+                 {chip_rate} chip/s; at least 4 are needed"
+            )));
+        }
+        Ok(Self {
+            samples_per_chip,
+            mix_step: -2.0 * PI * offset_hz / sample_rate as f64,
+            mix_phase: 0.0,
+            chan_len: ((samples_per_chip / 2.0).round() as usize).max(1),
+            chan_buf: VecDeque::new(),
+            chan_sum: (0.0, 0.0),
+            prev: None,
+            mf_len: (samples_per_chip.round() as usize).max(1),
+            mf_buf: VecDeque::new(),
+            mf_sum: 0.0,
+            dc: 0.0,
+            dc_alpha: 1.0 / (DC_TIME_CONSTANT_CHIPS * samples_per_chip),
+            clock_phase: 0.0,
+            last_level: false,
+        })
+    }
+
+    /// Demodulate `samples`, appending one chip (`0`/`1`, `1` = upper tone) per recovered
+    /// chip period to `chips`.
+    pub fn process(&mut self, samples: &[IqSample], chips: &mut Vec<u8>) {
+        let step = 1.0 / self.samples_per_chip;
+        for s in samples {
+            // 1. Mix the channel down to 0 Hz.
+            let (sin, cos) = self.mix_phase.sin_cos();
+            let (i, q) = (s.i as f64, s.q as f64);
+            let mixed = (i * cos - q * sin, i * sin + q * cos);
+            self.mix_phase = (self.mix_phase + self.mix_step) % (2.0 * PI);
+
+            // 2. Channel filter (boxcar).
+            let filtered =
+                push_boxcar(&mut self.chan_buf, &mut self.chan_sum, mixed, self.chan_len);
+
+            // 3. FM discriminator: arg(z[n] * conj(z[n-1])).
+            let freq = match self.prev.replace(filtered) {
+                Some((pi, pq)) => {
+                    let re = filtered.0 * pi + filtered.1 * pq;
+                    let im = filtered.1 * pi - filtered.0 * pq;
+                    im.atan2(re)
+                }
+                None => 0.0,
+            };
+
+            // 4. One-chip matched filter, then DC removal.
+            self.mf_buf.push_back(freq);
+            self.mf_sum += freq;
+            if self.mf_buf.len() > self.mf_len {
+                self.mf_sum -= self.mf_buf.pop_front().unwrap_or(0.0);
+            }
+            let level = self.mf_sum / self.mf_buf.len() as f64;
+            self.dc += self.dc_alpha * (level - self.dc);
+            let bit = level > self.dc;
+
+            // 5. Clock recovery: transitions belong half-way between sampling instants.
+            self.clock_phase += step;
+            if bit != self.last_level {
+                self.clock_phase -= CLOCK_GAIN * (self.clock_phase - 0.5);
+                self.last_level = bit;
+            }
+            if self.clock_phase >= 1.0 {
+                self.clock_phase -= 1.0;
+                chips.push(bit as u8);
+            }
+        }
+    }
+}
+
+/// Add `x` to a running boxcar of `len` samples and return the current mean.
+fn push_boxcar(
+    buf: &mut VecDeque<(f64, f64)>,
+    sum: &mut (f64, f64),
+    x: (f64, f64),
+    len: usize,
+) -> (f64, f64) {
+    buf.push_back(x);
+    sum.0 += x.0;
+    sum.1 += x.1;
+    if buf.len() > len {
+        if let Some(old) = buf.pop_front() {
+            sum.0 -= old.0;
+            sum.1 -= old.1;
+        }
+    }
+    let n = buf.len() as f64;
+    (sum.0 / n, sum.1 / n)
+}
+
+/// Demodulate and decode in-memory samples on every configured channel.
+///
+/// Frames are returned ordered by time.
+pub fn decode_iq_samples(
+    samples: &[IqSample],
+    config: &SdrConfig,
+) -> Result<Vec<SdrFrame>, SdrError> {
+    let mut channels = build_channels(config)?;
+    let mut frames = Vec::new();
+    for (demod, scanner) in channels.iter_mut() {
+        demod.process(samples, &mut scanner.chips);
+        scanner.scan(&mut frames, true);
+    }
+    frames.sort_by(|a, b| a.time_s.total_cmp(&b.time_s));
+    Ok(frames)
+}
+
+/// Read an IQ recording from `reader` and decode it on every configured channel.
+///
+/// The recording is demodulated and scanned block by block; per channel only the last
+/// few thousand chips are held in memory.
+pub fn decode_iq_reader<R: Read>(
+    mut reader: R,
+    config: &SdrConfig,
+) -> Result<Vec<SdrFrame>, SdrError> {
+    let mut channels = build_channels(config)?;
+    let mut frames = Vec::new();
+    let bytes_per_sample = config.format.bytes_per_sample();
+    let mut block = vec![0u8; READ_BLOCK_SAMPLES * bytes_per_sample];
+    let mut carry = 0; // bytes of a partial sample kept from the previous read
+
+    loop {
+        let n = reader.read(&mut block[carry..])?;
+        if n == 0 {
+            break;
+        }
+        let filled = carry + n;
+        let whole = filled - filled % bytes_per_sample;
+        let samples = config.format.decode(&block[..whole]);
+        for (demod, scanner) in channels.iter_mut() {
+            demod.process(&samples, &mut scanner.chips);
+            scanner.scan(&mut frames, false);
+        }
+        block.copy_within(whole..filled, 0);
+        carry = filled - whole;
+    }
+    for (_, scanner) in channels.iter_mut() {
+        scanner.scan(&mut frames, true);
+    }
+    frames.sort_by(|a, b| a.time_s.total_cmp(&b.time_s));
+    Ok(frames)
+}
+
+/// Decode an IQ recording file on every configured channel.
+pub fn decode_iq_file(
+    path: impl AsRef<Path>,
+    config: &SdrConfig,
+) -> Result<Vec<SdrFrame>, SdrError> {
+    let file = std::fs::File::open(path)?;
+    decode_iq_reader(std::io::BufReader::new(file), config)
+}
+
+fn build_channels(config: &SdrConfig) -> Result<Vec<(FskDemodulator, ChipScanner)>, SdrError> {
+    if config.channels.is_empty() {
+        return Err(SdrError::InvalidConfig(
+            "no channel lies within the recorded bandwidth".to_string(),
+        ));
+    }
+    config
+        .channels
+        .iter()
+        .enumerate()
+        .map(|(index, c)| {
+            let demod = FskDemodulator::new(config.sample_rate, c.chip_rate, c.offset_hz)?;
+            Ok((demod, ChipScanner::new(index, c)))
+        })
+        .collect()
+}
+
+/// Scans one channel's chips as they are recovered.
+#[derive(Debug)]
+struct ChipScanner {
+    channel: usize,
+    chip_rate: u32,
+    scanner: BitstreamScanner,
+    /// Chips not yet scanned to completion
+    chips: Vec<u8>,
+    /// Chip offset of `chips[0]` from the start of the recording
+    base: usize,
+    /// Frames starting before this chip offset overlap one already reported
+    reported_until: usize,
+}
+
+impl ChipScanner {
+    fn new(channel: usize, config: &SdrChannel) -> Self {
+        Self {
+            channel,
+            chip_rate: config.chip_rate,
+            scanner: BitstreamScanner::new(ScanConfig {
+                modes: config.modes.clone(),
+                ..ScanConfig::default()
+            }),
+            chips: Vec::new(),
+            base: 0,
+            reported_until: 0,
+        }
+    }
+
+    /// Report the frames that start early enough to be complete, then drop the chips
+    /// before them. At the end of the recording (`last`) every chip is complete.
+    fn scan(&mut self, frames: &mut Vec<SdrFrame>, last: bool) {
+        let complete = if last {
+            self.chips.len()
+        } else {
+            self.chips.len().saturating_sub(MAX_FRAME_CHIPS)
+        };
+        if complete == 0 {
+            return;
+        }
+        for mut frame in self.scanner.scan_chips(&self.chips) {
+            let start = self.base + frame.bit_offset;
+            if frame.bit_offset >= complete || start < self.reported_until {
+                continue;
+            }
+            self.reported_until = start + frame.raw.len() * frame.mode.chips_per_byte();
+            frame.bit_offset = start;
+            frames.push(SdrFrame {
+                channel: self.channel,
+                time_s: start as f64 / self.chip_rate as f64,
+                frame,
+            });
+        }
+        self.chips.drain(..complete);
+        self.base += complete;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::line_code::{bytes_to_chips, encode_3of6, encode_manchester};
+
+    /// Real, complete Type B frame from meter 74644444 (KAM), CI=0x8D.
+    const REAL_TYPE_B: &str =
+        "3d25442d2c444464741b168d208d3048a121f6597959d56873b609a439b99d58531a8a726d9f0c";
+
+    /// A format-A link frame (without type byte) with valid block CRCs.
+    fn format_a(payload: &[u8]) -> Vec<u8> {
+        use crate::wmbus::crc::calculate_wmbus_crc;
+        let mut header = vec![(9 + payload.len()) as u8, 0x44, 0x2D, 0x2C];
+        header.extend_from_slice(&[0x21, 0x43, 0x65, 0x87, 0x1B, 0x07]);
+        let mut out = header.clone();
+        out.extend_from_slice(&calculate_wmbus_crc(&header).to_be_bytes());
+        for block in payload.chunks(16) {
+            out.extend_from_slice(block);
+            out.extend_from_slice(&calculate_wmbus_crc(block).to_be_bytes());
+        }
+        out
+    }
+
+    fn preamble(pairs: usize) -> Vec<u8> {
+        (0..pairs).flat_map(|_| [0, 1]).collect()
+    }
+
+    /// Continuous-phase FSK modulator with additive (deterministic) noise.
+    fn modulate(
+        chips: &[u8],
+        sample_rate: u32,
+        chip_rate: u32,
+        carrier_hz: f64,
+        deviation_hz: f64,
+        noise: f32,
+    ) -> Vec<IqSample> {
+        let sps = sample_rate as f64 / chip_rate as f64;
+        let total = (chips.len() as f64 * sps) as usize;
+        let mut phase = 0.0f64;
+        let mut seed = 0x2468_ACE1u32;
+        let mut rnd = move || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+        };
+        (0..total)
+            .map(|n| {
+                let chip = chips[((n as f64) / sps) as usize];
+                let f = carrier_hz
+                    + if chip == 1 {
+                        deviation_hz
+                    } else {
+                        -deviation_hz
+                    };
+                phase += 2.0 * PI * f / sample_rate as f64;
+                IqSample {
+                    i: 0.7 * phase.cos() as f32 + noise * rnd(),
+                    q: 0.7 * phase.sin() as f32 + noise * rnd(),
+                }
+            })
+            .collect()
+    }
+
+    /// Idle carrier-free noise chips around a burst.
+    fn burst(body: Vec<u8>) -> Vec<u8> {
+        let mut chips: Vec<u8> = (0..200).map(|k| ((k * 7) % 3 == 0) as u8).collect();
+        chips.extend(body);
+        chips.extend((0..200).map(|k| ((k * 5) % 3 == 0) as u8));
+        chips
+    }
+
+    #[test]
+    fn decodes_c1_frame_from_cs16_with_carrier_offset() {
+        let frame = hex::decode(REAL_TYPE_B).unwrap();
+        let mut body = preamble(16);
+        body.extend(bytes_to_chips(&[0x54, 0x3D, 0x54]));
+        body.extend(bytes_to_chips(&frame));
+        let samples = modulate(&burst(body), 1_600_000, 100_000, 7_000.0, 50_000.0, 0.05);
+
+        // Round-trip through the on-disk encoding.
+        let bytes = IqFormat::Cs16.encode(&samples);
+        let config = SdrConfig {
+            sample_rate: 1_600_000,
+            format: IqFormat::Cs16,
+            channels: vec![SdrChannel::t1_c1(0.0)],
+        };
+        let frames = decode_iq_reader(bytes.as_slice(), &config).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].frame.mode, LinkMode::C);
+        assert_eq!(frames[0].frame.raw, frame);
+        // 200 noise chips + 32 preamble chips, then the sync word.
+        assert!((frames[0].time_s - 232.0 / 100_000.0).abs() < 0.000_1);
+    }
+
+    #[test]
+    fn decodes_t1_frame_from_cu8_on_offset_channel() {
+        let frame = format_a(&(0..24u8).collect::<Vec<_>>());
+        let mut body = preamble(24);
+        body.extend([0, 0, 0, 0, 1, 1, 1, 1, 0, 1]); // T sync
+        body.extend(encode_3of6(&frame));
+        // Recording centred 300 kHz below the channel.
+        let samples = modulate(&burst(body), 2_000_000, 100_000, 300_000.0, 50_000.0, 0.05);
+        let bytes = IqFormat::Cu8.encode(&samples);
+
+        let config = SdrConfig {
+            sample_rate: 2_000_000,
+            format: IqFormat::Cu8,
+            channels: vec![SdrChannel::t1_c1(300_000.0)],
+        };
+        let frames = decode_iq_reader(bytes.as_slice(), &config).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].frame.mode, LinkMode::T);
+        assert_eq!(frames[0].frame.symbol_errors, 0);
+        assert_eq!(frames[0].frame.link.device_address, 87_654_321);
+    }
+
+    #[test]
+    fn decodes_s1_frame_from_cf32() {
+        let frame = format_a(&[0x78, 0x04, 0x13, 0x01, 0x02, 0x03, 0x04]);
+        let mut body = preamble(40);
+        body.extend([0, 0, 0, 1, 1, 1, 0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0]); // S sync
+        body.extend(encode_manchester(&frame));
+        let samples = modulate(&burst(body), 1_048_576, 32_768, -2_000.0, 50_000.0, 0.05);
+
+        let frames = decode_iq_samples(
+            &IqFormat::Cf32.decode(&IqFormat::Cf32.encode(&samples)),
+            &SdrConfig {
+                sample_rate: 1_048_576,
+                format: IqFormat::Cf32,
+                channels: vec![SdrChannel::s1(0.0)],
+            },
+        )
+        .unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].frame.mode, LinkMode::S);
+        assert!(frames[0].frame.link.crc_ok);
+    }
+
+    #[test]
+    fn frames_split_across_blocks_are_found_once() {
+        let noise = |n: usize| (0..n).map(|k| ((k * 7) % 3 == 0) as u8);
+        let t_frame = format_a(&(0..24u8).collect::<Vec<_>>());
+        let mut chips: Vec<u8> = noise(7_000).collect();
+        chips.extend(preamble(24));
+        let t_start = chips.len();
+        chips.extend([0, 0, 0, 0, 1, 1, 1, 1, 0, 1]); // T sync
+        chips.extend(encode_3of6(&t_frame));
+        chips.extend(noise(9_000));
+        chips.extend(preamble(16));
+        let c_start = chips.len();
+        chips.extend(bytes_to_chips(&[0x54, 0x3D, 0x54]));
+        chips.extend(bytes_to_chips(&hex::decode(REAL_TYPE_B).unwrap()));
+        chips.extend(noise(500));
+
+        let mut scanner = ChipScanner::new(0, &SdrChannel::t1_c1(0.0));
+        let mut frames = Vec::new();
+        for block in chips.chunks(333) {
+            scanner.chips.extend_from_slice(block);
+            scanner.scan(&mut frames, false);
+            assert!(scanner.chips.len() <= MAX_FRAME_CHIPS + 333);
+        }
+        scanner.scan(&mut frames, true);
+        assert!(scanner.chips.is_empty());
+
+        let found: Vec<(LinkMode, usize)> = frames
+            .iter()
+            .map(|f| (f.frame.mode, f.frame.bit_offset))
+            .collect();
+        assert_eq!(found, vec![(LinkMode::T, t_start), (LinkMode::C, c_start)]);
+    }
+
+    #[test]
+    fn config_for_recording_picks_channels_in_band() {
+        let both = SdrConfig::for_recording(2_400_000, IqFormat::Cu8, 868_625_000);
+        assert_eq!(both.channels.len(), 2);
+        assert_eq!(both.channels[0].offset_hz, 325_000.0);
+        assert_eq!(both.channels[1].offset_hz, -325_000.0);
+
+        let narrow = SdrConfig::for_recording(250_000, IqFormat::Cu8, 868_950_000);
+        assert_eq!(narrow.channels, vec![SdrChannel::t1_c1(0.0)]);
+    }
+
+    #[test]
+    fn rejects_undersampled_and_unknown_formats() {
+        assert!(matches!(
+            FskDemodulator::new(250_000, 100_000, 0.0),
+            Err(SdrError::InvalidConfig(_))
+        ));
+        assert_eq!(IqFormat::from_path("cap.cs16").unwrap(), IqFormat::Cs16);
+        assert_eq!(IqFormat::from_path("cap.CFILE").unwrap(), IqFormat::Cf32);
+        assert!(matches!(
+            IqFormat::from_path("cap.wav"),
+            Err(SdrError::UnknownFormat(_))
+        ));
+    }
+}
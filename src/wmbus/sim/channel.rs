@@ -0,0 +1,176 @@
+//! The virtual RF channel: signal strength, collisions and bit errors.
+
+use tokio::time::Instant;
+
+use super::SimRng;
+
+/// Propagation and reception model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelConfig {
+    /// Log-distance path loss exponent (2 = free space, 2.7–3.5 = urban/indoor).
+    pub path_loss_exponent: f64,
+    /// Standard deviation of log-normal shadowing per telegram, in dB.
+    pub shadowing_db: f64,
+    /// Weakest signal the receiver decodes, in dBm.
+    pub sensitivity_dbm: f64,
+    /// A telegram survives an overlapping one that is at least this much weaker.
+    pub capture_db: f64,
+    /// Bit error rate at the sensitivity limit (1e-3 loses about one in five short
+    /// telegrams).
+    pub ber_at_sensitivity: f64,
+    /// The bit error rate falls tenfold every this many dB above sensitivity.
+    pub ber_slope_db: f64,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            path_loss_exponent: 2.7,
+            shadowing_db: 4.0,
+            sensitivity_dbm: -105.0,
+            capture_db: 6.0,
+            ber_at_sensitivity: 1e-3,
+            ber_slope_db: 2.0,
+        }
+    }
+}
+
+impl ChannelConfig {
+    /// An ideal channel: no shadowing, no bit errors, collisions still apply.
+    pub fn ideal() -> Self {
+        Self {
+            shadowing_db: 0.0,
+            ber_at_sensitivity: 0.0,
+            ..Self::default()
+        }
+    }
+
+    /// Mean received power of a transmitter `distance_m` away: free-space loss over the
+    /// first metre, then the path loss exponent.
+    pub fn mean_rssi_dbm(&self, tx_power_dbm: f64, distance_m: f64, frequency_hz: u32) -> f64 {
+        let loss_1m = 20.0 * f64::from(frequency_hz).log10() - 147.55;
+        let loss = loss_1m + 10.0 * self.path_loss_exponent * distance_m.max(1.0).log10();
+        tx_power_dbm - loss
+    }
+
+    /// Bit error rate of a telegram received at `rssi_dbm`.
+    pub fn bit_error_rate(&self, rssi_dbm: f64) -> f64 {
+        let margin = rssi_dbm - self.sensitivity_dbm;
+        (self.ber_at_sensitivity * 10f64.powf(-margin / self.ber_slope_db)).min(0.5)
+    }
+
+    /// What happens to `tx` given the transmissions overlapping it on its carrier.
+    pub(super) fn resolve<'a>(
+        &self,
+        tx: &Transmission,
+        overlapping: impl Iterator<Item = &'a Transmission>,
+        rng: &mut SimRng,
+    ) -> Fate {
+        if tx.rssi_dbm < self.sensitivity_dbm {
+            return Fate::TooWeak;
+        }
+        let mut overlapping = overlapping;
+        if overlapping.any(|other| other.rssi_dbm + self.capture_db > tx.rssi_dbm) {
+            return Fate::Collided;
+        }
+        let ber = self.bit_error_rate(tx.rssi_dbm);
+        let mut frame = tx.frame.clone();
+        let mut bit_errors = 0;
+        if ber > 0.0 {
+            for byte in &mut frame {
+                for bit in 0..8 {
+                    if rng.uniform() < ber {
+                        *byte ^= 1 << bit;
+                        bit_errors += 1;
+                    }
+                }
+            }
+        }
+        Fate::Delivered { frame, bit_errors }
+    }
+}
+
+/// One telegram on the air.
+#[derive(Debug, Clone)]
+pub(super) struct Transmission {
+    pub(super) frequency_hz: u32,
+    pub(super) start: Instant,
+    pub(super) end: Instant,
+    pub(super) rssi_dbm: f64,
+    /// The radio frame a receiver hands on.
+    pub(super) frame: Vec<u8>,
+    pub(super) resolved: bool,
+}
+
+impl Transmission {
+    pub(super) fn overlaps(&self, other: &Self) -> bool {
+        self.frequency_hz == other.frequency_hz && self.start < other.end && other.start < self.end
+    }
+}
+
+/// How a transmission ended at the receiver.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Fate {
+    Delivered { frame: Vec<u8>, bit_errors: u32 },
+    Collided,
+    TooWeak,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn tx(start_ms: u64, rssi_dbm: f64) -> Transmission {
+        let epoch = Instant::now();
+        Transmission {
+            frequency_hz: 868_950_000,
+            start: epoch + Duration::from_millis(start_ms),
+            end: epoch + Duration::from_millis(start_ms + 5),
+            rssi_dbm,
+            frame: vec![0; 32],
+            resolved: false,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn capture_effect_and_sensitivity() {
+        let channel = ChannelConfig::ideal();
+        let mut rng = SimRng::new(3);
+        let strong = tx(0, -60.0);
+        let weak = tx(2, -70.0);
+        let close = tx(3, -63.0);
+        assert!(strong.overlaps(&weak) && !strong.overlaps(&tx(5, -60.0)));
+
+        // 10 dB stronger survives; the weaker one and a 3 dB contest do not.
+        assert!(matches!(
+            channel.resolve(&strong, [&weak].into_iter(), &mut rng),
+            Fate::Delivered { bit_errors: 0, .. }
+        ));
+        assert_eq!(
+            channel.resolve(&weak, [&strong].into_iter(), &mut rng),
+            Fate::Collided
+        );
+        assert_eq!(
+            channel.resolve(&strong, [&close].into_iter(), &mut rng),
+            Fate::Collided
+        );
+        assert_eq!(
+            channel.resolve(&tx(0, -110.0), std::iter::empty(), &mut rng),
+            Fate::TooWeak
+        );
+    }
+
+    #[test]
+    fn path_loss_and_bit_errors_fall_off_with_margin() {
+        let channel = ChannelConfig::default();
+        let near = channel.mean_rssi_dbm(14.0, 10.0, 868_950_000);
+        let far = channel.mean_rssi_dbm(14.0, 100.0, 868_950_000);
+        assert!((near - far - 27.0).abs() < 1e-9);
+        // 169 MHz loses ~14 dB less than 868 MHz.
+        assert!(channel.mean_rssi_dbm(14.0, 100.0, 169_406_250) - far > 14.0);
+
+        assert!((channel.bit_error_rate(-105.0) - 1e-3).abs() < 1e-12);
+        assert!((channel.bit_error_rate(-101.0) - 1e-5).abs() < 1e-12);
+    }
+}
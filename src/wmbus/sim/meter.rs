@@ -0,0 +1,278 @@
+//! Virtual meters: what they send, how often, and how strong.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use super::SimRng;
+use crate::payload::record_encoder::{DataRecord, RecordValue};
+use crate::wmbus::bidirectional::MeterAddress;
+use crate::wmbus::bitstream::LinkMode;
+use crate::wmbus::mode_c::bcd4_encode;
+use crate::wmbus::telegram::{Security, Telegram, TelegramBuilder, TelegramFormat};
+
+/// How a meter's reading evolves from one telegram to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trajectory {
+    /// Always the same reading.
+    Constant(i64),
+    /// `start` plus `per_hour` for every hour since the simulation began.
+    Linear { start: i64, per_hour: f64 },
+    /// A random walk that never decreases: each telegram adds `0..=max_step`, like a
+    /// volume register.
+    Monotonic { start: i64, max_step: i64 },
+}
+
+impl Trajectory {
+    fn start(&self) -> i64 {
+        match *self {
+            Self::Constant(value) => value,
+            Self::Linear { start, .. } | Self::Monotonic { start, .. } => start,
+        }
+    }
+}
+
+/// One virtual meter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimMeter {
+    /// Link layer address.
+    pub address: MeterAddress,
+    /// Link mode it transmits in; decides carrier, chip rate and line code.
+    pub mode: LinkMode,
+    /// Nominal transmit interval.
+    pub interval: Duration,
+    /// Each interval is drawn from `interval ± jitter`.
+    pub jitter: Duration,
+    /// Security mode and key.
+    pub security: Security,
+    /// VIF chain of the reading.
+    pub vib: Vec<u8>,
+    /// How the reading evolves.
+    pub trajectory: Trajectory,
+    /// Transmit power in dBm.
+    pub tx_power_dbm: f64,
+    /// Distance to the receiver in metres.
+    pub distance_m: f64,
+    /// A repeater re-sends each telegram this long after the meter.
+    pub repeat_after: Option<Duration>,
+}
+
+impl SimMeter {
+    /// A meter 100 m away sending an unencrypted, monotonically rising volume (VIF
+    /// `0x13`) every `interval` at 14 dBm.
+    pub fn new(address: MeterAddress, mode: LinkMode, interval: Duration) -> Self {
+        Self {
+            address,
+            mode,
+            interval,
+            jitter: Duration::ZERO,
+            security: Security::None,
+            vib: vec![0x13],
+            trajectory: Trajectory::Monotonic {
+                start: 0,
+                max_step: 10,
+            },
+            tx_power_dbm: 14.0,
+            distance_m: 100.0,
+            repeat_after: None,
+        }
+    }
+
+    /// `count` meters with consecutive BCD addresses from 20000000, spread between 20 m
+    /// and 500 m from the receiver, with 10% interval jitter.
+    pub fn fleet(count: usize, mode: LinkMode, interval: Duration) -> Vec<Self> {
+        (0..count)
+            .map(|i| {
+                let address = MeterAddress {
+                    manufacturer_id: 0x2C2D,
+                    address: bcd4_encode(20_000_000 + i as u32)
+                        .expect("fleet addresses fit 8 BCD digits"),
+                    version: 0x01,
+                    device_type: 0x07,
+                };
+                Self::new(address, mode, interval)
+                    .jitter(interval / 10)
+                    .distance(20.0 + (i * 7919 % 481) as f64)
+            })
+            .collect()
+    }
+
+    /// Set the interval jitter.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the security mode and key.
+    pub fn security(mut self, security: Security) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Set the reading's VIF chain.
+    pub fn vib(mut self, vib: &[u8]) -> Self {
+        self.vib = vib.to_vec();
+        self
+    }
+
+    /// Set the reading's trajectory.
+    pub fn trajectory(mut self, trajectory: Trajectory) -> Self {
+        self.trajectory = trajectory;
+        self
+    }
+
+    /// Set the transmit power.
+    pub fn tx_power(mut self, tx_power_dbm: f64) -> Self {
+        self.tx_power_dbm = tx_power_dbm;
+        self
+    }
+
+    /// Set the distance to the receiver.
+    pub fn distance(mut self, distance_m: f64) -> Self {
+        self.distance_m = distance_m;
+        self
+    }
+
+    /// Have a repeater re-send every telegram `delay` after the meter.
+    pub fn repeated_after(mut self, delay: Duration) -> Self {
+        self.repeat_after = Some(delay);
+        self
+    }
+}
+
+/// Carrier frequency of `mode` (N mode on channel 1a).
+pub fn frequency_hz(mode: LinkMode) -> u32 {
+    match mode {
+        LinkMode::T | LinkMode::C => 868_950_000,
+        LinkMode::S => 868_300_000,
+        LinkMode::N => 169_406_250,
+    }
+}
+
+/// Time on air of a telegram in `mode`: preamble and sync, then the format A frame in
+/// the mode's line code.
+pub fn airtime(mode: LinkMode, telegram: &Telegram) -> Duration {
+    // (preamble + sync chips, chips per byte, chip rate)
+    let (overhead, per_byte, rate) = match mode {
+        LinkMode::T => (48, 12, 100_000),
+        LinkMode::S => (576, 16, 32_768),
+        LinkMode::C => (64, 8, 100_000),
+        LinkMode::N => (32, 8, 4_800),
+    };
+    let bytes = telegram.encode(TelegramFormat::ModeCTypeA).len() - 1;
+    let chips = overhead + bytes as u64 * per_byte;
+    Duration::from_nanos(chips * 1_000_000_000 / rate)
+}
+
+/// A meter while the simulation runs.
+pub(super) struct MeterState {
+    pub(super) config: SimMeter,
+    builder: TelegramBuilder,
+    value: i64,
+    epoch: Instant,
+}
+
+impl MeterState {
+    pub(super) fn new(config: SimMeter, epoch: Instant, rng: &mut SimRng) -> Self {
+        let builder = TelegramBuilder::new(config.address)
+            .security(config.security.clone())
+            .access_number(rng.next_u64() as u8);
+        Self {
+            value: config.trajectory.start(),
+            config,
+            builder,
+            epoch,
+        }
+    }
+
+    /// Build the telegram sent at `now`.
+    pub(super) fn transmit(&mut self, now: Instant, rng: &mut SimRng) -> Option<Telegram> {
+        self.value = match self.config.trajectory {
+            Trajectory::Constant(value) => value,
+            Trajectory::Linear { start, per_hour } => {
+                start + (per_hour * (now - self.epoch).as_secs_f64() / 3600.0) as i64
+            }
+            Trajectory::Monotonic { max_step, .. } => {
+                self.value + rng.up_to(max_step.max(0) as u64) as i64
+            }
+        };
+        let len = if i32::try_from(self.value).is_ok() {
+            4
+        } else {
+            8
+        };
+        let record = DataRecord::new(
+            &self.config.vib,
+            RecordValue::Integer {
+                value: self.value,
+                len,
+            },
+        );
+        match self.builder.build(&[record]) {
+            Ok(telegram) => Some(telegram),
+            Err(e) => {
+                log::warn!(
+                    "Meter {:#X} cannot build a telegram: {e}",
+                    self.config.address.address
+                );
+                None
+            }
+        }
+    }
+
+    /// When the telegram after one sent at `now` is due.
+    pub(super) fn next_due(&self, now: Instant, rng: &mut SimRng) -> Instant {
+        let jitter = self.config.jitter.as_secs_f64();
+        let offset = (2.0 * rng.uniform() - 1.0) * jitter;
+        let interval = (self.config.interval.as_secs_f64() + offset).max(0.001);
+        now + Duration::from_secs_f64(interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fleet_addresses_are_bcd_and_airtime_follows_the_mode() {
+        let fleet = SimMeter::fleet(12, LinkMode::T, Duration::from_secs(60));
+        assert_eq!(fleet[11].address.address, 0x2000_0011);
+        assert!(fleet.iter().all(|m| (20.0..=500.0).contains(&m.distance_m)));
+
+        let telegram = TelegramBuilder::new(fleet[0].address).build(&[]).unwrap();
+        let t = airtime(LinkMode::T, &telegram);
+        assert!(airtime(LinkMode::C, &telegram) < t);
+        assert!(airtime(LinkMode::S, &telegram) > t);
+        assert!(airtime(LinkMode::N, &telegram) > airtime(LinkMode::S, &telegram));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn trajectories_evolve_per_telegram() {
+        let epoch = Instant::now();
+        let mut rng = SimRng::new(1);
+        let meter = SimMeter::new(
+            SimMeter::fleet(1, LinkMode::C, Duration::from_secs(60))[0].address,
+            LinkMode::C,
+            Duration::from_secs(60),
+        );
+        let mut linear = MeterState::new(
+            meter.clone().trajectory(Trajectory::Linear {
+                start: 100,
+                per_hour: 60.0,
+            }),
+            epoch,
+            &mut rng,
+        );
+        let mut monotonic = MeterState::new(meter, epoch, &mut rng);
+        let mut last = 0;
+        for minute in 1..=30 {
+            let now = epoch + Duration::from_secs(60 * minute);
+            linear.transmit(now, &mut rng).unwrap();
+            assert_eq!(linear.value, 100 + minute as i64);
+            monotonic.transmit(now, &mut rng).unwrap();
+            assert!(monotonic.value >= last);
+            last = monotonic.value;
+        }
+        assert!(last > 0);
+    }
+}
@@ -0,0 +1,597 @@
+//! The simulated receiver: a [`RadioDriver`] over the virtual channel.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use super::channel::{ChannelConfig, Fate, Transmission};
+use super::meter::{airtime, frequency_hz, MeterState, SimMeter};
+use super::SimRng;
+use crate::wmbus::bitstream::LinkMode;
+use crate::wmbus::radio::driver::{ModeTaggedPacket, RadioProfile, Sx126xExt};
+use crate::wmbus::radio::modulation::PacketType;
+use crate::wmbus::radio::radio_driver::{
+    DriverInfo, RadioDriver, RadioDriverError, RadioMode, RadioStats, ReceivedPacket, WMBusConfig,
+};
+use crate::wmbus::telegram::Telegram;
+
+/// RSSI the receiver reports on a quiet channel, in dBm.
+const NOISE_FLOOR_DBM: i16 = -120;
+
+/// Received packets the radio buffers before dropping new ones.
+const DEFAULT_RX_DEPTH: usize = 64;
+
+/// What happened to every transmission so far.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SimStats {
+    /// Telegrams put on the air, repeater copies included
+    pub transmitted: u64,
+    /// Received without bit errors
+    pub delivered: u64,
+    /// Received with bit errors (the CRC fails)
+    pub corrupted: u64,
+    /// Lost to an overlapping transmission within the capture threshold
+    pub collided: u64,
+    /// Below the receiver sensitivity
+    pub too_weak: u64,
+    /// Sent while the receiver was not listening on that carrier
+    pub missed: u64,
+    /// Received while the receive buffer was full
+    pub overflowed: u64,
+}
+
+/// A simulated wM-Bus receiver listening to a fleet of [`SimMeter`]s.
+///
+/// Clones share one simulation, so a test can keep a clone to add meters and read
+/// [`SimStats`] while a [`WMBusHandle`](crate::wmbus::handle::WMBusHandle) owns another.
+/// The radio hears a transmission only if it was receiving on the transmission's carrier
+/// with the GFSK modem from before its first chip until it ended. It also implements
+/// [`Sx126xExt`], so a [`ProfileScheduler`](crate::wmbus::radio::scheduler::ProfileScheduler)
+/// can move it to LoRa and back; traffic sent meanwhile is missed.
+#[derive(Clone)]
+pub struct SimRadio {
+    state: Arc<Mutex<SimState>>,
+}
+
+impl SimRadio {
+    /// An empty simulation over `channel`, reproducible from `seed`.
+    pub fn new(channel: ChannelConfig, seed: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            state: Arc::new(Mutex::new(SimState {
+                channel,
+                rng: SimRng::new(seed),
+                epoch: now,
+                meters: Vec::new(),
+                events: BinaryHeap::new(),
+                sequence: 0,
+                on_air: Vec::new(),
+                rx: VecDeque::new(),
+                rx_depth: DEFAULT_RX_DEPTH,
+                frequency_hz: 868_950_000,
+                packet_type: None,
+                mode: RadioMode::Standby,
+                listening_since: None,
+                stats: SimStats::default(),
+                radio_stats: RadioStats::default(),
+            })),
+        }
+    }
+
+    /// Buffer at most `depth` received packets; further ones are counted as overflowed
+    /// until the driver is polled.
+    pub fn with_rx_depth(self, depth: usize) -> Self {
+        self.lock().rx_depth = depth;
+        self
+    }
+
+    /// Add a meter. Its first telegram goes out at a random point within one interval
+    /// from now, so a fleet does not start in lockstep.
+    pub fn add_meter(&self, meter: SimMeter) {
+        let mut state = self.lock();
+        let now = Instant::now();
+        state.advance(now);
+        let index = state.meters.len();
+        let epoch = state.epoch;
+        let first = now + meter.interval.mul_f64(state.rng.uniform());
+        let meter = MeterState::new(meter, epoch, &mut state.rng);
+        state.meters.push(meter);
+        state.schedule(first, Event::Meter(index));
+    }
+
+    /// Counters up to now.
+    pub fn stats(&self) -> SimStats {
+        let mut state = self.lock();
+        state.advance(Instant::now());
+        state.stats
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SimState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Advance the simulation to now, then run `f` on it.
+    fn with<T>(&self, f: impl FnOnce(&mut SimState, Instant) -> T) -> T {
+        let mut state = self.lock();
+        let now = Instant::now();
+        state.advance(now);
+        f(&mut state, now)
+    }
+}
+
+struct SimState {
+    channel: ChannelConfig,
+    rng: SimRng,
+    epoch: Instant,
+    meters: Vec<MeterState>,
+    events: BinaryHeap<Scheduled>,
+    sequence: u64,
+    /// Transmissions that may still overlap an unresolved one, in start order
+    on_air: Vec<OnAir>,
+    rx: VecDeque<(LinkMode, ReceivedPacket)>,
+    rx_depth: usize,
+    frequency_hz: u32,
+    packet_type: Option<PacketType>,
+    mode: RadioMode,
+    listening_since: Option<Instant>,
+    stats: SimStats,
+    radio_stats: RadioStats,
+}
+
+struct OnAir {
+    mode: LinkMode,
+    tx: Transmission,
+}
+
+enum Event {
+    /// The meter's next telegram is due.
+    Meter(usize),
+    /// A repeater sends its copy of a meter's telegram.
+    Repeat(usize, Telegram),
+}
+
+struct Scheduled {
+    at: Instant,
+    sequence: u64,
+    event: Event,
+}
+
+// Min-heap on (time, insertion order).
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.sequence).cmp(&(self.at, self.sequence))
+    }
+}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl SimState {
+    fn schedule(&mut self, at: Instant, event: Event) {
+        self.sequence += 1;
+        self.events.push(Scheduled {
+            at,
+            sequence: self.sequence,
+            event,
+        });
+    }
+
+    /// Put every transmission that started by `now` on the air, then resolve those that
+    /// have ended.
+    fn advance(&mut self, now: Instant) {
+        while self.events.peek().is_some_and(|next| next.at <= now) {
+            let Scheduled { at, event, .. } = self.events.pop().expect("peeked");
+            // Everything that ended before this start is final; resolving as we go keeps
+            // the air small across long gaps between polls.
+            self.resolve(at);
+            match event {
+                Event::Meter(index) => {
+                    let next = self.meters[index].next_due(at, &mut self.rng);
+                    self.schedule(next, Event::Meter(index));
+                    let Some(telegram) = self.meters[index].transmit(at, &mut self.rng) else {
+                        continue;
+                    };
+                    if let Some(delay) = self.meters[index].config.repeat_after {
+                        self.schedule(at + delay, Event::Repeat(index, telegram.repeated()));
+                    }
+                    self.launch(index, at, &telegram);
+                }
+                Event::Repeat(index, telegram) => self.launch(index, at, &telegram),
+            }
+        }
+        self.resolve(now);
+    }
+
+    fn launch(&mut self, index: usize, start: Instant, telegram: &Telegram) {
+        let meter = &self.meters[index].config;
+        let frequency_hz = frequency_hz(meter.mode);
+        let rssi_dbm =
+            self.channel
+                .mean_rssi_dbm(meter.tx_power_dbm, meter.distance_m, frequency_hz)
+                + self.channel.shadowing_db * self.rng.normal();
+        self.stats.transmitted += 1;
+        self.on_air.push(OnAir {
+            mode: meter.mode,
+            tx: Transmission {
+                frequency_hz,
+                start,
+                end: start + airtime(meter.mode, telegram),
+                rssi_dbm,
+                frame: telegram.frame_bytes(),
+                resolved: false,
+            },
+        });
+    }
+
+    /// Decide the fate of every transmission that ended by `now`, in the order they
+    /// ended. All transmissions that could overlap them have started, so the outcome is
+    /// final.
+    fn resolve(&mut self, now: Instant) {
+        let mut ended: Vec<usize> = (0..self.on_air.len())
+            .filter(|&i| !self.on_air[i].tx.resolved && self.on_air[i].tx.end <= now)
+            .collect();
+        ended.sort_by_key(|&i| self.on_air[i].tx.end);
+
+        for i in ended {
+            let tx = &self.on_air[i].tx;
+            let heard = self.packet_type == Some(PacketType::Gfsk)
+                && self.mode == RadioMode::Receive
+                && tx.frequency_hz == self.frequency_hz
+                && self.listening_since.is_some_and(|since| since <= tx.start);
+            if !heard {
+                self.stats.missed += 1;
+            } else {
+                let overlapping = self
+                    .on_air
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, other)| j != i && other.tx.overlaps(tx))
+                    .map(|(_, other)| &other.tx);
+                match self.channel.resolve(tx, overlapping, &mut self.rng) {
+                    Fate::TooWeak => self.stats.too_weak += 1,
+                    Fate::Collided => self.stats.collided += 1,
+                    Fate::Delivered { frame, bit_errors } => {
+                        let packet = ReceivedPacket {
+                            data: frame,
+                            rssi_dbm: tx.rssi_dbm.round() as i16,
+                            freq_error_hz: None,
+                            lqi: None,
+                            crc_valid: bit_errors == 0,
+                        };
+                        if bit_errors == 0 {
+                            self.stats.delivered += 1;
+                        } else {
+                            self.stats.corrupted += 1;
+                        }
+                        if self.rx.len() < self.rx_depth {
+                            self.rx.push_back((self.on_air[i].mode, packet));
+                        } else {
+                            self.stats.overflowed += 1;
+                        }
+                    }
+                }
+            }
+            self.on_air[i].tx.resolved = true;
+        }
+
+        // Keep what may still overlap a transmission that has not ended.
+        let horizon = self
+            .on_air
+            .iter()
+            .filter(|o| !o.tx.resolved)
+            .map(|o| o.tx.start)
+            .min()
+            .unwrap_or(now);
+        self.on_air.retain(|o| !o.tx.resolved || o.tx.end > horizon);
+    }
+
+    /// Stop receiving; packets not yet read are lost.
+    fn leave_receive(&mut self, mode: RadioMode) {
+        self.mode = mode;
+        self.listening_since = None;
+    }
+
+    fn pop_packet(&mut self) -> Option<(LinkMode, ReceivedPacket)> {
+        let (mode, packet) = self.rx.pop_front()?;
+        self.radio_stats.packets_received += 1;
+        if packet.crc_valid {
+            self.radio_stats.packets_crc_valid += 1;
+        } else {
+            self.radio_stats.packets_crc_error += 1;
+        }
+        self.radio_stats.last_rssi_dbm = packet.rssi_dbm;
+        Some((mode, packet))
+    }
+
+    /// Strongest signal on the receive carrier right now.
+    fn rssi(&self, now: Instant) -> i16 {
+        self.on_air
+            .iter()
+            .filter(|o| {
+                o.tx.frequency_hz == self.frequency_hz && o.tx.start <= now && now < o.tx.end
+            })
+            .map(|o| o.tx.rssi_dbm.round() as i16)
+            .max()
+            .unwrap_or(NOISE_FLOOR_DBM)
+            .max(NOISE_FLOOR_DBM)
+    }
+}
+
+#[async_trait]
+impl RadioDriver for SimRadio {
+    async fn initialize(&mut self, config: WMBusConfig) -> Result<(), RadioDriverError> {
+        self.with(|state, _| {
+            state.frequency_hz = config.frequency_hz;
+            state.packet_type = Some(PacketType::Gfsk);
+            state.rx.clear();
+            state.leave_receive(RadioMode::Standby);
+        });
+        Ok(())
+    }
+
+    async fn start_receive(&mut self) -> Result<(), RadioDriverError> {
+        self.with(|state, now| {
+            if state.packet_type.is_none() {
+                return Err(RadioDriverError::WrongState(
+                    "Radio not initialized".to_string(),
+                ));
+            }
+            if state.mode != RadioMode::Receive {
+                state.mode = RadioMode::Receive;
+                state.listening_since = Some(now);
+            }
+            Ok(())
+        })
+    }
+
+    async fn stop_receive(&mut self) -> Result<(), RadioDriverError> {
+        self.with(|state, _| state.leave_receive(RadioMode::Standby));
+        Ok(())
+    }
+
+    /// Accepted and discarded: the simulated meters do not listen.
+    async fn transmit(&mut self, _data: &[u8]) -> Result<(), RadioDriverError> {
+        self.with(|state, _| state.leave_receive(RadioMode::Standby));
+        Ok(())
+    }
+
+    async fn get_received_packet(&mut self) -> Result<Option<ReceivedPacket>, RadioDriverError> {
+        Ok(self.with(|state, _| state.pop_packet().map(|(_, packet)| packet)))
+    }
+
+    async fn get_stats(&mut self) -> Result<RadioStats, RadioDriverError> {
+        Ok(self.with(|state, _| state.radio_stats))
+    }
+
+    async fn reset_stats(&mut self) -> Result<(), RadioDriverError> {
+        self.with(|state, _| state.radio_stats = RadioStats::default());
+        Ok(())
+    }
+
+    async fn get_mode(&mut self) -> Result<RadioMode, RadioDriverError> {
+        Ok(self.with(|state, _| state.mode))
+    }
+
+    async fn sleep(&mut self) -> Result<(), RadioDriverError> {
+        self.with(|state, _| state.leave_receive(RadioMode::Sleep));
+        Ok(())
+    }
+
+    async fn wake_up(&mut self) -> Result<(), RadioDriverError> {
+        self.with(|state, _| {
+            if state.mode == RadioMode::Sleep {
+                state.mode = RadioMode::Standby;
+            }
+        });
+        Ok(())
+    }
+
+    async fn get_rssi(&mut self) -> Result<i16, RadioDriverError> {
+        Ok(self.with(|state, now| state.rssi(now)))
+    }
+
+    async fn is_channel_clear(
+        &mut self,
+        threshold_dbm: i16,
+        listen_duration: Duration,
+    ) -> Result<bool, RadioDriverError> {
+        let busy_before = self.get_rssi().await? >= threshold_dbm;
+        tokio::time::sleep(listen_duration).await;
+        Ok(!busy_before && self.get_rssi().await? < threshold_dbm)
+    }
+
+    /// Tags each packet with the link mode of the meter that sent it.
+    async fn get_mode_tagged_packet(
+        &mut self,
+    ) -> Result<Option<ModeTaggedPacket>, RadioDriverError> {
+        Ok(self.with(|state, _| {
+            state.pop_packet().map(|(mode, packet)| ModeTaggedPacket {
+                mode: PacketType::Gfsk,
+                link_mode: Some(mode),
+                payload: packet.data,
+                rssi_dbm: packet.rssi_dbm,
+                lora: None,
+            })
+        }))
+    }
+
+    fn get_driver_info(&self) -> DriverInfo {
+        DriverInfo {
+            name: "Simulated wM-Bus channel".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            frequency_bands: vec![(169_400_000, 169_475_000), (868_000_000, 870_000_000)],
+            max_packet_size: 255,
+            supported_bitrates: vec![4_800, 32_768, 100_000],
+            power_range_dbm: (0, 14),
+            features: vec!["simulation".to_string()],
+        }
+    }
+}
+
+#[async_trait]
+impl Sx126xExt for SimRadio {
+    async fn switch_profile(&mut self, profile: &RadioProfile) -> Result<(), RadioDriverError> {
+        self.with(|state, _| {
+            state.leave_receive(RadioMode::Standby);
+            state.rx.clear();
+            state.packet_type = Some(profile.packet_type());
+            state.frequency_hz = match profile {
+                RadioProfile::Wmbus(p) => p.frequency_hz,
+                RadioProfile::LoRa(p) => p.frequency_hz,
+            };
+        });
+        Ok(())
+    }
+
+    fn active_packet_type(&self) -> Option<PacketType> {
+        self.lock().packet_type
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::frame::parse_wmbus_frame;
+    use crate::wmbus::radio::driver::{LoRaProfile, WmbusProfile};
+    use crate::wmbus::radio::modulation::{CodingRate, LoRaBandwidth, SpreadingFactor};
+    use crate::wmbus::radio::scheduler::ProfileScheduler;
+
+    fn meter(i: usize, mode: LinkMode) -> SimMeter {
+        SimMeter::fleet(i + 1, mode, Duration::from_secs(10))
+            .pop()
+            .unwrap()
+    }
+
+    async fn receiving(radio: &SimRadio) -> SimRadio {
+        let mut driver = radio.clone();
+        driver.initialize(WMBusConfig::default()).await.unwrap();
+        driver.start_receive().await.unwrap();
+        driver
+    }
+
+    async fn drain(driver: &mut SimRadio) -> Vec<ReceivedPacket> {
+        let mut packets = Vec::new();
+        while let Some(packet) = driver.get_received_packet().await.unwrap() {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn receives_only_the_mode_it_listens_to() {
+        let radio = SimRadio::new(ChannelConfig::ideal(), 1);
+        radio.add_meter(meter(0, LinkMode::T));
+        radio.add_meter(meter(1, LinkMode::S));
+        let mut driver = receiving(&radio).await;
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        let packets = drain(&mut driver).await;
+        assert!((5..=7).contains(&packets.len()));
+        for packet in &packets {
+            let frame = parse_wmbus_frame(&packet.data).unwrap();
+            assert_eq!(frame.device_address, 0x2000_0000);
+            assert!(packet.crc_valid && packet.rssi_dbm < -40);
+        }
+        let stats = radio.stats();
+        assert_eq!(stats.delivered, packets.len() as u64);
+        assert!(
+            stats.missed >= 5,
+            "S-mode traffic is off-carrier: {stats:?}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn overlapping_telegrams_collide_unless_one_captures() {
+        // Ten meters sending every 20 ms keep the carrier saturated.
+        let radio = SimRadio::new(ChannelConfig::ideal(), 2);
+        for i in 0..10 {
+            let mut busy = meter(i, LinkMode::T).jitter(Duration::from_millis(5));
+            busy.interval = Duration::from_millis(20);
+            radio.add_meter(busy);
+        }
+        // One much closer meter captures the receiver over the others.
+        radio.add_meter(meter(10, LinkMode::T).distance(2.0));
+        let mut driver = receiving(&radio).await.with_rx_depth(usize::MAX);
+
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        let stats = radio.stats();
+        assert!(stats.collided > 0, "{stats:?}");
+        let near = drain(&mut driver)
+            .await
+            .iter()
+            .filter(|p| parse_wmbus_frame(&p.data).unwrap().device_address == 0x2000_0010)
+            .count();
+        assert!(near >= 11, "near meter captured {near} of 12");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn weak_signals_arrive_with_bit_errors_or_not_at_all() {
+        let radio = SimRadio::new(ChannelConfig::default(), 3);
+        for i in 0..20 {
+            radio.add_meter(meter(i, LinkMode::C).distance(1500.0 + 40.0 * i as f64));
+        }
+        let mut driver = receiving(&radio).await.with_rx_depth(usize::MAX);
+
+        tokio::time::sleep(Duration::from_secs(600)).await;
+        let stats = radio.stats();
+        assert!(stats.corrupted > 0 && stats.too_weak > 0, "{stats:?}");
+        let bad = drain(&mut driver)
+            .await
+            .iter()
+            .filter(|p| !p.crc_valid)
+            .count();
+        assert!(bad > 0);
+        assert_eq!(
+            driver.get_stats().await.unwrap().packets_crc_error,
+            bad as u32
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_lora_window_misses_wmbus_traffic() {
+        let radio = SimRadio::new(ChannelConfig::ideal(), 4);
+        radio.add_meter(meter(0, LinkMode::C).jitter(Duration::ZERO));
+        let driver = receiving(&radio).await;
+        let base = RadioProfile::Wmbus(WmbusProfile::mode_c(868_950_000, 100_000));
+        let scheduler =
+            ProfileScheduler::new(Arc::new(tokio::sync::Mutex::new(driver)), base.clone());
+
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        let heard = radio.stats().delivered;
+        assert_eq!(heard, 3);
+
+        scheduler
+            .switch_to(&RadioProfile::LoRa(LoRaProfile {
+                frequency_hz: 868_100_000,
+                sf: SpreadingFactor::SF7,
+                bw: LoRaBandwidth::BW125,
+                cr: CodingRate::CR4_5,
+                power_dbm: 14,
+                sync_word: None,
+            }))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(radio.stats().delivered, heard);
+        assert_eq!(radio.stats().missed, 3);
+
+        scheduler.switch_to(&base).await.unwrap();
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(radio.stats().delivered, heard + 3);
+    }
+}
@@ -0,0 +1,386 @@
+//! # Received Item Streams
+//!
+//! [`WMBusHandle::subscribe`](crate::wmbus::handle::WMBusHandle::subscribe) hands out an
+//! [`ItemStream`]: an `impl Stream<Item = ReceivedItem>` fed by the handle's background
+//! receiver. Every subscriber gets its own copy of every item (broadcast fan-out), so a
+//! service can `select!` over radio frames, timers and shutdown without sharing the
+//! handle's `recv_item` channel.
+//!
+//! ## Lag
+//!
+//! The fan-out holds the last [`FANOUT_CAPACITY`] items. A subscriber that falls further
+//! behind loses the oldest ones rather than stalling the receiver; the loss is counted in
+//! [`ItemStream::lagged`], logged, and — for consumers that must react to it — surfaced
+//! in-band by [`ItemStream::with_lag_reports`].
+//!
+//! ## Filters
+//!
+//! [`ItemFilter`] narrows a stream by manufacturer, address, CI and RSSI. The same
+//! conditions are available directly on [`ItemStream`] as chainable combinators; items
+//! that do not match are dropped inside the stream and are not counted as lag.
+//!
+//! ## Usage
+//!
+//! ```rust,no_run
+//! use mbus_rs::wmbus::handle::WMBusHandle;
+//! use mbus_rs::wmbus::radio::hal::MockHal;
+//! use tokio_stream::StreamExt;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut wmbus = WMBusHandle::new(MockHal::new(), None).await?;
+//!     wmbus.start_receiver().await?;
+//!
+//!     // Kamstrup meters heard better than -90 dBm
+//!     let mut frames = wmbus.subscribe().manufacturer(0x2C2D).min_rssi(-90);
+//!     let mut tick = tokio::time::interval(std::time::Duration::from_secs(60));
+//!
+//!     loop {
+//!         tokio::select! {
+//!             Some(item) = frames.next() => println!("{item:?}"),
+//!             _ = tick.tick() => println!("{} items lost to lag", frames.lagged()),
+//!             _ = tokio::signal::ctrl_c() => break,
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::wmbus::handle::ReceivedItem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::Stream;
+
+/// Number of items the broadcast fan-out retains for slow subscribers
+pub const FANOUT_CAPACITY: usize = 256;
+
+/// Reported in-band by [`ItemStream::with_lag_reports`] when items were lost
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Subscriber lagged: {missed} items dropped")]
+pub struct Lagged {
+    /// Number of items dropped since the previous item
+    pub missed: u64,
+}
+
+/// Conditions a [`ReceivedItem`] must meet to pass a stream
+///
+/// All set conditions must hold. Manufacturer, address and CI conditions only match
+/// wM-Bus frames; the RSSI condition applies to LoRa items too.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItemFilter {
+    /// Allowed manufacturer IDs (M-field)
+    pub manufacturers: Vec<u16>,
+    /// Allowed device addresses (A-field)
+    pub addresses: Vec<u32>,
+    /// Allowed CI fields
+    pub ci_fields: Vec<u8>,
+    /// Minimum RSSI in dBm
+    pub min_rssi_dbm: Option<i16>,
+    /// Drop LoRa items
+    pub wmbus_only: bool,
+}
+
+impl ItemFilter {
+    /// A filter that passes everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also allow this manufacturer ID
+    pub fn manufacturer(mut self, manufacturer_id: u16) -> Self {
+        self.manufacturers.push(manufacturer_id);
+        self
+    }
+
+    /// Also allow this device address
+    pub fn address(mut self, address: u32) -> Self {
+        self.addresses.push(address);
+        self
+    }
+
+    /// Also allow this CI field
+    pub fn ci(mut self, ci: u8) -> Self {
+        self.ci_fields.push(ci);
+        self
+    }
+
+    /// Require at least this RSSI
+    pub fn min_rssi(mut self, rssi_dbm: i16) -> Self {
+        self.min_rssi_dbm = Some(rssi_dbm);
+        self
+    }
+
+    /// Drop LoRa items
+    pub fn wmbus_only(mut self) -> Self {
+        self.wmbus_only = true;
+        self
+    }
+
+    /// Whether `item` passes every set condition
+    pub fn matches(&self, item: &ReceivedItem) -> bool {
+        let rssi_dbm = match item {
+            ReceivedItem::Wmbus { rssi_dbm, .. } | ReceivedItem::Lora { rssi_dbm, .. } => *rssi_dbm,
+        };
+        if self.min_rssi_dbm.is_some_and(|min| rssi_dbm < min) {
+            return false;
+        }
+
+        match item {
+            ReceivedItem::Wmbus { frame, .. } => {
+                allows(&self.manufacturers, frame.manufacturer_id)
+                    && allows(&self.addresses, frame.device_address)
+                    && allows(&self.ci_fields, frame.control_info)
+            }
+            ReceivedItem::Lora { .. } => {
+                !self.wmbus_only
+                    && self.manufacturers.is_empty()
+                    && self.addresses.is_empty()
+                    && self.ci_fields.is_empty()
+            }
+        }
+    }
+}
+
+/// An empty allow-list allows everything
+fn allows<T: PartialEq>(allowed: &[T], value: T) -> bool {
+    allowed.is_empty() || allowed.contains(&value)
+}
+
+/// A subscriber's view of the received items
+///
+/// Created by [`WMBusHandle::subscribe`](crate::wmbus::handle::WMBusHandle::subscribe)
+/// or from any fan-out receiver with [`ItemStream::new`]. Ends once the handle is
+/// dropped and its receiver stopped.
+pub struct ItemStream {
+    inner: BroadcastStream<ReceivedItem>,
+    filter: ItemFilter,
+    lagged: u64,
+}
+
+impl ItemStream {
+    /// Wrap a fan-out receiver
+    pub fn new(receiver: broadcast::Receiver<ReceivedItem>) -> Self {
+        Self {
+            inner: BroadcastStream::new(receiver),
+            filter: ItemFilter::default(),
+            lagged: 0,
+        }
+    }
+
+    /// Replace the stream's filter
+    pub fn filter(mut self, filter: ItemFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Only pass frames from this manufacturer (repeat to allow several)
+    pub fn manufacturer(mut self, manufacturer_id: u16) -> Self {
+        self.filter = self.filter.manufacturer(manufacturer_id);
+        self
+    }
+
+    /// Only pass frames from this device address (repeat to allow several)
+    pub fn address(mut self, address: u32) -> Self {
+        self.filter = self.filter.address(address);
+        self
+    }
+
+    /// Only pass frames with this CI field (repeat to allow several)
+    pub fn ci(mut self, ci: u8) -> Self {
+        self.filter = self.filter.ci(ci);
+        self
+    }
+
+    /// Only pass items received at or above this RSSI
+    pub fn min_rssi(mut self, rssi_dbm: i16) -> Self {
+        self.filter = self.filter.min_rssi(rssi_dbm);
+        self
+    }
+
+    /// Drop LoRa items
+    pub fn wmbus_only(mut self) -> Self {
+        self.filter = self.filter.wmbus_only();
+        self
+    }
+
+    /// Total number of items this subscriber lost by falling behind
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+
+    /// Turn the stream into one that reports lag in-band, as `Err(Lagged)` ahead of
+    /// the first item after the gap
+    pub fn with_lag_reports(self) -> LagReportingStream {
+        LagReportingStream { inner: self }
+    }
+
+    /// Next matching item, or the size of a gap
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<ReceivedItem, Lagged>>> {
+        loop {
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(item))) => {
+                    if self.filter.matches(&item) {
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                }
+                Poll::Ready(Some(Err(BroadcastStreamRecvError::Lagged(missed)))) => {
+                    log::warn!("wM-Bus subscriber lagged: {missed} items dropped");
+                    self.lagged += missed;
+                    return Poll::Ready(Some(Err(Lagged { missed })));
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Stream for ItemStream {
+    type Item = ReceivedItem;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.poll_event(cx) {
+                Poll::Ready(Some(Ok(item))) => return Poll::Ready(Some(item)),
+                Poll::Ready(Some(Err(_))) => continue, // counted in `lagged`
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// An [`ItemStream`] that yields `Err(Lagged)` where items were lost
+pub struct LagReportingStream {
+    inner: ItemStream,
+}
+
+impl LagReportingStream {
+    /// Total number of items this subscriber lost by falling behind
+    pub fn lagged(&self) -> u64 {
+        self.inner.lagged
+    }
+}
+
+impl Stream for LagReportingStream {
+    type Item = Result<ReceivedItem, Lagged>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_event(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::frame::{parse_wmbus_frame, WMBusFrame};
+    use crate::wmbus::radio::driver::LoRaRxInfo;
+    use crate::wmbus::radio::modulation::{LoRaBandwidth, SpreadingFactor};
+    use tokio_stream::StreamExt;
+
+    fn wmbus(manufacturer_id: u16, address: u32, ci: u8, rssi_dbm: i16) -> ReceivedItem {
+        let raw = WMBusFrame::build(0x44, manufacturer_id, address, 0x1B, 0x07, ci, &[0, 0]);
+        ReceivedItem::Wmbus {
+            frame: parse_wmbus_frame(&raw).unwrap(),
+            rssi_dbm,
+            link_mode: None,
+            freshness: None,
+        }
+    }
+
+    fn lora(rssi_dbm: i16) -> ReceivedItem {
+        ReceivedItem::Lora {
+            payload: vec![0x40],
+            rssi_dbm,
+            lora: LoRaRxInfo {
+                snr_db: 7.5,
+                freq_error_hz: None,
+                sf: SpreadingFactor::SF7,
+                bw: LoRaBandwidth::BW125,
+            },
+        }
+    }
+
+    fn address_of(item: &ReceivedItem) -> Option<u32> {
+        match item {
+            ReceivedItem::Wmbus { frame, .. } => Some(frame.device_address),
+            ReceivedItem::Lora { .. } => None,
+        }
+    }
+
+    #[test]
+    fn filter_conditions_combine() {
+        let kamstrup = ItemFilter::new().manufacturer(0x2C2D).min_rssi(-90);
+        assert!(kamstrup.matches(&wmbus(0x2C2D, 1, 0x7A, -80)));
+        assert!(!kamstrup.matches(&wmbus(0x2C2D, 1, 0x7A, -95)));
+        assert!(!kamstrup.matches(&wmbus(0x1596, 1, 0x7A, -80)));
+        // Frame conditions never match LoRa items.
+        assert!(!kamstrup.matches(&lora(-50)));
+
+        let meters = ItemFilter::new().address(1).address(2).ci(0x72);
+        assert!(meters.matches(&wmbus(0x2C2D, 2, 0x72, -80)));
+        assert!(!meters.matches(&wmbus(0x2C2D, 3, 0x72, -80)));
+        assert!(!meters.matches(&wmbus(0x2C2D, 1, 0x7A, -80)));
+
+        let strong = ItemFilter::new().min_rssi(-60);
+        assert!(strong.matches(&lora(-50)));
+        assert!(!strong.wmbus_only().matches(&lora(-50)));
+    }
+
+    #[tokio::test]
+    async fn every_subscriber_sees_its_filtered_items() {
+        let (tx, _) = broadcast::channel(FANOUT_CAPACITY);
+        let mut all = ItemStream::new(tx.subscribe());
+        let mut one = ItemStream::new(tx.subscribe()).address(2);
+
+        for item in [
+            wmbus(0x2C2D, 1, 0x7A, -70),
+            lora(-90),
+            wmbus(0x2C2D, 2, 0x7A, -70),
+        ] {
+            tx.send(item).unwrap();
+        }
+        drop(tx);
+
+        let all: Vec<_> = (&mut all).collect().await;
+        assert_eq!(
+            all.iter().map(address_of).collect::<Vec<_>>(),
+            vec![Some(1), None, Some(2)]
+        );
+        assert_eq!(one.next().await.as_ref().and_then(address_of), Some(2));
+        assert!(one.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn slow_subscriber_reports_lag() {
+        let (tx, _) = broadcast::channel(2);
+        let mut quiet = ItemStream::new(tx.subscribe());
+        let mut loud = ItemStream::new(tx.subscribe()).with_lag_reports();
+
+        for address in 1..=5 {
+            tx.send(wmbus(0x2C2D, address, 0x7A, -70)).unwrap();
+        }
+        drop(tx);
+
+        // The plain stream skips the gap and counts it.
+        let seen: Vec<_> = (&mut quiet).collect().await;
+        assert_eq!(
+            seen.iter().map(address_of).collect::<Vec<_>>(),
+            vec![Some(4), Some(5)]
+        );
+        assert_eq!(quiet.lagged(), 3);
+
+        // The lag-reporting stream surfaces it in-band first.
+        assert_eq!(
+            loud.next().await.unwrap().unwrap_err(),
+            Lagged { missed: 3 }
+        );
+        let item = loud.next().await.unwrap().unwrap();
+        assert_eq!(address_of(&item), Some(4));
+        assert_eq!(loud.lagged(), 3);
+    }
+}
@@ -0,0 +1,232 @@
+//! # Simultaneous T1 + C1 reception
+//!
+//! Modes T1 and C1 share the 868.95 MHz carrier but differ in line code (3-out-of-6
+//! versus NRZ) and sync. Time-slicing a radio between the two misses every telegram
+//! sent while it listens in the wrong mode. Both can be received at once because the
+//! mode-C sync `0x543D` ends in the mode-T sync word (`0000111101`):
+//!
+//! | mode | chips after the radio's `0x543D` sync match       |
+//! |------|---------------------------------------------------|
+//! | C    | NRZ `0x54`, `0xCD` (Type A) / `0x3D` (Type B), L… |
+//! | T    | 3-out-of-6 L-field, link header, blocks…          |
+//!
+//! So a radio matching the 16-bit [`COMMON_SYNC`] captures the raw bytes of either
+//! mode. Afterwards [`classify`] tells them apart (`010101` — the NRZ `0x54` — is not a
+//! valid 3-out-of-6 code word), [`capture_len`] finds the frame boundary from the first
+//! [`HEAD_LEN`] bytes, and [`decode_capture`] line-decodes and de-blocks the frame.
+//!
+//! ```rust
+//! use mbus_rs::wmbus::bitstream::LinkMode;
+//! use mbus_rs::wmbus::t1c1::{capture_len, decode_capture};
+//!
+//! // Real Type B frame from meter 74644444 (KAM), as captured after the common sync.
+//! let mut raw = vec![0x54];
+//! raw.extend(hex::decode(
+//!     "3d25442d2c444464741b168d208d3048a121f6597959d56873b609a439b99d58531a8a726d9f0c",
+//! ).unwrap());
+//! assert_eq!(capture_len(&raw), Some(raw.len()));
+//!
+//! let (mode, link) = decode_capture(&raw).unwrap();
+//! assert_eq!(mode, LinkMode::C);
+//! assert_eq!(link.device_address, 74644444);
+//! ```
+
+use crate::wmbus::bitstream::LinkMode;
+use crate::wmbus::frame_decode::{sync, DecodeError};
+use crate::wmbus::line_code::{bytes_to_chips, decode_3of6};
+use crate::wmbus::mode_c::{decode_mode_c, WMBusLinkFrame};
+use crate::wmbus::radio::rfm69_packet::packet_size;
+
+/// Radio sync word shared by modes T and C: the tail of the `0101…` preamble plus the
+/// mode-T sync.
+pub const COMMON_SYNC: [u8; 2] = [0x54, 0x3D];
+
+/// Bytes needed after the sync before [`classify`] and [`capture_len`] can decide.
+pub const HEAD_LEN: usize = 3;
+
+/// Second byte of the mode-C sync, sent NRZ before the type byte.
+const C_SYNC_TAIL: u8 = 0x54;
+
+/// Tell a capture taken after [`COMMON_SYNC`] apart as mode C or mode T.
+///
+/// Returns `None` when fewer than [`HEAD_LEN`] bytes are available or the head is
+/// neither a mode-C type byte nor a readable 3-out-of-6 L-field.
+pub fn classify(raw: &[u8]) -> Option<LinkMode> {
+    if raw.len() < HEAD_LEN {
+        return None;
+    }
+    if raw[0] == C_SYNC_TAIL && matches!(raw[1], sync::A_NORM | sync::B_NORM) {
+        return Some(LinkMode::C);
+    }
+    t_l_field(raw).map(|_| LinkMode::T)
+}
+
+/// Number of captured bytes (after the sync) that make up the whole frame.
+///
+/// Needs the first [`HEAD_LEN`] bytes; returns `None` if the head is not a mode-C or
+/// mode-T frame.
+pub fn capture_len(raw: &[u8]) -> Option<usize> {
+    match classify(raw)? {
+        // 0x54, then the type byte, L-field and blocks.
+        LinkMode::C => Some(1 + frame_len_after_type(raw[1], raw[2])? + 1),
+        // Format A bytes (L-field onward), 12 chips each.
+        _ => {
+            let bytes = frame_len_after_type(sync::A_NORM, t_l_field(raw)?)?;
+            Some((bytes * 12).div_ceil(8))
+        }
+    }
+}
+
+/// Decode a capture taken after [`COMMON_SYNC`] into its mode and link frame.
+///
+/// Bytes beyond [`capture_len`] (fixed-length captures run past the frame) are ignored.
+/// The returned [`WMBusLinkFrame::crc_ok`] reports whether every block CRC validated.
+///
+/// # Errors
+///
+/// Returns [`DecodeError::InvalidHeader`] if the capture is neither mode C nor mode T,
+/// [`DecodeError::BufferTooShort`] if it ends before the frame does, and otherwise as
+/// [`decode_mode_c`].
+pub fn decode_capture(raw: &[u8]) -> Result<(LinkMode, WMBusLinkFrame), DecodeError> {
+    let mode = classify(raw).ok_or(DecodeError::InvalidHeader)?;
+    let len = capture_len(raw).ok_or(DecodeError::InvalidHeader)?;
+    if raw.len() < len {
+        return Err(DecodeError::BufferTooShort {
+            needed: len,
+            actual: raw.len(),
+        });
+    }
+    let link = match mode {
+        LinkMode::C => decode_mode_c(&raw[1..len])?,
+        _ => {
+            let (bytes, symbol_errors) = decode_3of6(&bytes_to_chips(&raw[..len]));
+            let mut frame = Vec::with_capacity(bytes.len() + 1);
+            frame.push(sync::A_NORM);
+            frame.extend(bytes);
+            let mut link = decode_mode_c(&frame)?;
+            link.crc_ok &= symbol_errors == 0;
+            link
+        }
+    };
+    Ok((mode, link))
+}
+
+/// Decode a capture into the radio frame
+/// [`parse_wmbus_frame`](crate::wmbus::frame::parse_wmbus_frame) accepts, tagged with its
+/// mode.
+///
+/// This is what the T1 + C1 receive paths hand on. Captures that cannot be classified,
+/// fail a block CRC or carry no application layer yield `None`.
+pub fn capture_to_frame(raw: &[u8]) -> Option<(LinkMode, Vec<u8>)> {
+    match decode_capture(raw) {
+        Ok((mode, link)) if link.crc_ok => match link.to_frame_bytes() {
+            Ok(frame) => Some((mode, frame)),
+            Err(e) => {
+                log::debug!("Dropping {mode:?} capture: {e}");
+                None
+            }
+        },
+        Ok((mode, _)) => {
+            log::debug!("Dropping {mode:?} capture with failed block CRC");
+            None
+        }
+        Err(e) => {
+            log::debug!("Dropping unclassifiable T1/C1 capture: {e}");
+            None
+        }
+    }
+}
+
+/// The L-field of a mode-T capture, if its first 12 chips are valid 3-out-of-6 words.
+fn t_l_field(raw: &[u8]) -> Option<u8> {
+    match decode_3of6(&bytes_to_chips(&raw[..2])) {
+        (l, 0) => l.first().copied(),
+        _ => None,
+    }
+}
+
+/// Total link-layer bytes after the type byte, from the type byte and L-field.
+fn frame_len_after_type(type_byte: u8, l_field: u8) -> Option<usize> {
+    match packet_size(&[type_byte, l_field]) {
+        n if n > 1 => Some(n as usize - 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wmbus::crc::calculate_wmbus_crc;
+    use crate::wmbus::line_code::{chips_to_bytes, encode_3of6};
+
+    /// Real, complete Type B frame from meter 74644444 (KAM), CI=0x8D.
+    const REAL_TYPE_B: &str =
+        "3d25442d2c444464741b168d208d3048a121f6597959d56873b609a439b99d58531a8a726d9f0c";
+
+    /// A format-A link frame (without the type byte) with valid block CRCs.
+    fn format_a(payload: &[u8]) -> Vec<u8> {
+        let mut header = vec![(9 + payload.len()) as u8, 0x44, 0x2D, 0x2C];
+        header.extend_from_slice(&[0x78, 0x56, 0x34, 0x12, 0x1B, 0x07]);
+        let mut out = header.clone();
+        out.extend_from_slice(&calculate_wmbus_crc(&header).to_be_bytes());
+        for block in payload.chunks(16) {
+            out.extend_from_slice(block);
+            out.extend_from_slice(&calculate_wmbus_crc(block).to_be_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn mode_c_type_a_and_b_are_told_apart_from_mode_t() {
+        let mut c_b = vec![C_SYNC_TAIL];
+        c_b.extend(hex::decode(REAL_TYPE_B).unwrap());
+        let frame_len = c_b.len();
+        c_b.extend_from_slice(&[0xA5; 20]); // fixed-length capture runs past the frame
+        assert_eq!(classify(&c_b), Some(LinkMode::C));
+        assert_eq!(capture_len(&c_b), Some(frame_len));
+        let (mode, link) = decode_capture(&c_b).unwrap();
+        assert_eq!(mode, LinkMode::C);
+        assert!(link.crc_ok);
+        assert_eq!(link.ci(), Some(0x8D));
+
+        let payload: Vec<u8> = (0..20u8).map(|b| b.wrapping_mul(37) ^ 0x5A).collect();
+        let mut c_a = vec![C_SYNC_TAIL, sync::A_NORM];
+        c_a.extend(format_a(&payload));
+        let (mode, link) = decode_capture(&c_a).unwrap();
+        assert_eq!(mode, LinkMode::C);
+        assert!(link.crc_ok);
+        assert_eq!(link.payload, payload);
+
+        let t = chips_to_bytes(&encode_3of6(&format_a(&payload)));
+        assert_eq!(classify(&t), Some(LinkMode::T));
+        assert_eq!(capture_len(&t), Some(t.len()));
+        let (mode, link) = decode_capture(&t).unwrap();
+        assert_eq!(mode, LinkMode::T);
+        assert!(link.crc_ok);
+        assert_eq!(link.device_address, 12345678);
+        assert_eq!(link.payload, payload);
+
+        // The re-encoded radio frame carries the wire address.
+        let frame =
+            crate::wmbus::frame::parse_wmbus_frame(&link.to_frame_bytes().unwrap()).unwrap();
+        assert_eq!(frame.device_address, 0x12345678);
+        assert_eq!(frame.control_info, payload[0]);
+    }
+
+    #[test]
+    fn noise_and_short_heads_are_rejected() {
+        assert_eq!(classify(&[0x54, 0xCD]), None);
+        // 0xFF.. is neither a C type byte nor valid 3-out-of-6.
+        assert_eq!(classify(&[0xFF, 0xFF, 0xFF]), None);
+        assert!(matches!(
+            decode_capture(&[0xFF, 0xFF, 0xFF]),
+            Err(DecodeError::InvalidHeader)
+        ));
+        // A truncated mode-T capture.
+        let t = chips_to_bytes(&encode_3of6(&format_a(&[0x78, 1, 2, 3])));
+        assert!(matches!(
+            decode_capture(&t[..t.len() - 4]),
+            Err(DecodeError::BufferTooShort { .. })
+        ));
+    }
+}
@@ -0,0 +1,888 @@
+//! # Meter telegram builder
+//!
+//! Assembles complete, valid meter telegrams: link header, transport layer (TPL)
+//! header, variable data records and — optionally — OMS security mode 5, 7 or 9, ready
+//! to hand to the decode pipeline or a transmitter.
+//!
+//! The TPL header stays in plaintext as meters send it: the CI field (`0x7A` short,
+//! `0x72` long), the access number, status and configuration word, whose bits 12..8
+//! carry the security mode. For mode 5 the records are prefixed with the `2F 2F`
+//! decryption check, padded with `2F` fillers to whole AES blocks and encrypted with
+//! the OMS AES-CBC under the meter key ([`WMBusCrypto::encrypt_oms_mode5`]);
+//! [`decrypt_records`] reverses it.
+//!
+//! Modes 7 and 9 (with the `crypto` feature) put an authentication and fragmentation
+//! layer (AFL, CI `0x90`) in front of the TPL. It carries the message counter, which
+//! increments with every telegram like the access number. The records are encrypted
+//! under a message key derived from the meter key and that counter
+//! ([`WMBusCrypto::derive_oms_message_key`]). Mode 7 pads them like mode 5, encrypts
+//! with AES-CBC and adds an 8-byte AES-CMAC over the counter and the TPL to the AFL.
+//! Mode 9 encrypts the bare records with AES-GCM and appends the tag. Both leave the
+//! hop counter out of what they authenticate, so repeated copies still verify.
+//!
+//! Each [`TelegramBuilder::build`] uses the next access number. The [`Telegram`] renders
+//! as the radio frame [`parse_wmbus_frame`](crate::wmbus::frame::parse_wmbus_frame)
+//! reads, as mode-C Type A / Type B framing (type byte first, as
+//! [`decode_mode_c`](crate::wmbus::mode_c::decode_mode_c) takes it) or as mode-T
+//! 3-out-of-6 chips (as captured after the sync, see
+//! [`decode_capture`](crate::wmbus::t1c1::decode_capture)).
+//!
+//! ```rust
+//! use mbus_rs::payload::record_encoder::{DataRecord, RecordValue};
+//! use mbus_rs::wmbus::bidirectional::MeterAddress;
+//! use mbus_rs::wmbus::crypto::{AesKey, WMBusCrypto};
+//! use mbus_rs::wmbus::frame::parse_wmbus_frame;
+//! use mbus_rs::wmbus::telegram::{decrypt_records, Security, TelegramBuilder, TplHeader};
+//!
+//! let meter = MeterAddress {
+//!     manufacturer_id: 0x2C2D,
+//!     address: 0x12345678,
+//!     version: 0x1B,
+//!     device_type: 0x07,
+//! };
+//! let key = AesKey::from_bytes(&[0x42; 16]).unwrap();
+//! let mut builder = TelegramBuilder::new(meter)
+//!     .header(TplHeader::Short)
+//!     .security(Security::Mode5(key.clone()));
+//!
+//! let volume = DataRecord::new(&[0x13], RecordValue::Integer { value: 12345, len: 4 });
+//! let telegram = builder.build(&[volume]).unwrap();
+//!
+//! let frame = parse_wmbus_frame(&telegram.frame_bytes()).unwrap();
+//! assert_eq!(frame.access_number(), Some(telegram.access_number));
+//! let records = decrypt_records(&mut WMBusCrypto::new(key), &frame).unwrap();
+//! assert_eq!(&records[2..8], &[0x04, 0x13, 0x39, 0x30, 0x00, 0x00]);
+//! ```
+
+use crate::payload::record_encoder::{encode_records, DataRecord, RecordEncodeError};
+use crate::wmbus::bidirectional::MeterAddress;
+use crate::wmbus::crypto::{AesKey, CryptoError, DeviceInfo, WMBusCrypto};
+#[cfg(feature = "crypto")]
+use crate::wmbus::crypto::{OMS_KDF_ENC, OMS_KDF_MAC};
+use crate::wmbus::frame::{
+    add_wmbus_crc, tpl_header_len, RepeaterBits, WMBusFrame, TPL_CW_HOP_COUNTER,
+};
+use crate::wmbus::frame_decode::FrameType;
+use crate::wmbus::line_code::{chips_to_bytes, encode_3of6};
+use crate::wmbus::mode_c::encode_mode_c;
+use thiserror::Error;
+
+/// CI of a frame with a short TPL header.
+const CI_SHORT: u8 = 0x7A;
+/// CI of a frame with a long TPL header.
+const CI_LONG: u8 = 0x72;
+/// CI of the authentication and fragmentation layer.
+const CI_AFL: u8 = 0x90;
+/// AFL fragmentation control: message control field present.
+const AFL_FCL_MCLP: u16 = 0x2000;
+/// AFL fragmentation control: MAC present.
+const AFL_FCL_MACP: u16 = 0x0400;
+/// AFL fragmentation control: message counter present.
+const AFL_FCL_MCRP: u16 = 0x0800;
+/// AFL fragmentation control: key information present.
+const AFL_FCL_KIP: u16 = 0x0200;
+/// AFL message control: message counter present, AES-CMAC truncated to 8 bytes.
+#[cfg(feature = "crypto")]
+const AFL_MCL_CMAC8: u8 = 0x25;
+/// Length of the MAC [`AFL_MCL_CMAC8`] announces.
+const AFL_MAC_LEN: usize = 8;
+/// Link header after the L-field: C, M, A, V, T.
+const LINK_HEADER_LEN: usize = 9;
+/// Decryption check that opens the plaintext of modes 5 and 7.
+const VERIFY: [u8; 2] = [0x2F, 0x2F];
+/// Idle filler padding the plaintext of modes 5 and 7 to whole AES blocks.
+const FILLER: u8 = 0x2F;
+/// AES block size.
+const AES_BLOCK: usize = 16;
+/// Longest link frame (after the L-field, without CRCs) every output format can carry.
+const MAX_LINK_LEN: usize = 255 - 4;
+
+/// Errors raised while building or opening a telegram.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TelegramError {
+    #[error("Record encoding failed: {0}")]
+    Record(#[from] RecordEncodeError),
+
+    #[error("Encryption failed: {0}")]
+    Crypto(#[from] CryptoError),
+
+    #[error("Telegram too long: {length} bytes after the L-field")]
+    TooLong { length: usize },
+
+    #[error("Frame has no transport layer header (CI {ci:#04X})")]
+    NoTplHeader { ci: u8 },
+
+    #[error("Unsupported security mode {mode}")]
+    UnsupportedMode { mode: u8 },
+
+    #[error("Security mode {mode} needs an AFL message counter and MAC")]
+    NoMessageCounter { mode: u8 },
+
+    #[error("AFL MAC does not match")]
+    MacMismatch,
+
+    #[error("Decrypted records do not start with 2F 2F")]
+    VerificationFailed,
+
+    #[error("Frame type {frame_type:?} has no block framing")]
+    NoBlockFraming { frame_type: FrameType },
+}
+
+/// The transport layer header to send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TplHeader {
+    /// CI `0x7A`: ACC, status, configuration word.
+    Short,
+    /// CI `0x72`: the meter's ID, M, V, T, then ACC, status, configuration word. Used when
+    /// the link layer address is not the meter's own (e.g. a radio adapter).
+    Long(MeterAddress),
+}
+
+/// The security (encryption) mode of the application layer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Security {
+    None,
+    /// Mode 5: AES-128-CBC with the meter's own key, no key derivation.
+    Mode5(AesKey),
+    /// Mode 7: AES-128-CBC with a key derived per message counter, authenticated by an
+    /// AES-CMAC in the AFL.
+    #[cfg(feature = "crypto")]
+    Mode7(AesKey),
+    /// Mode 9: AES-128-GCM with a key derived per message counter.
+    #[cfg(feature = "crypto")]
+    Mode9(AesKey),
+}
+
+impl Security {
+    /// Security mode number in configuration word bits 12..8.
+    fn mode(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Mode5(_) => 5,
+            #[cfg(feature = "crypto")]
+            Self::Mode7(_) => 7,
+            #[cfg(feature = "crypto")]
+            Self::Mode9(_) => 9,
+        }
+    }
+
+    /// The meter key, if the mode encrypts.
+    fn key(&self) -> Option<&AesKey> {
+        match self {
+            Self::None => None,
+            Self::Mode5(key) => Some(key),
+            #[cfg(feature = "crypto")]
+            Self::Mode7(key) | Self::Mode9(key) => Some(key),
+        }
+    }
+}
+
+/// Output framing for a [`Telegram`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelegramFormat {
+    /// The single-CRC radio frame of [`WMBusFrame::build`].
+    Frame,
+    /// Mode C, frame format A, type byte first.
+    ModeCTypeA,
+    /// Mode C, frame format B, type byte first.
+    ModeCTypeB,
+    /// Mode T: frame format A, 3-out-of-6 coded, chips packed MSB-first.
+    ModeT,
+}
+
+/// A built telegram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Telegram {
+    /// Access number carried in the TPL header.
+    pub access_number: u8,
+    /// Message counter carried in the AFL (security modes 7 and 9).
+    pub message_counter: Option<u32>,
+    /// Link frame after the L-field, without CRCs.
+    link: Vec<u8>,
+}
+
+impl Telegram {
+    /// The radio frame with its single trailing CRC.
+    pub fn frame_bytes(&self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(1 + self.link.len());
+        frame.push(self.link.len() as u8);
+        frame.extend_from_slice(&self.link);
+        add_wmbus_crc(&frame)
+    }
+
+    /// Mode-C framing with block CRCs, starting with the type byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TelegramError::NoBlockFraming`] for [`FrameType::Unknown`].
+    pub fn mode_c(&self, frame_type: FrameType) -> Result<Vec<u8>, TelegramError> {
+        match frame_type {
+            FrameType::TypeA | FrameType::TypeB => Ok(self.blocks(frame_type)),
+            FrameType::Unknown => Err(TelegramError::NoBlockFraming { frame_type }),
+        }
+    }
+
+    /// Mode-T chips: the format A frame from the L-field on, 3-out-of-6 coded.
+    pub fn mode_t(&self) -> Vec<u8> {
+        chips_to_bytes(&encode_3of6(&self.blocks(FrameType::TypeA)[1..]))
+    }
+
+    /// Type A or Type B block framing. [`TelegramBuilder`] caps the link frame at
+    /// [`MAX_LINK_LEN`], which both formats can carry.
+    fn blocks(&self, frame_type: FrameType) -> Vec<u8> {
+        encode_mode_c(frame_type, &self.link).unwrap_or_default()
+    }
+
+    /// The copy a repeater sends: the hop counter (configuration word bits 1..0) raised
+    /// by one, saturating at 3. The records and their encryption are unchanged.
+    pub fn repeated(&self) -> Telegram {
+        let mut link = self.link.clone();
+        let payload = &mut link[LINK_HEADER_LEN + 1..];
+        let bits = RepeaterBits::locate(self.link[LINK_HEADER_LEN], payload);
+        if let Some(cw) = bits.tpl_cw.and_then(|at| payload.get_mut(at)) {
+            let hops = *cw & TPL_CW_HOP_COUNTER;
+            *cw = (*cw & !TPL_CW_HOP_COUNTER) | (hops + 1).min(TPL_CW_HOP_COUNTER);
+        }
+        Telegram {
+            link,
+            ..self.clone()
+        }
+    }
+
+    /// Render in `format`.
+    pub fn encode(&self, format: TelegramFormat) -> Vec<u8> {
+        match format {
+            TelegramFormat::Frame => self.frame_bytes(),
+            TelegramFormat::ModeCTypeA => self.blocks(FrameType::TypeA),
+            TelegramFormat::ModeCTypeB => self.blocks(FrameType::TypeB),
+            TelegramFormat::ModeT => self.mode_t(),
+        }
+    }
+}
+
+/// Builds successive telegrams of one meter.
+pub struct TelegramBuilder {
+    link: MeterAddress,
+    control_field: u8,
+    header: TplHeader,
+    status: u8,
+    security: Security,
+    crypto: Option<WMBusCrypto>,
+    access_number: u8,
+    message_counter: u32,
+}
+
+impl TelegramBuilder {
+    /// Telegrams sent as SND-NR from `link` with a short header and no encryption,
+    /// starting at access number 0 and message counter 0.
+    pub fn new(link: MeterAddress) -> Self {
+        Self {
+            link,
+            control_field: 0x44,
+            header: TplHeader::Short,
+            status: 0,
+            security: Security::None,
+            crypto: None,
+            access_number: 0,
+            message_counter: 0,
+        }
+    }
+
+    /// Set the link layer C-field.
+    pub fn control_field(mut self, control_field: u8) -> Self {
+        self.control_field = control_field;
+        self
+    }
+
+    /// Set the TPL header.
+    pub fn header(mut self, header: TplHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Set the TPL status byte.
+    pub fn status(mut self, status: u8) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Set the security mode.
+    pub fn security(mut self, security: Security) -> Self {
+        self.crypto = security.key().map(|key| WMBusCrypto::new(key.clone()));
+        self.security = security;
+        self
+    }
+
+    /// Set the access number of the next telegram.
+    pub fn access_number(mut self, access_number: u8) -> Self {
+        self.access_number = access_number;
+        self
+    }
+
+    /// Set the AFL message counter of the next telegram (security modes 7 and 9).
+    pub fn message_counter(mut self, message_counter: u32) -> Self {
+        self.message_counter = message_counter;
+        self
+    }
+
+    /// Build the next telegram carrying `records`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a record cannot be encoded, encryption fails, or the telegram exceeds
+    /// the L-field.
+    pub fn build(&mut self, records: &[DataRecord]) -> Result<Telegram, TelegramError> {
+        let data = encode_records(records)?;
+        self.build_raw(&data)
+    }
+
+    /// Build the next telegram carrying already encoded application data.
+    ///
+    /// # Errors
+    ///
+    /// As [`build`](Self::build).
+    pub fn build_raw(&mut self, data: &[u8]) -> Result<Telegram, TelegramError> {
+        let acc = self.access_number;
+        let mcr = self.message_counter;
+        let mode = self.security.mode();
+
+        let (ci, meter) = match self.header {
+            TplHeader::Short => (CI_SHORT, self.link),
+            TplHeader::Long(meter) => (CI_LONG, meter),
+        };
+        let mut tpl = vec![ci];
+        if let TplHeader::Long(meter) = self.header {
+            tpl.extend_from_slice(&meter.address.to_le_bytes());
+            tpl.extend_from_slice(&meter.manufacturer_id.to_le_bytes());
+            tpl.extend_from_slice(&[meter.version, meter.device_type]);
+        }
+
+        let plaintext = match mode {
+            5 | 7 => {
+                let mut plaintext = VERIFY.to_vec();
+                plaintext.extend_from_slice(data);
+                plaintext.resize(plaintext.len().next_multiple_of(AES_BLOCK), FILLER);
+                plaintext
+            }
+            _ => data.to_vec(),
+        };
+        let blocks = match mode {
+            5 | 7 => (plaintext.len() / AES_BLOCK).min(0x0F) as u16,
+            _ => 0,
+        };
+        let cw = (u16::from(mode) << 8) | (blocks << 4);
+        tpl.extend_from_slice(&[acc, self.status]);
+        tpl.extend_from_slice(&cw.to_le_bytes());
+
+        let mut link = link_header(self.control_field, &self.link);
+        let afl: Option<Vec<u8>> = match (&self.security, self.crypto.as_mut()) {
+            (Security::Mode5(_), Some(crypto)) => {
+                tpl.extend(crypto.encrypt_oms_mode5(&plaintext, &device_info(&meter, acc))?);
+                None
+            }
+            #[cfg(feature = "crypto")]
+            (Security::Mode7(_), Some(crypto)) => {
+                let kenc = crypto.derive_oms_message_key(OMS_KDF_ENC, mcr, meter.address)?;
+                tpl.extend(crypto.encrypt_oms_mode7(&kenc, &plaintext)?);
+                let mac = afl_mac(crypto, &meter, AFL_MCL_CMAC8, mcr, &tpl)?;
+                let fcl = AFL_FCL_MCLP | AFL_FCL_MACP | AFL_FCL_MCRP;
+                let mut fields = fcl.to_le_bytes().to_vec();
+                fields.push(AFL_MCL_CMAC8);
+                fields.extend_from_slice(&mcr.to_le_bytes());
+                fields.extend_from_slice(&mac);
+                Some(fields)
+            }
+            #[cfg(feature = "crypto")]
+            (Security::Mode9(_), Some(crypto)) => {
+                let kenc = crypto.derive_oms_message_key(OMS_KDF_ENC, mcr, meter.address)?;
+                let aad = gcm_aad(&link, &tpl);
+                tpl.extend(crypto.encrypt_oms_mode9(
+                    &kenc,
+                    &plaintext,
+                    &aad,
+                    &nonce_info(&meter, mcr),
+                )?);
+                let mut fields = AFL_FCL_MCRP.to_le_bytes().to_vec();
+                fields.extend_from_slice(&mcr.to_le_bytes());
+                Some(fields)
+            }
+            _ => {
+                tpl.extend(plaintext);
+                None
+            }
+        };
+        if let Some(fields) = &afl {
+            link.extend_from_slice(&[CI_AFL, fields.len() as u8]);
+            link.extend_from_slice(fields);
+        }
+        link.extend(tpl);
+
+        if link.len() > MAX_LINK_LEN {
+            return Err(TelegramError::TooLong { length: link.len() });
+        }
+        self.access_number = acc.wrapping_add(1);
+        if afl.is_some() {
+            self.message_counter = mcr.wrapping_add(1);
+        }
+        Ok(Telegram {
+            access_number: acc,
+            message_counter: afl.map(|_| mcr),
+            link,
+        })
+    }
+}
+
+/// Decrypt the application data of a telegram built by [`TelegramBuilder`].
+///
+/// Reads the security mode from the configuration word. Modes 7 and 9 take the message
+/// counter from the AFL; mode 7 checks its MAC first, mode 9 the GCM tag. Modes 5 and
+/// 7 check the `2F 2F` prefix and return the plaintext after the TPL header with the
+/// fillers included; mode 9 returns the bare records. Unencrypted telegrams are
+/// returned as they are.
+///
+/// # Errors
+///
+/// Fails if the frame has no short or long TPL header, uses a security mode this build
+/// does not open (7 and 9 need the `crypto` feature), lacks the AFL fields its mode
+/// needs, fails authentication, does not decrypt, or fails the `2F 2F` check.
+pub fn decrypt_records(
+    crypto: &mut WMBusCrypto,
+    frame: &WMBusFrame,
+) -> Result<Vec<u8>, TelegramError> {
+    let mut apl = vec![frame.control_info];
+    apl.extend_from_slice(&frame.payload);
+    #[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+    let (afl, tpl) = split_afl(&apl)?;
+
+    let ci = tpl.first().copied().unwrap_or(CI_AFL);
+    let header_len = tpl_header_len(ci);
+    if header_len == 0 || tpl.len() <= header_len {
+        return Err(TelegramError::NoTplHeader { ci });
+    }
+    let (header, body) = tpl.split_at(1 + header_len);
+    let cw = u16::from_le_bytes([header[header_len - 1], header[header_len]]);
+    let acc = header[header_len - 3];
+    let mode = ((cw >> 8) & 0x1F) as u8;
+
+    let meter = match ci {
+        CI_LONG => MeterAddress {
+            address: u32::from_le_bytes(header[1..5].try_into().unwrap()),
+            manufacturer_id: u16::from_le_bytes([header[5], header[6]]),
+            version: header[7],
+            device_type: header[8],
+        },
+        _ => MeterAddress::of(frame),
+    };
+    // Only the blocks the configuration word counts are encrypted.
+    let encrypted = (usize::from((cw >> 4) & 0x0F) * AES_BLOCK).min(body.len());
+    let (encrypted, clear) = body.split_at(encrypted);
+    let mut plaintext = match mode {
+        0 => return Ok(body.to_vec()),
+        5 => crypto.decrypt_oms_mode5(encrypted, &device_info(&meter, acc))?,
+        #[cfg(feature = "crypto")]
+        7 => {
+            let (Some(mcl), Some(mcr), Some(mac)) = (afl.mcl, afl.message_counter, afl.mac) else {
+                return Err(TelegramError::NoMessageCounter { mode });
+            };
+            if afl_mac(crypto, &meter, mcl, mcr, tpl)?[..] != mac[..] {
+                return Err(TelegramError::MacMismatch);
+            }
+            let kenc = crypto.derive_oms_message_key(OMS_KDF_ENC, mcr, meter.address)?;
+            crypto.decrypt_oms_mode7(&kenc, encrypted)?
+        }
+        #[cfg(feature = "crypto")]
+        9 => {
+            let mcr = afl
+                .message_counter
+                .ok_or(TelegramError::NoMessageCounter { mode })?;
+            let kenc = crypto.derive_oms_message_key(OMS_KDF_ENC, mcr, meter.address)?;
+            let aad = gcm_aad(
+                &link_header(frame.control_field, &MeterAddress::of(frame)),
+                header,
+            );
+            let device = nonce_info(&meter, mcr);
+            return Ok(crypto.decrypt_oms_mode9(&kenc, body, &aad, &device)?);
+        }
+        mode => return Err(TelegramError::UnsupportedMode { mode }),
+    };
+    if !plaintext.starts_with(&VERIFY) {
+        return Err(TelegramError::VerificationFailed);
+    }
+    plaintext.extend_from_slice(clear);
+    Ok(plaintext)
+}
+
+/// The AFL fields [`decrypt_records`] needs.
+#[cfg_attr(not(feature = "crypto"), allow(dead_code))]
+#[derive(Debug, Default)]
+struct AflFields {
+    mcl: Option<u8>,
+    message_counter: Option<u32>,
+    mac: Option<[u8; AFL_MAC_LEN]>,
+}
+
+/// Split the application layer `apl` (from the first CI on) into the fields of its AFL,
+/// if it has one, and the TPL behind it.
+fn split_afl(apl: &[u8]) -> Result<(AflFields, &[u8]), TelegramError> {
+    let mut afl = AflFields::default();
+    if apl.first() != Some(&CI_AFL) {
+        return Ok((afl, apl));
+    }
+    let truncated = TelegramError::NoTplHeader { ci: CI_AFL };
+    let len = usize::from(*apl.get(1).ok_or(truncated.clone())?);
+    let fields = apl.get(2..2 + len).ok_or(truncated.clone())?;
+    let fcl = u16::from_le_bytes(
+        fields
+            .get(..2)
+            .ok_or(truncated.clone())?
+            .try_into()
+            .unwrap(),
+    );
+    let mut at = 2;
+    let mut take = |present: bool, n: usize| -> Result<Option<&[u8]>, TelegramError> {
+        if !present {
+            return Ok(None);
+        }
+        let field = fields.get(at..at + n).ok_or(truncated.clone())?;
+        at += n;
+        Ok(Some(field))
+    };
+    afl.mcl = take(fcl & AFL_FCL_MCLP != 0, 1)?.map(|f| f[0]);
+    take(fcl & AFL_FCL_KIP != 0, 2)?;
+    afl.message_counter =
+        take(fcl & AFL_FCL_MCRP != 0, 4)?.map(|f| u32::from_le_bytes(f.try_into().unwrap()));
+    afl.mac = take(fcl & AFL_FCL_MACP != 0, AFL_MAC_LEN)?.map(|f| f.try_into().unwrap());
+    Ok((afl, &apl[2 + len..]))
+}
+
+/// The mode 7 AFL MAC: AES-CMAC under Kmac of MCL, MCR and the TPL from its CI on,
+/// truncated to [`AFL_MAC_LEN`] bytes.
+#[cfg(feature = "crypto")]
+fn afl_mac(
+    crypto: &WMBusCrypto,
+    meter: &MeterAddress,
+    mcl: u8,
+    message_counter: u32,
+    tpl: &[u8],
+) -> Result<[u8; AFL_MAC_LEN], TelegramError> {
+    let kmac = crypto.derive_oms_message_key(OMS_KDF_MAC, message_counter, meter.address)?;
+    let mut input = vec![mcl];
+    input.extend_from_slice(&message_counter.to_le_bytes());
+    input.extend(without_hops(tpl));
+    let mac = crypto.aes_cmac(&kmac, &input)?;
+    Ok(mac[..AFL_MAC_LEN].try_into().unwrap())
+}
+
+/// The mode 9 additional authenticated data: the link header and the TPL header.
+#[cfg(feature = "crypto")]
+fn gcm_aad(link_header: &[u8], tpl_header: &[u8]) -> Vec<u8> {
+    let mut aad = link_header.to_vec();
+    aad.extend(without_hops(tpl_header));
+    aad
+}
+
+/// `tpl` (from its CI on) with the hop counter cleared, as repeaters raise it.
+#[cfg(feature = "crypto")]
+fn without_hops(tpl: &[u8]) -> Vec<u8> {
+    let mut tpl = tpl.to_vec();
+    if let Some((&mut ci, payload)) = tpl.split_first_mut() {
+        RepeaterBits::locate(ci, payload).clear(payload);
+    }
+    tpl
+}
+
+/// C, M, A, V, T of the link layer.
+fn link_header(control_field: u8, link: &MeterAddress) -> Vec<u8> {
+    let mut header = Vec::with_capacity(LINK_HEADER_LEN + 1);
+    header.push(control_field);
+    header.extend_from_slice(&link.manufacturer_id.to_le_bytes());
+    header.extend_from_slice(&link.address.to_le_bytes());
+    header.extend_from_slice(&[link.version, link.device_type]);
+    header
+}
+
+/// Key derivation and IV inputs for `meter`.
+fn device_info(meter: &MeterAddress, access_number: u8) -> DeviceInfo {
+    DeviceInfo {
+        access_number: Some(u64::from(access_number)),
+        ..nonce_info(meter, 0)
+    }
+}
+
+/// Mode 9 nonce inputs for `meter`: the message counter takes the access number's place.
+fn nonce_info(meter: &MeterAddress, message_counter: u32) -> DeviceInfo {
+    DeviceInfo {
+        device_id: meter.address,
+        manufacturer: meter.manufacturer_id,
+        version: meter.version,
+        device_type: meter.device_type,
+        access_number: Some(u64::from(message_counter)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::record_encoder::RecordValue;
+    use crate::wmbus::dedup::DedupKey;
+    use crate::wmbus::frame::parse_wmbus_frame;
+    use crate::wmbus::mode_c::decode_mode_c;
+    use crate::wmbus::replay_guard::FrameCounters;
+    use crate::wmbus::t1c1::capture_to_frame;
+
+    const METER: MeterAddress = MeterAddress {
+        manufacturer_id: 0x2C2D,
+        address: 0x12345678,
+        version: 0x1B,
+        device_type: 0x07,
+    };
+
+    fn key() -> AesKey {
+        AesKey::from_bytes(&[0x5A; 16]).unwrap()
+    }
+
+    fn records() -> Vec<DataRecord> {
+        vec![
+            DataRecord::new(
+                &[0x13],
+                RecordValue::Integer {
+                    value: 4711,
+                    len: 4,
+                },
+            ),
+            DataRecord::new(
+                &[0x13],
+                RecordValue::Integer {
+                    value: 4000,
+                    len: 4,
+                },
+            )
+            .storage(1),
+            DataRecord::new(&[0xFD, 0x17], RecordValue::Integer { value: 0, len: 2 }),
+        ]
+    }
+
+    /// Every security mode this build can send.
+    fn securities() -> Vec<Security> {
+        vec![
+            Security::None,
+            Security::Mode5(key()),
+            #[cfg(feature = "crypto")]
+            Security::Mode7(key()),
+            #[cfg(feature = "crypto")]
+            Security::Mode9(key()),
+        ]
+    }
+
+    /// The TPL configuration word behind any AFL.
+    fn config_word(frame: &WMBusFrame) -> u16 {
+        let mut apl = vec![frame.control_info];
+        apl.extend_from_slice(&frame.payload);
+        let (_, tpl) = split_afl(&apl).unwrap();
+        let cw = tpl_header_len(tpl[0]) - 1;
+        u16::from_le_bytes([tpl[cw], tpl[cw + 1]])
+    }
+
+    /// Decode `bytes` in `format` back to the radio frame.
+    fn receive(format: TelegramFormat, bytes: &[u8]) -> WMBusFrame {
+        let frame = match format {
+            TelegramFormat::Frame => bytes.to_vec(),
+            TelegramFormat::ModeCTypeA | TelegramFormat::ModeCTypeB => {
+                let link = decode_mode_c(bytes).unwrap();
+                assert!(link.crc_ok);
+                link.to_frame_bytes().unwrap()
+            }
+            TelegramFormat::ModeT => capture_to_frame(bytes).unwrap().1,
+        };
+        parse_wmbus_frame(&frame).unwrap()
+    }
+
+    #[test]
+    fn every_mode_and_format_round_trips() {
+        let plain = encode_records(&records()).unwrap();
+        for security in securities() {
+            let mode = security.mode();
+            let mut builder = TelegramBuilder::new(METER)
+                .message_counter(0x0102_0304)
+                .security(security);
+            for format in [
+                TelegramFormat::Frame,
+                TelegramFormat::ModeCTypeA,
+                TelegramFormat::ModeCTypeB,
+                TelegramFormat::ModeT,
+            ] {
+                let telegram = builder.build(&records()).unwrap();
+                let frame = receive(format, &telegram.encode(format));
+                let afl = matches!(mode, 7 | 9);
+                assert_eq!(frame.control_info, if afl { CI_AFL } else { CI_SHORT });
+                assert_eq!(frame.device_address, METER.address);
+                let counters = FrameCounters::of(&frame);
+                assert_eq!(counters.tpl_access, Some(telegram.access_number));
+                assert_eq!(counters.afl_message_counter, telegram.message_counter);
+                assert_eq!(telegram.message_counter.is_some(), afl);
+                assert_eq!(config_word(&frame) >> 8, u16::from(mode));
+                if mode != 0 {
+                    assert!(!frame.payload.windows(plain.len()).any(|w| w == plain));
+                }
+
+                let data = decrypt_records(&mut WMBusCrypto::new(key()), &frame).unwrap();
+                let data = match mode {
+                    5 | 7 => &data[VERIFY.len()..VERIFY.len() + plain.len()],
+                    _ => &data[..],
+                };
+                assert_eq!(data, &plain[..], "mode {mode} {format:?}");
+            }
+        }
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn mode5_matches_the_oms_example() {
+        // OMS Specification Vol. 2, Annex N: gas meter ELS 12345678, access number 0x2A,
+        // key 01..0F 11. The IV does not include the configuration word, so the first
+        // cipher block matches the published telegram (which also sets bit 13).
+        let meter = MeterAddress {
+            manufacturer_id: 0x1593,
+            address: 0x12345678,
+            version: 0x33,
+            device_type: 0x03,
+        };
+        let key = AesKey::from_hex("0102030405060708090A0B0C0D0E0F11").unwrap();
+        let plain = hex::decode("0C1427048502046D32371F1502FD170000").unwrap();
+        let telegram = TelegramBuilder::new(meter)
+            .access_number(0x2A)
+            .security(Security::Mode5(key.clone()))
+            .build_raw(&plain)
+            .unwrap();
+        let bytes = telegram.frame_bytes();
+        assert_eq!(
+            &bytes[..15],
+            hex::decode("2E449315785634123303 7A2A002005".replace(' ', "")).unwrap()
+        );
+        assert_eq!(
+            &bytes[15..31],
+            hex::decode("5923C95AAA26D1B2E7493B013EC4A6F6").unwrap()
+        );
+
+        let frame = parse_wmbus_frame(&bytes).unwrap();
+        let data = decrypt_records(&mut WMBusCrypto::new(key), &frame).unwrap();
+        assert_eq!(&data[VERIFY.len()..VERIFY.len() + plain.len()], &plain[..]);
+    }
+
+    #[test]
+    fn other_security_modes_and_frame_types_are_refused() {
+        let telegram = TelegramBuilder::new(METER).build(&records()).unwrap();
+        assert!(matches!(
+            telegram.mode_c(FrameType::Unknown),
+            Err(TelegramError::NoBlockFraming { .. })
+        ));
+        assert_eq!(
+            telegram.mode_c(FrameType::TypeB).unwrap(),
+            telegram.encode(TelegramFormat::ModeCTypeB)
+        );
+
+        // A mode 8 configuration word is not mistaken for something this module opens.
+        let mut bytes = telegram.frame_bytes();
+        bytes[14] = 0x08;
+        let frame = parse_wmbus_frame(&add_wmbus_crc(&bytes[..bytes.len() - 2])).unwrap();
+        assert_eq!(
+            decrypt_records(&mut WMBusCrypto::new(key()), &frame),
+            Err(TelegramError::UnsupportedMode { mode: 8 })
+        );
+
+        // Mode 7 without an AFL has no message counter to derive its key from.
+        let mut frame = frame;
+        frame.payload[3] = 0x07;
+        let refused = decrypt_records(&mut WMBusCrypto::new(key()), &frame);
+        #[cfg(feature = "crypto")]
+        assert_eq!(refused, Err(TelegramError::NoMessageCounter { mode: 7 }));
+        #[cfg(not(feature = "crypto"))]
+        assert_eq!(refused, Err(TelegramError::UnsupportedMode { mode: 7 }));
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn afl_authenticates_modes_7_and_9() {
+        for security in [Security::Mode7(key()), Security::Mode9(key())] {
+            let mode = security.mode();
+            let mut builder = TelegramBuilder::new(METER)
+                .message_counter(u32::MAX)
+                .security(security);
+            let telegram = builder.build(&records()).unwrap();
+            assert_eq!(telegram.message_counter, Some(u32::MAX));
+            assert_eq!(builder.build(&[]).unwrap().message_counter, Some(0));
+
+            let frame = parse_wmbus_frame(&telegram.frame_bytes()).unwrap();
+            let open = |frame: &WMBusFrame| decrypt_records(&mut WMBusCrypto::new(key()), frame);
+            assert!(open(&frame).is_ok());
+
+            // Changing the records, the access number or the message counter is caught
+            // by the MAC (mode 7) or the GCM tag (mode 9).
+            let mcr_at = if mode == 7 { 4 } else { 3 };
+            let acc_at = frame.payload[0] as usize + 2;
+            for at in [frame.payload.len() - 1, acc_at, mcr_at] {
+                let mut tampered = frame.clone();
+                tampered.payload[at] ^= 0x01;
+                match (mode, open(&tampered)) {
+                    (7, Err(TelegramError::MacMismatch)) => {}
+                    (9, Err(TelegramError::Crypto(CryptoError::DecryptionFailed { .. }))) => {}
+                    (mode, result) => panic!("mode {mode}, byte {at}: {result:?}"),
+                }
+            }
+
+            // A repeated copy raises the hop counter, which neither authenticates.
+            let repeated = parse_wmbus_frame(&telegram.repeated().frame_bytes()).unwrap();
+            assert_eq!(config_word(&repeated), config_word(&frame) + 1);
+            assert_eq!(open(&repeated), open(&frame));
+
+            // Without its key nothing opens.
+            let wrong = AesKey::from_bytes(&[0xA5; 16]).unwrap();
+            assert!(decrypt_records(&mut WMBusCrypto::new(wrong), &frame).is_err());
+        }
+    }
+
+    #[test]
+    fn access_number_increments_and_wraps() {
+        let mut builder = TelegramBuilder::new(METER).access_number(0xFE);
+        let accs: Vec<u8> = (0..3)
+            .map(|_| builder.build(&records()).unwrap().access_number)
+            .collect();
+        assert_eq!(accs, [0xFE, 0xFF, 0x00]);
+
+        // A failed build does not use up an access number.
+        assert!(builder.build_raw(&[0; 250]).is_err());
+        assert_eq!(builder.build(&[]).unwrap().access_number, 0x01);
+    }
+
+    #[test]
+    fn long_header_carries_the_meter_behind_an_adapter() {
+        let adapter = MeterAddress {
+            manufacturer_id: 0x1234,
+            address: 0x99999999,
+            version: 0x01,
+            device_type: 0x31,
+        };
+        let mut builder = TelegramBuilder::new(adapter)
+            .header(TplHeader::Long(METER))
+            .status(0x04)
+            .security(Security::Mode5(key()));
+        let telegram = builder.build(&records()).unwrap();
+        let frame = parse_wmbus_frame(&telegram.frame_bytes()).unwrap();
+        assert_eq!(frame.control_info, CI_LONG);
+        assert_eq!(frame.device_address, adapter.address);
+        assert_eq!(&frame.payload[..4], &METER.address.to_le_bytes());
+        assert_eq!(frame.payload[9], 0x04);
+
+        // Keys derive from the meter in the long header, so a wrong key fails the check.
+        let data = decrypt_records(&mut WMBusCrypto::new(key()), &frame).unwrap();
+        assert!(data.starts_with(&VERIFY));
+        let wrong = AesKey::from_bytes(&[0xA5; 16]).unwrap();
+        assert!(decrypt_records(&mut WMBusCrypto::new(wrong), &frame).is_err());
+
+        // A repeated copy raises only the hop counter, and still decrypts.
+        let repeated = parse_wmbus_frame(&telegram.repeated().frame_bytes()).unwrap();
+        assert_eq!(repeated.config_word(), frame.config_word().map(|cw| cw + 1));
+        assert_eq!(DedupKey::of(&repeated), DedupKey::of(&frame));
+        assert!(decrypt_records(&mut WMBusCrypto::new(key()), &repeated).is_ok());
+    }
+}
@@ -157,12 +157,16 @@ async fn test_configuration_builders() {
     assert_eq!(s_mode.frequency_hz, 868_950_000);
     assert_eq!(s_mode.bitrate, 100_000);
 
+    let c_mode = WMBusConfigBuilder::eu_c_mode().build();
+    assert_eq!(c_mode.frequency_hz, 868_950_000);
+    assert_eq!(c_mode.bitrate, 100_000);
+
     let t_mode = WMBusConfigBuilder::eu_t_mode().build();
     assert_eq!(t_mode.frequency_hz, 868_300_000);
     assert_eq!(t_mode.bitrate, 100_000);
 
     let n_mode = WMBusConfigBuilder::eu_n_mode().build();
-    assert_eq!(n_mode.frequency_hz, 869_525_000);
+    assert_eq!(n_mode.frequency_hz, 169_406_250);
     assert_eq!(n_mode.bitrate, 4800);
 
     let fast_scan = WMBusConfigBuilder::fast_scan().build();
@@ -0,0 +1,154 @@
+//! Fleet-scale load tests: simulated meters feeding the real `WMBusHandle`.
+//!
+//! Everything runs on tokio's paused clock, so half an hour of traffic from ten thousand
+//! meters takes seconds.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use mbus_rs::wmbus::bitstream::LinkMode;
+use mbus_rs::wmbus::crypto::{AesKey, WMBusCrypto};
+use mbus_rs::wmbus::dedup::DedupConfig;
+use mbus_rs::wmbus::handle::{WMBusError, WMBusHandle};
+use mbus_rs::wmbus::radio::driver::{LoRaProfile, RadioProfile, WmbusProfile};
+use mbus_rs::wmbus::radio::modulation::{CodingRate, LoRaBandwidth, SpreadingFactor};
+use mbus_rs::wmbus::radio::scheduler::{CancelToken, ProfileScheduler, ScheduledWindow};
+use mbus_rs::wmbus::sim::{ChannelConfig, SimMeter, SimRadio};
+use mbus_rs::wmbus::telegram::{decrypt_records, Security};
+use tokio::time::Instant;
+
+fn key() -> AesKey {
+    AesKey::from_bytes(&[0x39; 16]).unwrap()
+}
+
+/// The volume in the single `04 13` record of a decrypted body.
+fn volume(body: &[u8]) -> i32 {
+    let start = if body.starts_with(&[0x2F, 0x2F]) {
+        2
+    } else {
+        0
+    };
+    assert_eq!(&body[start..start + 2], &[0x04, 0x13]);
+    i32::from_le_bytes(body[start + 2..start + 6].try_into().unwrap())
+}
+
+#[tokio::test(start_paused = true)]
+async fn ten_thousand_meters_through_dedup_and_decode() {
+    const METERS: usize = 10_000;
+    let interval = Duration::from_secs(15 * 60);
+    let run = Duration::from_secs(30 * 60);
+
+    // Every tenth meter is heard twice through a repeater, every fourth encrypts.
+    let radio = SimRadio::new(ChannelConfig::default(), 39);
+    for (i, mut meter) in SimMeter::fleet(METERS, LinkMode::T, interval)
+        .into_iter()
+        .enumerate()
+    {
+        if i % 10 == 0 {
+            meter = meter.repeated_after(Duration::from_millis(300));
+        }
+        if i % 4 == 0 {
+            meter = meter.security(Security::Mode5(key()));
+        }
+        radio.add_meter(meter);
+    }
+
+    let mut handle = WMBusHandle::with_driver(radio.clone(), None).await.unwrap();
+    handle.enable_deduplication(DedupConfig::default());
+    handle.start_receiver().await.unwrap();
+
+    let mut crypto = WMBusCrypto::new(key());
+    let mut last: HashMap<u32, (u8, i32)> = HashMap::new();
+    let (mut released, mut via_repeater) = (0u64, 0u64);
+    let end = Instant::now() + run;
+    while Instant::now() < end {
+        let telegram = match handle.recv_deduplicated(Some(1_000)).await {
+            Ok(telegram) => telegram,
+            Err(WMBusError::Timeout) => continue,
+            Err(e) => panic!("receive failed: {e}"),
+        };
+        released += 1;
+        via_repeater += u64::from(telegram.via_repeater());
+
+        // Every released telegram decodes, and each meter's volume never decreases.
+        let frame = &telegram.frame;
+        let reading = volume(&decrypt_records(&mut crypto, frame).unwrap());
+        let access = frame.access_number().unwrap();
+        if let Some((prev_access, prev)) = last.insert(frame.device_address, (access, reading)) {
+            assert_ne!(
+                prev_access, access,
+                "{:08X} released twice",
+                frame.device_address
+            );
+            assert!(reading >= prev);
+        }
+    }
+    handle.stop_receiver().await;
+
+    let sim = radio.stats();
+    let dedup = handle.dedup_stats().unwrap();
+    // Two intervals of traffic plus the repeater copies.
+    assert!(sim.transmitted > 2 * METERS as u64, "{sim:?}");
+    assert_eq!(sim.overflowed, 0, "the pipeline kept up: {sim:?}");
+    assert!(
+        sim.collided > 0 && sim.delivered > sim.transmitted * 9 / 10,
+        "{sim:?}"
+    );
+    // Only CRC-clean copies reach dedup, and repeats fold into their originals.
+    assert!(dedup.received <= sim.delivered, "{dedup:?} {sim:?}");
+    assert!(dedup.suppressed > 0 && via_repeater > 0, "{dedup:?}");
+    assert_eq!(released, dedup.released);
+    let pending = dedup.received - dedup.released - dedup.suppressed;
+    assert!(pending < 100, "{pending} still in their window");
+    assert!(last.len() > METERS * 9 / 10, "heard {} meters", last.len());
+}
+
+#[tokio::test(start_paused = true)]
+async fn lora_windows_cost_their_share_of_wmbus_traffic() {
+    let radio = SimRadio::new(ChannelConfig::ideal(), 40);
+    for meter in SimMeter::fleet(2_000, LinkMode::C, Duration::from_secs(60)) {
+        radio.add_meter(meter);
+    }
+    let mut handle = WMBusHandle::with_driver(radio.clone(), None).await.unwrap();
+    handle.start_receiver().await.unwrap();
+
+    // A one-second LoRa window every ten seconds for ten minutes.
+    let base = RadioProfile::Wmbus(WmbusProfile::mode_c(868_950_000, 100_000));
+    let lora = RadioProfile::LoRa(LoRaProfile {
+        frequency_hz: 868_100_000,
+        sf: SpreadingFactor::SF9,
+        bw: LoRaBandwidth::BW125,
+        cr: CodingRate::CR4_5,
+        power_dbm: 14,
+        sync_word: None,
+    });
+    let windows: Vec<ScheduledWindow> = (0..60)
+        .map(|i| ScheduledWindow {
+            offset: Duration::from_secs(10 * i + 9),
+            duration: Duration::from_secs(1),
+            profile: lora.clone(),
+        })
+        .collect();
+    let scheduler = ProfileScheduler::new(handle.shared_driver(), base);
+    let schedule = tokio::spawn(async move { scheduler.run(&windows, &CancelToken::new()).await });
+
+    let mut frames = 0u64;
+    let end = Instant::now() + Duration::from_secs(600);
+    while Instant::now() < end {
+        match handle.recv_frame(Some(1_000)).await {
+            Ok(_) => frames += 1,
+            Err(WMBusError::Timeout) => continue,
+            Err(e) => panic!("receive failed: {e}"),
+        }
+    }
+    schedule.await.unwrap().unwrap();
+    handle.stop_receiver().await;
+
+    let sim = radio.stats();
+    let missed = sim.missed as f64 / sim.transmitted as f64;
+    assert!(
+        (0.07..0.13).contains(&missed),
+        "missed {missed:.3}: {sim:?}"
+    );
+    assert!(frames + 100 >= sim.delivered, "{frames} of {sim:?}");
+}
@@ -63,20 +63,16 @@ impl Hal for MockHal {
     fn write_command(&mut self, _opcode: u8, _data: &[u8]) -> Result<(), HalError> {
         // Simulate command execution
         match _opcode {
-            0x80 => {
-                // SetStandby command - update state
-                if !_data.is_empty() {
-                    match _data[0] {
-                        0x00 => self
-                            .state
-                            .store(RadioState::StandbyRc as u8, Ordering::Relaxed),
-                        0x01 => self
-                            .state
-                            .store(RadioState::StandbyXosc as u8, Ordering::Relaxed),
-                        _ => {}
-                    }
-                }
-            }
+            // SetStandby command - update state
+            0x80 if !_data.is_empty() => match _data[0] {
+                0x00 => self
+                    .state
+                    .store(RadioState::StandbyRc as u8, Ordering::Relaxed),
+                0x01 => self
+                    .state
+                    .store(RadioState::StandbyXosc as u8, Ordering::Relaxed),
+                _ => {}
+            },
             0x82 => {
                 // SetRx command - enter RX mode
                 self.state.store(RadioState::Rx as u8, Ordering::Relaxed);
@@ -252,7 +248,7 @@ async fn test_wmbus_handle_frame_transmission() {
 
     // Verify the error is one we expect (not a panic or crash)
     match result {
-        Err(WMBusError::Radio(_)) => {
+        Err(WMBusError::Radio(_)) | Err(WMBusError::RadioDriver(_)) => {
             // Expected radio-level error in mock environment
         }
         _ => panic!("Unexpected error type"),
@@ -292,7 +288,7 @@ async fn test_network_configuration() {
 #[tokio::test]
 async fn test_network_manager_creation() {
     let config = NetworkConfig::default();
-    let network = WMBusNetwork::<MockHal>::new(config);
+    let network = WMBusNetwork::<Sx126xDriver<MockHal>>::new(config);
 
     // Test that network manager can be created
     // Actual initialization would require HAL